@@ -0,0 +1,76 @@
+use {
+    solana_client::rpc_client::RpcClient,
+    solana_program::{program_pack::Pack, pubkey::Pubkey},
+    spl_token_lending::state::{LendingMarket, Obligation, Reserve},
+    spl_token_swap::state::SwapVersion,
+};
+
+type Error = Box<dyn std::error::Error>;
+
+pub fn get_lending_market(
+    rpc_client: &RpcClient,
+    lending_market_address: &Pubkey,
+) -> Result<LendingMarket, Error> {
+    let account_data = rpc_client.get_account_data(lending_market_address)?;
+    let lending_market = LendingMarket::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid lending market {}: {}", lending_market_address, err))?;
+    Ok(lending_market)
+}
+
+pub fn get_reserve(rpc_client: &RpcClient, reserve_address: &Pubkey) -> Result<Reserve, Error> {
+    let account_data = rpc_client.get_account_data(reserve_address)?;
+    let reserve = Reserve::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid reserve {}: {}", reserve_address, err))?;
+    Ok(reserve)
+}
+
+pub fn get_obligation(
+    rpc_client: &RpcClient,
+    obligation_address: &Pubkey,
+) -> Result<Obligation, Error> {
+    let account_data = rpc_client.get_account_data(obligation_address)?;
+    let obligation = Obligation::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid obligation {}: {}", obligation_address, err))?;
+    Ok(obligation)
+}
+
+pub fn get_token_swap_accounts(
+    rpc_client: &RpcClient,
+    token_swap_address: &Pubkey,
+) -> Result<(Pubkey, Pubkey), Error> {
+    let account_data = rpc_client.get_account_data(token_swap_address)?;
+    let token_swap = SwapVersion::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid token swap {}: {}", token_swap_address, err))?;
+    Ok((*token_swap.token_a_account(), *token_swap.token_b_account()))
+}
+
+pub fn get_token_account(
+    rpc_client: &RpcClient,
+    token_account_address: &Pubkey,
+    expected_token_mint: &Pubkey,
+) -> Result<spl_token::state::Account, Error> {
+    let account_data = rpc_client.get_account_data(token_account_address)?;
+    let token_account = spl_token::state::Account::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token account {}: {}", token_account_address, err))?;
+
+    if token_account.mint != *expected_token_mint {
+        Err(format!(
+            "Invalid token mint for {}, expected mint is {}",
+            token_account_address, expected_token_mint
+        )
+        .into())
+    } else {
+        Ok(token_account)
+    }
+}
+
+pub fn get_token_mint(
+    rpc_client: &RpcClient,
+    token_mint_address: &Pubkey,
+) -> Result<spl_token::state::Mint, Error> {
+    let account_data = rpc_client.get_account_data(token_mint_address)?;
+    let token_mint = spl_token::state::Mint::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token mint {}: {}", token_mint_address, err))?;
+
+    Ok(token_mint)
+}
@@ -0,0 +1,1630 @@
+mod client;
+
+use {
+    crate::client::*,
+    clap::{
+        crate_description, crate_name, crate_version, value_t, value_t_or_exit, values_t_or_exit,
+        App, AppSettings, Arg, SubCommand,
+    },
+    solana_clap_utils::{
+        input_parsers::{keypair_of, pubkey_of, pubkeys_of},
+        input_validators::{
+            is_amount, is_amount_or_all, is_keypair, is_keypair_or_ask_keyword, is_parsable,
+            is_pubkey, is_url,
+        },
+        keypair::signer_from_path,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_program::{
+        instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        native_token::Sol,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    },
+    spl_associated_token_account::{create_associated_token_account, get_associated_token_address},
+    spl_token_lending::{
+        self,
+        instruction::{
+            borrow_obligation_liquidity, deposit_reserve_liquidity_and_obligation_collateral,
+            init_lending_market, init_obligation, init_reserve, liquidate_obligation,
+            refresh_obligation, refresh_reserve, repay_obligation_liquidity,
+            set_lending_market_pyth_program,
+        },
+        oracle::OracleType,
+        state::{
+            LendingMarket, Obligation, RateModel, RateModelKink, Reserve, ReserveConfig,
+            ReserveFees, MAX_EXTENDED_OBLIGATION_RESERVES, MAX_ISOLATED_BORROW_RESERVES,
+            STALE_AFTER_SLOTS_ELAPSED,
+        },
+    },
+    std::process::exit,
+};
+
+struct Config {
+    rpc_client: RpcClient,
+    verbose: bool,
+    lending_market_owner: Box<dyn Signer>,
+    token_owner: Box<dyn Signer>,
+    fee_payer: Box<dyn Signer>,
+    dry_run: bool,
+}
+
+type Error = Box<dyn std::error::Error>;
+type CommandResult = Result<(), Error>;
+
+macro_rules! unique_signers {
+    ($vec:ident) => {
+        $vec.sort_by_key(|l| l.pubkey());
+        $vec.dedup();
+    };
+}
+
+fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
+    let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
+    if balance < required_balance {
+        Err(format!(
+            "Fee payer, {}, has insufficient balance: {} required, {} available",
+            config.fee_payer.pubkey(),
+            Sol(required_balance),
+            Sol(balance)
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+fn send_transaction(
+    config: &Config,
+    transaction: Transaction,
+) -> solana_client::client_error::Result<()> {
+    if config.dry_run {
+        let result = config.rpc_client.simulate_transaction(&transaction)?;
+        println!("Simulate result: {:?}", result);
+    } else {
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)?;
+        println!("Signature: {}", signature);
+    }
+    Ok(())
+}
+
+fn sign_and_send_transaction(
+    config: &Config,
+    instructions: &[Instruction],
+    mut signers: Vec<&dyn Signer>,
+    rent_free_balances: u64,
+) -> CommandResult {
+    let mut transaction =
+        Transaction::new_with_payer(instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn add_associated_token_account(
+    config: &Config,
+    mint: &Pubkey,
+    instructions: &mut Vec<Instruction>,
+    rent_free_balances: &mut u64,
+) -> Pubkey {
+    // Account for tokens not specified, creating one
+    let account = get_associated_token_address(&config.token_owner.pubkey(), mint);
+    if get_token_account(&config.rpc_client, &account, mint).is_err() {
+        println!("Creating account to receive tokens {}", account);
+
+        let min_account_balance = config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .unwrap();
+
+        instructions.push(create_associated_token_account(
+            &config.fee_payer.pubkey(),
+            &config.token_owner.pubkey(),
+            mint,
+        ));
+
+        *rent_free_balances += min_account_balance;
+    }
+
+    account
+}
+
+/// Oracle accounts expected after the reserve when refreshing it
+fn oracle_pubkeys(
+    config: &Config,
+    oracle: Option<Pubkey>,
+    oracle_type: OracleType,
+) -> Result<Vec<Pubkey>, Error> {
+    let mut oracle_pubkeys = vec![];
+    if let Some(oracle) = oracle {
+        oracle_pubkeys.push(oracle);
+        if oracle_type == OracleType::TokenSwap {
+            let (token_a, token_b) = get_token_swap_accounts(&config.rpc_client, &oracle)?;
+            oracle_pubkeys.push(token_a);
+            oracle_pubkeys.push(token_b);
+        }
+    }
+    Ok(oracle_pubkeys)
+}
+
+fn reserve_oracle_pubkeys(config: &Config, reserve: &Reserve) -> Result<Vec<Pubkey>, Error> {
+    oracle_pubkeys(
+        config,
+        reserve.liquidity.oracle_pubkey.into(),
+        reserve.liquidity.oracle_type,
+    )
+}
+
+fn refresh_reserve_instruction(
+    config: &Config,
+    reserve_address: &Pubkey,
+) -> Result<Instruction, Error> {
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    Ok(refresh_reserve(
+        spl_token_lending::id(),
+        *reserve_address,
+        reserve_oracle_pubkeys(config, &reserve)?,
+    ))
+}
+
+/// Refresh every reserve of the obligation, along with any other reserves an instruction needs
+/// fresh, then the obligation itself
+fn refresh_obligation_instructions(
+    config: &Config,
+    obligation_address: &Pubkey,
+    obligation: &Obligation,
+    other_reserves: &[Pubkey],
+) -> Result<Vec<Instruction>, Error> {
+    let obligation_reserves: Vec<Pubkey> = obligation
+        .deposits
+        .iter()
+        .map(|collateral| collateral.deposit_reserve)
+        .chain(
+            obligation
+                .borrows
+                .iter()
+                .map(|liquidity| liquidity.borrow_reserve),
+        )
+        .collect();
+
+    let mut instructions = vec![];
+    let mut refreshed_reserves: Vec<Pubkey> = vec![];
+    for reserve_address in obligation_reserves.iter().chain(other_reserves.iter()) {
+        if refreshed_reserves.contains(reserve_address) {
+            continue;
+        }
+        refreshed_reserves.push(*reserve_address);
+        instructions.push(refresh_reserve_instruction(config, reserve_address)?);
+    }
+
+    instructions.push(refresh_obligation(
+        spl_token_lending::id(),
+        *obligation_address,
+        obligation.lending_market,
+        obligation_reserves,
+    ));
+    Ok(instructions)
+}
+
+fn command_create_market(
+    config: &Config,
+    quote_token_mint: &Pubkey,
+    stale_after_slots_elapsed: u64,
+    pyth_program_id: Option<Pubkey>,
+    lending_market_keypair: Option<Keypair>,
+) -> CommandResult {
+    let lending_market_keypair = lending_market_keypair.unwrap_or_else(Keypair::new);
+    println!(
+        "Creating lending market {}",
+        lending_market_keypair.pubkey()
+    );
+
+    let lending_market_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(LendingMarket::LEN)?;
+
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &lending_market_keypair.pubkey(),
+            lending_market_balance,
+            LendingMarket::LEN as u64,
+            &spl_token_lending::id(),
+        ),
+        init_lending_market(
+            spl_token_lending::id(),
+            lending_market_keypair.pubkey(),
+            config.lending_market_owner.pubkey(),
+            *quote_token_mint,
+            stale_after_slots_elapsed,
+        ),
+    ];
+    let mut signers: Vec<&dyn Signer> = vec![config.fee_payer.as_ref(), &lending_market_keypair];
+    if let Some(pyth_program_id) = pyth_program_id {
+        instructions.push(set_lending_market_pyth_program(
+            spl_token_lending::id(),
+            lending_market_keypair.pubkey(),
+            config.lending_market_owner.pubkey(),
+            pyth_program_id,
+        ));
+        signers.push(config.lending_market_owner.as_ref());
+    }
+
+    sign_and_send_transaction(config, &instructions, signers, lending_market_balance)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_add_reserve(
+    config: &Config,
+    lending_market_address: &Pubkey,
+    liquidity_mint: &Pubkey,
+    source_liquidity: &Option<Pubkey>,
+    amount: f64,
+    reserve_config: ReserveConfig,
+    oracle_type: OracleType,
+    oracle: Option<Pubkey>,
+    reserve_keypair: Option<Keypair>,
+) -> CommandResult {
+    let lending_market = get_lending_market(&config.rpc_client, lending_market_address)?;
+    if lending_market.owner != config.lending_market_owner.pubkey() {
+        let error = format!(
+            "Invalid lending market owner specified, expected {}, received {}",
+            lending_market.owner,
+            config.lending_market_owner.pubkey()
+        );
+        return Err(error.into());
+    }
+
+    let mint = get_token_mint(&config.rpc_client, liquidity_mint)?;
+    let liquidity_amount = spl_token::ui_amount_to_amount(amount, mint.decimals);
+    let source_liquidity = source_liquidity.unwrap_or_else(|| {
+        get_associated_token_address(&config.token_owner.pubkey(), liquidity_mint)
+    });
+    let oracle_pubkeys = oracle_pubkeys(config, oracle, oracle_type)?;
+
+    let reserve_keypair = reserve_keypair.unwrap_or_else(Keypair::new);
+    let collateral_mint_keypair = Keypair::new();
+    let collateral_supply_keypair = Keypair::new();
+    let liquidity_supply_keypair = Keypair::new();
+    let liquidity_fee_receiver_keypair = Keypair::new();
+    let destination_collateral_keypair = Keypair::new();
+
+    let reserve_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Reserve::LEN)?;
+    let mint_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
+    let token_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+
+    println!("Creating reserve {}", reserve_keypair.pubkey());
+    println!(
+        "Creating reserve collateral mint {}",
+        collateral_mint_keypair.pubkey()
+    );
+    sign_and_send_transaction(
+        config,
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &reserve_keypair.pubkey(),
+                reserve_balance,
+                Reserve::LEN as u64,
+                &spl_token_lending::id(),
+            ),
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &collateral_mint_keypair.pubkey(),
+                mint_balance,
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &collateral_supply_keypair.pubkey(),
+                token_account_balance,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+        ],
+        vec![
+            config.fee_payer.as_ref(),
+            &reserve_keypair,
+            &collateral_mint_keypair,
+            &collateral_supply_keypair,
+        ],
+        reserve_balance + mint_balance + token_account_balance,
+    )?;
+
+    println!(
+        "Creating collateral account {}",
+        destination_collateral_keypair.pubkey()
+    );
+    sign_and_send_transaction(
+        config,
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &liquidity_supply_keypair.pubkey(),
+                token_account_balance,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &liquidity_fee_receiver_keypair.pubkey(),
+                token_account_balance,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &destination_collateral_keypair.pubkey(),
+                token_account_balance,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+        ],
+        vec![
+            config.fee_payer.as_ref(),
+            &liquidity_supply_keypair,
+            &liquidity_fee_receiver_keypair,
+            &destination_collateral_keypair,
+        ],
+        3 * token_account_balance,
+    )?;
+
+    sign_and_send_transaction(
+        config,
+        &[init_reserve(
+            spl_token_lending::id(),
+            liquidity_amount,
+            reserve_config,
+            oracle_type,
+            source_liquidity,
+            destination_collateral_keypair.pubkey(),
+            reserve_keypair.pubkey(),
+            *liquidity_mint,
+            liquidity_supply_keypair.pubkey(),
+            liquidity_fee_receiver_keypair.pubkey(),
+            collateral_mint_keypair.pubkey(),
+            collateral_supply_keypair.pubkey(),
+            lending_market.quote_token_mint,
+            *lending_market_address,
+            config.lending_market_owner.pubkey(),
+            config.token_owner.pubkey(),
+            oracle_pubkeys,
+        )],
+        vec![
+            config.fee_payer.as_ref(),
+            config.lending_market_owner.as_ref(),
+            config.token_owner.as_ref(),
+        ],
+        0,
+    )
+}
+
+fn command_refresh_reserve(config: &Config, reserve_address: &Pubkey) -> CommandResult {
+    sign_and_send_transaction(
+        config,
+        &[refresh_reserve_instruction(config, reserve_address)?],
+        vec![config.fee_payer.as_ref()],
+        0,
+    )
+}
+
+fn command_create_obligation(
+    config: &Config,
+    lending_market_address: &Pubkey,
+    extended: bool,
+    obligation_keypair: Option<Keypair>,
+) -> CommandResult {
+    let obligation_keypair = obligation_keypair.unwrap_or_else(Keypair::new);
+    println!("Creating obligation {}", obligation_keypair.pubkey());
+
+    let obligation_len = if extended {
+        Obligation::packed_len(MAX_EXTENDED_OBLIGATION_RESERVES)
+    } else {
+        Obligation::LEN
+    };
+    let obligation_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(obligation_len)?;
+
+    sign_and_send_transaction(
+        config,
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &obligation_keypair.pubkey(),
+                obligation_balance,
+                obligation_len as u64,
+                &spl_token_lending::id(),
+            ),
+            init_obligation(
+                spl_token_lending::id(),
+                obligation_keypair.pubkey(),
+                *lending_market_address,
+                config.token_owner.pubkey(),
+            ),
+        ],
+        vec![
+            config.fee_payer.as_ref(),
+            &obligation_keypair,
+            config.token_owner.as_ref(),
+        ],
+        obligation_balance,
+    )
+}
+
+fn command_refresh_obligation(config: &Config, obligation_address: &Pubkey) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    sign_and_send_transaction(
+        config,
+        &refresh_obligation_instructions(config, obligation_address, &obligation, &[])?,
+        vec![config.fee_payer.as_ref()],
+        0,
+    )
+}
+
+fn command_deposit(
+    config: &Config,
+    obligation_address: &Pubkey,
+    reserve_address: &Pubkey,
+    source_liquidity: &Option<Pubkey>,
+    amount: f64,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    let liquidity_amount = spl_token::ui_amount_to_amount(amount, reserve.liquidity.mint_decimals);
+    let source_liquidity = source_liquidity.unwrap_or_else(|| {
+        get_associated_token_address(&config.token_owner.pubkey(), &reserve.liquidity.mint_pubkey)
+    });
+
+    if config.verbose {
+        println!(
+            "Depositing {} from {} into reserve {}",
+            amount, source_liquidity, reserve_address
+        );
+    }
+
+    sign_and_send_transaction(
+        config,
+        &[deposit_reserve_liquidity_and_obligation_collateral(
+            spl_token_lending::id(),
+            liquidity_amount,
+            source_liquidity,
+            *reserve_address,
+            reserve.liquidity.supply_pubkey,
+            reserve.collateral.mint_pubkey,
+            reserve.collateral.supply_pubkey,
+            *obligation_address,
+            obligation.lending_market,
+            config.token_owner.pubkey(),
+            config.token_owner.pubkey(),
+            reserve_oracle_pubkeys(config, &reserve)?,
+        )],
+        vec![config.fee_payer.as_ref(), config.token_owner.as_ref()],
+        0,
+    )
+}
+
+fn command_borrow(
+    config: &Config,
+    obligation_address: &Pubkey,
+    reserve_address: &Pubkey,
+    destination_liquidity: &Option<Pubkey>,
+    amount: f64,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    let liquidity_amount = spl_token::ui_amount_to_amount(amount, reserve.liquidity.mint_decimals);
+
+    // debt owed to the other isolated borrow reserves counts toward the debt ceiling
+    let isolated_borrow_reserves: Vec<Pubkey> = match obligation.isolated_collateral_reserve {
+        COption::Some(isolated_collateral_reserve) => {
+            get_reserve(&config.rpc_client, &isolated_collateral_reserve)?
+                .config
+                .isolated_borrow_reserves
+                .iter()
+                .filter(|pubkey| **pubkey != Pubkey::default() && *pubkey != reserve_address)
+                .copied()
+                .collect()
+        }
+        COption::None => vec![],
+    };
+    let mut other_reserves = vec![*reserve_address];
+    other_reserves.extend(isolated_borrow_reserves.iter());
+
+    let mut instructions =
+        refresh_obligation_instructions(config, obligation_address, &obligation, &other_reserves)?;
+    let mut total_rent_free_balances: u64 = 0;
+
+    // Create token account if not specified
+    let destination_liquidity = destination_liquidity.unwrap_or_else(|| {
+        add_associated_token_account(
+            &config,
+            &reserve.liquidity.mint_pubkey,
+            &mut instructions,
+            &mut total_rent_free_balances,
+        )
+    });
+
+    instructions.push(borrow_obligation_liquidity(
+        spl_token_lending::id(),
+        liquidity_amount,
+        reserve.liquidity.supply_pubkey,
+        destination_liquidity,
+        *reserve_address,
+        reserve.liquidity.fee_receiver,
+        *obligation_address,
+        obligation.lending_market,
+        config.token_owner.pubkey(),
+        obligation.isolated_collateral_reserve.into(),
+        isolated_borrow_reserves,
+        None,
+    ));
+
+    sign_and_send_transaction(
+        config,
+        &instructions,
+        vec![config.fee_payer.as_ref(), config.token_owner.as_ref()],
+        total_rent_free_balances,
+    )
+}
+
+fn command_repay(
+    config: &Config,
+    obligation_address: &Pubkey,
+    reserve_address: &Pubkey,
+    source_liquidity: &Option<Pubkey>,
+    amount: Option<f64>,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    let liquidity_amount = amount.map_or(u64::MAX, |amount| {
+        spl_token::ui_amount_to_amount(amount, reserve.liquidity.mint_decimals)
+    });
+    let source_liquidity = source_liquidity.unwrap_or_else(|| {
+        get_associated_token_address(&config.token_owner.pubkey(), &reserve.liquidity.mint_pubkey)
+    });
+
+    sign_and_send_transaction(
+        config,
+        &[
+            refresh_reserve_instruction(config, reserve_address)?,
+            repay_obligation_liquidity(
+                spl_token_lending::id(),
+                liquidity_amount,
+                source_liquidity,
+                reserve.liquidity.supply_pubkey,
+                *reserve_address,
+                *obligation_address,
+                obligation.lending_market,
+                config.token_owner.pubkey(),
+                obligation.isolated_collateral_reserve.into(),
+            ),
+        ],
+        vec![config.fee_payer.as_ref(), config.token_owner.as_ref()],
+        0,
+    )
+}
+
+fn command_liquidate(
+    config: &Config,
+    obligation_address: &Pubkey,
+    repay_reserve_address: &Pubkey,
+    withdraw_reserve_address: &Pubkey,
+    source_liquidity: &Option<Pubkey>,
+    amount: f64,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let repay_reserve = get_reserve(&config.rpc_client, repay_reserve_address)?;
+    let withdraw_reserve = get_reserve(&config.rpc_client, withdraw_reserve_address)?;
+    let liquidity_amount =
+        spl_token::ui_amount_to_amount(amount, repay_reserve.liquidity.mint_decimals);
+    let source_liquidity = source_liquidity.unwrap_or_else(|| {
+        get_associated_token_address(
+            &config.token_owner.pubkey(),
+            &repay_reserve.liquidity.mint_pubkey,
+        )
+    });
+
+    let mut instructions =
+        refresh_obligation_instructions(config, obligation_address, &obligation, &[])?;
+    let mut total_rent_free_balances: u64 = 0;
+
+    let destination_collateral = add_associated_token_account(
+        &config,
+        &withdraw_reserve.collateral.mint_pubkey,
+        &mut instructions,
+        &mut total_rent_free_balances,
+    );
+
+    instructions.push(liquidate_obligation(
+        spl_token_lending::id(),
+        liquidity_amount,
+        source_liquidity,
+        destination_collateral,
+        *repay_reserve_address,
+        repay_reserve.liquidity.supply_pubkey,
+        *withdraw_reserve_address,
+        withdraw_reserve.collateral.supply_pubkey,
+        *obligation_address,
+        obligation.lending_market,
+        config.token_owner.pubkey(),
+    ));
+
+    sign_and_send_transaction(
+        config,
+        &instructions,
+        vec![config.fee_payer.as_ref(), config.token_owner.as_ref()],
+        total_rent_free_balances,
+    )
+}
+
+fn command_show_obligation(config: &Config, obligation_address: &Pubkey) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let lending_market = get_lending_market(&config.rpc_client, &obligation.lending_market)?;
+    let slot = config.rpc_client.get_slot()?;
+
+    println!("Obligation: {}", obligation_address);
+    println!("Lending Market: {}", obligation.lending_market);
+    println!("Owner: {}", obligation.owner);
+    if obligation.delegate != Pubkey::default() {
+        println!(
+            "Delegate: {}\tPermissions: {:#04x}",
+            obligation.delegate, obligation.delegate_permissions
+        );
+    }
+
+    for collateral in &obligation.deposits {
+        let reserve = get_reserve(&config.rpc_client, &collateral.deposit_reserve)?;
+        println!(
+            "Deposit Reserve: {}\tCollateral: {}\tMarket Value: {}",
+            collateral.deposit_reserve,
+            spl_token::amount_to_ui_amount(
+                collateral.deposited_amount,
+                reserve.liquidity.mint_decimals
+            ),
+            collateral.market_value
+        );
+    }
+    for liquidity in &obligation.borrows {
+        println!(
+            "Borrow Reserve: {}\tBorrowed: {}\tMarket Value: {}",
+            liquidity.borrow_reserve, liquidity.borrowed_amount_wads, liquidity.market_value
+        );
+    }
+
+    println!(
+        "Deposited Value: {}{}",
+        obligation.deposited_value,
+        if obligation
+            .last_update
+            .is_stale(slot, lending_market.stale_after_slots_elapsed)?
+        {
+            " [REFRESH REQUIRED]"
+        } else {
+            ""
+        }
+    );
+    println!("Borrowed Value: {}", obligation.borrowed_value);
+    println!("Allowed Borrow Value: {}", obligation.allowed_borrow_value);
+    println!(
+        "Unhealthy Borrow Value: {}",
+        obligation.unhealthy_borrow_value
+    );
+    if !obligation.deposits.is_empty() {
+        println!(
+            "Loan To Value: {}{}",
+            obligation.loan_to_value()?,
+            if !obligation.borrows.is_empty()
+                && obligation.borrowed_value >= obligation.unhealthy_borrow_value
+            {
+                " [UNHEALTHY]"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+fn parse_oracle_type(oracle_type: &str) -> Result<OracleType, String> {
+    match oracle_type {
+        "flux-aggregator" => Ok(OracleType::FluxAggregator),
+        "pyth" => Ok(OracleType::Pyth),
+        "token-swap" => Ok(OracleType::TokenSwap),
+        _ => Err(format!("Unknown oracle type {}", oracle_type)),
+    }
+}
+
+fn parse_rate_model_kink(kink: &str) -> Result<RateModelKink, String> {
+    let mut parts = kink.split(':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(utilization_bps), Some(borrow_rate_bps), None) => Ok(RateModelKink {
+            utilization_bps: utilization_bps
+                .parse()
+                .map_err(|err| format!("Invalid kink utilization rate {}: {}", kink, err))?,
+            borrow_rate_bps: borrow_rate_bps
+                .parse()
+                .map_err(|err| format!("Invalid kink borrow rate {}: {}", kink, err))?,
+        }),
+        _ => Err(format!(
+            "Invalid kink {}, expected UTILIZATION_BPS:BORROW_RATE_BPS",
+            kink
+        )),
+    }
+}
+
+fn is_rate_model_kink(kink: String) -> Result<(), String> {
+    parse_rate_model_kink(&kink).map(|_| ())
+}
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(&config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transaction instead of executing"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            Arg::with_name("lending_market_owner")
+                .long("lending-market-owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the lending market owner. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("token_owner")
+                .long("token-owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the owner of the token accounts and obligations. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fee_payer")
+                .long("fee-payer")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the fee-payer account. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .subcommand(SubCommand::with_name("create-market")
+            .about("Create a new lending market")
+            .arg(
+                Arg::with_name("quote_token_mint")
+                    .long("quote-token-mint")
+                    .validator(is_pubkey)
+                    .value_name("MINT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Mint of the currency market prices are quoted in"),
+            )
+            .arg(
+                Arg::with_name("stale_after_slots_elapsed")
+                    .long("stale-after-slots-elapsed")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .help("Number of slots after which refreshed reserves and obligations are stale"),
+            )
+            .arg(
+                Arg::with_name("pyth_program")
+                    .long("pyth-program")
+                    .validator(is_pubkey)
+                    .value_name("PUBKEY")
+                    .takes_value(true)
+                    .help("Program that owns the Pyth price accounts reserves can be priced by"),
+            )
+            .arg(
+                Arg::with_name("market_keypair")
+                    .long("market-keypair")
+                    .validator(is_keypair_or_ask_keyword)
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("Lending market keypair [default: new keypair]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("add-reserve")
+            .about("Add a reserve to a lending market. Must be signed by the lending market owner.")
+            .arg(
+                Arg::with_name("market")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("MARKET_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lending market address"),
+            )
+            .arg(
+                Arg::with_name("liquidity_mint")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("MINT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Mint of the reserve liquidity"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to deposit into the new reserve"),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Source liquidity token account [default: token owner's associated token account]"),
+            )
+            .arg(
+                Arg::with_name("oracle")
+                    .long("oracle")
+                    .validator(is_pubkey)
+                    .value_name("ORACLE_ADDRESS")
+                    .takes_value(true)
+                    .help("Reserve liquidity oracle, omitted if the liquidity is the quote currency"),
+            )
+            .arg(
+                Arg::with_name("oracle_type")
+                    .long("oracle-type")
+                    .value_name("ORACLE_TYPE")
+                    .takes_value(true)
+                    .possible_values(&["flux-aggregator", "pyth", "token-swap"])
+                    .default_value("flux-aggregator")
+                    .help("Kind of the reserve liquidity oracle"),
+            )
+            .arg(
+                Arg::with_name("reserve_keypair")
+                    .long("reserve-keypair")
+                    .validator(is_keypair_or_ask_keyword)
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("Reserve keypair [default: new keypair]"),
+            )
+            .arg(
+                Arg::with_name("optimal_utilization_rate")
+                    .long("optimal-utilization-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Optimal utilization rate, as a percentage"),
+            )
+            .arg(
+                Arg::with_name("loan_to_value_ratio")
+                    .long("loan-to-value-ratio")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Target ratio of the value of borrows to deposits, as a percentage. 0 disables use as collateral."),
+            )
+            .arg(
+                Arg::with_name("liquidation_bonus")
+                    .long("liquidation-bonus")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage"),
+            )
+            .arg(
+                Arg::with_name("liquidation_threshold")
+                    .long("liquidation-threshold")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Loan to value ratio at which an obligation can be liquidated, as a percentage"),
+            )
+            .arg(
+                Arg::with_name("min_borrow_rate")
+                    .long("min-borrow-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Min borrow APY"),
+            )
+            .arg(
+                Arg::with_name("optimal_borrow_rate")
+                    .long("optimal-borrow-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Optimal (utilization) borrow APY"),
+            )
+            .arg(
+                Arg::with_name("max_borrow_rate")
+                    .long("max-borrow-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Max borrow APY"),
+            )
+            .arg(
+                Arg::with_name("borrow_fee_wad")
+                    .long("borrow-fee-wad")
+                    .validator(is_parsable::<u64>)
+                    .value_name("WAD")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Fee assessed on borrows, expressed as a Wad"),
+            )
+            .arg(
+                Arg::with_name("flash_loan_fee_wad")
+                    .long("flash-loan-fee-wad")
+                    .validator(is_parsable::<u64>)
+                    .value_name("WAD")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Fee assessed on flash loans, expressed as a Wad"),
+            )
+            .arg(
+                Arg::with_name("host_fee_percentage")
+                    .long("host-fee-percentage")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Share of fees going to the host account, if provided"),
+            )
+            .arg(
+                Arg::with_name("max_oracle_age_seconds")
+                    .long("max-oracle-age-seconds")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .default_value("60")
+                    .help("Maximum age of the reserve liquidity oracle price"),
+            )
+            .arg(
+                Arg::with_name("protocol_take_rate")
+                    .long("protocol-take-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Share of accrued interest set aside for the lending market owner"),
+            )
+            .arg(
+                Arg::with_name("rate_model")
+                    .long("rate-model")
+                    .value_name("RATE_MODEL")
+                    .takes_value(true)
+                    .possible_values(&["two-slope", "multi-kink", "fixed"])
+                    .default_value("two-slope")
+                    .help("Borrow rate model, the two-slope model uses the min, optimal and max borrow rates"),
+            )
+            .arg(
+                Arg::with_name("rate_model_kink")
+                    .long("rate-model-kink")
+                    .validator(is_rate_model_kink)
+                    .value_name("UTILIZATION_BPS:BORROW_RATE_BPS")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .required_if("rate_model", "multi-kink")
+                    .help("Kink of a multi-kink rate model, may be specified multiple times"),
+            )
+            .arg(
+                Arg::with_name("fixed_borrow_rate_bps")
+                    .long("fixed-borrow-rate-bps")
+                    .validator(is_parsable::<u16>)
+                    .value_name("BPS")
+                    .takes_value(true)
+                    .required_if("rate_model", "fixed")
+                    .help("Borrow APY of a fixed rate model, in basis points"),
+            )
+            .arg(
+                Arg::with_name("isolated")
+                    .long("isolated")
+                    .takes_value(false)
+                    .help("Obligations depositing this collateral can only borrow from the isolated borrow reserves"),
+            )
+            .arg(
+                Arg::with_name("isolated_debt_ceiling")
+                    .long("isolated-debt-ceiling")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Max market value of debt backed by this collateral while isolated, in quote currency"),
+            )
+            .arg(
+                Arg::with_name("isolated_borrow_reserve")
+                    .long("isolated-borrow-reserve")
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Reserve that can be borrowed from against this collateral while isolated, may be specified multiple times"),
+            )
+            .arg(
+                Arg::with_name("deposit_limit")
+                    .long("deposit-limit")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Max total supply of liquidity, 0 if deposits are not limited"),
+            )
+            .arg(
+                Arg::with_name("borrow_limit")
+                    .long("borrow-limit")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Max total borrowed liquidity, 0 if borrows are not limited"),
+            )
+            .arg(
+                Arg::with_name("liquidation_close_factor")
+                    .long("liquidation-close-factor")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("50")
+                    .help("Percentage of an obligation borrow that can be repaid during each liquidation"),
+            )
+            .arg(
+                Arg::with_name("liquidation_auction_slots")
+                    .long("liquidation-auction-slots")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Number of slots over which the liquidation bonus grows to the configured bonus, 0 for a fixed bonus"),
+            )
+        )
+        .subcommand(SubCommand::with_name("refresh-reserve")
+            .about("Refresh the market price and accrued interest of a reserve")
+            .arg(
+                Arg::with_name("reserve")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve address"),
+            )
+        )
+        .subcommand(SubCommand::with_name("create-obligation")
+            .about("Create a new obligation owned by the token owner")
+            .arg(
+                Arg::with_name("market")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("MARKET_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lending market address"),
+            )
+            .arg(
+                Arg::with_name("extended")
+                    .long("extended")
+                    .takes_value(false)
+                    .help("Create an extended obligation with room for more deposits and borrows"),
+            )
+            .arg(
+                Arg::with_name("obligation_keypair")
+                    .long("obligation-keypair")
+                    .validator(is_keypair_or_ask_keyword)
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("Obligation keypair [default: new keypair]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("refresh-obligation")
+            .about("Refresh an obligation along with all of its reserves")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address"),
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit")
+            .about("Deposit liquidity into a reserve and use the collateral in an obligation")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address"),
+            )
+            .arg(
+                Arg::with_name("reserve")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve to deposit into"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to deposit"),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Source liquidity token account [default: token owner's associated token account]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("borrow")
+            .about("Borrow liquidity from a reserve against an obligation")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address"),
+            )
+            .arg(
+                Arg::with_name("reserve")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve to borrow from"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to borrow"),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Destination liquidity token account [default: token owner's associated token account]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("repay")
+            .about("Repay liquidity borrowed by an obligation")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address"),
+            )
+            .arg(
+                Arg::with_name("reserve")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve the liquidity was borrowed from"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount_or_all)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to repay, or ALL to repay the whole borrow"),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Source liquidity token account [default: token owner's associated token account]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("liquidate")
+            .about("Repay part of an unhealthy obligation's borrow in exchange for its collateral")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address"),
+            )
+            .arg(
+                Arg::with_name("repay_reserve")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve of the borrow to repay"),
+            )
+            .arg(
+                Arg::with_name("withdraw_reserve")
+                    .index(3)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve of the collateral to receive"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(4)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to repay"),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Source liquidity token account [default: token owner's associated token account]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("show-obligation")
+            .about("Show the deposits, borrows and health of an obligation")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address"),
+            )
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+
+        let lending_market_owner = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "lending_market_owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let token_owner = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "token_owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let fee_payer = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let verbose = matches.is_present("verbose");
+        let dry_run = matches.is_present("dry_run");
+
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            verbose,
+            lending_market_owner,
+            token_owner,
+            fee_payer,
+            dry_run,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("create-market", Some(arg_matches)) => {
+            let quote_token_mint = pubkey_of(arg_matches, "quote_token_mint").unwrap();
+            let stale_after_slots_elapsed = value_t!(arg_matches, "stale_after_slots_elapsed", u64)
+                .unwrap_or(STALE_AFTER_SLOTS_ELAPSED);
+            let pyth_program_id = pubkey_of(arg_matches, "pyth_program");
+            let market_keypair = keypair_of(arg_matches, "market_keypair");
+            command_create_market(
+                &config,
+                &quote_token_mint,
+                stale_after_slots_elapsed,
+                pyth_program_id,
+                market_keypair,
+            )
+        }
+        ("add-reserve", Some(arg_matches)) => {
+            let lending_market_address = pubkey_of(arg_matches, "market").unwrap();
+            let liquidity_mint = pubkey_of(arg_matches, "liquidity_mint").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let source = pubkey_of(arg_matches, "source");
+            let oracle = pubkey_of(arg_matches, "oracle");
+            let oracle_type =
+                parse_oracle_type(arg_matches.value_of("oracle_type").unwrap()).unwrap();
+            let reserve_keypair = keypair_of(arg_matches, "reserve_keypair");
+
+            let rate_model = match arg_matches.value_of("rate_model").unwrap() {
+                "multi-kink" => {
+                    let kinks = values_t_or_exit!(arg_matches, "rate_model_kink", String)
+                        .iter()
+                        .map(|kink| parse_rate_model_kink(kink).unwrap())
+                        .collect::<Vec<_>>();
+                    RateModel::multi_kink(&kinks).unwrap_or_else(|e| {
+                        eprintln!("error: {}", e);
+                        exit(1);
+                    })
+                }
+                "fixed" => RateModel::Fixed {
+                    borrow_rate_bps: value_t_or_exit!(arg_matches, "fixed_borrow_rate_bps", u16),
+                },
+                _ => RateModel::TwoSlope,
+            };
+
+            let isolated_borrow_reserve_list =
+                pubkeys_of(arg_matches, "isolated_borrow_reserve").unwrap_or_default();
+            if isolated_borrow_reserve_list.len() > MAX_ISOLATED_BORROW_RESERVES {
+                eprintln!(
+                    "error: at most {} isolated borrow reserves can be specified",
+                    MAX_ISOLATED_BORROW_RESERVES
+                );
+                exit(1);
+            }
+            let mut isolated_borrow_reserves = [Pubkey::default(); MAX_ISOLATED_BORROW_RESERVES];
+            isolated_borrow_reserves[..isolated_borrow_reserve_list.len()]
+                .copy_from_slice(&isolated_borrow_reserve_list);
+
+            let reserve_config = ReserveConfig {
+                optimal_utilization_rate: value_t_or_exit!(
+                    arg_matches,
+                    "optimal_utilization_rate",
+                    u8
+                ),
+                loan_to_value_ratio: value_t_or_exit!(arg_matches, "loan_to_value_ratio", u8),
+                liquidation_bonus: value_t_or_exit!(arg_matches, "liquidation_bonus", u8),
+                liquidation_threshold: value_t_or_exit!(arg_matches, "liquidation_threshold", u8),
+                min_borrow_rate: value_t_or_exit!(arg_matches, "min_borrow_rate", u8),
+                optimal_borrow_rate: value_t_or_exit!(arg_matches, "optimal_borrow_rate", u8),
+                max_borrow_rate: value_t_or_exit!(arg_matches, "max_borrow_rate", u8),
+                fees: ReserveFees {
+                    borrow_fee_wad: value_t_or_exit!(arg_matches, "borrow_fee_wad", u64),
+                    flash_loan_fee_wad: value_t_or_exit!(arg_matches, "flash_loan_fee_wad", u64),
+                    host_fee_percentage: value_t_or_exit!(arg_matches, "host_fee_percentage", u8),
+                },
+                max_oracle_age_seconds: value_t_or_exit!(
+                    arg_matches,
+                    "max_oracle_age_seconds",
+                    u64
+                ),
+                protocol_take_rate: value_t_or_exit!(arg_matches, "protocol_take_rate", u8),
+                rate_model,
+                isolated: arg_matches.is_present("isolated"),
+                isolated_debt_ceiling: value_t_or_exit!(arg_matches, "isolated_debt_ceiling", u64),
+                isolated_borrow_reserves,
+                deposit_limit: value_t_or_exit!(arg_matches, "deposit_limit", u64),
+                borrow_limit: value_t_or_exit!(arg_matches, "borrow_limit", u64),
+                liquidation_close_factor: value_t_or_exit!(
+                    arg_matches,
+                    "liquidation_close_factor",
+                    u8
+                ),
+                liquidation_auction_slots: value_t_or_exit!(
+                    arg_matches,
+                    "liquidation_auction_slots",
+                    u64
+                ),
+            };
+
+            command_add_reserve(
+                &config,
+                &lending_market_address,
+                &liquidity_mint,
+                &source,
+                amount,
+                reserve_config,
+                oracle_type,
+                oracle,
+                reserve_keypair,
+            )
+        }
+        ("refresh-reserve", Some(arg_matches)) => {
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            command_refresh_reserve(&config, &reserve_address)
+        }
+        ("create-obligation", Some(arg_matches)) => {
+            let lending_market_address = pubkey_of(arg_matches, "market").unwrap();
+            let extended = arg_matches.is_present("extended");
+            let obligation_keypair = keypair_of(arg_matches, "obligation_keypair");
+            command_create_obligation(
+                &config,
+                &lending_market_address,
+                extended,
+                obligation_keypair,
+            )
+        }
+        ("refresh-obligation", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            command_refresh_obligation(&config, &obligation_address)
+        }
+        ("deposit", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let source = pubkey_of(arg_matches, "source");
+            command_deposit(
+                &config,
+                &obligation_address,
+                &reserve_address,
+                &source,
+                amount,
+            )
+        }
+        ("borrow", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let destination = pubkey_of(arg_matches, "destination");
+            command_borrow(
+                &config,
+                &obligation_address,
+                &reserve_address,
+                &destination,
+                amount,
+            )
+        }
+        ("repay", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let amount = match arg_matches.value_of("amount").unwrap() {
+                "ALL" => None,
+                amount => Some(amount.parse::<f64>().unwrap()),
+            };
+            let source = pubkey_of(arg_matches, "source");
+            command_repay(
+                &config,
+                &obligation_address,
+                &reserve_address,
+                &source,
+                amount,
+            )
+        }
+        ("liquidate", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let repay_reserve_address = pubkey_of(arg_matches, "repay_reserve").unwrap();
+            let withdraw_reserve_address = pubkey_of(arg_matches, "withdraw_reserve").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let source = pubkey_of(arg_matches, "source");
+            command_liquidate(
+                &config,
+                &obligation_address,
+                &repay_reserve_address,
+                &withdraw_reserve_address,
+                &source,
+                amount,
+            )
+        }
+        ("show-obligation", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            command_show_obligation(&config, &obligation_address)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
@@ -153,6 +153,43 @@ pub enum LendingError {
     /// Oracle config is invalid
     #[error("Input oracle config is invalid")]
     InvalidOracleConfig,
+    /// Flash loan receiver program is invalid
+    #[error("Input flash loan receiver program is invalid")]
+    InvalidFlashLoanReceiverProgram,
+    /// Not enough liquidity after flash loan
+    #[error("Not enough liquidity returned after flash loan")]
+    NotEnoughLiquidityAfterFlashLoan,
+
+    // 45
+    /// Oracle price is too old to be used
+    #[error("Oracle price is stale")]
+    OracleStale,
+    /// Oracle price could not be trusted
+    #[error("Oracle price is invalid")]
+    InvalidOraclePrice,
+    /// Isolated collateral combined with other collateral
+    #[error("Isolated collateral cannot be combined with other collateral")]
+    IsolatedCollateral,
+    /// Reserve is not on the isolated collateral borrow allow-list
+    #[error("Reserve cannot be borrowed from against isolated collateral")]
+    IsolatedBorrowNotAllowed,
+    /// Isolated collateral debt ceiling would be exceeded
+    #[error("Isolated collateral debt ceiling exceeded")]
+    IsolatedDebtCeilingExceeded,
+
+    // 50
+    /// Deposit would exceed the reserve deposit limit
+    #[error("Deposit limit exceeded")]
+    DepositLimitExceeded,
+    /// Borrow would exceed the reserve borrow limit
+    #[error("Borrow limit exceeded")]
+    BorrowLimitExceeded,
+    /// Operation is paused by the lending market owner or guardian
+    #[error("Lending market operation is paused")]
+    MarketPaused,
+    /// Borrow by an obligation delegate would exceed the delegate borrow limit
+    #[error("Delegate borrow limit exceeded")]
+    DelegateBorrowLimitExceeded,
 }
 
 impl From<LendingError> for ProgramError {
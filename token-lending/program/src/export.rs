@@ -0,0 +1,744 @@
+//! Stable JSON representation of lending market state
+//!
+//! Integer amounts wider than 32 bits, `Decimal` and `Rate` values are serialized as exact
+//! decimal strings so they survive JSON parsers that read numbers as doubles. Addresses are
+//! base58 strings and unset optional addresses are `null`. The layout is described by
+//! `schema/market.schema.json` and only changes along with `EXPORT_VERSION`.
+
+use crate::{
+    math::{Decimal, Rate},
+    oracle::OracleType,
+    state::{
+        LastUpdate, LendingMarket, Obligation, ObligationCollateral, ObligationLiquidity,
+        RateModel, RateSnapshot, Reserve, ReserveCollateral, ReserveConfig, ReserveLiquidity,
+    },
+};
+use serde::Serialize;
+use solana_program::{
+    msg,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+
+/// Version of the exported JSON layout
+pub const EXPORT_VERSION: u8 = 2;
+
+fn optional_pubkey(pubkey: &Pubkey) -> Option<String> {
+    if *pubkey == Pubkey::default() {
+        None
+    } else {
+        Some(pubkey.to_string())
+    }
+}
+
+fn coption_pubkey(pubkey: &COption<Pubkey>) -> Option<String> {
+    match pubkey {
+        COption::Some(pubkey) => Some(pubkey.to_string()),
+        COption::None => None,
+    }
+}
+
+/// Exported lending market along with its reserves and obligations
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketExport {
+    /// Version of the exported JSON layout
+    pub export_version: u8,
+    /// Lending market
+    pub lending_market: LendingMarketExport,
+    /// Reserves of the lending market
+    pub reserves: Vec<ReserveExport>,
+    /// Obligations of the lending market
+    pub obligations: Vec<ObligationExport>,
+}
+
+/// Exported last update slot
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastUpdateExport {
+    /// Last slot when updated
+    pub slot: String,
+    /// True when marked stale, false when slot updated
+    pub stale: bool,
+}
+
+impl From<&LastUpdate> for LastUpdateExport {
+    fn from(last_update: &LastUpdate) -> Self {
+        Self {
+            slot: last_update.slot.to_string(),
+            stale: last_update.stale,
+        }
+    }
+}
+
+/// Exported lending market state
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LendingMarketExport {
+    /// Lending market address
+    pub pubkey: String,
+    /// Version of lending market
+    pub version: u8,
+    /// Owner authority which can add new reserves
+    pub owner: String,
+    /// Quote currency token mint
+    pub quote_token_mint: String,
+    /// Token program id
+    pub token_program_id: String,
+    /// Number of slots after which refreshed reserves and obligations are considered stale
+    pub stale_after_slots_elapsed: String,
+    /// Guardian authority which can set pause flags alongside the owner
+    pub guardian: Option<String>,
+    /// Bitmask of paused operations
+    pub pause_flags: u8,
+    /// Program that owns the Pyth price accounts reserves can be priced by
+    pub pyth_program_id: Option<String>,
+}
+
+impl LendingMarketExport {
+    /// Export a lending market
+    pub fn new(pubkey: &Pubkey, lending_market: &LendingMarket) -> Self {
+        Self {
+            pubkey: pubkey.to_string(),
+            version: lending_market.version,
+            owner: lending_market.owner.to_string(),
+            quote_token_mint: lending_market.quote_token_mint.to_string(),
+            token_program_id: lending_market.token_program_id.to_string(),
+            stale_after_slots_elapsed: lending_market.stale_after_slots_elapsed.to_string(),
+            guardian: optional_pubkey(&lending_market.guardian),
+            pause_flags: lending_market.pause_flags,
+            pyth_program_id: optional_pubkey(&lending_market.pyth_program_id),
+        }
+    }
+}
+
+/// Exported reserve state, with derived rates
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveExport {
+    /// Reserve address
+    pub pubkey: String,
+    /// Version of the struct
+    pub version: u8,
+    /// Last slot when supply and rates updated
+    pub last_update: LastUpdateExport,
+    /// Lending market address
+    pub lending_market: String,
+    /// Reserve liquidity
+    pub liquidity: ReserveLiquidityExport,
+    /// Reserve collateral
+    pub collateral: ReserveCollateralExport,
+    /// Reserve configuration values
+    pub config: ReserveConfigExport,
+    /// Ratio of borrowed liquidity to total liquidity supply
+    pub utilization_rate: String,
+    /// Current borrow APY
+    pub borrow_apy: String,
+    /// Current supply APY, the borrow APY earned on utilized liquidity less the protocol take
+    pub supply_apy: String,
+    /// Amount of collateral minted per unit of liquidity
+    pub collateral_exchange_rate: String,
+    /// Recent rate snapshots, oldest first
+    pub rate_history: Vec<RateSnapshotExport>,
+}
+
+impl ReserveExport {
+    /// Export a reserve and calculate its derived rates
+    pub fn new(pubkey: &Pubkey, reserve: &Reserve) -> Result<Self, ProgramError> {
+        let utilization_rate = reserve.liquidity.utilization_rate()?;
+        let borrow_rate = reserve.current_borrow_rate()?;
+        let supply_rate = reserve.current_supply_rate()?;
+        let collateral_exchange_rate = Rate::from(reserve.collateral_exchange_rate()?);
+
+        Ok(Self {
+            pubkey: pubkey.to_string(),
+            version: reserve.version,
+            last_update: LastUpdateExport::from(&reserve.last_update),
+            lending_market: reserve.lending_market.to_string(),
+            liquidity: ReserveLiquidityExport::new(&reserve.liquidity)?,
+            collateral: ReserveCollateralExport::from(&reserve.collateral),
+            config: ReserveConfigExport::from(&reserve.config),
+            utilization_rate: utilization_rate.to_string(),
+            borrow_apy: borrow_rate.to_string(),
+            supply_apy: supply_rate.to_string(),
+            collateral_exchange_rate: collateral_exchange_rate.to_string(),
+            rate_history: reserve
+                .rate_history
+                .iter()
+                .map(RateSnapshotExport::from)
+                .collect(),
+        })
+    }
+}
+
+/// Exported reserve rate snapshot
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateSnapshotExport {
+    /// Slot the rates were recorded at
+    pub slot: String,
+    /// Reserve liquidity cumulative borrow rate
+    pub cumulative_borrow_rate_wads: String,
+    /// Amount of collateral minted per unit of liquidity
+    pub collateral_exchange_rate: String,
+}
+
+impl From<&RateSnapshot> for RateSnapshotExport {
+    fn from(snapshot: &RateSnapshot) -> Self {
+        Self {
+            slot: snapshot.slot.to_string(),
+            cumulative_borrow_rate_wads: snapshot.cumulative_borrow_rate_wads.to_string(),
+            collateral_exchange_rate: snapshot.collateral_exchange_rate.to_string(),
+        }
+    }
+}
+
+/// Exported reserve liquidity
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveLiquidityExport {
+    /// Reserve liquidity mint address
+    pub mint_pubkey: String,
+    /// Reserve liquidity mint decimals
+    pub mint_decimals: u8,
+    /// Reserve liquidity supply address
+    pub supply_pubkey: String,
+    /// Reserve liquidity fee receiver address
+    pub fee_receiver: String,
+    /// Reserve liquidity oracle account, `null` if the liquidity is the quote currency
+    pub oracle_pubkey: Option<String>,
+    /// Reserve liquidity oracle type
+    pub oracle_type: OracleTypeExport,
+    /// Decimals of the quote currency
+    pub quote_decimals: u8,
+    /// Reserve liquidity available
+    pub available_amount: String,
+    /// Reserve liquidity borrowed
+    pub borrowed_amount_wads: String,
+    /// Reserve liquidity cumulative borrow rate
+    pub cumulative_borrow_rate_wads: String,
+    /// Reserve liquidity market price in quote currency
+    pub market_price: String,
+    /// Accrued interest set aside for the lending market owner
+    pub accumulated_protocol_fees_wads: String,
+    /// Total liquidity owed to depositors, available and borrowed less protocol fees
+    pub total_supply: String,
+}
+
+impl ReserveLiquidityExport {
+    /// Export reserve liquidity
+    pub fn new(liquidity: &ReserveLiquidity) -> Result<Self, ProgramError> {
+        Ok(Self {
+            mint_pubkey: liquidity.mint_pubkey.to_string(),
+            mint_decimals: liquidity.mint_decimals,
+            supply_pubkey: liquidity.supply_pubkey.to_string(),
+            fee_receiver: liquidity.fee_receiver.to_string(),
+            oracle_pubkey: coption_pubkey(&liquidity.oracle_pubkey),
+            oracle_type: liquidity.oracle_type.into(),
+            quote_decimals: liquidity.quote_decimals,
+            available_amount: liquidity.available_amount.to_string(),
+            borrowed_amount_wads: liquidity.borrowed_amount_wads.to_string(),
+            cumulative_borrow_rate_wads: liquidity.cumulative_borrow_rate_wads.to_string(),
+            market_price: liquidity.market_price.to_string(),
+            accumulated_protocol_fees_wads: liquidity.accumulated_protocol_fees_wads.to_string(),
+            total_supply: liquidity.total_supply()?.to_string(),
+        })
+    }
+}
+
+/// Exported oracle type
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OracleTypeExport {
+    /// Flux aggregator median
+    FluxAggregator,
+    /// Pyth price account aggregate price
+    Pyth,
+    /// Time-weighted price of a token-swap constant product pool
+    TokenSwap,
+}
+
+impl From<OracleType> for OracleTypeExport {
+    fn from(oracle_type: OracleType) -> Self {
+        match oracle_type {
+            OracleType::FluxAggregator => OracleTypeExport::FluxAggregator,
+            OracleType::Pyth => OracleTypeExport::Pyth,
+            OracleType::TokenSwap => OracleTypeExport::TokenSwap,
+        }
+    }
+}
+
+/// Exported reserve collateral
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveCollateralExport {
+    /// Reserve collateral mint address
+    pub mint_pubkey: String,
+    /// Reserve collateral mint supply
+    pub mint_total_supply: String,
+    /// Reserve collateral supply address
+    pub supply_pubkey: String,
+    /// Debt backed by this collateral while isolated, for each isolated borrow reserve, as
+    /// borrowed liquidity wads divided by that reserve's cumulative borrow rate
+    pub isolated_borrowed_scaled_wads: Vec<String>,
+}
+
+impl From<&ReserveCollateral> for ReserveCollateralExport {
+    fn from(collateral: &ReserveCollateral) -> Self {
+        Self {
+            mint_pubkey: collateral.mint_pubkey.to_string(),
+            mint_total_supply: collateral.mint_total_supply.to_string(),
+            supply_pubkey: collateral.supply_pubkey.to_string(),
+            isolated_borrowed_scaled_wads: collateral
+                .isolated_borrowed_scaled_wads
+                .iter()
+                .map(|scaled_wads| scaled_wads.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Exported reserve configuration values, percentages are whole numbers
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveConfigExport {
+    /// Optimal utilization rate, as a percentage
+    pub optimal_utilization_rate: u8,
+    /// Target ratio of the value of borrows to deposits, as a percentage
+    pub loan_to_value_ratio: u8,
+    /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage
+    pub liquidation_bonus: u8,
+    /// Loan to value ratio at which an obligation can be liquidated, as a percentage
+    pub liquidation_threshold: u8,
+    /// Min borrow APY, as a percentage
+    pub min_borrow_rate: u8,
+    /// Optimal (utilization) borrow APY, as a percentage
+    pub optimal_borrow_rate: u8,
+    /// Max borrow APY, as a percentage
+    pub max_borrow_rate: u8,
+    /// Fee assessed on borrows, as a Wad
+    pub borrow_fee_wad: String,
+    /// Fee assessed on flash loans, as a Wad
+    pub flash_loan_fee_wad: String,
+    /// Share of fees going to the host account, as a percentage
+    pub host_fee_percentage: u8,
+    /// Maximum age of the reserve liquidity oracle price, in seconds
+    pub max_oracle_age_seconds: String,
+    /// Share of accrued interest set aside for the lending market owner, as a percentage
+    pub protocol_take_rate: u8,
+    /// Borrow rate model
+    pub rate_model: RateModelExport,
+    /// Whether obligations depositing this collateral are isolated
+    pub isolated: bool,
+    /// Max market value of debt backed by this collateral while isolated, in quote currency
+    pub isolated_debt_ceiling: String,
+    /// Reserves that can be borrowed from against this collateral while isolated
+    pub isolated_borrow_reserves: Vec<String>,
+    /// Max total supply of liquidity, 0 if deposits are not limited
+    pub deposit_limit: String,
+    /// Max total borrowed liquidity, 0 if borrows are not limited
+    pub borrow_limit: String,
+    /// Percentage of an obligation borrow that can be repaid during each liquidation call
+    pub liquidation_close_factor: u8,
+    /// Number of slots over which the liquidation bonus grows to the configured bonus
+    pub liquidation_auction_slots: String,
+}
+
+impl From<&ReserveConfig> for ReserveConfigExport {
+    fn from(config: &ReserveConfig) -> Self {
+        Self {
+            optimal_utilization_rate: config.optimal_utilization_rate,
+            loan_to_value_ratio: config.loan_to_value_ratio,
+            liquidation_bonus: config.liquidation_bonus,
+            liquidation_threshold: config.liquidation_threshold,
+            min_borrow_rate: config.min_borrow_rate,
+            optimal_borrow_rate: config.optimal_borrow_rate,
+            max_borrow_rate: config.max_borrow_rate,
+            borrow_fee_wad: config.fees.borrow_fee_wad.to_string(),
+            flash_loan_fee_wad: config.fees.flash_loan_fee_wad.to_string(),
+            host_fee_percentage: config.fees.host_fee_percentage,
+            max_oracle_age_seconds: config.max_oracle_age_seconds.to_string(),
+            protocol_take_rate: config.protocol_take_rate,
+            rate_model: RateModelExport::from(&config.rate_model),
+            isolated: config.isolated,
+            isolated_debt_ceiling: config.isolated_debt_ceiling.to_string(),
+            isolated_borrow_reserves: config
+                .isolated_borrow_reserves
+                .iter()
+                .filter_map(optional_pubkey)
+                .collect(),
+            deposit_limit: config.deposit_limit.to_string(),
+            borrow_limit: config.borrow_limit.to_string(),
+            liquidation_close_factor: config.liquidation_close_factor,
+            liquidation_auction_slots: config.liquidation_auction_slots.to_string(),
+        }
+    }
+}
+
+/// Exported point on a multi-kink borrow rate curve
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateModelKinkExport {
+    /// Utilization rate, in basis points
+    pub utilization_bps: u16,
+    /// Borrow APY at the utilization rate, in basis points
+    pub borrow_rate_bps: u16,
+}
+
+/// Exported borrow rate model, tagged by `type`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RateModelExport {
+    /// Two linear segments through the min, optimal and max borrow rates
+    TwoSlope,
+    /// Linear segments between kinks
+    MultiKink {
+        /// Kinks sorted by utilization rate
+        kinks: Vec<RateModelKinkExport>,
+    },
+    /// Constant borrow rate
+    #[serde(rename_all = "camelCase")]
+    Fixed {
+        /// Borrow APY, in basis points
+        borrow_rate_bps: u16,
+    },
+}
+
+impl From<&RateModel> for RateModelExport {
+    fn from(rate_model: &RateModel) -> Self {
+        match rate_model {
+            RateModel::TwoSlope => RateModelExport::TwoSlope,
+            RateModel::MultiKink { kink_count, kinks } => RateModelExport::MultiKink {
+                kinks: kinks
+                    .iter()
+                    .take(*kink_count as usize)
+                    .map(|kink| RateModelKinkExport {
+                        utilization_bps: kink.utilization_bps,
+                        borrow_rate_bps: kink.borrow_rate_bps,
+                    })
+                    .collect(),
+            },
+            RateModel::Fixed { borrow_rate_bps } => RateModelExport::Fixed {
+                borrow_rate_bps: *borrow_rate_bps,
+            },
+        }
+    }
+}
+
+/// Exported obligation state, with derived loan to value ratio
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObligationExport {
+    /// Obligation address
+    pub pubkey: String,
+    /// Version of the struct
+    pub version: u8,
+    /// Last update to collateral, liquidity, or their market values
+    pub last_update: LastUpdateExport,
+    /// Lending market address
+    pub lending_market: String,
+    /// Owner authority which can borrow liquidity
+    pub owner: String,
+    /// Deposited collateral for the obligation
+    pub deposits: Vec<ObligationCollateralExport>,
+    /// Borrowed liquidity for the obligation
+    pub borrows: Vec<ObligationLiquidityExport>,
+    /// Market value of deposits
+    pub deposited_value: String,
+    /// Market value of borrows
+    pub borrowed_value: String,
+    /// The maximum borrow value at the weighted average loan to value ratio
+    pub allowed_borrow_value: String,
+    /// The dangerous borrow value at the weighted average liquidation threshold
+    pub unhealthy_borrow_value: String,
+    /// Ratio of borrowed value to deposited value, `null` without deposits
+    pub loan_to_value: Option<String>,
+    /// Isolated collateral reserve the obligation is restricted to
+    pub isolated_collateral_reserve: Option<String>,
+    /// Slot at which the obligation was first refreshed while unhealthy, 0 if healthy
+    pub unhealthy_since_slot: String,
+    /// Delegate authority which can act for the owner
+    pub delegate: Option<String>,
+    /// Bitmask of operations the delegate is permitted to perform
+    pub delegate_permissions: u8,
+    /// Max total market value the delegate can borrow, 0 if the delegate cannot borrow
+    pub delegate_borrow_limit: String,
+    /// Market value borrowed by the delegate since it was set
+    pub delegate_borrowed_value: String,
+}
+
+impl ObligationExport {
+    /// Export an obligation and calculate its loan to value ratio
+    pub fn new(pubkey: &Pubkey, obligation: &Obligation) -> Result<Self, ProgramError> {
+        let loan_to_value = if obligation.deposited_value == Decimal::zero() {
+            None
+        } else {
+            Some(obligation.loan_to_value()?.to_string())
+        };
+
+        Ok(Self {
+            pubkey: pubkey.to_string(),
+            version: obligation.version,
+            last_update: LastUpdateExport::from(&obligation.last_update),
+            lending_market: obligation.lending_market.to_string(),
+            owner: obligation.owner.to_string(),
+            deposits: obligation.deposits.iter().map(Into::into).collect(),
+            borrows: obligation.borrows.iter().map(Into::into).collect(),
+            deposited_value: obligation.deposited_value.to_string(),
+            borrowed_value: obligation.borrowed_value.to_string(),
+            allowed_borrow_value: obligation.allowed_borrow_value.to_string(),
+            unhealthy_borrow_value: obligation.unhealthy_borrow_value.to_string(),
+            loan_to_value,
+            isolated_collateral_reserve: coption_pubkey(&obligation.isolated_collateral_reserve),
+            unhealthy_since_slot: obligation.unhealthy_since_slot.to_string(),
+            delegate: optional_pubkey(&obligation.delegate),
+            delegate_permissions: obligation.delegate_permissions,
+            delegate_borrow_limit: obligation.delegate_borrow_limit.to_string(),
+            delegate_borrowed_value: obligation.delegate_borrowed_value.to_string(),
+        })
+    }
+}
+
+/// Exported obligation collateral
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObligationCollateralExport {
+    /// Reserve collateral is deposited to
+    pub deposit_reserve: String,
+    /// Amount of collateral deposited
+    pub deposited_amount: String,
+    /// Collateral market value in quote currency
+    pub market_value: String,
+}
+
+impl From<&ObligationCollateral> for ObligationCollateralExport {
+    fn from(collateral: &ObligationCollateral) -> Self {
+        Self {
+            deposit_reserve: collateral.deposit_reserve.to_string(),
+            deposited_amount: collateral.deposited_amount.to_string(),
+            market_value: collateral.market_value.to_string(),
+        }
+    }
+}
+
+/// Exported obligation liquidity
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObligationLiquidityExport {
+    /// Reserve liquidity is borrowed from
+    pub borrow_reserve: String,
+    /// Borrow rate used for calculating interest
+    pub cumulative_borrow_rate_wads: String,
+    /// Amount of liquidity borrowed plus interest
+    pub borrowed_amount_wads: String,
+    /// Liquidity market value in quote currency
+    pub market_value: String,
+}
+
+impl From<&ObligationLiquidity> for ObligationLiquidityExport {
+    fn from(liquidity: &ObligationLiquidity) -> Self {
+        Self {
+            borrow_reserve: liquidity.borrow_reserve.to_string(),
+            cumulative_borrow_rate_wads: liquidity.cumulative_borrow_rate_wads.to_string(),
+            borrowed_amount_wads: liquidity.borrowed_amount_wads.to_string(),
+            market_value: liquidity.market_value.to_string(),
+        }
+    }
+}
+
+/// Export a lending market from a set of fetched program accounts, such as the result of
+/// `getProgramAccounts`. Reserves and obligations are told apart by their data length, and
+/// accounts of other lending markets or that are not initialized are skipped.
+pub fn export_market(
+    lending_market_pubkey: &Pubkey,
+    accounts: &[(Pubkey, &[u8])],
+) -> Result<MarketExport, ProgramError> {
+    let lending_market_data = accounts
+        .iter()
+        .find(|(pubkey, _)| pubkey == lending_market_pubkey)
+        .map(|(_, data)| *data)
+        .ok_or_else(|| {
+            msg!("Lending market account was not provided");
+            ProgramError::NotEnoughAccountKeys
+        })?;
+    let lending_market = LendingMarket::unpack(lending_market_data)?;
+
+    let mut reserves = vec![];
+    let mut obligations = vec![];
+    for (pubkey, data) in accounts {
+        if Reserve::has_rate_history(data.len()).is_ok() {
+            let reserve = Reserve::unpack_unchecked(data)?;
+            if reserve.is_initialized() && reserve.lending_market == *lending_market_pubkey {
+                reserves.push(ReserveExport::new(pubkey, &reserve)?);
+            }
+        } else if Obligation::max_reserves_for_len(data.len()).is_ok() {
+            let obligation = Obligation::unpack_unchecked(data)?;
+            if obligation.is_initialized() && obligation.lending_market == *lending_market_pubkey {
+                obligations.push(ObligationExport::new(pubkey, &obligation)?);
+            }
+        }
+    }
+
+    Ok(MarketExport {
+        export_version: EXPORT_VERSION,
+        lending_market: LendingMarketExport::new(lending_market_pubkey, &lending_market),
+        reserves,
+        obligations,
+    })
+}
+
+/// Render a lending market from a set of fetched program accounts as a JSON string
+pub fn render_market(
+    lending_market_pubkey: &Pubkey,
+    accounts: &[(Pubkey, &[u8])],
+) -> Result<String, ProgramError> {
+    let market = export_market(lending_market_pubkey, accounts)?;
+    serde_json::to_string(&market).map_err(|_| {
+        msg!("Lending market cannot be serialized");
+        ProgramError::InvalidAccountData
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{InitLendingMarketParams, PROGRAM_VERSION, RATE_HISTORY_LEN};
+    use serde_json::{json, Value};
+    use spl_token_swap::twap::PRICE_ACCUMULATORS_LEN;
+
+    fn pack<T: Pack>(state: T, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        T::pack(state, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn export_market_accounts() {
+        let lending_market_pubkey = Pubkey::new_unique();
+        let lending_market = LendingMarket::new(InitLendingMarketParams {
+            bump_seed: 255,
+            owner: Pubkey::new_unique(),
+            quote_token_mint: Pubkey::new_unique(),
+            token_program_id: spl_token::id(),
+            stale_after_slots_elapsed: 1,
+        });
+
+        let reserve_pubkey = Pubkey::new_unique();
+        let mut reserve = Reserve {
+            version: PROGRAM_VERSION,
+            lending_market: lending_market_pubkey,
+            liquidity: ReserveLiquidity {
+                mint_decimals: 6,
+                available_amount: 750,
+                borrowed_amount_wads: Decimal::from(250u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                market_price: 1,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 2_000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                rate_model: RateModel::Fixed {
+                    borrow_rate_bps: 1_000,
+                },
+                protocol_take_rate: 20,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        reserve.record_rate_snapshot(100).unwrap();
+        let legacy_reserve_pubkey = Pubkey::new_unique();
+        let legacy_reserve = reserve.clone();
+        let other_reserve = Reserve {
+            version: PROGRAM_VERSION,
+            lending_market: Pubkey::new_unique(),
+            ..reserve.clone()
+        };
+
+        let obligation_pubkey = Pubkey::new_unique();
+        let obligation = Obligation {
+            version: PROGRAM_VERSION,
+            lending_market: lending_market_pubkey,
+            owner: Pubkey::new_unique(),
+            deposited_value: Decimal::from(300u64),
+            borrowed_value: Decimal::from(100u64),
+            ..Obligation::default()
+        };
+
+        let lending_market_data = pack(lending_market, LendingMarket::LEN);
+        let reserve_data = pack(reserve, Reserve::LEN);
+        let legacy_reserve_data = pack(
+            legacy_reserve,
+            Reserve::LEN - RATE_HISTORY_LEN - PRICE_ACCUMULATORS_LEN,
+        );
+        let other_reserve_data = pack(other_reserve, Reserve::LEN);
+        let obligation_data = pack(obligation, Obligation::LEN);
+        let accounts = [
+            (lending_market_pubkey, lending_market_data.as_slice()),
+            (reserve_pubkey, reserve_data.as_slice()),
+            (legacy_reserve_pubkey, legacy_reserve_data.as_slice()),
+            (Pubkey::new_unique(), other_reserve_data.as_slice()),
+            (obligation_pubkey, obligation_data.as_slice()),
+        ];
+
+        let market: Value =
+            serde_json::from_str(&render_market(&lending_market_pubkey, &accounts).unwrap())
+                .unwrap();
+        assert_eq!(market["exportVersion"], json!(EXPORT_VERSION));
+        assert_eq!(market["lendingMarket"]["guardian"], Value::Null);
+        assert_eq!(market["reserves"].as_array().unwrap().len(), 2);
+        assert_eq!(market["obligations"].as_array().unwrap().len(), 1);
+
+        let reserve = &market["reserves"][0];
+        assert_eq!(reserve["pubkey"], json!(reserve_pubkey.to_string()));
+        assert_eq!(
+            reserve["liquidity"]["borrowedAmountWads"],
+            json!("250.000000000000000000")
+        );
+        assert_eq!(reserve["utilizationRate"], json!("0.250000000000000000"));
+        assert_eq!(reserve["borrowApy"], json!("0.100000000000000000"));
+        // 10% borrow APY * 25% utilization * 80% after the protocol take
+        assert_eq!(reserve["supplyApy"], json!("0.020000000000000000"));
+        assert_eq!(
+            reserve["collateralExchangeRate"],
+            json!("2.000000000000000000")
+        );
+        assert_eq!(
+            reserve["config"]["rateModel"],
+            json!({ "type": "fixed", "borrowRateBps": 1_000 })
+        );
+        assert_eq!(
+            reserve["rateHistory"],
+            json!([{
+                "slot": "100",
+                "cumulativeBorrowRateWads": "1.000000000000000000",
+                "collateralExchangeRate": "2.000000000000000000",
+            }])
+        );
+
+        // reserves created without room for rate history are exported with an empty history
+        let legacy_reserve = &market["reserves"][1];
+        assert_eq!(
+            legacy_reserve["pubkey"],
+            json!(legacy_reserve_pubkey.to_string())
+        );
+        assert_eq!(legacy_reserve["rateHistory"], json!([]));
+
+        let obligation = &market["obligations"][0];
+        assert_eq!(obligation["pubkey"], json!(obligation_pubkey.to_string()));
+        assert_eq!(obligation["loanToValue"], json!("0.333333333333333333"));
+        assert_eq!(obligation["delegate"], Value::Null);
+    }
+
+    #[test]
+    fn export_market_missing_lending_market() {
+        assert_eq!(
+            export_market(&Pubkey::new_unique(), &[]),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+}
@@ -2,7 +2,12 @@
 
 use crate::{
     error::LendingError,
-    state::{ReserveConfig, ReserveFees},
+    oracle::OracleType,
+    state::{
+        RateModel, ReserveConfig, ReserveFees, DEFAULT_MAX_ORACLE_AGE_SECONDS,
+        LIQUIDATION_CLOSE_FACTOR, MAX_ISOLATED_BORROW_RESERVES, RATE_MODEL_LEN,
+        STALE_AFTER_SLOTS_ELAPSED,
+    },
 };
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -11,7 +16,14 @@ use solana_program::{
     pubkey::{Pubkey, PUBKEY_BYTES},
     sysvar,
 };
-use std::{convert::TryInto, mem::size_of};
+use std::{
+    convert::{TryFrom, TryInto},
+    mem::size_of,
+};
+
+/// Length of the reserve config of `InitReserve` as packed by clients from before the fields
+/// after the host fee percentage were added
+const ORIGINAL_RESERVE_CONFIG_LEN: usize = 16;
 
 /// Instructions supported by the lending program.
 #[derive(Clone, Debug, PartialEq)]
@@ -28,6 +40,9 @@ pub enum LendingInstruction {
     InitLendingMarket {
         /// Owner authority which can add new reserves
         owner: Pubkey,
+        /// Number of slots after which refreshed reserves and obligations are considered stale,
+        /// `STALE_AFTER_SLOTS_ELAPSED` if the instruction data ends before it
+        stale_after_slots_elapsed: u64,
     },
 
     // 1
@@ -67,11 +82,16 @@ pub enum LendingInstruction {
     ///   15 `[optional]` Reserve liquidity oracle account.
     ///                     Not required for quote currency reserves.
     ///                     Must match base and quote currency mint, and quote currency decimals.
+    ///   16 `[optional]` Token swap pool token A account, for token swap oracles.
+    ///   17 `[optional]` Token swap pool token B account, for token swap oracles.
     InitReserve {
         /// Initial amount of liquidity to deposit into the new reserve
         liquidity_amount: u64,
-        /// Reserve configuration values
+        /// Reserve configuration values. If the instruction data ends after the host fee
+        /// percentage, the fields added since are defaulted as for zeroed reserve state.
         config: ReserveConfig,
+        /// Reserve liquidity oracle type, a flux aggregator if the instruction data ends before it
+        oracle_type: OracleType,
     },
 
     // 3
@@ -84,6 +104,8 @@ pub enum LendingInstruction {
     ///   2. `[optional]` Reserve liquidity oracle account.
     ///                     Required if the reserve currency is not the lending market quote
     ///                     currency.
+    ///   3. `[optional]` Token swap pool token A account, for token swap oracles.
+    ///   4. `[optional]` Token swap pool token B account, for token swap oracles.
     RefreshReserve,
 
     // 4
@@ -130,7 +152,9 @@ pub enum LendingInstruction {
     },
 
     // 6
-    /// Initializes a new lending market obligation.
+    /// Initializes a new lending market obligation. The obligation account is `Obligation::LEN`
+    /// bytes for up to `MAX_OBLIGATION_RESERVES` deposits and borrows combined, or
+    /// `Obligation::packed_len(max_reserves)` bytes for up to `MAX_EXTENDED_OBLIGATION_RESERVES`.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -150,7 +174,8 @@ pub enum LendingInstruction {
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Obligation account.
-    ///   1. `[]` Clock sysvar.
+    ///   1. `[]` Lending market account.
+    ///   2. `[]` Clock sysvar.
     ///   .. `[]` Collateral deposit reserve accounts - refreshed, all, in order.
     ///   .. `[]` Liquidity borrow reserve accounts - refreshed, all, in order.
     RefreshObligation,
@@ -168,7 +193,7 @@ pub enum LendingInstruction {
     ///   3. `[writable]` Obligation account.
     ///   4. `[]` Lending market account.
     ///   5. `[]` Derived lending market authority.
-    ///   6. `[signer]` Obligation owner.
+    ///   6. `[signer]` Obligation owner, or delegate with deposit permission.
     ///   7. `[signer]` User transfer authority ($authority).
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
@@ -185,11 +210,12 @@ pub enum LendingInstruction {
     ///   0. `[writable]` Source withdraw reserve collateral supply SPL Token account.
     ///   1. `[writable]` Destination collateral token account.
     ///                     Minted by withdraw reserve collateral mint.
+    ///                     Owned by the obligation owner if the delegate signs.
     ///   2. `[]` Withdraw reserve account - refreshed.
     ///   3. `[writable]` Obligation account - refreshed.
     ///   4. `[]` Lending market account.
     ///   5. `[]` Derived lending market authority.
-    ///   6. `[signer]` Obligation owner.
+    ///   6. `[signer]` Obligation owner, or delegate with withdraw permission.
     ///   7. `[]` Clock sysvar.
     ///   8. `[]` Token program id.
     WithdrawObligationCollateral {
@@ -207,16 +233,22 @@ pub enum LendingInstruction {
     ///   0. `[writable]` Source borrow reserve liquidity supply SPL Token account.
     ///   1. `[writable]` Destination liquidity token account.
     ///                     Minted by borrow reserve liquidity mint.
+    ///                     Owned by the obligation owner if the delegate signs.
     ///   2. `[writable]` Borrow reserve account - refreshed.
     ///   3. `[writable]` Borrow reserve liquidity fee receiver account.
     ///                     Must be the fee account specified at InitReserve.
     ///   4. `[writable]` Obligation account - refreshed.
     ///   5. `[]` Lending market account.
     ///   6. `[]` Derived lending market authority.
-    ///   7. `[signer]` Obligation owner.
+    ///   7. `[signer]` Obligation owner, or delegate with borrow permission.
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
-    ///   10 `[optional, writable]` Host fee receiver account.
+    ///   10 `[writable]` Isolated collateral reserve account.
+    ///                     Only if the obligation has isolated collateral.
+    ///   .. `[]` Isolated borrow reserve accounts - refreshed, all configured on the isolated
+    ///                     collateral reserve other than the borrow reserve, in config order.
+    ///                     Only if the obligation has isolated collateral.
+    ///   .. `[optional, writable]` Host fee receiver account.
     BorrowObligationLiquidity {
         /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
         liquidity_amount: u64,
@@ -235,10 +267,11 @@ pub enum LendingInstruction {
     ///   2. `[writable]` Repay reserve account - refreshed.
     ///   3. `[writable]` Obligation account - refreshed.
     ///   4. `[]` Lending market account.
-    ///   5. `[]` Derived lending market authority.
-    ///   6. `[signer]` User transfer authority ($authority).
-    ///   7. `[]` Clock sysvar.
-    ///   8. `[]` Token program id.
+    ///   5. `[signer]` User transfer authority ($authority).
+    ///   6. `[]` Clock sysvar.
+    ///   7. `[]` Token program id.
+    ///   8. `[writable]` Isolated collateral reserve account.
+    ///                     Only if the obligation has isolated collateral.
     RepayObligationLiquidity {
         /// Amount of liquidity to repay - u64::MAX for 100% of borrowed amount
         liquidity_amount: u64,
@@ -257,7 +290,7 @@ pub enum LendingInstruction {
     ///                     Minted by withdraw reserve collateral mint.
     ///   2. `[writable]` Repay reserve account - refreshed.
     ///   3. `[writable]` Repay reserve liquidity supply SPL Token account.
-    ///   4. `[]` Withdraw reserve account - refreshed.
+    ///   4. `[writable]` Withdraw reserve account - refreshed.
     ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account.
     ///   6. `[writable]` Obligation account - refreshed.
     ///   7. `[]` Lending market account.
@@ -269,6 +302,236 @@ pub enum LendingInstruction {
         /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
         liquidity_amount: u64,
     },
+
+    // 13
+    /// Make a flash loan: lend reserve liquidity to a receiver program, which must return the
+    /// liquidity plus the flash loan fee to the reserve liquidity supply before the instruction
+    /// completes.
+    ///
+    /// The flash loan receiver program is invoked with instruction data `[0, repay_amount]`, where
+    /// `repay_amount` is a little-endian u64 of the amount lent plus the fee, and these accounts:
+    ///
+    ///   0. `[writable]` Destination liquidity token account.
+    ///   1. `[writable]` Source reserve liquidity supply SPL Token account.
+    ///   2. `[]` Token program id.
+    ///   .. Additional accounts passed to this instruction, in order.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source reserve liquidity supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Token program id.
+    ///   6. `[]` Flash loan receiver program id.
+    ///             Must not be the lending program.
+    ///   .. `[any]` Additional accounts expected by the flash loan receiver program.
+    FlashLoan {
+        /// Amount of liquidity to flash loan - u64::MAX for 100% of available liquidity
+        liquidity_amount: u64,
+    },
+
+    // 14
+    /// Update the configuration of a reserve. Interest is accrued at the current rates before the
+    /// new configuration is applied.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    ///   3. `[]` Clock sysvar.
+    ///   4. `[optional]` Reserve liquidity oracle account.
+    ///                     Required if the reserve currency is not the lending market quote
+    ///                     currency.
+    ///   5. `[optional]` Token swap pool token A account, for token swap oracles.
+    ///   6. `[optional]` Token swap pool token B account, for token swap oracles.
+    SetReserveConfig {
+        /// Reserve configuration values
+        config: ReserveConfig,
+    },
+
+    // 15
+    /// Withdraw the whole amount of accumulated protocol fees from a reserve to its liquidity fee
+    /// receiver, up to the liquidity available. Requires a refreshed reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account - refreshed.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Clock sysvar.
+    ///   6. `[]` Token program id.
+    WithdrawProtocolFees,
+
+    // 16
+    /// Set the pause flags of a lending market. Repayments cannot be paused.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner or guardian.
+    SetMarketPauseFlags {
+        /// Bitmask of paused operations
+        pause_flags: u8,
+    },
+
+    // 17
+    /// Sets the guardian of a lending market, which can set pause flags alongside the owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetLendingMarketGuardian {
+        /// The new guardian, default pubkey to remove the guardian
+        new_guardian: Pubkey,
+    },
+
+    // 18
+    /// Liquidate an unhealthy obligation like `LiquidateObligation`, then redeem the collateral
+    /// received for withdraw reserve liquidity, up to the liquidity available. Any collateral left
+    /// over is transferred to the destination collateral account. Requires a refreshed obligation
+    /// and reserves.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///   2. `[writable]` Repay reserve account - refreshed.
+    ///   3. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Withdraw reserve account - refreshed.
+    ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   6. `[writable]` Obligation account - refreshed.
+    ///   7. `[]` Lending market account.
+    ///   8. `[]` Derived lending market authority.
+    ///   9. `[signer]` User transfer authority ($authority).
+    ///   10 `[]` Clock sysvar.
+    ///   11 `[]` Token program id.
+    ///   12 `[writable]` Destination liquidity token account.
+    ///                     Minted by withdraw reserve liquidity mint.
+    ///   13 `[writable]` Withdraw reserve collateral SPL Token mint.
+    ///   14 `[writable]` Withdraw reserve liquidity supply SPL Token account.
+    LiquidateObligationAndRedeemReserveCollateral {
+        /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
+        liquidity_amount: u64,
+    },
+
+    // 19
+    /// Deposit liquidity into a reserve and the collateral received into an obligation, like
+    /// `DepositReserveLiquidity` followed by `DepositObligationCollateral`. The reserve is
+    /// refreshed by this instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Reserve account.
+    ///   2. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   3. `[writable]` Reserve collateral SPL Token mint.
+    ///   4. `[writable]` Reserve collateral supply SPL Token account.
+    ///   5. `[writable]` Obligation account.
+    ///   6. `[]` Lending market account.
+    ///   7. `[]` Derived lending market authority.
+    ///   8. `[signer]` Obligation owner, or delegate with deposit permission.
+    ///   9. `[signer]` User transfer authority ($authority).
+    ///   10 `[]` Clock sysvar.
+    ///   11 `[]` Token program id.
+    ///   12 `[optional]` Reserve liquidity oracle account(s), as for `RefreshReserve`.
+    DepositReserveLiquidityAndObligationCollateral {
+        /// Amount of liquidity to deposit in exchange for collateral tokens
+        liquidity_amount: u64,
+    },
+
+    // 20
+    /// Withdraw collateral from an obligation and redeem it for reserve liquidity, like
+    /// `WithdrawObligationCollateral` followed by `RedeemReserveCollateral`. The reserve is
+    /// refreshed by this instruction. Requires a refreshed obligation if it has borrows.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve collateral supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Owned by the obligation owner if the delegate signs.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[writable]` Reserve collateral SPL Token mint.
+    ///   4. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   5. `[writable]` Obligation account - refreshed if it has borrows.
+    ///   6. `[]` Lending market account.
+    ///   7. `[]` Derived lending market authority.
+    ///   8. `[signer]` Obligation owner, or delegate with withdraw permission.
+    ///   9. `[]` Clock sysvar.
+    ///   10 `[]` Token program id.
+    ///   11 `[optional]` Reserve liquidity oracle account(s), as for `RefreshReserve`.
+    WithdrawObligationCollateralAndRedeemReserveCollateral {
+        /// Amount of collateral tokens to withdraw - u64::MAX for up to 100% of deposited amount
+        collateral_amount: u64,
+    },
+
+    // 21
+    /// Sets the delegate of an obligation, which can deposit, withdraw or borrow for the owner
+    /// depending on its permissions. Repayments do not require an obligation authority. Tokens the
+    /// delegate withdraws or borrows must go to the owner, and setting the delegate resets the
+    /// value it has borrowed towards its borrow limit.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer]` Obligation owner.
+    SetObligationDelegate {
+        /// The new delegate, default pubkey to remove the delegate
+        delegate: Pubkey,
+        /// Bitmask of operations the delegate is permitted to perform
+        permissions: u8,
+        /// Max total market value the delegate can borrow, in the smallest unit of the quote
+        /// currency, 0 if the delegate cannot borrow
+        borrow_limit: u64,
+    },
+
+    // 22
+    /// Sets the new owner of an obligation and removes its delegate.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer]` Current obligation owner.
+    SetObligationOwner {
+        /// The new owner, cannot be the default pubkey
+        new_owner: Pubkey,
+    },
+
+    // 23
+    /// Sets the number of slots after which refreshed reserves and obligations are considered
+    /// stale.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetLendingMarketStaleWindow {
+        /// Number of slots after which refreshed reserves and obligations are considered stale
+        stale_after_slots_elapsed: u64,
+    },
+
+    // 24
+    /// Sets the program that owns the Pyth price accounts new reserves can be priced by.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetLendingMarketPythProgram {
+        /// Pyth oracle program id
+        pyth_program_id: Pubkey,
+    },
 }
 
 impl LendingInstruction {
@@ -279,8 +542,17 @@ impl LendingInstruction {
             .ok_or(LendingError::InstructionUnpackError)?;
         Ok(match tag {
             0 => {
-                let (owner, _rest) = Self::unpack_pubkey(rest)?;
-                Self::InitLendingMarket { owner }
+                let (owner, rest) = Self::unpack_pubkey(rest)?;
+                // data packed by clients from before the stale window was added ends here
+                let (stale_after_slots_elapsed, _rest) = if rest.is_empty() {
+                    (STALE_AFTER_SLOTS_ELAPSED, rest)
+                } else {
+                    Self::unpack_u64(rest)?
+                };
+                Self::InitLendingMarket {
+                    owner,
+                    stale_after_slots_elapsed,
+                }
             }
             1 => {
                 let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
@@ -288,30 +560,20 @@ impl LendingInstruction {
             }
             2 => {
                 let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
-                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
-                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
-                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (host_fee_percentage, _rest) = Self::unpack_u8(rest)?;
+                // data packed by clients from before the config was extended and oracle types
+                // were added ends after the original config
+                let (config, oracle_type) = if rest.len() == ORIGINAL_RESERVE_CONFIG_LEN {
+                    let (config, _rest) = Self::unpack_original_reserve_config(rest)?;
+                    (config, OracleType::default())
+                } else {
+                    let (config, rest) = Self::unpack_reserve_config(rest)?;
+                    let (oracle_type, _rest) = Self::unpack_u8(rest)?;
+                    (config, OracleType::try_from(oracle_type)?)
+                };
                 Self::InitReserve {
                     liquidity_amount,
-                    config: ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees: ReserveFees {
-                            borrow_fee_wad,
-                            host_fee_percentage,
-                        },
-                    },
+                    config,
+                    oracle_type,
                 }
             }
             3 => Self::RefreshReserve,
@@ -345,6 +607,59 @@ impl LendingInstruction {
                 let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
                 Self::LiquidateObligation { liquidity_amount }
             }
+            13 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashLoan { liquidity_amount }
+            }
+            14 => {
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
+                Self::SetReserveConfig { config }
+            }
+            15 => Self::WithdrawProtocolFees,
+            16 => {
+                let (pause_flags, _rest) = Self::unpack_u8(rest)?;
+                Self::SetMarketPauseFlags { pause_flags }
+            }
+            17 => {
+                let (new_guardian, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetLendingMarketGuardian { new_guardian }
+            }
+            18 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount }
+            }
+            19 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount }
+            }
+            20 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount }
+            }
+            21 => {
+                let (delegate, rest) = Self::unpack_pubkey(rest)?;
+                let (permissions, rest) = Self::unpack_u8(rest)?;
+                let (borrow_limit, _rest) = Self::unpack_u64(rest)?;
+                Self::SetObligationDelegate {
+                    delegate,
+                    permissions,
+                    borrow_limit,
+                }
+            }
+            22 => {
+                let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetObligationOwner { new_owner }
+            }
+            23 => {
+                let (stale_after_slots_elapsed, _rest) = Self::unpack_u64(rest)?;
+                Self::SetLendingMarketStaleWindow {
+                    stale_after_slots_elapsed,
+                }
+            }
+            24 => {
+                let (pyth_program_id, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetLendingMarketPythProgram { pyth_program_id }
+            }
             _ => {
                 msg!("Instruction cannot be unpacked");
                 return Err(LendingError::InstructionUnpackError.into());
@@ -352,6 +667,107 @@ impl LendingInstruction {
         })
     }
 
+    /// Unpacks the fields of the original reserve config layout, defaulting the fields added
+    /// since as for zeroed reserve state
+    fn unpack_original_reserve_config(
+        input: &[u8],
+    ) -> Result<(ReserveConfig, &[u8]), ProgramError> {
+        let (optimal_utilization_rate, rest) = Self::unpack_u8(input)?;
+        let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+        let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
+        let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
+        let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
+        let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
+        let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+        Ok((
+            ReserveConfig {
+                optimal_utilization_rate,
+                loan_to_value_ratio,
+                liquidation_bonus,
+                liquidation_threshold,
+                min_borrow_rate,
+                optimal_borrow_rate,
+                max_borrow_rate,
+                fees: ReserveFees {
+                    borrow_fee_wad,
+                    host_fee_percentage,
+                    ..ReserveFees::default()
+                },
+                max_oracle_age_seconds: DEFAULT_MAX_ORACLE_AGE_SECONDS,
+                liquidation_close_factor: LIQUIDATION_CLOSE_FACTOR,
+                ..ReserveConfig::default()
+            },
+            rest,
+        ))
+    }
+
+    fn unpack_reserve_config(input: &[u8]) -> Result<(ReserveConfig, &[u8]), ProgramError> {
+        // fields added after the original layout are appended
+        let (original, rest) = Self::unpack_original_reserve_config(input)?;
+        let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
+        let (max_oracle_age_seconds, rest) = Self::unpack_u64(rest)?;
+        let (protocol_take_rate, rest) = Self::unpack_u8(rest)?;
+        let (rate_model, rest) = Self::unpack_rate_model(rest)?;
+        let (isolated, rest) = Self::unpack_u8(rest)?;
+        let isolated = match isolated {
+            0 => false,
+            1 => true,
+            _ => {
+                msg!("Isolated flag cannot be unpacked");
+                return Err(LendingError::InstructionUnpackError.into());
+            }
+        };
+        let (isolated_debt_ceiling, mut rest) = Self::unpack_u64(rest)?;
+        let mut isolated_borrow_reserves = [Pubkey::default(); MAX_ISOLATED_BORROW_RESERVES];
+        for borrow_reserve in isolated_borrow_reserves.iter_mut() {
+            let (pubkey, remaining) = Self::unpack_pubkey(rest)?;
+            *borrow_reserve = pubkey;
+            rest = remaining;
+        }
+        let (deposit_limit, rest) = Self::unpack_u64(rest)?;
+        let (borrow_limit, rest) = Self::unpack_u64(rest)?;
+        let (liquidation_close_factor, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_auction_slots, rest) = Self::unpack_u64(rest)?;
+        Ok((
+            ReserveConfig {
+                fees: ReserveFees {
+                    flash_loan_fee_wad,
+                    ..original.fees
+                },
+                max_oracle_age_seconds,
+                protocol_take_rate,
+                rate_model,
+                isolated,
+                isolated_debt_ceiling,
+                isolated_borrow_reserves,
+                deposit_limit,
+                borrow_limit,
+                liquidation_close_factor,
+                liquidation_auction_slots,
+                ..original
+            },
+            rest,
+        ))
+    }
+
+    fn unpack_rate_model(input: &[u8]) -> Result<(RateModel, &[u8]), ProgramError> {
+        if input.len() < RATE_MODEL_LEN {
+            msg!("Rate model cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (rate_model, rest) = input.split_at(RATE_MODEL_LEN);
+        let rate_model = rate_model
+            .try_into()
+            .map_err(|_| LendingError::InstructionUnpackError)
+            .and_then(|rate_model| {
+                RateModel::unpack_from_slice(rate_model)
+                    .map_err(|_| LendingError::InstructionUnpackError)
+            })?;
+        Ok((rate_model, rest))
+    }
+
     fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
         if input.len() < 8 {
             msg!("u64 cannot be unpacked");
@@ -394,9 +810,13 @@ impl LendingInstruction {
     pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
         match *self {
-            Self::InitLendingMarket { owner } => {
+            Self::InitLendingMarket {
+                owner,
+                stale_after_slots_elapsed,
+            } => {
                 buf.push(0);
                 buf.extend_from_slice(owner.as_ref());
+                buf.extend_from_slice(&stale_after_slots_elapsed.to_le_bytes());
             }
             Self::SetLendingMarketOwner { new_owner } => {
                 buf.push(1);
@@ -404,33 +824,13 @@ impl LendingInstruction {
             }
             Self::InitReserve {
                 liquidity_amount,
-                config:
-                    ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees:
-                            ReserveFees {
-                                borrow_fee_wad,
-                                host_fee_percentage,
-                            },
-                    },
+                config,
+                oracle_type,
             } => {
                 buf.push(2);
                 buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-                buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
-                buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
-                buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
-                buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
+                Self::pack_reserve_config(config, &mut buf);
+                buf.extend_from_slice(&(oracle_type as u8).to_le_bytes());
             }
             Self::RefreshReserve => {
                 buf.push(3);
@@ -469,9 +869,116 @@ impl LendingInstruction {
                 buf.push(12);
                 buf.extend_from_slice(&liquidity_amount.to_le_bytes());
             }
+            Self::FlashLoan { liquidity_amount } => {
+                buf.push(13);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::SetReserveConfig { config } => {
+                buf.push(14);
+                Self::pack_reserve_config(config, &mut buf);
+            }
+            Self::WithdrawProtocolFees => {
+                buf.push(15);
+            }
+            Self::SetMarketPauseFlags { pause_flags } => {
+                buf.push(16);
+                buf.extend_from_slice(&pause_flags.to_le_bytes());
+            }
+            Self::SetLendingMarketGuardian { new_guardian } => {
+                buf.push(17);
+                buf.extend_from_slice(new_guardian.as_ref());
+            }
+            Self::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount } => {
+                buf.push(18);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
+                buf.push(19);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount } => {
+                buf.push(20);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::SetObligationDelegate {
+                delegate,
+                permissions,
+                borrow_limit,
+            } => {
+                buf.push(21);
+                buf.extend_from_slice(delegate.as_ref());
+                buf.extend_from_slice(&permissions.to_le_bytes());
+                buf.extend_from_slice(&borrow_limit.to_le_bytes());
+            }
+            Self::SetObligationOwner { new_owner } => {
+                buf.push(22);
+                buf.extend_from_slice(new_owner.as_ref());
+            }
+            Self::SetLendingMarketStaleWindow {
+                stale_after_slots_elapsed,
+            } => {
+                buf.push(23);
+                buf.extend_from_slice(&stale_after_slots_elapsed.to_le_bytes());
+            }
+            Self::SetLendingMarketPythProgram { pyth_program_id } => {
+                buf.push(24);
+                buf.extend_from_slice(pyth_program_id.as_ref());
+            }
         }
         buf
     }
+
+    fn pack_reserve_config(config: ReserveConfig, buf: &mut Vec<u8>) {
+        let ReserveConfig {
+            optimal_utilization_rate,
+            loan_to_value_ratio,
+            liquidation_bonus,
+            liquidation_threshold,
+            min_borrow_rate,
+            optimal_borrow_rate,
+            max_borrow_rate,
+            fees:
+                ReserveFees {
+                    borrow_fee_wad,
+                    flash_loan_fee_wad,
+                    host_fee_percentage,
+                },
+            max_oracle_age_seconds,
+            protocol_take_rate,
+            rate_model,
+            isolated,
+            isolated_debt_ceiling,
+            isolated_borrow_reserves,
+            deposit_limit,
+            borrow_limit,
+            liquidation_close_factor,
+            liquidation_auction_slots,
+        } = config;
+        buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
+        buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
+        buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
+        buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
+        buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
+        buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
+        buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
+        buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
+        buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
+        buf.extend_from_slice(&flash_loan_fee_wad.to_le_bytes());
+        buf.extend_from_slice(&max_oracle_age_seconds.to_le_bytes());
+        buf.extend_from_slice(&protocol_take_rate.to_le_bytes());
+        let mut packed_rate_model = [0u8; RATE_MODEL_LEN];
+        rate_model.pack_into_slice(&mut packed_rate_model);
+        buf.extend_from_slice(&packed_rate_model);
+        buf.push(isolated as u8);
+        buf.extend_from_slice(&isolated_debt_ceiling.to_le_bytes());
+        for borrow_reserve in isolated_borrow_reserves.iter() {
+            buf.extend_from_slice(borrow_reserve.as_ref());
+        }
+        buf.extend_from_slice(&deposit_limit.to_le_bytes());
+        buf.extend_from_slice(&borrow_limit.to_le_bytes());
+        buf.extend_from_slice(&liquidation_close_factor.to_le_bytes());
+        buf.extend_from_slice(&liquidation_auction_slots.to_le_bytes());
+    }
 }
 
 /// Creates an 'InitLendingMarket' instruction.
@@ -480,6 +987,7 @@ pub fn init_lending_market(
     lending_market_pubkey: Pubkey,
     lending_market_owner: Pubkey,
     quote_token_mint: Pubkey,
+    stale_after_slots_elapsed: u64,
 ) -> Instruction {
     Instruction {
         program_id,
@@ -491,6 +999,7 @@ pub fn init_lending_market(
         ],
         data: LendingInstruction::InitLendingMarket {
             owner: lending_market_owner,
+            stale_after_slots_elapsed,
         }
         .pack(),
     }
@@ -519,6 +1028,7 @@ pub fn init_reserve(
     program_id: Pubkey,
     liquidity_amount: u64,
     config: ReserveConfig,
+    oracle_type: OracleType,
     source_liquidity_pubkey: Pubkey,
     destination_collateral_pubkey: Pubkey,
     reserve_pubkey: Pubkey,
@@ -531,7 +1041,7 @@ pub fn init_reserve(
     lending_market_pubkey: Pubkey,
     lending_market_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
-    reserve_liquidity_oracle_pubkey: Option<Pubkey>,
+    reserve_liquidity_oracle_pubkeys: impl IntoIterator<Item = Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -555,18 +1065,18 @@ pub fn init_reserve(
         AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
-    if let Some(reserve_liquidity_oracle_pubkey) = reserve_liquidity_oracle_pubkey {
-        accounts.push(AccountMeta::new_readonly(
-            reserve_liquidity_oracle_pubkey,
-            false,
-        ));
-    }
+    accounts.extend(
+        reserve_liquidity_oracle_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
     Instruction {
         program_id,
         accounts,
         data: LendingInstruction::InitReserve {
             liquidity_amount,
             config,
+            oracle_type,
         }
         .pack(),
     }
@@ -576,18 +1086,17 @@ pub fn init_reserve(
 pub fn refresh_reserve(
     program_id: Pubkey,
     reserve_pubkey: Pubkey,
-    reserve_liquidity_oracle_pubkey: Option<Pubkey>,
+    reserve_liquidity_oracle_pubkeys: impl IntoIterator<Item = Pubkey>,
 ) -> Instruction {
     let mut accounts = vec![
         AccountMeta::new(reserve_pubkey, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
     ];
-    if let Some(reserve_liquidity_oracle_pubkey) = reserve_liquidity_oracle_pubkey {
-        accounts.push(AccountMeta::new_readonly(
-            reserve_liquidity_oracle_pubkey,
-            false,
-        ));
-    }
+    accounts.extend(
+        reserve_liquidity_oracle_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
     Instruction {
         program_id,
         accounts,
@@ -692,10 +1201,12 @@ pub fn init_obligation(
 pub fn refresh_obligation(
     program_id: Pubkey,
     obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
     reserve_pubkeys: Vec<Pubkey>,
 ) -> Instruction {
     let mut accounts = vec![
         AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
     ];
     accounts.extend(
@@ -790,6 +1301,8 @@ pub fn borrow_obligation_liquidity(
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
+    isolated_collateral_reserve_pubkey: Option<Pubkey>,
+    isolated_borrow_reserve_pubkeys: Vec<Pubkey>,
     host_fee_receiver_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
@@ -808,6 +1321,14 @@ pub fn borrow_obligation_liquidity(
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    if let Some(isolated_collateral_reserve_pubkey) = isolated_collateral_reserve_pubkey {
+        accounts.push(AccountMeta::new(isolated_collateral_reserve_pubkey, false));
+    }
+    accounts.extend(
+        isolated_borrow_reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
     if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
         accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
     }
@@ -829,34 +1350,202 @@ pub fn repay_obligation_liquidity(
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    isolated_collateral_reserve_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(repay_reserve_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(isolated_collateral_reserve_pubkey) = isolated_collateral_reserve_pubkey {
+        accounts.push(AccountMeta::new(isolated_collateral_reserve_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `LiquidateObligation` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
     Instruction {
         program_id,
         accounts: vec![
             AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
             AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
+        data: LendingInstruction::LiquidateObligation { liquidity_amount }.pack(),
     }
 }
 
-/// Creates a `LiquidateObligation` instruction
+/// Creates a `FlashLoan` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn liquidate_obligation(
+pub fn flash_loan(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+    ];
+    accounts.extend(flash_loan_receiver_program_accounts);
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::FlashLoan { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `SetReserveConfig` instruction
+pub fn set_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    reserve_liquidity_oracle_pubkeys: impl IntoIterator<Item = Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(
+        reserve_liquidity_oracle_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::SetReserveConfig { config }.pack(),
+    }
+}
+
+/// Creates a `WithdrawProtocolFees` instruction
+pub fn withdraw_protocol_fees(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::WithdrawProtocolFees.pack(),
+    }
+}
+
+/// Creates a 'SetMarketPauseFlags' instruction.
+pub fn set_market_pause_flags(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    pause_authority: Pubkey,
+    pause_flags: u8,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(pause_authority, true),
+        ],
+        data: LendingInstruction::SetMarketPauseFlags { pause_flags }.pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketGuardian' instruction.
+pub fn set_lending_market_guardian(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    new_guardian: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketGuardian { new_guardian }.pack(),
+    }
+}
+
+/// Creates a `LiquidateObligationAndRedeemReserveCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation_and_redeem_reserve_collateral(
     program_id: Pubkey,
     liquidity_amount: u64,
     source_liquidity_pubkey: Pubkey,
     destination_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
     repay_reserve_pubkey: Pubkey,
     repay_reserve_liquidity_supply_pubkey: Pubkey,
     withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_mint_pubkey: Pubkey,
     withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
@@ -872,7 +1561,7 @@ pub fn liquidate_obligation(
             AccountMeta::new(destination_collateral_pubkey, false),
             AccountMeta::new(repay_reserve_pubkey, false),
             AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_pubkey, false),
             AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
@@ -880,7 +1569,276 @@ pub fn liquidate_obligation(
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
         ],
-        data: LendingInstruction::LiquidateObligation { liquidity_amount }.pack(),
+        data: LendingInstruction::LiquidateObligationAndRedeemReserveCollateral {
+            liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `DepositReserveLiquidityAndObligationCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_reserve_liquidity_and_obligation_collateral(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+    reserve_liquidity_oracle_pubkeys: impl IntoIterator<Item = Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(
+        reserve_liquidity_oracle_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
+            liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `WithdrawObligationCollateralAndRedeemReserveCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_obligation_collateral_and_redeem_reserve_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    reserve_collateral_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    reserve_liquidity_oracle_pubkeys: impl IntoIterator<Item = Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new(reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(
+        reserve_liquidity_oracle_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
+            collateral_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetObligationDelegate` instruction
+pub fn set_obligation_delegate(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner: Pubkey,
+    delegate: Pubkey,
+    permissions: u8,
+    borrow_limit: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner, true),
+        ],
+        data: LendingInstruction::SetObligationDelegate {
+            delegate,
+            permissions,
+            borrow_limit,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetObligationOwner` instruction
+pub fn set_obligation_owner(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner: Pubkey,
+    new_owner: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner, true),
+        ],
+        data: LendingInstruction::SetObligationOwner { new_owner }.pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketStaleWindow' instruction.
+pub fn set_lending_market_stale_window(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    stale_after_slots_elapsed: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketStaleWindow {
+            stale_after_slots_elapsed,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketPythProgram' instruction.
+pub fn set_lending_market_pyth_program(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    pyth_program_id: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketPythProgram { pyth_program_id }.pack(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unpack_init_lending_market_without_stale_window() {
+        // clients from before the stale window was added pack only the owner
+        let owner = Pubkey::new_unique();
+        let mut data = vec![0];
+        data.extend_from_slice(owner.as_ref());
+        assert_eq!(
+            LendingInstruction::unpack(&data).unwrap(),
+            LendingInstruction::InitLendingMarket {
+                owner,
+                stale_after_slots_elapsed: STALE_AFTER_SLOTS_ELAPSED,
+            }
+        );
+
+        let instruction = LendingInstruction::InitLendingMarket {
+            owner,
+            stale_after_slots_elapsed: 10,
+        };
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.pack()).unwrap(),
+            instruction
+        );
+    }
+
+    #[test]
+    fn unpack_init_reserve_original_layout() {
+        // clients from before the config was extended pack it up to the host fee percentage,
+        // without an oracle type
+        let mut data = vec![2];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&[80, 50, 5, 55, 0, 4, 30]);
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.push(20);
+        let config = ReserveConfig {
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            liquidation_threshold: 55,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 30,
+            fees: ReserveFees {
+                borrow_fee_wad: 100,
+                flash_loan_fee_wad: 0,
+                host_fee_percentage: 20,
+            },
+            max_oracle_age_seconds: DEFAULT_MAX_ORACLE_AGE_SECONDS,
+            liquidation_close_factor: LIQUIDATION_CLOSE_FACTOR,
+            ..ReserveConfig::default()
+        };
+        assert_eq!(
+            LendingInstruction::unpack(&data).unwrap(),
+            LendingInstruction::InitReserve {
+                liquidity_amount: 1_000,
+                config,
+                oracle_type: OracleType::FluxAggregator,
+            }
+        );
+
+        // the current layout round trips, and cannot be cut short
+        let instruction = LendingInstruction::InitReserve {
+            liquidity_amount: 1_000,
+            config: ReserveConfig {
+                protocol_take_rate: 10,
+                liquidation_auction_slots: 100,
+                ..config
+            },
+            oracle_type: OracleType::Pyth,
+        };
+        let data = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&data).unwrap(), instruction);
+        assert_eq!(
+            LendingInstruction::unpack(&data[..data.len() - 1]).unwrap_err(),
+            LendingError::InstructionUnpackError.into()
+        );
+
+        // set reserve config never had an original layout
+        let data = LendingInstruction::SetReserveConfig { config }.pack();
+        assert_eq!(
+            LendingInstruction::unpack(&data[..1 + ORIGINAL_RESERVE_CONFIG_LEN]).unwrap_err(),
+            LendingError::InstructionUnpackError.into()
+        );
     }
 }
@@ -4,9 +4,13 @@
 
 pub mod entrypoint;
 pub mod error;
+#[cfg(feature = "export")]
+pub mod export;
 pub mod instruction;
 pub mod math;
+pub mod oracle;
 pub mod processor;
+pub mod simulation;
 pub mod state;
 
 // Export current sdk types for downstream users building with a different sdk version
@@ -61,6 +61,11 @@ impl Rate {
         Self(U128::from(percent as u64 * PERCENT_SCALER))
     }
 
+    /// Create scaled decimal from basis points value
+    pub fn from_bps(bps: u16) -> Self {
+        Self(U128::from(bps as u64 * BPS_SCALER))
+    }
+
     /// Return raw scaled value
     #[allow(clippy::wrong_self_convention)]
     pub fn to_scaled_val(&self) -> u128 {
@@ -196,4 +201,11 @@ mod test {
     fn checked_pow() {
         assert_eq!(Rate::one(), Rate::one().try_pow(u64::MAX).unwrap());
     }
+
+    #[test]
+    fn from_bps() {
+        assert_eq!(Rate::from_bps(10_000), Rate::one());
+        assert_eq!(Rate::from_bps(500), Rate::from_percent(5));
+        assert_eq!(Rate::from_bps(235).to_string(), "0.023500000000000000");
+    }
 }
@@ -0,0 +1,36 @@
+use crate::error::LendingError;
+use flux_aggregator::{borsh_state::InitBorshState, read_median, state::Aggregator};
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError,
+};
+
+/// Validate a flux aggregator for a new reserve
+pub fn validate_flux_aggregator(
+    aggregator_info: &AccountInfo,
+    quote_decimals: u8,
+) -> ProgramResult {
+    let aggregator = Aggregator::load_initialized(aggregator_info)?;
+    if aggregator.config.decimals != quote_decimals {
+        msg!("Quote token mint decimals does not match the aggregator config decimals provided");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    Ok(())
+}
+
+/// Get the median of the latest flux aggregator answer
+pub fn get_flux_aggregator_price(
+    aggregator_info: &AccountInfo,
+    max_age_seconds: u64,
+    clock: &Clock,
+) -> Result<u64, ProgramError> {
+    let answer = read_median(aggregator_info)?;
+
+    let answer_age = (clock.unix_timestamp as u64).saturating_sub(answer.updated_at);
+    if answer_age > max_age_seconds {
+        msg!("Flux aggregator answer is too old");
+        return Err(LendingError::OracleStale.into());
+    }
+
+    Ok(answer.median)
+}
@@ -0,0 +1,123 @@
+//! Price oracle adapters for reserve liquidity
+
+mod flux;
+mod pyth;
+mod token_swap;
+
+pub use flux::*;
+pub use pyth::*;
+pub use token_swap::*;
+
+use crate::{error::LendingError, state::ReserveLiquidity};
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, pubkey::Pubkey,
+};
+use spl_token_swap::twap::PriceAccumulators;
+use std::convert::TryFrom;
+
+/// Kind of oracle account used to price reserve liquidity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OracleType {
+    /// Flux aggregator median
+    FluxAggregator,
+    /// Pyth price account aggregate price
+    Pyth,
+    /// Time-weighted price of a token-swap constant product pool paired with the quote currency,
+    /// averaged from the pool's price accumulators
+    TokenSwap,
+}
+
+impl Default for OracleType {
+    fn default() -> Self {
+        OracleType::FluxAggregator
+    }
+}
+
+impl TryFrom<u8> for OracleType {
+    type Error = ProgramError;
+
+    fn try_from(oracle_type: u8) -> Result<Self, Self::Error> {
+        match oracle_type {
+            0 => Ok(OracleType::FluxAggregator),
+            1 => Ok(OracleType::Pyth),
+            2 => Ok(OracleType::TokenSwap),
+            _ => {
+                msg!("Oracle type cannot be unpacked");
+                Err(LendingError::InvalidOracleConfig.into())
+            }
+        }
+    }
+}
+
+/// Validate an oracle account for a new reserve
+pub fn validate_oracle(
+    oracle_type: OracleType,
+    oracle_info: &AccountInfo,
+    liquidity_mint_pubkey: &Pubkey,
+    quote_token_mint_pubkey: &Pubkey,
+    quote_decimals: u8,
+    pyth_program_id: &Pubkey,
+) -> ProgramResult {
+    match oracle_type {
+        OracleType::FluxAggregator => validate_flux_aggregator(oracle_info, quote_decimals),
+        OracleType::Pyth => validate_pyth_price(oracle_info, pyth_program_id),
+        OracleType::TokenSwap => {
+            validate_token_swap(oracle_info, liquidity_mint_pubkey, quote_token_mint_pubkey)
+        }
+    }
+}
+
+/// Validate the max oracle age of a reserve priced by an oracle of `oracle_type`
+pub fn validate_max_oracle_age(oracle_type: OracleType, max_age_seconds: u64) -> ProgramResult {
+    match oracle_type {
+        OracleType::FluxAggregator | OracleType::Pyth => Ok(()),
+        OracleType::TokenSwap => validate_token_swap_max_age(max_age_seconds),
+    }
+}
+
+/// Get the market price of reserve liquidity from its oracle, in quote currency. Each adapter
+/// checks its own staleness and confidence and fails if the price should not be trusted.
+/// Time-weighted token swap prices average the pool price since the reserve's earlier observation
+/// of its accumulators, which must be no older than the max age; otherwise the observation is
+/// restarted and `None` is returned, as the reserve has no price until a full window later.
+pub fn get_oracle_price<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    liquidity: &ReserveLiquidity,
+    max_age_seconds: u64,
+    oracle_info: &'a AccountInfo<'b>,
+    account_info_iter: &mut I,
+    price_observation: &mut PriceAccumulators,
+    clock: &Clock,
+) -> Result<Option<u64>, ProgramError> {
+    let market_price = match liquidity.oracle_type {
+        OracleType::FluxAggregator => {
+            get_flux_aggregator_price(oracle_info, max_age_seconds, clock)?
+        }
+        OracleType::Pyth => get_pyth_price(
+            oracle_info,
+            liquidity.quote_decimals,
+            max_age_seconds,
+            clock,
+        )?,
+        OracleType::TokenSwap => match get_token_swap_price(
+            oracle_info,
+            account_info_iter,
+            &liquidity.mint_pubkey,
+            liquidity.mint_decimals,
+            liquidity.market_price,
+            max_age_seconds,
+            price_observation,
+            clock,
+        )? {
+            Some(market_price) => market_price,
+            None => return Ok(None),
+        },
+    };
+
+    if market_price == 0 {
+        msg!("Oracle price cannot be zero");
+        return Err(LendingError::InvalidOraclePrice.into());
+    }
+
+    Ok(Some(market_price))
+}
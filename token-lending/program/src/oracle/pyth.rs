@@ -0,0 +1,291 @@
+use crate::error::LendingError;
+use arrayref::{array_ref, array_refs};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::{Clock, Slot, DEFAULT_MS_PER_SLOT},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+/// Maximum Pyth confidence interval, as a percentage of the aggregate price
+pub const PYTH_MAX_CONFIDENCE_PERCENT: u8 = 2;
+
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_VERSION: u32 = 2;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+
+// Length of the price account up to and including the aggregate price, the fields that are read
+const PYTH_PRICE_LEN: usize = 240; // 4 + 4 + 4 + 4 + 4 + 4 + 4 + 4 + 8 + 8 + 24 + 24 + 8 + 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 4 + 4 + 8
+
+/// Pyth price account fields used to price reserve liquidity
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PythPrice {
+    /// Price exponent
+    pub expo: i32,
+    /// Aggregate price
+    pub price: i64,
+    /// Aggregate confidence interval
+    pub conf: u64,
+    /// Aggregate price status
+    pub status: u32,
+    /// Slot when the aggregate price was published
+    pub pub_slot: u64,
+}
+
+impl PythPrice {
+    /// Unpack a Pyth price account
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < PYTH_PRICE_LEN {
+            msg!("Pyth price account data is too short");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        let input = array_ref![data, 0, PYTH_PRICE_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            magic,
+            version,
+            account_type,
+            _size,
+            _price_type,
+            expo,
+            _num,
+            _num_qt,
+            _last_slot,
+            _valid_slot,
+            _twap,
+            _twac,
+            _drv1,
+            _drv2,
+            _product,
+            _next,
+            _prev_slot,
+            _prev_price,
+            _prev_conf,
+            _drv3,
+            price,
+            conf,
+            status,
+            _corp_act,
+            pub_slot,
+        ) = array_refs![
+            input, 4, 4, 4, 4, 4, 4, 4, 4, 8, 8, 24, 24, 8, 8, 32, 32, 8, 8, 8, 8, 8, 8, 4, 4, 8
+        ];
+
+        if u32::from_le_bytes(*magic) != PYTH_MAGIC {
+            msg!("Pyth price account magic number is invalid");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        if u32::from_le_bytes(*version) != PYTH_VERSION {
+            msg!("Pyth price account version is not supported");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        if u32::from_le_bytes(*account_type) != PYTH_ACCOUNT_TYPE_PRICE {
+            msg!("Pyth account provided is not a price account");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+
+        Ok(Self {
+            expo: i32::from_le_bytes(*expo),
+            price: i64::from_le_bytes(*price),
+            conf: u64::from_le_bytes(*conf),
+            status: u32::from_le_bytes(*status),
+            pub_slot: u64::from_le_bytes(*pub_slot),
+        })
+    }
+
+    /// Check the aggregate price status, age and confidence, and scale it to the quote currency
+    /// decimals
+    pub fn market_price(
+        &self,
+        quote_decimals: u8,
+        max_age_seconds: u64,
+        current_slot: Slot,
+    ) -> Result<u64, ProgramError> {
+        if self.status != PYTH_STATUS_TRADING {
+            msg!("Pyth aggregate price is not trading");
+            return Err(LendingError::InvalidOraclePrice.into());
+        }
+
+        // Pyth publishes by slot, so the age is estimated from the target slot duration
+        let slots_elapsed = current_slot.checked_sub(self.pub_slot).ok_or_else(|| {
+            msg!("Pyth aggregate price publish slot is ahead of the current slot");
+            LendingError::InvalidOraclePrice
+        })?;
+        let age_seconds = slots_elapsed
+            .checked_mul(DEFAULT_MS_PER_SLOT)
+            .ok_or(LendingError::MathOverflow)?
+            / 1_000;
+        if age_seconds > max_age_seconds {
+            msg!("Pyth aggregate price is too old");
+            return Err(LendingError::OracleStale.into());
+        }
+
+        let price = u64::try_from(self.price).map_err(|_| {
+            msg!("Pyth aggregate price cannot be negative");
+            LendingError::InvalidOraclePrice
+        })?;
+
+        let max_conf = (price as u128)
+            .checked_mul(PYTH_MAX_CONFIDENCE_PERCENT as u128)
+            .and_then(|value| value.checked_div(100))
+            .ok_or(LendingError::MathOverflow)?;
+        if self.conf as u128 > max_conf {
+            msg!("Pyth confidence interval is too wide");
+            return Err(LendingError::InvalidOraclePrice.into());
+        }
+
+        let exponent = (quote_decimals as i32)
+            .checked_add(self.expo)
+            .ok_or(LendingError::MathOverflow)?;
+        let scale = 10u64
+            .checked_pow(exponent.unsigned_abs())
+            .ok_or(LendingError::MathOverflow)?;
+        let market_price = if exponent >= 0 {
+            price.checked_mul(scale)
+        } else {
+            price.checked_div(scale)
+        }
+        .ok_or(LendingError::MathOverflow)?;
+
+        Ok(market_price)
+    }
+}
+
+/// Validate a Pyth price account for a new reserve. Only the Pyth program can close its price
+/// accounts, so the owner does not need to be checked again when the price is read.
+pub fn validate_pyth_price(price_info: &AccountInfo, pyth_program_id: &Pubkey) -> ProgramResult {
+    if *pyth_program_id == Pubkey::default() {
+        msg!("Lending market Pyth program is not set");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if price_info.owner != pyth_program_id {
+        msg!("Pyth price account is not owned by the lending market Pyth program");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    PythPrice::unpack(&price_info.data.borrow())?;
+    Ok(())
+}
+
+/// Get the Pyth aggregate price scaled to the quote currency decimals
+pub fn get_pyth_price(
+    price_info: &AccountInfo,
+    quote_decimals: u8,
+    max_age_seconds: u64,
+    clock: &Clock,
+) -> Result<u64, ProgramError> {
+    PythPrice::unpack(&price_info.data.borrow())?.market_price(
+        quote_decimals,
+        max_age_seconds,
+        clock.slot,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pyth_price_data(expo: i32, price: i64, conf: u64, status: u32, pub_slot: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 3312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&PYTH_VERSION.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[216..224].copy_from_slice(&conf.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        data[232..240].copy_from_slice(&pub_slot.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn unpack_pyth_price() {
+        let data = pyth_price_data(-8, 2_000_000_000, 1_000_000, PYTH_STATUS_TRADING, 100);
+        assert_eq!(
+            PythPrice::unpack(&data).unwrap(),
+            PythPrice {
+                expo: -8,
+                price: 2_000_000_000,
+                conf: 1_000_000,
+                status: PYTH_STATUS_TRADING,
+                pub_slot: 100,
+            }
+        );
+
+        let mut data = data;
+        data[0] = 0;
+        assert_eq!(
+            PythPrice::unpack(&data).unwrap_err(),
+            LendingError::InvalidOracleConfig.into()
+        );
+        assert_eq!(
+            PythPrice::unpack(&[0u8; 8]).unwrap_err(),
+            LendingError::InvalidOracleConfig.into()
+        );
+    }
+
+    #[test]
+    fn pyth_market_price() {
+        // $20.00000000 with 8 decimals, scaled to 6 quote decimals
+        let pyth_price = PythPrice {
+            expo: -8,
+            price: 2_000_000_000,
+            conf: 1_000_000,
+            status: PYTH_STATUS_TRADING,
+            pub_slot: 100,
+        };
+        assert_eq!(pyth_price.market_price(6, 10, 100).unwrap(), 20_000_000);
+        assert_eq!(
+            pyth_price.market_price(10, 10, 101).unwrap(),
+            200_000_000_000
+        );
+
+        // too old
+        assert_eq!(
+            pyth_price
+                .market_price(6, 10, 100 + 28) // 28 slots is 11.2 seconds
+                .unwrap_err(),
+            LendingError::OracleStale.into()
+        );
+
+        // published after the current slot
+        assert_eq!(
+            pyth_price.market_price(6, 10, 99).unwrap_err(),
+            LendingError::InvalidOraclePrice.into()
+        );
+
+        // not trading
+        let halted_price = PythPrice {
+            status: 2,
+            ..pyth_price.clone()
+        };
+        assert_eq!(
+            halted_price.market_price(6, 10, 100).unwrap_err(),
+            LendingError::InvalidOraclePrice.into()
+        );
+
+        // confidence interval wider than 2% of price
+        let unconfident_price = PythPrice {
+            conf: 40_000_001,
+            ..pyth_price.clone()
+        };
+        assert_eq!(
+            unconfident_price.market_price(6, 10, 100).unwrap_err(),
+            LendingError::InvalidOraclePrice.into()
+        );
+
+        // negative price
+        let negative_price = PythPrice {
+            price: -1,
+            conf: 0,
+            ..pyth_price
+        };
+        assert_eq!(
+            negative_price.market_price(6, 10, 100).unwrap_err(),
+            LendingError::InvalidOraclePrice.into()
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use crate::error::LendingError;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::{Clock, UnixTimestamp},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Account;
+use spl_token_swap::{
+    curve::base::CurveType,
+    state::{SwapState, SwapVersion},
+    twap::{time_weighted_average_price, PriceAccumulators, PRICE_SCALE},
+};
+use std::convert::TryFrom;
+
+/// Minimum number of seconds between the two observations of a token-swap pool's price
+/// accumulators that a time-weighted price is computed from
+pub const TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS: UnixTimestamp = 300;
+
+/// Validate a token-swap pool for a new reserve
+pub fn validate_token_swap(
+    swap_info: &AccountInfo,
+    liquidity_mint_pubkey: &Pubkey,
+    quote_token_mint_pubkey: &Pubkey,
+) -> ProgramResult {
+    let token_swap = unpack_token_swap(swap_info)?;
+    if token_swap.swap_curve().curve_type != CurveType::ConstantProduct {
+        msg!("Token swap pool must use a constant product curve");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if token_swap.price_accumulators().is_none() {
+        msg!("Token swap pool must record cumulative prices");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    let token_a_mint = token_swap.token_a_mint();
+    let token_b_mint = token_swap.token_b_mint();
+    if !(token_a_mint == liquidity_mint_pubkey && token_b_mint == quote_token_mint_pubkey
+        || token_a_mint == quote_token_mint_pubkey && token_b_mint == liquidity_mint_pubkey)
+    {
+        msg!("Token swap pool must pair the reserve liquidity mint with the quote token mint");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    Ok(())
+}
+
+/// Observe a token-swap pool's price accumulators up to the current time. The next two accounts
+/// must be the pool's token A and token B accounts; they are checked but their balances are not
+/// read, since anyone can move them by transferring tokens to the pool, and the accumulators are
+/// extended from the reserves the pool recorded at its last update instead.
+pub fn observe_token_swap<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    swap_info: &AccountInfo,
+    account_info_iter: &mut I,
+    clock: &Clock,
+) -> Result<PriceAccumulators, ProgramError> {
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+
+    let token_swap = unpack_token_swap(swap_info)?;
+    if token_swap.token_a_account() != token_a_info.key {
+        msg!("Token A account provided does not match the token swap pool");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if token_swap.token_b_account() != token_b_info.key {
+        msg!("Token B account provided does not match the token swap pool");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let price_accumulators = token_swap.price_accumulators().ok_or_else(|| {
+        msg!("Token swap pool does not record cumulative prices");
+        LendingError::InvalidOracleConfig
+    })?;
+
+    unpack_pool_token_account(token_a_info)?;
+    unpack_pool_token_account(token_b_info)?;
+    Ok(price_accumulators.observe(clock.unix_timestamp))
+}
+
+/// Get the time-weighted price of a token-swap pool between the reserve's earlier observation of
+/// its price accumulators and the current one. Once the minimum window has elapsed, the current
+/// observation replaces the earlier one and the new average is returned, otherwise the previous
+/// average is kept. If the earlier observation is older than `max_age_seconds`, the average would
+/// span too long a window and the previous average is too old to keep, so the observation is
+/// restarted and `None` is returned. The next two accounts must be the pool's token A and token B
+/// accounts.
+#[allow(clippy::too_many_arguments)]
+pub fn get_token_swap_price<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    swap_info: &AccountInfo,
+    account_info_iter: &mut I,
+    liquidity_mint_pubkey: &Pubkey,
+    liquidity_mint_decimals: u8,
+    previous_price: u64,
+    max_age_seconds: u64,
+    price_observation: &mut PriceAccumulators,
+    clock: &Clock,
+) -> Result<Option<u64>, ProgramError> {
+    let token_swap = unpack_token_swap(swap_info)?;
+    let liquidity_is_token_a = token_swap.token_a_mint() == liquidity_mint_pubkey;
+    let current_observation = observe_token_swap(swap_info, account_info_iter, clock)?;
+
+    update_token_swap_price(
+        liquidity_is_token_a,
+        liquidity_mint_decimals,
+        previous_price,
+        max_age_seconds,
+        price_observation,
+        current_observation,
+    )
+}
+
+/// Replace the reserve's earlier observation of a token-swap pool's price accumulators with the
+/// current one and return the market price, as described in `get_token_swap_price`
+pub fn update_token_swap_price(
+    liquidity_is_token_a: bool,
+    liquidity_mint_decimals: u8,
+    previous_price: u64,
+    max_age_seconds: u64,
+    price_observation: &mut PriceAccumulators,
+    current_observation: PriceAccumulators,
+) -> Result<Option<u64>, ProgramError> {
+    let seconds_elapsed = current_observation
+        .last_update_timestamp
+        .saturating_sub(price_observation.last_update_timestamp);
+    let max_age_seconds = UnixTimestamp::try_from(max_age_seconds).unwrap_or(UnixTimestamp::MAX);
+    if seconds_elapsed > max_age_seconds {
+        msg!("Token swap pool observation is older than the max oracle age and was restarted");
+        *price_observation = current_observation;
+        return Ok(None);
+    }
+    if seconds_elapsed < TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS {
+        if previous_price == 0 {
+            msg!("Token swap pool has not been observed for the minimum price window");
+            return Err(LendingError::InvalidOraclePrice.into());
+        }
+        // the previous average was taken when the earlier observation was, so it is no older
+        // than the max age
+        return Ok(Some(previous_price));
+    }
+
+    let average_price = time_weighted_average_price(price_observation, &current_observation)
+        .ok_or(LendingError::MathOverflow)?;
+    let liquidity_price = if liquidity_is_token_a {
+        average_price.token_a_price
+    } else {
+        average_price.token_b_price
+    };
+    *price_observation = current_observation;
+
+    calculate_market_price(liquidity_price, liquidity_mint_decimals).map(Some)
+}
+
+/// Validate the max oracle age of a reserve priced by a token-swap pool, which must leave room to
+/// refresh the reserve after each minimum price window before its observation is too old
+pub fn validate_token_swap_max_age(max_age_seconds: u64) -> ProgramResult {
+    if max_age_seconds < 2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS as u64 {
+        msg!(
+            "Max oracle age of a token swap reserve must be at least {} seconds",
+            2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS
+        );
+        return Err(LendingError::InvalidConfig.into());
+    }
+    Ok(())
+}
+
+/// Convert a Q64.64 price of one liquidity token unit in quote token units to the price of one
+/// whole liquidity token, rounded to the nearest quote token unit
+pub fn calculate_market_price(
+    liquidity_price: u128,
+    liquidity_mint_decimals: u8,
+) -> Result<u64, ProgramError> {
+    let scale = 10u128
+        .checked_pow(liquidity_mint_decimals as u32)
+        .ok_or(LendingError::MathOverflow)?;
+    // split the price so the scaled value fits in a u128
+    let whole = (liquidity_price / PRICE_SCALE)
+        .checked_mul(scale)
+        .ok_or(LendingError::MathOverflow)?;
+    let fraction = (liquidity_price % PRICE_SCALE)
+        .checked_mul(scale)
+        .and_then(|value| value.checked_add(PRICE_SCALE / 2))
+        .ok_or(LendingError::MathOverflow)?
+        / PRICE_SCALE;
+    let market_price = whole
+        .checked_add(fraction)
+        .ok_or(LendingError::MathOverflow)?;
+
+    u64::try_from(market_price).map_err(|_| LendingError::MathOverflow.into())
+}
+
+fn unpack_token_swap(swap_info: &AccountInfo) -> Result<Box<dyn SwapState>, ProgramError> {
+    if swap_info.owner != &spl_token_swap::id() {
+        msg!("Token swap pool is not owned by the token swap program");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+    if !token_swap.is_initialized() {
+        msg!("Token swap pool is not initialized");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    Ok(token_swap)
+}
+
+fn unpack_pool_token_account(token_account_info: &AccountInfo) -> Result<Account, ProgramError> {
+    if token_account_info.owner != &spl_token::id() {
+        msg!("Token swap pool token account is not owned by the token program");
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+    Account::unpack(&token_account_info.data.borrow())
+        .map_err(|_| LendingError::InvalidTokenAccount.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_swap_market_price() {
+        // 1_000 SOL (9 decimals) against 20_000 USDC (6 decimals)
+        let liquidity_price = (20_000_000_000u128 << 64) / 1_000_000_000_000;
+        assert_eq!(
+            calculate_market_price(liquidity_price, 9).unwrap(),
+            20_000_000
+        );
+        assert_eq!(calculate_market_price(PRICE_SCALE / 2, 0).unwrap(), 1);
+        assert_eq!(calculate_market_price(PRICE_SCALE / 2 - 1, 0).unwrap(), 0);
+        assert_eq!(
+            calculate_market_price(u128::MAX, 9).unwrap_err(),
+            LendingError::MathOverflow.into()
+        );
+    }
+
+    #[test]
+    fn token_swap_time_weighted_market_price() {
+        // 1 SOL = 20 USDC for one window, then 1 SOL = 40 USDC for one window
+        let earlier = PriceAccumulators::new(0, 1_000_000_000_000, 20_000_000_000);
+        let mut pool = earlier;
+        pool.update(
+            TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS,
+            1_000_000_000_000,
+            40_000_000_000,
+        );
+        let later = pool.observe(2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS);
+        let average_price = time_weighted_average_price(&earlier, &later).unwrap();
+        assert_eq!(
+            calculate_market_price(average_price.token_a_price, 9).unwrap(),
+            30_000_000
+        );
+    }
+
+    #[test]
+    fn token_swap_price_window() {
+        let max_age_seconds = 2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS as u64;
+        // 1 SOL = 20 USDC
+        let earlier = PriceAccumulators::new(0, 1_000_000_000_000, 20_000_000_000);
+
+        // no price until a full window has been observed
+        let mut price_observation = earlier;
+        assert_eq!(
+            update_token_swap_price(
+                true,
+                9,
+                0,
+                max_age_seconds,
+                &mut price_observation,
+                earlier.observe(TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS - 1),
+            )
+            .unwrap_err(),
+            LendingError::InvalidOraclePrice.into()
+        );
+
+        // the previous price is kept inside the minimum window
+        assert_eq!(
+            update_token_swap_price(
+                true,
+                9,
+                10_000_000,
+                max_age_seconds,
+                &mut price_observation,
+                earlier.observe(TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS - 1),
+            )
+            .unwrap(),
+            Some(10_000_000)
+        );
+        assert_eq!(price_observation, earlier);
+
+        // a window up to the max age is averaged and replaces the observation
+        let current_observation = earlier.observe(max_age_seconds as UnixTimestamp);
+        assert_eq!(
+            update_token_swap_price(
+                true,
+                9,
+                10_000_000,
+                max_age_seconds,
+                &mut price_observation,
+                current_observation,
+            )
+            .unwrap(),
+            Some(20_000_000)
+        );
+        assert_eq!(price_observation, current_observation);
+        let mut price_observation = earlier;
+        assert_eq!(
+            update_token_swap_price(
+                false,
+                6,
+                0,
+                max_age_seconds,
+                &mut price_observation,
+                current_observation,
+            )
+            .unwrap(),
+            Some(50_000_000)
+        );
+    }
+
+    #[test]
+    fn token_swap_price_older_than_max_age() {
+        let max_age_seconds = 2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS as u64;
+        let earlier = PriceAccumulators::new(0, 1_000_000_000_000, 20_000_000_000);
+
+        // the observation restarts instead of averaging over a longer window
+        let mut price_observation = earlier;
+        let current_observation = earlier.observe(max_age_seconds as UnixTimestamp + 1);
+        assert_eq!(
+            update_token_swap_price(
+                true,
+                9,
+                20_000_000,
+                max_age_seconds,
+                &mut price_observation,
+                current_observation,
+            )
+            .unwrap(),
+            None
+        );
+        assert_eq!(price_observation, current_observation);
+
+        // and the reserve has no price until a full window after the restart
+        assert_eq!(
+            update_token_swap_price(
+                true,
+                9,
+                0,
+                max_age_seconds,
+                &mut price_observation,
+                current_observation.observe(max_age_seconds as UnixTimestamp + 2),
+            )
+            .unwrap_err(),
+            LendingError::InvalidOraclePrice.into()
+        );
+    }
+
+    #[test]
+    fn token_swap_max_age() {
+        assert_eq!(
+            validate_token_swap_max_age(2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS as u64 - 1)
+                .unwrap_err(),
+            LendingError::InvalidConfig.into()
+        );
+        assert_eq!(
+            validate_token_swap_max_age(2 * TOKEN_SWAP_MIN_PRICE_WINDOW_SECONDS as u64),
+            Ok(())
+        );
+    }
+}
@@ -4,19 +4,24 @@ use crate::{
     error::LendingError,
     instruction::LendingInstruction,
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul, WAD},
+    oracle::{
+        get_oracle_price, observe_token_swap, validate_max_oracle_age, validate_oracle, OracleType,
+    },
     state::{
-        CalculateBorrowResult, CalculateLiquidationResult, CalculateRepayResult,
-        InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
-        NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, Reserve,
-        ReserveCollateral, ReserveConfig, ReserveLiquidity,
+        CalculateBorrowResult, CalculateFlashLoanResult, CalculateLiquidationResult,
+        CalculateRepayResult, InitLendingMarketParams, InitObligationParams, InitReserveParams,
+        LendingMarket, NewReserveCollateralParams, NewReserveLiquidityParams, Obligation,
+        ObligationCollateral, Reserve, ReserveCollateral, ReserveConfig, ReserveLiquidity,
+        DELEGATE_ALL, DELEGATE_BORROW, DELEGATE_DEPOSIT, DELEGATE_WITHDRAW, PAUSE_ALL,
+        PAUSE_BORROWS, PAUSE_DEPOSITS, PAUSE_LIQUIDATIONS, PAUSE_WITHDRAWALS,
     },
 };
-use flux_aggregator::{borsh_state::InitBorshState, read_median, state::Aggregator};
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     decode_error::DecodeError,
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
     program::{invoke, invoke_signed},
     program_error::{PrintProgramError, ProgramError},
@@ -25,7 +30,9 @@ use solana_program::{
     pubkey::Pubkey,
     sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
-use spl_token::state::Mint;
+use spl_token::state::{Account, Mint};
+use spl_token_swap::twap::PriceAccumulators;
+use std::iter::Peekable;
 
 /// Processes an instruction
 pub fn process_instruction(
@@ -35,16 +42,20 @@ pub fn process_instruction(
 ) -> ProgramResult {
     let instruction = LendingInstruction::unpack(input)?;
     match instruction {
-        LendingInstruction::InitLendingMarket { owner } => {
+        LendingInstruction::InitLendingMarket {
+            owner,
+            stale_after_slots_elapsed,
+        } => {
             msg!("Instruction: Init Lending Market");
-            process_init_lending_market(program_id, owner, accounts)
+            process_init_lending_market(program_id, owner, stale_after_slots_elapsed, accounts)
         }
         LendingInstruction::InitReserve {
             liquidity_amount,
             config,
+            oracle_type,
         } => {
             msg!("Instruction: Init Reserve");
-            process_init_reserve(program_id, liquidity_amount, config, accounts)
+            process_init_reserve(program_id, liquidity_amount, config, oracle_type, accounts)
         }
         LendingInstruction::InitObligation => {
             msg!("Instruction: Init Obligation");
@@ -90,14 +101,94 @@ pub fn process_instruction(
             msg!("Instruction: Refresh Obligation");
             process_refresh_obligation(program_id, accounts)
         }
+        LendingInstruction::FlashLoan { liquidity_amount } => {
+            msg!("Instruction: Flash Loan");
+            process_flash_loan(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::SetReserveConfig { config } => {
+            msg!("Instruction: Set Reserve Config");
+            process_set_reserve_config(program_id, config, accounts)
+        }
+        LendingInstruction::WithdrawProtocolFees => {
+            msg!("Instruction: Withdraw Protocol Fees");
+            process_withdraw_protocol_fees(program_id, accounts)
+        }
+        LendingInstruction::SetMarketPauseFlags { pause_flags } => {
+            msg!("Instruction: Set Market Pause Flags");
+            process_set_market_pause_flags(program_id, pause_flags, accounts)
+        }
+        LendingInstruction::SetLendingMarketGuardian { new_guardian } => {
+            msg!("Instruction: Set Lending Market Guardian");
+            process_set_lending_market_guardian(program_id, new_guardian, accounts)
+        }
+        LendingInstruction::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount } => {
+            msg!("Instruction: Liquidate Obligation and Redeem Reserve Collateral");
+            process_liquidate_obligation_and_redeem_reserve_collateral(
+                program_id,
+                liquidity_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
+            msg!("Instruction: Deposit Reserve Liquidity and Obligation Collateral");
+            process_deposit_reserve_liquidity_and_obligation_collateral(
+                program_id,
+                liquidity_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
+            collateral_amount,
+        } => {
+            msg!("Instruction: Withdraw Obligation Collateral and Redeem Reserve Collateral");
+            process_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+                program_id,
+                collateral_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::SetObligationDelegate {
+            delegate,
+            permissions,
+            borrow_limit,
+        } => {
+            msg!("Instruction: Set Obligation Delegate");
+            process_set_obligation_delegate(
+                program_id,
+                delegate,
+                permissions,
+                borrow_limit,
+                accounts,
+            )
+        }
+        LendingInstruction::SetObligationOwner { new_owner } => {
+            msg!("Instruction: Set Obligation Owner");
+            process_set_obligation_owner(program_id, new_owner, accounts)
+        }
+        LendingInstruction::SetLendingMarketStaleWindow {
+            stale_after_slots_elapsed,
+        } => {
+            msg!("Instruction: Set Lending Market Stale Window");
+            process_set_lending_market_stale_window(program_id, stale_after_slots_elapsed, accounts)
+        }
+        LendingInstruction::SetLendingMarketPythProgram { pyth_program_id } => {
+            msg!("Instruction: Set Lending Market Pyth Program");
+            process_set_lending_market_pyth_program(program_id, pyth_program_id, accounts)
+        }
     }
 }
 
 fn process_init_lending_market(
     program_id: &Pubkey,
     owner: Pubkey,
+    stale_after_slots_elapsed: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
+    if stale_after_slots_elapsed == 0 {
+        msg!("Stale after slots elapsed must be greater than zero");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
     let account_info_iter = &mut accounts.iter();
     let lending_market_info = next_account_info(account_info_iter)?;
     let quote_token_mint_info = next_account_info(account_info_iter)?;
@@ -122,6 +213,7 @@ fn process_init_lending_market(
         token_program_id: *token_program_id.key,
         quote_token_mint: *quote_token_mint_info.key,
         owner,
+        stale_after_slots_elapsed,
     });
     LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
 
@@ -158,51 +250,223 @@ fn process_set_lending_market_owner(
     Ok(())
 }
 
-fn process_init_reserve(
+fn process_set_market_pause_flags(
     program_id: &Pubkey,
-    liquidity_amount: u64,
-    config: ReserveConfig,
+    pause_flags: u8,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Reserve must be initialized with liquidity");
-        return Err(LendingError::InvalidAmount.into());
-    }
-    if config.optimal_utilization_rate > 100 {
-        msg!("Optimal utilization rate must be in range [0, 100]");
+    if pause_flags & !PAUSE_ALL != 0 {
+        msg!("Pause flags provided contain unknown flags");
         return Err(LendingError::InvalidConfig.into());
     }
-    if config.loan_to_value_ratio >= 100 {
-        msg!("Loan to value ratio must be in range [0, 100)");
-        return Err(LendingError::InvalidConfig.into());
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let pause_authority_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
     }
-    if config.liquidation_bonus > 100 {
-        msg!("Liquidation bonus must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
+    if !lending_market.is_pause_authority(pause_authority_info.key) {
+        msg!("Pause authority provided is not the lending market owner or guardian");
+        return Err(LendingError::InvalidMarketOwner.into());
     }
-    if config.liquidation_threshold <= config.loan_to_value_ratio
-        || config.liquidation_threshold > 100
-    {
-        msg!("Liquidation threshold must be in range (LTV, 100]");
-        return Err(LendingError::InvalidConfig.into());
+    if !pause_authority_info.is_signer {
+        msg!("Pause authority provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
     }
-    if config.optimal_borrow_rate < config.min_borrow_rate {
-        msg!("Optimal borrow rate must be >= min borrow rate");
-        return Err(LendingError::InvalidConfig.into());
+
+    lending_market.pause_flags = pause_flags;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_lending_market_guardian(
+    program_id: &Pubkey,
+    new_guardian: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
     }
-    if config.optimal_borrow_rate > config.max_borrow_rate {
-        msg!("Optimal borrow rate must be <= max borrow rate");
-        return Err(LendingError::InvalidConfig.into());
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
     }
-    if config.fees.borrow_fee_wad >= WAD {
-        msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.guardian = new_guardian;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_lending_market_stale_window(
+    program_id: &Pubkey,
+    stale_after_slots_elapsed: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if stale_after_slots_elapsed == 0 {
+        msg!("Stale after slots elapsed must be greater than zero");
         return Err(LendingError::InvalidConfig.into());
     }
-    if config.fees.host_fee_percentage > 100 {
-        msg!("Host fee percentage must be in range [0, 100]");
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.stale_after_slots_elapsed = stale_after_slots_elapsed;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_lending_market_pyth_program(
+    program_id: &Pubkey,
+    pyth_program_id: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.pyth_program_id = pyth_program_id;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_obligation_delegate(
+    program_id: &Pubkey,
+    delegate: Pubkey,
+    permissions: u8,
+    borrow_limit: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if permissions & !DELEGATE_ALL != 0 {
+        msg!("Delegate permissions provided contain unknown permissions");
         return Err(LendingError::InvalidConfig.into());
     }
 
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    obligation.delegate = delegate;
+    obligation.delegate_permissions = permissions;
+    obligation.delegate_borrow_limit = borrow_limit;
+    obligation.delegate_borrowed_value = Decimal::zero();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_obligation_owner(
+    program_id: &Pubkey,
+    new_owner: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if new_owner == Pubkey::default() {
+        msg!("New obligation owner cannot be the default pubkey");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    // the delegate was chosen by the previous owner
+    obligation.owner = new_owner;
+    obligation.delegate = Pubkey::default();
+    obligation.delegate_permissions = 0;
+    obligation.delegate_borrow_limit = 0;
+    obligation.delegate_borrowed_value = Decimal::zero();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_init_reserve(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    config: ReserveConfig,
+    oracle_type: OracleType,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Reserve must be initialized with liquidity");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    validate_reserve_config(config)?;
+
     let account_info_iter = &mut accounts.iter().peekable();
     let source_liquidity_info = next_account_info(account_info_iter)?;
     let destination_collateral_info = next_account_info(account_info_iter)?;
@@ -262,34 +526,6 @@ fn process_init_reserve(
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    let (reserve_liquidity_oracle_pubkey, reserve_liquidity_market_price) = if &lending_market
-        .quote_token_mint
-        == reserve_liquidity_mint_info.key
-    {
-        if account_info_iter.peek().is_some() {
-            msg!("Reserve liquidity oracle cannot be provided when reserve liquidity is the quote currency");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-        // 1 because quote token price is equal to itself
-        (COption::None, 1)
-    } else {
-        let reserve_liquidity_oracle_info = next_account_info(account_info_iter)?;
-        assert_rent_exempt(rent, reserve_liquidity_oracle_info)?;
-
-        let aggregator = Aggregator::load_initialized(reserve_liquidity_oracle_info)?;
-        if aggregator.config.decimals != quote_token_mint.decimals {
-            msg!(
-                "Quote token mint decimals does not match the aggregator config decimals provided"
-            );
-            return Err(LendingError::InvalidOracleConfig.into());
-        }
-
-        (
-            COption::Some(*reserve_liquidity_oracle_info.key),
-            read_median(reserve_liquidity_oracle_info)?.median,
-        )
-    };
-
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
         &[lending_market.bump_seed],
@@ -309,23 +545,71 @@ fn process_init_reserve(
         return Err(LendingError::InvalidTokenOwner.into());
     }
 
+    let mut reserve_liquidity = ReserveLiquidity::new(NewReserveLiquidityParams {
+        mint_pubkey: *reserve_liquidity_mint_info.key,
+        mint_decimals: reserve_liquidity_mint.decimals,
+        supply_pubkey: *reserve_liquidity_supply_info.key,
+        fee_receiver: *reserve_liquidity_fee_receiver_info.key,
+        oracle_pubkey: COption::None,
+        oracle_type,
+        quote_decimals: quote_token_mint.decimals,
+        market_price: 0,
+    });
+
+    let mut price_observation = PriceAccumulators::default();
+    if &lending_market.quote_token_mint == reserve_liquidity_mint_info.key {
+        if account_info_iter.peek().is_some() {
+            msg!("Reserve liquidity oracle cannot be provided when reserve liquidity is the quote currency");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        // 1 because quote token price is equal to itself
+        reserve_liquidity.market_price = 1;
+    } else {
+        let reserve_liquidity_oracle_info = next_account_info(account_info_iter)?;
+        assert_rent_exempt(rent, reserve_liquidity_oracle_info)?;
+        validate_oracle(
+            oracle_type,
+            reserve_liquidity_oracle_info,
+            reserve_liquidity_mint_info.key,
+            quote_token_mint_info.key,
+            quote_token_mint.decimals,
+            &lending_market.pyth_program_id,
+        )?;
+        validate_max_oracle_age(oracle_type, config.max_oracle_age_seconds)?;
+
+        if oracle_type == OracleType::TokenSwap {
+            if !Reserve::has_rate_history(reserve_info.data_len())? {
+                msg!("Reserve account is too small to observe a token swap oracle");
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+            // the reserve has no price until the pool is observed again a full window later
+            price_observation =
+                observe_token_swap(reserve_liquidity_oracle_info, account_info_iter, clock)?;
+        } else {
+            reserve_liquidity.market_price = get_oracle_price(
+                &reserve_liquidity,
+                config.max_oracle_age_seconds,
+                reserve_liquidity_oracle_info,
+                account_info_iter,
+                &mut price_observation,
+                clock,
+            )?
+            .ok_or(LendingError::InvalidOraclePrice)?;
+        }
+        reserve_liquidity.oracle_pubkey = COption::Some(*reserve_liquidity_oracle_info.key);
+    }
+
     reserve.init(InitReserveParams {
         current_slot: clock.slot,
         lending_market: *lending_market_info.key,
-        liquidity: ReserveLiquidity::new(NewReserveLiquidityParams {
-            mint_pubkey: *reserve_liquidity_mint_info.key,
-            mint_decimals: reserve_liquidity_mint.decimals,
-            supply_pubkey: *reserve_liquidity_supply_info.key,
-            fee_receiver: *reserve_liquidity_fee_receiver_info.key,
-            oracle_pubkey: reserve_liquidity_oracle_pubkey,
-            market_price: reserve_liquidity_market_price,
-        }),
+        liquidity: reserve_liquidity,
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
             mint_pubkey: *reserve_collateral_mint_info.key,
             supply_pubkey: *reserve_collateral_supply_info.key,
         }),
         config,
     });
+    reserve.price_observation = price_observation;
 
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -402,6 +686,19 @@ fn process_refresh_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         return Err(LendingError::InvalidAccountOwner.into());
     }
 
+    refresh_reserve(&mut reserve, clock, account_info_iter)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+/// Updates the market price of reserve liquidity from its oracle, if any, and accrues interest.
+fn refresh_reserve<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    reserve: &mut Reserve,
+    clock: &Clock,
+    account_info_iter: &mut Peekable<I>,
+) -> ProgramResult {
+    let mut market_price = Some(reserve.liquidity.market_price);
     if let COption::Some(reserve_liquidity_oracle_pubkey) = reserve.liquidity.oracle_pubkey {
         let reserve_liquidity_oracle_info = next_account_info(account_info_iter)?;
         if &reserve_liquidity_oracle_pubkey != reserve_liquidity_oracle_info.key {
@@ -409,26 +706,89 @@ fn process_refresh_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
             return Err(LendingError::InvalidAccountInput.into());
         }
 
-        // @TODO: sanity check https://git.io/JOCcb
-        reserve.liquidity.market_price = read_median(reserve_liquidity_oracle_info)?.median;
+        market_price = get_oracle_price(
+            &reserve.liquidity,
+            reserve.config.max_oracle_age_seconds,
+            reserve_liquidity_oracle_info,
+            account_info_iter,
+            &mut reserve.price_observation,
+            clock,
+        )?;
     } else if account_info_iter.peek().is_some() {
         msg!("Reserve liquidity oracle cannot be provided when reserve liquidity is the quote currency");
         return Err(LendingError::InvalidAccountInput.into());
     }
 
     reserve.accrue_interest(clock.slot)?;
+    reserve.record_rate_snapshot(clock.slot)?;
     reserve.last_update.update_slot(clock.slot);
-    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    match market_price {
+        Some(market_price) => reserve.liquidity.market_price = market_price,
+        // the token swap price observation was restarted, so interest is accrued but the reserve
+        // has no price and stays stale until a full price window later
+        None => {
+            reserve.liquidity.market_price = 0;
+            reserve.last_update.mark_stale();
+        }
+    }
 
     Ok(())
 }
 
-fn process_deposit_reserve_liquidity(
+#[inline(never)] // avoid stack frame limit
+fn process_set_reserve_config(
     program_id: &Pubkey,
-    liquidity_amount: u64,
+    config: ReserveConfig,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
+    validate_reserve_config(config)?;
+
+    let account_info_iter = &mut accounts.iter().peekable();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    // settle interest accrued at the old rates before the new config applies
+    refresh_reserve(&mut reserve, clock, account_info_iter)?;
+    validate_max_oracle_age(reserve.liquidity.oracle_type, config.max_oracle_age_seconds)?;
+
+    reserve.set_config(config)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_deposit_reserve_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
         msg!("Liquidity amount provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
@@ -450,6 +810,10 @@ fn process_deposit_reserve_liquidity(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_DEPOSITS) {
+        msg!("Deposits are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
@@ -480,8 +844,11 @@ fn process_deposit_reserve_liquidity(
         msg!("Reserve collateral supply cannot be used as the destination collateral provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if reserve.last_update.is_stale(clock.slot)? {
-        msg!("Reserve is stale and must be refreshed in the current slot");
+    if reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Reserve is stale and must be refreshed");
         return Err(LendingError::ReserveStale.into());
     }
 
@@ -498,6 +865,9 @@ fn process_deposit_reserve_liquidity(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    reserve.accrue_interest_and_mark_stale(clock.slot)?;
+    assert_deposit_limit(&reserve, liquidity_amount)?;
+
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -550,6 +920,10 @@ fn process_redeem_reserve_collateral(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_WITHDRAWALS) {
+        msg!("Withdrawals are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
@@ -580,8 +954,11 @@ fn process_redeem_reserve_collateral(
         msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if reserve.last_update.is_stale(clock.slot)? {
-        msg!("Reserve is stale and must be refreshed in the current slot");
+    if reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Reserve is stale and must be refreshed");
         return Err(LendingError::ReserveStale.into());
     }
 
@@ -598,6 +975,7 @@ fn process_redeem_reserve_collateral(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    reserve.accrue_interest_and_mark_stale(clock.slot)?;
     let liquidity_amount = reserve.redeem_collateral(collateral_amount)?;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -639,6 +1017,10 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         msg!("Obligation provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if Obligation::is_legacy_len(obligation_info.data_len()) {
+        msg!("Obligation account length is invalid");
+        return Err(ProgramError::InvalidAccountData);
+    }
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -670,119 +1052,68 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
 fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter().peekable();
     let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         msg!("Obligation provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
 
-    let mut deposited_value = Decimal::zero();
-    let mut borrowed_value = Decimal::zero();
-    let mut allowed_borrow_value = Decimal::zero();
-    let mut unhealthy_borrow_value = Decimal::zero();
-
-    for (index, collateral) in obligation.deposits.iter_mut().enumerate() {
-        let deposit_reserve_info = next_account_info(account_info_iter)?;
-        if deposit_reserve_info.owner != program_id {
+    obligation.refresh(clock.slot, |reserve_pubkey| {
+        let reserve_info = next_account_info(account_info_iter)?;
+        if reserve_info.owner != program_id {
             msg!(
-                "Deposit reserve provided for collateral {} is not owned by the lending program",
-                index
+                "Reserve provided for obligation reserve {} is not owned by the lending program",
+                reserve_pubkey
             );
             return Err(LendingError::InvalidAccountOwner.into());
         }
-        if collateral.deposit_reserve != *deposit_reserve_info.key {
+        if reserve_pubkey != reserve_info.key {
             msg!(
-                "Deposit reserve of collateral {} does not match the deposit reserve provided",
-                index
+                "Obligation reserve {} does not match the reserve provided",
+                reserve_pubkey
             );
             return Err(LendingError::InvalidAccountInput.into());
         }
 
-        let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
-        if deposit_reserve.last_update.is_stale(clock.slot)? {
-            msg!(
-                "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
-                index
-            );
-            return Err(LendingError::ReserveStale.into());
-        }
-
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(deposit_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
-
-        let market_value = deposit_reserve
-            .collateral_exchange_rate()?
-            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?
-            .try_mul(deposit_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
-        collateral.market_value = market_value;
-
-        let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
-        let liquidation_threshold_rate =
-            Rate::from_percent(deposit_reserve.config.liquidation_threshold);
-
-        deposited_value = deposited_value.try_add(market_value)?;
-        allowed_borrow_value =
-            allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
-        unhealthy_borrow_value =
-            unhealthy_borrow_value.try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
-    }
-
-    for (index, liquidity) in obligation.borrows.iter_mut().enumerate() {
-        let borrow_reserve_info = next_account_info(account_info_iter)?;
-        if borrow_reserve_info.owner != program_id {
-            msg!(
-                "Borrow reserve provided for liquidity {} is not owned by the lending program",
-                index
-            );
-            return Err(LendingError::InvalidAccountOwner.into());
-        }
-        if liquidity.borrow_reserve != *borrow_reserve_info.key {
+        let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        if &reserve.lending_market != lending_market_info.key {
             msg!(
-                "Borrow reserve of liquidity {} does not match the borrow reserve provided",
-                index
+                "Reserve provided for obligation reserve {} is not in the obligation lending market",
+                reserve_pubkey
             );
             return Err(LendingError::InvalidAccountInput.into());
         }
-
-        let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-        if borrow_reserve.last_update.is_stale(clock.slot)? {
+        if reserve
+            .last_update
+            .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+        {
             msg!(
-                "Borrow reserve provided for liquidity {} is stale and must be refreshed in the current slot",
-                index
+                "Reserve provided for obligation reserve {} is stale and must be refreshed",
+                reserve_pubkey
             );
             return Err(LendingError::ReserveStale.into());
         }
-
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
-
-        liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
-        let market_value = liquidity
-            .borrowed_amount_wads
-            .try_mul(borrow_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
-        liquidity.market_value = market_value;
-        borrowed_value = borrowed_value.try_add(market_value)?;
-    }
+        Ok(reserve)
+    })?;
 
     if account_info_iter.peek().is_some() {
         msg!("Too many obligation deposit or borrow reserves provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    obligation.deposited_value = deposited_value;
-    obligation.borrowed_value = borrowed_value;
-    obligation.allowed_borrow_value = allowed_borrow_value;
-    obligation.unhealthy_borrow_value = unhealthy_borrow_value;
-
-    obligation.last_update.update_slot(clock.slot);
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     Ok(())
@@ -806,7 +1137,7 @@ fn process_deposit_obligation_collateral(
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let obligation_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
@@ -816,6 +1147,10 @@ fn process_deposit_obligation_collateral(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_DEPOSITS) {
+        msg!("Deposits are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
@@ -840,8 +1175,11 @@ fn process_deposit_obligation_collateral(
         );
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if deposit_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Deposit reserve is stale and must be refreshed in the current slot");
+    if deposit_reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Deposit reserve is stale and must be refreshed");
         return Err(LendingError::ReserveStale.into());
     }
     if deposit_reserve.config.loan_to_value_ratio == 0 {
@@ -858,12 +1196,12 @@ fn process_deposit_obligation_collateral(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
+    if !obligation.is_authority(obligation_authority_info.key, DELEGATE_DEPOSIT) {
+        msg!("Obligation authority provided is not the obligation owner or a permitted delegate");
         return Err(LendingError::InvalidObligationOwner.into());
     }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
+    if !obligation_authority_info.is_signer {
+        msg!("Obligation authority provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
 
@@ -880,8 +1218,13 @@ fn process_deposit_obligation_collateral(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    update_isolated_collateral(&mut obligation, &deposit_reserve, deposit_reserve_info.key)?;
+
     obligation
-        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .find_or_add_collateral_to_deposits(
+            *deposit_reserve_info.key,
+            Obligation::max_reserves_for_len(obligation_info.data_len())?,
+        )?
         .deposit(collateral_amount)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
@@ -916,7 +1259,7 @@ fn process_withdraw_obligation_collateral(
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let obligation_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
@@ -925,6 +1268,10 @@ fn process_withdraw_obligation_collateral(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_WITHDRAWALS) {
+        msg!("Withdrawals are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
@@ -947,8 +1294,11 @@ fn process_withdraw_obligation_collateral(
         msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if withdraw_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+    if withdraw_reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Withdraw reserve is stale and must be refreshed");
         return Err(LendingError::ReserveStale.into());
     }
 
@@ -961,16 +1311,24 @@ fn process_withdraw_obligation_collateral(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
+    if !obligation.is_authority(obligation_authority_info.key, DELEGATE_WITHDRAW) {
+        msg!("Obligation authority provided is not the obligation owner or a permitted delegate");
         return Err(LendingError::InvalidObligationOwner.into());
     }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
+    if !obligation_authority_info.is_signer {
+        msg!("Obligation authority provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
-    if obligation.last_update.is_stale(clock.slot)? {
-        msg!("Obligation is stale and must be refreshed in the current slot");
+    assert_delegate_destination(
+        &obligation,
+        obligation_authority_info,
+        destination_collateral_info,
+    )?;
+    if obligation
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Obligation is stale and must be refreshed");
         return Err(LendingError::ObligationStale.into());
     }
 
@@ -994,46 +1352,7 @@ fn process_withdraw_obligation_collateral(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let withdraw_amount = if obligation.borrows.is_empty() {
-        if collateral_amount == u64::MAX {
-            collateral.deposited_amount
-        } else {
-            collateral.deposited_amount.min(collateral_amount)
-        }
-    } else if obligation.deposited_value == Decimal::zero() {
-        msg!("Obligation deposited value is zero");
-        return Err(LendingError::ObligationDepositsZero.into());
-    } else {
-        let max_withdraw_value = obligation.max_withdraw_value()?;
-        if max_withdraw_value == Decimal::zero() {
-            msg!("Maximum withdraw value is zero");
-            return Err(LendingError::WithdrawTooLarge.into());
-        }
-
-        let withdraw_amount = if collateral_amount == u64::MAX {
-            let withdraw_value = max_withdraw_value.min(collateral.market_value);
-            let withdraw_pct = withdraw_value.try_div(collateral.market_value)?;
-            withdraw_pct
-                .try_mul(collateral.deposited_amount)?
-                .try_floor_u64()?
-                .min(collateral.deposited_amount)
-        } else {
-            let withdraw_amount = collateral_amount.min(collateral.deposited_amount);
-            let withdraw_pct =
-                Decimal::from(withdraw_amount).try_div(collateral.deposited_amount)?;
-            let withdraw_value = collateral.market_value.try_mul(withdraw_pct)?;
-            if withdraw_value > max_withdraw_value {
-                msg!("Withdraw value cannot exceed maximum withdraw value");
-                return Err(LendingError::WithdrawTooLarge.into());
-            }
-            withdraw_amount
-        };
-        if withdraw_amount == 0 {
-            msg!("Withdraw amount is too small to transfer collateral");
-            return Err(LendingError::WithdrawTooSmall.into());
-        }
-        withdraw_amount
-    };
+    let withdraw_amount = calculate_withdraw_amount(&obligation, collateral, collateral_amount)?;
 
     obligation.withdraw(withdraw_amount, collateral_index)?;
     obligation.last_update.mark_stale();
@@ -1052,7 +1371,7 @@ fn process_withdraw_obligation_collateral(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_borrow_obligation_liquidity(
+fn process_deposit_reserve_liquidity_and_obligation_collateral(
     program_id: &Pubkey,
     liquidity_amount: u64,
     accounts: &[AccountInfo],
@@ -1062,15 +1381,17 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::InvalidAmount.into());
     }
 
-    let account_info_iter = &mut accounts.iter();
+    let account_info_iter = &mut accounts.iter().peekable();
     let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let obligation_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
@@ -1079,37 +1400,43 @@ fn process_borrow_obligation_liquidity(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_DEPOSITS) {
+        msg!("Deposits are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-    if borrow_reserve_info.owner != program_id {
-        msg!("Borrow reserve provided is not owned by the lending program");
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &borrow_reserve.lending_market != lending_market_info.key {
-        msg!("Borrow reserve lending market does not match the lending market provided");
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
-        msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
-        msg!(
-            "Borrow reserve liquidity supply cannot be used as the destination liquidity provided"
-        );
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Reserve collateral mint does not match the reserve collateral mint provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &borrow_reserve.liquidity.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
-        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Reserve collateral supply does not match the reserve collateral supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if borrow_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Borrow reserve is stale and must be refreshed in the current slot");
-        return Err(LendingError::ReserveStale.into());
+    if &reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.config.loan_to_value_ratio == 0 {
+        msg!("Reserve has collateral disabled for borrowing");
+        return Err(LendingError::ReserveCollateralDisabled.into());
     }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
@@ -1121,26 +1448,14 @@ fn process_borrow_obligation_liquidity(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
+    if !obligation.is_authority(obligation_authority_info.key, DELEGATE_DEPOSIT) {
+        msg!("Obligation authority provided is not the obligation owner or a permitted delegate");
         return Err(LendingError::InvalidObligationOwner.into());
     }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
+    if !obligation_authority_info.is_signer {
+        msg!("Obligation authority provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
-    if obligation.last_update.is_stale(clock.slot)? {
-        msg!("Obligation is stale and must be refreshed in the current slot");
-        return Err(LendingError::ObligationStale.into());
-    }
-    if obligation.deposits.is_empty() {
-        msg!("Obligation has no deposits to borrow against");
-        return Err(LendingError::ObligationDepositsEmpty.into());
-    }
-    if obligation.deposited_value == Decimal::zero() {
-        msg!("Obligation deposits have zero value");
-        return Err(LendingError::ObligationDepositsZero.into());
-    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -1155,92 +1470,65 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let remaining_borrow_value = obligation.remaining_borrow_value()?;
-    if remaining_borrow_value == Decimal::zero() {
-        msg!("Remaining borrow value is zero");
-        return Err(LendingError::BorrowTooLarge.into());
-    }
-
-    let CalculateBorrowResult {
-        borrow_amount,
-        receive_amount,
-        borrow_fee,
-        host_fee,
-    } = borrow_reserve.calculate_borrow(liquidity_amount, remaining_borrow_value)?;
-
-    if receive_amount == 0 {
-        msg!("Borrow amount is too small to receive liquidity after fees");
-        return Err(LendingError::BorrowTooSmall.into());
-    }
+    refresh_reserve(&mut reserve, clock, account_info_iter)?;
+    assert_deposit_limit(&reserve, liquidity_amount)?;
+    update_isolated_collateral(&mut obligation, &reserve, reserve_info.key)?;
 
-    borrow_reserve.liquidity.borrow(borrow_amount)?;
-    borrow_reserve.last_update.mark_stale();
-    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+    let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     obligation
-        .find_or_add_liquidity_to_borrows(*borrow_reserve_info.key)?
-        .borrow(borrow_amount)?;
+        .find_or_add_collateral_to_deposits(
+            *reserve_info.key,
+            Obligation::max_reserves_for_len(obligation_info.data_len())?,
+        )?
+        .deposit(collateral_amount)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    let mut owner_fee = borrow_fee;
-    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
-        if host_fee > 0 {
-            owner_fee = owner_fee
-                .checked_sub(host_fee)
-                .ok_or(LendingError::MathOverflow)?;
-
-            spl_token_transfer(TokenTransferParams {
-                source: source_liquidity_info.clone(),
-                destination: host_fee_receiver_info.clone(),
-                amount: host_fee,
-                authority: lending_market_authority_info.clone(),
-                authority_signer_seeds,
-                token_program: token_program_id.clone(),
-            })?;
-        }
-    }
-    if owner_fee > 0 {
-        spl_token_transfer(TokenTransferParams {
-            source: source_liquidity_info.clone(),
-            destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
-            amount: owner_fee,
-            authority: lending_market_authority_info.clone(),
-            authority_signer_seeds,
-            token_program: token_program_id.clone(),
-        })?;
-    }
-
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
-        destination: destination_liquidity_info.clone(),
-        amount: receive_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
+        destination: reserve_liquidity_supply_info.clone(),
+        amount: liquidity_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
         token_program: token_program_id.clone(),
     })?;
 
-    Ok(())
+    spl_token_mint_to(TokenMintToParams {
+        mint: reserve_collateral_mint_info.clone(),
+        destination: reserve_collateral_supply_info.clone(),
+        amount: collateral_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_repay_obligation_liquidity(
+fn process_withdraw_obligation_collateral_and_redeem_reserve_collateral(
     program_id: &Pubkey,
-    liquidity_amount: u64,
+    collateral_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Liquidity amount provided cannot be zero");
+    if collateral_amount == 0 {
+        msg!("Collateral amount provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
-    let account_info_iter = &mut accounts.iter();
-    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let account_info_iter = &mut accounts.iter().peekable();
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
     let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
@@ -1249,31 +1537,39 @@ fn process_repay_obligation_liquidity(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_WITHDRAWALS) {
+        msg!("Withdrawals are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
-    if repay_reserve_info.owner != program_id {
-        msg!("Repay reserve provided is not owned by the lending program");
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &repay_reserve.lending_market != lending_market_info.key {
-        msg!("Repay reserve lending market does not match the lending market provided");
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Reserve collateral supply does not match the reserve collateral supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.liquidity.supply_pubkey != destination_liquidity_info.key {
-        msg!("Repay reserve liquidity supply must be used as the destination liquidity provided");
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Reserve collateral mint does not match the reserve collateral mint provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if repay_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Repay reserve is stale and must be refreshed in the current slot");
-        return Err(LendingError::ReserveStale.into());
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
     }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
@@ -1285,42 +1581,75 @@ fn process_repay_obligation_liquidity(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if obligation.last_update.is_stale(clock.slot)? {
-        msg!("Obligation is stale and must be refreshed in the current slot");
+    if !obligation.is_authority(obligation_authority_info.key, DELEGATE_WITHDRAW) {
+        msg!("Obligation authority provided is not the obligation owner or a permitted delegate");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_authority_info.is_signer {
+        msg!("Obligation authority provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    assert_delegate_destination(
+        &obligation,
+        obligation_authority_info,
+        destination_liquidity_info,
+    )?;
+    // collateral market values are only used to check the health of obligations with borrows
+    if !obligation.borrows.is_empty()
+        && obligation
+            .last_update
+            .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Obligation is stale and must be refreshed");
         return Err(LendingError::ObligationStale.into());
     }
 
-    let (liquidity, liquidity_index) =
-        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
-    if liquidity.borrowed_amount_wads == Decimal::zero() {
-        msg!("Liquidity borrowed amount is zero");
-        return Err(LendingError::ObligationLiquidityEmpty.into());
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        msg!("Collateral deposited amount is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
     }
 
-    let CalculateRepayResult {
-        settle_amount,
-        repay_amount,
-    } = repay_reserve.calculate_repay(liquidity_amount, liquidity.borrowed_amount_wads)?;
-
-    if repay_amount == 0 {
-        msg!("Repay amount is too small to transfer liquidity");
-        return Err(LendingError::RepayTooSmall.into());
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
-    repay_reserve.last_update.mark_stale();
-    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+    let withdraw_amount = calculate_withdraw_amount(&obligation, collateral, collateral_amount)?;
 
-    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
+    refresh_reserve(&mut reserve, clock, account_info_iter)?;
+    let liquidity_amount = reserve.redeem_collateral(withdraw_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_burn(TokenBurnParams {
+        mint: reserve_collateral_mint_info.clone(),
+        source: reserve_collateral_supply_info.clone(),
+        amount: withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
     spl_token_transfer(TokenTransferParams {
-        source: source_liquidity_info.clone(),
+        source: reserve_liquidity_supply_info.clone(),
         destination: destination_liquidity_info.clone(),
-        amount: repay_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
+        amount: liquidity_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
         token_program: token_program_id.clone(),
     })?;
 
@@ -1328,7 +1657,7 @@ fn process_repay_obligation_liquidity(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_liquidate_obligation(
+fn process_borrow_obligation_liquidity(
     program_id: &Pubkey,
     liquidity_amount: u64,
     accounts: &[AccountInfo],
@@ -1340,15 +1669,13 @@ fn process_liquidate_obligation(
 
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_collateral_info = next_account_info(account_info_iter)?;
-    let repay_reserve_info = next_account_info(account_info_iter)?;
-    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let obligation_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
@@ -1357,62 +1684,43 @@ fn process_liquidate_obligation(
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    if lending_market.is_paused(PAUSE_BORROWS) {
+        msg!("Borrows are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
-    if repay_reserve_info.owner != program_id {
-        msg!("Repay reserve provided is not owned by the lending program");
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &repay_reserve.lending_market != lending_market_info.key {
-        msg!("Repay reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
-        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+    if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
         msg!(
-            "Repay reserve collateral supply cannot be used as the destination collateral provided"
+            "Borrow reserve liquidity supply cannot be used as the destination liquidity provided"
         );
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if repay_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Repay reserve is stale and must be refreshed in the current slot");
-        return Err(LendingError::ReserveStale.into());
-    }
-
-    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
-    if withdraw_reserve_info.owner != program_id {
-        msg!("Withdraw reserve provided is not owned by the lending program");
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &withdraw_reserve.lending_market != lending_market_info.key {
-        msg!("Withdraw reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
-        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
-        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+    if &borrow_reserve.liquidity.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
+        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if withdraw_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+    if borrow_reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Borrow reserve is stale and must be refreshed");
         return Err(LendingError::ReserveStale.into());
     }
 
@@ -1425,35 +1733,33 @@ fn process_liquidate_obligation(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if obligation.last_update.is_stale(clock.slot)? {
-        msg!("Obligation is stale and must be refreshed in the current slot");
-        return Err(LendingError::ObligationStale.into());
-    }
-    if obligation.deposited_value == Decimal::zero() {
-        msg!("Obligation deposited value is zero");
-        return Err(LendingError::ObligationDepositsZero.into());
+    if !obligation.is_authority(obligation_authority_info.key, DELEGATE_BORROW) {
+        msg!("Obligation authority provided is not the obligation owner or a permitted delegate");
+        return Err(LendingError::InvalidObligationOwner.into());
     }
-    if obligation.borrowed_value == Decimal::zero() {
-        msg!("Obligation borrowed value is zero");
-        return Err(LendingError::ObligationBorrowsZero.into());
+    if !obligation_authority_info.is_signer {
+        msg!("Obligation authority provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
     }
-    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
-        msg!("Obligation is healthy and cannot be liquidated");
-        return Err(LendingError::ObligationHealthy.into());
+    assert_delegate_destination(
+        &obligation,
+        obligation_authority_info,
+        destination_liquidity_info,
+    )?;
+    if obligation
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Obligation is stale and must be refreshed");
+        return Err(LendingError::ObligationStale.into());
     }
-
-    let (liquidity, liquidity_index) =
-        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
-    if liquidity.market_value == Decimal::zero() {
-        msg!("Obligation borrow value is zero");
-        return Err(LendingError::ObligationLiquidityEmpty.into());
+    if obligation.deposits.is_empty() {
+        msg!("Obligation has no deposits to borrow against");
+        return Err(LendingError::ObligationDepositsEmpty.into());
     }
-
-    let (collateral, collateral_index) =
-        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
-    if collateral.market_value == Decimal::zero() {
-        msg!("Obligation deposit value is zero");
-        return Err(LendingError::ObligationCollateralEmpty.into());
+    if obligation.deposited_value == Decimal::zero() {
+        msg!("Obligation deposits have zero value");
+        return Err(LendingError::ObligationDepositsZero.into());
     }
 
     let authority_signer_seeds = &[
@@ -1469,48 +1775,834 @@ fn process_liquidate_obligation(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let CalculateLiquidationResult {
-        settle_amount,
-        repay_amount,
-        withdraw_amount,
-    } = withdraw_reserve.calculate_liquidation(
-        liquidity_amount,
-        &obligation,
-        &liquidity,
-        &collateral,
-    )?;
+    borrow_reserve.accrue_interest_and_mark_stale(clock.slot)?;
 
-    if repay_amount == 0 {
-        msg!("Liquidation is too small to transfer liquidity");
-        return Err(LendingError::LiquidationTooSmall.into());
-    }
-    if withdraw_amount == 0 {
-        msg!("Liquidation is too small to receive collateral");
-        return Err(LendingError::LiquidationTooSmall.into());
+    let remaining_borrow_value = obligation.remaining_borrow_value()?;
+    if remaining_borrow_value == Decimal::zero() {
+        msg!("Remaining borrow value is zero");
+        return Err(LendingError::BorrowTooLarge.into());
     }
 
-    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
-    repay_reserve.last_update.mark_stale();
-    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+    let CalculateBorrowResult {
+        borrow_amount,
+        receive_amount,
+        borrow_fee,
+        host_fee,
+    } = borrow_reserve.calculate_borrow(liquidity_amount, remaining_borrow_value)?;
 
-    obligation.repay(settle_amount, liquidity_index)?;
-    obligation.withdraw(withdraw_amount, collateral_index)?;
-    obligation.last_update.mark_stale();
-    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+    if receive_amount == 0 {
+        msg!("Borrow amount is too small to receive liquidity after fees");
+        return Err(LendingError::BorrowTooSmall.into());
+    }
 
-    spl_token_transfer(TokenTransferParams {
-        source: source_liquidity_info.clone(),
-        destination: repay_reserve_liquidity_supply_info.clone(),
-        amount: repay_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
+    if borrow_reserve.config.borrow_limit > 0
+        && borrow_reserve
+            .liquidity
+            .borrowed_amount_wads
+            .try_add(borrow_amount)?
+            > Decimal::from(borrow_reserve.config.borrow_limit)
+    {
+        msg!("Borrow would exceed the reserve borrow limit");
+        return Err(LendingError::BorrowLimitExceeded.into());
+    }
 
-    spl_token_transfer(TokenTransferParams {
-        source: withdraw_reserve_collateral_supply_info.clone(),
-        destination: destination_collateral_info.clone(),
-        amount: withdraw_amount,
+    if obligation_authority_info.key != &obligation.owner {
+        obligation.delegate_borrow(borrow_reserve.liquidity_market_value(borrow_amount)?)?;
+    }
+
+    if let COption::Some(isolated_reserve_pubkey) = obligation.isolated_collateral_reserve {
+        let isolated_reserve_info = next_account_info(account_info_iter)?;
+        if isolated_reserve_info.key != &isolated_reserve_pubkey {
+            msg!("Isolated collateral reserve does not match the isolated collateral reserve provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if isolated_reserve_info.key == borrow_reserve_info.key {
+            msg!("Isolated collateral reserve cannot be borrowed from against itself");
+            return Err(LendingError::IsolatedBorrowNotAllowed.into());
+        }
+
+        let mut isolated_reserve = Reserve::unpack(&isolated_reserve_info.data.borrow())?;
+        if isolated_reserve_info.owner != program_id {
+            msg!("Isolated collateral reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if !isolated_reserve
+            .config
+            .is_isolated_borrow_reserve(borrow_reserve_info.key)
+        {
+            msg!("Borrow reserve is not allowed to be borrowed from against isolated collateral");
+            return Err(LendingError::IsolatedBorrowNotAllowed.into());
+        }
+
+        let mut other_borrow_reserves = vec![];
+        for isolated_borrow_reserve_pubkey in
+            isolated_reserve.config.isolated_borrow_reserves.iter()
+        {
+            if *isolated_borrow_reserve_pubkey == Pubkey::default()
+                || isolated_borrow_reserve_pubkey == borrow_reserve_info.key
+            {
+                continue;
+            }
+            let other_borrow_reserve_info = next_account_info(account_info_iter)?;
+            if other_borrow_reserve_info.key != isolated_borrow_reserve_pubkey {
+                msg!("Isolated borrow reserve does not match the isolated borrow reserve provided");
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+            if other_borrow_reserve_info.owner != program_id {
+                msg!("Isolated borrow reserve provided is not owned by the lending program");
+                return Err(LendingError::InvalidAccountOwner.into());
+            }
+            let other_borrow_reserve = Reserve::unpack(&other_borrow_reserve_info.data.borrow())?;
+            if other_borrow_reserve
+                .last_update
+                .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+            {
+                msg!("Isolated borrow reserve is stale and must be refreshed in the current slot");
+                return Err(LendingError::ReserveStale.into());
+            }
+            other_borrow_reserves.push(other_borrow_reserve);
+        }
+
+        isolated_reserve.borrow_isolated(
+            borrow_reserve_info.key,
+            &borrow_reserve,
+            borrow_amount,
+            &other_borrow_reserves,
+        )?;
+        Reserve::pack(
+            isolated_reserve,
+            &mut isolated_reserve_info.data.borrow_mut(),
+        )?;
+    }
+
+    let liquidity = obligation.find_or_add_liquidity_to_borrows(
+        *borrow_reserve_info.key,
+        Obligation::max_reserves_for_len(obligation_info.data_len())?,
+    )?;
+    // interest is only charged on the new borrow from the current cumulative borrow rate
+    liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+    liquidity.borrow(borrow_amount)?;
+
+    borrow_reserve.liquidity.borrow(borrow_amount)?;
+    borrow_reserve.last_update.mark_stale();
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    let mut owner_fee = borrow_fee;
+    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
+        if host_fee > 0 {
+            owner_fee = owner_fee
+                .checked_sub(host_fee)
+                .ok_or(LendingError::MathOverflow)?;
+
+            spl_token_transfer(TokenTransferParams {
+                source: source_liquidity_info.clone(),
+                destination: host_fee_receiver_info.clone(),
+                amount: host_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: receive_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_repay_obligation_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve_info.owner != program_id {
+        msg!("Repay reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &repay_reserve.lending_market != lending_market_info.key {
+        msg!("Repay reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != destination_liquidity_info.key {
+        msg!("Repay reserve liquidity supply must be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Repay reserve is stale and must be refreshed");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Obligation is stale and must be refreshed");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    repay_reserve.accrue_interest_and_mark_stale(clock.slot)?;
+    obligation.accrue_borrow_interest(
+        *repay_reserve_info.key,
+        repay_reserve.liquidity.cumulative_borrow_rate_wads,
+    )?;
+
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    if liquidity.borrowed_amount_wads == Decimal::zero() {
+        msg!("Liquidity borrowed amount is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+
+    let CalculateRepayResult {
+        settle_amount,
+        repay_amount,
+    } = repay_reserve.calculate_repay(liquidity_amount, liquidity.borrowed_amount_wads)?;
+
+    if repay_amount == 0 {
+        msg!("Repay amount is too small to transfer liquidity");
+        return Err(LendingError::RepayTooSmall.into());
+    }
+
+    if let COption::Some(isolated_reserve_pubkey) = obligation.isolated_collateral_reserve {
+        let isolated_reserve_info = next_account_info(account_info_iter)?;
+        if isolated_reserve_info.key != &isolated_reserve_pubkey {
+            msg!("Isolated collateral reserve does not match the isolated collateral reserve provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+
+        let mut isolated_reserve = Reserve::unpack(&isolated_reserve_info.data.borrow())?;
+        if isolated_reserve_info.owner != program_id {
+            msg!("Isolated collateral reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+
+        isolated_reserve.repay_isolated(repay_reserve_info.key, &repay_reserve, settle_amount)?;
+        Reserve::pack(
+            isolated_reserve,
+            &mut isolated_reserve_info.data.borrow_mut(),
+        )?;
+    }
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: repay_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_liquidate_obligation(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    liquidate_obligation(program_id, liquidity_amount, accounts, false)
+}
+
+fn process_liquidate_obligation_and_redeem_reserve_collateral(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    liquidate_obligation(program_id, liquidity_amount, accounts, true)
+}
+
+#[inline(never)] // avoid stack frame limit
+fn liquidate_obligation(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+    redeem: bool,
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let redeem_accounts = if redeem {
+        Some((
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+        ))
+    } else {
+        None
+    };
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if lending_market.is_paused(PAUSE_LIQUIDATIONS) {
+        msg!("Liquidations are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve_info.owner != program_id {
+        msg!("Repay reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &repay_reserve.lending_market != lending_market_info.key {
+        msg!("Repay reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+        msg!(
+            "Repay reserve collateral supply cannot be used as the destination collateral provided"
+        );
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Repay reserve is stale and must be refreshed");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if withdraw_reserve_info.owner != program_id {
+        msg!("Withdraw reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &withdraw_reserve.lending_market != lending_market_info.key {
+        msg!("Withdraw reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
+        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if withdraw_reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Withdraw reserve is stale and must be refreshed");
+        return Err(LendingError::ReserveStale.into());
+    }
+    if let Some((
+        destination_liquidity_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+    )) = redeem_accounts
+    {
+        if &withdraw_reserve.collateral.mint_pubkey != withdraw_reserve_collateral_mint_info.key {
+            msg!("Withdraw reserve collateral mint does not match the withdraw reserve collateral mint provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &withdraw_reserve.liquidity.supply_pubkey != withdraw_reserve_liquidity_supply_info.key {
+            msg!("Withdraw reserve liquidity supply does not match the withdraw reserve liquidity supply provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &withdraw_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+            msg!("Withdraw reserve liquidity supply cannot be used as the destination liquidity provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Obligation is stale and must be refreshed");
+        return Err(LendingError::ObligationStale.into());
+    }
+    if obligation.deposited_value == Decimal::zero() {
+        msg!("Obligation deposited value is zero");
+        return Err(LendingError::ObligationDepositsZero.into());
+    }
+    if obligation.borrowed_value == Decimal::zero() {
+        msg!("Obligation borrowed value is zero");
+        return Err(LendingError::ObligationBorrowsZero.into());
+    }
+    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
+        msg!("Obligation is healthy and cannot be liquidated");
+        return Err(LendingError::ObligationHealthy.into());
+    }
+
+    repay_reserve.accrue_interest_and_mark_stale(clock.slot)?;
+    withdraw_reserve.accrue_interest_and_mark_stale(clock.slot)?;
+    obligation.accrue_borrow_interest(
+        *repay_reserve_info.key,
+        repay_reserve.liquidity.cumulative_borrow_rate_wads,
+    )?;
+
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    if liquidity.market_value == Decimal::zero() {
+        msg!("Obligation borrow value is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+    if collateral.market_value == Decimal::zero() {
+        msg!("Obligation deposit value is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let CalculateLiquidationResult {
+        settle_amount,
+        repay_amount,
+        withdraw_amount,
+    } = withdraw_reserve.calculate_liquidation(
+        liquidity_amount,
+        &obligation,
+        &liquidity,
+        &collateral,
+        Rate::from_percent(repay_reserve.config.liquidation_close_factor),
+        clock.slot,
+    )?;
+
+    if repay_amount == 0 {
+        msg!("Liquidation is too small to transfer liquidity");
+        return Err(LendingError::LiquidationTooSmall.into());
+    }
+    if withdraw_amount == 0 {
+        msg!("Liquidation is too small to receive collateral");
+        return Err(LendingError::LiquidationTooSmall.into());
+    }
+
+    // isolated obligations only hold their isolated collateral, so it is the withdraw reserve
+    if obligation.isolated_collateral_reserve == COption::Some(*withdraw_reserve_info.key) {
+        withdraw_reserve.repay_isolated(repay_reserve_info.key, &repay_reserve, settle_amount)?;
+        Reserve::pack(
+            withdraw_reserve,
+            &mut withdraw_reserve_info.data.borrow_mut(),
+        )?;
+    }
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: repay_reserve_liquidity_supply_info.clone(),
+        amount: repay_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    // redeem as much of the withdrawn collateral as the withdraw reserve has liquidity for
+    let mut collateral_amount = withdraw_amount;
+    if let Some((
+        destination_liquidity_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+    )) = redeem_accounts
+    {
+        // reload after liquidation in case the withdraw reserve is also the repay reserve
+        let mut withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+        withdraw_reserve.accrue_interest_and_mark_stale(clock.slot)?;
+        let redeem_collateral_amount = if lending_market.is_paused(PAUSE_WITHDRAWALS) {
+            0
+        } else {
+            withdraw_reserve
+                .max_redeem_collateral_amount()?
+                .min(withdraw_amount)
+        };
+        if redeem_collateral_amount > 0 {
+            let redeem_liquidity_amount =
+                withdraw_reserve.redeem_collateral(redeem_collateral_amount)?;
+            withdraw_reserve.last_update.mark_stale();
+            Reserve::pack(
+                withdraw_reserve,
+                &mut withdraw_reserve_info.data.borrow_mut(),
+            )?;
+
+            spl_token_burn(TokenBurnParams {
+                mint: withdraw_reserve_collateral_mint_info.clone(),
+                source: withdraw_reserve_collateral_supply_info.clone(),
+                amount: redeem_collateral_amount,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+
+            spl_token_transfer(TokenTransferParams {
+                source: withdraw_reserve_liquidity_supply_info.clone(),
+                destination: destination_liquidity_info.clone(),
+                amount: redeem_liquidity_amount,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+
+            collateral_amount = collateral_amount
+                .checked_sub(redeem_collateral_amount)
+                .ok_or(LendingError::MathOverflow)?;
+        }
+    }
+
+    if collateral_amount > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: withdraw_reserve_collateral_supply_info.clone(),
+            destination: destination_collateral_info.clone(),
+            amount: collateral_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_loan(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if lending_market.is_paused(PAUSE_BORROWS) {
+        msg!("Borrows are paused in the lending market");
+        return Err(LendingError::MarketPaused.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    // Reentrancy is not allowed through another program, but the runtime does allow a program to
+    // invoke itself, which would let the flash loan liquidity be used to modify the reserve
+    if flash_loan_receiver_program_id.key == program_id {
+        msg!("Lending program cannot be used as the flash loan receiver program provided");
+        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let CalculateFlashLoanResult {
+        flash_loan_amount,
+        flash_loan_fee,
+        repay_amount,
+    } = reserve.calculate_flash_loan(liquidity_amount)?;
+
+    if flash_loan_amount == 0 {
+        msg!("Flash loan amount is zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let source_liquidity_balance_before =
+        unpack_token_account(&source_liquidity_info.data.borrow())?.amount;
+    let expected_source_liquidity_balance_after = source_liquidity_balance_before
+        .checked_add(flash_loan_fee)
+        .ok_or(LendingError::MathOverflow)?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: flash_loan_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    let mut flash_loan_instruction_data = Vec::with_capacity(9);
+    flash_loan_instruction_data.push(0);
+    flash_loan_instruction_data.extend_from_slice(&repay_amount.to_le_bytes());
+
+    let mut flash_loan_instruction_accounts = vec![
+        AccountMeta::new(*destination_liquidity_info.key, false),
+        AccountMeta::new(*source_liquidity_info.key, false),
+        AccountMeta::new_readonly(*token_program_id.key, false),
+    ];
+    let mut flash_loan_instruction_account_infos = vec![
+        destination_liquidity_info.clone(),
+        source_liquidity_info.clone(),
+        token_program_id.clone(),
+    ];
+    for account_info in account_info_iter {
+        flash_loan_instruction_accounts.push(AccountMeta {
+            pubkey: *account_info.key,
+            is_signer: account_info.is_signer,
+            is_writable: account_info.is_writable,
+        });
+        flash_loan_instruction_account_infos.push(account_info.clone());
+    }
+
+    invoke(
+        &Instruction {
+            program_id: *flash_loan_receiver_program_id.key,
+            accounts: flash_loan_instruction_accounts,
+            data: flash_loan_instruction_data,
+        },
+        &flash_loan_instruction_account_infos,
+    )?;
+
+    let source_liquidity_balance_after =
+        unpack_token_account(&source_liquidity_info.data.borrow())?.amount;
+    if source_liquidity_balance_after < expected_source_liquidity_balance_after {
+        msg!("Flash loan receiver program did not return the flash loan amount plus fee");
+        return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
+    }
+
+    reserve.settle_flash_loan_fee(flash_loan_fee)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_protocol_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve
+        .last_update
+        .is_stale(clock.slot, lending_market.stale_after_slots_elapsed)?
+    {
+        msg!("Reserve is stale and must be refreshed");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    reserve.accrue_interest_and_mark_stale(clock.slot)?;
+    let withdraw_amount = reserve.liquidity.withdraw_protocol_fees()?;
+    if withdraw_amount == 0 {
+        msg!("No protocol fees are available to withdraw");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_liquidity_supply_info.clone(),
+        destination: reserve_liquidity_fee_receiver_info.clone(),
+        amount: withdraw_amount,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
@@ -1519,6 +2611,154 @@ fn process_liquidate_obligation(
     Ok(())
 }
 
+/// Checks that a deposit does not exceed the reserve deposit limit.
+fn assert_deposit_limit(reserve: &Reserve, liquidity_amount: u64) -> ProgramResult {
+    if reserve.config.deposit_limit > 0
+        && reserve
+            .liquidity
+            .total_supply()?
+            .try_add(Decimal::from(liquidity_amount))?
+            > Decimal::from(reserve.config.deposit_limit)
+    {
+        msg!("Deposit would exceed the reserve deposit limit");
+        return Err(LendingError::DepositLimitExceeded.into());
+    }
+    Ok(())
+}
+
+/// Restricts an obligation to isolated collateral, or rejects collateral it cannot be combined
+/// with.
+fn update_isolated_collateral(
+    obligation: &mut Obligation,
+    deposit_reserve: &Reserve,
+    deposit_reserve_pubkey: &Pubkey,
+) -> ProgramResult {
+    match obligation.isolated_collateral_reserve {
+        COption::Some(isolated_reserve_pubkey) => {
+            if &isolated_reserve_pubkey != deposit_reserve_pubkey {
+                msg!("Obligation with isolated collateral cannot deposit other collateral");
+                return Err(LendingError::IsolatedCollateral.into());
+            }
+        }
+        COption::None => {
+            if deposit_reserve.config.isolated {
+                if !obligation.deposits.is_empty() || !obligation.borrows.is_empty() {
+                    msg!("Isolated collateral can only be deposited to an empty obligation");
+                    return Err(LendingError::IsolatedCollateral.into());
+                }
+                obligation.isolated_collateral_reserve = COption::Some(*deposit_reserve_pubkey);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Calculates the amount of collateral that can be withdrawn from an obligation.
+fn calculate_withdraw_amount(
+    obligation: &Obligation,
+    collateral: &ObligationCollateral,
+    collateral_amount: u64,
+) -> Result<u64, ProgramError> {
+    let withdraw_amount = if obligation.borrows.is_empty() {
+        if collateral_amount == u64::MAX {
+            collateral.deposited_amount
+        } else {
+            collateral.deposited_amount.min(collateral_amount)
+        }
+    } else if obligation.deposited_value == Decimal::zero() {
+        msg!("Obligation deposited value is zero");
+        return Err(LendingError::ObligationDepositsZero.into());
+    } else {
+        let max_withdraw_value = obligation.max_withdraw_value()?;
+        if max_withdraw_value == Decimal::zero() {
+            msg!("Maximum withdraw value is zero");
+            return Err(LendingError::WithdrawTooLarge.into());
+        }
+
+        let withdraw_amount = if collateral_amount == u64::MAX {
+            let withdraw_value = max_withdraw_value.min(collateral.market_value);
+            let withdraw_pct = withdraw_value.try_div(collateral.market_value)?;
+            withdraw_pct
+                .try_mul(collateral.deposited_amount)?
+                .try_floor_u64()?
+                .min(collateral.deposited_amount)
+        } else {
+            let withdraw_amount = collateral_amount.min(collateral.deposited_amount);
+            let withdraw_pct =
+                Decimal::from(withdraw_amount).try_div(collateral.deposited_amount)?;
+            let withdraw_value = collateral.market_value.try_mul(withdraw_pct)?;
+            if withdraw_value > max_withdraw_value {
+                msg!("Withdraw value cannot exceed maximum withdraw value");
+                return Err(LendingError::WithdrawTooLarge.into());
+            }
+            withdraw_amount
+        };
+        if withdraw_amount == 0 {
+            msg!("Withdraw amount is too small to transfer collateral");
+            return Err(LendingError::WithdrawTooSmall.into());
+        }
+        withdraw_amount
+    };
+    Ok(withdraw_amount)
+}
+
+/// Validates reserve configuration values, shared by `InitReserve` and `SetReserveConfig`.
+fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
+    if config.optimal_utilization_rate > 100 {
+        msg!("Optimal utilization rate must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.loan_to_value_ratio >= 100 {
+        msg!("Loan to value ratio must be in range [0, 100)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.liquidation_bonus > 100 {
+        msg!("Liquidation bonus must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.liquidation_close_factor == 0 || config.liquidation_close_factor > 100 {
+        msg!("Liquidation close factor must be in range (0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.liquidation_threshold <= config.loan_to_value_ratio
+        || config.liquidation_threshold > 100
+    {
+        msg!("Liquidation threshold must be in range (LTV, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.optimal_borrow_rate < config.min_borrow_rate {
+        msg!("Optimal borrow rate must be >= min borrow rate");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.optimal_borrow_rate > config.max_borrow_rate {
+        msg!("Optimal borrow rate must be <= max borrow rate");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.borrow_fee_wad >= WAD {
+        msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.flash_loan_fee_wad >= WAD {
+        msg!("Flash loan fee must be in range [0, 1_000_000_000_000_000_000)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.host_fee_percentage > 100 {
+        msg!("Host fee percentage must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.protocol_take_rate > 100 {
+        msg!("Protocol take rate must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.max_oracle_age_seconds == 0 {
+        msg!("Max oracle age must be greater than zero");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    config.rate_model.validate()?;
+
+    Ok(())
+}
+
 fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
     if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
         msg!(&rent.minimum_balance(account_info.data_len()).to_string());
@@ -1539,6 +2779,29 @@ fn assert_uninitialized<T: Pack + IsInitialized>(
     }
 }
 
+/// Check that tokens sent out on behalf of an obligation by its delegate go to the obligation
+/// owner, the owner can send them anywhere
+fn assert_delegate_destination(
+    obligation: &Obligation,
+    obligation_authority_info: &AccountInfo,
+    destination_info: &AccountInfo,
+) -> ProgramResult {
+    if obligation_authority_info.key == &obligation.owner {
+        return Ok(());
+    }
+    let destination = unpack_token_account(&destination_info.data.borrow())?;
+    if destination.owner != obligation.owner {
+        msg!("Destination token account must be owned by the obligation owner when the delegate signs");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    Ok(())
+}
+
+/// Unpacks a spl_token `Account`.
+fn unpack_token_account(data: &[u8]) -> Result<Account, LendingError> {
+    Account::unpack(data).map_err(|_| LendingError::InvalidTokenAccount)
+}
+
 /// Unpacks a spl_token `Mint`.
 fn unpack_mint(data: &[u8]) -> Result<Mint, LendingError> {
     Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
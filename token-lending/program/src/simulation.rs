@@ -0,0 +1,317 @@
+//! Off-chain simulation of obligation health and liquidations
+//!
+//! Shares its math with the processor, so clients such as liquidation bots can project an
+//! obligation forward to a slot without copying the program's calculations.
+
+use crate::{
+    error::LendingError,
+    math::{Decimal, Rate, TrySub},
+    state::{CalculateLiquidationResult, Obligation, Reserve},
+};
+use solana_program::{clock::Slot, msg, program_error::ProgramError, pubkey::Pubkey};
+
+/// Projected liquidation of an unhealthy obligation
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedLiquidation {
+    /// Liquidity amount to pass to `LiquidateObligation`
+    pub liquidity_amount: u64,
+    /// Amount of liquidity the liquidator repays
+    pub repay_amount: u64,
+    /// Amount of collateral the liquidator receives
+    pub withdraw_amount: u64,
+    /// Market value of the repaid liquidity, in quote currency
+    pub repay_value: Decimal,
+    /// Market value of the received collateral, in quote currency
+    pub withdraw_value: Decimal,
+}
+
+impl SimulatedLiquidation {
+    /// Value gained by the liquidator, zero if the liquidation is not profitable
+    pub fn profit_value(&self) -> Decimal {
+        self.withdraw_value
+            .try_sub(self.repay_value)
+            .unwrap_or_else(|_| Decimal::zero())
+    }
+}
+
+/// Project a reserve to a slot by accruing interest, as `RefreshReserve` does without an oracle
+/// price update
+pub fn simulate_refresh_reserve(reserve: &Reserve, slot: Slot) -> Result<Reserve, ProgramError> {
+    let mut reserve = reserve.clone();
+    reserve.accrue_interest(slot)?;
+    reserve.record_rate_snapshot(slot)?;
+    reserve.last_update.update_slot(slot);
+    Ok(reserve)
+}
+
+/// Project an obligation to a slot as `RefreshObligation` would after refreshing every reserve
+/// it uses. Reserves are looked up by address and must all belong to the obligation's lending
+/// market.
+pub fn simulate_refresh_obligation(
+    obligation: &Obligation,
+    reserves: &[(Pubkey, Reserve)],
+    slot: Slot,
+) -> Result<Obligation, ProgramError> {
+    let mut obligation = obligation.clone();
+    let lending_market = obligation.lending_market;
+    obligation.refresh(slot, |reserve_pubkey| {
+        simulate_refresh_reserve(
+            find_reserve(&lending_market, reserves, reserve_pubkey)?,
+            slot,
+        )
+    })?;
+    Ok(obligation)
+}
+
+/// Project an obligation to a slot and find the largest `LiquidateObligation` that repays
+/// liquidity from the repay reserve and receives collateral from the withdraw reserve. Returns
+/// `None` if the obligation is healthy or the liquidation would not be profitable.
+pub fn simulate_liquidation(
+    obligation: &Obligation,
+    reserves: &[(Pubkey, Reserve)],
+    repay_reserve_pubkey: &Pubkey,
+    withdraw_reserve_pubkey: &Pubkey,
+    slot: Slot,
+) -> Result<Option<SimulatedLiquidation>, ProgramError> {
+    let obligation = simulate_refresh_obligation(obligation, reserves, slot)?;
+    if obligation.deposited_value == Decimal::zero()
+        || obligation.borrowed_value == Decimal::zero()
+        || obligation.borrowed_value < obligation.unhealthy_borrow_value
+    {
+        return Ok(None);
+    }
+
+    let repay_reserve = simulate_refresh_reserve(
+        find_reserve(&obligation.lending_market, reserves, repay_reserve_pubkey)?,
+        slot,
+    )?;
+    let withdraw_reserve = simulate_refresh_reserve(
+        find_reserve(
+            &obligation.lending_market,
+            reserves,
+            withdraw_reserve_pubkey,
+        )?,
+        slot,
+    )?;
+
+    let (liquidity, _) = obligation.find_liquidity_in_borrows(*repay_reserve_pubkey)?;
+    let (collateral, _) = obligation.find_collateral_in_deposits(*withdraw_reserve_pubkey)?;
+    if liquidity.market_value == Decimal::zero() || collateral.market_value == Decimal::zero() {
+        return Ok(None);
+    }
+
+    let CalculateLiquidationResult {
+        repay_amount,
+        withdraw_amount,
+        ..
+    } = withdraw_reserve.calculate_liquidation(
+        u64::MAX,
+        &obligation,
+        liquidity,
+        collateral,
+        Rate::from_percent(repay_reserve.config.liquidation_close_factor),
+        slot,
+    )?;
+    if repay_amount == 0 || withdraw_amount == 0 {
+        return Ok(None);
+    }
+
+    let simulated_liquidation = SimulatedLiquidation {
+        liquidity_amount: repay_amount,
+        repay_amount,
+        withdraw_amount,
+        repay_value: repay_reserve.liquidity_market_value(repay_amount.into())?,
+        withdraw_value: withdraw_reserve.collateral_market_value(withdraw_amount)?,
+    };
+    if simulated_liquidation.profit_value() == Decimal::zero() {
+        return Ok(None);
+    }
+
+    Ok(Some(simulated_liquidation))
+}
+
+fn find_reserve<'a>(
+    lending_market: &Pubkey,
+    reserves: &'a [(Pubkey, Reserve)],
+    reserve_pubkey: &Pubkey,
+) -> Result<&'a Reserve, ProgramError> {
+    let (_, reserve) = reserves
+        .iter()
+        .find(|(pubkey, _)| pubkey == reserve_pubkey)
+        .ok_or_else(|| {
+            msg!("Reserve {} was not provided", reserve_pubkey);
+            LendingError::InvalidAccountInput
+        })?;
+    if &reserve.lending_market != lending_market {
+        msg!(
+            "Reserve {} is not in the obligation lending market",
+            reserve_pubkey
+        );
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    Ok(reserve)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{
+        LastUpdate, ObligationCollateral, ObligationLiquidity, ReserveCollateral, ReserveConfig,
+        ReserveLiquidity, LIQUIDATION_CLOSE_FACTOR,
+    };
+
+    const SOL_DECIMALS: u8 = 9;
+    const USDC_DECIMALS: u8 = 6;
+
+    fn test_reserves(lending_market: Pubkey, sol_price: u64) -> [(Pubkey, Reserve); 2] {
+        let sol_reserve = Reserve {
+            lending_market,
+            liquidity: ReserveLiquidity {
+                mint_decimals: SOL_DECIMALS,
+                available_amount: 1_000_000_000_000,
+                market_price: sol_price,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000_000_000_000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                loan_to_value_ratio: 50,
+                liquidation_threshold: 80,
+                liquidation_bonus: 5,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let usdc_reserve = Reserve {
+            lending_market,
+            liquidity: ReserveLiquidity {
+                mint_decimals: USDC_DECIMALS,
+                available_amount: 1_000_000_000_000,
+                borrowed_amount_wads: Decimal::from(100_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                market_price: 1_000_000,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000_000_000_000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                min_borrow_rate: 10,
+                optimal_borrow_rate: 10,
+                max_borrow_rate: 10,
+                liquidation_close_factor: LIQUIDATION_CLOSE_FACTOR,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        [
+            (Pubkey::new_unique(), sol_reserve),
+            (Pubkey::new_unique(), usdc_reserve),
+        ]
+    }
+
+    fn test_obligation(lending_market: Pubkey, reserves: &[(Pubkey, Reserve); 2]) -> Obligation {
+        Obligation {
+            lending_market,
+            last_update: LastUpdate::new(0),
+            // 10 SOL deposited
+            deposits: vec![ObligationCollateral {
+                deposit_reserve: reserves[0].0,
+                deposited_amount: 10_000_000_000,
+                ..ObligationCollateral::default()
+            }],
+            // 100 USDC borrowed
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: reserves[1].0,
+                cumulative_borrow_rate_wads: Decimal::one(),
+                borrowed_amount_wads: Decimal::from(100_000_000u64),
+                market_value: Decimal::zero(),
+            }],
+            ..Obligation::default()
+        }
+    }
+
+    #[test]
+    fn refresh_obligation_values() {
+        let lending_market = Pubkey::new_unique();
+        // $20 SOL
+        let reserves = test_reserves(lending_market, 20_000_000);
+        let obligation = test_obligation(lending_market, &reserves);
+
+        let refreshed = simulate_refresh_obligation(&obligation, &reserves, 0).unwrap();
+        assert_eq!(refreshed.deposited_value, Decimal::from(200_000_000u64));
+        assert_eq!(
+            refreshed.allowed_borrow_value,
+            Decimal::from(100_000_000u64)
+        );
+        assert_eq!(
+            refreshed.unhealthy_borrow_value,
+            Decimal::from(160_000_000u64)
+        );
+        assert_eq!(refreshed.borrowed_value, Decimal::from(100_000_000u64));
+
+        // borrows accrue interest as the slot advances
+        let refreshed = simulate_refresh_obligation(&obligation, &reserves, 1_000_000).unwrap();
+        assert!(refreshed.borrowed_value > Decimal::from(100_000_000u64));
+        assert_eq!(refreshed.last_update.slot, 1_000_000);
+    }
+
+    #[test]
+    fn refresh_obligation_missing_reserve() {
+        let lending_market = Pubkey::new_unique();
+        let reserves = test_reserves(lending_market, 20_000_000);
+        let obligation = test_obligation(lending_market, &reserves);
+
+        assert_eq!(
+            simulate_refresh_obligation(&obligation, &reserves[..1], 0),
+            Err(LendingError::InvalidAccountInput.into())
+        );
+
+        // reserves from another lending market
+        let other_market_obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            ..obligation
+        };
+        assert_eq!(
+            simulate_refresh_obligation(&other_market_obligation, &reserves, 0),
+            Err(LendingError::InvalidAccountInput.into())
+        );
+    }
+
+    #[test]
+    fn liquidation_healthy() {
+        let lending_market = Pubkey::new_unique();
+        let reserves = test_reserves(lending_market, 20_000_000);
+        let obligation = test_obligation(lending_market, &reserves);
+
+        assert_eq!(
+            simulate_liquidation(&obligation, &reserves, &reserves[1].0, &reserves[0].0, 0)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn liquidation_unhealthy() {
+        let lending_market = Pubkey::new_unique();
+        // $12 SOL puts the obligation below the 80% liquidation threshold
+        let reserves = test_reserves(lending_market, 12_000_000);
+        let obligation = test_obligation(lending_market, &reserves);
+
+        let liquidation =
+            simulate_liquidation(&obligation, &reserves, &reserves[1].0, &reserves[0].0, 0)
+                .unwrap()
+                .unwrap();
+
+        // half of the borrow can be repaid for its value in collateral plus a 5% bonus
+        assert_eq!(liquidation.liquidity_amount, 50_000_000);
+        assert_eq!(liquidation.repay_amount, 50_000_000);
+        assert_eq!(liquidation.withdraw_amount, 4_375_000_000);
+        assert_eq!(liquidation.repay_value, Decimal::from(50_000_000u64));
+        assert_eq!(liquidation.withdraw_value, Decimal::from(52_500_000u64));
+        assert_eq!(liquidation.profit_value(), Decimal::from(2_500_000u64));
+    }
+}
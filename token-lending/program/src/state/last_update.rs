@@ -2,7 +2,7 @@ use crate::error::LendingError;
 use solana_program::{clock::Slot, program_error::ProgramError};
 use std::cmp::Ordering;
 
-/// Number of slots to consider stale after
+/// Default number of slots to consider stale after, for new lending markets
 pub const STALE_AFTER_SLOTS_ELAPSED: u64 = 1;
 
 /// Last update state
@@ -39,9 +39,13 @@ impl LastUpdate {
         self.stale = true;
     }
 
-    /// Check if marked stale or last update slot is too long ago
-    pub fn is_stale(&self, slot: Slot) -> Result<bool, ProgramError> {
-        Ok(self.stale || self.slots_elapsed(slot)? >= STALE_AFTER_SLOTS_ELAPSED)
+    /// Check if marked stale or last update slot is at least the lending market stale window ago
+    pub fn is_stale(
+        &self,
+        slot: Slot,
+        stale_after_slots_elapsed: u64,
+    ) -> Result<bool, ProgramError> {
+        Ok(self.stale || self.slots_elapsed(slot)? >= stale_after_slots_elapsed)
     }
 }
 
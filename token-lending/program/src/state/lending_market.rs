@@ -7,6 +7,17 @@ use solana_program::{
     pubkey::{Pubkey, PUBKEY_BYTES},
 };
 
+/// Pause flag for deposits of reserve liquidity and obligation collateral
+pub const PAUSE_DEPOSITS: u8 = 1 << 0;
+/// Pause flag for borrows and flash loans
+pub const PAUSE_BORROWS: u8 = 1 << 1;
+/// Pause flag for liquidations
+pub const PAUSE_LIQUIDATIONS: u8 = 1 << 2;
+/// Pause flag for withdrawals of obligation collateral and redemptions of reserve collateral
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 3;
+/// All valid pause flags, repayments cannot be paused
+pub const PAUSE_ALL: u8 = PAUSE_DEPOSITS | PAUSE_BORROWS | PAUSE_LIQUIDATIONS | PAUSE_WITHDRAWALS;
+
 /// Lending market state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct LendingMarket {
@@ -20,6 +31,14 @@ pub struct LendingMarket {
     pub quote_token_mint: Pubkey,
     /// Token program id
     pub token_program_id: Pubkey,
+    /// Number of slots after which refreshed reserves and obligations are considered stale
+    pub stale_after_slots_elapsed: u64,
+    /// Guardian authority which can set pause flags alongside the owner, default if unset
+    pub guardian: Pubkey,
+    /// Bitmask of paused operations
+    pub pause_flags: u8,
+    /// Program that owns the Pyth price accounts reserves can be priced by, default if unset
+    pub pyth_program_id: Pubkey,
 }
 
 impl LendingMarket {
@@ -37,6 +56,21 @@ impl LendingMarket {
         self.token_program_id = params.token_program_id;
         self.quote_token_mint = params.quote_token_mint;
         self.owner = params.owner;
+        self.stale_after_slots_elapsed = params.stale_after_slots_elapsed;
+        self.guardian = Pubkey::default();
+        self.pause_flags = 0;
+        self.pyth_program_id = Pubkey::default();
+    }
+
+    /// Check if any of the operations in a pause flag bitmask are paused
+    pub fn is_paused(&self, pause_flags: u8) -> bool {
+        self.pause_flags & pause_flags != 0
+    }
+
+    /// Check if an authority can set the pause flags
+    pub fn is_pause_authority(&self, authority: &Pubkey) -> bool {
+        *authority == self.owner
+            || (self.guardian != Pubkey::default() && *authority == self.guardian)
     }
 }
 
@@ -50,6 +84,8 @@ pub struct InitLendingMarketParams {
     pub quote_token_mint: Pubkey,
     /// Token program id
     pub token_program_id: Pubkey,
+    /// Number of slots after which refreshed reserves and obligations are considered stale
+    pub stale_after_slots_elapsed: u64,
 }
 
 impl Sealed for LendingMarket {}
@@ -59,29 +95,77 @@ impl IsInitialized for LendingMarket {
     }
 }
 
-const LENDING_MARKET_LEN: usize = 226; // 1 + 1 + 32 + 32 + 32 + 128
+const LENDING_MARKET_LEN: usize = 226; // 1 + 1 + 32 + 32 + 32 + 8 + 32 + 1 + 32 + 55
 impl Pack for LendingMarket {
     const LEN: usize = LENDING_MARKET_LEN;
 
     fn pack_into_slice(&self, output: &mut [u8]) {
         let output = array_mut_ref![output, 0, LENDING_MARKET_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
-        let (version, bump_seed, owner, quote_token_mint, token_program_id, _padding) =
-            mut_array_refs![output, 1, 1, PUBKEY_BYTES, PUBKEY_BYTES, PUBKEY_BYTES, 128];
+        let (
+            version,
+            bump_seed,
+            owner,
+            quote_token_mint,
+            token_program_id,
+            stale_after_slots_elapsed,
+            guardian,
+            pause_flags,
+            pyth_program_id,
+            _padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            1,
+            PUBKEY_BYTES,
+            55
+        ];
 
         *version = self.version.to_le_bytes();
         *bump_seed = self.bump_seed.to_le_bytes();
         owner.copy_from_slice(self.owner.as_ref());
         quote_token_mint.copy_from_slice(self.quote_token_mint.as_ref());
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        *stale_after_slots_elapsed = self.stale_after_slots_elapsed.to_le_bytes();
+        guardian.copy_from_slice(self.guardian.as_ref());
+        *pause_flags = self.pause_flags.to_le_bytes();
+        pyth_program_id.copy_from_slice(self.pyth_program_id.as_ref());
     }
 
     /// Unpacks a byte buffer into a [LendingMarketInfo](struct.LendingMarketInfo.html)
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
         let input = array_ref![input, 0, LENDING_MARKET_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
-        let (version, bump_seed, owner, quote_token_mint, token_program_id, _padding) =
-            array_refs![input, 1, 1, PUBKEY_BYTES, PUBKEY_BYTES, PUBKEY_BYTES, 128];
+        let (
+            version,
+            bump_seed,
+            owner,
+            quote_token_mint,
+            token_program_id,
+            stale_after_slots_elapsed,
+            guardian,
+            pause_flags,
+            pyth_program_id,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            1,
+            PUBKEY_BYTES,
+            55
+        ];
 
         let version = u8::from_le_bytes(*version);
         if version > PROGRAM_VERSION {
@@ -95,6 +179,48 @@ impl Pack for LendingMarket {
             owner: Pubkey::new_from_array(*owner),
             quote_token_mint: Pubkey::new_from_array(*quote_token_mint),
             token_program_id: Pubkey::new_from_array(*token_program_id),
+            // markets created before the stale window was added read zeroed padding
+            stale_after_slots_elapsed: match u64::from_le_bytes(*stale_after_slots_elapsed) {
+                0 => STALE_AFTER_SLOTS_ELAPSED,
+                stale_after_slots_elapsed => stale_after_slots_elapsed,
+            },
+            guardian: Pubkey::new_from_array(*guardian),
+            pause_flags: u8::from_le_bytes(*pause_flags),
+            pyth_program_id: Pubkey::new_from_array(*pyth_program_id),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unpack_lending_market_without_stale_window() {
+        // lending markets created before the stale window was added have it in zeroed padding
+        let owner = Pubkey::new_unique();
+        let quote_token_mint = Pubkey::new_unique();
+        let token_program_id = Pubkey::new_unique();
+        let mut data = [0u8; LENDING_MARKET_LEN];
+        data[0] = PROGRAM_VERSION;
+        data[1] = 255;
+        data[2..34].copy_from_slice(owner.as_ref());
+        data[34..66].copy_from_slice(quote_token_mint.as_ref());
+        data[66..98].copy_from_slice(token_program_id.as_ref());
+
+        assert_eq!(
+            LendingMarket::unpack(&data).unwrap(),
+            LendingMarket {
+                version: PROGRAM_VERSION,
+                bump_seed: 255,
+                owner,
+                quote_token_mint,
+                token_program_id,
+                stale_after_slots_elapsed: STALE_AFTER_SLOTS_ELAPSED,
+                guardian: Pubkey::default(),
+                pause_flags: 0,
+                pyth_program_id: Pubkey::default(),
+            }
+        );
+    }
+}
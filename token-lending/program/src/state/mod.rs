@@ -3,11 +3,15 @@
 mod last_update;
 mod lending_market;
 mod obligation;
+mod rate_history;
+mod rate_model;
 mod reserve;
 
 pub use last_update::*;
 pub use lending_market::*;
 pub use obligation::*;
+pub use rate_history::*;
+pub use rate_model::*;
 pub use reserve::*;
 
 use crate::math::{Decimal, WAD};
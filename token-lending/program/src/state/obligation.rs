@@ -9,6 +9,7 @@ use solana_program::{
     entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::{Pubkey, PUBKEY_BYTES},
 };
@@ -17,9 +18,22 @@ use std::{
     convert::{TryFrom, TryInto},
 };
 
-/// Max number of collateral and liquidity reserve accounts combined for an obligation
+/// Max number of collateral and liquidity reserve accounts combined for a standard size obligation
 pub const MAX_OBLIGATION_RESERVES: usize = 10;
 
+/// Max number of collateral and liquidity reserve accounts combined for an obligation account
+/// created with extra space, see `Obligation::packed_len`
+pub const MAX_EXTENDED_OBLIGATION_RESERVES: usize = 20;
+
+/// Delegate permission to deposit obligation collateral
+pub const DELEGATE_DEPOSIT: u8 = 1 << 0;
+/// Delegate permission to withdraw obligation collateral
+pub const DELEGATE_WITHDRAW: u8 = 1 << 1;
+/// Delegate permission to borrow obligation liquidity, up to the delegate borrow limit
+pub const DELEGATE_BORROW: u8 = 1 << 2;
+/// All valid delegate permissions, repayments do not require an obligation authority
+pub const DELEGATE_ALL: u8 = DELEGATE_DEPOSIT | DELEGATE_WITHDRAW | DELEGATE_BORROW;
+
 /// Lending market obligation state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Obligation {
@@ -43,6 +57,22 @@ pub struct Obligation {
     pub allowed_borrow_value: Decimal,
     /// The dangerous borrow value at the weighted average liquidation threshold
     pub unhealthy_borrow_value: Decimal,
+    /// Isolated collateral reserve the obligation is restricted to, set when isolated collateral
+    /// is deposited and cleared once the obligation has no deposits or borrows
+    pub isolated_collateral_reserve: COption<Pubkey>,
+    /// Slot at which the obligation was first refreshed while unhealthy, 0 if healthy or if the
+    /// obligation account is too small to record it
+    pub unhealthy_since_slot: Slot,
+    /// Delegate authority which can act for the owner with scoped permissions, default if unset
+    pub delegate: Pubkey,
+    /// Bitmask of operations the delegate is permitted to perform
+    pub delegate_permissions: u8,
+    /// Max total market value the delegate can borrow, in the smallest unit of the quote
+    /// currency, 0 if the delegate cannot borrow
+    pub delegate_borrow_limit: u64,
+    /// Market value of liquidity borrowed by the delegate since it was set, at the time of each
+    /// borrow, in the smallest unit of the quote currency. Repayments do not reduce it.
+    pub delegate_borrowed_value: Decimal,
 }
 
 impl Obligation {
@@ -63,6 +93,29 @@ impl Obligation {
         self.borrows = params.borrows;
     }
 
+    /// Check if an authority is the owner, or the delegate with all of the given permissions
+    pub fn is_authority(&self, authority: &Pubkey, permissions: u8) -> bool {
+        *authority == self.owner || self.is_delegate(authority, permissions)
+    }
+
+    /// Check if an authority is the delegate with all of the given permissions
+    pub fn is_delegate(&self, authority: &Pubkey, permissions: u8) -> bool {
+        self.delegate != Pubkey::default()
+            && *authority == self.delegate
+            && self.delegate_permissions & permissions == permissions
+    }
+
+    /// Record liquidity borrowed by the delegate, up to the delegate borrow limit
+    pub fn delegate_borrow(&mut self, borrow_value: Decimal) -> ProgramResult {
+        let delegate_borrowed_value = self.delegate_borrowed_value.try_add(borrow_value)?;
+        if delegate_borrowed_value > Decimal::from(self.delegate_borrow_limit) {
+            msg!("Borrow would exceed the obligation delegate borrow limit");
+            return Err(LendingError::DelegateBorrowLimitExceeded.into());
+        }
+        self.delegate_borrowed_value = delegate_borrowed_value;
+        Ok(())
+    }
+
     /// Calculate the current ratio of borrowed value to deposited value
     pub fn loan_to_value(&self) -> Result<Decimal, ProgramError> {
         self.borrowed_value.try_div(self.deposited_value)
@@ -73,6 +126,7 @@ impl Obligation {
         let liquidity = &mut self.borrows[liquidity_index];
         if settle_amount == liquidity.borrowed_amount_wads {
             self.borrows.remove(liquidity_index);
+            self.release_isolated_collateral();
         } else {
             liquidity.repay(settle_amount)?;
         }
@@ -84,12 +138,19 @@ impl Obligation {
         let collateral = &mut self.deposits[collateral_index];
         if withdraw_amount == collateral.deposited_amount {
             self.deposits.remove(collateral_index);
+            self.release_isolated_collateral();
         } else {
             collateral.withdraw(withdraw_amount)?;
         }
         Ok(())
     }
 
+    fn release_isolated_collateral(&mut self) {
+        if self.deposits.is_empty() && self.borrows.is_empty() {
+            self.isolated_collateral_reserve = COption::None;
+        }
+    }
+
     /// Calculate the maximum collateral value that can be withdrawn
     pub fn max_withdraw_value(&self) -> Result<Decimal, ProgramError> {
         let required_deposit_value = self
@@ -107,14 +168,78 @@ impl Obligation {
         self.allowed_borrow_value.try_sub(self.borrowed_value)
     }
 
+    /// Record the slot at which the obligation was first seen unhealthy, or clear it if healthy
+    pub fn update_unhealthy_since_slot(&mut self, current_slot: Slot) {
+        if self.borrowed_value > Decimal::zero()
+            && self.borrowed_value >= self.unhealthy_borrow_value
+        {
+            if self.unhealthy_since_slot == 0 {
+                self.unhealthy_since_slot = current_slot;
+            }
+        } else {
+            self.unhealthy_since_slot = 0;
+        }
+    }
+
+    /// Recalculate collateral and liquidity market values and the obligation health at a slot.
+    /// `get_reserve` is called with each deposit reserve and then each borrow reserve, in order,
+    /// and must return that reserve refreshed to the slot.
+    pub fn refresh<F>(&mut self, current_slot: Slot, mut get_reserve: F) -> ProgramResult
+    where
+        F: FnMut(&Pubkey) -> Result<Reserve, ProgramError>,
+    {
+        let mut deposited_value = Decimal::zero();
+        let mut borrowed_value = Decimal::zero();
+        let mut allowed_borrow_value = Decimal::zero();
+        let mut unhealthy_borrow_value = Decimal::zero();
+
+        for collateral in self.deposits.iter_mut() {
+            let deposit_reserve = get_reserve(&collateral.deposit_reserve)?;
+
+            let market_value =
+                deposit_reserve.collateral_market_value(collateral.deposited_amount)?;
+            collateral.market_value = market_value;
+
+            let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
+            let liquidation_threshold_rate =
+                Rate::from_percent(deposit_reserve.config.liquidation_threshold);
+
+            deposited_value = deposited_value.try_add(market_value)?;
+            allowed_borrow_value =
+                allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
+            unhealthy_borrow_value = unhealthy_borrow_value
+                .try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
+        }
+
+        for liquidity in self.borrows.iter_mut() {
+            let borrow_reserve = get_reserve(&liquidity.borrow_reserve)?;
+
+            liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+            let market_value =
+                borrow_reserve.liquidity_market_value(liquidity.borrowed_amount_wads)?;
+            liquidity.market_value = market_value;
+            borrowed_value = borrowed_value.try_add(market_value)?;
+        }
+
+        self.deposited_value = deposited_value;
+        self.borrowed_value = borrowed_value;
+        self.allowed_borrow_value = allowed_borrow_value;
+        self.unhealthy_borrow_value = unhealthy_borrow_value;
+        self.update_unhealthy_since_slot(current_slot);
+
+        self.last_update.update_slot(current_slot);
+        Ok(())
+    }
+
     /// Calculate the maximum liquidation amount for a given liquidity
     pub fn max_liquidation_amount(
         &self,
         liquidity: &ObligationLiquidity,
+        close_factor: Rate,
     ) -> Result<Decimal, ProgramError> {
         let max_liquidation_value = self
             .borrowed_value
-            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
+            .try_mul(close_factor)?
             .min(liquidity.market_value);
         let max_liquidation_pct = max_liquidation_value.try_div(liquidity.market_value)?;
         liquidity.borrowed_amount_wads.try_mul(max_liquidation_pct)
@@ -135,18 +260,19 @@ impl Obligation {
         Ok((&self.deposits[collateral_index], collateral_index))
     }
 
-    /// Find or add collateral by deposit reserve
+    /// Find or add collateral by deposit reserve, up to `max_reserves` deposits and borrows
     pub fn find_or_add_collateral_to_deposits(
         &mut self,
         deposit_reserve: Pubkey,
+        max_reserves: usize,
     ) -> Result<&mut ObligationCollateral, ProgramError> {
         if let Some(collateral_index) = self._find_collateral_index_in_deposits(deposit_reserve) {
             return Ok(&mut self.deposits[collateral_index]);
         }
-        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+        if self.deposits.len() + self.borrows.len() >= max_reserves {
             msg!(
                 "Obligation cannot have more than {} deposits and borrows combined",
-                MAX_OBLIGATION_RESERVES
+                max_reserves
             );
             return Err(LendingError::ObligationReserveLimit.into());
         }
@@ -176,18 +302,19 @@ impl Obligation {
         Ok((&self.borrows[liquidity_index], liquidity_index))
     }
 
-    /// Find or add liquidity by borrow reserve
+    /// Find or add liquidity by borrow reserve, up to `max_reserves` deposits and borrows
     pub fn find_or_add_liquidity_to_borrows(
         &mut self,
         borrow_reserve: Pubkey,
+        max_reserves: usize,
     ) -> Result<&mut ObligationLiquidity, ProgramError> {
         if let Some(liquidity_index) = self._find_liquidity_index_in_borrows(borrow_reserve) {
             return Ok(&mut self.borrows[liquidity_index]);
         }
-        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+        if self.deposits.len() + self.borrows.len() >= max_reserves {
             msg!(
                 "Obligation cannot have more than {} deposits and borrows combined",
-                MAX_OBLIGATION_RESERVES
+                max_reserves
             );
             return Err(LendingError::ObligationReserveLimit.into());
         }
@@ -196,6 +323,18 @@ impl Obligation {
         Ok(self.borrows.last_mut().unwrap())
     }
 
+    /// Accrue interest on liquidity borrowed from a reserve up to its cumulative borrow rate
+    pub fn accrue_borrow_interest(
+        &mut self,
+        borrow_reserve: Pubkey,
+        cumulative_borrow_rate_wads: Decimal,
+    ) -> ProgramResult {
+        if let Some(liquidity_index) = self._find_liquidity_index_in_borrows(borrow_reserve) {
+            self.borrows[liquidity_index].accrue_interest(cumulative_borrow_rate_wads)?;
+        }
+        Ok(())
+    }
+
     fn _find_liquidity_index_in_borrows(&self, borrow_reserve: Pubkey) -> Option<usize> {
         self.borrows
             .iter()
@@ -217,6 +356,47 @@ pub struct InitObligationParams {
     pub borrows: Vec<ObligationLiquidity>,
 }
 
+impl Obligation {
+    /// Length of an obligation account that can hold `max_reserves` deposits and borrows
+    /// combined, from `MAX_OBLIGATION_RESERVES` to `MAX_EXTENDED_OBLIGATION_RESERVES`
+    pub fn packed_len(max_reserves: usize) -> usize {
+        OBLIGATION_LEN
+            + OBLIGATION_LIQUIDITY_LEN * max_reserves.saturating_sub(MAX_OBLIGATION_RESERVES)
+    }
+
+    /// Max number of deposits and borrows combined that fit in an obligation account
+    pub fn max_reserves_for_len(len: usize) -> Result<usize, ProgramError> {
+        if Self::is_legacy_len(len) {
+            return Ok(MAX_OBLIGATION_RESERVES);
+        }
+        let extra_len = len
+            .checked_sub(OBLIGATION_LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let max_reserves = MAX_OBLIGATION_RESERVES + extra_len / OBLIGATION_LIQUIDITY_LEN;
+        if extra_len % OBLIGATION_LIQUIDITY_LEN != 0
+            || max_reserves > MAX_EXTENDED_OBLIGATION_RESERVES
+        {
+            msg!("Obligation account length is invalid");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(max_reserves)
+    }
+
+    /// Whether an obligation account of `len` bytes was created before isolated collateral,
+    /// liquidation auctions and delegates were added
+    pub fn is_legacy_len(len: usize) -> bool {
+        len == LEGACY_OBLIGATION_LEN
+    }
+
+    fn positions_capacity(len: usize) -> usize {
+        if Self::is_legacy_len(len) {
+            len - OBLIGATION_HEADER_LEN
+        } else {
+            len.saturating_sub(OBLIGATION_HEADER_LEN + OBLIGATION_EXTENSION_LEN)
+        }
+    }
+}
+
 impl Sealed for Obligation {}
 impl IsInitialized for Obligation {
     fn is_initialized(&self) -> bool {
@@ -326,13 +506,49 @@ impl ObligationLiquidity {
 
 const OBLIGATION_COLLATERAL_LEN: usize = 56; // 32 + 8 + 16
 const OBLIGATION_LIQUIDITY_LEN: usize = 80; // 32 + 16 + 16 + 16
-const OBLIGATION_LEN: usize = 916; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9)
-                                   // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
+const OBLIGATION_LEN: usize = 1032; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9) + (4 + 32) + 8 + 32 + 1 + 8 + 16 + 15
+                                    // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
+const OBLIGATION_HEADER_LEN: usize = 140; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1
+const OBLIGATION_EXTENSION_LEN: usize = 116; // (4 + 32) + 8 + 32 + 1 + 8 + 16 + 15
+const LEGACY_OBLIGATION_LEN: usize = 916; // OBLIGATION_LEN - OBLIGATION_EXTENSION_LEN
+fn packed_positions_len(deposits_len: usize, borrows_len: usize) -> usize {
+    OBLIGATION_COLLATERAL_LEN * deposits_len + OBLIGATION_LIQUIDITY_LEN * borrows_len
+}
+
 impl Pack for Obligation {
+    /// Length of a standard size obligation, extended obligations hold extra liquidity positions.
+    /// Obligations created before isolated collateral, liquidation auctions and delegates were
+    /// added are `LEGACY_OBLIGATION_LEN` bytes and have no room for their state at the end.
     const LEN: usize = OBLIGATION_LEN;
 
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::max_reserves_for_len(input.len())?;
+        Self::unpack_from_slice(input)
+    }
+
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        Self::max_reserves_for_len(dst.len())?;
+        if packed_positions_len(src.deposits.len(), src.borrows.len())
+            > Self::positions_capacity(dst.len())
+        {
+            msg!("Obligation deposits and borrows do not fit in the obligation account");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Self::is_legacy_len(dst.len())
+            && (src.isolated_collateral_reserve.is_some()
+                || src.delegate != Pubkey::default()
+                || src.delegate_permissions != 0)
+        {
+            msg!("Obligation account is too small for isolated collateral or a delegate");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        src.pack_into_slice(dst);
+        Ok(())
+    }
+
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let output = array_mut_ref![dst, 0, OBLIGATION_LEN];
+        let (output, data_flat) = dst.split_at_mut(OBLIGATION_HEADER_LEN);
+        let output = array_mut_ref![output, 0, OBLIGATION_HEADER_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -346,7 +562,6 @@ impl Pack for Obligation {
             unhealthy_borrow_value,
             deposits_len,
             borrows_len,
-            data_flat,
         ) = mut_array_refs![
             output,
             1,
@@ -359,8 +574,7 @@ impl Pack for Obligation {
             16,
             16,
             1,
-            1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            1
         ];
 
         // obligation
@@ -405,11 +619,39 @@ impl Pack for Obligation {
             pack_decimal(liquidity.market_value, market_value);
             offset += OBLIGATION_LIQUIDITY_LEN;
         }
+
+        // legacy obligations cannot record when they became unhealthy
+        if Self::is_legacy_len(dst.len()) {
+            return;
+        }
+        let extension_offset = dst.len() - OBLIGATION_EXTENSION_LEN;
+        let output = array_mut_ref![dst, extension_offset, OBLIGATION_EXTENSION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            isolated_collateral_reserve,
+            unhealthy_since_slot,
+            delegate,
+            delegate_permissions,
+            delegate_borrow_limit,
+            delegate_borrowed_value,
+            _padding,
+        ) = mut_array_refs![output, 4 + PUBKEY_BYTES, 8, PUBKEY_BYTES, 1, 8, 16, 15];
+
+        pack_coption_key(
+            &self.isolated_collateral_reserve,
+            isolated_collateral_reserve,
+        );
+        *unhealthy_since_slot = self.unhealthy_since_slot.to_le_bytes();
+        delegate.copy_from_slice(self.delegate.as_ref());
+        *delegate_permissions = self.delegate_permissions.to_le_bytes();
+        *delegate_borrow_limit = self.delegate_borrow_limit.to_le_bytes();
+        pack_decimal(self.delegate_borrowed_value, delegate_borrowed_value);
     }
 
     /// Unpacks a byte buffer into an [ObligationInfo](struct.ObligationInfo.html).
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![src, 0, OBLIGATION_LEN];
+        let (input, data_flat) = src.split_at(OBLIGATION_HEADER_LEN);
+        let input = array_ref![input, 0, OBLIGATION_HEADER_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -423,7 +665,6 @@ impl Pack for Obligation {
             unhealthy_borrow_value,
             deposits_len,
             borrows_len,
-            data_flat,
         ) = array_refs![
             input,
             1,
@@ -436,8 +677,7 @@ impl Pack for Obligation {
             16,
             16,
             1,
-            1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            1
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -448,6 +688,12 @@ impl Pack for Obligation {
 
         let deposits_len = u8::from_le_bytes(*deposits_len);
         let borrows_len = u8::from_le_bytes(*borrows_len);
+        if packed_positions_len(deposits_len as usize, borrows_len as usize)
+            > Self::positions_capacity(src.len())
+        {
+            msg!("Obligation deposits and borrows do not fit in the obligation account");
+            return Err(ProgramError::InvalidAccountData);
+        }
         let mut deposits = Vec::with_capacity(deposits_len as usize + 1);
         let mut borrows = Vec::with_capacity(borrows_len as usize + 1);
 
@@ -478,7 +724,7 @@ impl Pack for Obligation {
             offset += OBLIGATION_LIQUIDITY_LEN;
         }
 
-        Ok(Self {
+        let mut obligation = Self {
             version,
             last_update: LastUpdate {
                 slot: u64::from_le_bytes(*last_update_slot),
@@ -492,7 +738,33 @@ impl Pack for Obligation {
             borrowed_value: unpack_decimal(borrowed_value),
             allowed_borrow_value: unpack_decimal(allowed_borrow_value),
             unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
-        })
+            ..Self::default()
+        };
+
+        if Self::is_legacy_len(src.len()) {
+            return Ok(obligation);
+        }
+        let extension_offset = src.len() - OBLIGATION_EXTENSION_LEN;
+        let input = array_ref![src, extension_offset, OBLIGATION_EXTENSION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            isolated_collateral_reserve,
+            unhealthy_since_slot,
+            delegate,
+            delegate_permissions,
+            delegate_borrow_limit,
+            delegate_borrowed_value,
+            _padding,
+        ) = array_refs![input, 4 + PUBKEY_BYTES, 8, PUBKEY_BYTES, 1, 8, 16, 15];
+
+        obligation.isolated_collateral_reserve = unpack_coption_key(isolated_collateral_reserve)?;
+        obligation.unhealthy_since_slot = u64::from_le_bytes(*unhealthy_since_slot);
+        obligation.delegate = Pubkey::new_from_array(*delegate);
+        obligation.delegate_permissions = u8::from_le_bytes(*delegate_permissions);
+        obligation.delegate_borrow_limit = u64::from_le_bytes(*delegate_borrow_limit);
+        obligation.delegate_borrowed_value = unpack_decimal(delegate_borrowed_value);
+
+        Ok(obligation)
     }
 }
 
@@ -535,6 +807,205 @@ mod test {
         );
     }
 
+    #[test]
+    fn obligation_unhealthy_since_slot() {
+        let mut obligation = Obligation {
+            borrowed_value: Decimal::from(90u64),
+            unhealthy_borrow_value: Decimal::from(80u64),
+            ..Obligation::default()
+        };
+
+        obligation.update_unhealthy_since_slot(10);
+        assert_eq!(obligation.unhealthy_since_slot, 10);
+
+        // first unhealthy slot is kept while unhealthy
+        obligation.update_unhealthy_since_slot(20);
+        assert_eq!(obligation.unhealthy_since_slot, 10);
+
+        obligation.borrowed_value = Decimal::from(70u64);
+        obligation.update_unhealthy_since_slot(30);
+        assert_eq!(obligation.unhealthy_since_slot, 0);
+    }
+
+    #[test]
+    fn obligation_delegate_permissions() {
+        let owner = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let mut obligation = Obligation {
+            owner,
+            delegate,
+            delegate_permissions: DELEGATE_DEPOSIT | DELEGATE_BORROW,
+            ..Obligation::default()
+        };
+
+        assert!(obligation.is_authority(&owner, DELEGATE_ALL));
+        assert!(obligation.is_authority(&delegate, DELEGATE_BORROW));
+        assert!(!obligation.is_authority(&delegate, DELEGATE_WITHDRAW));
+        assert!(!obligation.is_authority(&delegate, DELEGATE_BORROW | DELEGATE_WITHDRAW));
+        assert!(!obligation.is_authority(&Pubkey::new_unique(), DELEGATE_DEPOSIT));
+
+        // an unset delegate has no permissions
+        obligation.delegate = Pubkey::default();
+        assert!(!obligation.is_authority(&Pubkey::default(), DELEGATE_DEPOSIT));
+    }
+
+    #[test]
+    fn pack_and_unpack_extended_obligation() {
+        let obligation_len = Obligation::packed_len(MAX_EXTENDED_OBLIGATION_RESERVES);
+        assert_eq!(
+            Obligation::max_reserves_for_len(obligation_len),
+            Ok(MAX_EXTENDED_OBLIGATION_RESERVES)
+        );
+        assert_eq!(
+            Obligation::max_reserves_for_len(Obligation::LEN),
+            Ok(MAX_OBLIGATION_RESERVES)
+        );
+        assert_eq!(
+            Obligation::max_reserves_for_len(Obligation::LEN + 1),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Obligation::max_reserves_for_len(Obligation::packed_len(
+                MAX_EXTENDED_OBLIGATION_RESERVES + 1
+            )),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut obligation = Obligation::new(InitObligationParams {
+            current_slot: 1,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![],
+            borrows: vec![],
+        });
+        for _ in 0..MAX_EXTENDED_OBLIGATION_RESERVES / 2 {
+            obligation
+                .find_or_add_collateral_to_deposits(
+                    Pubkey::new_unique(),
+                    MAX_EXTENDED_OBLIGATION_RESERVES,
+                )
+                .unwrap()
+                .deposit(1)
+                .unwrap();
+            obligation
+                .find_or_add_liquidity_to_borrows(
+                    Pubkey::new_unique(),
+                    MAX_EXTENDED_OBLIGATION_RESERVES,
+                )
+                .unwrap()
+                .borrow(Decimal::one())
+                .unwrap();
+        }
+        assert_eq!(
+            obligation
+                .find_or_add_collateral_to_deposits(
+                    Pubkey::new_unique(),
+                    MAX_EXTENDED_OBLIGATION_RESERVES
+                )
+                .unwrap_err(),
+            LendingError::ObligationReserveLimit.into()
+        );
+
+        // too many positions for a standard size obligation
+        let mut packed = vec![0u8; Obligation::LEN];
+        assert_eq!(
+            Obligation::pack(obligation.clone(), &mut packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        obligation.delegate = Pubkey::new_unique();
+        obligation.delegate_permissions = DELEGATE_ALL;
+        obligation.delegate_borrow_limit = 1_000;
+        obligation.delegate_borrow(Decimal::from(600u64)).unwrap();
+        assert_eq!(
+            obligation.delegate_borrow(Decimal::from(401u64)),
+            Err(LendingError::DelegateBorrowLimitExceeded.into())
+        );
+        assert_eq!(obligation.delegate_borrowed_value, Decimal::from(600u64));
+        let mut packed = vec![0u8; obligation_len];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        assert_eq!(Obligation::unpack(&packed).unwrap(), obligation);
+    }
+
+    #[test]
+    fn pack_and_unpack_legacy_obligation() {
+        let lending_market = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+
+        // obligation packed before isolated collateral, liquidation auctions and delegates
+        let mut legacy_data = vec![0u8; LEGACY_OBLIGATION_LEN];
+        legacy_data[0] = PROGRAM_VERSION;
+        legacy_data[1..9].copy_from_slice(&10u64.to_le_bytes());
+        legacy_data[10..42].copy_from_slice(lending_market.as_ref());
+        legacy_data[42..74].copy_from_slice(owner.as_ref());
+        legacy_data[74..90]
+            .copy_from_slice(&Decimal::from(100u64).to_scaled_val().unwrap().to_le_bytes());
+        legacy_data[90..106]
+            .copy_from_slice(&Decimal::from(50u64).to_scaled_val().unwrap().to_le_bytes());
+        legacy_data[138] = 1;
+        legacy_data[139] = 1;
+        legacy_data[140..172].copy_from_slice(deposit_reserve.as_ref());
+        legacy_data[172..180].copy_from_slice(&1_000u64.to_le_bytes());
+        legacy_data[196..228].copy_from_slice(borrow_reserve.as_ref());
+        legacy_data[228..244]
+            .copy_from_slice(&Decimal::one().to_scaled_val().unwrap().to_le_bytes());
+        legacy_data[244..260]
+            .copy_from_slice(&Decimal::from(500u64).to_scaled_val().unwrap().to_le_bytes());
+
+        let obligation = Obligation::unpack(&legacy_data).unwrap();
+        assert_eq!(
+            obligation,
+            Obligation {
+                version: PROGRAM_VERSION,
+                last_update: LastUpdate {
+                    slot: 10,
+                    stale: false,
+                },
+                lending_market,
+                owner,
+                deposits: vec![ObligationCollateral {
+                    deposit_reserve,
+                    deposited_amount: 1_000,
+                    market_value: Decimal::zero(),
+                }],
+                borrows: vec![ObligationLiquidity {
+                    borrow_reserve,
+                    cumulative_borrow_rate_wads: Decimal::one(),
+                    borrowed_amount_wads: Decimal::from(500u64),
+                    market_value: Decimal::zero(),
+                }],
+                deposited_value: Decimal::from(100u64),
+                borrowed_value: Decimal::from(50u64),
+                ..Obligation::default()
+            }
+        );
+        assert_eq!(
+            Obligation::max_reserves_for_len(legacy_data.len()),
+            Ok(MAX_OBLIGATION_RESERVES)
+        );
+
+        let mut packed = vec![0u8; LEGACY_OBLIGATION_LEN];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        assert_eq!(packed, legacy_data);
+
+        // the unhealthy since slot cannot be recorded and is dropped
+        let mut unhealthy_obligation = obligation.clone();
+        unhealthy_obligation.unhealthy_since_slot = 10;
+        Obligation::pack(unhealthy_obligation, &mut packed).unwrap();
+        assert_eq!(packed, legacy_data);
+
+        // isolated collateral and delegates cannot be recorded
+        let mut delegated_obligation = obligation;
+        delegated_obligation.delegate = Pubkey::new_unique();
+        delegated_obligation.delegate_permissions = DELEGATE_DEPOSIT;
+        assert_eq!(
+            Obligation::pack(delegated_obligation, &mut packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
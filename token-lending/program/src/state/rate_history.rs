@@ -0,0 +1,248 @@
+use super::*;
+use crate::{
+    error::LendingError,
+    math::{Decimal, Rate, TryDiv, TrySub},
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{clock::Slot, msg, program_error::ProgramError};
+use std::convert::TryFrom;
+
+/// Max number of rate snapshots kept by a reserve
+pub const MAX_RATE_SNAPSHOTS: usize = 24;
+
+/// Minimum number of slots between rate snapshots, about an hour
+pub const RATE_SNAPSHOT_INTERVAL_SLOTS: u64 = SLOTS_PER_YEAR / 365 / 24;
+
+/// Length of a packed rate history
+pub const RATE_HISTORY_LEN: usize = 962; // 1 + 1 + (8 + 16 + 16) * MAX_RATE_SNAPSHOTS
+
+const RATE_SNAPSHOT_LEN: usize = 40; // 8 + 16 + 16
+
+/// Reserve rates recorded at a slot
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateSnapshot {
+    /// Slot the rates were recorded at
+    pub slot: Slot,
+    /// Reserve liquidity cumulative borrow rate
+    pub cumulative_borrow_rate_wads: Decimal,
+    /// Amount of collateral minted per unit of liquidity
+    pub collateral_exchange_rate: Rate,
+}
+
+impl RateSnapshot {
+    /// Interest accrued on borrows between an earlier snapshot and this one
+    pub fn borrow_interest_since(&self, earlier: &RateSnapshot) -> Result<Rate, ProgramError> {
+        let growth = self
+            .cumulative_borrow_rate_wads
+            .try_div(earlier.cumulative_borrow_rate_wads)?;
+        Rate::try_from(growth)?.try_sub(Rate::one())
+    }
+
+    /// Yield earned by collateral holders between an earlier snapshot and this one. Collateral
+    /// redeems for more liquidity as interest accrues, so the exchange rate falls over time.
+    pub fn supply_yield_since(&self, earlier: &RateSnapshot) -> Result<Rate, ProgramError> {
+        if self.collateral_exchange_rate == Rate::zero() {
+            msg!("Rate snapshot collateral exchange rate cannot be zero");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        let liquidity_per_collateral = Decimal::from(earlier.collateral_exchange_rate)
+            .try_div(Decimal::from(self.collateral_exchange_rate))?;
+        Rate::try_from(liquidity_per_collateral)?.try_sub(Rate::one())
+    }
+}
+
+/// Ring buffer of reserve rate snapshots, written at most once per
+/// `RATE_SNAPSHOT_INTERVAL_SLOTS` by `RefreshReserve`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateHistory {
+    /// Number of snapshots recorded, up to `MAX_RATE_SNAPSHOTS`
+    pub snapshot_count: u8,
+    /// Index the next snapshot will be written to
+    pub next_index: u8,
+    /// Snapshots, the oldest is overwritten once the buffer is full
+    pub snapshots: [RateSnapshot; MAX_RATE_SNAPSHOTS],
+}
+
+impl RateHistory {
+    /// Record a snapshot if the snapshot interval has elapsed since the latest one, returning
+    /// whether it was recorded
+    pub fn record(&mut self, snapshot: RateSnapshot) -> bool {
+        if let Some(latest) = self.latest() {
+            if snapshot.slot < latest.slot.saturating_add(RATE_SNAPSHOT_INTERVAL_SLOTS) {
+                return false;
+            }
+        }
+
+        let next_index = self.next_index as usize % MAX_RATE_SNAPSHOTS;
+        self.snapshots[next_index] = snapshot;
+        self.next_index = ((next_index + 1) % MAX_RATE_SNAPSHOTS) as u8;
+        self.snapshot_count = (self.snapshot_count as usize + 1).min(MAX_RATE_SNAPSHOTS) as u8;
+        true
+    }
+
+    /// Most recent snapshot
+    pub fn latest(&self) -> Option<&RateSnapshot> {
+        self.iter().last()
+    }
+
+    /// Most recent snapshot recorded at or before a slot
+    pub fn at_or_before(&self, slot: Slot) -> Option<&RateSnapshot> {
+        self.iter()
+            .take_while(|snapshot| snapshot.slot <= slot)
+            .last()
+    }
+
+    /// Iterate over snapshots from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &RateSnapshot> {
+        let snapshot_count = (self.snapshot_count as usize).min(MAX_RATE_SNAPSHOTS);
+        let start =
+            (self.next_index as usize + MAX_RATE_SNAPSHOTS - snapshot_count) % MAX_RATE_SNAPSHOTS;
+        (0..snapshot_count)
+            .map(move |offset| &self.snapshots[(start + offset) % MAX_RATE_SNAPSHOTS])
+    }
+
+    /// Pack a rate history into a fixed size buffer
+    pub fn pack_into_slice(&self, dst: &mut [u8; RATE_HISTORY_LEN]) {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (snapshot_count, next_index, snapshots_dst) =
+            mut_array_refs![dst, 1, 1, RATE_SNAPSHOT_LEN * MAX_RATE_SNAPSHOTS];
+        *snapshot_count = self.snapshot_count.to_le_bytes();
+        *next_index = self.next_index.to_le_bytes();
+        for (snapshot, snapshot_dst) in self
+            .snapshots
+            .iter()
+            .zip(snapshots_dst.chunks_exact_mut(RATE_SNAPSHOT_LEN))
+        {
+            let snapshot_dst = array_mut_ref![snapshot_dst, 0, RATE_SNAPSHOT_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (slot, cumulative_borrow_rate_wads, collateral_exchange_rate) =
+                mut_array_refs![snapshot_dst, 8, 16, 16];
+            *slot = snapshot.slot.to_le_bytes();
+            pack_decimal(
+                snapshot.cumulative_borrow_rate_wads,
+                cumulative_borrow_rate_wads,
+            );
+            pack_decimal(
+                Decimal::from(snapshot.collateral_exchange_rate),
+                collateral_exchange_rate,
+            );
+        }
+    }
+
+    /// Unpack a rate history from a fixed size buffer
+    pub fn unpack_from_slice(src: &[u8; RATE_HISTORY_LEN]) -> Result<Self, ProgramError> {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (snapshot_count, next_index, snapshots_src) =
+            array_refs![src, 1, 1, RATE_SNAPSHOT_LEN * MAX_RATE_SNAPSHOTS];
+        let snapshot_count = u8::from_le_bytes(*snapshot_count);
+        let next_index = u8::from_le_bytes(*next_index);
+        if snapshot_count as usize > MAX_RATE_SNAPSHOTS || next_index as usize >= MAX_RATE_SNAPSHOTS
+        {
+            msg!("Rate history cannot be unpacked");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut snapshots = [RateSnapshot::default(); MAX_RATE_SNAPSHOTS];
+        for (snapshot, snapshot_src) in snapshots
+            .iter_mut()
+            .zip(snapshots_src.chunks_exact(RATE_SNAPSHOT_LEN))
+        {
+            let snapshot_src = array_ref![snapshot_src, 0, RATE_SNAPSHOT_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (slot, cumulative_borrow_rate_wads, collateral_exchange_rate) =
+                array_refs![snapshot_src, 8, 16, 16];
+            snapshot.slot = u64::from_le_bytes(*slot);
+            snapshot.cumulative_borrow_rate_wads = unpack_decimal(cumulative_borrow_rate_wads);
+            snapshot.collateral_exchange_rate =
+                Rate::try_from(unpack_decimal(collateral_exchange_rate))?;
+        }
+
+        Ok(Self {
+            snapshot_count,
+            next_index,
+            snapshots,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(slot: Slot) -> RateSnapshot {
+        RateSnapshot {
+            slot,
+            ..RateSnapshot::default()
+        }
+    }
+
+    #[test]
+    fn record_rate_snapshots() {
+        let mut history = RateHistory::default();
+        assert!(history.latest().is_none());
+
+        assert!(history.record(snapshot(100)));
+        // interval has not elapsed
+        assert!(!history.record(snapshot(100 + RATE_SNAPSHOT_INTERVAL_SLOTS - 1)));
+        assert_eq!(history.latest().unwrap().slot, 100);
+
+        // wrap around the buffer, overwriting the oldest snapshots
+        for i in 1..=MAX_RATE_SNAPSHOTS as u64 + 1 {
+            assert!(history.record(snapshot(100 + i * RATE_SNAPSHOT_INTERVAL_SLOTS)));
+        }
+        assert_eq!(history.snapshot_count as usize, MAX_RATE_SNAPSHOTS);
+        let slots: Vec<Slot> = history.iter().map(|snapshot| snapshot.slot).collect();
+        assert_eq!(slots.len(), MAX_RATE_SNAPSHOTS);
+        assert_eq!(slots[0], 100 + 2 * RATE_SNAPSHOT_INTERVAL_SLOTS);
+        assert!(slots.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(
+            history
+                .at_or_before(100 + 3 * RATE_SNAPSHOT_INTERVAL_SLOTS + 1)
+                .unwrap()
+                .slot,
+            100 + 3 * RATE_SNAPSHOT_INTERVAL_SLOTS
+        );
+        assert!(history.at_or_before(100).is_none());
+    }
+
+    #[test]
+    fn realized_rates_between_snapshots() {
+        let earlier = RateSnapshot {
+            slot: 0,
+            cumulative_borrow_rate_wads: Decimal::one(),
+            collateral_exchange_rate: Rate::one(),
+        };
+        let later = RateSnapshot {
+            slot: RATE_SNAPSHOT_INTERVAL_SLOTS,
+            cumulative_borrow_rate_wads: Decimal::from_percent(110),
+            collateral_exchange_rate: Rate::from_percent(80),
+        };
+
+        assert_eq!(
+            later.borrow_interest_since(&earlier).unwrap(),
+            Rate::from_percent(10)
+        );
+        // 1 / 0.8 = 1.25 liquidity per collateral
+        assert_eq!(
+            later.supply_yield_since(&earlier).unwrap(),
+            Rate::from_percent(25)
+        );
+    }
+
+    #[test]
+    fn pack_and_unpack_rate_history() {
+        let mut history = RateHistory::default();
+        for i in 0..3 {
+            history.record(RateSnapshot {
+                slot: i * RATE_SNAPSHOT_INTERVAL_SLOTS,
+                cumulative_borrow_rate_wads: Decimal::from(i + 1),
+                collateral_exchange_rate: Rate::from_percent(100 - i as u8),
+            });
+        }
+
+        let mut packed = [0u8; RATE_HISTORY_LEN];
+        history.pack_into_slice(&mut packed);
+        assert_eq!(RateHistory::unpack_from_slice(&packed).unwrap(), history);
+    }
+}
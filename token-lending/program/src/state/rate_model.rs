@@ -0,0 +1,297 @@
+use crate::{
+    error::LendingError,
+    math::{Rate, TryAdd, TryDiv, TryMul, TrySub},
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{entrypoint::ProgramResult, msg, program_error::ProgramError};
+
+/// Maximum number of kinks in a multi-kink rate model
+pub const MAX_RATE_MODEL_KINKS: usize = 5;
+
+/// Length of a packed rate model
+pub const RATE_MODEL_LEN: usize = 22; // 1 + 1 + (2 + 2) * MAX_RATE_MODEL_KINKS
+
+const RATE_MODEL_KINK_LEN: usize = 4; // 2 + 2
+const MAX_UTILIZATION_BPS: u16 = 10_000;
+
+/// Point on a piecewise linear borrow rate curve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateModelKink {
+    /// Utilization rate, in basis points
+    pub utilization_bps: u16,
+    /// Borrow APY at the utilization rate, in basis points
+    pub borrow_rate_bps: u16,
+}
+
+/// Borrow rate model of a reserve. The packed tag doubles as the model version, so new models can
+/// be added without changing the layout of existing ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateModel {
+    /// Two linear segments through the min, optimal and max borrow rates of the reserve config
+    TwoSlope,
+    /// Linear segments between kinks, from 0% to 100% utilization
+    MultiKink {
+        /// Number of kinks used
+        kink_count: u8,
+        /// Kinks sorted by utilization rate, unused kinks are zeroed
+        kinks: [RateModelKink; MAX_RATE_MODEL_KINKS],
+    },
+    /// Constant borrow rate regardless of utilization
+    Fixed {
+        /// Borrow APY, in basis points
+        borrow_rate_bps: u16,
+    },
+}
+
+impl Default for RateModel {
+    fn default() -> Self {
+        RateModel::TwoSlope
+    }
+}
+
+impl RateModel {
+    /// Create a multi-kink rate model from up to `MAX_RATE_MODEL_KINKS` kinks
+    pub fn multi_kink(kinks: &[RateModelKink]) -> Result<Self, ProgramError> {
+        if kinks.len() > MAX_RATE_MODEL_KINKS {
+            msg!(
+                "Rate model cannot have more than {} kinks",
+                MAX_RATE_MODEL_KINKS
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        let mut model_kinks = [RateModelKink::default(); MAX_RATE_MODEL_KINKS];
+        model_kinks[..kinks.len()].copy_from_slice(kinks);
+        Ok(RateModel::MultiKink {
+            kink_count: kinks.len() as u8,
+            kinks: model_kinks,
+        })
+    }
+
+    /// Check that a multi-kink curve covers all utilization rates and never decreases
+    pub fn validate(&self) -> ProgramResult {
+        if let RateModel::MultiKink { kink_count, kinks } = self {
+            let kink_count = *kink_count as usize;
+            if !(2..=MAX_RATE_MODEL_KINKS).contains(&kink_count) {
+                msg!(
+                    "Rate model kink count must be in range [2, {}]",
+                    MAX_RATE_MODEL_KINKS
+                );
+                return Err(LendingError::InvalidConfig.into());
+            }
+            let kinks = &kinks[..kink_count];
+            if kinks[0].utilization_bps != 0
+                || kinks[kink_count - 1].utilization_bps != MAX_UTILIZATION_BPS
+            {
+                msg!("Rate model kinks must start at 0% and end at 100% utilization");
+                return Err(LendingError::InvalidConfig.into());
+            }
+            for pair in kinks.windows(2) {
+                if pair[1].utilization_bps <= pair[0].utilization_bps {
+                    msg!("Rate model kink utilization rates must be increasing");
+                    return Err(LendingError::InvalidConfig.into());
+                }
+                if pair[1].borrow_rate_bps < pair[0].borrow_rate_bps {
+                    msg!("Rate model kink borrow rates cannot decrease");
+                    return Err(LendingError::InvalidConfig.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pack a rate model into a fixed size buffer
+    pub fn pack_into_slice(&self, dst: &mut [u8; RATE_MODEL_LEN]) {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (tag, kink_count_dst, kinks_dst) =
+            mut_array_refs![dst, 1, 1, RATE_MODEL_KINK_LEN * MAX_RATE_MODEL_KINKS];
+        *kinks_dst = [0; RATE_MODEL_KINK_LEN * MAX_RATE_MODEL_KINKS];
+        *kink_count_dst = [0];
+        match self {
+            RateModel::TwoSlope => {
+                *tag = [0];
+            }
+            RateModel::MultiKink { kink_count, kinks } => {
+                *tag = [1];
+                *kink_count_dst = kink_count.to_le_bytes();
+                for (kink, kink_dst) in kinks
+                    .iter()
+                    .zip(kinks_dst.chunks_exact_mut(RATE_MODEL_KINK_LEN))
+                {
+                    let kink_dst = array_mut_ref![kink_dst, 0, RATE_MODEL_KINK_LEN];
+                    #[allow(clippy::ptr_offset_with_cast)]
+                    let (utilization_bps, borrow_rate_bps) = mut_array_refs![kink_dst, 2, 2];
+                    *utilization_bps = kink.utilization_bps.to_le_bytes();
+                    *borrow_rate_bps = kink.borrow_rate_bps.to_le_bytes();
+                }
+            }
+            RateModel::Fixed { borrow_rate_bps } => {
+                *tag = [2];
+                kinks_dst[..2].copy_from_slice(&borrow_rate_bps.to_le_bytes());
+            }
+        }
+    }
+
+    /// Unpack a rate model from a fixed size buffer
+    pub fn unpack_from_slice(src: &[u8; RATE_MODEL_LEN]) -> Result<Self, ProgramError> {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (tag, kink_count, kinks_src) =
+            array_refs![src, 1, 1, RATE_MODEL_KINK_LEN * MAX_RATE_MODEL_KINKS];
+        match tag[0] {
+            0 => Ok(RateModel::TwoSlope),
+            1 => {
+                let mut kinks = [RateModelKink::default(); MAX_RATE_MODEL_KINKS];
+                for (kink, kink_src) in kinks
+                    .iter_mut()
+                    .zip(kinks_src.chunks_exact(RATE_MODEL_KINK_LEN))
+                {
+                    let kink_src = array_ref![kink_src, 0, RATE_MODEL_KINK_LEN];
+                    #[allow(clippy::ptr_offset_with_cast)]
+                    let (utilization_bps, borrow_rate_bps) = array_refs![kink_src, 2, 2];
+                    kink.utilization_bps = u16::from_le_bytes(*utilization_bps);
+                    kink.borrow_rate_bps = u16::from_le_bytes(*borrow_rate_bps);
+                }
+                Ok(RateModel::MultiKink {
+                    kink_count: kink_count[0],
+                    kinks,
+                })
+            }
+            2 => Ok(RateModel::Fixed {
+                borrow_rate_bps: u16::from_le_bytes(*array_ref![kinks_src, 0, 2]),
+            }),
+            _ => {
+                msg!("Rate model cannot be unpacked");
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+}
+
+/// Calculate the borrow rate at a utilization rate by linear interpolation between kinks
+pub fn multi_kink_borrow_rate(
+    kinks: &[RateModelKink],
+    utilization_rate: Rate,
+) -> Result<Rate, ProgramError> {
+    for pair in kinks.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let end_utilization_rate = Rate::from_bps(end.utilization_bps);
+        if utilization_rate > end_utilization_rate {
+            continue;
+        }
+
+        let start_utilization_rate = Rate::from_bps(start.utilization_bps);
+        let normalized_rate = utilization_rate
+            .try_sub(start_utilization_rate)?
+            .try_div(end_utilization_rate.try_sub(start_utilization_rate)?)?;
+        let min_rate = Rate::from_bps(start.borrow_rate_bps);
+        let rate_range = Rate::from_bps(
+            end.borrow_rate_bps
+                .checked_sub(start.borrow_rate_bps)
+                .ok_or(LendingError::MathOverflow)?,
+        );
+
+        return normalized_rate.try_mul(rate_range)?.try_add(min_rate);
+    }
+
+    kinks
+        .last()
+        .map(|kink| Rate::from_bps(kink.borrow_rate_bps))
+        .ok_or_else(|| LendingError::InvalidConfig.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_kinks() -> [RateModelKink; 3] {
+        [
+            RateModelKink {
+                utilization_bps: 0,
+                borrow_rate_bps: 0,
+            },
+            RateModelKink {
+                utilization_bps: 8_000,
+                borrow_rate_bps: 400,
+            },
+            RateModelKink {
+                utilization_bps: 10_000,
+                borrow_rate_bps: 3_000,
+            },
+        ]
+    }
+
+    #[test]
+    fn multi_kink_interpolation() {
+        let kinks = test_kinks();
+        assert_eq!(
+            multi_kink_borrow_rate(&kinks, Rate::zero()).unwrap(),
+            Rate::zero()
+        );
+        // halfway to the first kink
+        assert_eq!(
+            multi_kink_borrow_rate(&kinks, Rate::from_percent(40)).unwrap(),
+            Rate::from_bps(200)
+        );
+        assert_eq!(
+            multi_kink_borrow_rate(&kinks, Rate::from_percent(80)).unwrap(),
+            Rate::from_bps(400)
+        );
+        // halfway through the second segment
+        assert_eq!(
+            multi_kink_borrow_rate(&kinks, Rate::from_percent(90)).unwrap(),
+            Rate::from_bps(1_700)
+        );
+        assert_eq!(
+            multi_kink_borrow_rate(&kinks, Rate::one()).unwrap(),
+            Rate::from_bps(3_000)
+        );
+    }
+
+    #[test]
+    fn validate_multi_kink() {
+        assert!(RateModel::multi_kink(&test_kinks())
+            .unwrap()
+            .validate()
+            .is_ok());
+
+        // does not reach 100% utilization
+        let mut kinks = test_kinks();
+        kinks[2].utilization_bps = 9_000;
+        assert_eq!(
+            RateModel::multi_kink(&kinks).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        // decreasing borrow rate
+        let mut kinks = test_kinks();
+        kinks[1].borrow_rate_bps = 3_001;
+        assert_eq!(
+            RateModel::multi_kink(&kinks).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        // single kink
+        assert_eq!(
+            RateModel::multi_kink(&test_kinks()[..1])
+                .unwrap()
+                .validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+    }
+
+    #[test]
+    fn pack_and_unpack_rate_model() {
+        for rate_model in [
+            RateModel::TwoSlope,
+            RateModel::multi_kink(&test_kinks()).unwrap(),
+            RateModel::Fixed {
+                borrow_rate_bps: 235,
+            },
+        ]
+        .iter()
+        {
+            let mut packed = [0u8; RATE_MODEL_LEN];
+            rate_model.pack_into_slice(&mut packed);
+            assert_eq!(RateModel::unpack_from_slice(&packed).unwrap(), *rate_model);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use super::*;
 use crate::{
     error::LendingError,
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
+    oracle::OracleType,
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
@@ -13,17 +14,24 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::{Pubkey, PUBKEY_BYTES},
 };
+use spl_token_swap::twap::{PriceAccumulators, PRICE_ACCUMULATORS_LEN};
 use std::{
     cmp::Ordering,
     convert::{TryFrom, TryInto},
 };
 
-/// Percentage of an obligation that can be repaid during each liquidation call
+/// Default percentage of an obligation borrow that can be repaid during each liquidation call
 pub const LIQUIDATION_CLOSE_FACTOR: u8 = 50;
 
 /// Obligation borrow amount that is small enough to close out
 pub const LIQUIDATION_CLOSE_AMOUNT: u64 = 2;
 
+/// Default maximum age of a reserve liquidity oracle price, in seconds
+pub const DEFAULT_MAX_ORACLE_AGE_SECONDS: u64 = 60;
+
+/// Max number of reserves that can be borrowed from against isolated collateral
+pub const MAX_ISOLATED_BORROW_RESERVES: usize = 3;
+
 /// Lending market reserve state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Reserve {
@@ -39,6 +47,11 @@ pub struct Reserve {
     pub collateral: ReserveCollateral,
     /// Reserve configuration values
     pub config: ReserveConfig,
+    /// Recent rate snapshots, empty for reserve accounts created without room for history
+    pub rate_history: RateHistory,
+    /// Earlier observation of a token swap oracle's price accumulators, that the next
+    /// time-weighted price is averaged from
+    pub price_observation: PriceAccumulators,
 }
 
 impl Reserve {
@@ -83,9 +96,60 @@ impl Reserve {
         Ok(liquidity_amount)
     }
 
+    /// Calculate the max amount of collateral that can be redeemed for available liquidity
+    pub fn max_redeem_collateral_amount(&self) -> Result<u64, ProgramError> {
+        self.collateral_exchange_rate()?
+            .liquidity_to_collateral(self.liquidity.available_amount)
+    }
+
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
+        match self.config.rate_model {
+            RateModel::TwoSlope => self.two_slope_borrow_rate(utilization_rate),
+            RateModel::MultiKink { kink_count, kinks } => {
+                multi_kink_borrow_rate(&kinks[..kink_count as usize], utilization_rate)
+            }
+            RateModel::Fixed { borrow_rate_bps } => Ok(Rate::from_bps(borrow_rate_bps)),
+        }
+    }
+
+    /// Calculate the current supply rate, the share of borrow interest paid to depositors after
+    /// the protocol take
+    pub fn current_supply_rate(&self) -> Result<Rate, ProgramError> {
+        let utilization_rate = self.liquidity.utilization_rate()?;
+        let depositor_share =
+            Rate::one().try_sub(Rate::from_percent(self.config.protocol_take_rate))?;
+        self.current_borrow_rate()?
+            .try_mul(utilization_rate)?
+            .try_mul(depositor_share)
+    }
+
+    /// Record a rate snapshot if the snapshot interval has elapsed since the latest one
+    pub fn record_rate_snapshot(&mut self, slot: Slot) -> ProgramResult {
+        let snapshot = RateSnapshot {
+            slot,
+            cumulative_borrow_rate_wads: self.liquidity.cumulative_borrow_rate_wads,
+            collateral_exchange_rate: self.collateral_exchange_rate()?.into(),
+        };
+        self.rate_history.record(snapshot);
+        Ok(())
+    }
+
+    /// Whether a reserve account of `len` bytes has room for rate history and a token swap price
+    /// observation
+    pub fn has_rate_history(len: usize) -> Result<bool, ProgramError> {
+        match len {
+            RESERVE_BASE_LEN => Ok(false),
+            RESERVE_LEN => Ok(true),
+            _ => {
+                msg!("Reserve account length is invalid");
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+
+    fn two_slope_borrow_rate(&self, utilization_rate: Rate) -> Result<Rate, ProgramError> {
         let optimal_utilization_rate = Rate::from_percent(self.config.optimal_utilization_rate);
         let low_utilization = utilization_rate < optimal_utilization_rate;
         if low_utilization || self.config.optimal_utilization_rate == 100 {
@@ -125,17 +189,176 @@ impl Reserve {
         self.collateral.exchange_rate(total_liquidity)
     }
 
+    /// Market value of an amount of collateral, in quote currency
+    pub fn collateral_market_value(&self, collateral_amount: u64) -> Result<Decimal, ProgramError> {
+        let liquidity_amount = self
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(collateral_amount.into())?;
+        self.liquidity_market_value(liquidity_amount)
+    }
+
+    /// Market value of an amount of liquidity, in quote currency
+    pub fn liquidity_market_value(
+        &self,
+        liquidity_amount: Decimal,
+    ) -> Result<Decimal, ProgramError> {
+        // @TODO: add lookup table https://git.io/JOCYq
+        let decimals = 10u64
+            .checked_pow(self.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+
+        liquidity_amount
+            .try_mul(self.liquidity.market_price)?
+            .try_div(decimals)
+    }
+
+    /// Add debt borrowed from an isolated borrow reserve against this reserve's isolated
+    /// collateral, up to the debt ceiling. Debt already owed to the other isolated borrow reserves
+    /// is valued with `other_borrow_reserves`, given in config order without the borrow reserve.
+    pub fn borrow_isolated(
+        &mut self,
+        borrow_reserve_pubkey: &Pubkey,
+        borrow_reserve: &Reserve,
+        borrow_amount: Decimal,
+        other_borrow_reserves: &[Reserve],
+    ) -> ProgramResult {
+        let borrow_index = self.isolated_borrow_index(borrow_reserve_pubkey)?;
+        let mut other_borrow_reserves = other_borrow_reserves.iter();
+        let mut isolated_borrowed_value = Decimal::zero();
+        for (index, isolated_borrow_reserve) in
+            self.config.isolated_borrow_reserves.iter().enumerate()
+        {
+            if *isolated_borrow_reserve == Pubkey::default() {
+                continue;
+            }
+            let reserve = if index == borrow_index {
+                borrow_reserve
+            } else {
+                other_borrow_reserves
+                    .next()
+                    .ok_or(LendingError::InvalidAccountInput)?
+            };
+            let borrowed_amount_wads = self.collateral.isolated_borrowed_scaled_wads[index]
+                .try_mul(reserve.liquidity.cumulative_borrow_rate_wads)?;
+            isolated_borrowed_value = isolated_borrowed_value
+                .try_add(reserve.liquidity_market_value(borrowed_amount_wads)?)?;
+        }
+
+        isolated_borrowed_value = isolated_borrowed_value
+            .try_add(borrow_reserve.liquidity_market_value(borrow_amount)?)?;
+        if isolated_borrowed_value > Decimal::from(self.config.isolated_debt_ceiling) {
+            msg!("Borrow value would exceed the isolated collateral debt ceiling");
+            return Err(LendingError::IsolatedDebtCeilingExceeded.into());
+        }
+
+        let scaled_wads = &mut self.collateral.isolated_borrowed_scaled_wads[borrow_index];
+        *scaled_wads = scaled_wads.try_add(
+            borrow_amount.try_div(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?,
+        )?;
+        Ok(())
+    }
+
+    /// Remove debt repaid to an isolated borrow reserve against this reserve's isolated
+    /// collateral. Debt owed to a reserve that is no longer tracked is ignored so repayment and
+    /// liquidation are never blocked.
+    pub fn repay_isolated(
+        &mut self,
+        repay_reserve_pubkey: &Pubkey,
+        repay_reserve: &Reserve,
+        settle_amount: Decimal,
+    ) -> ProgramResult {
+        if let Ok(index) = self.isolated_borrow_index(repay_reserve_pubkey) {
+            let settle_scaled_wads =
+                settle_amount.try_div(repay_reserve.liquidity.cumulative_borrow_rate_wads)?;
+            let scaled_wads = &mut self.collateral.isolated_borrowed_scaled_wads[index];
+            // rounding can leave obligations owing slightly more than is tracked
+            *scaled_wads = scaled_wads
+                .try_sub(settle_scaled_wads)
+                .unwrap_or_else(|_| Decimal::zero());
+        }
+        Ok(())
+    }
+
+    /// Replace the reserve config, moving isolated debt to the new position of its borrow reserve.
+    /// Obligations record whether their collateral was isolated when it was deposited, so whether
+    /// the reserve is isolated can only change while it has no collateral or isolated debt.
+    pub fn set_config(&mut self, config: ReserveConfig) -> ProgramResult {
+        if config.isolated != self.config.isolated
+            && (self.collateral.mint_total_supply != 0
+                || self
+                    .collateral
+                    .isolated_borrowed_scaled_wads
+                    .iter()
+                    .any(|scaled_wads| *scaled_wads != Decimal::zero()))
+        {
+            msg!(
+                "Reserve isolation cannot change while the reserve has collateral or isolated debt"
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+
+        let mut isolated_borrowed_scaled_wads = [Decimal::zero(); MAX_ISOLATED_BORROW_RESERVES];
+        for (borrow_reserve, scaled_wads) in self
+            .config
+            .isolated_borrow_reserves
+            .iter()
+            .zip(self.collateral.isolated_borrowed_scaled_wads.iter())
+        {
+            if *scaled_wads == Decimal::zero() {
+                continue;
+            }
+            let index = config
+                .isolated_borrow_reserves
+                .iter()
+                .position(|pubkey| pubkey == borrow_reserve)
+                .ok_or_else(|| {
+                    msg!("Isolated borrow reserve with outstanding debt cannot be removed");
+                    LendingError::InvalidConfig
+                })?;
+            isolated_borrowed_scaled_wads[index] = *scaled_wads;
+        }
+        self.collateral.isolated_borrowed_scaled_wads = isolated_borrowed_scaled_wads;
+        self.config = config;
+        Ok(())
+    }
+
+    fn isolated_borrow_index(&self, borrow_reserve_pubkey: &Pubkey) -> Result<usize, ProgramError> {
+        if *borrow_reserve_pubkey == Pubkey::default() {
+            return Err(LendingError::IsolatedBorrowNotAllowed.into());
+        }
+        self.config
+            .isolated_borrow_reserves
+            .iter()
+            .position(|pubkey| pubkey == borrow_reserve_pubkey)
+            .ok_or_else(|| LendingError::IsolatedBorrowNotAllowed.into())
+    }
+
     /// Update borrow rate and accrue interest
     pub fn accrue_interest(&mut self, current_slot: Slot) -> ProgramResult {
         let slots_elapsed = self.last_update.slots_elapsed(current_slot)?;
         if slots_elapsed > 0 {
             let current_borrow_rate = self.current_borrow_rate()?;
-            self.liquidity
-                .compound_interest(current_borrow_rate, slots_elapsed)?;
+            let protocol_take_rate = Rate::from_percent(self.config.protocol_take_rate);
+            self.liquidity.compound_interest(
+                current_borrow_rate,
+                slots_elapsed,
+                protocol_take_rate,
+            )?;
         }
         Ok(())
     }
 
+    /// Accrue interest up to the current slot for a reserve refreshed within the stale window, so
+    /// amounts are calculated at current rates. The last update slot is advanced so the interest
+    /// is not accrued twice, and the reserve is marked stale so its price is refreshed before it
+    /// is used again.
+    pub fn accrue_interest_and_mark_stale(&mut self, current_slot: Slot) -> ProgramResult {
+        self.accrue_interest(current_slot)?;
+        self.last_update.slot = current_slot;
+        self.last_update.mark_stale();
+        Ok(())
+    }
+
     /// Borrow liquidity up to a maximum market value
     pub fn calculate_borrow(
         &self,
@@ -211,15 +434,83 @@ impl Reserve {
         })
     }
 
-    /// Liquidate some or all of an unhealthy obligation
+    /// Calculate the amount of liquidity to flash loan and the fee owed on it
+    pub fn calculate_flash_loan(
+        &self,
+        amount_to_flash_loan: u64,
+    ) -> Result<CalculateFlashLoanResult, ProgramError> {
+        let flash_loan_amount = if amount_to_flash_loan == u64::MAX {
+            self.liquidity.available_amount
+        } else {
+            amount_to_flash_loan
+        };
+        if flash_loan_amount > self.liquidity.available_amount {
+            msg!("Flash loan amount cannot exceed available amount");
+            return Err(LendingError::InsufficientLiquidity.into());
+        }
+
+        let flash_loan_fee = self
+            .config
+            .fees
+            .calculate_flash_loan_fee(flash_loan_amount.into())?;
+        let repay_amount = flash_loan_amount
+            .checked_add(flash_loan_fee)
+            .ok_or(LendingError::MathOverflow)?;
+
+        Ok(CalculateFlashLoanResult {
+            flash_loan_amount,
+            flash_loan_fee,
+            repay_amount,
+        })
+    }
+
+    /// Record a repaid flash loan fee as liquidity, increasing the value of collateral held by
+    /// depositors
+    pub fn settle_flash_loan_fee(&mut self, flash_loan_fee: u64) -> ProgramResult {
+        self.liquidity.deposit(flash_loan_fee)
+    }
+
+    /// Calculate the liquidation bonus for collateral in this reserve. In auction mode the bonus
+    /// grows linearly from zero to the configured bonus over the auction slots, starting when the
+    /// obligation was first seen unhealthy.
+    pub fn liquidation_bonus(
+        &self,
+        obligation: &Obligation,
+        current_slot: Slot,
+    ) -> Result<Rate, ProgramError> {
+        let max_bonus = Rate::from_percent(self.config.liquidation_bonus);
+        let auction_slots = self.config.liquidation_auction_slots;
+        // reserves created before liquidation auctions unpack zero auction slots
+        if auction_slots == 0 {
+            return Ok(max_bonus);
+        }
+
+        // legacy obligations cannot record when they became unhealthy, so keep the fixed bonus
+        if obligation.unhealthy_since_slot == 0 {
+            return Ok(max_bonus);
+        }
+
+        let unhealthy_slots = current_slot.saturating_sub(obligation.unhealthy_since_slot);
+        if unhealthy_slots >= auction_slots {
+            return Ok(max_bonus);
+        }
+        max_bonus.try_mul(unhealthy_slots)?.try_div(auction_slots)
+    }
+
+    /// Liquidate some or all of an unhealthy obligation, repaying at most the close factor of the
+    /// liquidity borrowed from the repay reserve
     pub fn calculate_liquidation(
         &self,
         amount_to_liquidate: u64,
         obligation: &Obligation,
         liquidity: &ObligationLiquidity,
         collateral: &ObligationCollateral,
+        close_factor: Rate,
+        current_slot: Slot,
     ) -> Result<CalculateLiquidationResult, ProgramError> {
-        let bonus_rate = Rate::from_percent(self.config.liquidation_bonus).try_add(Rate::one())?;
+        let bonus_rate = self
+            .liquidation_bonus(obligation, current_slot)?
+            .try_add(Rate::one())?;
 
         let max_amount = if amount_to_liquidate == u64::MAX {
             liquidity.borrowed_amount_wads
@@ -258,7 +549,7 @@ impl Reserve {
         } else {
             // calculate settle_amount and withdraw_amount, repay_amount is settle_amount rounded
             let liquidation_amount = obligation
-                .max_liquidation_amount(liquidity)?
+                .max_liquidation_amount(liquidity, close_factor)?
                 .min(max_amount);
             let liquidation_pct = liquidation_amount.try_div(liquidity.borrowed_amount_wads)?;
             let liquidation_value = liquidity
@@ -333,6 +624,17 @@ pub struct CalculateRepayResult {
     pub repay_amount: u64,
 }
 
+/// Calculate flash loan result
+#[derive(Debug)]
+pub struct CalculateFlashLoanResult {
+    /// Amount of liquidity lent out for the duration of the flash loan
+    pub flash_loan_amount: u64,
+    /// Fee owed to the reserve depositors
+    pub flash_loan_fee: u64,
+    /// Amount that must be returned to the reserve liquidity supply, including the fee
+    pub repay_amount: u64,
+}
+
 /// Calculate liquidation result
 #[derive(Debug)]
 pub struct CalculateLiquidationResult {
@@ -358,6 +660,10 @@ pub struct ReserveLiquidity {
     pub fee_receiver: Pubkey,
     /// Optional reserve liquidity oracle state account
     pub oracle_pubkey: COption<Pubkey>,
+    /// Reserve liquidity oracle type
+    pub oracle_type: OracleType,
+    /// Quote currency decimals used to scale oracle prices
+    pub quote_decimals: u8,
     /// Reserve liquidity available
     pub available_amount: u64,
     /// Reserve liquidity borrowed
@@ -366,6 +672,8 @@ pub struct ReserveLiquidity {
     pub cumulative_borrow_rate_wads: Decimal,
     /// Reserve liquidity market price in quote currency
     pub market_price: u64,
+    /// Interest set aside for the lending market owner, not yet withdrawn to the fee receiver
+    pub accumulated_protocol_fees_wads: Decimal,
 }
 
 impl ReserveLiquidity {
@@ -377,16 +685,21 @@ impl ReserveLiquidity {
             supply_pubkey: params.supply_pubkey,
             fee_receiver: params.fee_receiver,
             oracle_pubkey: params.oracle_pubkey,
+            oracle_type: params.oracle_type,
+            quote_decimals: params.quote_decimals,
             cumulative_borrow_rate_wads: Decimal::one(),
             market_price: params.market_price,
             available_amount: 0,
             borrowed_amount_wads: Decimal::zero(),
+            accumulated_protocol_fees_wads: Decimal::zero(),
         }
     }
 
-    /// Calculate the total reserve supply including active loans
+    /// Calculate the total reserve supply including active loans, excluding protocol fees
     pub fn total_supply(&self) -> Result<Decimal, ProgramError> {
-        Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)
+        Decimal::from(self.available_amount)
+            .try_add(self.borrowed_amount_wads)?
+            .try_sub(self.accumulated_protocol_fees_wads)
     }
 
     /// Add liquidity to available amount
@@ -439,6 +752,25 @@ impl ReserveLiquidity {
         Ok(())
     }
 
+    /// Remove accumulated protocol fees from available liquidity and return the amount to transfer
+    /// to the fee receiver. Fees can only be withdrawn up to the available amount.
+    pub fn withdraw_protocol_fees(&mut self) -> Result<u64, ProgramError> {
+        let withdraw_amount = self
+            .accumulated_protocol_fees_wads
+            .try_floor_u64()?
+            .min(self.available_amount);
+
+        self.available_amount = self
+            .available_amount
+            .checked_sub(withdraw_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        self.accumulated_protocol_fees_wads = self
+            .accumulated_protocol_fees_wads
+            .try_sub(withdraw_amount.into())?;
+
+        Ok(withdraw_amount)
+    }
+
     /// Calculate the liquidity utilization rate of the reserve
     pub fn utilization_rate(&self) -> Result<Rate, ProgramError> {
         let total_supply = self.total_supply()?;
@@ -448,11 +780,13 @@ impl ReserveLiquidity {
         self.borrowed_amount_wads.try_div(total_supply)?.try_into()
     }
 
-    /// Compound current borrow rate over elapsed slots
+    /// Compound current borrow rate over elapsed slots, setting aside the protocol take rate of
+    /// the accrued interest as protocol fees
     fn compound_interest(
         &mut self,
         current_borrow_rate: Rate,
         slots_elapsed: u64,
+        protocol_take_rate: Rate,
     ) -> ProgramResult {
         let slot_interest_rate = current_borrow_rate.try_div(SLOTS_PER_YEAR)?;
         let compounded_interest_rate = Rate::one()
@@ -461,9 +795,17 @@ impl ReserveLiquidity {
         self.cumulative_borrow_rate_wads = self
             .cumulative_borrow_rate_wads
             .try_mul(compounded_interest_rate)?;
-        self.borrowed_amount_wads = self
+
+        let new_borrowed_amount_wads = self
             .borrowed_amount_wads
             .try_mul(compounded_interest_rate)?;
+        let protocol_fees_wads = new_borrowed_amount_wads
+            .try_sub(self.borrowed_amount_wads)?
+            .try_mul(protocol_take_rate)?;
+        self.accumulated_protocol_fees_wads = self
+            .accumulated_protocol_fees_wads
+            .try_add(protocol_fees_wads)?;
+        self.borrowed_amount_wads = new_borrowed_amount_wads;
         Ok(())
     }
 }
@@ -480,6 +822,10 @@ pub struct NewReserveLiquidityParams {
     pub fee_receiver: Pubkey,
     /// Optional reserve liquidity oracle state account
     pub oracle_pubkey: COption<Pubkey>,
+    /// Reserve liquidity oracle type
+    pub oracle_type: OracleType,
+    /// Quote currency decimals used to scale oracle prices
+    pub quote_decimals: u8,
     /// Reserve liquidity market price in quote currency
     pub market_price: u64,
 }
@@ -493,6 +839,9 @@ pub struct ReserveCollateral {
     pub mint_total_supply: u64,
     /// Reserve collateral supply address
     pub supply_pubkey: Pubkey,
+    /// Debt backed by this collateral while isolated, for each isolated borrow reserve in config
+    /// order, as borrowed liquidity wads divided by that reserve's cumulative borrow rate
+    pub isolated_borrowed_scaled_wads: [Decimal; MAX_ISOLATED_BORROW_RESERVES],
 }
 
 impl ReserveCollateral {
@@ -502,6 +851,7 @@ impl ReserveCollateral {
             mint_pubkey: params.mint_pubkey,
             mint_total_supply: 0,
             supply_pubkey: params.supply_pubkey,
+            isolated_borrowed_scaled_wads: [Decimal::zero(); MAX_ISOLATED_BORROW_RESERVES],
         }
     }
 
@@ -607,6 +957,38 @@ pub struct ReserveConfig {
     pub max_borrow_rate: u8,
     /// Program owner fees assessed, separate from gains due to interest accrual
     pub fees: ReserveFees,
+    /// Maximum age of the reserve liquidity oracle price, in seconds
+    pub max_oracle_age_seconds: u64,
+    /// Share of accrued interest set aside for the lending market owner, as a percentage
+    pub protocol_take_rate: u8,
+    /// Borrow rate model, the two-slope model uses the min, optimal and max borrow rates
+    pub rate_model: RateModel,
+    /// Whether obligations depositing this collateral can only borrow from the isolated borrow
+    /// reserves, up to the isolated debt ceiling
+    pub isolated: bool,
+    /// Max market value of debt backed by this collateral while isolated, in quote currency
+    pub isolated_debt_ceiling: u64,
+    /// Reserves that can be borrowed from against this collateral while isolated, unused entries
+    /// are the default pubkey
+    pub isolated_borrow_reserves: [Pubkey; MAX_ISOLATED_BORROW_RESERVES],
+    /// Max total supply of liquidity after a deposit, 0 if deposits are not limited
+    pub deposit_limit: u64,
+    /// Max total borrowed liquidity after a borrow, 0 if borrows are not limited
+    pub borrow_limit: u64,
+    /// Percentage of an obligation borrow that can be repaid during each liquidation call,
+    /// `LIQUIDATION_CLOSE_FACTOR` for reserves created before it was configurable
+    pub liquidation_close_factor: u8,
+    /// Number of slots over which the liquidation bonus grows from zero to the configured bonus
+    /// once an obligation is unhealthy, 0 for a fixed liquidation bonus
+    pub liquidation_auction_slots: u64,
+}
+
+impl ReserveConfig {
+    /// Check if a reserve can be borrowed from against this collateral while isolated
+    pub fn is_isolated_borrow_reserve(&self, borrow_reserve: &Pubkey) -> bool {
+        *borrow_reserve != Pubkey::default()
+            && self.isolated_borrow_reserves.contains(borrow_reserve)
+    }
 }
 
 /// Additional fee information on a reserve
@@ -623,6 +1005,10 @@ pub struct ReserveFees {
     /// 0.01% (1 basis point) = 100_000_000_000_000
     /// 0.00001% (Aave borrow fee) = 100_000_000_000
     pub borrow_fee_wad: u64,
+    /// Fee assessed on `FlashLoan`, expressed as a Wad.
+    /// Must be between 0 and 10^18, such that 10^18 = 1.
+    /// The fee is credited to the reserve's depositors.
+    pub flash_loan_fee_wad: u64,
     /// Amount of fee going to host account, if provided in liquidate and repay
     pub host_fee_percentage: u8,
 }
@@ -672,6 +1058,23 @@ impl ReserveFees {
             Ok((0, 0))
         }
     }
+
+    /// Calculate the fee owed on a flash loan
+    pub fn calculate_flash_loan_fee(
+        &self,
+        flash_loan_amount: Decimal,
+    ) -> Result<u64, ProgramError> {
+        let flash_loan_fee_rate = Rate::from_scaled_val(self.flash_loan_fee_wad);
+        if flash_loan_fee_rate > Rate::zero() && flash_loan_amount > Decimal::zero() {
+            // 1 token minimum so that small flash loans cannot be taken for free
+            Ok(flash_loan_amount
+                .try_mul(flash_loan_fee_rate)?
+                .try_ceil_u64()?
+                .max(1))
+        } else {
+            Ok(0)
+        }
+    }
 }
 
 /// Calculate fees exlusive or inclusive of an amount
@@ -689,13 +1092,38 @@ impl IsInitialized for Reserve {
     }
 }
 
-const RESERVE_LEN: usize = 567; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + (4 + 32) + 16 + 8 + 8 + 16 + 32 + 32 + 8 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 1 + 256
-                                // @TODO: break this up by reserve / liquidity / collateral / config https://git.io/JOCca
+const RESERVE_BASE_LEN: usize = 567; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + (4 + 32) + 16 + 8 + 8 + 16 + 32 + 32 + 8 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 1 + 8 + 1 + 1 + 8 + 1 + 16 + RATE_MODEL_LEN + 1 + 8 + (32 * MAX_ISOLATED_BORROW_RESERVES) + (16 * MAX_ISOLATED_BORROW_RESERVES) + 8 + 8 + 1 + 8 + 21
+                                     // @TODO: break this up by reserve / liquidity / collateral / config https://git.io/JOCca
+const RESERVE_LEN: usize = 1617; // RESERVE_BASE_LEN + RATE_HISTORY_LEN + PRICE_ACCUMULATORS_LEN
+
 impl Pack for Reserve {
+    /// Length of a reserve with rate history and a price observation, reserves created before
+    /// rate history was added are `RESERVE_BASE_LEN` bytes and are unpacked with an empty history
+    /// and observation
     const LEN: usize = RESERVE_LEN;
 
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::has_rate_history(input.len())?;
+        Self::unpack_from_slice(input)
+    }
+
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        Self::has_rate_history(dst.len())?;
+        src.pack_into_slice(dst);
+        Ok(())
+    }
+
     fn pack_into_slice(&self, output: &mut [u8]) {
-        let output = array_mut_ref![output, 0, RESERVE_LEN];
+        let (output, extension) = output.split_at_mut(RESERVE_BASE_LEN);
+        if extension.len() >= RATE_HISTORY_LEN + PRICE_ACCUMULATORS_LEN {
+            let extension = array_mut_ref![extension, 0, RATE_HISTORY_LEN + PRICE_ACCUMULATORS_LEN];
+            let (rate_history, price_observation) =
+                mut_array_refs![extension, RATE_HISTORY_LEN, PRICE_ACCUMULATORS_LEN];
+            self.rate_history.pack_into_slice(rate_history);
+            self.price_observation.pack_into_slice(price_observation);
+        }
+
+        let output = array_mut_ref![output, 0, RESERVE_BASE_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -723,6 +1151,21 @@ impl Pack for Reserve {
             config_max_borrow_rate,
             config_fees_borrow_fee_wad,
             config_fees_host_fee_percentage,
+            config_fees_flash_loan_fee_wad,
+            liquidity_oracle_type,
+            liquidity_quote_decimals,
+            config_max_oracle_age_seconds,
+            config_protocol_take_rate,
+            liquidity_accumulated_protocol_fees_wads,
+            config_rate_model,
+            config_isolated,
+            config_isolated_debt_ceiling,
+            config_isolated_borrow_reserves,
+            collateral_isolated_borrowed_scaled_wads,
+            config_deposit_limit,
+            config_borrow_limit,
+            config_liquidation_close_factor,
+            config_liquidation_auction_slots,
             _padding,
         ) = mut_array_refs![
             output,
@@ -751,7 +1194,22 @@ impl Pack for Reserve {
             1,
             8,
             1,
-            256
+            8,
+            1,
+            1,
+            8,
+            1,
+            16,
+            RATE_MODEL_LEN,
+            1,
+            8,
+            PUBKEY_BYTES * MAX_ISOLATED_BORROW_RESERVES,
+            16 * MAX_ISOLATED_BORROW_RESERVES,
+            8,
+            8,
+            1,
+            8,
+            21
         ];
 
         // reserve
@@ -792,11 +1250,69 @@ impl Pack for Reserve {
         *config_max_borrow_rate = self.config.max_borrow_rate.to_le_bytes();
         *config_fees_borrow_fee_wad = self.config.fees.borrow_fee_wad.to_le_bytes();
         *config_fees_host_fee_percentage = self.config.fees.host_fee_percentage.to_le_bytes();
+        *config_fees_flash_loan_fee_wad = self.config.fees.flash_loan_fee_wad.to_le_bytes();
+
+        // liquidity oracle
+        *liquidity_oracle_type = (self.liquidity.oracle_type as u8).to_le_bytes();
+        *liquidity_quote_decimals = self.liquidity.quote_decimals.to_le_bytes();
+        *config_max_oracle_age_seconds = self.config.max_oracle_age_seconds.to_le_bytes();
+
+        // protocol fees
+        *config_protocol_take_rate = self.config.protocol_take_rate.to_le_bytes();
+        pack_decimal(
+            self.liquidity.accumulated_protocol_fees_wads,
+            liquidity_accumulated_protocol_fees_wads,
+        );
+
+        // rate model
+        self.config.rate_model.pack_into_slice(config_rate_model);
+
+        // isolated collateral
+        pack_bool(self.config.isolated, config_isolated);
+        *config_isolated_debt_ceiling = self.config.isolated_debt_ceiling.to_le_bytes();
+        for (borrow_reserve, dst) in self
+            .config
+            .isolated_borrow_reserves
+            .iter()
+            .zip(config_isolated_borrow_reserves.chunks_exact_mut(PUBKEY_BYTES))
+        {
+            dst.copy_from_slice(borrow_reserve.as_ref());
+        }
+        for (scaled_wads, dst) in self
+            .collateral
+            .isolated_borrowed_scaled_wads
+            .iter()
+            .zip(collateral_isolated_borrowed_scaled_wads.chunks_exact_mut(16))
+        {
+            pack_decimal(*scaled_wads, array_mut_ref![dst, 0, 16]);
+        }
+
+        // limits
+        *config_deposit_limit = self.config.deposit_limit.to_le_bytes();
+        *config_borrow_limit = self.config.borrow_limit.to_le_bytes();
+
+        // liquidation
+        *config_liquidation_close_factor = self.config.liquidation_close_factor.to_le_bytes();
+        *config_liquidation_auction_slots = self.config.liquidation_auction_slots.to_le_bytes();
     }
 
     /// Unpacks a byte buffer into a [ReserveInfo](struct.ReserveInfo.html).
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![input, 0, RESERVE_LEN];
+        let (input, extension) = input.split_at(RESERVE_BASE_LEN);
+        let (rate_history, price_observation) =
+            if extension.len() >= RATE_HISTORY_LEN + PRICE_ACCUMULATORS_LEN {
+                let extension = array_ref![extension, 0, RATE_HISTORY_LEN + PRICE_ACCUMULATORS_LEN];
+                let (rate_history, price_observation) =
+                    array_refs![extension, RATE_HISTORY_LEN, PRICE_ACCUMULATORS_LEN];
+                (
+                    RateHistory::unpack_from_slice(rate_history)?,
+                    PriceAccumulators::unpack_from_slice(price_observation),
+                )
+            } else {
+                (RateHistory::default(), PriceAccumulators::default())
+            };
+
+        let input = array_ref![input, 0, RESERVE_BASE_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -824,6 +1340,21 @@ impl Pack for Reserve {
             config_max_borrow_rate,
             config_fees_borrow_fee_wad,
             config_fees_host_fee_percentage,
+            config_fees_flash_loan_fee_wad,
+            liquidity_oracle_type,
+            liquidity_quote_decimals,
+            config_max_oracle_age_seconds,
+            config_protocol_take_rate,
+            liquidity_accumulated_protocol_fees_wads,
+            config_rate_model,
+            config_isolated,
+            config_isolated_debt_ceiling,
+            config_isolated_borrow_reserves,
+            collateral_isolated_borrowed_scaled_wads,
+            config_deposit_limit,
+            config_borrow_limit,
+            config_liquidation_close_factor,
+            config_liquidation_auction_slots,
             _padding,
         ) = array_refs![
             input,
@@ -852,7 +1383,22 @@ impl Pack for Reserve {
             1,
             8,
             1,
-            256
+            8,
+            1,
+            1,
+            8,
+            1,
+            16,
+            RATE_MODEL_LEN,
+            1,
+            8,
+            PUBKEY_BYTES * MAX_ISOLATED_BORROW_RESERVES,
+            16 * MAX_ISOLATED_BORROW_RESERVES,
+            8,
+            8,
+            1,
+            8,
+            21
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -861,6 +1407,21 @@ impl Pack for Reserve {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        let mut isolated_borrow_reserves = [Pubkey::default(); MAX_ISOLATED_BORROW_RESERVES];
+        for (borrow_reserve, src) in isolated_borrow_reserves
+            .iter_mut()
+            .zip(config_isolated_borrow_reserves.chunks_exact(PUBKEY_BYTES))
+        {
+            *borrow_reserve = Pubkey::new(src);
+        }
+        let mut isolated_borrowed_scaled_wads = [Decimal::zero(); MAX_ISOLATED_BORROW_RESERVES];
+        for (scaled_wads, src) in isolated_borrowed_scaled_wads
+            .iter_mut()
+            .zip(collateral_isolated_borrowed_scaled_wads.chunks_exact(16))
+        {
+            *scaled_wads = unpack_decimal(array_ref![src, 0, 16]);
+        }
+
         Ok(Self {
             version,
             last_update: LastUpdate {
@@ -874,15 +1435,21 @@ impl Pack for Reserve {
                 supply_pubkey: Pubkey::new_from_array(*liquidity_supply_pubkey),
                 fee_receiver: Pubkey::new_from_array(*liquidity_fee_receiver),
                 oracle_pubkey: unpack_coption_key(liquidity_oracle_pubkey)?,
+                oracle_type: OracleType::try_from(u8::from_le_bytes(*liquidity_oracle_type))?,
+                quote_decimals: u8::from_le_bytes(*liquidity_quote_decimals),
                 available_amount: u64::from_le_bytes(*liquidity_available_amount),
                 borrowed_amount_wads: unpack_decimal(liquidity_borrowed_amount_wads),
                 cumulative_borrow_rate_wads: unpack_decimal(liquidity_cumulative_borrow_rate_wads),
                 market_price: u64::from_le_bytes(*liquidity_market_price),
+                accumulated_protocol_fees_wads: unpack_decimal(
+                    liquidity_accumulated_protocol_fees_wads,
+                ),
             },
             collateral: ReserveCollateral {
                 mint_pubkey: Pubkey::new_from_array(*collateral_mint_pubkey),
                 mint_total_supply: u64::from_le_bytes(*collateral_mint_total_supply),
                 supply_pubkey: Pubkey::new_from_array(*collateral_supply_pubkey),
+                isolated_borrowed_scaled_wads,
             },
             config: ReserveConfig {
                 optimal_utilization_rate: u8::from_le_bytes(*config_optimal_utilization_rate),
@@ -894,9 +1461,32 @@ impl Pack for Reserve {
                 max_borrow_rate: u8::from_le_bytes(*config_max_borrow_rate),
                 fees: ReserveFees {
                     borrow_fee_wad: u64::from_le_bytes(*config_fees_borrow_fee_wad),
+                    flash_loan_fee_wad: u64::from_le_bytes(*config_fees_flash_loan_fee_wad),
                     host_fee_percentage: u8::from_le_bytes(*config_fees_host_fee_percentage),
                 },
+                // reserves created before the max oracle age was added read zeroed padding
+                max_oracle_age_seconds: match u64::from_le_bytes(*config_max_oracle_age_seconds) {
+                    0 => DEFAULT_MAX_ORACLE_AGE_SECONDS,
+                    max_oracle_age_seconds => max_oracle_age_seconds,
+                },
+                protocol_take_rate: u8::from_le_bytes(*config_protocol_take_rate),
+                rate_model: RateModel::unpack_from_slice(config_rate_model)?,
+                isolated: unpack_bool(config_isolated)?,
+                isolated_debt_ceiling: u64::from_le_bytes(*config_isolated_debt_ceiling),
+                isolated_borrow_reserves,
+                deposit_limit: u64::from_le_bytes(*config_deposit_limit),
+                borrow_limit: u64::from_le_bytes(*config_borrow_limit),
+                // reserves created before the close factor was configurable read zeroed padding
+                liquidation_close_factor: match u8::from_le_bytes(*config_liquidation_close_factor)
+                {
+                    0 => LIQUIDATION_CLOSE_FACTOR,
+                    liquidation_close_factor => liquidation_close_factor,
+                },
+                // zeroed padding means a fixed liquidation bonus, as before auctions were added
+                liquidation_auction_slots: u64::from_le_bytes(*config_liquidation_auction_slots),
             },
+            rate_history,
+            price_observation,
         })
     }
 }
@@ -997,6 +1587,48 @@ mod test {
             }
         }
 
+        #[test]
+        fn current_borrow_rate_multi_kink(
+            total_liquidity in 0..=MAX_LIQUIDITY,
+            borrowed_percent in 0..=WAD,
+            fixed_borrow_rate_bps in 0..=u16::MAX,
+        ) {
+            let borrowed_amount_wads = Decimal::from(total_liquidity).try_mul(Rate::from_scaled_val(borrowed_percent))?;
+            let liquidity = ReserveLiquidity {
+                borrowed_amount_wads,
+                available_amount: total_liquidity - borrowed_amount_wads.try_round_u64()?,
+                ..ReserveLiquidity::default()
+            };
+            let kinks = [
+                RateModelKink { utilization_bps: 0, borrow_rate_bps: 100 },
+                RateModelKink { utilization_bps: 4_500, borrow_rate_bps: 235 },
+                RateModelKink { utilization_bps: 8_000, borrow_rate_bps: 900 },
+                RateModelKink { utilization_bps: 10_000, borrow_rate_bps: 15_000 },
+            ];
+            let reserve = Reserve {
+                liquidity: liquidity.clone(),
+                config: ReserveConfig { rate_model: RateModel::multi_kink(&kinks)?, ..ReserveConfig::default() },
+                ..Reserve::default()
+            };
+
+            let current_borrow_rate = reserve.current_borrow_rate()?;
+            let utilization_rate = reserve.liquidity.utilization_rate()?;
+            for pair in kinks.windows(2) {
+                let (start, end) = (pair[0], pair[1]);
+                if utilization_rate >= Rate::from_bps(start.utilization_bps) && utilization_rate <= Rate::from_bps(end.utilization_bps) {
+                    assert!(current_borrow_rate >= Rate::from_bps(start.borrow_rate_bps));
+                    assert!(current_borrow_rate <= Rate::from_bps(end.borrow_rate_bps));
+                }
+            }
+
+            let reserve = Reserve {
+                liquidity,
+                config: ReserveConfig { rate_model: RateModel::Fixed { borrow_rate_bps: fixed_borrow_rate_bps }, ..ReserveConfig::default() },
+                ..Reserve::default()
+            };
+            assert_eq!(reserve.current_borrow_rate()?, Rate::from_bps(fixed_borrow_rate_bps));
+        }
+
         #[test]
         fn current_utilization_rate(
             total_liquidity in 0..=MAX_LIQUIDITY,
@@ -1068,7 +1700,7 @@ mod test {
             // Simulate running for max 1000 years, assuming that interest is
             // compounded at least once a year
             for _ in 0..1000 {
-                reserve.liquidity.compound_interest(borrow_rate, slots_elapsed)?;
+                reserve.liquidity.compound_interest(borrow_rate, slots_elapsed, Rate::zero())?;
                 reserve.liquidity.cumulative_borrow_rate_wads.to_scaled_val()?;
             }
         }
@@ -1101,6 +1733,44 @@ mod test {
             }
         }
 
+        #[test]
+        fn reserve_accrue_interest_protocol_fees(
+            slots_elapsed in 0..=SLOTS_PER_YEAR,
+            borrowed_liquidity in 0..=u64::MAX,
+            borrow_rate in 0..=u8::MAX,
+            protocol_take_rate in 0..=100u8,
+        ) {
+            let borrowed_amount_wads = Decimal::from(borrowed_liquidity);
+            let mut reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    borrowed_amount_wads,
+                    ..ReserveLiquidity::default()
+                },
+                config: ReserveConfig {
+                    max_borrow_rate: borrow_rate,
+                    protocol_take_rate,
+                    ..ReserveConfig::default()
+                },
+                ..Reserve::default()
+            };
+
+            reserve.accrue_interest(slots_elapsed)?;
+
+            let interest = reserve.liquidity.borrowed_amount_wads.try_sub(borrowed_amount_wads)?;
+            let protocol_fees = reserve.liquidity.accumulated_protocol_fees_wads;
+            assert!(protocol_fees <= interest);
+            if protocol_take_rate == 0 {
+                assert_eq!(protocol_fees, Decimal::zero());
+            }
+            if protocol_take_rate == 100 {
+                assert_eq!(protocol_fees, interest);
+            }
+            assert_eq!(
+                reserve.liquidity.total_supply()?,
+                borrowed_amount_wads.try_add(interest)?.try_sub(protocol_fees)?
+            );
+        }
+
         #[test]
         fn borrow_fee_calculation(
             borrow_fee_wad in 0..WAD, // at WAD, fee == borrow amount, which fails
@@ -1110,6 +1780,7 @@ mod test {
         ) {
             let fees = ReserveFees {
                 borrow_fee_wad,
+                flash_loan_fee_wad: 0,
                 host_fee_percentage,
             };
             let (total_fee, host_fee) = fees.calculate_borrow_fees(Decimal::from(borrow_amount), FeeCalculation::Exclusive)?;
@@ -1140,12 +1811,35 @@ mod test {
                 assert_eq!(host_fee, 0);
             }
         }
+
+        #[test]
+        fn flash_loan_fee_calculation(
+            flash_loan_fee_wad in 0..=WAD,
+            flash_loan_amount in 0..=MAX_LIQUIDITY,
+        ) {
+            let fees = ReserveFees {
+                flash_loan_fee_wad,
+                ..ReserveFees::default()
+            };
+            let fee = fees.calculate_flash_loan_fee(Decimal::from(flash_loan_amount))?;
+
+            // the fee can't be greater than the amount lent while the rate is at most 100%
+            assert!(fee <= flash_loan_amount.max(1));
+
+            // for all fee rates greater than 0, we must have some fee
+            if flash_loan_fee_wad > 0 && flash_loan_amount > 0 {
+                assert!(fee > 0);
+            } else {
+                assert_eq!(fee, 0);
+            }
+        }
     }
 
     #[test]
     fn borrow_fee_calculation_min_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
         };
 
@@ -1173,6 +1867,7 @@ mod test {
     fn borrow_fee_calculation_min_no_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
         };
 
@@ -1201,6 +1896,7 @@ mod test {
     fn borrow_fee_calculation_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
         };
 
@@ -1216,6 +1912,7 @@ mod test {
     fn borrow_fee_calculation_no_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
         };
 
@@ -1226,4 +1923,358 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn flash_loan_calculation() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 1000,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 1000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+                    ..ReserveFees::default()
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        // flash loan all available liquidity
+        let result = reserve.calculate_flash_loan(u64::MAX).unwrap();
+        assert_eq!(result.flash_loan_amount, 1000);
+        assert_eq!(result.flash_loan_fee, 3); // 0.3% of 1000
+        assert_eq!(result.repay_amount, 1003);
+
+        // fee is rounded up to at least 1 token
+        let result = reserve.calculate_flash_loan(10).unwrap();
+        assert_eq!(result.flash_loan_fee, 1);
+        assert_eq!(result.repay_amount, 11);
+
+        // can't flash loan more than available
+        let err = reserve.calculate_flash_loan(1001).unwrap_err();
+        assert_eq!(err, LendingError::InsufficientLiquidity.into());
+
+        // fee is credited to depositors, making collateral worth more liquidity
+        let exchange_rate = reserve.collateral_exchange_rate().unwrap();
+        reserve.settle_flash_loan_fee(3).unwrap();
+        assert_eq!(reserve.liquidity.available_amount, 1003);
+        let new_exchange_rate = reserve.collateral_exchange_rate().unwrap();
+        assert!(new_exchange_rate.0 < exchange_rate.0);
+    }
+
+    #[test]
+    fn withdraw_protocol_fees() {
+        let mut liquidity = ReserveLiquidity {
+            available_amount: 100,
+            borrowed_amount_wads: Decimal::from(1000u64),
+            accumulated_protocol_fees_wads: Decimal::from_scaled_val(10_500_000_000_000_000_000),
+            ..ReserveLiquidity::default()
+        };
+        let total_supply = liquidity.total_supply().unwrap();
+
+        // whole fees are withdrawn, the fraction remains
+        assert_eq!(liquidity.withdraw_protocol_fees().unwrap(), 10);
+        assert_eq!(liquidity.available_amount, 90);
+        assert_eq!(
+            liquidity.accumulated_protocol_fees_wads,
+            Decimal::from_scaled_val(500_000_000_000_000_000)
+        );
+        assert_eq!(liquidity.total_supply().unwrap(), total_supply);
+
+        // fees are limited to available liquidity
+        liquidity.accumulated_protocol_fees_wads = Decimal::from(150u64);
+        assert_eq!(liquidity.withdraw_protocol_fees().unwrap(), 90);
+        assert_eq!(liquidity.available_amount, 0);
+        assert_eq!(
+            liquidity.accumulated_protocol_fees_wads,
+            Decimal::from(60u64)
+        );
+    }
+
+    #[test]
+    fn isolated_debt_ceiling() {
+        let usdc_reserve_pubkey = Pubkey::new_unique();
+        let sol_reserve_pubkey = Pubkey::new_unique();
+        let mut reserve = Reserve::default();
+        reserve.config.isolated = true;
+        reserve.config.isolated_debt_ceiling = 100;
+        reserve.config.isolated_borrow_reserves[0] = usdc_reserve_pubkey;
+        reserve.config.isolated_borrow_reserves[2] = sol_reserve_pubkey;
+
+        let mut usdc_reserve = Reserve::default();
+        usdc_reserve.liquidity.cumulative_borrow_rate_wads = Decimal::one();
+        usdc_reserve.liquidity.market_price = 1;
+        let mut sol_reserve = Reserve::default();
+        sol_reserve.liquidity.cumulative_borrow_rate_wads = Decimal::one();
+        sol_reserve.liquidity.market_price = 10;
+
+        assert!(reserve
+            .borrow_isolated(
+                &usdc_reserve_pubkey,
+                &usdc_reserve,
+                Decimal::from(40u64),
+                &[sol_reserve.clone()],
+            )
+            .is_ok());
+        assert!(reserve
+            .borrow_isolated(
+                &sol_reserve_pubkey,
+                &sol_reserve,
+                Decimal::from(2u64),
+                &[usdc_reserve.clone()],
+            )
+            .is_ok());
+
+        // interest on the usdc debt counts toward the ceiling
+        usdc_reserve.liquidity.cumulative_borrow_rate_wads = Decimal::from_percent(150);
+        assert_eq!(
+            reserve.borrow_isolated(
+                &sol_reserve_pubkey,
+                &sol_reserve,
+                Decimal::from(3u64),
+                &[usdc_reserve.clone()],
+            ),
+            Err(LendingError::IsolatedDebtCeilingExceeded.into())
+        );
+
+        // so does the sol price
+        sol_reserve.liquidity.market_price = 15;
+        assert_eq!(
+            reserve.borrow_isolated(
+                &usdc_reserve_pubkey,
+                &usdc_reserve,
+                Decimal::from(11u64),
+                &[sol_reserve.clone()],
+            ),
+            Err(LendingError::IsolatedDebtCeilingExceeded.into())
+        );
+
+        // the other isolated borrow reserves must be provided
+        assert_eq!(
+            reserve.borrow_isolated(
+                &usdc_reserve_pubkey,
+                &usdc_reserve,
+                Decimal::from(1u64),
+                &[],
+            ),
+            Err(LendingError::InvalidAccountInput.into())
+        );
+
+        // repaying the accrued usdc debt clears it
+        assert!(reserve
+            .repay_isolated(&usdc_reserve_pubkey, &usdc_reserve, Decimal::from(60u64))
+            .is_ok());
+        assert_eq!(
+            reserve.collateral.isolated_borrowed_scaled_wads,
+            [Decimal::zero(), Decimal::zero(), Decimal::from(2u64)]
+        );
+
+        // debt moves with its borrow reserve when the config changes
+        let mut config = reserve.config;
+        config.isolated_borrow_reserves =
+            [sol_reserve_pubkey, Pubkey::default(), Pubkey::default()];
+        assert!(reserve.set_config(config).is_ok());
+        assert_eq!(
+            reserve.collateral.isolated_borrowed_scaled_wads,
+            [Decimal::from(2u64), Decimal::zero(), Decimal::zero()]
+        );
+        config.isolated_borrow_reserves =
+            [usdc_reserve_pubkey, Pubkey::default(), Pubkey::default()];
+        assert_eq!(
+            reserve.set_config(config),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        // isolation cannot be lifted while isolated debt is outstanding
+        let mut config = reserve.config;
+        config.isolated = false;
+        assert_eq!(
+            reserve.set_config(config),
+            Err(LendingError::InvalidConfig.into())
+        );
+        reserve.collateral.isolated_borrowed_scaled_wads = [Decimal::zero(); 3];
+        assert!(reserve.set_config(config).is_ok());
+
+        // nor set while the reserve has collateral
+        config.isolated = true;
+        reserve.collateral.mint_total_supply = 1;
+        assert_eq!(
+            reserve.set_config(config),
+            Err(LendingError::InvalidConfig.into())
+        );
+    }
+
+    #[test]
+    fn liquidation_bonus_auction() {
+        let mut reserve = Reserve::default();
+        reserve.config.liquidation_bonus = 10;
+        let obligation = Obligation {
+            unhealthy_since_slot: 100,
+            ..Obligation::default()
+        };
+
+        // zero auction slots give a fixed bonus
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation, 100).unwrap(),
+            Rate::from_percent(10)
+        );
+
+        reserve.config.liquidation_auction_slots = 50;
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation, 100).unwrap(),
+            Rate::zero()
+        );
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation, 125).unwrap(),
+            Rate::from_percent(5)
+        );
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation, 200).unwrap(),
+            Rate::from_percent(10)
+        );
+
+        // unhealthy since slot is not recorded for legacy obligations
+        let legacy_obligation = Obligation::default();
+        assert_eq!(
+            reserve.liquidation_bonus(&legacy_obligation, 100).unwrap(),
+            Rate::from_percent(10)
+        );
+    }
+
+    #[test]
+    fn current_supply_rate() {
+        let mut reserve = Reserve::default();
+        reserve.liquidity.available_amount = 750;
+        reserve.liquidity.borrowed_amount_wads = Decimal::from(250u64);
+        reserve.config.rate_model = RateModel::Fixed {
+            borrow_rate_bps: 1_000,
+        };
+        reserve.config.protocol_take_rate = 20;
+
+        // 10% borrow rate * 25% utilization * 80% after the protocol take
+        assert_eq!(
+            reserve.current_supply_rate().unwrap(),
+            Rate::from_percent(2)
+        );
+    }
+
+    #[test]
+    fn pack_reserve_rate_history() {
+        let mut reserve = Reserve {
+            version: PROGRAM_VERSION,
+            ..Reserve::default()
+        };
+        reserve.config.max_oracle_age_seconds = DEFAULT_MAX_ORACLE_AGE_SECONDS;
+        reserve.config.liquidation_close_factor = LIQUIDATION_CLOSE_FACTOR;
+        reserve.liquidity.cumulative_borrow_rate_wads = Decimal::one();
+        reserve.record_rate_snapshot(100).unwrap();
+        assert_eq!(reserve.rate_history.snapshot_count, 1);
+        reserve.price_observation = PriceAccumulators::new(1, 1_000, 20_000).observe(301);
+
+        let mut data = [0u8; RESERVE_LEN];
+        Reserve::pack(reserve.clone(), &mut data).unwrap();
+        assert_eq!(Reserve::unpack(&data).unwrap(), reserve);
+
+        // reserves created before rate history was added unpack with an empty history
+        let mut legacy_data = [0u8; RESERVE_BASE_LEN];
+        Reserve::pack(reserve.clone(), &mut legacy_data).unwrap();
+        assert_eq!(
+            Reserve::unpack(&legacy_data).unwrap(),
+            Reserve {
+                rate_history: RateHistory::default(),
+                price_observation: PriceAccumulators::default(),
+                ..reserve.clone()
+            }
+        );
+
+        let mut invalid_data = [0u8; RESERVE_BASE_LEN + 1];
+        assert_eq!(
+            Reserve::pack(reserve, &mut invalid_data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn unpack_reserve_without_max_oracle_age() {
+        // reserves created before the max oracle age was added have it zeroed
+        let reserve = Reserve {
+            version: PROGRAM_VERSION,
+            ..Reserve::default()
+        };
+        let mut data = [0u8; RESERVE_LEN];
+        Reserve::pack(reserve, &mut data).unwrap();
+        assert_eq!(
+            Reserve::unpack(&data)
+                .unwrap()
+                .config
+                .max_oracle_age_seconds,
+            DEFAULT_MAX_ORACLE_AGE_SECONDS
+        );
+    }
+
+    #[test]
+    fn unpack_reserve_without_liquidation_config() {
+        // reserves created before liquidation auctions were added have the close factor and
+        // auction slots zeroed
+        let reserve = Reserve {
+            version: PROGRAM_VERSION,
+            config: ReserveConfig {
+                liquidation_bonus: 10,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let mut data = [0u8; RESERVE_LEN];
+        Reserve::pack(reserve, &mut data).unwrap();
+        let reserve = Reserve::unpack(&data).unwrap();
+        assert_eq!(
+            reserve.config.liquidation_close_factor,
+            LIQUIDATION_CLOSE_FACTOR
+        );
+        assert_eq!(reserve.config.liquidation_auction_slots, 0);
+
+        // with zero auction slots the bonus is fixed, even right after becoming unhealthy
+        let obligation = Obligation {
+            unhealthy_since_slot: 100,
+            ..Obligation::default()
+        };
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation, 100).unwrap(),
+            Rate::from_percent(10)
+        );
+    }
+
+    #[test]
+    fn reserve_accrue_interest_and_mark_stale() {
+        let mut reserve = Reserve {
+            last_update: LastUpdate::new(100),
+            liquidity: ReserveLiquidity {
+                borrowed_amount_wads: Decimal::from(1_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                max_borrow_rate: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        reserve.last_update.update_slot(100);
+
+        reserve.accrue_interest_and_mark_stale(200).unwrap();
+        let accrued_liquidity = reserve.liquidity.clone();
+        assert!(accrued_liquidity.cumulative_borrow_rate_wads > Decimal::one());
+        assert_eq!(reserve.last_update.slot, 200);
+        assert!(reserve.last_update.stale);
+
+        // interest up to the current slot is only accrued once
+        reserve.accrue_interest_and_mark_stale(200).unwrap();
+        assert_eq!(reserve.liquidity, accrued_liquidity);
+    }
 }
@@ -94,6 +94,7 @@ async fn test_borrow_quote_currency() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey],
             ),
             borrow_obligation_liquidity(
@@ -106,6 +107,8 @@ async fn test_borrow_quote_currency() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                None,
+                vec![],
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -238,6 +241,7 @@ async fn test_borrow_max_base_currency() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![usdc_test_reserve.pubkey],
             ),
             borrow_obligation_liquidity(
@@ -250,6 +254,8 @@ async fn test_borrow_max_base_currency() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                None,
+                vec![],
                 Some(sol_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -365,6 +371,7 @@ async fn test_borrow_too_large() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey],
             ),
             borrow_obligation_liquidity(
@@ -377,6 +384,8 @@ async fn test_borrow_too_large() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                None,
+                vec![],
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -398,3 +407,236 @@ async fn test_borrow_too_large() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_borrow_limit_exceeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 500 * FRACTIONAL_TO_USDC;
+    const USDC_BORROW_LIMIT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT_FRACTIONAL - 1;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let mut usdc_reserve_config = reserve_config;
+    usdc_reserve_config.borrow_limit = USDC_BORROW_LIMIT_FRACTIONAL;
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: usdc_reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                spl_token_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                vec![],
+                Some(usdc_test_reserve.liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+
+    // check that transaction fails
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowLimitExceeded as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_within_stale_window() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market_with_stale_window(&mut test, usdc_mint.pubkey, 10);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    // Configure reserve to a fixed borrow rate of 1%
+    const BORROW_RATE: u8 = 1;
+    reserve_config.min_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_utilization_rate = 100;
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 4 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(5).unwrap(); // reserves were refreshed at slot 1
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let initial_usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                spl_token_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                vec![],
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+
+    // interest is accrued up to the borrow before it is recorded
+    assert_eq!(usdc_reserve.last_update.slot, 5);
+    assert!(
+        usdc_reserve.liquidity.cumulative_borrow_rate_wads
+            > initial_usdc_reserve.liquidity.cumulative_borrow_rate_wads
+    );
+
+    // the new borrow starts accruing interest from the current cumulative borrow rate
+    let liquidity = &obligation.borrows[0];
+    assert_eq!(
+        liquidity.cumulative_borrow_rate_wads,
+        usdc_reserve.liquidity.cumulative_borrow_rate_wads
+    );
+    assert!(liquidity.borrowed_amount_wads >= Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL));
+    assert!(
+        liquidity.borrowed_amount_wads
+            < Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL + USDC_BORROW_AMOUNT_FRACTIONAL / 100)
+    );
+}
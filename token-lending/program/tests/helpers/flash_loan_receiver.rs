@@ -0,0 +1,73 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+
+/// Flash loan receiver which returns the repay amount requested by the lending program.
+///
+/// Accounts expected:
+///
+///   0. `[writable]` Destination liquidity token account.
+///   1. `[writable]` Source reserve liquidity supply SPL Token account.
+///   2. `[]` Token program id.
+///   3. `[signer]` Destination liquidity token account owner.
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let repay_amount = unpack_repay_amount(input)?;
+    repay(program_id, accounts, repay_amount)
+}
+
+/// Flash loan receiver which returns one token less than the repay amount requested.
+pub fn process_instruction_underpay(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let repay_amount = unpack_repay_amount(input)?;
+    repay(program_id, accounts, repay_amount - 1)
+}
+
+fn unpack_repay_amount(input: &[u8]) -> Result<u64, ProgramError> {
+    let (&tag, rest) = input
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    if tag != 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    rest.get(..8)
+        .and_then(|slice| slice.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(ProgramError::InvalidInstructionData)
+}
+
+fn repay(_program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_id.key,
+            destination_liquidity_info.key,
+            source_liquidity_info.key,
+            user_transfer_authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            destination_liquidity_info.clone(),
+            source_liquidity_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_program_id.clone(),
+        ],
+    )
+}
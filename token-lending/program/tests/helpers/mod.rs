@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+pub mod flash_loan_receiver;
 pub mod genesis;
 
 use assert_matches::*;
@@ -28,14 +29,24 @@ use spl_token_lending::{
         init_obligation, init_reserve, liquidate_obligation, refresh_reserve,
     },
     math::{Decimal, Rate, TryAdd, TryMul},
+    oracle::OracleType,
     processor::process_instruction,
     state::{
         InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
         NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, ObligationCollateral,
-        ObligationLiquidity, Reserve, ReserveCollateral, ReserveConfig, ReserveFees,
-        ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+        ObligationLiquidity, RateModel, Reserve, ReserveCollateral, ReserveConfig, ReserveFees,
+        ReserveLiquidity, INITIAL_COLLATERAL_RATIO, LIQUIDATION_CLOSE_FACTOR,
+        MAX_ISOLATED_BORROW_RESERVES, PROGRAM_VERSION, STALE_AFTER_SLOTS_ELAPSED,
     },
 };
+use spl_token_swap::{
+    curve::{
+        base::{CurveType, SwapCurve},
+        constant_product::ConstantProductCurve,
+    },
+    state::{SwapV3, SwapVersion},
+    twap::PriceAccumulators,
+};
 use std::str::FromStr;
 
 pub const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
@@ -52,13 +63,26 @@ pub const TEST_RESERVE_CONFIG: ReserveConfig = ReserveConfig {
     max_borrow_rate: 30,
     fees: ReserveFees {
         borrow_fee_wad: 100_000_000_000,
+        flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3% (Aave flash loan fee)
         /// 0.00001% (Aave borrow fee)
         host_fee_percentage: 20,
     },
+    // the test clock follows the wall clock, so fixture oracle prices are never too old
+    max_oracle_age_seconds: u64::MAX,
+    protocol_take_rate: 0,
+    rate_model: RateModel::TwoSlope,
+    isolated: false,
+    isolated_debt_ceiling: 0,
+    isolated_borrow_reserves: [Pubkey::new_from_array([0; 32]); MAX_ISOLATED_BORROW_RESERVES],
+    deposit_limit: 0,
+    borrow_limit: 0,
+    liquidation_close_factor: LIQUIDATION_CLOSE_FACTOR,
+    liquidation_auction_slots: 0,
 };
 
 pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 pub const SRM_MINT: &str = "SRMuApVNdxXokk5GT7XD5cUUgXMBCoAz2LHeuAoKWRt";
+pub const PYTH_PROGRAM_ID: &str = "FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH";
 
 #[allow(non_camel_case_types)]
 pub enum TestAggregatorPair {
@@ -122,6 +146,14 @@ impl AddPacked for ProgramTest {
 }
 
 pub fn add_lending_market(test: &mut ProgramTest, quote_token_mint: Pubkey) -> TestLendingMarket {
+    add_lending_market_with_stale_window(test, quote_token_mint, STALE_AFTER_SLOTS_ELAPSED)
+}
+
+pub fn add_lending_market_with_stale_window(
+    test: &mut ProgramTest,
+    quote_token_mint: Pubkey,
+    stale_after_slots_elapsed: u64,
+) -> TestLendingMarket {
     let lending_market_pubkey = Pubkey::new_unique();
     let (lending_market_authority, bump_seed) =
         Pubkey::find_program_address(&[lending_market_pubkey.as_ref()], &spl_token_lending::id());
@@ -129,15 +161,19 @@ pub fn add_lending_market(test: &mut ProgramTest, quote_token_mint: Pubkey) -> T
     let lending_market_owner =
         read_keypair_file("tests/fixtures/lending_market_owner.json").unwrap();
 
+    let mut lending_market = LendingMarket::new(InitLendingMarketParams {
+        bump_seed,
+        owner: lending_market_owner.pubkey(),
+        quote_token_mint,
+        token_program_id: spl_token::id(),
+        stale_after_slots_elapsed,
+    });
+    lending_market.pyth_program_id = Pubkey::from_str(PYTH_PROGRAM_ID).unwrap();
+
     test.add_packable_account(
         lending_market_pubkey,
         u32::MAX as u64,
-        &LendingMarket::new(InitLendingMarketParams {
-            bump_seed,
-            owner: lending_market_owner.pubkey(),
-            quote_token_mint,
-            token_program_id: spl_token::id(),
-        }),
+        &lending_market,
         &spl_token_lending::id(),
     );
 
@@ -153,6 +189,7 @@ pub fn add_lending_market(test: &mut ProgramTest, quote_token_mint: Pubkey) -> T
 pub struct AddObligationArgs<'a> {
     pub deposits: &'a [(&'a TestReserve, u64)],
     pub borrows: &'a [(&'a TestReserve, u64)],
+    pub isolated_collateral_reserve: Option<&'a TestReserve>,
     pub mark_fresh: bool,
     pub slots_elapsed: u64,
 }
@@ -166,6 +203,7 @@ pub fn add_obligation(
     let AddObligationArgs {
         deposits,
         borrows,
+        isolated_collateral_reserve,
         mark_fresh,
         slots_elapsed,
     } = args;
@@ -220,6 +258,10 @@ pub fn add_obligation(
         borrows: obligation_borrows,
     });
 
+    if let Some(isolated_collateral_reserve) = isolated_collateral_reserve {
+        obligation.isolated_collateral_reserve = COption::Some(isolated_collateral_reserve.pubkey);
+    }
+
     if mark_fresh {
         obligation.last_update.update_slot(current_slot);
     }
@@ -250,10 +292,13 @@ pub struct AddReserveArgs {
     pub user_liquidity_amount: u64,
     pub borrow_amount: u64,
     pub initial_borrow_rate: u8,
+    pub accumulated_protocol_fees: u64,
     pub collateral_amount: u64,
     pub mark_fresh: bool,
     pub slots_elapsed: u64,
     pub aggregator_pair: Option<TestAggregatorPair>,
+    pub pyth_price: Option<TestPythPrice>,
+    pub token_swap: Option<TestTokenSwap>,
 }
 
 pub fn add_reserve(
@@ -271,13 +316,26 @@ pub fn add_reserve(
         user_liquidity_amount,
         borrow_amount,
         initial_borrow_rate,
+        accumulated_protocol_fees,
         collateral_amount,
         mark_fresh,
         slots_elapsed,
         aggregator_pair,
+        pyth_price,
+        token_swap,
     } = args;
 
-    let (liquidity_oracle_pubkey, market_price) = if let Some(aggregator_pair) = aggregator_pair {
+    let mut oracle_type = OracleType::FluxAggregator;
+    let mut price_observation = PriceAccumulators::default();
+    let (liquidity_oracle_pubkey, market_price) = if let Some(pyth_price) = pyth_price {
+        oracle_type = OracleType::Pyth;
+        (Some(pyth_price.pubkey), pyth_price.price)
+    } else if let Some(token_swap) = token_swap {
+        // the reserve has observed the pool since it was created, and has no price until refreshed
+        oracle_type = OracleType::TokenSwap;
+        price_observation = token_swap.price_accumulators;
+        (Some(token_swap.pubkey), 0)
+    } else if let Some(aggregator_pair) = aggregator_pair {
         let aggregator = add_aggregator(test, aggregator_pair);
         (Some(aggregator.pubkey), aggregator.price)
     } else if liquidity_mint_pubkey == spl_token::native_mint::id() {
@@ -385,6 +443,8 @@ pub fn add_reserve(
             supply_pubkey: liquidity_supply_pubkey,
             fee_receiver: liquidity_fee_receiver_pubkey,
             oracle_pubkey: liquidity_oracle_pubkey.into(),
+            oracle_type,
+            quote_decimals: 6,
             market_price,
         }),
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
@@ -393,6 +453,7 @@ pub fn add_reserve(
         }),
         config,
     });
+    reserve.price_observation = price_observation;
     reserve.deposit_liquidity(liquidity_amount).unwrap();
     reserve.liquidity.borrow(borrow_amount.into()).unwrap();
     let borrow_rate_multiplier = Rate::one()
@@ -400,6 +461,7 @@ pub fn add_reserve(
         .unwrap();
     reserve.liquidity.cumulative_borrow_rate_wads =
         Decimal::one().try_mul(borrow_rate_multiplier).unwrap();
+    reserve.liquidity.accumulated_protocol_fees_wads = accumulated_protocol_fees.into();
 
     if mark_fresh {
         reserve.last_update.update_slot(current_slot);
@@ -517,6 +579,7 @@ impl TestLendingMarket {
                     lending_market_pubkey,
                     lending_market_owner.pubkey(),
                     quote_token_mint,
+                    STALE_AFTER_SLOTS_ELAPSED,
                 ),
             ],
             Some(&payer.pubkey()),
@@ -674,6 +737,8 @@ impl TestLendingMarket {
                 obligation.pubkey,
                 self.pubkey,
                 obligation.owner,
+                None,
+                vec![],
                 Some(borrow_reserve.liquidity_host_pubkey),
             )],
             Some(&payer.pubkey()),
@@ -699,6 +764,10 @@ impl TestLendingMarket {
         assert_eq!(lending_market.version, PROGRAM_VERSION);
         assert_eq!(lending_market.owner, self.owner.pubkey());
         assert_eq!(lending_market.quote_token_mint, self.quote_token_mint);
+        assert_eq!(
+            lending_market.stale_after_slots_elapsed,
+            STALE_AFTER_SLOTS_ELAPSED
+        );
     }
 
     pub async fn add_to_genesis(
@@ -745,6 +814,34 @@ impl TestReserve {
         payer: &Keypair,
         user_accounts_owner: &Keypair,
         aggregator: Option<&TestAggregator>,
+    ) -> Result<Self, TransactionError> {
+        Self::init_with_oracle(
+            name,
+            banks_client,
+            lending_market,
+            liquidity_amount,
+            config,
+            liquidity_mint_pubkey,
+            user_liquidity_pubkey,
+            payer,
+            user_accounts_owner,
+            aggregator.map(TestOracle::FluxAggregator),
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn init_with_oracle(
+        name: String,
+        banks_client: &mut BanksClient,
+        lending_market: &TestLendingMarket,
+        liquidity_amount: u64,
+        config: ReserveConfig,
+        liquidity_mint_pubkey: Pubkey,
+        user_liquidity_pubkey: Pubkey,
+        payer: &Keypair,
+        user_accounts_owner: &Keypair,
+        oracle: Option<TestOracle<'_>>,
     ) -> Result<Self, TransactionError> {
         let reserve_keypair = Keypair::new();
         let reserve_pubkey = reserve_keypair.pubkey();
@@ -756,13 +853,25 @@ impl TestReserve {
         let user_collateral_token_keypair = Keypair::new();
         let user_transfer_authority_keypair = Keypair::new();
 
-        let (liquidity_oracle_pubkey, market_price) = if let Some(aggregator) = aggregator {
-            (Some(aggregator.pubkey), aggregator.price)
-        } else if liquidity_mint_pubkey == lending_market.quote_token_mint {
-            (None, 1 * FRACTIONAL_TO_USDC)
-        } else {
-            panic!("aggregator is required");
+        let (oracle_type, liquidity_oracle_pubkeys, market_price) = match oracle {
+            Some(TestOracle::FluxAggregator(aggregator)) => (
+                OracleType::FluxAggregator,
+                vec![aggregator.pubkey],
+                aggregator.price,
+            ),
+            Some(TestOracle::Pyth(pyth_price)) => {
+                (OracleType::Pyth, vec![pyth_price.pubkey], pyth_price.price)
+            }
+            // token swap prices are only set once the pool has been observed for a full window
+            Some(TestOracle::TokenSwap(token_swap)) => {
+                (OracleType::TokenSwap, token_swap.oracle_pubkeys(), 0)
+            }
+            None if liquidity_mint_pubkey == lending_market.quote_token_mint => {
+                (OracleType::FluxAggregator, vec![], 1 * FRACTIONAL_TO_USDC)
+            }
+            None => panic!("oracle is required"),
         };
+        let liquidity_oracle_pubkey = liquidity_oracle_pubkeys.first().copied();
 
         let liquidity_mint_account = banks_client
             .get_account(liquidity_mint_pubkey)
@@ -836,6 +945,7 @@ impl TestReserve {
                     spl_token_lending::id(),
                     liquidity_amount,
                     config,
+                    oracle_type,
                     user_liquidity_pubkey,
                     user_collateral_token_keypair.pubkey(),
                     reserve_pubkey,
@@ -848,7 +958,7 @@ impl TestReserve {
                     lending_market.pubkey,
                     lending_market.owner.pubkey(),
                     user_transfer_authority_keypair.pubkey(),
-                    liquidity_oracle_pubkey,
+                    liquidity_oracle_pubkeys,
                 ),
             ],
             Some(&payer.pubkey()),
@@ -991,6 +1101,7 @@ impl TestReserve {
             };
 
         assert_eq!(liquidity_oracle_coption, reserve.liquidity.oracle_pubkey);
+        assert_eq!(OracleType::FluxAggregator, reserve.liquidity.oracle_type);
         assert_eq!(
             reserve.liquidity.cumulative_borrow_rate_wads,
             Decimal::one()
@@ -1184,6 +1295,12 @@ pub fn add_srm_mint(test: &mut ProgramTest) -> TestQuoteMint {
     }
 }
 
+pub enum TestOracle<'a> {
+    FluxAggregator(&'a TestAggregator),
+    Pyth(&'a TestPythPrice),
+    TokenSwap(&'a TestTokenSwap),
+}
+
 pub struct TestAggregator {
     pub name: String,
     pub pubkey: Pubkey,
@@ -1214,6 +1331,7 @@ pub fn add_aggregator(test: &mut ProgramTest, pair: TestAggregatorPair) -> TestA
         answer: Answer {
             median: price,
             created_at: 1, // set to > 0 to initialize
+            updated_at: 1,
             ..Answer::default()
         },
         ..Aggregator::default()
@@ -1358,3 +1476,135 @@ pub async fn get_token_balance(banks_client: &mut BanksClient, pubkey: Pubkey) -
         .unwrap()
         .amount
 }
+
+#[derive(Clone, Copy)]
+pub struct TestPythPrice {
+    pub pubkey: Pubkey,
+    pub price: u64,
+}
+
+pub fn add_pyth_price(test: &mut ProgramTest, pub_slot: u64) -> TestPythPrice {
+    // price @ 1 SOL = 20 USDC, as 2_000_000_000 * 10^-8
+    let pubkey = Pubkey::new_unique();
+    let mut account = Account::new(
+        u32::MAX as u64,
+        3312,
+        &Pubkey::from_str(PYTH_PROGRAM_ID).unwrap(),
+    );
+    let data = &mut account.data;
+    data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes()); // magic
+    data[4..8].copy_from_slice(&2u32.to_le_bytes()); // version
+    data[8..12].copy_from_slice(&3u32.to_le_bytes()); // price account
+    data[20..24].copy_from_slice(&(-8i32).to_le_bytes()); // expo
+    data[208..216].copy_from_slice(&2_000_000_000i64.to_le_bytes()); // aggregate price
+    data[216..224].copy_from_slice(&1_000_000u64.to_le_bytes()); // aggregate confidence
+    data[224..228].copy_from_slice(&1u32.to_le_bytes()); // trading
+    data[232..240].copy_from_slice(&pub_slot.to_le_bytes());
+    test.add_account(pubkey, account);
+
+    TestPythPrice {
+        pubkey,
+        price: 20 * FRACTIONAL_TO_USDC,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TestTokenSwap {
+    pub pubkey: Pubkey,
+    pub token_a_pubkey: Pubkey,
+    pub token_b_pubkey: Pubkey,
+    pub price_accumulators: PriceAccumulators,
+    pub price: u64,
+}
+
+impl TestTokenSwap {
+    pub fn oracle_pubkeys(&self) -> Vec<Pubkey> {
+        vec![self.pubkey, self.token_a_pubkey, self.token_b_pubkey]
+    }
+}
+
+/// Add a constant product pool of the liquidity mint as token A against the quote token mint as
+/// token B, whose price accumulators were last updated at the start of the Unix epoch
+pub fn add_token_swap_pool(
+    test: &mut ProgramTest,
+    liquidity_mint_pubkey: Pubkey,
+    liquidity_mint_decimals: u8,
+    liquidity_amount: u64,
+    quote_token_mint_pubkey: Pubkey,
+    quote_amount: u64,
+) -> TestTokenSwap {
+    let pubkey = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+
+    let token_a_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        token_a_pubkey,
+        u32::MAX as u64,
+        &Token {
+            mint: liquidity_mint_pubkey,
+            owner: authority,
+            amount: liquidity_amount,
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+
+    let token_b_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        token_b_pubkey,
+        u32::MAX as u64,
+        &Token {
+            mint: quote_token_mint_pubkey,
+            owner: authority,
+            amount: quote_amount,
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+
+    let price_accumulators = PriceAccumulators::new(1, liquidity_amount, quote_amount);
+    let mut account = Account::new(
+        u32::MAX as u64,
+        SwapVersion::LATEST_LEN,
+        &spl_token_swap::id(),
+    );
+    SwapVersion::pack(
+        SwapVersion::SwapV3(SwapV3 {
+            is_initialized: true,
+            nonce: 0,
+            token_program_id: spl_token::id(),
+            token_a: token_a_pubkey,
+            token_b: token_b_pubkey,
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: liquidity_mint_pubkey,
+            token_b_mint: quote_token_mint_pubkey,
+            pool_fee_account: Pubkey::new_unique(),
+            fees: Default::default(),
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve),
+            },
+            oracle: Pubkey::default(),
+            price_accumulators,
+            admin: Pubkey::default(),
+            amp_ramp: Default::default(),
+            status: Default::default(),
+        }),
+        &mut account.data,
+    )
+    .unwrap();
+    test.add_account(pubkey, account);
+
+    let price = (quote_amount as u128 * 10u128.pow(liquidity_mint_decimals as u32)
+        / liquidity_amount as u128) as u64;
+
+    TestTokenSwap {
+        pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        price_accumulators,
+        price,
+    }
+}
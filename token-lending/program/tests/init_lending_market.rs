@@ -6,12 +6,17 @@ use helpers::*;
 use solana_program_test::*;
 use solana_sdk::{
     instruction::InstructionError,
+    program_pack::Pack,
     pubkey::Pubkey,
-    signature::Signer,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
     transaction::{Transaction, TransactionError},
 };
 use spl_token_lending::{
-    error::LendingError, instruction::init_lending_market, processor::process_instruction,
+    error::LendingError,
+    instruction::init_lending_market,
+    processor::process_instruction,
+    state::{LendingMarket, STALE_AFTER_SLOTS_ELAPSED},
 };
 
 #[tokio::test]
@@ -55,6 +60,7 @@ async fn test_already_initialized() {
             existing_market.pubkey,
             existing_market.owner.pubkey(),
             usdc_mint.pubkey,
+            STALE_AFTER_SLOTS_ELAPSED,
         )],
         Some(&payer.pubkey()),
     );
@@ -71,3 +77,49 @@ async fn test_already_initialized() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_invalid_stale_after_slots_elapsed() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let lending_market_keypair = Keypair::new();
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &lending_market_keypair.pubkey(),
+                rent.minimum_balance(LendingMarket::LEN),
+                LendingMarket::LEN as u64,
+                &spl_token_lending::id(),
+            ),
+            init_lending_market(
+                spl_token_lending::id(),
+                lending_market_keypair.pubkey(),
+                payer.pubkey(),
+                usdc_mint.pubkey,
+                0,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market_keypair], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+}
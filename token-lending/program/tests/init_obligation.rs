@@ -6,12 +6,17 @@ use helpers::*;
 use solana_program_test::*;
 use solana_sdk::{
     instruction::InstructionError,
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
+    system_instruction::create_account,
     transaction::{Transaction, TransactionError},
 };
 use spl_token_lending::{
-    error::LendingError, instruction::init_obligation, processor::process_instruction,
+    error::LendingError,
+    instruction::init_obligation,
+    processor::process_instruction,
+    state::{Obligation, MAX_EXTENDED_OBLIGATION_RESERVES},
 };
 
 #[tokio::test]
@@ -88,3 +93,57 @@ async fn test_already_initialized() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_success_extended() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let obligation_keypair = Keypair::new();
+    let obligation_len = Obligation::packed_len(MAX_EXTENDED_OBLIGATION_RESERVES);
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &obligation_keypair.pubkey(),
+                rent.minimum_balance(obligation_len),
+                obligation_len as u64,
+                &spl_token_lending::id(),
+            ),
+            init_obligation(
+                spl_token_lending::id(),
+                obligation_keypair.pubkey(),
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &obligation_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation_account = banks_client
+        .get_account(obligation_keypair.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        Obligation::max_reserves_for_len(obligation_account.data.len()),
+        Ok(MAX_EXTENDED_OBLIGATION_RESERVES)
+    );
+    let obligation = Obligation::unpack(&obligation_account.data).unwrap();
+    assert_eq!(obligation.owner, user_accounts_owner.pubkey());
+}
@@ -12,7 +12,8 @@ use solana_sdk::{
 };
 use spl_token_lending::{
     error::LendingError,
-    instruction::init_reserve,
+    instruction::{init_reserve, refresh_reserve, set_lending_market_pyth_program},
+    oracle::OracleType,
     processor::process_instruction,
     state::{ReserveFees, INITIAL_COLLATERAL_RATIO},
 };
@@ -111,6 +112,7 @@ async fn test_already_initialized() {
             spl_token_lending::id(),
             42,
             usdc_test_reserve.config,
+            OracleType::FluxAggregator,
             usdc_test_reserve.user_liquidity_pubkey,
             usdc_test_reserve.user_collateral_pubkey,
             usdc_test_reserve.pubkey,
@@ -176,6 +178,7 @@ async fn test_invalid_fees() {
         let mut config = TEST_RESERVE_CONFIG;
         config.fees = ReserveFees {
             borrow_fee_wad: 1_000_000_000_000_000_001,
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
         };
 
@@ -206,6 +209,7 @@ async fn test_invalid_fees() {
         let mut config = TEST_RESERVE_CONFIG;
         config.fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000,
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 101,
         };
 
@@ -231,3 +235,141 @@ async fn test_invalid_fees() {
         );
     }
 }
+
+#[tokio::test]
+async fn test_pyth_price_not_owned_by_pyth_program() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let sol_pyth_price = add_pyth_price(&mut test, 1);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_pyth_program(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            Pubkey::new_unique(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    assert_eq!(
+        TestReserve::init_with_oracle(
+            "sol".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            RESERVE_AMOUNT,
+            TEST_RESERVE_CONFIG,
+            spl_token::native_mint::id(),
+            sol_user_liquidity_account,
+            &payer,
+            &user_accounts_owner,
+            Some(TestOracle::Pyth(&sol_pyth_price)),
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            8,
+            InstructionError::Custom(LendingError::InvalidOracleConfig as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_token_swap_price_before_window() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let sol_usdc_token_swap = add_token_swap_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        9,
+        1_000 * LAMPORTS_TO_SOL,
+        usdc_mint.pubkey,
+        20_000 * FRACTIONAL_TO_USDC,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let sol_reserve = TestReserve::init_with_oracle(
+        "sol".to_owned(),
+        &mut banks_client,
+        &lending_market,
+        RESERVE_AMOUNT,
+        TEST_RESERVE_CONFIG,
+        spl_token::native_mint::id(),
+        sol_user_liquidity_account,
+        &payer,
+        &user_accounts_owner,
+        Some(TestOracle::TokenSwap(&sol_usdc_token_swap)),
+    )
+    .await
+    .unwrap();
+
+    let reserve = sol_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve.liquidity.oracle_type, OracleType::TokenSwap);
+    assert_eq!(reserve.liquidity.market_price, 0);
+    assert!(reserve.last_update.stale);
+
+    // the pool has not been observed for a full window since the reserve was created
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            spl_token_lending::id(),
+            sol_reserve.pubkey,
+            sol_usdc_token_swap.oracle_pubkeys(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidOraclePrice as u32)
+        )
+    );
+}
@@ -0,0 +1,325 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_option::COption,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_token::instruction::approve;
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{borrow_obligation_liquidity, deposit_obligation_collateral, refresh_obligation},
+    math::TryDiv,
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+
+struct IsolatedTest {
+    test: ProgramTest,
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+}
+
+fn setup(allow_usdc: bool, isolated_debt_ceiling: u64) -> IsolatedTest {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut isolated_config = reserve_config;
+    isolated_config.isolated = true;
+    isolated_config.isolated_debt_ceiling = isolated_debt_ceiling;
+    if allow_usdc {
+        isolated_config.isolated_borrow_reserves[0] = usdc_test_reserve.pubkey;
+    }
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: isolated_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    IsolatedTest {
+        test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+    }
+}
+
+async fn borrow_against_isolated_collateral(
+    isolated_test: IsolatedTest,
+) -> (
+    BanksClient,
+    TestReserve,
+    TestObligation,
+    Result<(), TransportError>,
+) {
+    let IsolatedTest {
+        mut test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+    } = isolated_test;
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            isolated_collateral_reserve: Some(&sol_test_reserve),
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                spl_token_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                Some(sol_test_reserve.pubkey),
+                vec![],
+                Some(usdc_test_reserve.liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    let result = banks_client.process_transaction(transaction).await;
+
+    (banks_client, sol_test_reserve, test_obligation, result)
+}
+
+#[tokio::test]
+async fn test_borrow_allowed_reserve() {
+    let (mut banks_client, sol_test_reserve, test_obligation, result) =
+        borrow_against_isolated_collateral(setup(true, u64::MAX)).await;
+    assert!(result.is_ok());
+
+    // the tracked debt grows with the usdc reserve's cumulative borrow rate like the obligation's
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        sol_reserve.collateral.isolated_borrowed_scaled_wads[0],
+        obligation.borrows[0]
+            .borrowed_amount_wads
+            .try_div(obligation.borrows[0].cumulative_borrow_rate_wads)
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_reserve_not_allowed() {
+    let (_, _, _, result) = borrow_against_isolated_collateral(setup(false, u64::MAX)).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::IsolatedBorrowNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_debt_ceiling_exceeded() {
+    let (_, _, _, result) =
+        borrow_against_isolated_collateral(setup(true, USDC_BORROW_AMOUNT_FRACTIONAL)).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::IsolatedDebtCeilingExceeded as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_isolated_collateral() {
+    let IsolatedTest {
+        mut test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        ..
+    } = setup(true, u64::MAX);
+
+    let user_transfer_authority = Keypair::new();
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &sol_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            )
+            .unwrap(),
+            deposit_obligation_collateral(
+                spl_token_lending::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &vec![&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.isolated_collateral_reserve,
+        COption::Some(sol_test_reserve.pubkey)
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_other_collateral() {
+    let IsolatedTest {
+        mut test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+    } = setup(true, u64::MAX);
+
+    let user_transfer_authority = Keypair::new();
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            isolated_collateral_reserve: Some(&sol_test_reserve),
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            deposit_obligation_collateral(
+                spl_token_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.collateral_supply_pubkey,
+                usdc_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &vec![&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::IsolatedCollateral as u32)
+        )
+    );
+}
@@ -115,6 +115,7 @@ async fn test_success() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
             ),
             liquidate_obligation(
@@ -150,6 +150,7 @@ async fn test_success() {
             refresh_obligation(
                 spl_token_lending::id(),
                 obligation_pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey],
             ),
             // 6
@@ -165,6 +166,8 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_accounts_owner_pubkey,
+                None,
+                vec![],
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
             // 8
@@ -173,6 +176,7 @@ async fn test_success() {
             refresh_obligation(
                 spl_token_lending::id(),
                 obligation_pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
             ),
             // 10
@@ -195,11 +199,13 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_transfer_authority_pubkey,
+                None,
             ),
             // 12
             refresh_obligation(
                 spl_token_lending::id(),
                 obligation_pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey],
             ),
             // 13
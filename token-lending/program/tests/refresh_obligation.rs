@@ -15,7 +15,7 @@ use spl_token_lending::{
     instruction::{refresh_obligation, refresh_reserve},
     math::{Decimal, TryDiv},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
+    state::{DEFAULT_MAX_ORACLE_AGE_SECONDS, INITIAL_COLLATERAL_RATIO, STALE_AFTER_SLOTS_ELAPSED},
 };
 
 #[tokio::test]
@@ -112,6 +112,7 @@ async fn test_success() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
             ),
         ],
@@ -162,3 +163,117 @@ async fn test_success() {
         liquidity_price,
     );
 }
+
+#[tokio::test]
+async fn test_baseline_lending_market_and_reserves() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+
+    // accounts packed before the stale window and max oracle age were added have them zeroed
+    let lending_market = add_lending_market_with_stale_window(&mut test, usdc_mint.pubkey, 0);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.max_oracle_age_seconds = 0;
+
+    // Pyth prices are aged by slot, so the default max oracle age applies to the test clock
+    let sol_pyth_price = add_pyth_price(&mut test, 1);
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            pyth_price: Some(sol_pyth_price),
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(spl_token_lending::id(), usdc_test_reserve.pubkey, None),
+            refresh_reserve(
+                spl_token_lending::id(),
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market_info = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(
+        lending_market_info.stale_after_slots_elapsed,
+        STALE_AFTER_SLOTS_ELAPSED
+    );
+
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        sol_reserve.config.max_oracle_age_seconds,
+        DEFAULT_MAX_ORACLE_AGE_SECONDS
+    );
+    assert!(!sol_reserve.last_update.stale);
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert!(!obligation.last_update.stale);
+}
@@ -5,11 +5,13 @@ mod helpers;
 use helpers::*;
 use solana_program_test::*;
 use solana_sdk::{
+    instruction::InstructionError,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use spl_token_lending::{
+    error::LendingError,
     instruction::refresh_reserve,
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul},
     processor::process_instruction,
@@ -130,3 +132,193 @@ async fn test_success() {
         usdc_test_reserve.market_price
     );
 }
+
+#[tokio::test]
+async fn test_pyth_price() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let sol_pyth_price = add_pyth_price(&mut test, 90);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.max_oracle_age_seconds = 60;
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            pyth_price: Some(sol_pyth_price),
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(100).unwrap(); // clock.slot = 100
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            spl_token_lending::id(),
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_oracle_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(sol_reserve.liquidity.market_price, sol_pyth_price.price);
+    assert!(!sol_reserve.last_update.stale);
+}
+
+#[tokio::test]
+async fn test_pyth_price_published_in_future_slot() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let sol_pyth_price = add_pyth_price(&mut test, 1_000);
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            pyth_price: Some(sol_pyth_price),
+            config: TEST_RESERVE_CONFIG,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(100).unwrap(); // clock.slot = 100
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            spl_token_lending::id(),
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_oracle_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidOraclePrice as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_token_swap_price() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    // price @ 1 SOL = 20 USDC, held since the pool and reserve last observed it
+    let sol_usdc_token_swap = add_token_swap_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        9,
+        1_000 * LAMPORTS_TO_SOL,
+        usdc_mint.pubkey,
+        20_000 * FRACTIONAL_TO_USDC,
+    );
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            token_swap: Some(sol_usdc_token_swap),
+            config: TEST_RESERVE_CONFIG,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            spl_token_lending::id(),
+            sol_test_reserve.pubkey,
+            sol_usdc_token_swap.oracle_pubkeys(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(sol_reserve.liquidity.market_price, 20 * FRACTIONAL_TO_USDC);
+    assert_eq!(
+        sol_reserve.liquidity.market_price,
+        sol_usdc_token_swap.price
+    );
+    assert!(
+        sol_reserve.price_observation.last_update_timestamp
+            > sol_usdc_token_swap.price_accumulators.last_update_timestamp
+    );
+}
@@ -102,6 +102,7 @@ async fn test_success() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
             ),
             repay_obligation_liquidity(
@@ -113,6 +114,7 @@ async fn test_success() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 user_transfer_authority.pubkey(),
+                None,
             ),
         ],
         Some(&payer.pubkey()),
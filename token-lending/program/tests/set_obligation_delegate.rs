@@ -0,0 +1,299 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{borrow_obligation_liquidity, refresh_obligation, set_obligation_delegate},
+    math::Decimal,
+    processor::process_instruction,
+    state::{DELEGATE_BORROW, DELEGATE_DEPOSIT, INITIAL_COLLATERAL_RATIO},
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+
+struct DelegateTest {
+    test: ProgramTest,
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+fn setup() -> DelegateTest {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    DelegateTest {
+        test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    }
+}
+
+async fn borrow_as_delegate(
+    permissions: u8,
+    borrow_limit: u64,
+    to_delegate_account: bool,
+) -> (BanksClient, TestObligation, Result<(), TransportError>) {
+    let DelegateTest {
+        test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    } = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let delegate = Keypair::new();
+    let destination_liquidity_pubkey = if to_delegate_account {
+        create_token_account(
+            &mut banks_client,
+            usdc_test_reserve.liquidity_mint_pubkey,
+            &payer,
+            Some(delegate.pubkey()),
+            None,
+        )
+        .await
+    } else {
+        usdc_test_reserve.user_liquidity_pubkey
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_obligation_delegate(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                test_obligation.owner,
+                delegate.pubkey(),
+                permissions,
+                borrow_limit,
+            ),
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                spl_token_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                destination_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                delegate.pubkey(),
+                None,
+                vec![],
+                Some(usdc_test_reserve.liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner, &delegate], recent_blockhash);
+    let result = banks_client.process_transaction(transaction).await;
+
+    (banks_client, test_obligation, result)
+}
+
+#[tokio::test]
+async fn test_success() {
+    let DelegateTest {
+        test,
+        user_accounts_owner,
+        test_obligation,
+        ..
+    } = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let delegate = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_obligation_delegate(
+            spl_token_lending::id(),
+            test_obligation.pubkey,
+            test_obligation.owner,
+            delegate.pubkey(),
+            DELEGATE_DEPOSIT | DELEGATE_BORROW,
+            1_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.delegate, delegate.pubkey());
+    assert_eq!(
+        obligation.delegate_permissions,
+        DELEGATE_DEPOSIT | DELEGATE_BORROW
+    );
+    assert_eq!(obligation.delegate_borrow_limit, 1_000);
+}
+
+#[tokio::test]
+async fn test_delegate_borrow() {
+    let (mut banks_client, test_obligation, result) =
+        borrow_as_delegate(DELEGATE_BORROW, 2 * USDC_BORROW_AMOUNT_FRACTIONAL, false).await;
+    assert!(result.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.borrows.len(), 1);
+    assert!(obligation.delegate_borrowed_value > Decimal::zero());
+}
+
+#[tokio::test]
+async fn test_delegate_borrow_limit_exceeded() {
+    let (_, _, result) =
+        borrow_as_delegate(DELEGATE_BORROW, USDC_BORROW_AMOUNT_FRACTIONAL / 2, false).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::DelegateBorrowLimitExceeded as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_delegate_zero_borrow_limit() {
+    let (_, _, result) = borrow_as_delegate(DELEGATE_BORROW, 0, false).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::DelegateBorrowLimitExceeded as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_delegate_borrow_to_delegate_account() {
+    let (_, _, result) =
+        borrow_as_delegate(DELEGATE_BORROW, 2 * USDC_BORROW_AMOUNT_FRACTIONAL, true).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_delegate_without_permission() {
+    let (_, _, result) = borrow_as_delegate(DELEGATE_DEPOSIT, 0, false).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let DelegateTest {
+        test,
+        test_obligation,
+        ..
+    } = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_obligation_delegate(
+            spl_token_lending::id(),
+            test_obligation.pubkey,
+            invalid_owner.pubkey(),
+            invalid_owner.pubkey(),
+            DELEGATE_DEPOSIT,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+}
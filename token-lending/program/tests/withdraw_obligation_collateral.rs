@@ -100,6 +100,7 @@ async fn test_withdraw_base_currency_fixed_amount() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
             ),
             withdraw_obligation_collateral(
@@ -208,6 +209,7 @@ async fn test_withdraw_quote_currency_all() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![usdc_test_reserve.pubkey],
             ),
             withdraw_obligation_collateral(
@@ -315,6 +317,7 @@ async fn test_withdraw_too_large() {
             refresh_obligation(
                 spl_token_lending::id(),
                 test_obligation.pubkey,
+                lending_market.pubkey,
                 vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
             ),
             withdraw_obligation_collateral(
@@ -4,6 +4,7 @@ use crate::{
     curve::{
         base::{CurveType, SwapCurve},
         fees::Fees,
+        multi_token::{MultiTokenCurve, MultiTokenCurveType},
     },
     error::SwapError,
 };
@@ -24,6 +25,8 @@ pub struct SwapConstraints<'a> {
     pub owner_key: &'a str,
     /// Valid curve types
     pub valid_curve_types: &'a [CurveType],
+    /// Valid curve types for multi-token pools
+    pub valid_multi_token_curve_types: &'a [MultiTokenCurveType],
     /// Valid fees
     pub fees: &'a Fees,
 }
@@ -42,6 +45,19 @@ impl<'a> SwapConstraints<'a> {
         }
     }
 
+    /// Checks that the provided multi-token curve is valid for the given
+    /// constraints
+    pub fn validate_multi_token_curve(&self, curve: &MultiTokenCurve) -> Result<(), ProgramError> {
+        if self
+            .valid_multi_token_curve_types
+            .contains(&curve.curve_type)
+        {
+            Ok(())
+        } else {
+            Err(SwapError::UnsupportedCurveType.into())
+        }
+    }
+
     /// Checks that the provided curve is valid for the given constraints
     pub fn validate_fees(&self, fees: &Fees) -> Result<(), ProgramError> {
         if fees.trade_fee_numerator >= self.fees.trade_fee_numerator
@@ -75,6 +91,9 @@ const FEES: &Fees = &Fees {
 };
 #[cfg(feature = "production")]
 const VALID_CURVE_TYPES: &[CurveType] = &[CurveType::ConstantPrice, CurveType::ConstantProduct];
+#[cfg(feature = "production")]
+const VALID_MULTI_TOKEN_CURVE_TYPES: &[MultiTokenCurveType] =
+    &[MultiTokenCurveType::WeightedConstantProduct];
 
 /// Fee structure defined by program creator in order to enforce certain
 /// fees when others use the program.  Adds checks on pool creation and
@@ -88,6 +107,7 @@ pub const SWAP_CONSTRAINTS: Option<SwapConstraints> = {
         Some(SwapConstraints {
             owner_key: OWNER_KEY,
             valid_curve_types: VALID_CURVE_TYPES,
+            valid_multi_token_curve_types: VALID_MULTI_TOKEN_CURVE_TYPES,
             fees: FEES,
         })
     }
@@ -133,6 +153,7 @@ mod tests {
         let constraints = SwapConstraints {
             owner_key,
             valid_curve_types: &[curve_type],
+            valid_multi_token_curve_types: &[MultiTokenCurveType::WeightedConstantProduct],
             fees: &valid_fees,
         };
 
@@ -193,5 +214,15 @@ mod tests {
             Err(SwapError::UnsupportedCurveType.into()),
             constraints.validate_curve(&swap_curve),
         );
+
+        let mut multi_token_curve = MultiTokenCurve::default();
+        constraints
+            .validate_multi_token_curve(&multi_token_curve)
+            .unwrap();
+        multi_token_curve.curve_type = MultiTokenCurveType::Stable;
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveType.into()),
+            constraints.validate_multi_token_curve(&multi_token_curve),
+        );
     }
 }
@@ -11,6 +11,7 @@ use crate::curve::{
     constant_product::ConstantProductCurve,
     fees::Fees,
     offset::OffsetCurve,
+    oracle::OracleCurve,
     stable::StableCurve,
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
@@ -29,6 +30,9 @@ pub enum CurveType {
     Stable,
     /// Offset curve, like Uniswap, but the token B side has a faked offset
     Offset,
+    /// Oracle curve, quoting a spread around the price of token B read from
+    /// an oracle account
+    Oracle,
 }
 
 /// Encodes all results of swapping from a source token to a destination token
@@ -130,6 +134,28 @@ impl SwapCurve {
             round_direction,
         )
     }
+
+    /// Get the parameters of an oracle curve, without a price, to be priced
+    /// before use
+    pub fn oracle_curve(&self) -> Option<OracleCurve> {
+        if self.curve_type != CurveType::Oracle {
+            return None;
+        }
+        let mut packed_calculator = [0u8; 32];
+        self.calculator.pack_into_slice(&mut packed_calculator);
+        OracleCurve::unpack_from_slice(&packed_calculator).ok()
+    }
+
+    /// Get the parameters of a stable curve, to read or ramp its
+    /// amplification
+    pub fn stable_curve(&self) -> Option<StableCurve> {
+        if self.curve_type != CurveType::Stable {
+            return None;
+        }
+        let mut packed_calculator = [0u8; 32];
+        self.calculator.pack_into_slice(&mut packed_calculator);
+        StableCurve::unpack_from_slice(&packed_calculator).ok()
+    }
 }
 
 /// Default implementation for SwapCurve cannot be derived because of
@@ -194,6 +220,7 @@ impl Pack for SwapCurve {
                 }
                 CurveType::Stable => Box::new(StableCurve::unpack_from_slice(calculator)?),
                 CurveType::Offset => Box::new(OffsetCurve::unpack_from_slice(calculator)?),
+                CurveType::Oracle => Box::new(OracleCurve::unpack_from_slice(calculator)?),
             },
         })
     }
@@ -224,6 +251,7 @@ impl TryFrom<u8> for CurveType {
             1 => Ok(CurveType::ConstantPrice),
             2 => Ok(CurveType::Stable),
             3 => Ok(CurveType::Offset),
+            4 => Ok(CurveType::Oracle),
             _ => Err(ProgramError::InvalidAccountData),
         }
     }
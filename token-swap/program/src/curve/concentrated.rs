@@ -0,0 +1,1152 @@
+//! Concentrated liquidity: liquidity provided over price ranges between
+//! ticks instead of the whole price curve.
+//!
+//! The price of token A in token B is stored as its square root, as a Q64.64
+//! fixed point number, and each tick `i` is the price `1.0001^i`.  Within a
+//! range of ticks the pool behaves like a constant product pool with
+//! `liquidity = sqrt(x * y)`, and the liquidity in use changes as swaps cross
+//! initialized ticks.
+
+use crate::{
+    curve::{
+        calculator::{RoundDirection, TradeDirection},
+        fees::Fees,
+    },
+    error::SwapError,
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+use spl_math::uint::U256;
+
+/// Lowest tick, where token A is worth about 2^-64 token B
+pub const MIN_TICK: i32 = -443_636;
+
+/// Highest tick, where token A is worth about 2^64 token B
+pub const MAX_TICK: i32 = 443_636;
+
+/// Largest spacing allowed between usable ticks
+pub const MAX_TICK_SPACING: u16 = 16_384;
+
+/// Largest number of initialized ticks a pool can be created with room for.
+/// Ticks are all stored in the pool account so that swaps do not need extra
+/// accounts, and must fit in the program heap once unpacked.
+pub const MAX_INITIALIZED_TICKS: u16 = 256;
+
+/// Maximum liquidity referencing one tick, which keeps all token amount
+/// calculations within 256 bits
+pub const MAX_TICK_LIQUIDITY: u128 = u64::MAX as u128;
+
+/// Q64.64 fixed point one
+const Q64: u128 = 1 << 64;
+
+/// sqrt(1.0001)^(-2^i) for i from 0 to 19, as Q128.128 fixed point numbers, to
+/// keep the precision of their products
+const SQRT_RATIO_NEG_POWERS: [u128; 20] = [
+    340_265_354_078_544_963_557_816_517_032_075_149_313,
+    340_248_342_086_729_790_484_326_174_814_286_782_777,
+    340_214_320_654_664_324_051_920_982_716_015_181_259,
+    340_146_287_995_602_323_631_171_512_101_879_684_303,
+    340_010_263_488_231_146_823_593_991_679_159_461_443,
+    339_738_377_640_345_403_697_157_401_104_375_502_015,
+    339_195_258_003_219_555_707_034_227_454_543_997_024,
+    338_111_622_100_601_834_656_805_679_988_414_885_970,
+    335_954_724_994_790_223_023_589_805_789_778_977_699,
+    331_682_121_138_379_247_127_172_139_078_559_817_299,
+    323_299_236_684_853_023_288_211_250_268_160_618_738,
+    307_163_716_377_032_989_948_697_243_942_600_083_928,
+    277_268_403_626_896_220_162_999_269_216_087_595_045,
+    225_923_453_940_442_621_947_126_027_127_485_391_332,
+    149_997_214_084_966_997_727_330_242_082_538_205_942,
+    66_119_101_136_024_775_622_716_233_608_466_517_925,
+    12_847_376_061_809_297_530_290_974_190_478_138_312,
+    485_053_260_817_066_172_746_253_684_029_974_020,
+    691_415_978_906_521_570_653_435_304_214_167,
+    1_404_880_482_679_654_955_896_180_642,
+];
+
+/// Square root price of a tick, as a Q64.64 fixed point number
+pub fn sqrt_price_at_tick(tick: i32) -> Option<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = U256::one() << 128;
+    for (i, factor) in SQRT_RATIO_NEG_POWERS.iter().enumerate() {
+        if abs_tick & (1 << i) != 0 {
+            ratio = ratio.checked_mul(U256::from(*factor))? >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX.checked_div(ratio)?;
+    }
+    u256_to_u128(ratio >> 64)
+}
+
+/// Greatest tick whose square root price is less than or equal to the given
+/// square root price
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Option<i32> {
+    if sqrt_price < sqrt_price_at_tick(MIN_TICK)? || sqrt_price > sqrt_price_at_tick(MAX_TICK)? {
+        return None;
+    }
+    let mut low = MIN_TICK;
+    let mut high = MAX_TICK;
+    while low < high {
+        let middle = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(middle)? <= sqrt_price {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    Some(low)
+}
+
+fn ceil_div(numerator: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let quotient = numerator / denominator;
+    if (quotient * denominator) == numerator {
+        Some(quotient)
+    } else {
+        quotient.checked_add(U256::one())
+    }
+}
+
+fn u256_to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+/// Amount of token A held by the liquidity between two square root prices,
+/// `liquidity * (1 / sqrt_price_lower - 1 / sqrt_price_upper)`
+pub fn token_a_delta(
+    sqrt_price_0: u128,
+    sqrt_price_1: u128,
+    liquidity: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let (lower, upper) = if sqrt_price_0 <= sqrt_price_1 {
+        (sqrt_price_0, sqrt_price_1)
+    } else {
+        (sqrt_price_1, sqrt_price_0)
+    };
+    if lower == 0 {
+        return None;
+    }
+    let numerator = (U256::from(liquidity) << 64).checked_mul(U256::from(upper - lower))?;
+    let amount = match round_direction {
+        RoundDirection::Floor => numerator / U256::from(upper) / U256::from(lower),
+        RoundDirection::Ceiling => {
+            ceil_div(ceil_div(numerator, U256::from(upper))?, U256::from(lower))?
+        }
+    };
+    u256_to_u128(amount)
+}
+
+/// Amount of token B held by the liquidity between two square root prices,
+/// `liquidity * (sqrt_price_upper - sqrt_price_lower)`
+pub fn token_b_delta(
+    sqrt_price_0: u128,
+    sqrt_price_1: u128,
+    liquidity: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let (lower, upper) = if sqrt_price_0 <= sqrt_price_1 {
+        (sqrt_price_0, sqrt_price_1)
+    } else {
+        (sqrt_price_1, sqrt_price_0)
+    };
+    let product = U256::from(liquidity).checked_mul(U256::from(upper - lower))?;
+    let amount = match round_direction {
+        RoundDirection::Floor => product >> 64,
+        RoundDirection::Ceiling => ceil_div(product, U256::from(Q64))?,
+    };
+    u256_to_u128(amount)
+}
+
+/// Square root price after adding token A, rounded up so that the pool
+/// keeps enough token B
+fn next_sqrt_price_from_token_a_in(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u128,
+) -> Option<u128> {
+    let liquidity = U256::from(liquidity) << 64;
+    let numerator = liquidity.checked_mul(U256::from(sqrt_price))?;
+    let denominator =
+        liquidity.checked_add(U256::from(amount).checked_mul(U256::from(sqrt_price))?)?;
+    u256_to_u128(ceil_div(numerator, denominator)?)
+}
+
+/// Square root price after adding token B, rounded down so that the pool
+/// keeps enough token A
+fn next_sqrt_price_from_token_b_in(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u128,
+) -> Option<u128> {
+    let delta = (U256::from(amount) << 64).checked_div(U256::from(liquidity))?;
+    sqrt_price.checked_add(u256_to_u128(delta)?)
+}
+
+/// Fees earned by liquidity since the last recorded fee growth, rounded down
+pub fn fees_earned(liquidity: u128, fee_growth: u128, fee_growth_last: u128) -> Option<u128> {
+    let growth = fee_growth.wrapping_sub(fee_growth_last);
+    u256_to_u128(U256::from(liquidity).checked_mul(U256::from(growth))? >> 64)
+}
+
+/// Apply a signed liquidity change
+pub fn add_liquidity_delta(liquidity: u128, liquidity_delta: i128) -> Option<u128> {
+    if liquidity_delta >= 0 {
+        liquidity.checked_add(liquidity_delta as u128)
+    } else {
+        liquidity.checked_sub(liquidity_delta.unsigned_abs())
+    }
+}
+
+/// Result of one step of a swap, within a range of constant liquidity
+#[derive(Debug, PartialEq)]
+struct SwapStep {
+    sqrt_price_next: u128,
+    amount_in: u128,
+    amount_out: u128,
+    fee_amount: u128,
+}
+
+/// Swap as much of the remaining amount as possible before reaching the
+/// target price, taking the trade fee from the input
+fn compute_swap_step(
+    sqrt_price: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u128,
+    fees: &Fees,
+) -> Option<SwapStep> {
+    let a_to_b = sqrt_price_target <= sqrt_price;
+    let fee_numerator = u128::from(fees.trade_fee_numerator);
+    let fee_denominator = u128::from(fees.trade_fee_denominator);
+    let amount_remaining_less_fee = if fee_numerator == 0 {
+        amount_remaining
+    } else {
+        amount_remaining
+            .checked_mul(fee_denominator.checked_sub(fee_numerator)?)?
+            .checked_div(fee_denominator)?
+    };
+
+    let amount_in_to_target = if a_to_b {
+        token_a_delta(
+            sqrt_price_target,
+            sqrt_price,
+            liquidity,
+            RoundDirection::Ceiling,
+        )?
+    } else {
+        token_b_delta(
+            sqrt_price,
+            sqrt_price_target,
+            liquidity,
+            RoundDirection::Ceiling,
+        )?
+    };
+    let reached_target = amount_remaining_less_fee >= amount_in_to_target;
+    let (sqrt_price_next, amount_in) = if reached_target {
+        (sqrt_price_target, amount_in_to_target)
+    } else if a_to_b {
+        let next =
+            next_sqrt_price_from_token_a_in(sqrt_price, liquidity, amount_remaining_less_fee)?;
+        (
+            next,
+            token_a_delta(next, sqrt_price, liquidity, RoundDirection::Ceiling)?,
+        )
+    } else {
+        let next =
+            next_sqrt_price_from_token_b_in(sqrt_price, liquidity, amount_remaining_less_fee)?;
+        (
+            next,
+            token_b_delta(sqrt_price, next, liquidity, RoundDirection::Ceiling)?,
+        )
+    };
+    let amount_out = if a_to_b {
+        token_b_delta(
+            sqrt_price_next,
+            sqrt_price,
+            liquidity,
+            RoundDirection::Floor,
+        )?
+    } else {
+        token_a_delta(
+            sqrt_price,
+            sqrt_price_next,
+            liquidity,
+            RoundDirection::Floor,
+        )?
+    };
+    let fee_amount = if !reached_target {
+        // the whole remainder is used, anything not swapped is kept as fee
+        amount_remaining.checked_sub(amount_in)?
+    } else if fee_numerator == 0 {
+        0
+    } else {
+        let numerator = U256::from(amount_in).checked_mul(U256::from(fee_numerator))?;
+        let denominator = U256::from(fee_denominator.checked_sub(fee_numerator)?);
+        u256_to_u128(ceil_div(numerator, denominator)?)?
+    };
+    Some(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+/// Result of a swap through concentrated liquidity
+#[derive(Debug, PartialEq)]
+pub struct ConcentratedSwapResult {
+    /// Amount of destination token swapped
+    pub destination_amount_swapped: u128,
+    /// Amount of source token kept as fees for liquidity providers
+    pub trade_fee: u128,
+}
+
+/// Initialized tick, the lower or upper bound of at least one position
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tick {
+    /// Index of the tick, its price is `1.0001^index`
+    pub index: i32,
+    /// Liquidity added when the price crosses the tick upwards, and removed
+    /// when crossing it downwards
+    pub liquidity_net: i128,
+    /// Total liquidity of the positions using the tick as a bound
+    pub liquidity_gross: u128,
+    /// Token A fee growth per unit of liquidity on the other side of the
+    /// tick from the current price, as a Q64.64 number
+    pub fee_growth_outside_a: u128,
+    /// Token B fee growth per unit of liquidity on the other side of the
+    /// tick from the current price, as a Q64.64 number
+    pub fee_growth_outside_b: u128,
+}
+
+impl Sealed for Tick {}
+impl Pack for Tick {
+    const LEN: usize = 68;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 68];
+        let (index, liquidity_net, liquidity_gross, fee_growth_outside_a, fee_growth_outside_b) =
+            mut_array_refs![output, 4, 16, 16, 16, 16];
+        *index = self.index.to_le_bytes();
+        *liquidity_net = self.liquidity_net.to_le_bytes();
+        *liquidity_gross = self.liquidity_gross.to_le_bytes();
+        *fee_growth_outside_a = self.fee_growth_outside_a.to_le_bytes();
+        *fee_growth_outside_b = self.fee_growth_outside_b.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 68];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (index, liquidity_net, liquidity_gross, fee_growth_outside_a, fee_growth_outside_b) =
+            array_refs![input, 4, 16, 16, 16, 16];
+        Ok(Self {
+            index: i32::from_le_bytes(*index),
+            liquidity_net: i128::from_le_bytes(*liquidity_net),
+            liquidity_gross: u128::from_le_bytes(*liquidity_gross),
+            fee_growth_outside_a: u128::from_le_bytes(*fee_growth_outside_a),
+            fee_growth_outside_b: u128::from_le_bytes(*fee_growth_outside_b),
+        })
+    }
+}
+
+/// Concentrated liquidity curve, with the current price, the liquidity in
+/// use and all initialized ticks of a pool
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConcentratedCurve {
+    /// Positions may only use ticks that are multiples of the spacing
+    pub tick_spacing: u16,
+    /// Minimum liquidity of a position that holds any
+    pub min_position_liquidity: u128,
+    /// Number of initialized ticks the pool account has room for, chosen
+    /// when the pool is created.  Positions must hold at least the minimum
+    /// liquidity, so that taking every slot locks up real capital.
+    pub max_ticks: u16,
+    /// Square root of the price of token A in token B, as a Q64.64 number
+    pub sqrt_price: u128,
+    /// Greatest tick at or below the current price
+    pub tick_current: i32,
+    /// Liquidity of all positions whose range contains the current price
+    pub liquidity: u128,
+    /// Token A fees earned per unit of liquidity over the life of the pool,
+    /// as a Q64.64 number which may wrap around
+    pub fee_growth_global_a: u128,
+    /// Token B fees earned per unit of liquidity over the life of the pool,
+    /// as a Q64.64 number which may wrap around
+    pub fee_growth_global_b: u128,
+    /// Initialized ticks, sorted by index
+    pub ticks: Vec<Tick>,
+}
+
+impl ConcentratedCurve {
+    /// Create a curve without liquidity at the given price
+    pub fn new(
+        tick_spacing: u16,
+        sqrt_price: u128,
+        min_position_liquidity: u128,
+        max_ticks: u16,
+    ) -> Result<Self, SwapError> {
+        if tick_spacing == 0
+            || tick_spacing > MAX_TICK_SPACING
+            || min_position_liquidity == 0
+            || !(2..=MAX_INITIALIZED_TICKS).contains(&max_ticks)
+        {
+            return Err(SwapError::InvalidCurve);
+        }
+        let max_sqrt_price = sqrt_price_at_tick(MAX_TICK).ok_or(SwapError::CalculationFailure)?;
+        if sqrt_price >= max_sqrt_price {
+            return Err(SwapError::InvalidCurve);
+        }
+        let tick_current = tick_at_sqrt_price(sqrt_price).ok_or(SwapError::InvalidCurve)?;
+        Ok(Self {
+            tick_spacing,
+            min_position_liquidity,
+            max_ticks,
+            sqrt_price,
+            tick_current,
+            ..Self::default()
+        })
+    }
+
+    /// Length of a packed curve with room for `max_ticks` initialized ticks
+    pub fn packed_len(max_ticks: u16) -> usize {
+        Self::LEN + usize::from(max_ticks) * Tick::LEN
+    }
+
+    /// Check that a position holds no liquidity or at least the minimum
+    pub fn validate_position_liquidity(&self, liquidity: u128) -> Result<(), SwapError> {
+        if liquidity > 0 && liquidity < self.min_position_liquidity {
+            Err(SwapError::PositionBelowMinLiquidity)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that a position range uses valid ticks
+    pub fn validate_tick_range(&self, tick_lower: i32, tick_upper: i32) -> Result<(), SwapError> {
+        let spacing = i32::from(self.tick_spacing);
+        if tick_lower >= tick_upper
+            || tick_lower < MIN_TICK
+            || tick_upper > MAX_TICK
+            || tick_lower % spacing != 0
+            || tick_upper % spacing != 0
+        {
+            Err(SwapError::InvalidTick)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Initialized tick at the given index
+    pub fn tick(&self, index: i32) -> Option<&Tick> {
+        self.ticks
+            .binary_search_by_key(&index, |tick| tick.index)
+            .ok()
+            .map(|position| &self.ticks[position])
+    }
+
+    /// Add liquidity to, or remove it from, the bounds of a range,
+    /// initializing its ticks if needed.  Ticks left without liquidity stay
+    /// initialized until `clear_ticks` so that the fees of the range can
+    /// still be read.
+    pub fn update_ticks(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i128,
+    ) -> Result<(), SwapError> {
+        self.update_tick(tick_lower, liquidity_delta, false)?;
+        self.update_tick(tick_upper, liquidity_delta, true)?;
+        if tick_lower <= self.tick_current && self.tick_current < tick_upper {
+            self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)
+                .ok_or(SwapError::CalculationFailure)?;
+        }
+        Ok(())
+    }
+
+    fn update_tick(
+        &mut self,
+        index: i32,
+        liquidity_delta: i128,
+        upper: bool,
+    ) -> Result<(), SwapError> {
+        let position = match self.ticks.binary_search_by_key(&index, |tick| tick.index) {
+            Ok(position) => position,
+            Err(position) => {
+                if self.ticks.len() >= usize::from(self.max_ticks) {
+                    return Err(SwapError::TooManyTicks);
+                }
+                // by convention, all fees before initialization were earned
+                // below the tick
+                let (fee_growth_outside_a, fee_growth_outside_b) = if index <= self.tick_current {
+                    (self.fee_growth_global_a, self.fee_growth_global_b)
+                } else {
+                    (0, 0)
+                };
+                self.ticks.insert(
+                    position,
+                    Tick {
+                        index,
+                        fee_growth_outside_a,
+                        fee_growth_outside_b,
+                        ..Tick::default()
+                    },
+                );
+                position
+            }
+        };
+        let tick = &mut self.ticks[position];
+        tick.liquidity_gross = add_liquidity_delta(tick.liquidity_gross, liquidity_delta)
+            .filter(|liquidity| *liquidity <= MAX_TICK_LIQUIDITY)
+            .ok_or(SwapError::CalculationFailure)?;
+        tick.liquidity_net = if upper {
+            tick.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            tick.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(SwapError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// Remove the bounds of a range if no position uses them anymore
+    pub fn clear_ticks(&mut self, tick_lower: i32, tick_upper: i32) {
+        self.ticks.retain(|tick| {
+            tick.liquidity_gross > 0 || (tick.index != tick_lower && tick.index != tick_upper)
+        });
+    }
+
+    /// Fee growth per unit of liquidity inside a range of initialized ticks,
+    /// for token A and B
+    pub fn fee_growth_inside(&self, tick_lower: i32, tick_upper: i32) -> Option<(u128, u128)> {
+        let lower = self.tick(tick_lower)?;
+        let upper = self.tick(tick_upper)?;
+        let (below_a, below_b) = if self.tick_current >= tick_lower {
+            (lower.fee_growth_outside_a, lower.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(lower.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(lower.fee_growth_outside_b),
+            )
+        };
+        let (above_a, above_b) = if self.tick_current < tick_upper {
+            (upper.fee_growth_outside_a, upper.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(upper.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(upper.fee_growth_outside_b),
+            )
+        };
+        Some((
+            self.fee_growth_global_a
+                .wrapping_sub(below_a)
+                .wrapping_sub(above_a),
+            self.fee_growth_global_b
+                .wrapping_sub(below_b)
+                .wrapping_sub(above_b),
+        ))
+    }
+
+    /// Amounts of token A and B backing the liquidity of a range at the
+    /// current price
+    pub fn token_amounts(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        round_direction: RoundDirection,
+    ) -> Option<(u128, u128)> {
+        let sqrt_price_lower = sqrt_price_at_tick(tick_lower)?;
+        let sqrt_price_upper = sqrt_price_at_tick(tick_upper)?;
+        if self.tick_current < tick_lower {
+            Some((
+                token_a_delta(
+                    sqrt_price_lower,
+                    sqrt_price_upper,
+                    liquidity,
+                    round_direction,
+                )?,
+                0,
+            ))
+        } else if self.tick_current < tick_upper {
+            Some((
+                token_a_delta(
+                    self.sqrt_price,
+                    sqrt_price_upper,
+                    liquidity,
+                    round_direction,
+                )?,
+                token_b_delta(
+                    sqrt_price_lower,
+                    self.sqrt_price,
+                    liquidity,
+                    round_direction,
+                )?,
+            ))
+        } else {
+            Some((
+                0,
+                token_b_delta(
+                    sqrt_price_lower,
+                    sqrt_price_upper,
+                    liquidity,
+                    round_direction,
+                )?,
+            ))
+        }
+    }
+
+    /// Swap an exact amount of source token, crossing initialized ticks until
+    /// all of it is used.  Fails if the pool runs out of liquidity first.
+    pub fn swap(
+        &mut self,
+        source_amount: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Result<ConcentratedSwapResult, SwapError> {
+        let mut amount_remaining = source_amount;
+        let mut destination_amount_swapped = 0u128;
+        let mut trade_fee = 0u128;
+        while amount_remaining > 0 {
+            let next_tick = match trade_direction {
+                TradeDirection::AtoB => self
+                    .ticks
+                    .iter()
+                    .rev()
+                    .find(|tick| tick.index <= self.tick_current),
+                TradeDirection::BtoA => self
+                    .ticks
+                    .iter()
+                    .find(|tick| tick.index > self.tick_current),
+            }
+            .map(|tick| tick.index);
+            let target_tick = next_tick.unwrap_or(match trade_direction {
+                TradeDirection::AtoB => MIN_TICK,
+                TradeDirection::BtoA => MAX_TICK,
+            });
+            let sqrt_price_target =
+                sqrt_price_at_tick(target_tick).ok_or(SwapError::CalculationFailure)?;
+
+            let step = compute_swap_step(
+                self.sqrt_price,
+                sqrt_price_target,
+                self.liquidity,
+                amount_remaining,
+                fees,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+            amount_remaining = amount_remaining
+                .checked_sub(step.amount_in)
+                .and_then(|amount| amount.checked_sub(step.fee_amount))
+                .ok_or(SwapError::CalculationFailure)?;
+            destination_amount_swapped = destination_amount_swapped
+                .checked_add(step.amount_out)
+                .ok_or(SwapError::CalculationFailure)?;
+            trade_fee = trade_fee
+                .checked_add(step.fee_amount)
+                .ok_or(SwapError::CalculationFailure)?;
+            if self.liquidity > 0 && step.fee_amount > 0 {
+                let fee_growth =
+                    u256_to_u128((U256::from(step.fee_amount) << 64) / U256::from(self.liquidity))
+                        .ok_or(SwapError::CalculationFailure)?;
+                match trade_direction {
+                    TradeDirection::AtoB => {
+                        self.fee_growth_global_a = self.fee_growth_global_a.wrapping_add(fee_growth)
+                    }
+                    TradeDirection::BtoA => {
+                        self.fee_growth_global_b = self.fee_growth_global_b.wrapping_add(fee_growth)
+                    }
+                }
+            }
+
+            self.sqrt_price = step.sqrt_price_next;
+            if step.sqrt_price_next == sqrt_price_target {
+                let index = next_tick.ok_or(SwapError::InsufficientLiquidity)?;
+                self.cross_tick(index, trade_direction)?;
+            } else {
+                self.tick_current =
+                    tick_at_sqrt_price(self.sqrt_price).ok_or(SwapError::CalculationFailure)?;
+            }
+        }
+        Ok(ConcentratedSwapResult {
+            destination_amount_swapped,
+            trade_fee,
+        })
+    }
+
+    /// Move the price across an initialized tick, flipping its fee growth
+    /// outside and updating the liquidity in use
+    fn cross_tick(&mut self, index: i32, trade_direction: TradeDirection) -> Result<(), SwapError> {
+        let position = self
+            .ticks
+            .binary_search_by_key(&index, |tick| tick.index)
+            .map_err(|_| SwapError::CalculationFailure)?;
+        let tick = &mut self.ticks[position];
+        tick.fee_growth_outside_a = self
+            .fee_growth_global_a
+            .wrapping_sub(tick.fee_growth_outside_a);
+        tick.fee_growth_outside_b = self
+            .fee_growth_global_b
+            .wrapping_sub(tick.fee_growth_outside_b);
+        let liquidity_delta = match trade_direction {
+            TradeDirection::AtoB => tick
+                .liquidity_net
+                .checked_neg()
+                .ok_or(SwapError::CalculationFailure)?,
+            TradeDirection::BtoA => tick.liquidity_net,
+        };
+        self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)
+            .ok_or(SwapError::CalculationFailure)?;
+        self.tick_current = match trade_direction {
+            TradeDirection::AtoB => index - 1,
+            TradeDirection::BtoA => index,
+        };
+        Ok(())
+    }
+}
+
+impl Sealed for ConcentratedCurve {}
+impl IsInitialized for ConcentratedCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+/// The packed curve is followed by `max_ticks` slots of `Tick::LEN` bytes,
+/// which are not included in `LEN`
+impl Pack for ConcentratedCurve {
+    const LEN: usize = 90;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let (output, ticks) = output.split_at_mut(Self::LEN);
+        let output = array_mut_ref![output, 0, 90];
+        let (
+            tick_spacing,
+            min_position_liquidity,
+            sqrt_price,
+            tick_current,
+            liquidity,
+            fee_growth_global_a,
+            fee_growth_global_b,
+            max_ticks,
+            tick_count,
+        ) = mut_array_refs![output, 2, 16, 16, 4, 16, 16, 16, 2, 2];
+        *tick_spacing = self.tick_spacing.to_le_bytes();
+        *min_position_liquidity = self.min_position_liquidity.to_le_bytes();
+        *sqrt_price = self.sqrt_price.to_le_bytes();
+        *tick_current = self.tick_current.to_le_bytes();
+        *liquidity = self.liquidity.to_le_bytes();
+        *fee_growth_global_a = self.fee_growth_global_a.to_le_bytes();
+        *fee_growth_global_b = self.fee_growth_global_b.to_le_bytes();
+        *max_ticks = self.max_ticks.to_le_bytes();
+        *tick_count = (self.ticks.len() as u16).to_le_bytes();
+        let ticks = &mut ticks[..usize::from(self.max_ticks) * Tick::LEN];
+        ticks.fill(0);
+        for (dst, src) in ticks.chunks_mut(Tick::LEN).zip(self.ticks.iter()) {
+            src.pack_into_slice(dst);
+        }
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let (input, ticks_data) = input.split_at(Self::LEN);
+        let input = array_ref![input, 0, 90];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            tick_spacing,
+            min_position_liquidity,
+            sqrt_price,
+            tick_current,
+            liquidity,
+            fee_growth_global_a,
+            fee_growth_global_b,
+            max_ticks,
+            tick_count,
+        ) = array_refs![input, 2, 16, 16, 4, 16, 16, 16, 2, 2];
+        let max_ticks = u16::from_le_bytes(*max_ticks);
+        let tick_count = u16::from_le_bytes(*tick_count);
+        if tick_count > max_ticks || ticks_data.len() < usize::from(max_ticks) * Tick::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // room for every tick up front, so that initializing one never
+        // reallocates
+        let mut ticks = Vec::with_capacity(usize::from(max_ticks));
+        for tick_data in ticks_data.chunks(Tick::LEN).take(usize::from(tick_count)) {
+            ticks.push(Tick::unpack_from_slice(tick_data)?);
+        }
+        Ok(Self {
+            tick_spacing: u16::from_le_bytes(*tick_spacing),
+            min_position_liquidity: u128::from_le_bytes(*min_position_liquidity),
+            max_ticks,
+            sqrt_price: u128::from_le_bytes(*sqrt_price),
+            tick_current: i32::from_le_bytes(*tick_current),
+            liquidity: u128::from_le_bytes(*liquidity),
+            fee_growth_global_a: u128::from_le_bytes(*fee_growth_global_a),
+            fee_growth_global_b: u128::from_le_bytes(*fee_growth_global_b),
+            ticks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees(trade_fee_numerator: u64, trade_fee_denominator: u64) -> Fees {
+        Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            ..Fees::default()
+        }
+    }
+
+    fn curve_with_position(
+        tick_spacing: u16,
+        tick_current: i32,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+    ) -> ConcentratedCurve {
+        let mut curve = ConcentratedCurve::new(
+            tick_spacing,
+            sqrt_price_at_tick(tick_current).unwrap(),
+            1,
+            8,
+        )
+        .unwrap();
+        curve
+            .update_ticks(tick_lower, tick_upper, liquidity as i128)
+            .unwrap();
+        curve
+    }
+
+    #[test]
+    fn sqrt_price_at_tick_bounds() {
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), Q64);
+        assert_eq!(sqrt_price_at_tick(MIN_TICK - 1), None);
+        assert_eq!(sqrt_price_at_tick(MAX_TICK + 1), None);
+
+        // exact values of 2^64 * sqrt(1.0001)^tick
+        let expected = [
+            (MIN_TICK, 4_295_048_016u128),
+            (-1, 18_445_821_805_675_392_311),
+            (1, 18_447_666_387_855_959_850),
+            (MAX_TICK, 79_226_673_515_401_279_992_447_579_061),
+        ];
+        for (tick, expected) in expected.iter() {
+            let sqrt_price = sqrt_price_at_tick(*tick).unwrap();
+            let difference = if sqrt_price > *expected {
+                sqrt_price - expected
+            } else {
+                expected - sqrt_price
+            };
+            assert!(difference <= 1, "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn tick_at_sqrt_price_round_trip() {
+        let ticks = [
+            MIN_TICK,
+            -300_001,
+            -100_000,
+            -1,
+            0,
+            1,
+            12_345,
+            300_001,
+            MAX_TICK - 1,
+        ];
+        for tick in ticks.iter() {
+            let sqrt_price = sqrt_price_at_tick(*tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), *tick);
+            let next_sqrt_price = sqrt_price_at_tick(tick + 1).unwrap();
+            assert!(next_sqrt_price > sqrt_price);
+            assert_eq!(tick_at_sqrt_price(next_sqrt_price - 1).unwrap(), *tick);
+        }
+        assert_eq!(
+            tick_at_sqrt_price(sqrt_price_at_tick(MAX_TICK).unwrap()).unwrap(),
+            MAX_TICK
+        );
+        assert_eq!(
+            tick_at_sqrt_price(sqrt_price_at_tick(MIN_TICK).unwrap() - 1),
+            None
+        );
+    }
+
+    #[test]
+    fn token_amounts_of_range() {
+        // price 1, range of about +-10%
+        let liquidity = 1_000_000_000u128;
+        let curve = curve_with_position(10, 0, -1000, 1000, liquidity);
+        let (token_a, token_b) = curve
+            .token_amounts(-1000, 1000, liquidity, RoundDirection::Floor)
+            .unwrap();
+        // liquidity * (1 - 1.0001^-500), about the same for both tokens at price 1
+        assert_eq!(token_a, 48_768_197);
+        assert!(token_b == token_a || token_b + 1 == token_a || token_b == token_a + 1);
+        let (token_a_up, token_b_up) = curve
+            .token_amounts(-1000, 1000, liquidity, RoundDirection::Ceiling)
+            .unwrap();
+        assert_eq!(token_a_up, token_a + 1);
+        assert_eq!(token_b_up, token_b + 1);
+
+        // ranges above the price only hold token A, below only token B
+        let (token_a, token_b) = curve
+            .token_amounts(10, 1000, liquidity, RoundDirection::Floor)
+            .unwrap();
+        assert!(token_a > 0);
+        assert_eq!(token_b, 0);
+        let (token_a, token_b) = curve
+            .token_amounts(-1000, -10, liquidity, RoundDirection::Floor)
+            .unwrap();
+        assert_eq!(token_a, 0);
+        assert!(token_b > 0);
+    }
+
+    #[test]
+    fn validate_ticks() {
+        let curve = ConcentratedCurve::new(60, Q64, 1, 8).unwrap();
+        assert_eq!(curve.validate_tick_range(-60, 60), Ok(()));
+        assert_eq!(
+            curve.validate_tick_range(60, -60),
+            Err(SwapError::InvalidTick)
+        );
+        assert_eq!(curve.validate_tick_range(0, 0), Err(SwapError::InvalidTick));
+        assert_eq!(
+            curve.validate_tick_range(-60, 90),
+            Err(SwapError::InvalidTick)
+        );
+        let min_tick = MIN_TICK - MIN_TICK % 60;
+        assert_eq!(curve.validate_tick_range(min_tick, 0), Ok(()));
+        assert_eq!(
+            curve.validate_tick_range(min_tick - 60, 0),
+            Err(SwapError::InvalidTick)
+        );
+
+        assert_eq!(
+            ConcentratedCurve::new(0, Q64, 1, 8),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedCurve::new(MAX_TICK_SPACING + 1, Q64, 1, 8),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedCurve::new(1, sqrt_price_at_tick(MAX_TICK).unwrap(), 1, 8),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedCurve::new(1, sqrt_price_at_tick(MIN_TICK).unwrap() - 1, 1, 8),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedCurve::new(1, Q64, 0, 8),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedCurve::new(1, Q64, 1, 1),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedCurve::new(1, Q64, 1, MAX_INITIALIZED_TICKS + 1),
+            Err(SwapError::InvalidCurve)
+        );
+    }
+
+    #[test]
+    fn validate_position_liquidity() {
+        let curve = ConcentratedCurve::new(60, Q64, 1_000, 8).unwrap();
+        assert_eq!(curve.validate_position_liquidity(0), Ok(()));
+        assert_eq!(
+            curve.validate_position_liquidity(999),
+            Err(SwapError::PositionBelowMinLiquidity)
+        );
+        assert_eq!(curve.validate_position_liquidity(1_000), Ok(()));
+    }
+
+    #[test]
+    fn swap_within_range_matches_constant_product() {
+        let liquidity = 1_000_000_000_000u128;
+        let mut curve = curve_with_position(1, 0, -10_000, 10_000, liquidity);
+        let source_amount = 1_000_000u128;
+        let result = curve
+            .swap(source_amount, TradeDirection::AtoB, &fees(0, 0))
+            .unwrap();
+        // at price 1, the virtual reserves of both tokens are the liquidity
+        let expected = liquidity * source_amount / (liquidity + source_amount);
+        assert!(result.destination_amount_swapped <= expected);
+        assert!(result.destination_amount_swapped + 1 >= expected);
+        assert_eq!(result.trade_fee, 0);
+        assert!(curve.sqrt_price < Q64);
+        assert_eq!(
+            curve.tick_current,
+            tick_at_sqrt_price(curve.sqrt_price).unwrap()
+        );
+        assert_eq!(curve.liquidity, liquidity);
+
+        let result = curve
+            .swap(
+                result.destination_amount_swapped,
+                TradeDirection::BtoA,
+                &fees(0, 0),
+            )
+            .unwrap();
+        // rounding keeps the round trip from making a profit
+        assert!(result.destination_amount_swapped < source_amount);
+        assert!(result.destination_amount_swapped + 2 >= source_amount);
+    }
+
+    #[test]
+    fn swap_fees_accrue_to_liquidity_in_range() {
+        let liquidity = 1_000_000_000u128;
+        let mut curve = curve_with_position(10, 0, -100, 100, liquidity);
+        curve.update_ticks(100, 200, liquidity as i128).unwrap();
+        let result = curve
+            .swap(100_000, TradeDirection::BtoA, &fees(1, 100))
+            .unwrap();
+        assert_eq!(result.trade_fee, 1_000);
+        assert_eq!(curve.fee_growth_global_a, 0);
+        assert_eq!(curve.fee_growth_global_b, (1_000u128 << 64) / liquidity);
+
+        let (inside_a, inside_b) = curve.fee_growth_inside(-100, 100).unwrap();
+        assert_eq!(inside_a, 0);
+        assert_eq!(
+            fees_earned(liquidity, inside_b, 0).unwrap(),
+            999 // rounded down
+        );
+        assert_eq!(curve.fee_growth_inside(100, 200).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn swap_crosses_initialized_ticks() {
+        let liquidity = 1_000_000_000u128;
+        // one wide position and one narrow position around the price
+        let mut curve = curve_with_position(10, 5, -1000, 1000, liquidity);
+        curve.update_ticks(-100, 100, liquidity as i128).unwrap();
+        assert_eq!(curve.liquidity, 2 * liquidity);
+        assert_eq!(curve.ticks.len(), 4);
+
+        // enough to move the price below the narrow position
+        let result = curve
+            .swap(20_000_000, TradeDirection::AtoB, &fees(3, 1000))
+            .unwrap();
+        assert!(curve.tick_current < -100);
+        assert!(curve.tick_current >= -1000);
+        assert_eq!(curve.liquidity, liquidity);
+        let lower = curve.tick(-100).unwrap();
+        // the narrow position earned all the fees it was in range for
+        assert_ne!(lower.fee_growth_outside_a, 0);
+        let (wide_a, _) = curve.fee_growth_inside(-1000, 1000).unwrap();
+        let (narrow_a, _) = curve.fee_growth_inside(-100, 100).unwrap();
+        assert!(wide_a > narrow_a);
+        let wide_fees = fees_earned(liquidity, wide_a, 0).unwrap();
+        let narrow_fees = fees_earned(liquidity, narrow_a, 0).unwrap();
+        assert!(wide_fees + narrow_fees <= result.trade_fee);
+        assert!(wide_fees + narrow_fees + 2 >= result.trade_fee);
+
+        // swapping back crosses the tick the other way
+        curve
+            .swap(19_000_000, TradeDirection::BtoA, &fees(3, 1000))
+            .unwrap();
+        assert!(curve.tick_current >= -100);
+        assert_eq!(curve.liquidity, 2 * liquidity);
+        let (narrow_a_after, _) = curve.fee_growth_inside(-100, 100).unwrap();
+        assert_eq!(narrow_a_after, narrow_a);
+    }
+
+    #[test]
+    fn swap_without_enough_liquidity() {
+        let liquidity = 1_000_000u128;
+        let mut curve = curve_with_position(10, 0, -100, 100, liquidity);
+        let before = curve.clone();
+        assert_eq!(
+            curve.swap(1_000_000, TradeDirection::AtoB, &fees(0, 0)),
+            Err(SwapError::InsufficientLiquidity)
+        );
+        let mut curve = before.clone();
+        assert_eq!(
+            curve.swap(1_000_000, TradeDirection::BtoA, &fees(0, 0)),
+            Err(SwapError::InsufficientLiquidity)
+        );
+        // the range holds about 5000 of each token
+        let mut curve = before;
+        let result = curve
+            .swap(4_000, TradeDirection::BtoA, &fees(0, 0))
+            .unwrap();
+        assert!(result.destination_amount_swapped > 0);
+    }
+
+    #[test]
+    fn update_and_clear_ticks() {
+        let max_ticks = 8;
+        let mut curve = ConcentratedCurve::new(1, Q64, 1, max_ticks).unwrap();
+        for i in 0..i32::from(max_ticks) / 2 {
+            curve.update_ticks(-1 - i, 1 + i, 1).unwrap();
+        }
+        assert_eq!(curve.liquidity, u128::from(max_ticks) / 2);
+        assert_eq!(
+            curve.update_ticks(-100, 100, 1),
+            Err(SwapError::TooManyTicks)
+        );
+        // existing ticks can still be used
+        let mut full = curve.clone();
+        full.update_ticks(-1, 2, 1).unwrap();
+        assert_eq!(full.ticks.len(), usize::from(max_ticks));
+
+        curve.update_ticks(-1, 1, -1).unwrap();
+        assert_eq!(curve.tick(-1).unwrap().liquidity_gross, 0);
+        curve.clear_ticks(-1, 1);
+        assert_eq!(curve.tick(-1), None);
+        assert_eq!(curve.tick(1), None);
+        assert_eq!(curve.ticks.len(), usize::from(max_ticks) - 2);
+        assert_eq!(
+            curve.update_ticks(-2, 2, -2),
+            Err(SwapError::CalculationFailure)
+        );
+        assert_eq!(
+            curve.update_ticks(-100, 100, MAX_TICK_LIQUIDITY as i128 + 1),
+            Err(SwapError::CalculationFailure)
+        );
+    }
+
+    #[test]
+    fn pack_concentrated_curve() {
+        let mut curve = curve_with_position(10, -15, -100, 100, 1_000);
+        curve.update_ticks(-1000, -20, 5_000).unwrap();
+        curve.fee_growth_global_a = u128::MAX - 7;
+        curve.fee_growth_global_b = 42;
+
+        let mut packed = vec![0u8; ConcentratedCurve::packed_len(curve.max_ticks)];
+        Pack::pack_into_slice(&curve, &mut packed[..]);
+        let unpacked = ConcentratedCurve::unpack_from_slice(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+        assert_eq!(unpacked.ticks.len(), 4);
+
+        // the ticks must fit in the room the curve was created with
+        assert_eq!(
+            ConcentratedCurve::unpack_from_slice(&packed[..packed.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        packed[88..90].copy_from_slice(&(curve.max_ticks + 1).to_le_bytes());
+        assert_eq!(
+            ConcentratedCurve::unpack_from_slice(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
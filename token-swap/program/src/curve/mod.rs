@@ -2,8 +2,11 @@
 
 pub mod base;
 pub mod calculator;
+pub mod concentrated;
 pub mod constant_price;
 pub mod constant_product;
 pub mod fees;
+pub mod multi_token;
 pub mod offset;
+pub mod oracle;
 pub mod stable;
@@ -0,0 +1,980 @@
+//! Curves for pools of more than two tokens: a Balancer-style weighted
+//! constant product, and the curve.fi stable invariant over N coins.
+
+use crate::{
+    curve::{base::SwapResult, calculator::RoundDirection, fees::Fees},
+    error::SwapError,
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+use spl_math::uint::U256;
+use std::convert::{TryFrom, TryInto};
+
+/// Minimum number of tokens in a multi-token pool, pools of two tokens use
+/// the original swap versions
+pub const MIN_TOKENS: usize = 3;
+
+/// Maximum number of tokens in a multi-token pool
+pub const MAX_TOKENS: usize = 8;
+
+/// Maximum weight of one token in a weighted pool
+pub const MAX_TOKEN_WEIGHT: u8 = 16;
+
+/// Q64.64 fixed point one, used for powers of balance ratios
+const FIXED_ONE: u128 = 1 << 64;
+
+/// Bound on the absolute error of `checked_pow_fraction`, in units of 2^-64,
+/// used to round powers in favor of the pool
+const POW_ERROR_BOUND: u128 = 1 << 20;
+
+/// 2^(-2^-i) for i from 1 to 64, as Q64.64 fixed point numbers
+const EXP2_NEG_FRACTIONS: [u64; 64] = [
+    13_043_817_825_332_782_212,
+    15_511_800_964_685_064_948,
+    16_915_738_899_553_466_670,
+    17_664_662_643_191_237_676,
+    18_051_468_387_014_017_850,
+    18_248_035_989_933_441_396,
+    18_347_121_020_861_646_923,
+    18_396_865_112_328_554_661,
+    18_421_787_711_448_657_617,
+    18_434_261_669_329_232_139,
+    18_440_501_815_349_552_981,
+    18_443_622_680_442_407_997,
+    18_445_183_311_048_607_332,
+    18_445_963_675_871_538_003,
+    18_446_353_870_663_572_145,
+    18_446_548_971_154_807_802,
+    18_446_646_522_174_239_825,
+    18_446_695_297_877_410_579,
+    18_446_719_685_777_359_790,
+    18_446_731_879_739_425_374,
+    18_446_737_976_723_480_912,
+    18_446_741_025_216_264_368,
+    18_446_742_549_462_845_018,
+    18_446_743_311_586_182_573,
+    18_446_743_692_647_863_158,
+    18_446_743_883_178_706_403,
+    18_446_743_978_444_128_763,
+    18_446_744_026_076_840_128,
+    18_446_744_049_893_195_856,
+    18_446_744_061_801_373_732,
+    18_446_744_067_755_462_673,
+    18_446_744_070_732_507_144,
+    18_446_744_072_221_029_380,
+    18_446_744_072_965_290_498,
+    18_446_744_073_337_421_057,
+    18_446_744_073_523_486_336,
+    18_446_744_073_616_518_976,
+    18_446_744_073_663_035_296,
+    18_446_744_073_686_293_456,
+    18_446_744_073_697_922_536,
+    18_446_744_073_703_737_076,
+    18_446_744_073_706_644_346,
+    18_446_744_073_708_097_981,
+    18_446_744_073_708_824_798,
+    18_446_744_073_709_188_207,
+    18_446_744_073_709_369_911,
+    18_446_744_073_709_460_763,
+    18_446_744_073_709_506_189,
+    18_446_744_073_709_528_902,
+    18_446_744_073_709_540_259,
+    18_446_744_073_709_545_937,
+    18_446_744_073_709_548_776,
+    18_446_744_073_709_550_196,
+    18_446_744_073_709_550_906,
+    18_446_744_073_709_551_261,
+    18_446_744_073_709_551_438,
+    18_446_744_073_709_551_527,
+    18_446_744_073_709_551_571,
+    18_446_744_073_709_551_593,
+    18_446_744_073_709_551_604,
+    18_446_744_073_709_551_610,
+    18_446_744_073_709_551_613,
+    18_446_744_073_709_551_614,
+    18_446_744_073_709_551_615,
+];
+
+/// Curve types supported by multi-token pools
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MultiTokenCurveType {
+    /// Balancer-style weighted constant product,
+    /// invariant = prod(balance_i ^ weight_i)
+    WeightedConstantProduct,
+    /// Stable, the curve.fi invariant over all the tokens of the pool
+    Stable,
+}
+
+/// Curve of a multi-token pool, with the parameters of its type
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MultiTokenCurve {
+    /// The type of curve
+    pub curve_type: MultiTokenCurveType,
+    /// Amplifier constant, only for stable curves
+    pub amp: u64,
+    /// Weight of each token, in the order of the pool's tokens, only for
+    /// weighted curves
+    pub weights: [u8; MAX_TOKENS],
+}
+
+impl MultiTokenCurve {
+    /// Subtract fees and calculate how much destination token will be provided
+    /// given an amount of source token.
+    pub fn swap(
+        &self,
+        source_index: usize,
+        destination_index: usize,
+        source_amount: u128,
+        balances: &[u128],
+        fees: &Fees,
+    ) -> Option<SwapResult> {
+        // debit the fee to calculate the amount swapped
+        let trade_fee = fees.trading_fee(source_amount)?;
+        let owner_fee = fees.owner_trading_fee(source_amount)?;
+
+        let total_fees = trade_fee.checked_add(owner_fee)?;
+        let source_amount_less_fees = source_amount.checked_sub(total_fees)?;
+
+        let destination_amount_swapped = self.swap_without_fees(
+            source_index,
+            destination_index,
+            source_amount_less_fees,
+            balances,
+        )?;
+
+        Some(SwapResult {
+            new_swap_source_amount: balances[source_index].checked_add(source_amount)?,
+            new_swap_destination_amount: balances[destination_index]
+                .checked_sub(destination_amount_swapped)?,
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+            trade_fee,
+            owner_fee,
+        })
+    }
+
+    /// Calculate how much destination token will be provided given an amount
+    /// of source token, without fees
+    pub fn swap_without_fees(
+        &self,
+        source_index: usize,
+        destination_index: usize,
+        source_amount: u128,
+        balances: &[u128],
+    ) -> Option<u128> {
+        if source_index == destination_index
+            || source_index >= balances.len()
+            || destination_index >= balances.len()
+        {
+            return None;
+        }
+        match self.curve_type {
+            MultiTokenCurveType::WeightedConstantProduct => {
+                // new_destination = destination * (source / new_source) ^ (source_weight / destination_weight)
+                let swap_source_amount = balances[source_index];
+                let swap_destination_amount = balances[destination_index];
+                let ratio = checked_ratio(
+                    swap_source_amount,
+                    swap_source_amount.checked_add(source_amount)?,
+                    RoundDirection::Ceiling,
+                )?;
+                let factor = checked_pow_fraction(
+                    ratio,
+                    self.weights[source_index] as u128,
+                    self.weights[destination_index] as u128,
+                    RoundDirection::Ceiling,
+                )?;
+                let new_destination_amount =
+                    checked_mul_fixed(swap_destination_amount, factor, RoundDirection::Ceiling)?;
+                swap_destination_amount.checked_sub(new_destination_amount)
+            }
+            MultiTokenCurveType::Stable => {
+                let leverage = self.leverage(balances.len())?;
+                let d = compute_d(leverage, balances)?;
+                let mut new_balances = balances.to_vec();
+                new_balances[source_index] =
+                    new_balances[source_index].checked_add(source_amount)?;
+                let new_destination_amount =
+                    compute_y(leverage, &new_balances, destination_index, d)?;
+                U256::from(balances[destination_index])
+                    .checked_sub(new_destination_amount)?
+                    .try_into()
+                    .ok()
+            }
+        }
+    }
+
+    /// Get the amount of pool tokens to mint for depositing an amount of one
+    /// token, rounded down.
+    ///
+    /// Like a swap of part of the deposit for the other tokens, followed by a
+    /// deposit of all tokens, so the trading fee is charged on that part.
+    pub fn deposit_single_token_type(
+        &self,
+        token_index: usize,
+        source_amount: u128,
+        balances: &[u128],
+        pool_supply: u128,
+        fees: &Fees,
+    ) -> Option<u128> {
+        if source_amount == 0 {
+            return Some(0);
+        }
+        let swap_token_amount = *balances.get(token_index)?;
+        match self.curve_type {
+            MultiTokenCurveType::WeightedConstantProduct => {
+                // pool_tokens = supply * ((1 + source / balance) ^ (weight / total_weight) - 1)
+                let (weight, total_weight) = self.token_weight(token_index, balances.len())?;
+                let trade_fee =
+                    fees.trading_fee(taxable_amount(source_amount, weight, total_weight)?)?;
+                let source_amount = source_amount.checked_sub(trade_fee)?;
+                let ratio = checked_ratio(
+                    swap_token_amount,
+                    swap_token_amount.checked_add(source_amount)?,
+                    RoundDirection::Ceiling,
+                )?;
+                let factor =
+                    checked_pow_fraction(ratio, weight, total_weight, RoundDirection::Ceiling)?;
+                U256::from(pool_supply)
+                    .checked_mul(U256::from(FIXED_ONE).checked_sub(factor)?)?
+                    .checked_div(factor)?
+                    .try_into()
+                    .ok()
+            }
+            MultiTokenCurveType::Stable => {
+                let mut new_balances = balances.to_vec();
+                new_balances[token_index] = swap_token_amount.checked_add(source_amount)?;
+                let leverage = self.leverage(balances.len())?;
+                let d0 = compute_d(leverage, balances)?;
+                let d2 = compute_d_less_imbalance_fees(leverage, balances, new_balances, d0, fees)?;
+                U256::from(pool_supply)
+                    .checked_mul(d2.checked_sub(d0)?)?
+                    .checked_div(d0)?
+                    .try_into()
+                    .ok()
+            }
+        }
+    }
+
+    /// Get the amount of pool tokens to burn for withdrawing an amount of one
+    /// token, rounded up.
+    ///
+    /// Like a withdrawal of all tokens, followed by a swap of the other tokens
+    /// for the withdrawn one, so the trading fee is charged on that part.
+    pub fn withdraw_single_token_type_exact_out(
+        &self,
+        token_index: usize,
+        destination_amount: u128,
+        balances: &[u128],
+        pool_supply: u128,
+        fees: &Fees,
+    ) -> Option<u128> {
+        if destination_amount == 0 {
+            return Some(0);
+        }
+        let swap_token_amount = *balances.get(token_index)?;
+        match self.curve_type {
+            MultiTokenCurveType::WeightedConstantProduct => {
+                // pool_tokens = supply * (1 - (1 - destination / balance) ^ (weight / total_weight))
+                let (weight, total_weight) = self.token_weight(token_index, balances.len())?;
+                let trade_fee =
+                    fees.trading_fee(taxable_amount(destination_amount, weight, total_weight)?)?;
+                let destination_amount = destination_amount.checked_add(trade_fee)?;
+                let ratio = checked_ratio(
+                    swap_token_amount.checked_sub(destination_amount)?,
+                    swap_token_amount,
+                    RoundDirection::Floor,
+                )?;
+                if ratio.is_zero() {
+                    return None;
+                }
+                let factor =
+                    checked_pow_fraction(ratio, weight, total_weight, RoundDirection::Floor)?;
+                checked_mul_fixed(
+                    pool_supply,
+                    U256::from(FIXED_ONE).checked_sub(factor)?,
+                    RoundDirection::Ceiling,
+                )
+            }
+            MultiTokenCurveType::Stable => {
+                let mut new_balances = balances.to_vec();
+                new_balances[token_index] = swap_token_amount.checked_sub(destination_amount)?;
+                let leverage = self.leverage(balances.len())?;
+                let d0 = compute_d(leverage, balances)?;
+                let d2 = compute_d_less_imbalance_fees(leverage, balances, new_balances, d0, fees)?;
+                let numerator = U256::from(pool_supply).checked_mul(d0.checked_sub(d2)?)?;
+                numerator
+                    .checked_add(d0.checked_sub(U256::one())?)?
+                    .checked_div(d0)?
+                    .try_into()
+                    .ok()
+            }
+        }
+    }
+
+    /// Validate the curve parameters for a pool of the given number of tokens
+    pub fn validate(&self, token_count: usize) -> Result<(), SwapError> {
+        if !(MIN_TOKENS..=MAX_TOKENS).contains(&token_count) {
+            return Err(SwapError::InvalidTokenCount);
+        }
+        match self.curve_type {
+            MultiTokenCurveType::WeightedConstantProduct => {
+                let (weights, unused_weights) = self.weights.split_at(token_count);
+                if weights
+                    .iter()
+                    .any(|weight| *weight == 0 || *weight > MAX_TOKEN_WEIGHT)
+                    || unused_weights.iter().any(|weight| *weight != 0)
+                {
+                    return Err(SwapError::InvalidCurve);
+                }
+            }
+            MultiTokenCurveType::Stable => {
+                if self.amp == 0 {
+                    return Err(SwapError::InvalidCurve);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Amplifier times the number of tokens, as used in the stable invariant
+    fn leverage(&self, token_count: usize) -> Option<u64> {
+        self.amp.checked_mul(token_count as u64)
+    }
+
+    /// Weight of one token, and the total weight of all tokens
+    fn token_weight(&self, token_index: usize, token_count: usize) -> Option<(u128, u128)> {
+        let weight = *self.weights[..token_count].get(token_index)? as u128;
+        let total_weight = self.weights[..token_count]
+            .iter()
+            .map(|weight| *weight as u128)
+            .sum();
+        Some((weight, total_weight))
+    }
+}
+
+/// Get the amount of one token for the given amount of pool tokens, provided
+/// the pool balance of that token and supply of pool tokens.
+pub fn pool_tokens_to_trading_tokens(
+    pool_tokens: u128,
+    pool_token_supply: u128,
+    swap_token_amount: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let numerator = U256::from(pool_tokens).checked_mul(U256::from(swap_token_amount))?;
+    let pool_token_supply = U256::from(pool_token_supply);
+    let amount = match round_direction {
+        RoundDirection::Floor => numerator.checked_div(pool_token_supply)?,
+        RoundDirection::Ceiling => numerator
+            .checked_add(pool_token_supply.checked_sub(U256::one())?)?
+            .checked_div(pool_token_supply)?,
+    };
+    amount.try_into().ok()
+}
+
+/// Part of a single-sided deposit or withdrawal that is implicitly swapped,
+/// the share of the other tokens in the pool's weight
+fn taxable_amount(amount: u128, weight: u128, total_weight: u128) -> Option<u128> {
+    amount
+        .checked_mul(total_weight.checked_sub(weight)?)?
+        .checked_div(total_weight)
+}
+
+/// Ratio of two amounts, at most one, as a Q64.64 fixed point number
+fn checked_ratio(
+    numerator: u128,
+    denominator: u128,
+    round_direction: RoundDirection,
+) -> Option<U256> {
+    let numerator = U256::from(numerator) << 64;
+    let denominator = U256::from(denominator);
+    match round_direction {
+        RoundDirection::Floor => numerator.checked_div(denominator),
+        RoundDirection::Ceiling => numerator
+            .checked_add(denominator.checked_sub(U256::one())?)?
+            .checked_div(denominator),
+    }
+}
+
+/// Multiply an amount by a Q64.64 fixed point number
+fn checked_mul_fixed(amount: u128, fixed: U256, round_direction: RoundDirection) -> Option<u128> {
+    let product = U256::from(amount).checked_mul(fixed)?;
+    let amount = match round_direction {
+        RoundDirection::Floor => product >> 64,
+        RoundDirection::Ceiling => product.checked_add(U256::from(FIXED_ONE - 1))? >> 64,
+    };
+    amount.try_into().ok()
+}
+
+/// -log2(x) for 0 < x <= 1, with x and the result as Q64.64 fixed point
+/// numbers, computed one bit at a time by repeated squaring
+fn checked_neg_log2(x: U256) -> Option<U256> {
+    let one = U256::from(FIXED_ONE);
+    if x.is_zero() || x > one {
+        return None;
+    }
+    // x = mantissa * 2^-shift, with the mantissa in [1, 2)
+    let shift = 65 - x.bits();
+    let mut mantissa = x << shift;
+    let two = one << 1;
+    let mut fraction = 0u64;
+    for bit in (0..64).rev() {
+        mantissa = mantissa.checked_mul(mantissa)? >> 64;
+        if mantissa >= two {
+            mantissa >>= 1;
+            fraction |= 1 << bit;
+        }
+    }
+    // -log2(x) = shift - log2(mantissa)
+    (U256::from(shift) << 64).checked_sub(U256::from(fraction))
+}
+
+/// 2^-y for y >= 0, with y and the result as Q64.64 fixed point numbers
+fn checked_exp2_neg(y: U256) -> Option<U256> {
+    let whole = y >> 64;
+    if whole >= U256::from(64) {
+        return Some(U256::zero());
+    }
+    let fraction = y.low_u64();
+    let mut result = U256::from(FIXED_ONE);
+    for (i, factor) in EXP2_NEG_FRACTIONS.iter().enumerate() {
+        if fraction & (1 << (63 - i)) != 0 {
+            result = result.checked_mul(U256::from(*factor))? >> 64;
+        }
+    }
+    Some(result >> whole.as_u32())
+}
+
+/// x^(numerator / denominator) for 0 < x <= 1, as Q64.64 fixed point numbers,
+/// rounded by more than the error of the approximation
+fn checked_pow_fraction(
+    x: U256,
+    numerator: u128,
+    denominator: u128,
+    round_direction: RoundDirection,
+) -> Option<U256> {
+    if numerator == denominator {
+        return Some(x);
+    }
+    let exponent = checked_neg_log2(x)?
+        .checked_mul(U256::from(numerator))?
+        .checked_div(U256::from(denominator))?;
+    let power = checked_exp2_neg(exponent)?;
+    let error_bound = U256::from(POW_ERROR_BOUND);
+    match round_direction {
+        RoundDirection::Floor => Some(power.saturating_sub(error_bound)),
+        RoundDirection::Ceiling => Some(std::cmp::min(
+            power.checked_add(error_bound)?,
+            U256::from(FIXED_ONE),
+        )),
+    }
+}
+
+/// Returns true if values differ by no more than 1
+fn almost_equal(a: U256, b: U256) -> bool {
+    if a > b {
+        a - b <= U256::one()
+    } else {
+        b - a <= U256::one()
+    }
+}
+
+/// Compute the stable swap invariant (D) over all balances
+/// Equation:
+/// A * sum(x_i) * n**n + D = A * D * n**n + D**(n+1) / (n**n * prod(x_i))
+fn compute_d(leverage: u64, balances: &[u128]) -> Option<U256> {
+    let n_coins = U256::from(balances.len());
+    let sum_x = balances.iter().try_fold(U256::zero(), |sum, balance| {
+        sum.checked_add(U256::from(*balance))
+    })?;
+    if sum_x.is_zero() {
+        return Some(U256::zero());
+    }
+    let leverage = U256::from(leverage);
+    let mut d = sum_x;
+
+    // Newton's method to approximate D
+    for _ in 0..32 {
+        let mut d_product = d;
+        for balance in balances {
+            d_product = d_product
+                .checked_mul(d)?
+                .checked_div(U256::from(*balance).checked_mul(n_coins)?)?;
+        }
+        let d_previous = d;
+        //d = (leverage * sum_x + d_p * n_coins) * d / ((leverage - 1) * d + (n_coins + 1) * d_p);
+        let numerator = leverage
+            .checked_mul(sum_x)?
+            .checked_add(d_product.checked_mul(n_coins)?)?
+            .checked_mul(d)?;
+        let denominator = leverage
+            .checked_sub(U256::one())?
+            .checked_mul(d)?
+            .checked_add(n_coins.checked_add(U256::one())?.checked_mul(d_product)?)?;
+        d = numerator.checked_div(denominator)?;
+        // Equality with the precision of 1
+        if almost_equal(d, d_previous) {
+            break;
+        }
+    }
+    Some(d)
+}
+
+/// Compute the balance of one token that keeps the invariant at D, given the
+/// balances of the others
+/// Solve for y:
+/// y**2 + y * (sum' - (A*n**n - 1) * D / (A * n**n)) = D ** (n + 1) / (n ** (2 * n) * prod' * A)
+/// y**2 + b*y = c
+fn compute_y(leverage: u64, balances: &[u128], index: usize, d: U256) -> Option<U256> {
+    let n_coins = U256::from(balances.len());
+    let leverage = U256::from(leverage);
+
+    // c = D ** (n + 1) / (n ** n * prod' * leverage)
+    // b = sum' + D / leverage
+    let mut c = d;
+    let mut sum = U256::zero();
+    for (i, balance) in balances.iter().enumerate() {
+        if i != index {
+            let balance = U256::from(*balance);
+            c = c
+                .checked_mul(d)?
+                .checked_div(balance.checked_mul(n_coins)?)?;
+            sum = sum.checked_add(balance)?;
+        }
+    }
+    c = c
+        .checked_mul(d)?
+        .checked_div(n_coins.checked_mul(leverage)?)?;
+    let b = sum.checked_add(d.checked_div(leverage)?)?;
+
+    // Solve for y by approximating: y**2 + b*y = c
+    let mut y = d;
+    for _ in 0..32 {
+        let y_previous = y;
+        y = y.checked_mul(y)?.checked_add(c)?.checked_div(
+            y.checked_mul(U256::from(2))?
+                .checked_add(b)?
+                .checked_sub(d)?,
+        )?;
+        if almost_equal(y, y_previous) {
+            break;
+        }
+    }
+    Some(y)
+}
+
+/// Compute D after charging the trading fee on the imbalance of a deposit or
+/// withdrawal: the difference of each new balance from its balance had the
+/// pool grown or shrunk proportionally
+fn compute_d_less_imbalance_fees(
+    leverage: u64,
+    balances: &[u128],
+    mut new_balances: Vec<u128>,
+    d0: U256,
+    fees: &Fees,
+) -> Option<U256> {
+    let n_coins = balances.len() as u128;
+    let d1 = compute_d(leverage, &new_balances)?;
+    for (balance, new_balance) in balances.iter().zip(new_balances.iter_mut()) {
+        let ideal_balance: u128 = d1
+            .checked_mul(U256::from(*balance))?
+            .checked_div(d0)?
+            .try_into()
+            .ok()?;
+        let difference = if ideal_balance > *new_balance {
+            ideal_balance - *new_balance
+        } else {
+            *new_balance - ideal_balance
+        };
+        let fee = fees
+            .trading_fee(difference)?
+            .checked_mul(n_coins)?
+            .checked_div(n_coins.checked_sub(1)?.checked_mul(4)?)?;
+        *new_balance = new_balance.checked_sub(fee)?;
+    }
+    compute_d(leverage, &new_balances)
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for MultiTokenCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for MultiTokenCurve {}
+impl Pack for MultiTokenCurve {
+    const LEN: usize = 17;
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 17];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (curve_type, amp, weights) = array_refs![input, 1, 8, MAX_TOKENS];
+        Ok(Self {
+            curve_type: curve_type[0].try_into()?,
+            amp: u64::from_le_bytes(*amp),
+            weights: *weights,
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 17];
+        let (curve_type, amp, weights) = mut_array_refs![output, 1, 8, MAX_TOKENS];
+        curve_type[0] = self.curve_type as u8;
+        *amp = self.amp.to_le_bytes();
+        *weights = self.weights;
+    }
+}
+
+/// Sensible default of MultiTokenCurveType to WeightedConstantProduct, the
+/// generalization of the constant product curve
+impl Default for MultiTokenCurveType {
+    fn default() -> Self {
+        MultiTokenCurveType::WeightedConstantProduct
+    }
+}
+
+impl TryFrom<u8> for MultiTokenCurveType {
+    type Error = ProgramError;
+
+    fn try_from(curve_type: u8) -> Result<Self, Self::Error> {
+        match curve_type {
+            0 => Ok(MultiTokenCurveType::WeightedConstantProduct),
+            1 => Ok(MultiTokenCurveType::Stable),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        calculator::{CurveCalculator, TradeDirection},
+        constant_product::ConstantProductCurve,
+        stable::StableCurve,
+    };
+    use proptest::prelude::*;
+    use sim::StableSwapModel;
+
+    fn weighted_curve(weights: &[u8]) -> MultiTokenCurve {
+        let mut curve = MultiTokenCurve {
+            curve_type: MultiTokenCurveType::WeightedConstantProduct,
+            ..MultiTokenCurve::default()
+        };
+        curve.weights[..weights.len()].copy_from_slice(weights);
+        curve
+    }
+
+    fn stable_curve(amp: u64) -> MultiTokenCurve {
+        MultiTokenCurve {
+            curve_type: MultiTokenCurveType::Stable,
+            amp,
+            ..MultiTokenCurve::default()
+        }
+    }
+
+    fn to_f64(fixed: U256) -> f64 {
+        fixed.as_u128() as f64 / FIXED_ONE as f64
+    }
+
+    /// Weighted invariant, as the logarithm of prod(balance_i ^ weight_i)
+    fn weighted_log_invariant(weights: &[u8], balances: &[u128]) -> f64 {
+        weights
+            .iter()
+            .zip(balances.iter())
+            .map(|(weight, balance)| *weight as f64 * (*balance as f64).ln())
+            .sum()
+    }
+
+    #[test]
+    fn pow_fraction() {
+        let tests: &[(f64, u128, u128)] = &[
+            (0.5, 1, 2),
+            (0.5, 2, 1),
+            (0.9, 1, 3),
+            (0.123_456, 7, 5),
+            (0.999_999, 16, 1),
+            (0.000_001, 1, 16),
+            (1.0, 3, 7),
+        ];
+        for (x, numerator, denominator) in tests {
+            let fixed = U256::from((x * FIXED_ONE as f64) as u128);
+            let expected = x.powf(*numerator as f64 / *denominator as f64);
+            let floor =
+                checked_pow_fraction(fixed, *numerator, *denominator, RoundDirection::Floor)
+                    .unwrap();
+            let ceiling =
+                checked_pow_fraction(fixed, *numerator, *denominator, RoundDirection::Ceiling)
+                    .unwrap();
+            assert!(floor <= ceiling);
+            assert!((to_f64(floor) - expected).abs() < 1e-12);
+            assert!((to_f64(ceiling) - expected).abs() < 1e-12);
+        }
+        assert_eq!(
+            checked_pow_fraction(U256::zero(), 1, 2, RoundDirection::Floor),
+            None
+        );
+    }
+
+    #[test]
+    fn validate_curves() {
+        assert_eq!(weighted_curve(&[1, 2, 3]).validate(3), Ok(()));
+        assert_eq!(
+            weighted_curve(&[1, 2, 3]).validate(2),
+            Err(SwapError::InvalidTokenCount)
+        );
+        assert_eq!(
+            weighted_curve(&[1, 2, 3]).validate(4),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            weighted_curve(&[1, 2, 3, 0]).validate(4),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            weighted_curve(&[1, MAX_TOKEN_WEIGHT + 1, 3]).validate(3),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(stable_curve(100).validate(MAX_TOKENS), Ok(()));
+        assert_eq!(
+            stable_curve(100).validate(MAX_TOKENS + 1),
+            Err(SwapError::InvalidTokenCount)
+        );
+        assert_eq!(stable_curve(0).validate(3), Err(SwapError::InvalidCurve));
+    }
+
+    #[test]
+    fn pack_curve() {
+        let curve = MultiTokenCurve {
+            curve_type: MultiTokenCurveType::Stable,
+            amp: 85,
+            weights: [1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let mut packed = [0u8; MultiTokenCurve::LEN];
+        Pack::pack_into_slice(&curve, &mut packed[..]);
+        let unpacked = MultiTokenCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+
+        let mut expect = vec![MultiTokenCurveType::Stable as u8];
+        expect.extend_from_slice(&85u64.to_le_bytes());
+        expect.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(&packed[..], &expect[..]);
+    }
+
+    proptest! {
+        #[test]
+        fn equal_weights_swap_like_constant_product(
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1..u64::MAX,
+            swap_destination_amount in 1..u64::MAX,
+        ) {
+            let curve = weighted_curve(&[5, 5, 5]);
+            let balances = [swap_source_amount as u128, swap_destination_amount as u128, 1_000];
+            let result = curve
+                .swap_without_fees(0, 1, source_amount as u128, &balances)
+                .unwrap();
+            let expected = ConstantProductCurve {}
+                .swap_without_fees(
+                    source_amount as u128,
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128,
+                    TradeDirection::AtoB,
+                )
+                .unwrap()
+                .destination_amount_swapped;
+            prop_assert!(result <= expected);
+            prop_assert!(expected - result <= 1);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn weighted_swap_keeps_invariant(
+            source_amount in 1..u32::MAX,
+            balances in prop::array::uniform4(1_000_000..u64::MAX),
+            weights in prop::array::uniform4(1..=MAX_TOKEN_WEIGHT),
+            source_index in 0..4usize,
+            destination_offset in 1..4usize,
+        ) {
+            let destination_index = (source_index + destination_offset) % 4;
+            let curve = weighted_curve(&weights);
+            let balances: Vec<u128> = balances.iter().map(|balance| *balance as u128).collect();
+            let destination_amount = curve
+                .swap_without_fees(source_index, destination_index, source_amount as u128, &balances)
+                .unwrap();
+            let mut new_balances = balances.clone();
+            new_balances[source_index] += source_amount as u128;
+            new_balances[destination_index] -= destination_amount;
+            let before = weighted_log_invariant(&weights, &balances);
+            let after = weighted_log_invariant(&weights, &new_balances);
+            prop_assert!(after >= before - 1e-9 * before.abs());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn weighted_single_deposit_and_withdraw(
+            amount in 1..u32::MAX,
+            balances in prop::array::uniform3(1_000_000_000..u64::MAX),
+            weights in prop::array::uniform3(1..=MAX_TOKEN_WEIGHT),
+            token_index in 0..3usize,
+        ) {
+            let curve = weighted_curve(&weights);
+            let balances: Vec<u128> = balances.iter().map(|balance| *balance as u128).collect();
+            let pool_supply = 1_000_000_000_000;
+            let fees = Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                ..Fees::default()
+            };
+            let deposited = curve
+                .deposit_single_token_type(token_index, amount as u128, &balances, pool_supply, &fees)
+                .unwrap();
+            let withdrawn = curve
+                .withdraw_single_token_type_exact_out(
+                    token_index,
+                    amount as u128,
+                    &balances,
+                    pool_supply,
+                    &fees,
+                )
+                .unwrap();
+            // depositing and withdrawing the same amount never profits
+            prop_assert!(deposited <= withdrawn);
+
+            // pool tokens are worth at most their share of the weighted value of
+            // the pool
+            let (weight, total_weight) = curve.token_weight(token_index, 3).unwrap();
+            let share = deposited as f64 / pool_supply as f64;
+            let value = amount as f64 * total_weight as f64 / weight as f64 / balances[token_index] as f64;
+            prop_assert!(share <= value * (1.0 + 1e-9));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn two_coin_stable_swap_like_stable_curve(
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1..u64::MAX,
+            swap_destination_amount in 1..u64::MAX,
+            amp in 1..150u64,
+        ) {
+            // the N coin curve with two coins is the original stable curve
+            let curve = stable_curve(amp);
+            let balances = [swap_source_amount as u128, swap_destination_amount as u128];
+            let result = curve.swap_without_fees(0, 1, source_amount as u128, &balances);
+            let expected = StableCurve { amp }.swap_without_fees(
+                source_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+                TradeDirection::AtoB,
+            );
+            if let (Some(result), Some(expected)) = (result, expected) {
+                let expected = expected.destination_amount_swapped;
+                prop_assert!(result <= expected + 1 && expected <= result + 1);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn stable_swap_keeps_invariant(
+            source_amount in 1..u32::MAX,
+            balances in prop::array::uniform5(1_000_000_000..1_000_000_000_000_000u64),
+            amp in 1..150u64,
+            source_index in 0..5usize,
+            destination_offset in 1..5usize,
+        ) {
+            let destination_index = (source_index + destination_offset) % 5;
+            let curve = stable_curve(amp);
+            let leverage = curve.leverage(5).unwrap();
+            let balances: Vec<u128> = balances.iter().map(|balance| *balance as u128).collect();
+            let destination_amount = curve
+                .swap_without_fees(source_index, destination_index, source_amount as u128, &balances)
+                .unwrap();
+            let mut new_balances = balances.clone();
+            new_balances[source_index] += source_amount as u128;
+            new_balances[destination_index] -= destination_amount;
+            let d0 = compute_d(leverage, &balances).unwrap();
+            let d1 = compute_d(leverage, &new_balances).unwrap();
+            // the invariant only loses its rounding error
+            prop_assert!(d1 + d0 / U256::from(1_000_000_000_000u64) + U256::from(5) >= d0);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn three_coin_stable_swap_like_sim(
+            source_amount in 100..100_000_000_000u128,
+            balances in prop::array::uniform3(100..1_000_000_000_000_000_000u128),
+            amp in 1..150u64,
+        ) {
+            prop_assume!(source_amount < balances[0]);
+            let curve = stable_curve(amp);
+            let model = StableSwapModel::new(amp.into(), balances.to_vec(), 3);
+            // extremely imbalanced pools may not converge, and fail the swap
+            if let Some(result) = curve.swap_without_fees(0, 2, source_amount, &balances) {
+                let sim_result = model.sim_exchange(0, 2, source_amount);
+                prop_assert!(
+                    result <= sim_result + 1 && sim_result <= result + 1,
+                    "result={}, sim_result={}", result, sim_result
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stable_single_deposit_and_withdraw() {
+        let curve = stable_curve(100);
+        let balances = [1_000_000_000_000, 1_000_000_000_000, 1_000_000_000_000];
+        let pool_supply = 3_000_000_000_000;
+        let fees = Fees {
+            trade_fee_numerator: 4,
+            trade_fee_denominator: 10_000,
+            ..Fees::default()
+        };
+        // a small balanced deposit gets nearly its share of the pool
+        let deposited = curve
+            .deposit_single_token_type(0, 1_000_000, &balances, pool_supply, &fees)
+            .unwrap();
+        assert!(deposited <= 1_000_000);
+        assert!(deposited > 999_000);
+        let withdrawn = curve
+            .withdraw_single_token_type_exact_out(0, 1_000_000, &balances, pool_supply, &fees)
+            .unwrap();
+        assert!(withdrawn >= 1_000_000);
+        assert!(withdrawn < 1_001_000);
+
+        // withdrawing more than the balance fails
+        assert_eq!(
+            curve.withdraw_single_token_type_exact_out(
+                1,
+                1_000_000_000_001,
+                &balances,
+                pool_supply,
+                &fees
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn proportional_pool_token_conversion() {
+        assert_eq!(
+            pool_tokens_to_trading_tokens(1, 3, 10, RoundDirection::Floor),
+            Some(3)
+        );
+        assert_eq!(
+            pool_tokens_to_trading_tokens(1, 3, 10, RoundDirection::Ceiling),
+            Some(4)
+        );
+        assert_eq!(
+            pool_tokens_to_trading_tokens(1, 0, 10, RoundDirection::Floor),
+            None
+        );
+    }
+}
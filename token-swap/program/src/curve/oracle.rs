@@ -0,0 +1,570 @@
+//! Proactive market making curve, quoting around an external oracle price
+
+use {
+    crate::{
+        curve::{
+            calculator::{
+                map_zero_to_none, CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult,
+                TradeDirection, TradingTokenResult,
+            },
+            constant_product::pool_tokens_to_trading_tokens,
+        },
+        error::SwapError,
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+    },
+    spl_math::{checked_ceil_div::CheckedCeilDiv, precise_number::PreciseNumber, uint::U256},
+};
+
+/// Basis points in one, used for the spread and inventory skew
+pub const BASIS_POINTS: u128 = 10_000;
+
+/// Price of one token B base unit in token A base units, as an exact fraction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    /// Token A base units
+    pub numerator: u128,
+    /// Token B base units
+    pub denominator: u128,
+}
+
+impl OraclePrice {
+    /// Value of an amount of token B in token A at this price, rounded down
+    pub fn token_b_value(&self, token_b_amount: u128) -> Option<U256> {
+        U256::from(token_b_amount)
+            .checked_mul(U256::from(self.numerator))?
+            .checked_div(U256::from(self.denominator))
+    }
+}
+
+/// OracleCurve struct implementing CurveCalculator
+///
+/// Swaps are quoted at the oracle price of token B plus a spread on each side.
+/// The quoted mid price is skewed against the token the pool holds more of, by
+/// up to `skew_bps` when the pool holds only one token, so that trades tend to
+/// rebalance the pool. Since the skew never exceeds the spread, the pool never
+/// sells below or buys above the oracle price.
+///
+/// The oracle price is not part of the packed curve. The processor reads it
+/// from the swap's oracle account and sets `token_b_price` before any
+/// calculation that needs it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OracleCurve {
+    /// Distance of each quote from the mid price, in basis points
+    pub spread_bps: u16,
+    /// Max shift of the mid price from the oracle price, in basis points
+    pub skew_bps: u16,
+    /// Max number of slots since the oracle price was published
+    pub max_price_age_slots: u64,
+    /// Decimals of the token A mint
+    pub token_a_decimals: u8,
+    /// Decimals of the token B mint
+    pub token_b_decimals: u8,
+    /// Current oracle price of token B, not packed
+    pub token_b_price: Option<OraclePrice>,
+}
+
+impl OracleCurve {
+    /// Price multiplier, in basis points of the oracle price, at which the pool
+    /// trades token B in the given direction
+    fn quote_bps(
+        &self,
+        token_b_price: &OraclePrice,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let token_a_value = U256::from(swap_token_a_amount);
+        let token_b_value = token_b_price.token_b_value(swap_token_b_amount)?;
+        let total_value = token_a_value.checked_add(token_b_value)?;
+
+        let mid_bps = if total_value.is_zero() {
+            BASIS_POINTS
+        } else if token_b_value > token_a_value {
+            // pool holds more token B, quote it cheaper
+            let skew_bps = skew_bps(self.skew_bps, token_b_value - token_a_value, total_value)?;
+            BASIS_POINTS.checked_sub(skew_bps)?
+        } else {
+            // pool holds more token A, quote token B higher
+            let skew_bps = skew_bps(self.skew_bps, token_a_value - token_b_value, total_value)?;
+            BASIS_POINTS.checked_add(skew_bps)?
+        };
+
+        let spread_bps = self.spread_bps as u128;
+        match trade_direction {
+            TradeDirection::AtoB => mid_bps.checked_add(spread_bps),
+            TradeDirection::BtoA => mid_bps.checked_sub(spread_bps),
+        }
+    }
+}
+
+fn skew_bps(max_skew_bps: u16, imbalance: U256, total_value: U256) -> Option<u128> {
+    let skew_bps = U256::from(max_skew_bps)
+        .checked_mul(imbalance)?
+        .checked_div(total_value)?;
+    Some(skew_bps.as_u128())
+}
+
+fn to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+impl CurveCalculator for OracleCurve {
+    /// Quote the whole trade at the pool's current price for the direction
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let token_b_price = self.token_b_price.as_ref()?;
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_source_amount, swap_destination_amount),
+            TradeDirection::BtoA => (swap_destination_amount, swap_source_amount),
+        };
+        let quote_bps = U256::from(self.quote_bps(
+            token_b_price,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            trade_direction,
+        )?);
+        let numerator = U256::from(token_b_price.numerator);
+        let denominator = U256::from(token_b_price.denominator);
+        let basis_points = U256::from(BASIS_POINTS);
+
+        let destination_amount_swapped = match trade_direction {
+            TradeDirection::AtoB => U256::from(source_amount)
+                .checked_mul(denominator)?
+                .checked_mul(basis_points)?
+                .checked_div(numerator.checked_mul(quote_bps)?)?,
+            TradeDirection::BtoA => U256::from(source_amount)
+                .checked_mul(numerator)?
+                .checked_mul(quote_bps)?
+                .checked_div(denominator.checked_mul(basis_points)?)?,
+        };
+        let destination_amount_swapped = map_zero_to_none(to_u128(destination_amount_swapped)?)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Deposits and withdrawals of both tokens are proportional to the pool
+    /// balances and do not need the oracle price
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// Get the amount of pool tokens for the given amount of token A or B.
+    /// Token B is valued at the pool's quote, its bid when depositing and its
+    /// ask when withdrawing, so single-sided deposits and withdrawals pay the
+    /// spread like a swap would.
+    fn trading_tokens_to_pool_tokens(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        let token_b_price = self.token_b_price.as_ref()?;
+        let given_value = match trade_direction {
+            TradeDirection::AtoB => U256::from(source_amount),
+            TradeDirection::BtoA => {
+                let quote_direction = match round_direction {
+                    RoundDirection::Floor => TradeDirection::BtoA,
+                    RoundDirection::Ceiling => TradeDirection::AtoB,
+                };
+                let quote_bps = self.quote_bps(
+                    token_b_price,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    quote_direction,
+                )?;
+                token_b_price
+                    .token_b_value(source_amount)?
+                    .checked_mul(U256::from(quote_bps))?
+                    .checked_div(U256::from(BASIS_POINTS))?
+            }
+        };
+        let total_value = U256::from(swap_token_a_amount)
+            .checked_add(token_b_price.token_b_value(swap_token_b_amount)?)?;
+        let pool_tokens = U256::from(pool_supply).checked_mul(given_value)?;
+        match round_direction {
+            RoundDirection::Floor => to_u128(pool_tokens.checked_div(total_value)?),
+            RoundDirection::Ceiling => to_u128(pool_tokens.checked_ceil_div(total_value)?.0),
+        }
+    }
+
+    fn validate(&self) -> Result<(), SwapError> {
+        if self.skew_bps > self.spread_bps
+            || self.spread_bps as u128 + self.skew_bps as u128 >= BASIS_POINTS
+            || self.max_price_age_slots == 0
+        {
+            Err(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The total normalized value of the oracle curve adds the value of the
+    /// token B side at the oracle price to the token A side, then divides by 2
+    /// to normalize the value between the two token types.
+    fn normalized_value(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        let token_b_price = self.token_b_price.as_ref()?;
+        let value = U256::from(swap_token_a_amount)
+            .checked_add(token_b_price.token_b_value(swap_token_b_amount)?)?
+            .checked_div(U256::from(2))?;
+        PreciseNumber::new(to_u128(value)?)
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for OracleCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for OracleCurve {}
+impl Pack for OracleCurve {
+    const LEN: usize = 14;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<OracleCurve, ProgramError> {
+        let input = array_ref![input, 0, 14];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (spread_bps, skew_bps, max_price_age_slots, token_a_decimals, token_b_decimals) =
+            array_refs![input, 2, 2, 8, 1, 1];
+        Ok(Self {
+            spread_bps: u16::from_le_bytes(*spread_bps),
+            skew_bps: u16::from_le_bytes(*skew_bps),
+            max_price_age_slots: u64::from_le_bytes(*max_price_age_slots),
+            token_a_decimals: token_a_decimals[0],
+            token_b_decimals: token_b_decimals[0],
+            token_b_price: None,
+        })
+    }
+}
+
+impl DynPack for OracleCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 14];
+        let (spread_bps, skew_bps, max_price_age_slots, token_a_decimals, token_b_decimals) =
+            mut_array_refs![output, 2, 2, 8, 1, 1];
+        *spread_bps = self.spread_bps.to_le_bytes();
+        *skew_bps = self.skew_bps.to_le_bytes();
+        *max_price_age_slots = self.max_price_age_slots.to_le_bytes();
+        token_a_decimals[0] = self.token_a_decimals;
+        token_b_decimals[0] = self.token_b_decimals;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::calculator::{test::total_and_intermediate, INITIAL_SWAP_POOL_AMOUNT};
+    use proptest::prelude::*;
+
+    fn priced_curve(
+        spread_bps: u16,
+        skew_bps: u16,
+        numerator: u128,
+        denominator: u128,
+    ) -> OracleCurve {
+        OracleCurve {
+            spread_bps,
+            skew_bps,
+            max_price_age_slots: 10,
+            token_a_decimals: 6,
+            token_b_decimals: 6,
+            token_b_price: Some(OraclePrice {
+                numerator,
+                denominator,
+            }),
+        }
+    }
+
+    /// Pool value in token A at the oracle price, scaled by the price
+    /// denominator to stay exact
+    fn scaled_value(
+        numerator: u32,
+        denominator: u32,
+        token_a_amount: u128,
+        token_b_amount: u128,
+    ) -> U256 {
+        U256::from(token_a_amount) * U256::from(denominator)
+            + U256::from(token_b_amount) * U256::from(numerator)
+    }
+
+    #[test]
+    fn pack_oracle_curve() {
+        let curve = OracleCurve {
+            spread_bps: 30,
+            skew_bps: 20,
+            max_price_age_slots: 25,
+            token_a_decimals: 6,
+            token_b_decimals: 9,
+            token_b_price: None,
+        };
+
+        let mut packed = [0u8; OracleCurve::LEN];
+        Pack::pack_into_slice(&curve, &mut packed[..]);
+        let unpacked = OracleCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+
+        let mut packed = vec![];
+        packed.extend_from_slice(&30u16.to_le_bytes());
+        packed.extend_from_slice(&20u16.to_le_bytes());
+        packed.extend_from_slice(&25u64.to_le_bytes());
+        packed.push(6);
+        packed.push(9);
+        let unpacked = OracleCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+    }
+
+    #[test]
+    fn validate_oracle_curve() {
+        assert!(priced_curve(30, 20, 1, 1).validate().is_ok());
+        assert!(priced_curve(30, 30, 1, 1).validate().is_ok());
+        // skew larger than the spread would quote through the oracle price
+        assert_eq!(
+            priced_curve(20, 30, 1, 1).validate(),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            priced_curve(5_000, 5_000, 1, 1).validate(),
+            Err(SwapError::InvalidCurve)
+        );
+        let curve = OracleCurve {
+            max_price_age_slots: 0,
+            ..priced_curve(30, 20, 1, 1)
+        };
+        assert_eq!(curve.validate(), Err(SwapError::InvalidCurve));
+    }
+
+    #[test]
+    fn swap_requires_oracle_price() {
+        let curve = OracleCurve {
+            token_b_price: None,
+            ..priced_curve(30, 0, 1, 1)
+        };
+        assert!(curve
+            .swap_without_fees(100, 1_000, 1_000, TradeDirection::AtoB)
+            .is_none());
+        assert!(curve
+            .trading_tokens_to_pool_tokens(
+                100,
+                1_000,
+                1_000,
+                INITIAL_SWAP_POOL_AMOUNT,
+                TradeDirection::AtoB,
+                RoundDirection::Floor
+            )
+            .is_none());
+        // proportional deposits and withdrawals don't need the price
+        assert!(curve
+            .pool_tokens_to_trading_tokens(10, 100, 1_000, 1_000, RoundDirection::Floor)
+            .is_some());
+    }
+
+    #[test]
+    fn swap_calculation_spread() {
+        // 1 token B = 2 token A, 1% spread, balanced pool
+        let curve = priced_curve(100, 50, 2, 1);
+
+        let result = curve
+            .swap_without_fees(10_100, 200_000, 100_000, TradeDirection::AtoB)
+            .unwrap();
+        assert_eq!(result.source_amount_swapped, 10_100);
+        // bought at 2.02
+        assert_eq!(result.destination_amount_swapped, 5_000);
+
+        let result = curve
+            .swap_without_fees(10_000, 100_000, 200_000, TradeDirection::BtoA)
+            .unwrap();
+        // sold at 1.98
+        assert_eq!(result.destination_amount_swapped, 19_800);
+    }
+
+    #[test]
+    fn swap_calculation_inventory_skew() {
+        // 1 token B = 1 token A, 1% spread, up to 0.5% skew
+        let curve = priced_curve(100, 50, 1, 1);
+
+        // pool holds only token B, so token B is quoted 0.5% cheaper on both sides
+        let result = curve
+            .swap_without_fees(10_050, 0, 100_000, TradeDirection::AtoB)
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 10_000);
+        let result = curve
+            .swap_without_fees(10_000, 100_000, 0, TradeDirection::BtoA)
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 9_850);
+
+        // pool holds three quarters token A, so token B is quoted 0.25% higher
+        let result = curve
+            .swap_without_fees(10_000, 25_000, 75_000, TradeDirection::BtoA)
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 9_925);
+    }
+
+    #[test]
+    fn single_sided_pool_tokens_pay_spread() {
+        let curve = priced_curve(100, 0, 1, 1);
+        let pool_supply = 1_000_000;
+
+        // token A is valued at face value
+        let pool_tokens = curve
+            .trading_tokens_to_pool_tokens(
+                10_000,
+                500_000,
+                500_000,
+                pool_supply,
+                TradeDirection::AtoB,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+        assert_eq!(pool_tokens, 10_000);
+
+        // token B is valued at the bid when depositing, the ask when withdrawing
+        let pool_tokens = curve
+            .trading_tokens_to_pool_tokens(
+                10_000,
+                500_000,
+                500_000,
+                pool_supply,
+                TradeDirection::BtoA,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+        assert_eq!(pool_tokens, 9_900);
+        let pool_tokens = curve
+            .trading_tokens_to_pool_tokens(
+                10_000,
+                500_000,
+                500_000,
+                pool_supply,
+                TradeDirection::BtoA,
+                RoundDirection::Ceiling,
+            )
+            .unwrap();
+        assert_eq!(pool_tokens, 10_100);
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_swap(
+            source_token_amount in 1..u64::MAX,
+            swap_source_amount in 1..u64::MAX,
+            swap_destination_amount in 1..u64::MAX,
+            spread_bps in 0..5_000u16,
+            skew_seed in 0..=u16::MAX,
+            numerator in 1..u32::MAX,
+            denominator in 1..u32::MAX,
+            a_to_b: bool,
+        ) {
+            let skew_bps = skew_seed % (spread_bps + 1);
+            let curve = priced_curve(spread_bps, skew_bps, numerator as u128, denominator as u128);
+            let trade_direction = if a_to_b { TradeDirection::AtoB } else { TradeDirection::BtoA };
+            let result = curve.swap_without_fees(
+                source_token_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+                trade_direction,
+            );
+            // Make sure the trade yields at least one token, with enough tokens on the other side
+            prop_assume!(matches!(&result, Some(result) if result.destination_amount_swapped <= swap_destination_amount as u128));
+            let result = result.unwrap();
+
+            // the pool trades away from the oracle price, so unlike the other
+            // curves its value grows by the spread and is not checked against
+            // an epsilon
+            let (swap_token_a_amount, swap_token_b_amount, new_swap_token_a_amount, new_swap_token_b_amount) = match trade_direction {
+                TradeDirection::AtoB => (
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128,
+                    swap_source_amount as u128 + result.source_amount_swapped,
+                    swap_destination_amount as u128 - result.destination_amount_swapped,
+                ),
+                TradeDirection::BtoA => (
+                    swap_destination_amount as u128,
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128 - result.destination_amount_swapped,
+                    swap_source_amount as u128 + result.source_amount_swapped,
+                ),
+            };
+            let value = scaled_value(numerator, denominator, swap_token_a_amount, swap_token_b_amount);
+            let new_value = scaled_value(numerator, denominator, new_swap_token_a_amount, new_swap_token_b_amount);
+            assert!(new_value >= value);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_withdraw(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(),
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+            numerator in 1..u32::MAX,
+            denominator in 1..u32::MAX,
+        ) {
+            let curve = priced_curve(30, 10, numerator as u128, denominator as u128);
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            let swap_token_a_amount = swap_token_a_amount as u128;
+            let swap_token_b_amount = swap_token_b_amount as u128;
+
+            let value = scaled_value(numerator, denominator, swap_token_a_amount, swap_token_b_amount);
+            let withdraw_result = curve
+                .pool_tokens_to_trading_tokens(
+                    pool_token_amount,
+                    pool_token_supply,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            prop_assume!(withdraw_result.token_a_amount <= swap_token_a_amount);
+            prop_assume!(withdraw_result.token_b_amount <= swap_token_b_amount);
+            let new_swap_token_a_amount = swap_token_a_amount - withdraw_result.token_a_amount;
+            let new_swap_token_b_amount = swap_token_b_amount - withdraw_result.token_b_amount;
+            let new_pool_token_supply = pool_token_supply - pool_token_amount;
+
+            let new_value = scaled_value(numerator, denominator, new_swap_token_a_amount, new_swap_token_b_amount);
+
+            // the following inequality must hold:
+            // new_value / new_pool_token_supply >= value / pool_token_supply
+            // which reduces to:
+            // new_value * pool_token_supply >= value * new_pool_token_supply
+            assert!(new_value * U256::from(pool_token_supply) >= value * U256::from(new_pool_token_supply));
+        }
+    }
+}
@@ -15,13 +15,27 @@ use crate::curve::{
         normalized_value, pool_tokens_to_trading_tokens, trading_tokens_to_pool_tokens,
     },
 };
-use arrayref::{array_mut_ref, array_ref};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::clock::UnixTimestamp;
 use spl_math::{precise_number::PreciseNumber, uint::U256};
 use std::convert::TryFrom;
 
 const N_COINS: u8 = 2;
 const N_COINS_SQUARED: u8 = 4;
 
+/// Smallest amplification coefficient an admin can ramp to
+pub const MIN_AMP: u64 = 1;
+/// Largest amplification coefficient an admin can ramp to
+pub const MAX_AMP: u64 = 1_000_000;
+/// Largest factor by which one ramp can raise or lower the amplification
+pub const MAX_AMP_CHANGE: u64 = 10;
+/// Shortest ramp, in seconds, so that the curve cannot be moved under
+/// liquidity providers within a few blocks
+pub const MIN_RAMP_DURATION: UnixTimestamp = 86_400;
+
+/// Length of a packed amplification ramp
+pub const AMP_RAMP_LEN: usize = 32; // 8 + 8 + 8 + 8
+
 /// Returns self to the power of b
 fn checked_u8_power(a: &U256, b: u8) -> Option<U256> {
     let mut result = *a;
@@ -56,6 +70,97 @@ pub struct StableCurve {
     pub amp: u64,
 }
 
+/// Linear change of a stable curve's amplification coefficient over time,
+/// started by a swap's admin.
+///
+/// Before `start_timestamp` the amplification is `initial_amp`, after
+/// `stop_timestamp` it is `target_amp`, and in between it moves linearly from
+/// one to the other, so the curve changes slowly enough for arbitrageurs to
+/// keep the pool priced.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AmpRamp {
+    /// Amplification when the ramp started
+    pub initial_amp: u64,
+    /// Amplification at the end of the ramp
+    pub target_amp: u64,
+    /// Unix timestamp of the start of the ramp
+    pub start_timestamp: UnixTimestamp,
+    /// Unix timestamp of the end of the ramp
+    pub stop_timestamp: UnixTimestamp,
+}
+
+impl AmpRamp {
+    /// Start a ramp from the current amplification to `target_amp`, ending
+    /// at `stop_timestamp`, failing if the ramp is too short, changes the
+    /// amplification too much, or ends out of bounds
+    pub fn new(
+        current_amp: u64,
+        target_amp: u64,
+        current_timestamp: UnixTimestamp,
+        stop_timestamp: UnixTimestamp,
+    ) -> Result<Self, SwapError> {
+        if !(MIN_AMP..=MAX_AMP).contains(&target_amp) {
+            return Err(SwapError::InvalidAmpRamp);
+        }
+        if stop_timestamp < current_timestamp.saturating_add(MIN_RAMP_DURATION) {
+            return Err(SwapError::InvalidAmpRamp);
+        }
+        let max_target = current_amp.saturating_mul(MAX_AMP_CHANGE);
+        let min_target = current_amp / MAX_AMP_CHANGE;
+        if target_amp > max_target || target_amp < min_target {
+            return Err(SwapError::InvalidAmpRamp);
+        }
+        Ok(Self {
+            initial_amp: current_amp,
+            target_amp,
+            start_timestamp: current_timestamp,
+            stop_timestamp,
+        })
+    }
+
+    /// Amplification coefficient at the given time
+    pub fn amp(&self, current_timestamp: UnixTimestamp) -> u64 {
+        if current_timestamp >= self.stop_timestamp {
+            return self.target_amp;
+        }
+        if current_timestamp <= self.start_timestamp {
+            return self.initial_amp;
+        }
+        let elapsed = (current_timestamp - self.start_timestamp) as u128;
+        let duration = (self.stop_timestamp - self.start_timestamp) as u128;
+        let initial_amp = self.initial_amp as u128;
+        let target_amp = self.target_amp as u128;
+        // the result lies between the two amps, so it always fits in a u64
+        if target_amp > initial_amp {
+            (initial_amp + (target_amp - initial_amp) * elapsed / duration) as u64
+        } else {
+            (initial_amp - (initial_amp - target_amp) * elapsed / duration) as u64
+        }
+    }
+
+    /// Pack an amplification ramp into a fixed size buffer
+    pub fn pack_into_slice(&self, dst: &mut [u8; AMP_RAMP_LEN]) {
+        let (initial_amp, target_amp, start_timestamp, stop_timestamp) =
+            mut_array_refs![dst, 8, 8, 8, 8];
+        *initial_amp = self.initial_amp.to_le_bytes();
+        *target_amp = self.target_amp.to_le_bytes();
+        *start_timestamp = self.start_timestamp.to_le_bytes();
+        *stop_timestamp = self.stop_timestamp.to_le_bytes();
+    }
+
+    /// Unpack an amplification ramp from a fixed size buffer
+    pub fn unpack_from_slice(src: &[u8; AMP_RAMP_LEN]) -> Self {
+        let (initial_amp, target_amp, start_timestamp, stop_timestamp) =
+            array_refs![src, 8, 8, 8, 8];
+        Self {
+            initial_amp: u64::from_le_bytes(*initial_amp),
+            target_amp: u64::from_le_bytes(*target_amp),
+            start_timestamp: UnixTimestamp::from_le_bytes(*start_timestamp),
+            stop_timestamp: UnixTimestamp::from_le_bytes(*stop_timestamp),
+        }
+    }
+}
+
 /// d = (leverage * sum_x + d_product * n_coins) * initial_d / ((leverage - 1) * initial_d + (n_coins + 1) * d_product)
 fn calculate_step(initial_d: &U256, leverage: u64, sum_x: u128, d_product: &U256) -> Option<U256> {
     let leverage_mul = U256::from(leverage).checked_mul(sum_x.into())?;
@@ -361,4 +466,47 @@ mod tests {
         let unpacked = StableCurve::unpack(&packed).unwrap();
         assert_eq!(curve, unpacked);
     }
+
+    #[test]
+    fn amp_ramp() {
+        let ramp = AmpRamp::new(100, 200, 1_000, 1_000 + MIN_RAMP_DURATION).unwrap();
+        assert_eq!(ramp.amp(0), 100);
+        assert_eq!(ramp.amp(1_000), 100);
+        assert_eq!(ramp.amp(1_000 + MIN_RAMP_DURATION / 4), 125);
+        assert_eq!(ramp.amp(1_000 + MIN_RAMP_DURATION / 2), 150);
+        assert_eq!(ramp.amp(1_000 + MIN_RAMP_DURATION), 200);
+        assert_eq!(ramp.amp(i64::MAX), 200);
+
+        let ramp = AmpRamp::new(200, 20, 0, MIN_RAMP_DURATION * 2).unwrap();
+        assert_eq!(ramp.amp(MIN_RAMP_DURATION), 110);
+        assert_eq!(ramp.amp(MIN_RAMP_DURATION * 2), 20);
+
+        // too short
+        assert_eq!(
+            AmpRamp::new(100, 200, 1_000, MIN_RAMP_DURATION),
+            Err(SwapError::InvalidAmpRamp)
+        );
+        // too large a change in either direction
+        assert_eq!(
+            AmpRamp::new(100, 1_001, 0, MIN_RAMP_DURATION),
+            Err(SwapError::InvalidAmpRamp)
+        );
+        assert_eq!(
+            AmpRamp::new(100, 9, 0, MIN_RAMP_DURATION),
+            Err(SwapError::InvalidAmpRamp)
+        );
+        // out of bounds
+        assert_eq!(
+            AmpRamp::new(1, 0, 0, MIN_RAMP_DURATION),
+            Err(SwapError::InvalidAmpRamp)
+        );
+        assert_eq!(
+            AmpRamp::new(MAX_AMP, MAX_AMP + 1, 0, MIN_RAMP_DURATION),
+            Err(SwapError::InvalidAmpRamp)
+        );
+
+        let mut packed = [0u8; AMP_RAMP_LEN];
+        ramp.pack_into_slice(&mut packed);
+        assert_eq!(AmpRamp::unpack_from_slice(&packed), ramp);
+    }
 }
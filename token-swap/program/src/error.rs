@@ -102,6 +102,54 @@ pub enum SwapError {
     /// The operation cannot be performed on the given curve
     #[error("The operation cannot be performed on the given curve")]
     UnsupportedCurveOperation,
+    /// The provided oracle account does not match the swap's oracle
+    #[error("The provided oracle account does not match the swap's oracle")]
+    IncorrectOracleAccount,
+    /// The oracle price is not trading, too old or too uncertain
+    #[error("The oracle price is not trading, too old or too uncertain")]
+    InvalidOraclePrice,
+
+    // 30.
+    /// The pools of a routed swap do not connect
+    #[error("The pools of a routed swap do not connect")]
+    InvalidRoute,
+    /// The number of tokens in a multi-token swap is not supported
+    #[error("The number of tokens in a multi-token swap is not supported")]
+    InvalidTokenCount,
+    /// The tick range is not valid for the concentrated liquidity pool
+    #[error("The tick range is not valid for the concentrated liquidity pool")]
+    InvalidTick,
+    /// The concentrated liquidity pool has no room for another initialized tick
+    #[error("The concentrated liquidity pool has no room for another initialized tick")]
+    TooManyTicks,
+    /// Not enough concentrated liquidity for the operation
+    #[error("Not enough concentrated liquidity for the operation")]
+    InsufficientLiquidity,
+
+    // 35.
+    /// The position still holds liquidity or uncollected fees
+    #[error("The position still holds liquidity or uncollected fees")]
+    PositionNotEmpty,
+    /// The swap has no admin, or the provided admin does not match it
+    #[error("The swap has no admin, or the provided admin does not match it")]
+    InvalidAdmin,
+    /// The amplification ramp is too short, too steep, or not on a stable curve
+    #[error("The amplification ramp is too short, too steep, or not on a stable curve")]
+    InvalidAmpRamp,
+    /// The swap is paused by its admin
+    #[error("The swap is paused by its admin")]
+    PoolPaused,
+    /// The swap is winding down and only allows withdrawals
+    #[error("The swap is winding down and only allows withdrawals")]
+    PoolWithdrawOnly,
+
+    // 40.
+    /// The oracle account is not owned by the Pyth program
+    #[error("The oracle account is not owned by the Pyth program")]
+    InvalidOracleOwner,
+    /// The position would hold less than the pool's minimum liquidity
+    #[error("The position would hold less than the pool's minimum liquidity")]
+    PositionBelowMinLiquidity,
 }
 impl From<SwapError> for ProgramError {
     fn from(e: SwapError) -> Self {
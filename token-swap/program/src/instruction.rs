@@ -2,15 +2,20 @@
 
 #![allow(clippy::too_many_arguments)]
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::curve::{
+    base::SwapCurve,
+    fees::Fees,
+    multi_token::{MultiTokenCurve, MAX_TOKENS},
+};
 use crate::error::SwapError;
+use crate::state::SwapStatus;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
 };
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::mem::size_of;
 
 #[cfg(feature = "fuzz")]
@@ -92,6 +97,148 @@ pub struct WithdrawSingleTokenTypeExactAmountOut {
     pub maximum_pool_token_amount: u64,
 }
 
+/// RouteSwap instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSwap {
+    /// SOURCE amount to transfer into the first pool of the route
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output from the last pool of
+    /// the route, prevents excessive slippage
+    pub minimum_amount_out: u64,
+}
+
+/// InitializeMultiToken instruction data
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct InitializeMultiToken {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// all swap fees
+    pub fees: Fees,
+    /// curve info for pool, including the type and its parameters
+    pub curve: MultiTokenCurve,
+    /// admin allowed to set the status of the swap, or the default pubkey
+    /// for a swap that cannot be paused
+    pub admin: Pubkey,
+}
+
+/// MultiTokenDepositAllTokenTypes instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiTokenDepositAllTokenTypes {
+    /// Pool token amount to transfer. The amount of each token is set by the
+    /// current exchange rate and size of the pool
+    pub pool_token_amount: u64,
+    /// Maximum amount of each token to deposit, in the order of the pool's
+    /// tokens, prevents excessive slippage
+    pub maximum_token_amounts: Vec<u64>,
+}
+
+/// MultiTokenWithdrawAllTokenTypes instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiTokenWithdrawAllTokenTypes {
+    /// Amount of pool tokens to burn. User receives an output of each token
+    /// based on the percentage of the pool tokens that are returned.
+    pub pool_token_amount: u64,
+    /// Minimum amount of each token to receive, in the order of the pool's
+    /// tokens, prevents excessive slippage
+    pub minimum_token_amounts: Vec<u64>,
+}
+
+/// InitializeConcentrated instruction data
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct InitializeConcentrated {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// all swap fees
+    pub fees: Fees,
+    /// positions may only use ticks that are multiples of the spacing
+    pub tick_spacing: u16,
+    /// initial square root of the price of token A in token B, as a Q64.64
+    /// fixed point number
+    pub sqrt_price: u128,
+    /// minimum liquidity of a position that holds any, which makes filling
+    /// the pool's tick slots with dust positions costly
+    pub min_position_liquidity: u128,
+    /// number of initialized ticks the pool account has room for, at most
+    /// `MAX_INITIALIZED_TICKS`
+    pub max_ticks: u16,
+    /// admin allowed to set the status of the swap, or the default pubkey
+    /// for a swap that cannot be paused
+    pub admin: Pubkey,
+}
+
+/// OpenPosition instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenPosition {
+    /// Lower tick of the position's range
+    pub tick_lower: i32,
+    /// Upper tick of the position's range
+    pub tick_upper: i32,
+}
+
+/// IncreaseLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncreaseLiquidity {
+    /// Liquidity to add to the position. The amount of each token is set by
+    /// the current price and the range of the position
+    pub liquidity: u128,
+    /// Maximum token A amount to deposit, prevents excessive slippage
+    pub maximum_token_a_amount: u64,
+    /// Maximum token B amount to deposit, prevents excessive slippage
+    pub maximum_token_b_amount: u64,
+}
+
+/// DecreaseLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecreaseLiquidity {
+    /// Liquidity to remove from the position
+    pub liquidity: u128,
+    /// Minimum token A amount to receive, prevents excessive slippage
+    pub minimum_token_a_amount: u64,
+    /// Minimum token B amount to receive, prevents excessive slippage
+    pub minimum_token_b_amount: u64,
+}
+
+/// UpdateFees instruction data
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct UpdateFees {
+    /// new swap fees, checked against the program's constraints
+    pub fees: Fees,
+}
+
+/// RampAmp instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RampAmp {
+    /// Amplification coefficient of the stable curve at the end of the ramp
+    pub target_amp: u64,
+    /// Unix timestamp of the end of the ramp
+    pub stop_ramp_timestamp: i64,
+}
+
+/// SetStatus instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetStatus {
+    /// Operations to allow on the swap from now on
+    pub status: SwapStatus,
+}
+
 /// Instructions supported by the token swap program.
 #[repr(C)]
 #[derive(Debug, PartialEq)]
@@ -108,11 +255,16 @@ pub enum SwapInstruction {
     ///   6. `[writable]` Pool Token Account to deposit the initial pool token
     ///   supply.  Must be empty, not owned by swap authority.
     ///   7. '[]` Token program id
+    ///   8. `[]` Oracle price account owned by the Pyth program, only for
+    ///   oracle curves, which need a Token-swap account of
+    ///   `SwapVersion::LATEST_LEN` or `SwapVersion::V2_LEN`
+    ///   9. `[]` Optional admin, allowed to retune the swap, which needs a
+    ///   Token-swap account of `SwapVersion::LATEST_LEN`
     Initialize(Initialize),
 
     ///   Swap the tokens in the pool.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, to update its price accumulators
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
@@ -122,14 +274,15 @@ pub enum SwapInstruction {
     ///   7. `[writable]` Pool token mint, to generate trading fees
     ///   8. `[writable]` Fee account, to receive trading fees
     ///   9. '[]` Token program id
-    ///   10 `[optional, writable]` Host fee account to receive additional trading fees
+    ///   10 `[]` Oracle price account, only for oracle curves
+    ///   10 or 11 `[optional, writable]` Host fee account to receive additional trading fees
     Swap(Swap),
 
     ///   Deposit both types of tokens into the pool.  The output is a "pool"
     ///   token representing ownership in the pool. Inputs are converted to
     ///   the current ratio.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, to update its price accumulators
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_a user transfer authority can transfer amount,
@@ -145,7 +298,7 @@ pub enum SwapInstruction {
     ///   pool tokens.  The pool tokens are burned in exchange for an equivalent
     ///   amount of token A and B.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, to update its price accumulators
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` Pool mint account, swap authority is the owner
@@ -162,7 +315,7 @@ pub enum SwapInstruction {
     ///   representing ownership into the pool. Input token is converted as if
     ///   a swap and deposit all token types were performed.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, to update its price accumulators
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
@@ -171,12 +324,13 @@ pub enum SwapInstruction {
     ///   6. `[writable]` Pool MINT account, swap authority is the owner.
     ///   7. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
     ///   8. '[]` Token program id
+    ///   9. `[]` Oracle price account, only for oracle curves
     DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
 
     ///   Withdraw one token type from the pool at the current ratio given the
     ///   exact amount out expected.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, to update its price accumulators
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` Pool mint account, swap authority is the owner
@@ -186,7 +340,239 @@ pub enum SwapInstruction {
     ///   7. `[writable]` token_(A|B) User Account to credit
     ///   8. `[writable]` Fee account, to receive withdrawal fees
     ///   9. '[]` Token program id
+    ///   10 `[]` Oracle price account, only for oracle curves
     WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Swap tokens through a route of pools, each pool swapping the output
+    ///   of the previous one.  Intermediate tokens move directly from one
+    ///   pool's token account to the next, and the slippage limit applies to
+    ///   the output of the last pool.
+    ///
+    ///   0. `[]` user transfer authority
+    ///   1. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
+    ///   2. `[writable]` token_(A|B) DESTINATION Account assigned to USER as the owner.
+    ///   3. '[]` Token program id
+    ///
+    ///   Then, for each pool of the route, in order:
+    ///
+    ///   0. `[writable]` Token-swap, to update its price accumulators
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the
+    ///   token swapped out of the previous pool, or the SOURCE token.
+    ///   3. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the
+    ///   token swapped into the next pool, or the DESTINATION token.
+    ///   4. `[writable]` Pool token mint, to generate trading fees
+    ///   5. `[writable]` Fee account, to receive trading fees
+    ///   6. `[]` Oracle price account, only for oracle curves
+    RouteSwap(RouteSwap),
+
+    ///   Initializes a new multi-token swap, holding 3 to 8 tokens, with an
+    ///   optional admin allowed to set its status.
+    ///
+    ///   0. `[writable, signer]` New Token-swap to create, of
+    ///   `SwapVersion::MULTI_TOKEN_LEN`.
+    ///   1. `[]` swap authority derived from `create_program_address(&[Token-swap account])`
+    ///   2. `[writable]` Pool Token Mint. Must be empty, owned by swap authority.
+    ///   3. `[]` Pool Token Account to deposit trading and withdraw fees.
+    ///   Must be empty, not owned by swap authority
+    ///   4. `[writable]` Pool Token Account to deposit the initial pool token
+    ///   supply.  Must be empty, not owned by swap authority.
+    ///   5. '[]` Token program id
+    ///   6. ..6+N `[]` Token Accounts of the pool, in the order of the curve
+    ///   weights.  Must be non zero, owned by swap authority.
+    InitializeMultiToken(InitializeMultiToken),
+
+    ///   Swap any two tokens of a multi-token pool.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` SOURCE Account, amount is transferable by user transfer authority,
+    ///   4. `[writable]` DESTINATION Account assigned to USER as the owner.
+    ///   5. `[writable]` Pool token mint, to generate trading fees
+    ///   6. `[writable]` Fee account, to receive trading fees
+    ///   7. '[]` Token program id
+    ///   8. ..8+N `[writable]` Token Accounts of the pool, in order
+    ///   8+N `[optional, writable]` Host fee account to receive additional trading fees
+    MultiTokenSwap(Swap),
+
+    ///   Deposit all tokens into a multi-token pool at the current ratio.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` Pool MINT account, swap authority is the owner.
+    ///   4. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
+    ///   5. '[]` Token program id
+    ///
+    ///   Then, for each token of the pool, in order:
+    ///
+    ///   0. `[writable]` user Account, user transfer authority can transfer amount
+    ///   1. `[writable]` Token Account of the pool to deposit into
+    MultiTokenDepositAllTokenTypes(MultiTokenDepositAllTokenTypes),
+
+    ///   Withdraw all tokens from a multi-token pool at the current ratio,
+    ///   given pool tokens.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` Pool mint account, swap authority is the owner
+    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
+    ///   5. `[writable]` Fee account, to receive withdrawal fees
+    ///   6. '[]` Token program id
+    ///
+    ///   Then, for each token of the pool, in order:
+    ///
+    ///   0. `[writable]` Token Account of the pool to withdraw FROM
+    ///   1. `[writable]` user Account to credit
+    MultiTokenWithdrawAllTokenTypes(MultiTokenWithdrawAllTokenTypes),
+
+    ///   Deposit one token into a multi-token pool.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` SOURCE Account, amount is transferable by user transfer authority,
+    ///   4. `[writable]` Pool MINT account, swap authority is the owner.
+    ///   5. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
+    ///   6. '[]` Token program id
+    ///   7. ..7+N `[writable]` Token Accounts of the pool, in order
+    MultiTokenDepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
+
+    ///   Withdraw one token from a multi-token pool given the exact amount
+    ///   out expected.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` Pool mint account, swap authority is the owner
+    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
+    ///   5. `[writable]` DESTINATION Account to credit
+    ///   6. `[writable]` Fee account, to receive withdrawal fees
+    ///   7. '[]` Token program id
+    ///   8. ..8+N `[writable]` Token Accounts of the pool, in order
+    MultiTokenWithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Initializes a new concentrated liquidity swap, starting without
+    ///   liquidity at the given price, with an optional admin allowed to set
+    ///   its status. The pool stores at most `max_ticks` ticks, so positions
+    ///   must hold at least the minimum position liquidity, or none at all.
+    ///
+    ///   0. `[writable, signer]` New Token-swap to create, of
+    ///      `SwapVersion::concentrated_len(max_ticks)` bytes.
+    ///   1. `[]` swap authority derived from `create_program_address(&[Token-swap account])`
+    ///   2. `[]` token_a Account, owned by swap authority.
+    ///   3. `[]` token_b Account, owned by swap authority.
+    ///   4. `[]` token_a Account to receive owner trading fees on swaps from token A.
+    ///   5. `[]` token_b Account to receive owner trading fees on swaps from token B.
+    ///   6. '[]` Token program id
+    InitializeConcentrated(InitializeConcentrated),
+
+    ///   Opens an empty position over a range of ticks of a concentrated
+    ///   liquidity swap.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[writable]` Position account to initialize, owned by the swap
+    ///      program, of `ConcentratedPosition::LEN` bytes.
+    ///   2. `[signer]` Owner of the position
+    OpenPosition(OpenPosition),
+
+    ///   Adds liquidity to a position, depositing the amounts of token A and
+    ///   B it needs at the current price.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` Position account
+    ///   4. `[signer]` Owner of the position
+    ///   5. `[writable]` token_a user transfer authority can transfer amount,
+    ///   6. `[writable]` token_b user transfer authority can transfer amount,
+    ///   7. `[writable]` token_a Base Account to deposit into.
+    ///   8. `[writable]` token_b Base Account to deposit into.
+    ///   9. '[]` Token program id
+    IncreaseLiquidity(IncreaseLiquidity),
+
+    ///   Removes liquidity from a position, withdrawing the amounts of token
+    ///   A and B it held at the current price.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Position account
+    ///   3. `[signer]` Owner of the position
+    ///   4. `[writable]` token_a Swap Account to withdraw FROM.
+    ///   5. `[writable]` token_b Swap Account to withdraw FROM.
+    ///   6. `[writable]` token_a user Account to credit.
+    ///   7. `[writable]` token_b user Account to credit.
+    ///   8. '[]` Token program id
+    DecreaseLiquidity(DecreaseLiquidity),
+
+    ///   Withdraws all trading fees earned by a position.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Position account
+    ///   3. `[signer]` Owner of the position
+    ///   4. `[writable]` token_a Swap Account to withdraw FROM.
+    ///   5. `[writable]` token_b Swap Account to withdraw FROM.
+    ///   6. `[writable]` token_a user Account to credit.
+    ///   7. `[writable]` token_b user Account to credit.
+    ///   8. '[]` Token program id
+    CollectFees,
+
+    ///   Closes a position without liquidity or uncollected fees, returning
+    ///   its lamports.
+    ///
+    ///   0. `[writable]` Position account
+    ///   1. `[signer]` Owner of the position
+    ///   2. `[writable]` Account to receive the position's lamports
+    ClosePosition,
+
+    ///   Swap the tokens in a concentrated liquidity swap, crossing as many
+    ///   initialized ticks as needed.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
+    ///   4. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
+    ///   5. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
+    ///   6. `[writable]` token_(A|B) DESTINATION Account assigned to USER as the owner.
+    ///   7. `[writable]` Fee account of the SOURCE token, to receive owner trading fees
+    ///   8. '[]` Token program id
+    ///   9. `[optional, writable]` Host fee account of the SOURCE token to receive additional trading fees
+    ConcentratedSwap(Swap),
+
+    ///   Replaces the fees of a swap with an admin.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin of the swap
+    UpdateFees(UpdateFees),
+
+    ///   Starts moving the amplification coefficient of a stable curve
+    ///   linearly from its current value to the target, ending at the given
+    ///   time, replacing any ramp in progress.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin of the swap
+    RampAmp(RampAmp),
+
+    ///   Replaces the pool token account receiving the trading and withdrawal
+    ///   fees of a swap with an admin.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin of the swap
+    ///   2. `[]` New pool token account to receive the fees, not owned by the
+    ///   swap authority
+    SetFeeAccount,
+
+    ///   Pauses a swap with an admin, restricts it to withdrawals, or makes
+    ///   it active again. Works on two-token, multi-token and concentrated
+    ///   liquidity swaps.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin of the swap
+    SetStatus(SetStatus),
 }
 
 impl SwapInstruction {
@@ -253,6 +639,157 @@ impl SwapInstruction {
                     maximum_pool_token_amount,
                 })
             }
+            6 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::RouteSwap(RouteSwap {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            7 => {
+                let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                if rest.len() >= Fees::LEN + MultiTokenCurve::LEN {
+                    let (fees, rest) = rest.split_at(Fees::LEN);
+                    let fees = Fees::unpack_unchecked(fees)?;
+                    let (curve, rest) = rest.split_at(MultiTokenCurve::LEN);
+                    let curve = MultiTokenCurve::unpack_unchecked(curve)?;
+                    let (admin, _rest) = Self::unpack_pubkey(rest)?;
+                    Self::InitializeMultiToken(InitializeMultiToken {
+                        nonce,
+                        fees,
+                        curve,
+                        admin,
+                    })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
+            8 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::MultiTokenSwap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            9 => {
+                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_amounts, _rest) = Self::unpack_u64_vec(rest)?;
+                Self::MultiTokenDepositAllTokenTypes(MultiTokenDepositAllTokenTypes {
+                    pool_token_amount,
+                    maximum_token_amounts,
+                })
+            }
+            10 => {
+                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_amounts, _rest) = Self::unpack_u64_vec(rest)?;
+                Self::MultiTokenWithdrawAllTokenTypes(MultiTokenWithdrawAllTokenTypes {
+                    pool_token_amount,
+                    minimum_token_amounts,
+                })
+            }
+            11 => {
+                let (source_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::MultiTokenDepositSingleTokenTypeExactAmountIn(
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                )
+            }
+            12 => {
+                let (destination_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::MultiTokenWithdrawSingleTokenTypeExactAmountOut(
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                )
+            }
+            13 => {
+                let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                if rest.len() >= Fees::LEN {
+                    let (fees, rest) = rest.split_at(Fees::LEN);
+                    let fees = Fees::unpack_unchecked(fees)?;
+                    let (tick_spacing, rest) = Self::unpack_u16(rest)?;
+                    let (sqrt_price, rest) = Self::unpack_u128(rest)?;
+                    let (min_position_liquidity, rest) = Self::unpack_u128(rest)?;
+                    let (max_ticks, rest) = Self::unpack_u16(rest)?;
+                    let (admin, _rest) = Self::unpack_pubkey(rest)?;
+                    Self::InitializeConcentrated(InitializeConcentrated {
+                        nonce,
+                        fees,
+                        tick_spacing,
+                        sqrt_price,
+                        min_position_liquidity,
+                        max_ticks,
+                        admin,
+                    })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
+            14 => {
+                let (tick_lower, rest) = Self::unpack_i32(rest)?;
+                let (tick_upper, _rest) = Self::unpack_i32(rest)?;
+                Self::OpenPosition(OpenPosition {
+                    tick_lower,
+                    tick_upper,
+                })
+            }
+            15 => {
+                let (liquidity, rest) = Self::unpack_u128(rest)?;
+                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::IncreaseLiquidity(IncreaseLiquidity {
+                    liquidity,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                })
+            }
+            16 => {
+                let (liquidity, rest) = Self::unpack_u128(rest)?;
+                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DecreaseLiquidity(DecreaseLiquidity {
+                    liquidity,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            17 => Self::CollectFees,
+            18 => Self::ClosePosition,
+            19 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::ConcentratedSwap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            20 => {
+                let fees = rest.get(..Fees::LEN).ok_or(SwapError::InvalidInstruction)?;
+                let fees = Fees::unpack_unchecked(fees)?;
+                Self::UpdateFees(UpdateFees { fees })
+            }
+            21 => {
+                let (target_amp, rest) = Self::unpack_u64(rest)?;
+                let (stop_ramp_timestamp, _rest) = Self::unpack_i64(rest)?;
+                Self::RampAmp(RampAmp {
+                    target_amp,
+                    stop_ramp_timestamp,
+                })
+            }
+            22 => Self::SetFeeAccount,
+            23 => {
+                let (&status, _rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                let status =
+                    SwapStatus::try_from(status).map_err(|_| SwapError::InvalidInstruction)?;
+                Self::SetStatus(SetStatus { status })
+            }
             _ => return Err(SwapError::InvalidInstruction.into()),
         })
     }
@@ -271,31 +808,110 @@ impl SwapInstruction {
         }
     }
 
-    /// Packs a [SwapInstruction](enum.SwapInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match &*self {
-            Self::Initialize(Initialize {
-                nonce,
-                fees,
-                swap_curve,
-            }) => {
-                buf.push(0);
-                buf.push(*nonce);
-                let mut fees_slice = [0u8; Fees::LEN];
-                Pack::pack_into_slice(fees, &mut fees_slice[..]);
-                buf.extend_from_slice(&fees_slice);
-                let mut swap_curve_slice = [0u8; SwapCurve::LEN];
-                Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
-                buf.extend_from_slice(&swap_curve_slice);
-            }
-            Self::Swap(Swap {
-                amount_in,
-                minimum_amount_out,
-            }) => {
-                buf.push(1);
-                buf.extend_from_slice(&amount_in.to_le_bytes());
-                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() >= 2 {
+            let (value, rest) = input.split_at(2);
+            let value = value
+                .try_into()
+                .map(u16::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_i64(input: &[u8]) -> Result<(i64, &[u8]), ProgramError> {
+        if input.len() >= 8 {
+            let (value, rest) = input.split_at(8);
+            let value = value
+                .try_into()
+                .map(i64::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_i32(input: &[u8]) -> Result<(i32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (value, rest) = input.split_at(4);
+            let value = value
+                .try_into()
+                .map(i32::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), ProgramError> {
+        if input.len() >= 16 {
+            let (value, rest) = input.split_at(16);
+            let value = value
+                .try_into()
+                .map(u128::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() >= 32 {
+            let (key, rest) = input.split_at(32);
+            let key = key
+                .try_into()
+                .map(Pubkey::new_from_array)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((key, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_u64_vec(input: &[u8]) -> Result<(Vec<u64>, &[u8]), ProgramError> {
+        let (&count, mut rest) = input.split_first().ok_or(SwapError::InvalidInstruction)?;
+        if count as usize > MAX_TOKENS {
+            return Err(SwapError::InvalidInstruction.into());
+        }
+        let mut amounts = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (amount, next) = Self::unpack_u64(rest)?;
+            amounts.push(amount);
+            rest = next;
+        }
+        Ok((amounts, rest))
+    }
+
+    /// Packs a [SwapInstruction](enum.SwapInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match &*self {
+            Self::Initialize(Initialize {
+                nonce,
+                fees,
+                swap_curve,
+            }) => {
+                buf.push(0);
+                buf.push(*nonce);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                let mut swap_curve_slice = [0u8; SwapCurve::LEN];
+                Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
+                buf.extend_from_slice(&swap_curve_slice);
+            }
+            Self::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(1);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
             }
             Self::DepositAllTokenTypes(DepositAllTokenTypes {
                 pool_token_amount,
@@ -335,6 +951,157 @@ impl SwapInstruction {
                 buf.extend_from_slice(&destination_token_amount.to_le_bytes());
                 buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
             }
+            Self::RouteSwap(RouteSwap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(6);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::InitializeMultiToken(InitializeMultiToken {
+                nonce,
+                fees,
+                curve,
+                admin,
+            }) => {
+                buf.push(7);
+                buf.push(*nonce);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                let mut curve_slice = [0u8; MultiTokenCurve::LEN];
+                Pack::pack_into_slice(curve, &mut curve_slice[..]);
+                buf.extend_from_slice(&curve_slice);
+                buf.extend_from_slice(admin.as_ref());
+            }
+            Self::MultiTokenSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(8);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::MultiTokenDepositAllTokenTypes(MultiTokenDepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_amounts,
+            }) => {
+                buf.push(9);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+                buf.push(maximum_token_amounts.len() as u8);
+                for amount in maximum_token_amounts {
+                    buf.extend_from_slice(&amount.to_le_bytes());
+                }
+            }
+            Self::MultiTokenWithdrawAllTokenTypes(MultiTokenWithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_amounts,
+            }) => {
+                buf.push(10);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+                buf.push(minimum_token_amounts.len() as u8);
+                for amount in minimum_token_amounts {
+                    buf.extend_from_slice(&amount.to_le_bytes());
+                }
+            }
+            Self::MultiTokenDepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                buf.push(11);
+                buf.extend_from_slice(&source_token_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_pool_token_amount.to_le_bytes());
+            }
+            Self::MultiTokenWithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                buf.push(12);
+                buf.extend_from_slice(&destination_token_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
+            }
+            Self::InitializeConcentrated(InitializeConcentrated {
+                nonce,
+                fees,
+                tick_spacing,
+                sqrt_price,
+                min_position_liquidity,
+                max_ticks,
+                admin,
+            }) => {
+                buf.push(13);
+                buf.push(*nonce);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                buf.extend_from_slice(&tick_spacing.to_le_bytes());
+                buf.extend_from_slice(&sqrt_price.to_le_bytes());
+                buf.extend_from_slice(&min_position_liquidity.to_le_bytes());
+                buf.extend_from_slice(&max_ticks.to_le_bytes());
+                buf.extend_from_slice(admin.as_ref());
+            }
+            Self::OpenPosition(OpenPosition {
+                tick_lower,
+                tick_upper,
+            }) => {
+                buf.push(14);
+                buf.extend_from_slice(&tick_lower.to_le_bytes());
+                buf.extend_from_slice(&tick_upper.to_le_bytes());
+            }
+            Self::IncreaseLiquidity(IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                buf.push(15);
+                buf.extend_from_slice(&liquidity.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+            }
+            Self::DecreaseLiquidity(DecreaseLiquidity {
+                liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                buf.push(16);
+                buf.extend_from_slice(&liquidity.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::CollectFees => buf.push(17),
+            Self::ClosePosition => buf.push(18),
+            Self::ConcentratedSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(19);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::UpdateFees(UpdateFees { fees }) => {
+                buf.push(20);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+            }
+            Self::RampAmp(RampAmp {
+                target_amp,
+                stop_ramp_timestamp,
+            }) => {
+                buf.push(21);
+                buf.extend_from_slice(&target_amp.to_le_bytes());
+                buf.extend_from_slice(&stop_ramp_timestamp.to_le_bytes());
+            }
+            Self::SetFeeAccount => buf.push(22),
+            Self::SetStatus(SetStatus { status }) => {
+                buf.push(23);
+                buf.push(*status as u8);
+            }
         }
         buf
     }
@@ -348,28 +1115,864 @@ pub fn initialize(
     authority_pubkey: &Pubkey,
     token_a_pubkey: &Pubkey,
     token_b_pubkey: &Pubkey,
-    pool_pubkey: &Pubkey,
-    fee_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    nonce: u8,
+    fees: Fees,
+    swap_curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    initialize_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        fee_pubkey,
+        destination_pubkey,
+        None,
+        None,
+        nonce,
+        fees,
+        swap_curve,
+    )
+}
+
+/// Creates an 'initialize' instruction for an oracle curve, optionally with an admin.
+pub fn initialize_with_oracle(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    admin_pubkey: Option<&Pubkey>,
+    nonce: u8,
+    fees: Fees,
+    swap_curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    initialize_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        fee_pubkey,
+        destination_pubkey,
+        Some(oracle_pubkey),
+        admin_pubkey,
+        nonce,
+        fees,
+        swap_curve,
+    )
+}
+
+/// Creates an 'initialize' instruction for a swap with an admin.
+pub fn initialize_with_admin(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    nonce: u8,
+    fees: Fees,
+    swap_curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    initialize_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        fee_pubkey,
+        destination_pubkey,
+        None,
+        Some(admin_pubkey),
+        nonce,
+        fees,
+        swap_curve,
+    )
+}
+
+fn initialize_instruction(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_pubkey: Option<&Pubkey>,
+    admin_pubkey: Option<&Pubkey>,
+    nonce: u8,
+    fees: Fees,
+    swap_curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    let init_data = SwapInstruction::Initialize(Initialize {
+        nonce,
+        fees,
+        swap_curve,
+    });
+    let data = init_data.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, true),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*token_a_pubkey, false),
+        AccountMeta::new_readonly(*token_b_pubkey, false),
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*fee_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(oracle_pubkey) = oracle_pubkey {
+        accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    }
+    if let Some(admin_pubkey) = admin_pubkey {
+        accounts.push(AccountMeta::new_readonly(*admin_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_all_token_types' instruction.
+pub fn deposit_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DepositAllTokenTypes(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*deposit_token_a_pubkey, false),
+        AccountMeta::new(*deposit_token_b_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_all_token_types' instruction.
+pub fn withdraw_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    instruction: WithdrawAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WithdrawAllTokenTypes(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_single_token_type_exact_amount_in' instruction.
+pub fn deposit_single_token_type_exact_amount_in(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    deposit_single_token_type_exact_amount_in_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        source_token_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        pool_mint_pubkey,
+        destination_pubkey,
+        None,
+        instruction,
+    )
+}
+
+/// Creates a 'deposit_single_token_type_exact_amount_in' instruction for an oracle curve.
+pub fn deposit_single_token_type_exact_amount_in_with_oracle(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    deposit_single_token_type_exact_amount_in_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        source_token_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        pool_mint_pubkey,
+        destination_pubkey,
+        Some(oracle_pubkey),
+        instruction,
+    )
+}
+
+fn deposit_single_token_type_exact_amount_in_instruction(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_pubkey: Option<&Pubkey>,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_token_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(oracle_pubkey) = oracle_pubkey {
+        accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction.
+pub fn withdraw_single_token_type_exact_amount_out(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    withdraw_single_token_type_exact_amount_out_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        pool_mint_pubkey,
+        fee_account_pubkey,
+        pool_token_source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_pubkey,
+        None,
+        instruction,
+    )
+}
+
+/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction for an oracle curve.
+pub fn withdraw_single_token_type_exact_amount_out_with_oracle(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    withdraw_single_token_type_exact_amount_out_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        pool_mint_pubkey,
+        fee_account_pubkey,
+        pool_token_source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_pubkey,
+        Some(oracle_pubkey),
+        instruction,
+    )
+}
+
+fn withdraw_single_token_type_exact_amount_out_instruction(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_pubkey: Option<&Pubkey>,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_token_source_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(oracle_pubkey) = oracle_pubkey {
+        accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'swap' instruction.
+pub fn swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    swap_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        source_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        pool_fee_pubkey,
+        None,
+        host_fee_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'swap' instruction for an oracle curve.
+pub fn swap_with_oracle(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    swap_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        source_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        pool_fee_pubkey,
+        Some(oracle_pubkey),
+        host_fee_pubkey,
+        instruction,
+    )
+}
+
+fn swap_instruction(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    oracle_pubkey: Option<&Pubkey>,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::Swap(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(oracle_pubkey) = oracle_pubkey {
+        accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    }
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Accounts of one pool in a routed swap
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSwapPool {
+    /// Token-swap account
+    pub swap_pubkey: Pubkey,
+    /// Swap authority
+    pub authority_pubkey: Pubkey,
+    /// Token account of the swap receiving tokens
+    pub swap_source_pubkey: Pubkey,
+    /// Token account of the swap sending tokens
+    pub swap_destination_pubkey: Pubkey,
+    /// Pool token mint
+    pub pool_mint_pubkey: Pubkey,
+    /// Pool token account receiving trading fees
+    pub pool_fee_pubkey: Pubkey,
+    /// Oracle price account, only for oracle curves
+    pub oracle_pubkey: Option<Pubkey>,
+}
+
+/// Creates a 'route_swap' instruction.
+pub fn route_swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pools: &[RouteSwapPool],
+    instruction: RouteSwap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RouteSwap(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for pool in pools {
+        accounts.push(AccountMeta::new(pool.swap_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(pool.authority_pubkey, false));
+        accounts.push(AccountMeta::new(pool.swap_source_pubkey, false));
+        accounts.push(AccountMeta::new(pool.swap_destination_pubkey, false));
+        accounts.push(AccountMeta::new(pool.pool_mint_pubkey, false));
+        accounts.push(AccountMeta::new(pool.pool_fee_pubkey, false));
+        if let Some(oracle_pubkey) = pool.oracle_pubkey {
+            accounts.push(AccountMeta::new_readonly(oracle_pubkey, false));
+        }
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_multi_token' instruction.
+pub fn initialize_multi_token(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    token_pubkeys: &[Pubkey],
+    admin_pubkey: Option<&Pubkey>,
+    nonce: u8,
+    fees: Fees,
+    curve: MultiTokenCurve,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::InitializeMultiToken(InitializeMultiToken {
+        nonce,
+        fees,
+        curve,
+        admin: admin_pubkey.copied().unwrap_or_default(),
+    })
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, true),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*fee_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for token_pubkey in token_pubkeys {
+        accounts.push(AccountMeta::new_readonly(*token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'multi_token_swap' instruction.
+pub fn multi_token_swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    swap_token_pubkeys: &[Pubkey],
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::MultiTokenSwap(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for swap_token_pubkey in swap_token_pubkeys {
+        accounts.push(AccountMeta::new(*swap_token_pubkey, false));
+    }
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'multi_token_deposit_all_token_types' instruction.
+pub fn multi_token_deposit_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    deposit_token_pubkeys: &[Pubkey],
+    swap_token_pubkeys: &[Pubkey],
+    instruction: MultiTokenDepositAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::MultiTokenDepositAllTokenTypes(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for (deposit_token_pubkey, swap_token_pubkey) in
+        deposit_token_pubkeys.iter().zip(swap_token_pubkeys.iter())
+    {
+        accounts.push(AccountMeta::new(*deposit_token_pubkey, false));
+        accounts.push(AccountMeta::new(*swap_token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'multi_token_withdraw_all_token_types' instruction.
+pub fn multi_token_withdraw_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    swap_token_pubkeys: &[Pubkey],
+    destination_token_pubkeys: &[Pubkey],
+    instruction: MultiTokenWithdrawAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::MultiTokenWithdrawAllTokenTypes(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for (swap_token_pubkey, destination_token_pubkey) in swap_token_pubkeys
+        .iter()
+        .zip(destination_token_pubkeys.iter())
+    {
+        accounts.push(AccountMeta::new(*swap_token_pubkey, false));
+        accounts.push(AccountMeta::new(*destination_token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'multi_token_deposit_single_token_type_exact_amount_in' instruction.
+pub fn multi_token_deposit_single_token_type_exact_amount_in(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    swap_token_pubkeys: &[Pubkey],
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::MultiTokenDepositSingleTokenTypeExactAmountIn(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_token_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for swap_token_pubkey in swap_token_pubkeys {
+        accounts.push(AccountMeta::new(*swap_token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'multi_token_withdraw_single_token_type_exact_amount_out' instruction.
+pub fn multi_token_withdraw_single_token_type_exact_amount_out(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    swap_token_pubkeys: &[Pubkey],
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::MultiTokenWithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_token_source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for swap_token_pubkey in swap_token_pubkeys {
+        accounts.push(AccountMeta::new(*swap_token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_concentrated' instruction.
+pub fn initialize_concentrated(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    fee_a_pubkey: &Pubkey,
+    fee_b_pubkey: &Pubkey,
+    admin_pubkey: Option<&Pubkey>,
     nonce: u8,
     fees: Fees,
-    swap_curve: SwapCurve,
+    tick_spacing: u16,
+    sqrt_price: u128,
+    min_position_liquidity: u128,
+    max_ticks: u16,
 ) -> Result<Instruction, ProgramError> {
-    let init_data = SwapInstruction::Initialize(Initialize {
+    let data = SwapInstruction::InitializeConcentrated(InitializeConcentrated {
         nonce,
         fees,
-        swap_curve,
-    });
-    let data = init_data.pack();
+        tick_spacing,
+        sqrt_price,
+        min_position_liquidity,
+        max_ticks,
+        admin: admin_pubkey.copied().unwrap_or_default(),
+    })
+    .pack();
 
     let accounts = vec![
         AccountMeta::new(*swap_pubkey, true),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*token_a_pubkey, false),
         AccountMeta::new_readonly(*token_b_pubkey, false),
-        AccountMeta::new(*pool_pubkey, false),
-        AccountMeta::new_readonly(*fee_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*fee_a_pubkey, false),
+        AccountMeta::new_readonly(*fee_b_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
 
@@ -380,33 +1983,56 @@ pub fn initialize(
     })
 }
 
-/// Creates a 'deposit_all_token_types' instruction.
-pub fn deposit_all_token_types(
+/// Creates an 'open_position' instruction.
+pub fn open_position(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    instruction: OpenPosition,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::OpenPosition(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'increase_liquidity' instruction.
+pub fn increase_liquidity(
     program_id: &Pubkey,
     token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
     authority_pubkey: &Pubkey,
     user_transfer_authority_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
     deposit_token_a_pubkey: &Pubkey,
     deposit_token_b_pubkey: &Pubkey,
     swap_token_a_pubkey: &Pubkey,
     swap_token_b_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: DepositAllTokenTypes,
+    instruction: IncreaseLiquidity,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::DepositAllTokenTypes(instruction).pack();
+    let data = SwapInstruction::IncreaseLiquidity(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*deposit_token_a_pubkey, false),
         AccountMeta::new(*deposit_token_b_pubkey, false),
         AccountMeta::new(*swap_token_a_pubkey, false),
         AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
 
@@ -417,35 +2043,31 @@ pub fn deposit_all_token_types(
     })
 }
 
-/// Creates a 'withdraw_all_token_types' instruction.
-pub fn withdraw_all_token_types(
+/// Creates a 'decrease_liquidity' instruction.
+pub fn decrease_liquidity(
     program_id: &Pubkey,
     token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
     authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    fee_account_pubkey: &Pubkey,
-    source_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
     swap_token_a_pubkey: &Pubkey,
     swap_token_b_pubkey: &Pubkey,
     destination_token_a_pubkey: &Pubkey,
     destination_token_b_pubkey: &Pubkey,
-    instruction: WithdrawAllTokenTypes,
+    instruction: DecreaseLiquidity,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::WithdrawAllTokenTypes(instruction).pack();
+    let data = SwapInstruction::DecreaseLiquidity(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*swap_token_a_pubkey, false),
         AccountMeta::new(*swap_token_b_pubkey, false),
         AccountMeta::new(*destination_token_a_pubkey, false),
         AccountMeta::new(*destination_token_b_pubkey, false),
-        AccountMeta::new(*fee_account_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
 
@@ -456,31 +2078,30 @@ pub fn withdraw_all_token_types(
     })
 }
 
-/// Creates a 'deposit_single_token_type_exact_amount_in' instruction.
-pub fn deposit_single_token_type_exact_amount_in(
+/// Creates a 'collect_fees' instruction.
+pub fn collect_fees(
     program_id: &Pubkey,
     token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
     authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    source_token_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
     swap_token_a_pubkey: &Pubkey,
     swap_token_b_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: DepositSingleTokenTypeExactAmountIn,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
+    let data = SwapInstruction::CollectFees.pack();
 
     let accounts = vec![
         AccountMeta::new_readonly(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*source_token_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*swap_token_a_pubkey, false),
         AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
 
@@ -491,34 +2112,19 @@ pub fn deposit_single_token_type_exact_amount_in(
     })
 }
 
-/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction.
-pub fn withdraw_single_token_type_exact_amount_out(
+/// Creates a 'close_position' instruction.
+pub fn close_position(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    fee_account_pubkey: &Pubkey,
-    pool_token_source_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
     destination_pubkey: &Pubkey,
-    instruction: WithdrawSingleTokenTypeExactAmountOut,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+    let data = SwapInstruction::ClosePosition.pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*pool_token_source_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new(*fee_account_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
     ];
 
     Ok(Instruction {
@@ -528,8 +2134,8 @@ pub fn withdraw_single_token_type_exact_amount_out(
     })
 }
 
-/// Creates a 'swap' instruction.
-pub fn swap(
+/// Creates a 'concentrated_swap' instruction.
+pub fn concentrated_swap(
     program_id: &Pubkey,
     token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
@@ -539,23 +2145,21 @@ pub fn swap(
     swap_source_pubkey: &Pubkey,
     swap_destination_pubkey: &Pubkey,
     destination_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    pool_fee_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
     host_fee_pubkey: Option<&Pubkey>,
     instruction: Swap,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::Swap(instruction).pack();
+    let data = SwapInstruction::ConcentratedSwap(instruction).pack();
 
     let mut accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
         AccountMeta::new(*source_pubkey, false),
         AccountMeta::new(*swap_source_pubkey, false),
         AccountMeta::new(*swap_destination_pubkey, false),
         AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new(*fee_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
     if let Some(host_fee_pubkey) = host_fee_pubkey {
@@ -569,6 +2173,91 @@ pub fn swap(
     })
 }
 
+/// Creates an 'update_fees' instruction.
+pub fn update_fees(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    fees: Fees,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::UpdateFees(UpdateFees { fees }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'ramp_amp' instruction.
+pub fn ramp_amp(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    instruction: RampAmp,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RampAmp(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_fee_account' instruction.
+pub fn set_fee_account(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetFeeAccount.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_status' instruction.
+pub fn set_status(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    status: SwapStatus,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetStatus(SetStatus { status }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
 /// Unpacks a reference from a bytes buffer.
 /// TODO actually pack / unpack instead of relying on normal memory layout.
 pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
@@ -584,7 +2273,7 @@ pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
 mod tests {
     use super::*;
 
-    use crate::curve::{base::CurveType, stable::StableCurve};
+    use crate::curve::{base::CurveType, multi_token::MultiTokenCurveType, stable::StableCurve};
 
     #[test]
     fn pack_intialize() {
@@ -731,4 +2420,318 @@ mod tests {
         let unpacked = SwapInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
     }
+
+    #[test]
+    fn pack_route_swap() {
+        let amount_in: u64 = 1_000;
+        let minimum_amount_out: u64 = 950;
+        let check = SwapInstruction::RouteSwap(RouteSwap {
+            amount_in,
+            minimum_amount_out,
+        });
+        let packed = check.pack();
+        let mut expect = vec![6];
+        expect.extend_from_slice(&amount_in.to_le_bytes());
+        expect.extend_from_slice(&minimum_amount_out.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_initialize_multi_token() {
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 4,
+            owner_trade_fee_numerator: 2,
+            owner_trade_fee_denominator: 5,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 3,
+            host_fee_numerator: 5,
+            host_fee_denominator: 20,
+        };
+        let nonce: u8 = 255;
+        let curve = MultiTokenCurve {
+            curve_type: MultiTokenCurveType::WeightedConstantProduct,
+            amp: 0,
+            weights: [4, 2, 2, 0, 0, 0, 0, 0],
+        };
+        let admin = Pubkey::new_from_array([3u8; 32]);
+        let check = SwapInstruction::InitializeMultiToken(InitializeMultiToken {
+            nonce,
+            fees: fees.clone(),
+            curve,
+            admin,
+        });
+        let packed = check.pack();
+        let mut expect = vec![7u8, nonce];
+        let mut fees_slice = [0u8; Fees::LEN];
+        fees.pack_into_slice(&mut fees_slice);
+        expect.extend_from_slice(&fees_slice);
+        expect.push(MultiTokenCurveType::WeightedConstantProduct as u8);
+        expect.extend_from_slice(&0u64.to_le_bytes());
+        expect.extend_from_slice(&[4, 2, 2, 0, 0, 0, 0, 0]);
+        expect.extend_from_slice(admin.as_ref());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        expect.pop();
+        assert_eq!(
+            SwapInstruction::unpack(&expect).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+    }
+
+    #[test]
+    fn pack_multi_token_deposit_and_withdraw_all() {
+        let pool_token_amount: u64 = 5;
+        let token_amounts: Vec<u64> = vec![10, 20, 30];
+        let check =
+            SwapInstruction::MultiTokenDepositAllTokenTypes(MultiTokenDepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_amounts: token_amounts.clone(),
+            });
+        let packed = check.pack();
+        let mut expect = vec![9u8];
+        expect.extend_from_slice(&pool_token_amount.to_le_bytes());
+        expect.push(3);
+        for amount in token_amounts.iter() {
+            expect.extend_from_slice(&amount.to_le_bytes());
+        }
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check =
+            SwapInstruction::MultiTokenWithdrawAllTokenTypes(MultiTokenWithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_amounts: token_amounts,
+            });
+        expect[0] = 10;
+        assert_eq!(check.pack(), expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        // too many amounts, or fewer than declared
+        expect[9] = (MAX_TOKENS + 1) as u8;
+        assert_eq!(
+            SwapInstruction::unpack(&expect).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+        expect[9] = 4;
+        assert_eq!(
+            SwapInstruction::unpack(&expect).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+    }
+
+    #[test]
+    fn pack_multi_token_swap_and_single_token_types() {
+        let first: u64 = 1_000;
+        let second: u64 = 950;
+        let checks = vec![
+            (
+                8u8,
+                SwapInstruction::MultiTokenSwap(Swap {
+                    amount_in: first,
+                    minimum_amount_out: second,
+                }),
+            ),
+            (
+                11u8,
+                SwapInstruction::MultiTokenDepositSingleTokenTypeExactAmountIn(
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount: first,
+                        minimum_pool_token_amount: second,
+                    },
+                ),
+            ),
+            (
+                12u8,
+                SwapInstruction::MultiTokenWithdrawSingleTokenTypeExactAmountOut(
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount: first,
+                        maximum_pool_token_amount: second,
+                    },
+                ),
+            ),
+        ];
+        for (tag, check) in checks {
+            let packed = check.pack();
+            let mut expect = vec![tag];
+            expect.extend_from_slice(&first.to_le_bytes());
+            expect.extend_from_slice(&second.to_le_bytes());
+            assert_eq!(packed, expect);
+            let unpacked = SwapInstruction::unpack(&expect).unwrap();
+            assert_eq!(unpacked, check);
+        }
+    }
+
+    #[test]
+    fn pack_initialize_concentrated() {
+        let fees = Fees {
+            trade_fee_numerator: 3,
+            trade_fee_denominator: 1000,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 2000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 1,
+            host_fee_denominator: 5,
+        };
+        let nonce: u8 = 255;
+        let tick_spacing: u16 = 60;
+        let sqrt_price: u128 = 1 << 64;
+        let min_position_liquidity: u128 = 1_000;
+        let max_ticks: u16 = 64;
+        let check = SwapInstruction::InitializeConcentrated(InitializeConcentrated {
+            nonce,
+            fees: fees.clone(),
+            tick_spacing,
+            sqrt_price,
+            min_position_liquidity,
+            max_ticks,
+            admin: Pubkey::default(),
+        });
+        let packed = check.pack();
+        let mut expect = vec![13u8, nonce];
+        let mut fees_slice = [0u8; Fees::LEN];
+        Pack::pack_into_slice(&fees, &mut fees_slice[..]);
+        expect.extend_from_slice(&fees_slice);
+        expect.extend_from_slice(&tick_spacing.to_le_bytes());
+        expect.extend_from_slice(&sqrt_price.to_le_bytes());
+        expect.extend_from_slice(&min_position_liquidity.to_le_bytes());
+        expect.extend_from_slice(&max_ticks.to_le_bytes());
+        expect.extend_from_slice(&[0u8; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        expect.pop();
+        assert_eq!(
+            SwapInstruction::unpack(&expect).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+    }
+
+    #[test]
+    fn pack_positions() {
+        let tick_lower: i32 = -120;
+        let tick_upper: i32 = 600;
+        let check = SwapInstruction::OpenPosition(OpenPosition {
+            tick_lower,
+            tick_upper,
+        });
+        let packed = check.pack();
+        let mut expect = vec![14u8];
+        expect.extend_from_slice(&tick_lower.to_le_bytes());
+        expect.extend_from_slice(&tick_upper.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let liquidity: u128 = 1 << 70;
+        let first: u64 = 500;
+        let second: u64 = 700;
+        let checks = vec![
+            (
+                15u8,
+                SwapInstruction::IncreaseLiquidity(IncreaseLiquidity {
+                    liquidity,
+                    maximum_token_a_amount: first,
+                    maximum_token_b_amount: second,
+                }),
+            ),
+            (
+                16u8,
+                SwapInstruction::DecreaseLiquidity(DecreaseLiquidity {
+                    liquidity,
+                    minimum_token_a_amount: first,
+                    minimum_token_b_amount: second,
+                }),
+            ),
+        ];
+        for (tag, check) in checks {
+            let packed = check.pack();
+            let mut expect = vec![tag];
+            expect.extend_from_slice(&liquidity.to_le_bytes());
+            expect.extend_from_slice(&first.to_le_bytes());
+            expect.extend_from_slice(&second.to_le_bytes());
+            assert_eq!(packed, expect);
+            let unpacked = SwapInstruction::unpack(&expect).unwrap();
+            assert_eq!(unpacked, check);
+        }
+
+        for (tag, check) in [
+            (17u8, SwapInstruction::CollectFees),
+            (18u8, SwapInstruction::ClosePosition),
+        ] {
+            assert_eq!(check.pack(), vec![tag]);
+            assert_eq!(SwapInstruction::unpack(&[tag]).unwrap(), check);
+        }
+
+        let check = SwapInstruction::ConcentratedSwap(Swap {
+            amount_in: first,
+            minimum_amount_out: second,
+        });
+        let packed = check.pack();
+        let mut expect = vec![19u8];
+        expect.extend_from_slice(&first.to_le_bytes());
+        expect.extend_from_slice(&second.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_admin_instructions() {
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 4,
+            owner_trade_fee_numerator: 2,
+            owner_trade_fee_denominator: 5,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 3,
+            host_fee_numerator: 5,
+            host_fee_denominator: 20,
+        };
+        let check = SwapInstruction::UpdateFees(UpdateFees { fees: fees.clone() });
+        let packed = check.pack();
+        let mut expect = vec![20u8];
+        let mut fees_slice = [0u8; Fees::LEN];
+        Pack::pack_into_slice(&fees, &mut fees_slice[..]);
+        expect.extend_from_slice(&fees_slice);
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let target_amp: u64 = 100;
+        let stop_ramp_timestamp: i64 = 1_620_086_400;
+        let check = SwapInstruction::RampAmp(RampAmp {
+            target_amp,
+            stop_ramp_timestamp,
+        });
+        let packed = check.pack();
+        let mut expect = vec![21u8];
+        expect.extend_from_slice(&target_amp.to_le_bytes());
+        expect.extend_from_slice(&stop_ramp_timestamp.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = SwapInstruction::SetFeeAccount;
+        assert_eq!(check.pack(), vec![22u8]);
+        assert_eq!(SwapInstruction::unpack(&[22u8]).unwrap(), check);
+
+        let check = SwapInstruction::SetStatus(SetStatus {
+            status: SwapStatus::WithdrawOnly,
+        });
+        assert_eq!(check.pack(), vec![23u8, 2]);
+        assert_eq!(SwapInstruction::unpack(&[23u8, 2]).unwrap(), check);
+        assert_eq!(
+            SwapInstruction::unpack(&[23u8, 3]),
+            Err(SwapError::InvalidInstruction.into())
+        );
+    }
 }
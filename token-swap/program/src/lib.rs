@@ -7,7 +7,9 @@ pub mod curve;
 pub mod error;
 pub mod instruction;
 pub mod processor;
+pub mod pyth;
 pub mod state;
+pub mod twap;
 
 #[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
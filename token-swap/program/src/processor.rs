@@ -3,20 +3,32 @@
 use crate::constraints::{SwapConstraints, SWAP_CONSTRAINTS};
 use crate::{
     curve::{
-        base::SwapCurve,
-        calculator::{RoundDirection, TradeDirection},
+        base::{CurveType, SwapCurve},
+        calculator::{RoundDirection, TradeDirection, INITIAL_SWAP_POOL_AMOUNT},
+        concentrated::ConcentratedCurve,
         fees::Fees,
+        multi_token::{self, MultiTokenCurve, MAX_TOKENS},
+        stable::{AmpRamp, StableCurve},
     },
     error::SwapError,
     instruction::{
-        DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Initialize, Swap,
-        SwapInstruction, WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
+        DecreaseLiquidity, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn,
+        IncreaseLiquidity, Initialize, InitializeConcentrated, InitializeMultiToken,
+        MultiTokenDepositAllTokenTypes, MultiTokenWithdrawAllTokenTypes, OpenPosition, RampAmp,
+        RouteSwap, SetStatus, Swap, SwapInstruction, UpdateFees, WithdrawAllTokenTypes,
+        WithdrawSingleTokenTypeExactAmountOut,
     },
-    state::{SwapState, SwapV1, SwapVersion},
+    pyth::PythPrice,
+    state::{
+        ConcentratedPosition, ConcentratedSwapV1, MultiTokenSwapV1, SwapState, SwapStatus, SwapV1,
+        SwapV2, SwapV3, SwapVersion,
+    },
+    twap::PriceAccumulators,
 };
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     msg,
@@ -25,8 +37,9 @@ use solana_program::{
     program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 /// Program state handler.
 pub struct Processor {}
@@ -202,6 +215,89 @@ impl Processor {
         Ok(())
     }
 
+    /// Reads the oracle account of a swap on an oracle curve, returning the
+    /// curve priced at the current oracle price, or returns a stable curve at
+    /// its current amplification while it ramps, or None for other curves
+    fn priced_swap_curve<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        token_swap: &dyn SwapState,
+        account_info_iter: &mut I,
+    ) -> Result<Option<SwapCurve>, ProgramError> {
+        if let Some(amp_ramp) = token_swap.amp_ramp() {
+            let clock = Clock::get()?;
+            if clock.unix_timestamp < amp_ramp.stop_timestamp {
+                return Ok(Some(SwapCurve {
+                    curve_type: CurveType::Stable,
+                    calculator: Box::new(StableCurve {
+                        amp: amp_ramp.amp(clock.unix_timestamp),
+                    }),
+                }));
+            }
+        }
+        let mut oracle_curve = match token_swap.swap_curve().oracle_curve() {
+            Some(oracle_curve) => oracle_curve,
+            None => return Ok(None),
+        };
+        let oracle_info = next_account_info(account_info_iter)?;
+        if Some(oracle_info.key) != token_swap.oracle() {
+            return Err(SwapError::IncorrectOracleAccount.into());
+        }
+        let clock = Clock::get()?;
+        let token_b_price = PythPrice::unpack_account(oracle_info)?.token_b_price(
+            oracle_curve.token_a_decimals,
+            oracle_curve.token_b_decimals,
+            oracle_curve.max_price_age_slots,
+            clock.slot,
+        )?;
+        oracle_curve.token_b_price = Some(token_b_price);
+        Ok(Some(SwapCurve {
+            curve_type: CurveType::Oracle,
+            calculator: Box::new(oracle_curve),
+        }))
+    }
+
+    /// Accumulates the pool prices up to the current time at the reserves
+    /// recorded by the last update, then records the balances the pool will
+    /// hold once the current instruction completes. Swaps of the original
+    /// version have no room for price accumulators, and only constant product
+    /// pools record prices, so other swaps are left untouched.
+    fn update_price_accumulators(
+        swap_info: &AccountInfo,
+        swap_token_a_amount: u64,
+        swap_token_b_amount: u64,
+    ) -> ProgramResult {
+        let mut swap_data = swap_info.data.borrow_mut();
+        match swap_data.split_first_mut() {
+            Some((2, swap_v2_data)) => {
+                let mut token_swap = SwapV2::unpack(swap_v2_data)?;
+                if token_swap.swap_curve.curve_type != CurveType::ConstantProduct {
+                    return Ok(());
+                }
+                let clock = Clock::get()?;
+                token_swap.price_accumulators.update(
+                    clock.unix_timestamp,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                );
+                SwapV2::pack(token_swap, swap_v2_data)?;
+            }
+            Some((5, swap_v3_data)) => {
+                let mut token_swap = SwapV3::unpack(swap_v3_data)?;
+                if token_swap.swap_curve.curve_type != CurveType::ConstantProduct {
+                    return Ok(());
+                }
+                let clock = Clock::get()?;
+                token_swap.price_accumulators.update(
+                    clock.unix_timestamp,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                );
+                SwapV3::pack(token_swap, swap_v3_data)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     /// Processes an [Initialize](enum.Instruction.html).
     pub fn process_initialize(
         program_id: &Pubkey,
@@ -293,6 +389,29 @@ impl Processor {
         fees.validate()?;
         swap_curve.calculator.validate()?;
 
+        let oracle = if swap_curve.curve_type == CurveType::Oracle {
+            // oracle curves only fit in the latest swap version
+            if swap_info.data_len() == SwapVersion::V1_LEN {
+                return Err(SwapError::InvalidCurve.into());
+            }
+            let oracle_info = next_account_info(account_info_iter)?;
+            PythPrice::unpack_account(oracle_info)?;
+            *oracle_info.key
+        } else {
+            Pubkey::default()
+        };
+
+        // only the latest swap version has room for an admin
+        let admin = match next_account_info(account_info_iter) {
+            Ok(admin_info) => {
+                if swap_info.data_len() != SwapVersion::LATEST_LEN {
+                    return Err(SwapError::InvalidAdmin.into());
+                }
+                *admin_info.key
+            }
+            Err(_) => Pubkey::default(),
+        };
+
         let initial_amount = swap_curve.calculator.new_pool_supply();
 
         Self::token_mint_to(
@@ -305,19 +424,65 @@ impl Processor {
             to_u64(initial_amount)?,
         )?;
 
-        let obj = SwapVersion::SwapV1(SwapV1 {
-            is_initialized: true,
-            nonce,
-            token_program_id,
-            token_a: *token_a_info.key,
-            token_b: *token_b_info.key,
-            pool_mint: *pool_mint_info.key,
-            token_a_mint: token_a.mint,
-            token_b_mint: token_b.mint,
-            pool_fee_account: *fee_account_info.key,
-            fees,
-            swap_curve,
-        });
+        // swaps created in accounts of the original size keep the original layout
+        let obj = if swap_info.data_len() == SwapVersion::V1_LEN {
+            SwapVersion::SwapV1(SwapV1 {
+                is_initialized: true,
+                nonce,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+            })
+        } else if swap_info.data_len() == SwapVersion::V2_LEN {
+            SwapVersion::SwapV2(SwapV2 {
+                is_initialized: true,
+                nonce,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+                oracle,
+                price_accumulators: PriceAccumulators::new(
+                    Clock::get()?.unix_timestamp,
+                    token_a.amount,
+                    token_b.amount,
+                ),
+            })
+        } else {
+            SwapVersion::SwapV3(SwapV3 {
+                is_initialized: true,
+                nonce,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+                oracle,
+                price_accumulators: PriceAccumulators::new(
+                    Clock::get()?.unix_timestamp,
+                    token_a.amount,
+                    token_b.amount,
+                ),
+                admin,
+                amp_ramp: AmpRamp::default(),
+                status: SwapStatus::Active,
+            })
+        };
         SwapVersion::pack(obj, &mut swap_info.data.borrow_mut())?;
         Ok(())
     }
@@ -345,6 +510,7 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        token_swap.status().check_trading()?;
 
         if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
         {
@@ -378,6 +544,10 @@ impl Processor {
         if *token_program_info.key != *token_swap.token_program_id() {
             return Err(SwapError::IncorrectTokenProgramId.into());
         }
+        let priced_swap_curve = Self::priced_swap_curve(token_swap.as_ref(), account_info_iter)?;
+        let swap_curve = priced_swap_curve
+            .as_ref()
+            .unwrap_or_else(|| token_swap.swap_curve());
 
         let source_account =
             Self::unpack_token_account(swap_source_info, &token_swap.token_program_id())?;
@@ -390,8 +560,7 @@ impl Processor {
         } else {
             TradeDirection::BtoA
         };
-        let result = token_swap
-            .swap_curve()
+        let result = swap_curve
             .swap(
                 to_u128(amount_in)?,
                 to_u128(source_account.amount)?,
@@ -414,6 +583,11 @@ impl Processor {
                 result.new_swap_source_amount,
             ),
         };
+        Self::update_price_accumulators(
+            swap_info,
+            to_u64(swap_token_a_amount)?,
+            to_u64(swap_token_b_amount)?,
+        )?;
 
         Self::token_transfer(
             swap_info.key,
@@ -425,8 +599,7 @@ impl Processor {
             to_u64(result.source_amount_swapped)?,
         )?;
 
-        let mut pool_token_amount = token_swap
-            .swap_curve()
+        let mut pool_token_amount = swap_curve
             .trading_tokens_to_pool_tokens(
                 result.owner_fee,
                 swap_token_a_amount,
@@ -491,6 +664,183 @@ impl Processor {
         Ok(())
     }
 
+    /// Processes a [RouteSwap](enum.Instruction.html).
+    pub fn process_route_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        // Tokens to swap into the next pool of the route, held first by the
+        // user, then by the swap token account of the previous pool
+        let mut input_info = source_info;
+        let mut input_authority_info = user_transfer_authority_info;
+        let mut input_signer = None;
+        let mut input_mint = None;
+        let mut input_amount = amount_in;
+
+        while let Ok(swap_info) = next_account_info(account_info_iter) {
+            let authority_info = next_account_info(account_info_iter)?;
+            let swap_source_info = next_account_info(account_info_iter)?;
+            let swap_destination_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+            if swap_info.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+            token_swap.status().check_trading()?;
+
+            if *authority_info.key
+                != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+            {
+                return Err(SwapError::InvalidProgramAddress.into());
+            }
+            if !(*swap_source_info.key == *token_swap.token_a_account()
+                || *swap_source_info.key == *token_swap.token_b_account())
+            {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            if !(*swap_destination_info.key == *token_swap.token_a_account()
+                || *swap_destination_info.key == *token_swap.token_b_account())
+            {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            if *swap_source_info.key == *swap_destination_info.key {
+                return Err(SwapError::InvalidInput.into());
+            }
+            if swap_source_info.key == input_info.key {
+                return Err(SwapError::InvalidInput.into());
+            }
+            if *pool_mint_info.key != *token_swap.pool_mint() {
+                return Err(SwapError::IncorrectPoolMint.into());
+            }
+            if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+            if *token_program_info.key != *token_swap.token_program_id() {
+                return Err(SwapError::IncorrectTokenProgramId.into());
+            }
+            let priced_swap_curve =
+                Self::priced_swap_curve(token_swap.as_ref(), account_info_iter)?;
+            let swap_curve = priced_swap_curve
+                .as_ref()
+                .unwrap_or_else(|| token_swap.swap_curve());
+
+            let source_account =
+                Self::unpack_token_account(swap_source_info, token_swap.token_program_id())?;
+            let dest_account =
+                Self::unpack_token_account(swap_destination_info, token_swap.token_program_id())?;
+            let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+            if let Some(input_mint) = input_mint {
+                if source_account.mint != input_mint {
+                    return Err(SwapError::InvalidRoute.into());
+                }
+            }
+
+            let trade_direction = if *swap_source_info.key == *token_swap.token_a_account() {
+                TradeDirection::AtoB
+            } else {
+                TradeDirection::BtoA
+            };
+            let result = swap_curve
+                .swap(
+                    to_u128(input_amount)?,
+                    to_u128(source_account.amount)?,
+                    to_u128(dest_account.amount)?,
+                    trade_direction,
+                    token_swap.fees(),
+                )
+                .ok_or(SwapError::ZeroTradingTokens)?;
+
+            let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+                TradeDirection::AtoB => (
+                    result.new_swap_source_amount,
+                    result.new_swap_destination_amount,
+                ),
+                TradeDirection::BtoA => (
+                    result.new_swap_destination_amount,
+                    result.new_swap_source_amount,
+                ),
+            };
+            Self::update_price_accumulators(
+                swap_info,
+                to_u64(swap_token_a_amount)?,
+                to_u64(swap_token_b_amount)?,
+            )?;
+
+            // Any intermediate tokens the curve does not take, from rounding,
+            // stay in the previous pool
+            let (signer_swap_key, signer_nonce) =
+                input_signer.unwrap_or((swap_info.key, token_swap.nonce()));
+            Self::token_transfer(
+                signer_swap_key,
+                token_program_info.clone(),
+                input_info.clone(),
+                swap_source_info.clone(),
+                input_authority_info.clone(),
+                signer_nonce,
+                to_u64(result.source_amount_swapped)?,
+            )?;
+
+            let pool_token_amount = swap_curve
+                .trading_tokens_to_pool_tokens(
+                    result.owner_fee,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    to_u128(pool_mint.supply)?,
+                    trade_direction,
+                    RoundDirection::Ceiling,
+                    token_swap.fees(),
+                )
+                .ok_or(SwapError::FeeCalculationFailure)?;
+            if pool_token_amount > 0 {
+                Self::token_mint_to(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    pool_mint_info.clone(),
+                    pool_fee_account_info.clone(),
+                    authority_info.clone(),
+                    token_swap.nonce(),
+                    to_u64(pool_token_amount)?,
+                )?;
+            }
+
+            input_info = swap_destination_info;
+            input_authority_info = authority_info;
+            input_signer = Some((swap_info.key, token_swap.nonce()));
+            input_mint = Some(dest_account.mint);
+            input_amount = to_u64(result.destination_amount_swapped)?;
+        }
+
+        let (signer_swap_key, signer_nonce) = input_signer.ok_or(SwapError::InvalidRoute)?;
+        if input_amount < minimum_amount_out {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if input_info.key == destination_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+
+        Self::token_transfer(
+            signer_swap_key,
+            token_program_info.clone(),
+            input_info.clone(),
+            destination_info.clone(),
+            input_authority_info.clone(),
+            signer_nonce,
+            input_amount,
+        )?;
+
+        Ok(())
+    }
+
     /// Processes an [DepositAllTokenTypes](enum.Instruction.html).
     pub fn process_deposit_all_token_types(
         program_id: &Pubkey,
@@ -512,6 +862,7 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        token_swap.status().check_trading()?;
         let calculator = &token_swap.swap_curve().calculator;
         if !calculator.allows_deposits() {
             return Err(SwapError::UnsupportedCurveOperation.into());
@@ -563,6 +914,17 @@ impl Processor {
         if token_b_amount == 0 {
             return Err(SwapError::ZeroTradingTokens.into());
         }
+        Self::update_price_accumulators(
+            swap_info,
+            token_a
+                .amount
+                .checked_add(token_a_amount)
+                .ok_or(SwapError::CalculationFailure)?,
+            token_b
+                .amount
+                .checked_add(token_b_amount)
+                .ok_or(SwapError::CalculationFailure)?,
+        )?;
 
         let pool_token_amount = to_u64(pool_token_amount)?;
 
@@ -619,6 +981,7 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        token_swap.status().check_withdrawing()?;
         Self::check_accounts(
             token_swap.as_ref(),
             program_id,
@@ -677,6 +1040,17 @@ impl Processor {
         if token_b_amount == 0 && token_b.amount != 0 {
             return Err(SwapError::ZeroTradingTokens.into());
         }
+        Self::update_price_accumulators(
+            swap_info,
+            token_a
+                .amount
+                .checked_sub(token_a_amount)
+                .ok_or(SwapError::CalculationFailure)?,
+            token_b
+                .amount
+                .checked_sub(token_b_amount)
+                .ok_or(SwapError::CalculationFailure)?,
+        )?;
 
         if withdraw_fee > 0 {
             Self::token_transfer(
@@ -743,6 +1117,7 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        token_swap.status().check_trading()?;
         let source_account =
             Self::unpack_token_account(source_info, &token_swap.token_program_id())?;
         let swap_token_a =
@@ -776,12 +1151,15 @@ impl Processor {
             source_b_info,
             None,
         )?;
+        let priced_swap_curve = Self::priced_swap_curve(token_swap.as_ref(), account_info_iter)?;
+        let swap_curve = priced_swap_curve
+            .as_ref()
+            .unwrap_or_else(|| token_swap.swap_curve());
 
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
         let pool_mint_supply = to_u128(pool_mint.supply)?;
         let pool_token_amount = if pool_mint_supply > 0 {
-            token_swap
-                .swap_curve()
+            swap_curve
                 .trading_tokens_to_pool_tokens(
                     to_u128(source_token_amount)?,
                     to_u128(swap_token_a.amount)?,
@@ -793,7 +1171,7 @@ impl Processor {
                 )
                 .ok_or(SwapError::ZeroTradingTokens)?
         } else {
-            token_swap.swap_curve().calculator.new_pool_supply()
+            swap_curve.calculator.new_pool_supply()
         };
 
         let pool_token_amount = to_u64(pool_token_amount)?;
@@ -803,6 +1181,23 @@ impl Processor {
         if pool_token_amount == 0 {
             return Err(SwapError::ZeroTradingTokens.into());
         }
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_token_a
+                    .amount
+                    .checked_add(source_token_amount)
+                    .ok_or(SwapError::CalculationFailure)?,
+                swap_token_b.amount,
+            ),
+            TradeDirection::BtoA => (
+                swap_token_a.amount,
+                swap_token_b
+                    .amount
+                    .checked_add(source_token_amount)
+                    .ok_or(SwapError::CalculationFailure)?,
+            ),
+        };
+        Self::update_price_accumulators(swap_info, swap_token_a_amount, swap_token_b_amount)?;
 
         match trade_direction {
             TradeDirection::AtoB => {
@@ -861,6 +1256,7 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        token_swap.status().check_withdrawing()?;
         let destination_account =
             Self::unpack_token_account(destination_info, &token_swap.token_program_id())?;
         let swap_token_a =
@@ -893,6 +1289,10 @@ impl Processor {
             destination_b_info,
             Some(pool_fee_account_info),
         )?;
+        let priced_swap_curve = Self::priced_swap_curve(token_swap.as_ref(), account_info_iter)?;
+        let swap_curve = priced_swap_curve
+            .as_ref()
+            .unwrap_or_else(|| token_swap.swap_curve());
 
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
         let pool_mint_supply = to_u128(pool_mint.supply)?;
@@ -917,8 +1317,7 @@ impl Processor {
             ),
         };
 
-        let burn_pool_token_amount = token_swap
-            .swap_curve()
+        let burn_pool_token_amount = swap_curve
             .trading_tokens_to_pool_tokens(
                 to_u128(destination_token_amount)?,
                 swap_token_a_amount,
@@ -949,6 +1348,11 @@ impl Processor {
         if pool_token_amount == 0 {
             return Err(SwapError::ZeroTradingTokens.into());
         }
+        Self::update_price_accumulators(
+            swap_info,
+            to_u64(swap_token_a_amount)?,
+            to_u64(swap_token_b_amount)?,
+        )?;
 
         if withdraw_fee > 0 {
             Self::token_transfer(
@@ -999,270 +1403,3293 @@ impl Processor {
         Ok(())
     }
 
-    /// Processes an [Instruction](enum.Instruction.html).
-    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
-        Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
+    /// Unpacks a multi-token swap, after checking that the program owns it
+    fn unpack_multi_token_swap(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+    ) -> Result<MultiTokenSwapV1, ProgramError> {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        SwapVersion::unpack_multi_token(&swap_info.data.borrow())
     }
 
-    /// Processes an instruction given extra constraint
-    pub fn process_with_constraints(
+    fn check_multi_token_accounts(
+        token_swap: &MultiTokenSwapV1,
         program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        input: &[u8],
-        swap_constraints: &Option<SwapConstraints>,
+        swap_account_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+        pool_fee_account_info: Option<&AccountInfo>,
     ) -> ProgramResult {
-        let instruction = SwapInstruction::unpack(input)?;
-        match instruction {
-            SwapInstruction::Initialize(Initialize {
-                nonce,
-                fees,
-                swap_curve,
-            }) => {
-                msg!("Instruction: Init");
-                Self::process_initialize(
-                    program_id,
-                    nonce,
-                    fees,
-                    swap_curve,
-                    accounts,
-                    swap_constraints,
-                )
-            }
-            SwapInstruction::Swap(Swap {
-                amount_in,
-                minimum_amount_out,
-            }) => {
-                msg!("Instruction: Swap");
-                Self::process_swap(program_id, amount_in, minimum_amount_out, accounts)
-            }
-            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
-                pool_token_amount,
-                maximum_token_a_amount,
-                maximum_token_b_amount,
-            }) => {
-                msg!("Instruction: DepositAllTokenTypes");
-                Self::process_deposit_all_token_types(
-                    program_id,
-                    pool_token_amount,
-                    maximum_token_a_amount,
-                    maximum_token_b_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
-                pool_token_amount,
-                minimum_token_a_amount,
-                minimum_token_b_amount,
-            }) => {
-                msg!("Instruction: WithdrawAllTokenTypes");
-                Self::process_withdraw_all_token_types(
-                    program_id,
-                    pool_token_amount,
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
-                DepositSingleTokenTypeExactAmountIn {
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                },
-            ) => {
-                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
-                Self::process_deposit_single_token_type_exact_amount_in(
-                    program_id,
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
-                WithdrawSingleTokenTypeExactAmountOut {
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                },
-            ) => {
-                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
-                Self::process_withdraw_single_token_type_exact_amount_out(
-                    program_id,
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                    accounts,
-                )
+        if *authority_info.key
+            != Self::authority_id(program_id, swap_account_info.key, token_swap.nonce)?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *pool_mint_info.key != token_swap.pool_mint {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if *token_program_info.key != token_swap.token_program_id {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        if let Some(pool_fee_account_info) = pool_fee_account_info {
+            if *pool_fee_account_info.key != token_swap.pool_fee_account {
+                return Err(SwapError::IncorrectFeeAccount.into());
             }
         }
+        Ok(())
     }
-}
 
-impl PrintProgramError for SwapError {
-    fn print<E>(&self)
-    where
-        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
-    {
-        match self {
-            SwapError::AlreadyInUse => msg!("Error: Swap account already in use"),
-            SwapError::InvalidProgramAddress => {
-                msg!("Error: Invalid program address generated from nonce and key")
+    /// Reads the token accounts of a multi-token swap, in the pool's order,
+    /// returning them along with their balances
+    fn next_multi_token_accounts<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        token_swap: &MultiTokenSwapV1,
+        account_info_iter: &mut I,
+    ) -> Result<(Vec<&'a AccountInfo<'b>>, Vec<u128>), ProgramError> {
+        let mut swap_token_infos = Vec::with_capacity(token_swap.token_count as usize);
+        let mut balances = Vec::with_capacity(token_swap.token_count as usize);
+        for token_account in token_swap.token_accounts() {
+            let swap_token_info = next_account_info(account_info_iter)?;
+            if swap_token_info.key != token_account {
+                return Err(SwapError::IncorrectSwapAccount.into());
             }
-            SwapError::InvalidOwner => {
-                msg!("Error: The input account owner is not the program address")
-            }
-            SwapError::InvalidOutputOwner => {
-                msg!("Error: Output pool account owner cannot be the program address")
-            }
-            SwapError::ExpectedMint => msg!("Error: Deserialized account is not an SPL Token mint"),
-            SwapError::ExpectedAccount => {
-                msg!("Error: Deserialized account is not an SPL Token account")
-            }
-            SwapError::EmptySupply => msg!("Error: Input token account empty"),
-            SwapError::InvalidSupply => msg!("Error: Pool token mint has a non-zero supply"),
-            SwapError::RepeatedMint => msg!("Error: Swap input token accounts have the same mint"),
-            SwapError::InvalidDelegate => msg!("Error: Token account has a delegate"),
-            SwapError::InvalidInput => msg!("Error: InvalidInput"),
-            SwapError::IncorrectSwapAccount => {
-                msg!("Error: Address of the provided swap token account is incorrect")
-            }
-            SwapError::IncorrectPoolMint => {
-                msg!("Error: Address of the provided pool token mint is incorrect")
-            }
-            SwapError::InvalidOutput => msg!("Error: InvalidOutput"),
-            SwapError::CalculationFailure => msg!("Error: CalculationFailure"),
-            SwapError::InvalidInstruction => msg!("Error: InvalidInstruction"),
-            SwapError::ExceededSlippage => {
-                msg!("Error: Swap instruction exceeds desired slippage limit")
-            }
-            SwapError::InvalidCloseAuthority => msg!("Error: Token account has a close authority"),
-            SwapError::InvalidFreezeAuthority => {
-                msg!("Error: Pool token mint has a freeze authority")
-            }
-            SwapError::IncorrectFeeAccount => msg!("Error: Pool fee token account incorrect"),
-            SwapError::ZeroTradingTokens => {
-                msg!("Error: Given pool token amount results in zero trading tokens")
-            }
-            SwapError::FeeCalculationFailure => msg!(
-                "Error: The fee calculation failed due to overflow, underflow, or unexpected 0"
-            ),
-            SwapError::ConversionFailure => msg!("Error: Conversion to or from u64 failed."),
-            SwapError::InvalidFee => {
-                msg!("Error: The provided fee does not match the program owner's constraints")
+            let swap_token =
+                Self::unpack_token_account(swap_token_info, &token_swap.token_program_id)?;
+            balances.push(to_u128(swap_token.amount)?);
+            swap_token_infos.push(swap_token_info);
+        }
+        Ok((swap_token_infos, balances))
+    }
+
+    /// Finds the position of a user's token account in a multi-token swap,
+    /// from its mint
+    fn multi_token_index(
+        token_swap: &MultiTokenSwapV1,
+        user_token_info: &AccountInfo,
+    ) -> Result<usize, ProgramError> {
+        if token_swap.token_accounts().contains(user_token_info.key) {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let user_token = Self::unpack_token_account(user_token_info, &token_swap.token_program_id)?;
+        token_swap
+            .token_mints()
+            .iter()
+            .position(|mint| *mint == user_token.mint)
+            .ok_or_else(|| SwapError::IncorrectSwapAccount.into())
+    }
+
+    /// Processes an [InitializeMultiToken](enum.Instruction.html).
+    pub fn process_initialize_multi_token(
+        program_id: &Pubkey,
+        nonce: u8,
+        fees: Fees,
+        curve: MultiTokenCurve,
+        admin: Pubkey,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let token_infos = account_info_iter.as_slice();
+
+        let token_program_id = *token_program_info.key;
+        if SwapVersion::is_initialized(&swap_info.data.borrow()) {
+            return Err(SwapError::AlreadyInUse.into());
+        }
+
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, nonce)? {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        curve.validate(token_infos.len())?;
+        let fee_account = Self::unpack_token_account(fee_account_info, &token_program_id)?;
+        let destination = Self::unpack_token_account(destination_info, &token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_program_id)?;
+        if *authority_info.key == destination.owner {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        if *authority_info.key == fee_account.owner {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        if COption::Some(*authority_info.key) != pool_mint.mint_authority {
+            return Err(SwapError::InvalidOwner.into());
+        }
+
+        let mut token_accounts = [Pubkey::default(); MAX_TOKENS];
+        let mut token_mints = [Pubkey::default(); MAX_TOKENS];
+        for (index, token_info) in token_infos.iter().enumerate() {
+            let token = Self::unpack_token_account(token_info, &token_program_id)?;
+            if *authority_info.key != token.owner {
+                return Err(SwapError::InvalidOwner.into());
             }
-            SwapError::IncorrectTokenProgramId => {
-                msg!("Error: The provided token program does not match the token program expected by the swap")
+            if token_mints[..index].contains(&token.mint) {
+                return Err(SwapError::RepeatedMint.into());
             }
-            SwapError::UnsupportedCurveType => {
-                msg!("Error: The provided curve type is not supported by the program owner")
+            if token.amount == 0 {
+                return Err(SwapError::EmptySupply.into());
             }
-            SwapError::InvalidCurve => {
-                msg!("Error: The provided curve parameters are invalid")
+            if token.delegate.is_some() {
+                return Err(SwapError::InvalidDelegate.into());
             }
-            SwapError::UnsupportedCurveOperation => {
-                msg!("Error: The operation cannot be performed on the given curve")
+            if token.close_authority.is_some() {
+                return Err(SwapError::InvalidCloseAuthority.into());
             }
+            token_accounts[index] = *token_info.key;
+            token_mints[index] = token.mint;
         }
-    }
-}
-
-fn to_u128(val: u64) -> Result<u128, SwapError> {
-    val.try_into().map_err(|_| SwapError::ConversionFailure)
-}
-
-fn to_u64(val: u128) -> Result<u64, SwapError> {
-    val.try_into().map_err(|_| SwapError::ConversionFailure)
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{
-        curve::calculator::{CurveCalculator, INITIAL_SWAP_POOL_AMOUNT},
-        curve::{
-            base::CurveType, constant_price::ConstantPriceCurve,
-            constant_product::ConstantProductCurve, offset::OffsetCurve,
-        },
-        instruction::{
-            deposit_all_token_types, deposit_single_token_type_exact_amount_in, initialize, swap,
-            withdraw_all_token_types, withdraw_single_token_type_exact_amount_out,
-        },
-    };
-    use solana_program::{instruction::Instruction, program_stubs, rent::Rent};
-    use solana_sdk::account::{create_account_for_test, create_is_signer_account_infos, Account};
-    use spl_token::{
-        error::TokenError,
-        instruction::{
-            approve, initialize_account, initialize_mint, mint_to, revoke, set_authority,
-            AuthorityType,
-        },
-    };
+        if pool_mint.supply != 0 {
+            return Err(SwapError::InvalidSupply.into());
+        }
+        if pool_mint.freeze_authority.is_some() {
+            return Err(SwapError::InvalidFreezeAuthority.into());
+        }
+        if *pool_mint_info.key != fee_account.mint {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
 
-    // Test program id for the swap program.
-    const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
-    // Test program id for the token program.
-    const TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if fee_account.owner != owner_key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            swap_constraints.validate_multi_token_curve(&curve)?;
+            swap_constraints.validate_fees(&fees)?;
+        }
+        fees.validate()?;
 
-    struct TestSyscallStubs {}
-    impl program_stubs::SyscallStubs for TestSyscallStubs {
-        fn sol_invoke_signed(
-            &self,
-            instruction: &Instruction,
-            account_infos: &[AccountInfo],
-            signers_seeds: &[&[&[u8]]],
-        ) -> ProgramResult {
-            msg!("TestSyscallStubs::sol_invoke_signed()");
+        Self::token_mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            nonce,
+            to_u64(INITIAL_SWAP_POOL_AMOUNT)?,
+        )?;
 
-            let mut new_account_infos = vec![];
+        let obj = MultiTokenSwapV1 {
+            is_initialized: true,
+            nonce,
+            token_program_id,
+            pool_mint: *pool_mint_info.key,
+            pool_fee_account: *fee_account_info.key,
+            fees,
+            curve,
+            token_count: token_infos.len() as u8,
+            token_accounts,
+            token_mints,
+            admin,
+            status: SwapStatus::Active,
+        };
+        SwapVersion::pack_multi_token(obj, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
 
-            // mimic check for token program in accounts
-            if !account_infos.iter().any(|x| *x.key == TOKEN_PROGRAM_ID) {
-                return Err(ProgramError::InvalidAccountData);
-            }
+    /// Processes a [MultiTokenSwap](enum.Instruction.html).
+    pub fn process_multi_token_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-            for meta in instruction.accounts.iter() {
-                for account_info in account_infos.iter() {
-                    if meta.pubkey == *account_info.key {
-                        let mut new_account_info = account_info.clone();
-                        for seeds in signers_seeds.iter() {
-                            let signer =
-                                Pubkey::create_program_address(&seeds, &SWAP_PROGRAM_ID).unwrap();
-                            if *account_info.key == signer {
-                                new_account_info.is_signer = true;
-                            }
-                        }
-                        new_account_infos.push(new_account_info);
-                    }
-                }
-            }
+        let token_swap = Self::unpack_multi_token_swap(program_id, swap_info)?;
+        token_swap.status.check_trading()?;
+        Self::check_multi_token_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+            Some(pool_fee_account_info),
+        )?;
+        let (swap_token_infos, balances) =
+            Self::next_multi_token_accounts(&token_swap, account_info_iter)?;
+        let source_index = Self::multi_token_index(&token_swap, source_info)?;
+        let destination_index = Self::multi_token_index(&token_swap, destination_info)?;
+        if source_index == destination_index {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id)?;
 
-            spl_token::processor::Processor::process(
-                &instruction.program_id,
-                &new_account_infos,
-                &instruction.data,
+        let result = token_swap
+            .curve
+            .swap(
+                source_index,
+                destination_index,
+                to_u128(amount_in)?,
+                &balances,
+                &token_swap.fees,
             )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        if result.destination_amount_swapped < to_u128(minimum_amount_out)? {
+            return Err(SwapError::ExceededSlippage.into());
         }
-    }
 
-    fn test_syscall_stubs() {
-        use std::sync::Once;
-        static ONCE: Once = Once::new();
+        Self::token_transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_token_infos[source_index].clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce,
+            to_u64(result.source_amount_swapped)?,
+        )?;
 
-        ONCE.call_once(|| {
-            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
-        });
-    }
+        let mut new_balances = balances;
+        new_balances[source_index] = result.new_swap_source_amount;
+        new_balances[destination_index] = result.new_swap_destination_amount;
+        let mut pool_token_amount = token_swap
+            .curve
+            .deposit_single_token_type(
+                source_index,
+                result.owner_fee,
+                &new_balances,
+                to_u128(pool_mint.supply)?,
+                &token_swap.fees,
+            )
+            .ok_or(SwapError::FeeCalculationFailure)?;
 
-    struct SwapAccountInfo {
-        nonce: u8,
-        authority_key: Pubkey,
-        fees: Fees,
-        swap_curve: SwapCurve,
-        swap_key: Pubkey,
-        swap_account: Account,
-        pool_mint_key: Pubkey,
-        pool_mint_account: Account,
-        pool_fee_key: Pubkey,
-        pool_fee_account: Account,
+        if pool_token_amount > 0 {
+            // Allow error to fall through
+            if let Ok(host_fee_account_info) = next_account_info(account_info_iter) {
+                let host_fee_account = Self::unpack_token_account(
+                    host_fee_account_info,
+                    &token_swap.token_program_id,
+                )?;
+                if *pool_mint_info.key != host_fee_account.mint {
+                    return Err(SwapError::IncorrectPoolMint.into());
+                }
+                let host_fee = token_swap
+                    .fees
+                    .host_fee(pool_token_amount)
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                if host_fee > 0 {
+                    pool_token_amount = pool_token_amount
+                        .checked_sub(host_fee)
+                        .ok_or(SwapError::FeeCalculationFailure)?;
+                    Self::token_mint_to(
+                        swap_info.key,
+                        token_program_info.clone(),
+                        pool_mint_info.clone(),
+                        host_fee_account_info.clone(),
+                        authority_info.clone(),
+                        token_swap.nonce,
+                        to_u64(host_fee)?,
+                    )?;
+                }
+            }
+            Self::token_mint_to(
+                swap_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                pool_fee_account_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce,
+                to_u64(pool_token_amount)?,
+            )?;
+        }
+
+        Self::token_transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_token_infos[destination_index].clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce,
+            to_u64(result.destination_amount_swapped)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Processes a [MultiTokenDepositAllTokenTypes](enum.Instruction.html).
+    pub fn process_multi_token_deposit_all_token_types(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        maximum_token_amounts: &[u64],
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let dest_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = Self::unpack_multi_token_swap(program_id, swap_info)?;
+        token_swap.status.check_trading()?;
+        Self::check_multi_token_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+            None,
+        )?;
+        if maximum_token_amounts.len() != token_swap.token_count as usize {
+            return Err(SwapError::InvalidInput.into());
+        }
+
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id)?;
+        let current_pool_mint_supply = to_u128(pool_mint.supply)?;
+        let (pool_token_amount, pool_mint_supply) = if current_pool_mint_supply > 0 {
+            (to_u128(pool_token_amount)?, current_pool_mint_supply)
+        } else {
+            (INITIAL_SWAP_POOL_AMOUNT, INITIAL_SWAP_POOL_AMOUNT)
+        };
+
+        let mut transfers = Vec::with_capacity(maximum_token_amounts.len());
+        for (token_account, maximum_token_amount) in token_swap
+            .token_accounts()
+            .iter()
+            .zip(maximum_token_amounts.iter())
+        {
+            let source_info = next_account_info(account_info_iter)?;
+            let token_info = next_account_info(account_info_iter)?;
+            if token_info.key != token_account {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            if token_info.key == source_info.key {
+                return Err(SwapError::InvalidInput.into());
+            }
+            let token = Self::unpack_token_account(token_info, &token_swap.token_program_id)?;
+            let token_amount = to_u64(
+                multi_token::pool_tokens_to_trading_tokens(
+                    pool_token_amount,
+                    pool_mint_supply,
+                    to_u128(token.amount)?,
+                    RoundDirection::Ceiling,
+                )
+                .ok_or(SwapError::ZeroTradingTokens)?,
+            )?;
+            if token_amount > *maximum_token_amount {
+                return Err(SwapError::ExceededSlippage.into());
+            }
+            if token_amount == 0 {
+                return Err(SwapError::ZeroTradingTokens.into());
+            }
+            transfers.push((source_info, token_info, token_amount));
+        }
+
+        for (source_info, token_info, token_amount) in transfers {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_info.clone(),
+                token_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce,
+                token_amount,
+            )?;
+        }
+        Self::token_mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce,
+            to_u64(pool_token_amount)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Processes a [MultiTokenWithdrawAllTokenTypes](enum.Instruction.html).
+    pub fn process_multi_token_withdraw_all_token_types(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        minimum_token_amounts: &[u64],
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = Self::unpack_multi_token_swap(program_id, swap_info)?;
+        token_swap.status.check_withdrawing()?;
+        Self::check_multi_token_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+            Some(pool_fee_account_info),
+        )?;
+        if minimum_token_amounts.len() != token_swap.token_count as usize {
+            return Err(SwapError::InvalidInput.into());
+        }
+
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id)?;
+        let withdraw_fee: u128 = if *pool_fee_account_info.key == *source_info.key {
+            // withdrawing from the fee account, don't assess withdraw fee
+            0
+        } else {
+            token_swap
+                .fees
+                .owner_withdraw_fee(to_u128(pool_token_amount)?)
+                .ok_or(SwapError::FeeCalculationFailure)?
+        };
+        let pool_token_amount = to_u128(pool_token_amount)?
+            .checked_sub(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        let mut transfers = Vec::with_capacity(minimum_token_amounts.len());
+        for (token_account, minimum_token_amount) in token_swap
+            .token_accounts()
+            .iter()
+            .zip(minimum_token_amounts.iter())
+        {
+            let token_info = next_account_info(account_info_iter)?;
+            let dest_token_info = next_account_info(account_info_iter)?;
+            if token_info.key != token_account {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            if token_info.key == dest_token_info.key {
+                return Err(SwapError::InvalidInput.into());
+            }
+            let token = Self::unpack_token_account(token_info, &token_swap.token_program_id)?;
+            let token_amount = to_u64(
+                multi_token::pool_tokens_to_trading_tokens(
+                    pool_token_amount,
+                    to_u128(pool_mint.supply)?,
+                    to_u128(token.amount)?,
+                    RoundDirection::Floor,
+                )
+                .ok_or(SwapError::ZeroTradingTokens)?,
+            )?;
+            let token_amount = std::cmp::min(token.amount, token_amount);
+            if token_amount < *minimum_token_amount {
+                return Err(SwapError::ExceededSlippage.into());
+            }
+            if token_amount == 0 && token.amount != 0 {
+                return Err(SwapError::ZeroTradingTokens.into());
+            }
+            transfers.push((token_info, dest_token_info, token_amount));
+        }
+
+        if withdraw_fee > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_info.clone(),
+                pool_fee_account_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce,
+                to_u64(withdraw_fee)?,
+            )?;
+        }
+        Self::token_burn(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce,
+            to_u64(pool_token_amount)?,
+        )?;
+
+        for (token_info, dest_token_info, token_amount) in transfers {
+            if token_amount > 0 {
+                Self::token_transfer(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    token_info.clone(),
+                    dest_token_info.clone(),
+                    authority_info.clone(),
+                    token_swap.nonce,
+                    token_amount,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Processes a [MultiTokenDepositSingleTokenTypeExactAmountIn](enum.Instruction.html).
+    pub fn process_multi_token_deposit_single_token_type_exact_amount_in(
+        program_id: &Pubkey,
+        source_token_amount: u64,
+        minimum_pool_token_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = Self::unpack_multi_token_swap(program_id, swap_info)?;
+        token_swap.status.check_trading()?;
+        Self::check_multi_token_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+            None,
+        )?;
+        let (swap_token_infos, balances) =
+            Self::next_multi_token_accounts(&token_swap, account_info_iter)?;
+        let token_index = Self::multi_token_index(&token_swap, source_info)?;
+
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id)?;
+        let pool_mint_supply = to_u128(pool_mint.supply)?;
+        let pool_token_amount = if pool_mint_supply > 0 {
+            token_swap
+                .curve
+                .deposit_single_token_type(
+                    token_index,
+                    to_u128(source_token_amount)?,
+                    &balances,
+                    pool_mint_supply,
+                    &token_swap.fees,
+                )
+                .ok_or(SwapError::ZeroTradingTokens)?
+        } else {
+            INITIAL_SWAP_POOL_AMOUNT
+        };
+
+        let pool_token_amount = to_u64(pool_token_amount)?;
+        if pool_token_amount < minimum_pool_token_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens.into());
+        }
+
+        Self::token_transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_token_infos[token_index].clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce,
+            source_token_amount,
+        )?;
+        Self::token_mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce,
+            pool_token_amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Processes a [MultiTokenWithdrawSingleTokenTypeExactAmountOut](enum.Instruction.html).
+    pub fn process_multi_token_withdraw_single_token_type_exact_amount_out(
+        program_id: &Pubkey,
+        destination_token_amount: u64,
+        maximum_pool_token_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = Self::unpack_multi_token_swap(program_id, swap_info)?;
+        token_swap.status.check_withdrawing()?;
+        Self::check_multi_token_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+            Some(pool_fee_account_info),
+        )?;
+        let (swap_token_infos, balances) =
+            Self::next_multi_token_accounts(&token_swap, account_info_iter)?;
+        let token_index = Self::multi_token_index(&token_swap, destination_info)?;
+
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id)?;
+        let burn_pool_token_amount = token_swap
+            .curve
+            .withdraw_single_token_type_exact_out(
+                token_index,
+                to_u128(destination_token_amount)?,
+                &balances,
+                to_u128(pool_mint.supply)?,
+                &token_swap.fees,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        let withdraw_fee: u128 = if *pool_fee_account_info.key == *source_info.key {
+            // withdrawing from the fee account, don't assess withdraw fee
+            0
+        } else {
+            token_swap
+                .fees
+                .owner_withdraw_fee(burn_pool_token_amount)
+                .ok_or(SwapError::FeeCalculationFailure)?
+        };
+        let pool_token_amount = burn_pool_token_amount
+            .checked_add(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        if to_u64(pool_token_amount)? > maximum_pool_token_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens.into());
+        }
+
+        if withdraw_fee > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_info.clone(),
+                pool_fee_account_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce,
+                to_u64(withdraw_fee)?,
+            )?;
+        }
+        Self::token_burn(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce,
+            to_u64(burn_pool_token_amount)?,
+        )?;
+        Self::token_transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_token_infos[token_index].clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce,
+            destination_token_amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Unpacks a concentrated liquidity swap, after checking that the program
+    /// owns it
+    fn unpack_concentrated_swap(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+    ) -> Result<ConcentratedSwapV1, ProgramError> {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        SwapVersion::unpack_concentrated(&swap_info.data.borrow())
+    }
+
+    fn check_concentrated_accounts(
+        token_swap: &ConcentratedSwapV1,
+        program_id: &Pubkey,
+        swap_account_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        token_a_info: &AccountInfo,
+        token_b_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> ProgramResult {
+        if *authority_info.key
+            != Self::authority_id(program_id, swap_account_info.key, token_swap.nonce)?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *token_a_info.key != token_swap.token_a {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *token_b_info.key != token_swap.token_b {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *token_program_info.key != token_swap.token_program_id {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        Ok(())
+    }
+
+    /// Unpacks a position, after checking that it belongs to the swap and
+    /// that its owner signed
+    fn unpack_position(
+        program_id: &Pubkey,
+        swap_key: &Pubkey,
+        position_info: &AccountInfo,
+        owner_info: &AccountInfo,
+    ) -> Result<ConcentratedPosition, ProgramError> {
+        if position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let position = ConcentratedPosition::unpack(&position_info.data.borrow())?;
+        if position.swap != *swap_key {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if position.owner != *owner_info.key {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(position)
+    }
+
+    /// Applies a liquidity change to a position and the ticks of its range,
+    /// recording the fees the position earned before the change
+    fn modify_position(
+        curve: &mut ConcentratedCurve,
+        position: &mut ConcentratedPosition,
+        liquidity_delta: i128,
+    ) -> ProgramResult {
+        curve.update_ticks(position.tick_lower, position.tick_upper, liquidity_delta)?;
+        let (fee_growth_inside_a, fee_growth_inside_b) = curve
+            .fee_growth_inside(position.tick_lower, position.tick_upper)
+            .ok_or(SwapError::CalculationFailure)?;
+        position.update(liquidity_delta, fee_growth_inside_a, fee_growth_inside_b)?;
+        curve.validate_position_liquidity(position.liquidity)?;
+        curve.clear_ticks(position.tick_lower, position.tick_upper);
+        Ok(())
+    }
+
+    /// Processes an [InitializeConcentrated](enum.Instruction.html).
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_initialize_concentrated(
+        program_id: &Pubkey,
+        nonce: u8,
+        fees: Fees,
+        tick_spacing: u16,
+        sqrt_price: u128,
+        min_position_liquidity: u128,
+        max_ticks: u16,
+        admin: Pubkey,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let fee_account_a_info = next_account_info(account_info_iter)?;
+        let fee_account_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_program_id = *token_program_info.key;
+        if SwapVersion::is_initialized(&swap_info.data.borrow()) {
+            return Err(SwapError::AlreadyInUse.into());
+        }
+
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, nonce)? {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let token_a = Self::unpack_token_account(token_a_info, &token_program_id)?;
+        let token_b = Self::unpack_token_account(token_b_info, &token_program_id)?;
+        let fee_account_a = Self::unpack_token_account(fee_account_a_info, &token_program_id)?;
+        let fee_account_b = Self::unpack_token_account(fee_account_b_info, &token_program_id)?;
+        if *authority_info.key != token_a.owner {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        if *authority_info.key != token_b.owner {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        if *authority_info.key == fee_account_a.owner {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        if *authority_info.key == fee_account_b.owner {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+
+        if token_a.mint == token_b.mint {
+            return Err(SwapError::RepeatedMint.into());
+        }
+        if token_a.delegate.is_some() {
+            return Err(SwapError::InvalidDelegate.into());
+        }
+        if token_b.delegate.is_some() {
+            return Err(SwapError::InvalidDelegate.into());
+        }
+        if token_a.close_authority.is_some() {
+            return Err(SwapError::InvalidCloseAuthority.into());
+        }
+        if token_b.close_authority.is_some() {
+            return Err(SwapError::InvalidCloseAuthority.into());
+        }
+        if fee_account_a.mint != token_a.mint {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        if fee_account_b.mint != token_b.mint {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if fee_account_a.owner != owner_key || fee_account_b.owner != owner_key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            swap_constraints.validate_fees(&fees)?;
+        }
+        fees.validate()?;
+        let curve =
+            ConcentratedCurve::new(tick_spacing, sqrt_price, min_position_liquidity, max_ticks)?;
+
+        let obj = ConcentratedSwapV1 {
+            is_initialized: true,
+            nonce,
+            token_program_id,
+            token_a: *token_a_info.key,
+            token_b: *token_b_info.key,
+            token_a_mint: token_a.mint,
+            token_b_mint: token_b.mint,
+            fee_account_a: *fee_account_a_info.key,
+            fee_account_b: *fee_account_b_info.key,
+            fees,
+            curve,
+            admin,
+            status: SwapStatus::Active,
+        };
+        SwapVersion::pack_concentrated(obj, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes an [OpenPosition](enum.Instruction.html).
+    pub fn process_open_position(
+        program_id: &Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let token_swap = Self::unpack_concentrated_swap(program_id, swap_info)?;
+        token_swap
+            .curve
+            .validate_tick_range(tick_lower, tick_upper)?;
+        if position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let position = ConcentratedPosition::unpack_unchecked(&position_info.data.borrow())?;
+        if position.is_initialized {
+            return Err(SwapError::AlreadyInUse.into());
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let position = ConcentratedPosition {
+            is_initialized: true,
+            swap: *swap_info.key,
+            owner: *owner_info.key,
+            tick_lower,
+            tick_upper,
+            ..ConcentratedPosition::default()
+        };
+        ConcentratedPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes an [IncreaseLiquidity](enum.Instruction.html).
+    pub fn process_increase_liquidity(
+        program_id: &Pubkey,
+        liquidity: u128,
+        maximum_token_a_amount: u64,
+        maximum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let source_a_info = next_account_info(account_info_iter)?;
+        let source_b_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_concentrated_swap(program_id, swap_info)?;
+        token_swap.status.check_trading()?;
+        Self::check_concentrated_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            token_a_info,
+            token_b_info,
+            token_program_info,
+        )?;
+        let mut position =
+            Self::unpack_position(program_id, swap_info.key, position_info, owner_info)?;
+        if liquidity == 0 {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let liquidity_delta =
+            i128::try_from(liquidity).map_err(|_| SwapError::CalculationFailure)?;
+
+        Self::modify_position(&mut token_swap.curve, &mut position, liquidity_delta)?;
+        let (token_a_amount, token_b_amount) = token_swap
+            .curve
+            .token_amounts(
+                position.tick_lower,
+                position.tick_upper,
+                liquidity,
+                RoundDirection::Ceiling,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+        let token_a_amount = to_u64(token_a_amount)?;
+        if token_a_amount > maximum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        let token_b_amount = to_u64(token_b_amount)?;
+        if token_b_amount > maximum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+
+        if token_a_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_a_info.clone(),
+                token_a_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce,
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_b_info.clone(),
+                token_b_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce,
+                token_b_amount,
+            )?;
+        }
+
+        ConcentratedPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        SwapVersion::pack_concentrated(token_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes a [DecreaseLiquidity](enum.Instruction.html).
+    pub fn process_decrease_liquidity(
+        program_id: &Pubkey,
+        liquidity: u128,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let destination_a_info = next_account_info(account_info_iter)?;
+        let destination_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_concentrated_swap(program_id, swap_info)?;
+        token_swap.status.check_withdrawing()?;
+        Self::check_concentrated_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            token_a_info,
+            token_b_info,
+            token_program_info,
+        )?;
+        let mut position =
+            Self::unpack_position(program_id, swap_info.key, position_info, owner_info)?;
+        if liquidity == 0 {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if liquidity > position.liquidity {
+            return Err(SwapError::InsufficientLiquidity.into());
+        }
+        let liquidity_delta =
+            i128::try_from(liquidity).map_err(|_| SwapError::CalculationFailure)?;
+
+        Self::modify_position(&mut token_swap.curve, &mut position, -liquidity_delta)?;
+        let (token_a_amount, token_b_amount) = token_swap
+            .curve
+            .token_amounts(
+                position.tick_lower,
+                position.tick_upper,
+                liquidity,
+                RoundDirection::Floor,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+        let token_a_amount = to_u64(token_a_amount)?;
+        if token_a_amount < minimum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        let token_b_amount = to_u64(token_b_amount)?;
+        if token_b_amount < minimum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+
+        if token_a_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_a_info.clone(),
+                destination_a_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce,
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_b_info.clone(),
+                destination_b_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce,
+                token_b_amount,
+            )?;
+        }
+
+        ConcentratedPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        SwapVersion::pack_concentrated(token_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes a [CollectFees](enum.Instruction.html).
+    pub fn process_collect_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let destination_a_info = next_account_info(account_info_iter)?;
+        let destination_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = Self::unpack_concentrated_swap(program_id, swap_info)?;
+        token_swap.status.check_withdrawing()?;
+        Self::check_concentrated_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            token_a_info,
+            token_b_info,
+            token_program_info,
+        )?;
+        let mut position =
+            Self::unpack_position(program_id, swap_info.key, position_info, owner_info)?;
+
+        // the ticks of a position without liquidity may be cleared, but it
+        // has no fees to record either
+        if position.liquidity > 0 {
+            let (fee_growth_inside_a, fee_growth_inside_b) = token_swap
+                .curve
+                .fee_growth_inside(position.tick_lower, position.tick_upper)
+                .ok_or(SwapError::CalculationFailure)?;
+            position.update(0, fee_growth_inside_a, fee_growth_inside_b)?;
+        }
+
+        if position.fees_owed_a > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_a_info.clone(),
+                destination_a_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce,
+                position.fees_owed_a,
+            )?;
+        }
+        if position.fees_owed_b > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_b_info.clone(),
+                destination_b_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce,
+                position.fees_owed_b,
+            )?;
+        }
+        position.fees_owed_a = 0;
+        position.fees_owed_b = 0;
+
+        ConcentratedPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes a [ClosePosition](enum.Instruction.html).
+    pub fn process_close_position(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+
+        if position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let position = ConcentratedPosition::unpack(&position_info.data.borrow())?;
+        let position =
+            Self::unpack_position(program_id, &position.swap, position_info, owner_info)?;
+        if position.liquidity > 0 || position.fees_owed_a > 0 || position.fees_owed_b > 0 {
+            return Err(SwapError::PositionNotEmpty.into());
+        }
+
+        let destination_starting_lamports = destination_info.lamports();
+        **destination_info.lamports.borrow_mut() = destination_starting_lamports
+            .checked_add(position_info.lamports())
+            .ok_or(SwapError::CalculationFailure)?;
+        **position_info.lamports.borrow_mut() = 0;
+        position_info.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    /// Processes a [ConcentratedSwap](enum.Instruction.html).
+    pub fn process_concentrated_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_concentrated_swap(program_id, swap_info)?;
+        token_swap.status.check_trading()?;
+        let trade_direction = if *swap_source_info.key == token_swap.token_a {
+            TradeDirection::AtoB
+        } else {
+            TradeDirection::BtoA
+        };
+        let (swap_token_a_info, swap_token_b_info, fee_account, source_mint) = match trade_direction
+        {
+            TradeDirection::AtoB => (
+                swap_source_info,
+                swap_destination_info,
+                token_swap.fee_account_a,
+                token_swap.token_a_mint,
+            ),
+            TradeDirection::BtoA => (
+                swap_destination_info,
+                swap_source_info,
+                token_swap.fee_account_b,
+                token_swap.token_b_mint,
+            ),
+        };
+        Self::check_concentrated_accounts(
+            &token_swap,
+            program_id,
+            swap_info,
+            authority_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            token_program_info,
+        )?;
+        if *source_info.key == *swap_source_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if *destination_info.key == *swap_destination_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if *fee_account_info.key != fee_account {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+
+        let amount_in = to_u128(amount_in)?;
+        let mut owner_fee = token_swap
+            .fees
+            .owner_trading_fee(amount_in)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let source_amount_swapped = amount_in
+            .checked_sub(owner_fee)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let result =
+            token_swap
+                .curve
+                .swap(source_amount_swapped, trade_direction, &token_swap.fees)?;
+        if result.destination_amount_swapped < to_u128(minimum_amount_out)? {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if result.destination_amount_swapped == 0 {
+            return Err(SwapError::ZeroTradingTokens.into());
+        }
+
+        Self::token_transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_source_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce,
+            to_u64(source_amount_swapped)?,
+        )?;
+
+        if owner_fee > 0 {
+            // Allow error to fall through
+            if let Ok(host_fee_account_info) = next_account_info(account_info_iter) {
+                let host_fee_account = Self::unpack_token_account(
+                    host_fee_account_info,
+                    &token_swap.token_program_id,
+                )?;
+                if host_fee_account.mint != source_mint {
+                    return Err(SwapError::IncorrectFeeAccount.into());
+                }
+                let host_fee = token_swap
+                    .fees
+                    .host_fee(owner_fee)
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                if host_fee > 0 {
+                    owner_fee = owner_fee
+                        .checked_sub(host_fee)
+                        .ok_or(SwapError::FeeCalculationFailure)?;
+                    Self::token_transfer(
+                        swap_info.key,
+                        token_program_info.clone(),
+                        source_info.clone(),
+                        host_fee_account_info.clone(),
+                        user_transfer_authority_info.clone(),
+                        token_swap.nonce,
+                        to_u64(host_fee)?,
+                    )?;
+                }
+            }
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_info.clone(),
+                fee_account_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce,
+                to_u64(owner_fee)?,
+            )?;
+        }
+
+        Self::token_transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_destination_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce,
+            to_u64(result.destination_amount_swapped)?,
+        )?;
+
+        SwapVersion::pack_concentrated(token_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Checks that the admin of a swap signed
+    fn check_admin(admin: Option<&Pubkey>, admin_info: &AccountInfo) -> ProgramResult {
+        if admin != Some(admin_info.key) {
+            return Err(SwapError::InvalidAdmin.into());
+        }
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// Unpacks a swap of the latest version, after checking that the program
+    /// owns it and that its admin signed
+    fn unpack_admin_swap(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        admin_info: &AccountInfo,
+    ) -> Result<SwapV3, ProgramError> {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // older swap versions have no admin
+        let token_swap = match swap_info.data.borrow().split_first() {
+            Some((5, swap_v3_data)) => SwapV3::unpack(swap_v3_data)?,
+            _ => return Err(SwapError::InvalidAdmin.into()),
+        };
+        Self::check_admin(token_swap.admin(), admin_info)?;
+        Ok(token_swap)
+    }
+
+    /// Processes an [UpdateFees](enum.Instruction.html).
+    pub fn process_update_fees(
+        program_id: &Pubkey,
+        fees: Fees,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_info)?;
+        if let Some(swap_constraints) = swap_constraints {
+            swap_constraints.validate_fees(&fees)?;
+        }
+        fees.validate()?;
+
+        token_swap.fees = fees;
+        SwapVersion::pack(
+            SwapVersion::SwapV3(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
+
+    /// Processes a [RampAmp](enum.Instruction.html).
+    pub fn process_ramp_amp(
+        program_id: &Pubkey,
+        target_amp: u64,
+        stop_ramp_timestamp: i64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_info)?;
+        let stable_curve = token_swap
+            .swap_curve
+            .stable_curve()
+            .ok_or(SwapError::InvalidAmpRamp)?;
+        let clock = Clock::get()?;
+        // a new ramp starts from wherever the previous one has got to
+        let current_amp = match token_swap.amp_ramp() {
+            Some(amp_ramp) => amp_ramp.amp(clock.unix_timestamp),
+            None => stable_curve.amp,
+        };
+        let amp_ramp = AmpRamp::new(
+            current_amp,
+            target_amp,
+            clock.unix_timestamp,
+            stop_ramp_timestamp,
+        )?;
+
+        // the stored curve holds the amplification from the end of the ramp
+        token_swap.swap_curve = SwapCurve {
+            curve_type: CurveType::Stable,
+            calculator: Box::new(StableCurve { amp: target_amp }),
+        };
+        token_swap.amp_ramp = amp_ramp;
+        SwapVersion::pack(
+            SwapVersion::SwapV3(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
+
+    /// Processes a [SetFeeAccount](enum.Instruction.html).
+    pub fn process_set_fee_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_info)?;
+        let fee_account =
+            Self::unpack_token_account(fee_account_info, &token_swap.token_program_id)?;
+        if fee_account.mint != token_swap.pool_mint {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if fee_account.owner == Self::authority_id(program_id, swap_info.key, token_swap.nonce)? {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if fee_account.owner != owner_key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+        }
+
+        token_swap.pool_fee_account = *fee_account_info.key;
+        SwapVersion::pack(
+            SwapVersion::SwapV3(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
+
+    /// Processes a [SetStatus](enum.Instruction.html).
+    pub fn process_set_status(
+        program_id: &Pubkey,
+        status: SwapStatus,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let version = swap_info.data.borrow().first().copied();
+        match version {
+            Some(3) => {
+                let mut token_swap = Self::unpack_multi_token_swap(program_id, swap_info)?;
+                Self::check_admin(token_swap.admin(), admin_info)?;
+                token_swap.status = status;
+                SwapVersion::pack_multi_token(token_swap, &mut swap_info.data.borrow_mut())?;
+            }
+            Some(4) => {
+                let mut token_swap = Self::unpack_concentrated_swap(program_id, swap_info)?;
+                Self::check_admin(token_swap.admin(), admin_info)?;
+                token_swap.status = status;
+                SwapVersion::pack_concentrated(token_swap, &mut swap_info.data.borrow_mut())?;
+            }
+            _ => {
+                let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_info)?;
+                token_swap.status = status;
+                SwapVersion::pack(
+                    SwapVersion::SwapV3(token_swap),
+                    &mut swap_info.data.borrow_mut(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Processes an [Instruction](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
+    }
+
+    /// Processes an instruction given extra constraint
+    pub fn process_with_constraints(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: &[u8],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let instruction = SwapInstruction::unpack(input)?;
+        match instruction {
+            SwapInstruction::Initialize(Initialize {
+                nonce,
+                fees,
+                swap_curve,
+            }) => {
+                msg!("Instruction: Init");
+                Self::process_initialize(
+                    program_id,
+                    nonce,
+                    fees,
+                    swap_curve,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: Swap");
+                Self::process_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: DepositAllTokenTypes");
+                Self::process_deposit_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: WithdrawAllTokenTypes");
+                Self::process_withdraw_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
+                Self::process_deposit_single_token_type_exact_amount_in(
+                    program_id,
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
+                Self::process_withdraw_single_token_type_exact_amount_out(
+                    program_id,
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::RouteSwap(RouteSwap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: RouteSwap");
+                Self::process_route_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::InitializeMultiToken(InitializeMultiToken {
+                nonce,
+                fees,
+                curve,
+                admin,
+            }) => {
+                msg!("Instruction: InitializeMultiToken");
+                Self::process_initialize_multi_token(
+                    program_id,
+                    nonce,
+                    fees,
+                    curve,
+                    admin,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::MultiTokenSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: MultiTokenSwap");
+                Self::process_multi_token_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::MultiTokenDepositAllTokenTypes(MultiTokenDepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_amounts,
+            }) => {
+                msg!("Instruction: MultiTokenDepositAllTokenTypes");
+                Self::process_multi_token_deposit_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    &maximum_token_amounts,
+                    accounts,
+                )
+            }
+            SwapInstruction::MultiTokenWithdrawAllTokenTypes(MultiTokenWithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_amounts,
+            }) => {
+                msg!("Instruction: MultiTokenWithdrawAllTokenTypes");
+                Self::process_multi_token_withdraw_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    &minimum_token_amounts,
+                    accounts,
+                )
+            }
+            SwapInstruction::MultiTokenDepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: MultiTokenDepositSingleTokenTypeExactAmountIn");
+                Self::process_multi_token_deposit_single_token_type_exact_amount_in(
+                    program_id,
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::MultiTokenWithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: MultiTokenWithdrawSingleTokenTypeExactAmountOut");
+                Self::process_multi_token_withdraw_single_token_type_exact_amount_out(
+                    program_id,
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::InitializeConcentrated(InitializeConcentrated {
+                nonce,
+                fees,
+                tick_spacing,
+                sqrt_price,
+                min_position_liquidity,
+                max_ticks,
+                admin,
+            }) => {
+                msg!("Instruction: InitializeConcentrated");
+                Self::process_initialize_concentrated(
+                    program_id,
+                    nonce,
+                    fees,
+                    tick_spacing,
+                    sqrt_price,
+                    min_position_liquidity,
+                    max_ticks,
+                    admin,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::OpenPosition(OpenPosition {
+                tick_lower,
+                tick_upper,
+            }) => {
+                msg!("Instruction: OpenPosition");
+                Self::process_open_position(program_id, tick_lower, tick_upper, accounts)
+            }
+            SwapInstruction::IncreaseLiquidity(IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: IncreaseLiquidity");
+                Self::process_increase_liquidity(
+                    program_id,
+                    liquidity,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::DecreaseLiquidity(DecreaseLiquidity {
+                liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: DecreaseLiquidity");
+                Self::process_decrease_liquidity(
+                    program_id,
+                    liquidity,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::CollectFees => {
+                msg!("Instruction: CollectFees");
+                Self::process_collect_fees(program_id, accounts)
+            }
+            SwapInstruction::ClosePosition => {
+                msg!("Instruction: ClosePosition");
+                Self::process_close_position(program_id, accounts)
+            }
+            SwapInstruction::ConcentratedSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: ConcentratedSwap");
+                Self::process_concentrated_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::UpdateFees(UpdateFees { fees }) => {
+                msg!("Instruction: UpdateFees");
+                Self::process_update_fees(program_id, fees, accounts, swap_constraints)
+            }
+            SwapInstruction::RampAmp(RampAmp {
+                target_amp,
+                stop_ramp_timestamp,
+            }) => {
+                msg!("Instruction: RampAmp");
+                Self::process_ramp_amp(program_id, target_amp, stop_ramp_timestamp, accounts)
+            }
+            SwapInstruction::SetFeeAccount => {
+                msg!("Instruction: SetFeeAccount");
+                Self::process_set_fee_account(program_id, accounts, swap_constraints)
+            }
+            SwapInstruction::SetStatus(SetStatus { status }) => {
+                msg!("Instruction: SetStatus");
+                Self::process_set_status(program_id, status, accounts)
+            }
+        }
+    }
+}
+
+impl PrintProgramError for SwapError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        match self {
+            SwapError::AlreadyInUse => msg!("Error: Swap account already in use"),
+            SwapError::InvalidProgramAddress => {
+                msg!("Error: Invalid program address generated from nonce and key")
+            }
+            SwapError::InvalidOwner => {
+                msg!("Error: The input account owner is not the program address")
+            }
+            SwapError::InvalidOutputOwner => {
+                msg!("Error: Output pool account owner cannot be the program address")
+            }
+            SwapError::ExpectedMint => msg!("Error: Deserialized account is not an SPL Token mint"),
+            SwapError::ExpectedAccount => {
+                msg!("Error: Deserialized account is not an SPL Token account")
+            }
+            SwapError::EmptySupply => msg!("Error: Input token account empty"),
+            SwapError::InvalidSupply => msg!("Error: Pool token mint has a non-zero supply"),
+            SwapError::RepeatedMint => msg!("Error: Swap input token accounts have the same mint"),
+            SwapError::InvalidDelegate => msg!("Error: Token account has a delegate"),
+            SwapError::InvalidInput => msg!("Error: InvalidInput"),
+            SwapError::IncorrectSwapAccount => {
+                msg!("Error: Address of the provided swap token account is incorrect")
+            }
+            SwapError::IncorrectPoolMint => {
+                msg!("Error: Address of the provided pool token mint is incorrect")
+            }
+            SwapError::InvalidOutput => msg!("Error: InvalidOutput"),
+            SwapError::CalculationFailure => msg!("Error: CalculationFailure"),
+            SwapError::InvalidInstruction => msg!("Error: InvalidInstruction"),
+            SwapError::ExceededSlippage => {
+                msg!("Error: Swap instruction exceeds desired slippage limit")
+            }
+            SwapError::InvalidCloseAuthority => msg!("Error: Token account has a close authority"),
+            SwapError::InvalidFreezeAuthority => {
+                msg!("Error: Pool token mint has a freeze authority")
+            }
+            SwapError::IncorrectFeeAccount => msg!("Error: Pool fee token account incorrect"),
+            SwapError::ZeroTradingTokens => {
+                msg!("Error: Given pool token amount results in zero trading tokens")
+            }
+            SwapError::FeeCalculationFailure => msg!(
+                "Error: The fee calculation failed due to overflow, underflow, or unexpected 0"
+            ),
+            SwapError::ConversionFailure => msg!("Error: Conversion to or from u64 failed."),
+            SwapError::InvalidFee => {
+                msg!("Error: The provided fee does not match the program owner's constraints")
+            }
+            SwapError::IncorrectTokenProgramId => {
+                msg!("Error: The provided token program does not match the token program expected by the swap")
+            }
+            SwapError::UnsupportedCurveType => {
+                msg!("Error: The provided curve type is not supported by the program owner")
+            }
+            SwapError::InvalidCurve => {
+                msg!("Error: The provided curve parameters are invalid")
+            }
+            SwapError::UnsupportedCurveOperation => {
+                msg!("Error: The operation cannot be performed on the given curve")
+            }
+            SwapError::IncorrectOracleAccount => {
+                msg!("Error: The provided oracle account does not match the swap's oracle")
+            }
+            SwapError::InvalidOraclePrice => {
+                msg!("Error: The oracle price is not trading, too old or too uncertain")
+            }
+            SwapError::InvalidRoute => msg!("Error: The pools of a routed swap do not connect"),
+            SwapError::InvalidTokenCount => {
+                msg!("Error: The number of tokens in a multi-token swap is not supported")
+            }
+            SwapError::InvalidTick => {
+                msg!("Error: The tick range is not valid for the concentrated liquidity pool")
+            }
+            SwapError::TooManyTicks => msg!(
+                "Error: The concentrated liquidity pool has no room for another initialized tick"
+            ),
+            SwapError::InsufficientLiquidity => {
+                msg!("Error: Not enough concentrated liquidity for the operation")
+            }
+            SwapError::PositionNotEmpty => {
+                msg!("Error: The position still holds liquidity or uncollected fees")
+            }
+            SwapError::InvalidAdmin => {
+                msg!("Error: The swap has no admin, or the provided admin does not match it")
+            }
+            SwapError::InvalidAmpRamp => {
+                msg!("Error: The amplification ramp is too short, too steep, or not on a stable curve")
+            }
+            SwapError::PoolPaused => msg!("Error: The swap is paused by its admin"),
+            SwapError::PoolWithdrawOnly => {
+                msg!("Error: The swap is winding down and only allows withdrawals")
+            }
+            SwapError::InvalidOracleOwner => {
+                msg!("Error: The oracle account is not owned by the Pyth program")
+            }
+            SwapError::PositionBelowMinLiquidity => {
+                msg!("Error: The position would hold less than the pool's minimum liquidity")
+            }
+        }
+    }
+}
+
+fn to_u128(val: u64) -> Result<u128, SwapError> {
+    val.try_into().map_err(|_| SwapError::ConversionFailure)
+}
+
+fn to_u64(val: u128) -> Result<u64, SwapError> {
+    val.try_into().map_err(|_| SwapError::ConversionFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::calculator::{CurveCalculator, INITIAL_SWAP_POOL_AMOUNT},
+        curve::{
+            base::CurveType, concentrated::sqrt_price_at_tick, constant_price::ConstantPriceCurve,
+            constant_product::ConstantProductCurve, multi_token::MultiTokenCurveType,
+            offset::OffsetCurve, oracle::OracleCurve,
+        },
+        instruction::{
+            close_position, collect_fees, concentrated_swap, decrease_liquidity,
+            deposit_all_token_types, deposit_single_token_type_exact_amount_in,
+            deposit_single_token_type_exact_amount_in_with_oracle, increase_liquidity, initialize,
+            initialize_concentrated, initialize_multi_token, initialize_with_admin,
+            initialize_with_oracle, multi_token_deposit_all_token_types,
+            multi_token_deposit_single_token_type_exact_amount_in, multi_token_swap,
+            multi_token_withdraw_all_token_types,
+            multi_token_withdraw_single_token_type_exact_amount_out, open_position, ramp_amp,
+            route_swap, set_fee_account, set_status, swap, swap_with_oracle, update_fees,
+            withdraw_all_token_types, withdraw_single_token_type_exact_amount_out,
+            withdraw_single_token_type_exact_amount_out_with_oracle, RouteSwapPool,
+        },
+        pyth::{self, tests::pyth_price_data},
+        twap::{time_weighted_average_price, TimeWeightedPrice, PRICE_SCALE},
+    };
+    use solana_program::{instruction::Instruction, program_stubs, rent::Rent};
+    use solana_sdk::account::{create_account_for_test, create_is_signer_account_infos, Account};
+    use spl_token::{
+        error::TokenError,
+        instruction::{
+            approve, initialize_account, initialize_mint, mint_to, revoke, set_authority, transfer,
+            AuthorityType,
+        },
+    };
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    // Test program id for the swap program.
+    const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
+    // Test program id for the token program.
+    const TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+
+    // Unix timestamp of the test clock sysvar, only changed by tests of price
+    // accumulators
+    static TEST_UNIX_TIMESTAMP: AtomicI64 = AtomicI64::new(0);
+
+    struct TestSyscallStubs {}
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            msg!("TestSyscallStubs::sol_invoke_signed()");
+
+            let mut new_account_infos = vec![];
+
+            // mimic check for token program in accounts
+            if !account_infos.iter().any(|x| *x.key == TOKEN_PROGRAM_ID) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            for meta in instruction.accounts.iter() {
+                for account_info in account_infos.iter() {
+                    if meta.pubkey == *account_info.key {
+                        let mut new_account_info = account_info.clone();
+                        for seeds in signers_seeds.iter() {
+                            let signer =
+                                Pubkey::create_program_address(&seeds, &SWAP_PROGRAM_ID).unwrap();
+                            if *account_info.key == signer {
+                                new_account_info.is_signer = true;
+                            }
+                        }
+                        new_account_infos.push(new_account_info);
+                    }
+                }
+            }
+
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &new_account_infos,
+                &instruction.data,
+            )
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut _ as *mut Clock) = Clock {
+                    unix_timestamp: TEST_UNIX_TIMESTAMP.load(Ordering::SeqCst),
+                    ..Clock::default()
+                };
+            }
+            solana_program::entrypoint::SUCCESS
+        }
+    }
+
+    fn test_syscall_stubs() {
+        use std::sync::Once;
+        static ONCE: Once = Once::new();
+
+        ONCE.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
+        });
+    }
+
+    struct SwapAccountInfo {
+        nonce: u8,
+        authority_key: Pubkey,
+        fees: Fees,
+        swap_curve: SwapCurve,
+        swap_key: Pubkey,
+        swap_account: Account,
+        pool_mint_key: Pubkey,
+        pool_mint_account: Account,
+        pool_fee_key: Pubkey,
+        pool_fee_account: Account,
+        pool_token_key: Pubkey,
+        pool_token_account: Account,
+        token_a_key: Pubkey,
+        token_a_account: Account,
+        token_a_mint_key: Pubkey,
+        token_a_mint_account: Account,
+        token_b_key: Pubkey,
+        token_b_account: Account,
+        token_b_mint_key: Pubkey,
+        token_b_mint_account: Account,
+        oracle_key: Option<Pubkey>,
+        oracle_account: Account,
+        admin_key: Option<Pubkey>,
+    }
+
+    impl SwapAccountInfo {
+        pub fn new(
+            user_key: &Pubkey,
+            fees: Fees,
+            swap_curve: SwapCurve,
+            token_a_amount: u64,
+            token_b_amount: u64,
+        ) -> Self {
+            let swap_key = Pubkey::new_unique();
+            let swap_account = Account::new(0, SwapVersion::LATEST_LEN, &SWAP_PROGRAM_ID);
+            let (authority_key, nonce) =
+                Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+
+            let (pool_mint_key, mut pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &authority_key, None);
+            let (pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                &user_key,
+                0,
+            );
+            let (pool_fee_key, pool_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                &user_key,
+                0,
+            );
+            let (token_a_mint_key, mut token_a_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let (token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &token_a_mint_key,
+                &mut token_a_mint_account,
+                &user_key,
+                &authority_key,
+                token_a_amount,
+            );
+            let (token_b_mint_key, mut token_b_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let (token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &token_b_mint_key,
+                &mut token_b_mint_account,
+                &user_key,
+                &authority_key,
+                token_b_amount,
+            );
+
+            SwapAccountInfo {
+                nonce,
+                authority_key,
+                fees,
+                swap_curve,
+                swap_key,
+                swap_account,
+                pool_mint_key,
+                pool_mint_account,
+                pool_fee_key,
+                pool_fee_account,
+                pool_token_key,
+                pool_token_account,
+                token_a_key,
+                token_a_account,
+                token_a_mint_key,
+                token_a_mint_account,
+                token_b_key,
+                token_b_account,
+                token_b_mint_key,
+                token_b_mint_account,
+                oracle_key: None,
+                oracle_account: Account::default(),
+                admin_key: None,
+            }
+        }
+
+        pub fn initialize_swap(&mut self) -> ProgramResult {
+            let mut admin_account = Account::default();
+            let mut optional_accounts = vec![];
+            if self.oracle_key.is_some() {
+                optional_accounts.push(&mut self.oracle_account);
+            }
+            if self.admin_key.is_some() {
+                optional_accounts.push(&mut admin_account);
+            }
+            let instruction = match (self.oracle_key.as_ref(), self.admin_key.as_ref()) {
+                (Some(oracle_key), admin_key) => initialize_with_oracle(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.pool_token_key,
+                    oracle_key,
+                    admin_key,
+                    self.nonce,
+                    self.fees.clone(),
+                    self.swap_curve.clone(),
+                ),
+                (None, Some(admin_key)) => initialize_with_admin(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.pool_token_key,
+                    admin_key,
+                    self.nonce,
+                    self.fees.clone(),
+                    self.swap_curve.clone(),
+                ),
+                (None, None) => initialize(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.pool_token_key,
+                    self.nonce,
+                    self.fees.clone(),
+                    self.swap_curve.clone(),
+                ),
+            }
+            .unwrap();
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut self.pool_fee_account,
+                    &mut self.pool_token_account,
+                    &mut Account::default(),
+                ]
+                .into_iter()
+                .chain(optional_accounts)
+                .collect(),
+            )
+        }
+
+        pub fn setup_token_accounts(
+            &mut self,
+            mint_owner: &Pubkey,
+            account_owner: &Pubkey,
+            a_amount: u64,
+            b_amount: u64,
+            pool_amount: u64,
+        ) -> (Pubkey, Account, Pubkey, Account, Pubkey, Account) {
+            let (token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.token_a_mint_key,
+                &mut self.token_a_mint_account,
+                &mint_owner,
+                &account_owner,
+                a_amount,
+            );
+            let (token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.token_b_mint_key,
+                &mut self.token_b_mint_account,
+                &mint_owner,
+                &account_owner,
+                b_amount,
+            );
+            let (pool_key, pool_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.pool_mint_key,
+                &mut self.pool_mint_account,
+                &self.authority_key,
+                &account_owner,
+                pool_amount,
+            );
+            (
+                token_a_key,
+                token_a_account,
+                token_b_key,
+                token_b_account,
+                pool_key,
+                pool_account,
+            )
+        }
+
+        fn get_token_account(&self, account_key: &Pubkey) -> &Account {
+            if *account_key == self.token_a_key {
+                return &self.token_a_account;
+            } else if *account_key == self.token_b_key {
+                return &self.token_b_account;
+            }
+            panic!("Could not find matching swap token account");
+        }
+
+        fn set_token_account(&mut self, account_key: &Pubkey, account: Account) {
+            if *account_key == self.token_a_key {
+                self.token_a_account = account;
+                return;
+            } else if *account_key == self.token_b_key {
+                self.token_b_account = account;
+                return;
+            }
+            panic!("Could not find matching swap token account");
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn swap(
+            &mut self,
+            user_key: &Pubkey,
+            user_source_key: &Pubkey,
+            mut user_source_account: &mut Account,
+            swap_source_key: &Pubkey,
+            swap_destination_key: &Pubkey,
+            user_destination_key: &Pubkey,
+            mut user_destination_account: &mut Account,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            // approve moving from user source account
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &user_source_key,
+                    &user_transfer_key,
+                    &user_key,
+                    &[],
+                    amount_in,
+                )
+                .unwrap(),
+                vec![
+                    &mut user_source_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            let mut swap_source_account = self.get_token_account(swap_source_key).clone();
+            let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
+
+            // perform the swap
+            let instruction = match self.oracle_key.as_ref() {
+                Some(oracle_key) => swap_with_oracle(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    &user_source_key,
+                    &swap_source_key,
+                    &swap_destination_key,
+                    &user_destination_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    oracle_key,
+                    None,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
+                    },
+                ),
+                None => swap(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    &user_source_key,
+                    &swap_source_key,
+                    &swap_destination_key,
+                    &user_destination_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    None,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
+                    },
+                ),
+            }
+            .unwrap();
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut user_source_account,
+                    &mut swap_source_account,
+                    &mut swap_destination_account,
+                    &mut user_destination_account,
+                    &mut self.pool_mint_account,
+                    &mut self.pool_fee_account,
+                    &mut Account::default(),
+                    &mut self.oracle_account,
+                ],
+            )?;
+
+            self.set_token_account(swap_source_key, swap_source_account);
+            self.set_token_account(swap_destination_key, swap_destination_account);
+
+            Ok(())
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_all_token_types(
+            &mut self,
+            depositor_key: &Pubkey,
+            depositor_token_a_key: &Pubkey,
+            mut depositor_token_a_account: &mut Account,
+            depositor_token_b_key: &Pubkey,
+            mut depositor_token_b_account: &mut Account,
+            depositor_pool_key: &Pubkey,
+            mut depositor_pool_account: &mut Account,
+            pool_token_amount: u64,
+            maximum_token_a_amount: u64,
+            maximum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority = Pubkey::new_unique();
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &depositor_token_a_key,
+                    &user_transfer_authority,
+                    &depositor_key,
+                    &[],
+                    maximum_token_a_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut depositor_token_a_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &depositor_token_b_key,
+                    &user_transfer_authority,
+                    &depositor_key,
+                    &[],
+                    maximum_token_b_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut depositor_token_b_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                deposit_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority,
+                    &depositor_token_a_key,
+                    &depositor_token_b_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &depositor_pool_key,
+                    DepositAllTokenTypes {
+                        pool_token_amount,
+                        maximum_token_a_amount,
+                        maximum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut depositor_token_a_account,
+                    &mut depositor_token_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut depositor_pool_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_all_token_types(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            mut pool_account: &mut Account,
+            token_a_key: &Pubkey,
+            mut token_a_account: &mut Account,
+            token_b_key: &Pubkey,
+            mut token_b_account: &mut Account,
+            pool_token_amount: u64,
+            minimum_token_a_amount: u64,
+            minimum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            // approve user transfer authority to take out pool tokens
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &pool_key,
+                    &user_transfer_authority_key,
+                    &user_key,
+                    &[],
+                    pool_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            // withdraw token a and b correctly
+            do_process_instruction(
+                withdraw_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &token_a_key,
+                    &token_b_key,
+                    WithdrawAllTokenTypes {
+                        pool_token_amount,
+                        minimum_token_a_amount,
+                        minimum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut self.pool_mint_account,
+                    &mut pool_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut token_a_account,
+                    &mut token_b_account,
+                    &mut self.pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_single_token_type_exact_amount_in(
+            &mut self,
+            depositor_key: &Pubkey,
+            deposit_account_key: &Pubkey,
+            mut deposit_token_account: &mut Account,
+            deposit_pool_key: &Pubkey,
+            mut deposit_pool_account: &mut Account,
+            source_token_amount: u64,
+            minimum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &deposit_account_key,
+                    &user_transfer_authority_key,
+                    &depositor_key,
+                    &[],
+                    source_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut deposit_token_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            let instruction = match self.oracle_key.as_ref() {
+                Some(oracle_key) => deposit_single_token_type_exact_amount_in_with_oracle(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &deposit_account_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &deposit_pool_key,
+                    oracle_key,
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                ),
+                None => deposit_single_token_type_exact_amount_in(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &deposit_account_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &deposit_pool_key,
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                ),
+            }
+            .unwrap();
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut deposit_token_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut deposit_pool_account,
+                    &mut Account::default(),
+                    &mut self.oracle_account,
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_single_token_type_exact_amount_out(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            mut pool_account: &mut Account,
+            destination_key: &Pubkey,
+            mut destination_account: &mut Account,
+            destination_token_amount: u64,
+            maximum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            // approve user transfer authority to take out pool tokens
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &pool_key,
+                    &user_transfer_authority_key,
+                    &user_key,
+                    &[],
+                    maximum_pool_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            let instruction = match self.oracle_key.as_ref() {
+                Some(oracle_key) => withdraw_single_token_type_exact_amount_out_with_oracle(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &destination_key,
+                    oracle_key,
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                ),
+                None => withdraw_single_token_type_exact_amount_out(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &destination_key,
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                ),
+            }
+            .unwrap();
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut self.pool_mint_account,
+                    &mut pool_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut destination_account,
+                    &mut self.pool_fee_account,
+                    &mut Account::default(),
+                    &mut self.oracle_account,
+                ],
+            )
+        }
+    }
+
+    /// Swap through a route of pools, each given with its swap token accounts
+    /// to swap into and out of
+    #[allow(clippy::too_many_arguments)]
+    fn route_swap_through(
+        route: &mut [(&mut SwapAccountInfo, Pubkey, Pubkey)],
+        user_key: &Pubkey,
+        user_source_key: &Pubkey,
+        mut user_source_account: &mut Account,
+        user_destination_key: &Pubkey,
+        mut user_destination_account: &mut Account,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let user_transfer_key = Pubkey::new_unique();
+        // approve moving from user source account
+        do_process_instruction(
+            approve(
+                &TOKEN_PROGRAM_ID,
+                &user_source_key,
+                &user_transfer_key,
+                &user_key,
+                &[],
+                amount_in,
+            )
+            .unwrap(),
+            vec![
+                &mut user_source_account,
+                &mut Account::default(),
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+
+        let pools = route
+            .iter()
+            .map(
+                |(accounts, swap_source_key, swap_destination_key)| RouteSwapPool {
+                    swap_pubkey: accounts.swap_key,
+                    authority_pubkey: accounts.authority_key,
+                    swap_source_pubkey: *swap_source_key,
+                    swap_destination_pubkey: *swap_destination_key,
+                    pool_mint_pubkey: accounts.pool_mint_key,
+                    pool_fee_pubkey: accounts.pool_fee_key,
+                    oracle_pubkey: accounts.oracle_key,
+                },
+            )
+            .collect::<Vec<_>>();
+        let mut swap_token_accounts = route
+            .iter()
+            .map(|(accounts, swap_source_key, swap_destination_key)| {
+                (
+                    accounts.get_token_account(swap_source_key).clone(),
+                    accounts.get_token_account(swap_destination_key).clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut authority_accounts = vec![Account::default(); route.len()];
+
+        let mut user_transfer_account = Account::default();
+        let mut token_program_account = Account::default();
+        let mut instruction_accounts: Vec<&mut Account> = vec![
+            &mut user_transfer_account,
+            &mut user_source_account,
+            &mut user_destination_account,
+            &mut token_program_account,
+        ];
+        for (((accounts, _, _), (swap_source_account, swap_destination_account)), authority) in
+            route
+                .iter_mut()
+                .zip(swap_token_accounts.iter_mut())
+                .zip(authority_accounts.iter_mut())
+        {
+            instruction_accounts.push(&mut accounts.swap_account);
+            instruction_accounts.push(authority);
+            instruction_accounts.push(swap_source_account);
+            instruction_accounts.push(swap_destination_account);
+            instruction_accounts.push(&mut accounts.pool_mint_account);
+            instruction_accounts.push(&mut accounts.pool_fee_account);
+            if accounts.oracle_key.is_some() {
+                instruction_accounts.push(&mut accounts.oracle_account);
+            }
+        }
+
+        do_process_instruction(
+            route_swap(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &user_transfer_key,
+                user_source_key,
+                user_destination_key,
+                &pools,
+                RouteSwap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap(),
+            instruction_accounts,
+        )?;
+
+        for ((accounts, swap_source_key, swap_destination_key), (source, destination)) in
+            route.iter_mut().zip(swap_token_accounts)
+        {
+            accounts.set_token_account(swap_source_key, source);
+            accounts.set_token_account(swap_destination_key, destination);
+        }
+        Ok(())
+    }
+
+    struct MultiTokenSwapAccountInfo {
+        nonce: u8,
+        authority_key: Pubkey,
+        fees: Fees,
+        curve: MultiTokenCurve,
+        swap_key: Pubkey,
+        swap_account: Account,
+        pool_mint_key: Pubkey,
+        pool_mint_account: Account,
+        pool_fee_key: Pubkey,
+        pool_fee_account: Account,
         pool_token_key: Pubkey,
         pool_token_account: Account,
+        token_keys: Vec<Pubkey>,
+        token_accounts: Vec<Account>,
+        token_mint_keys: Vec<Pubkey>,
+        token_mint_accounts: Vec<Account>,
+        admin_key: Option<Pubkey>,
+    }
+
+    impl MultiTokenSwapAccountInfo {
+        pub fn new(
+            user_key: &Pubkey,
+            fees: Fees,
+            curve: MultiTokenCurve,
+            token_amounts: &[u64],
+        ) -> Self {
+            let swap_key = Pubkey::new_unique();
+            let swap_account = Account::new(0, SwapVersion::MULTI_TOKEN_LEN, &SWAP_PROGRAM_ID);
+            let (authority_key, nonce) =
+                Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+
+            let (pool_mint_key, mut pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &authority_key, None);
+            let (pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                user_key,
+                0,
+            );
+            let (pool_fee_key, pool_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                user_key,
+                0,
+            );
+
+            let mut token_keys = vec![];
+            let mut token_accounts = vec![];
+            let mut token_mint_keys = vec![];
+            let mut token_mint_accounts = vec![];
+            for token_amount in token_amounts {
+                let (token_mint_key, mut token_mint_account) =
+                    create_mint(&TOKEN_PROGRAM_ID, user_key, None);
+                let (token_key, token_account) = mint_token(
+                    &TOKEN_PROGRAM_ID,
+                    &token_mint_key,
+                    &mut token_mint_account,
+                    user_key,
+                    &authority_key,
+                    *token_amount,
+                );
+                token_keys.push(token_key);
+                token_accounts.push(token_account);
+                token_mint_keys.push(token_mint_key);
+                token_mint_accounts.push(token_mint_account);
+            }
+
+            MultiTokenSwapAccountInfo {
+                nonce,
+                authority_key,
+                fees,
+                curve,
+                swap_key,
+                swap_account,
+                pool_mint_key,
+                pool_mint_account,
+                pool_fee_key,
+                pool_fee_account,
+                pool_token_key,
+                pool_token_account,
+                token_keys,
+                token_accounts,
+                token_mint_keys,
+                token_mint_accounts,
+                admin_key: None,
+            }
+        }
+
+        pub fn initialize_swap(&mut self) -> ProgramResult {
+            let mut authority_account = Account::default();
+            let mut token_program_account = Account::default();
+            let mut accounts: Vec<&mut Account> = vec![
+                &mut self.swap_account,
+                &mut authority_account,
+                &mut self.pool_mint_account,
+                &mut self.pool_fee_account,
+                &mut self.pool_token_account,
+                &mut token_program_account,
+            ];
+            accounts.extend(self.token_accounts.iter_mut());
+            do_process_instruction(
+                initialize_multi_token(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.pool_token_key,
+                    &self.token_keys,
+                    self.admin_key.as_ref(),
+                    self.nonce,
+                    self.fees.clone(),
+                    self.curve.clone(),
+                )
+                .unwrap(),
+                accounts,
+            )
+        }
+
+        pub fn setup_token_accounts(
+            &mut self,
+            mint_owner: &Pubkey,
+            account_owner: &Pubkey,
+            token_amounts: &[u64],
+            pool_amount: u64,
+        ) -> (Vec<Pubkey>, Vec<Account>, Pubkey, Account) {
+            let mut token_keys = vec![];
+            let mut token_accounts = vec![];
+            for ((token_mint_key, token_mint_account), token_amount) in self
+                .token_mint_keys
+                .iter()
+                .zip(self.token_mint_accounts.iter_mut())
+                .zip(token_amounts.iter())
+            {
+                let (token_key, token_account) = mint_token(
+                    &TOKEN_PROGRAM_ID,
+                    token_mint_key,
+                    token_mint_account,
+                    mint_owner,
+                    account_owner,
+                    *token_amount,
+                );
+                token_keys.push(token_key);
+                token_accounts.push(token_account);
+            }
+            let (pool_key, pool_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.pool_mint_key,
+                &mut self.pool_mint_account,
+                &self.authority_key,
+                account_owner,
+                pool_amount,
+            );
+            (token_keys, token_accounts, pool_key, pool_account)
+        }
+
+        fn approve_user_transfer(
+            user_key: &Pubkey,
+            user_transfer_key: &Pubkey,
+            source_key: &Pubkey,
+            source_account: &mut Account,
+            amount: u64,
+        ) {
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    source_key,
+                    user_transfer_key,
+                    user_key,
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+                vec![
+                    source_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn swap(
+            &mut self,
+            user_key: &Pubkey,
+            user_source_key: &Pubkey,
+            user_source_account: &mut Account,
+            user_destination_key: &Pubkey,
+            user_destination_account: &mut Account,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            Self::approve_user_transfer(
+                user_key,
+                &user_transfer_key,
+                user_source_key,
+                user_source_account,
+                amount_in,
+            );
+
+            let mut authority_account = Account::default();
+            let mut user_transfer_account = Account::default();
+            let mut token_program_account = Account::default();
+            let mut accounts: Vec<&mut Account> = vec![
+                &mut self.swap_account,
+                &mut authority_account,
+                &mut user_transfer_account,
+                user_source_account,
+                user_destination_account,
+                &mut self.pool_mint_account,
+                &mut self.pool_fee_account,
+                &mut token_program_account,
+            ];
+            accounts.extend(self.token_accounts.iter_mut());
+            do_process_instruction(
+                multi_token_swap(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    user_source_key,
+                    user_destination_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.token_keys,
+                    None,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
+                    },
+                )
+                .unwrap(),
+                accounts,
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_all_token_types(
+            &mut self,
+            depositor_key: &Pubkey,
+            depositor_token_keys: &[Pubkey],
+            depositor_token_accounts: &mut [Account],
+            depositor_pool_key: &Pubkey,
+            depositor_pool_account: &mut Account,
+            pool_token_amount: u64,
+            maximum_token_amounts: Vec<u64>,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            for ((token_key, token_account), maximum_token_amount) in depositor_token_keys
+                .iter()
+                .zip(depositor_token_accounts.iter_mut())
+                .zip(maximum_token_amounts.iter())
+            {
+                Self::approve_user_transfer(
+                    depositor_key,
+                    &user_transfer_key,
+                    token_key,
+                    token_account,
+                    *maximum_token_amount,
+                );
+            }
+
+            let mut authority_account = Account::default();
+            let mut user_transfer_account = Account::default();
+            let mut token_program_account = Account::default();
+            let mut accounts: Vec<&mut Account> = vec![
+                &mut self.swap_account,
+                &mut authority_account,
+                &mut user_transfer_account,
+                &mut self.pool_mint_account,
+                depositor_pool_account,
+                &mut token_program_account,
+            ];
+            for (depositor_token_account, token_account) in depositor_token_accounts
+                .iter_mut()
+                .zip(self.token_accounts.iter_mut())
+            {
+                accounts.push(depositor_token_account);
+                accounts.push(token_account);
+            }
+            do_process_instruction(
+                multi_token_deposit_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    &self.pool_mint_key,
+                    depositor_pool_key,
+                    depositor_token_keys,
+                    &self.token_keys,
+                    MultiTokenDepositAllTokenTypes {
+                        pool_token_amount,
+                        maximum_token_amounts,
+                    },
+                )
+                .unwrap(),
+                accounts,
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_all_token_types(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            pool_account: &mut Account,
+            destination_token_keys: &[Pubkey],
+            destination_token_accounts: &mut [Account],
+            pool_token_amount: u64,
+            minimum_token_amounts: Vec<u64>,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            Self::approve_user_transfer(
+                user_key,
+                &user_transfer_key,
+                pool_key,
+                pool_account,
+                pool_token_amount,
+            );
+
+            let mut authority_account = Account::default();
+            let mut user_transfer_account = Account::default();
+            let mut token_program_account = Account::default();
+            let mut accounts: Vec<&mut Account> = vec![
+                &mut self.swap_account,
+                &mut authority_account,
+                &mut user_transfer_account,
+                &mut self.pool_mint_account,
+                pool_account,
+                &mut self.pool_fee_account,
+                &mut token_program_account,
+            ];
+            for (token_account, destination_token_account) in self
+                .token_accounts
+                .iter_mut()
+                .zip(destination_token_accounts.iter_mut())
+            {
+                accounts.push(token_account);
+                accounts.push(destination_token_account);
+            }
+            do_process_instruction(
+                multi_token_withdraw_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    &self.pool_mint_key,
+                    pool_key,
+                    &self.pool_fee_key,
+                    &self.token_keys,
+                    destination_token_keys,
+                    MultiTokenWithdrawAllTokenTypes {
+                        pool_token_amount,
+                        minimum_token_amounts,
+                    },
+                )
+                .unwrap(),
+                accounts,
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_single_token_type_exact_amount_in(
+            &mut self,
+            depositor_key: &Pubkey,
+            deposit_token_key: &Pubkey,
+            deposit_token_account: &mut Account,
+            deposit_pool_key: &Pubkey,
+            deposit_pool_account: &mut Account,
+            source_token_amount: u64,
+            minimum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            Self::approve_user_transfer(
+                depositor_key,
+                &user_transfer_key,
+                deposit_token_key,
+                deposit_token_account,
+                source_token_amount,
+            );
+
+            let mut authority_account = Account::default();
+            let mut user_transfer_account = Account::default();
+            let mut token_program_account = Account::default();
+            let mut accounts: Vec<&mut Account> = vec![
+                &mut self.swap_account,
+                &mut authority_account,
+                &mut user_transfer_account,
+                deposit_token_account,
+                &mut self.pool_mint_account,
+                deposit_pool_account,
+                &mut token_program_account,
+            ];
+            accounts.extend(self.token_accounts.iter_mut());
+            do_process_instruction(
+                multi_token_deposit_single_token_type_exact_amount_in(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    deposit_token_key,
+                    &self.pool_mint_key,
+                    deposit_pool_key,
+                    &self.token_keys,
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                )
+                .unwrap(),
+                accounts,
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_single_token_type_exact_amount_out(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            pool_account: &mut Account,
+            destination_key: &Pubkey,
+            destination_account: &mut Account,
+            destination_token_amount: u64,
+            maximum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            Self::approve_user_transfer(
+                user_key,
+                &user_transfer_key,
+                pool_key,
+                pool_account,
+                maximum_pool_token_amount,
+            );
+
+            let mut authority_account = Account::default();
+            let mut user_transfer_account = Account::default();
+            let mut token_program_account = Account::default();
+            let mut accounts: Vec<&mut Account> = vec![
+                &mut self.swap_account,
+                &mut authority_account,
+                &mut user_transfer_account,
+                &mut self.pool_mint_account,
+                pool_account,
+                destination_account,
+                &mut self.pool_fee_account,
+                &mut token_program_account,
+            ];
+            accounts.extend(self.token_accounts.iter_mut());
+            do_process_instruction(
+                multi_token_withdraw_single_token_type_exact_amount_out(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    &self.pool_mint_key,
+                    pool_key,
+                    destination_key,
+                    &self.pool_fee_key,
+                    &self.token_keys,
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                )
+                .unwrap(),
+                accounts,
+            )
+        }
+
+        fn balances(&self) -> Vec<u128> {
+            self.token_accounts
+                .iter()
+                .map(|account| {
+                    spl_token::state::Account::unpack(&account.data)
+                        .unwrap()
+                        .amount as u128
+                })
+                .collect()
+        }
+
+        fn pool_supply(&self) -> u128 {
+            spl_token::state::Mint::unpack(&self.pool_mint_account.data)
+                .unwrap()
+                .supply as u128
+        }
+    }
+
+    struct ConcentratedSwapAccountInfo {
+        nonce: u8,
+        authority_key: Pubkey,
+        fees: Fees,
+        tick_spacing: u16,
+        sqrt_price: u128,
+        min_position_liquidity: u128,
+        max_ticks: u16,
+        swap_key: Pubkey,
+        swap_account: Account,
         token_a_key: Pubkey,
         token_a_account: Account,
         token_a_mint_key: Pubkey,
@@ -1271,73 +4698,72 @@ mod tests {
         token_b_account: Account,
         token_b_mint_key: Pubkey,
         token_b_mint_account: Account,
+        fee_a_key: Pubkey,
+        fee_a_account: Account,
+        fee_b_key: Pubkey,
+        fee_b_account: Account,
+        admin_key: Option<Pubkey>,
     }
 
-    impl SwapAccountInfo {
-        pub fn new(
-            user_key: &Pubkey,
-            fees: Fees,
-            swap_curve: SwapCurve,
-            token_a_amount: u64,
-            token_b_amount: u64,
-        ) -> Self {
+    impl ConcentratedSwapAccountInfo {
+        pub fn new(user_key: &Pubkey, fees: Fees, tick_spacing: u16, sqrt_price: u128) -> Self {
             let swap_key = Pubkey::new_unique();
-            let swap_account = Account::new(0, SwapVersion::LATEST_LEN, &SWAP_PROGRAM_ID);
+            let max_ticks = 64;
+            let swap_account = Account::new(
+                0,
+                SwapVersion::concentrated_len(max_ticks),
+                &SWAP_PROGRAM_ID,
+            );
             let (authority_key, nonce) =
                 Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
 
-            let (pool_mint_key, mut pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &authority_key, None);
-            let (pool_token_key, pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &pool_mint_key,
-                &mut pool_mint_account,
-                &authority_key,
-                &user_key,
-                0,
-            );
-            let (pool_fee_key, pool_fee_account) = mint_token(
+            let (token_a_mint_key, mut token_a_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, user_key, None);
+            let (token_a_key, token_a_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
-                &pool_mint_key,
-                &mut pool_mint_account,
+                &token_a_mint_key,
+                &mut token_a_mint_account,
+                user_key,
                 &authority_key,
-                &user_key,
                 0,
             );
-            let (token_a_mint_key, mut token_a_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
-            let (token_a_key, token_a_account) = mint_token(
+            let (fee_a_key, fee_a_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &token_a_mint_key,
                 &mut token_a_mint_account,
-                &user_key,
-                &authority_key,
-                token_a_amount,
+                user_key,
+                user_key,
+                0,
             );
             let (token_b_mint_key, mut token_b_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+                create_mint(&TOKEN_PROGRAM_ID, user_key, None);
             let (token_b_key, token_b_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &token_b_mint_key,
                 &mut token_b_mint_account,
-                &user_key,
+                user_key,
                 &authority_key,
-                token_b_amount,
+                0,
+            );
+            let (fee_b_key, fee_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &token_b_mint_key,
+                &mut token_b_mint_account,
+                user_key,
+                user_key,
+                0,
             );
 
-            SwapAccountInfo {
+            ConcentratedSwapAccountInfo {
                 nonce,
                 authority_key,
                 fees,
-                swap_curve,
+                tick_spacing,
+                sqrt_price,
+                min_position_liquidity: 1_000_000,
+                max_ticks,
                 swap_key,
                 swap_account,
-                pool_mint_key,
-                pool_mint_account,
-                pool_fee_key,
-                pool_fee_account,
-                pool_token_key,
-                pool_token_account,
                 token_a_key,
                 token_a_account,
                 token_a_mint_key,
@@ -1346,24 +4772,32 @@ mod tests {
                 token_b_account,
                 token_b_mint_key,
                 token_b_mint_account,
+                fee_a_key,
+                fee_a_account,
+                fee_b_key,
+                fee_b_account,
+                admin_key: None,
             }
         }
 
         pub fn initialize_swap(&mut self) -> ProgramResult {
             do_process_instruction(
-                initialize(
+                initialize_concentrated(
                     &SWAP_PROGRAM_ID,
                     &TOKEN_PROGRAM_ID,
                     &self.swap_key,
                     &self.authority_key,
                     &self.token_a_key,
                     &self.token_b_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &self.pool_token_key,
+                    &self.fee_a_key,
+                    &self.fee_b_key,
+                    self.admin_key.as_ref(),
                     self.nonce,
                     self.fees.clone(),
-                    self.swap_curve.clone(),
+                    self.tick_spacing,
+                    self.sqrt_price,
+                    self.min_position_liquidity,
+                    self.max_ticks,
                 )
                 .unwrap(),
                 vec![
@@ -1371,9 +4805,8 @@ mod tests {
                     &mut Account::default(),
                     &mut self.token_a_account,
                     &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut self.pool_token_account,
+                    &mut self.fee_a_account,
+                    &mut self.fee_b_account,
                     &mut Account::default(),
                 ],
             )
@@ -1383,207 +4816,92 @@ mod tests {
             &mut self,
             mint_owner: &Pubkey,
             account_owner: &Pubkey,
-            a_amount: u64,
-            b_amount: u64,
-            pool_amount: u64,
-        ) -> (Pubkey, Account, Pubkey, Account, Pubkey, Account) {
+            token_a_amount: u64,
+            token_b_amount: u64,
+        ) -> (Pubkey, Account, Pubkey, Account) {
             let (token_a_key, token_a_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &self.token_a_mint_key,
                 &mut self.token_a_mint_account,
-                &mint_owner,
-                &account_owner,
-                a_amount,
+                mint_owner,
+                account_owner,
+                token_a_amount,
             );
             let (token_b_key, token_b_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &self.token_b_mint_key,
                 &mut self.token_b_mint_account,
-                &mint_owner,
-                &account_owner,
-                b_amount,
-            );
-            let (pool_key, pool_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &self.pool_mint_key,
-                &mut self.pool_mint_account,
-                &self.authority_key,
-                &account_owner,
-                pool_amount,
+                mint_owner,
+                account_owner,
+                token_b_amount,
             );
-            (
-                token_a_key,
-                token_a_account,
-                token_b_key,
-                token_b_account,
-                pool_key,
-                pool_account,
-            )
-        }
-
-        fn get_token_account(&self, account_key: &Pubkey) -> &Account {
-            if *account_key == self.token_a_key {
-                return &self.token_a_account;
-            } else if *account_key == self.token_b_key {
-                return &self.token_b_account;
-            }
-            panic!("Could not find matching swap token account");
+            (token_a_key, token_a_account, token_b_key, token_b_account)
         }
 
-        fn set_token_account(&mut self, account_key: &Pubkey, account: Account) {
-            if *account_key == self.token_a_key {
-                self.token_a_account = account;
-                return;
-            } else if *account_key == self.token_b_key {
-                self.token_b_account = account;
-                return;
-            }
-            panic!("Could not find matching swap token account");
+        fn token_swap(&self) -> ConcentratedSwapV1 {
+            SwapVersion::unpack_concentrated(&self.swap_account.data).unwrap()
         }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn swap(
+        pub fn open_position(
             &mut self,
-            user_key: &Pubkey,
-            user_source_key: &Pubkey,
-            mut user_source_account: &mut Account,
-            swap_source_key: &Pubkey,
-            swap_destination_key: &Pubkey,
-            user_destination_key: &Pubkey,
-            mut user_destination_account: &mut Account,
-            amount_in: u64,
-            minimum_amount_out: u64,
-        ) -> ProgramResult {
-            let user_transfer_key = Pubkey::new_unique();
-            // approve moving from user source account
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &user_source_key,
-                    &user_transfer_key,
-                    &user_key,
-                    &[],
-                    amount_in,
-                )
-                .unwrap(),
-                vec![
-                    &mut user_source_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-
-            let mut swap_source_account = self.get_token_account(swap_source_key).clone();
-            let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
-
-            // perform the swap
+            owner_key: &Pubkey,
+            tick_lower: i32,
+            tick_upper: i32,
+        ) -> Result<(Pubkey, Account), ProgramError> {
+            let position_key = Pubkey::new_unique();
+            let mut position_account =
+                Account::new(1_000, ConcentratedPosition::LEN, &SWAP_PROGRAM_ID);
             do_process_instruction(
-                swap(
+                open_position(
                     &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
                     &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_key,
-                    &user_source_key,
-                    &swap_source_key,
-                    &swap_destination_key,
-                    &user_destination_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    None,
-                    Swap {
-                        amount_in,
-                        minimum_amount_out,
+                    &position_key,
+                    owner_key,
+                    OpenPosition {
+                        tick_lower,
+                        tick_upper,
                     },
                 )
                 .unwrap(),
                 vec![
                     &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut user_source_account,
-                    &mut swap_source_account,
-                    &mut swap_destination_account,
-                    &mut user_destination_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
+                    &mut position_account,
                     &mut Account::default(),
                 ],
             )?;
-
-            self.set_token_account(swap_source_key, swap_source_account);
-            self.set_token_account(swap_destination_key, swap_destination_account);
-
-            Ok(())
+            Ok((position_key, position_account))
         }
 
         #[allow(clippy::too_many_arguments)]
-        pub fn deposit_all_token_types(
+        pub fn increase_liquidity(
             &mut self,
-            depositor_key: &Pubkey,
-            depositor_token_a_key: &Pubkey,
-            mut depositor_token_a_account: &mut Account,
-            depositor_token_b_key: &Pubkey,
-            mut depositor_token_b_account: &mut Account,
-            depositor_pool_key: &Pubkey,
-            mut depositor_pool_account: &mut Account,
-            pool_token_amount: u64,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut Account,
+            source_a_key: &Pubkey,
+            source_a_account: &mut Account,
+            source_b_key: &Pubkey,
+            source_b_account: &mut Account,
+            liquidity: u128,
             maximum_token_a_amount: u64,
             maximum_token_b_amount: u64,
         ) -> ProgramResult {
-            let user_transfer_authority = Pubkey::new_unique();
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &depositor_token_a_key,
-                    &user_transfer_authority,
-                    &depositor_key,
-                    &[],
-                    maximum_token_a_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut depositor_token_a_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &depositor_token_b_key,
-                    &user_transfer_authority,
-                    &depositor_key,
-                    &[],
-                    maximum_token_b_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut depositor_token_b_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-
+            // the owner of the source accounts transfers directly
             do_process_instruction(
-                deposit_all_token_types(
+                increase_liquidity(
                     &SWAP_PROGRAM_ID,
                     &TOKEN_PROGRAM_ID,
                     &self.swap_key,
                     &self.authority_key,
-                    &user_transfer_authority,
-                    &depositor_token_a_key,
-                    &depositor_token_b_key,
+                    owner_key,
+                    position_key,
+                    owner_key,
+                    source_a_key,
+                    source_b_key,
                     &self.token_a_key,
                     &self.token_b_key,
-                    &self.pool_mint_key,
-                    &depositor_pool_key,
-                    DepositAllTokenTypes {
-                        pool_token_amount,
+                    IncreaseLiquidity {
+                        liquidity,
                         maximum_token_a_amount,
                         maximum_token_b_amount,
                     },
@@ -1593,68 +4911,45 @@ mod tests {
                     &mut self.swap_account,
                     &mut Account::default(),
                     &mut Account::default(),
-                    &mut depositor_token_a_account,
-                    &mut depositor_token_b_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut depositor_pool_account,
-                    &mut Account::default(),
-                ],
-            )
-        }
-
-        #[allow(clippy::too_many_arguments)]
-        pub fn withdraw_all_token_types(
-            &mut self,
-            user_key: &Pubkey,
-            pool_key: &Pubkey,
-            mut pool_account: &mut Account,
-            token_a_key: &Pubkey,
-            mut token_a_account: &mut Account,
-            token_b_key: &Pubkey,
-            mut token_b_account: &mut Account,
-            pool_token_amount: u64,
-            minimum_token_a_amount: u64,
-            minimum_token_b_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            // approve user transfer authority to take out pool tokens
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &pool_key,
-                    &user_transfer_authority_key,
-                    &user_key,
-                    &[],
-                    pool_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut pool_account,
+                    position_account,
                     &mut Account::default(),
+                    source_a_account,
+                    source_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
                     &mut Account::default(),
                 ],
             )
-            .unwrap();
+        }
 
-            // withdraw token a and b correctly
+        #[allow(clippy::too_many_arguments)]
+        pub fn decrease_liquidity(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut Account,
+            destination_a_key: &Pubkey,
+            destination_a_account: &mut Account,
+            destination_b_key: &Pubkey,
+            destination_b_account: &mut Account,
+            liquidity: u128,
+            minimum_token_a_amount: u64,
+            minimum_token_b_amount: u64,
+        ) -> ProgramResult {
             do_process_instruction(
-                withdraw_all_token_types(
+                decrease_liquidity(
                     &SWAP_PROGRAM_ID,
                     &TOKEN_PROGRAM_ID,
                     &self.swap_key,
                     &self.authority_key,
-                    &user_transfer_authority_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &pool_key,
+                    position_key,
+                    owner_key,
                     &self.token_a_key,
                     &self.token_b_key,
-                    &token_a_key,
-                    &token_b_key,
-                    WithdrawAllTokenTypes {
-                        pool_token_amount,
+                    destination_a_key,
+                    destination_b_key,
+                    DecreaseLiquidity {
+                        liquidity,
                         minimum_token_a_amount,
                         minimum_token_b_amount,
                     },
@@ -1663,128 +4958,109 @@ mod tests {
                 vec![
                     &mut self.swap_account,
                     &mut Account::default(),
+                    position_account,
                     &mut Account::default(),
-                    &mut self.pool_mint_account,
-                    &mut pool_account,
                     &mut self.token_a_account,
                     &mut self.token_b_account,
-                    &mut token_a_account,
-                    &mut token_b_account,
-                    &mut self.pool_fee_account,
+                    destination_a_account,
+                    destination_b_account,
                     &mut Account::default(),
                 ],
             )
         }
 
         #[allow(clippy::too_many_arguments)]
-        pub fn deposit_single_token_type_exact_amount_in(
+        pub fn collect_fees(
             &mut self,
-            depositor_key: &Pubkey,
-            deposit_account_key: &Pubkey,
-            mut deposit_token_account: &mut Account,
-            deposit_pool_key: &Pubkey,
-            mut deposit_pool_account: &mut Account,
-            source_token_amount: u64,
-            minimum_pool_token_amount: u64,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut Account,
+            destination_a_key: &Pubkey,
+            destination_a_account: &mut Account,
+            destination_b_key: &Pubkey,
+            destination_b_account: &mut Account,
         ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &deposit_account_key,
-                    &user_transfer_authority_key,
-                    &depositor_key,
-                    &[],
-                    source_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut deposit_token_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-
             do_process_instruction(
-                deposit_single_token_type_exact_amount_in(
+                collect_fees(
                     &SWAP_PROGRAM_ID,
                     &TOKEN_PROGRAM_ID,
                     &self.swap_key,
                     &self.authority_key,
-                    &user_transfer_authority_key,
-                    &deposit_account_key,
+                    position_key,
+                    owner_key,
                     &self.token_a_key,
                     &self.token_b_key,
-                    &self.pool_mint_key,
-                    &deposit_pool_key,
-                    DepositSingleTokenTypeExactAmountIn {
-                        source_token_amount,
-                        minimum_pool_token_amount,
-                    },
+                    destination_a_key,
+                    destination_b_key,
                 )
                 .unwrap(),
                 vec![
                     &mut self.swap_account,
                     &mut Account::default(),
+                    position_account,
                     &mut Account::default(),
-                    &mut deposit_token_account,
                     &mut self.token_a_account,
                     &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut deposit_pool_account,
+                    destination_a_account,
+                    destination_b_account,
                     &mut Account::default(),
                 ],
             )
         }
 
         #[allow(clippy::too_many_arguments)]
-        pub fn withdraw_single_token_type_exact_amount_out(
+        pub fn swap(
             &mut self,
             user_key: &Pubkey,
-            pool_key: &Pubkey,
-            mut pool_account: &mut Account,
-            destination_key: &Pubkey,
-            mut destination_account: &mut Account,
-            destination_token_amount: u64,
-            maximum_pool_token_amount: u64,
+            trade_direction: TradeDirection,
+            user_source_key: &Pubkey,
+            user_source_account: &mut Account,
+            user_destination_key: &Pubkey,
+            user_destination_account: &mut Account,
+            amount_in: u64,
+            minimum_amount_out: u64,
         ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            // approve user transfer authority to take out pool tokens
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &pool_key,
-                    &user_transfer_authority_key,
-                    &user_key,
-                    &[],
-                    maximum_pool_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut pool_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-
+            let (
+                swap_source_key,
+                swap_source_account,
+                swap_destination_key,
+                swap_destination_account,
+                fee_key,
+                fee_account,
+            ) = match trade_direction {
+                TradeDirection::AtoB => (
+                    &self.token_a_key,
+                    &mut self.token_a_account,
+                    &self.token_b_key,
+                    &mut self.token_b_account,
+                    &self.fee_a_key,
+                    &mut self.fee_a_account,
+                ),
+                TradeDirection::BtoA => (
+                    &self.token_b_key,
+                    &mut self.token_b_account,
+                    &self.token_a_key,
+                    &mut self.token_a_account,
+                    &self.fee_b_key,
+                    &mut self.fee_b_account,
+                ),
+            };
             do_process_instruction(
-                withdraw_single_token_type_exact_amount_out(
+                concentrated_swap(
                     &SWAP_PROGRAM_ID,
                     &TOKEN_PROGRAM_ID,
                     &self.swap_key,
                     &self.authority_key,
-                    &user_transfer_authority_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &pool_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &destination_key,
-                    WithdrawSingleTokenTypeExactAmountOut {
-                        destination_token_amount,
-                        maximum_pool_token_amount,
+                    user_key,
+                    user_source_key,
+                    swap_source_key,
+                    swap_destination_key,
+                    user_destination_key,
+                    fee_key,
+                    None,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
                     },
                 )
                 .unwrap(),
@@ -1792,12 +5068,11 @@ mod tests {
                     &mut self.swap_account,
                     &mut Account::default(),
                     &mut Account::default(),
-                    &mut self.pool_mint_account,
-                    &mut pool_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut destination_account,
-                    &mut self.pool_fee_account,
+                    user_source_account,
+                    swap_source_account,
+                    swap_destination_account,
+                    user_destination_account,
+                    fee_account,
                     &mut Account::default(),
                 ],
             )
@@ -2014,836 +5289,1451 @@ mod tests {
             let old_nonce = accounts.nonce;
             accounts.nonce = old_nonce - 1;
             assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.initialize_swap()
+            );
+            accounts.nonce = old_nonce;
+        }
+
+        // uninitialized token a account
+        {
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            assert_eq!(
+                Err(SwapError::ExpectedAccount.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // uninitialized token b account
+        {
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            assert_eq!(
+                Err(SwapError::ExpectedAccount.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // uninitialized pool mint
+        {
+            let old_account = accounts.pool_mint_account;
+            accounts.pool_mint_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            assert_eq!(
+                Err(SwapError::ExpectedMint.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_mint_account = old_account;
+        }
+
+        // token A account owner is not swap authority
+        {
+            let (_token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // token B account owner is not swap authority
+        {
+            let (_token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
+                &user_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // pool token account owner is swap authority
+        {
+            let (_pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                0,
+            );
+            let old_account = accounts.pool_token_account;
+            accounts.pool_token_account = pool_token_account;
+            assert_eq!(
+                Err(SwapError::InvalidOutputOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_token_account = old_account;
+        }
+
+        // pool fee account owner is swap authority
+        {
+            let (_pool_fee_key, pool_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                0,
+            );
+            let old_account = accounts.pool_fee_account;
+            accounts.pool_fee_account = pool_fee_account;
+            assert_eq!(
+                Err(SwapError::InvalidOutputOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_fee_account = old_account;
+        }
+
+        // pool mint authority is not swap authority
+        {
+            let (_pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_mint_account = old_mint;
+        }
+
+        // pool mint token has freeze authority
+        {
+            let (_pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, Some(&user_key));
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
+            assert_eq!(
+                Err(SwapError::InvalidFreezeAuthority.into()),
                 accounts.initialize_swap()
             );
-            accounts.nonce = old_nonce;
+            accounts.pool_mint_account = old_mint;
         }
 
-        // uninitialized token a account
+        // token A account owned by wrong program
         {
+            let (_token_a_key, mut token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                token_a_amount,
+            );
+            token_a_account.owner = SWAP_PROGRAM_ID;
             let old_account = accounts.token_a_account;
-            accounts.token_a_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            accounts.token_a_account = token_a_account;
             assert_eq!(
-                Err(SwapError::ExpectedAccount.into()),
+                Err(SwapError::IncorrectTokenProgramId.into()),
                 accounts.initialize_swap()
             );
             accounts.token_a_account = old_account;
         }
 
-        // uninitialized token b account
+        // token B account owned by wrong program
         {
+            let (_token_b_key, mut token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                token_b_amount,
+            );
+            token_b_account.owner = SWAP_PROGRAM_ID;
             let old_account = accounts.token_b_account;
-            accounts.token_b_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            accounts.token_b_account = token_b_account;
             assert_eq!(
-                Err(SwapError::ExpectedAccount.into()),
+                Err(SwapError::IncorrectTokenProgramId.into()),
                 accounts.initialize_swap()
             );
             accounts.token_b_account = old_account;
         }
 
-        // uninitialized pool mint
-        {
-            let old_account = accounts.pool_mint_account;
-            accounts.pool_mint_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
-            assert_eq!(
-                Err(SwapError::ExpectedMint.into()),
-                accounts.initialize_swap()
-            );
-            accounts.pool_mint_account = old_account;
-        }
-
-        // token A account owner is not swap authority
+        // empty token A account
         {
             let (_token_a_key, token_a_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &accounts.token_a_mint_key,
                 &mut accounts.token_a_mint_account,
                 &user_key,
-                &user_key,
+                &accounts.authority_key,
                 0,
             );
             let old_account = accounts.token_a_account;
             accounts.token_a_account = token_a_account;
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
+                Err(SwapError::EmptySupply.into()),
                 accounts.initialize_swap()
             );
             accounts.token_a_account = old_account;
         }
 
-        // token B account owner is not swap authority
+        // empty token B account
         {
             let (_token_b_key, token_b_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &accounts.token_b_mint_key,
                 &mut accounts.token_b_mint_account,
                 &user_key,
-                &user_key,
+                &accounts.authority_key,
                 0,
             );
             let old_account = accounts.token_b_account;
             accounts.token_b_account = token_b_account;
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
+                Err(SwapError::EmptySupply.into()),
                 accounts.initialize_swap()
             );
             accounts.token_b_account = old_account;
         }
 
-        // pool token account owner is swap authority
+        // invalid pool tokens
         {
-            let (_pool_token_key, pool_token_account) = mint_token(
+            let old_mint = accounts.pool_mint_account;
+            let old_pool_account = accounts.pool_token_account;
+
+            let (_pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            accounts.pool_mint_account = pool_mint_account;
+
+            let (_empty_pool_token_key, empty_pool_token_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
                 &accounts.pool_mint_key,
                 &mut accounts.pool_mint_account,
                 &accounts.authority_key,
-                &accounts.authority_key,
+                &user_key,
                 0,
             );
-            let old_account = accounts.pool_token_account;
+
+            let (_pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &user_key,
+                pool_token_amount,
+            );
+
+            // non-empty pool token account
             accounts.pool_token_account = pool_token_account;
             assert_eq!(
-                Err(SwapError::InvalidOutputOwner.into()),
+                Err(SwapError::InvalidSupply.into()),
                 accounts.initialize_swap()
             );
-            accounts.pool_token_account = old_account;
+
+            // pool tokens already in circulation
+            accounts.pool_token_account = empty_pool_token_account;
+            assert_eq!(
+                Err(SwapError::InvalidSupply.into()),
+                accounts.initialize_swap()
+            );
+
+            accounts.pool_mint_account = old_mint;
+            accounts.pool_token_account = old_pool_account;
         }
 
-        // pool fee account owner is swap authority
+        // pool fee account has wrong mint
         {
             let (_pool_fee_key, pool_fee_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &accounts.authority_key,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &user_key,
                 0,
             );
             let old_account = accounts.pool_fee_account;
             accounts.pool_fee_account = pool_fee_account;
             assert_eq!(
-                Err(SwapError::InvalidOutputOwner.into()),
+                Err(SwapError::IncorrectPoolMint.into()),
                 accounts.initialize_swap()
             );
             accounts.pool_fee_account = old_account;
         }
 
-        // pool mint authority is not swap authority
+        // token A account is delegated
         {
-            let (_pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
-            let old_mint = accounts.pool_mint_account;
-            accounts.pool_mint_account = pool_mint_account;
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.token_a_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
+                Err(SwapError::InvalidDelegate.into()),
                 accounts.initialize_swap()
             );
-            accounts.pool_mint_account = old_mint;
+
+            do_process_instruction(
+                revoke(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut Account::default()],
+            )
+            .unwrap();
         }
 
-        // pool mint token has freeze authority
+        // token B account is delegated
         {
-            let (_pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, Some(&user_key));
-            let old_mint = accounts.pool_mint_account;
-            accounts.pool_mint_account = pool_mint_account;
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.token_b_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
             assert_eq!(
-                Err(SwapError::InvalidFreezeAuthority.into()),
+                Err(SwapError::InvalidDelegate.into()),
                 accounts.initialize_swap()
             );
-            accounts.pool_mint_account = old_mint;
+
+            do_process_instruction(
+                revoke(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+
+        // token A account has close authority
+        {
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    Some(&user_key),
+                    AuthorityType::CloseAccount,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut Account::default()],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &user_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+
+        // token B account has close authority
+        {
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    Some(&user_key),
+                    AuthorityType::CloseAccount,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut Account::default()],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &user_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut Account::default()],
+            )
+            .unwrap();
         }
 
-        // token A account owned by wrong program
+        // wrong token program id
         {
-            let (_token_a_key, mut token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                token_a_amount,
-            );
-            token_a_account.owner = SWAP_PROGRAM_ID;
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
+            let wrong_program_id = Pubkey::new_unique();
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
-                accounts.initialize_swap()
+                do_process_instruction(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.nonce,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
+                    ],
+                )
             );
-            accounts.token_a_account = old_account;
         }
 
-        // token B account owned by wrong program
+        // create swap with same token A and B
         {
-            let (_token_b_key, mut token_b_account) = mint_token(
+            let (_token_a_repeat_key, token_a_repeat_account) = mint_token(
                 &TOKEN_PROGRAM_ID,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
                 &user_key,
                 &accounts.authority_key,
-                token_b_amount,
+                10,
             );
-            token_b_account.owner = SWAP_PROGRAM_ID;
             let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
+            accounts.token_b_account = token_a_repeat_account;
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
+                Err(SwapError::RepeatedMint.into()),
                 accounts.initialize_swap()
             );
             accounts.token_b_account = old_account;
         }
 
-        // empty token A account
+        // create valid swap
+        accounts.initialize_swap().unwrap();
+
+        // create invalid flat swap
         {
-            let (_token_a_key, token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
+            let token_b_price = 0;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve { token_b_price }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
             assert_eq!(
-                Err(SwapError::EmptySupply.into()),
+                Err(SwapError::InvalidCurve.into()),
                 accounts.initialize_swap()
             );
-            accounts.token_a_account = old_account;
         }
 
-        // empty token B account
+        // create valid flat swap
         {
-            let (_token_b_key, token_b_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let token_b_price = 10_000;
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve { token_b_price }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            accounts.initialize_swap().unwrap();
+        }
+
+        // create invalid offset swap
+        {
+            let token_b_offset = 0;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Offset,
+                calculator: Box::new(OffsetCurve { token_b_offset }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
             assert_eq!(
-                Err(SwapError::EmptySupply.into()),
+                Err(SwapError::InvalidCurve.into()),
                 accounts.initialize_swap()
             );
-            accounts.token_b_account = old_account;
         }
 
-        // invalid pool tokens
+        // create valid offset swap
         {
-            let old_mint = accounts.pool_mint_account;
-            let old_pool_account = accounts.pool_token_account;
-
-            let (_pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            accounts.pool_mint_account = pool_mint_account;
-
-            let (_empty_pool_token_key, empty_pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &user_key,
-                0,
-            );
+            let token_b_offset = 10;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Offset,
+                calculator: Box::new(OffsetCurve { token_b_offset }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            accounts.initialize_swap().unwrap();
+        }
 
-            let (_pool_token_key, pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
+        // wrong owner key in constraint
+        {
+            let new_key = Pubkey::new_unique();
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(curve),
+            };
+            let owner_key = &new_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                valid_multi_token_curve_types: &[],
+                fees: &fees,
+            });
+            let mut accounts = SwapAccountInfo::new(
                 &user_key,
-                pool_token_amount,
-            );
-
-            // non-empty pool token account
-            accounts.pool_token_account = pool_token_account;
-            assert_eq!(
-                Err(SwapError::InvalidSupply.into()),
-                accounts.initialize_swap()
+                fees.clone(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
             );
-
-            // pool tokens already in circulation
-            accounts.pool_token_account = empty_pool_token_account;
             assert_eq!(
-                Err(SwapError::InvalidSupply.into()),
-                accounts.initialize_swap()
+                Err(SwapError::InvalidOwner.into()),
+                do_process_instruction_with_fee_constraints(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.nonce,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
+                    ],
+                    &constraints,
+                )
             );
-
-            accounts.pool_mint_account = old_mint;
-            accounts.pool_token_account = old_pool_account;
         }
 
-        // pool fee account has wrong mint
+        // wrong fee in constraint
         {
-            let (_pool_fee_key, pool_fee_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(curve),
+            };
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                valid_multi_token_curve_types: &[],
+                fees: &fees,
+            });
+            let mut bad_fees = fees.clone();
+            bad_fees.trade_fee_numerator = trade_fee_numerator - 1;
+            let mut accounts = SwapAccountInfo::new(
                 &user_key,
-                0,
+                bad_fees,
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
             );
-            let old_account = accounts.pool_fee_account;
-            accounts.pool_fee_account = pool_fee_account;
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.initialize_swap()
-            );
-            accounts.pool_fee_account = old_account;
-        }
-
-        // token A account is delegated
-        {
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction_with_fee_constraints(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.nonce,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
+                    ],
+                    &constraints,
                 )
-                .unwrap(),
-                vec![
-                    &mut accounts.token_a_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
             );
-
-            do_process_instruction(
-                revoke(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut Account::default()],
-            )
-            .unwrap();
         }
 
-        // token B account is delegated
+        // create valid swap with constraints
         {
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
-                )
-                .unwrap(),
-                vec![
-                    &mut accounts.token_b_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(curve),
+            };
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                valid_multi_token_curve_types: &[],
+                fees: &fees,
+            });
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
             );
-
-            do_process_instruction(
-                revoke(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_b_account, &mut Account::default()],
-            )
-            .unwrap();
-        }
-
-        // token A account has close authority
-        {
-            do_process_instruction(
-                set_authority(
+            do_process_instruction_with_fee_constraints(
+                initialize(
+                    &SWAP_PROGRAM_ID,
                     &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    Some(&user_key),
-                    AuthorityType::CloseAccount,
+                    &accounts.swap_key,
                     &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut Account::default()],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
-                accounts.initialize_swap()
-            );
-
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
                     &accounts.token_a_key,
-                    None,
-                    AuthorityType::CloseAccount,
-                    &user_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut Account::default()],
-            )
-            .unwrap();
-        }
-
-        // token B account has close authority
-        {
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
                     &accounts.token_b_key,
-                    Some(&user_key),
-                    AuthorityType::CloseAccount,
-                    &accounts.authority_key,
-                    &[],
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    &accounts.pool_token_key,
+                    accounts.nonce,
+                    accounts.fees,
+                    accounts.swap_curve.clone(),
                 )
                 .unwrap(),
-                vec![&mut accounts.token_b_account, &mut Account::default()],
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut Account::default(),
+                ],
+                &constraints,
             )
             .unwrap();
+        }
+
+        // create again
+        {
             assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
+                Err(SwapError::AlreadyInUse.into()),
                 accounts.initialize_swap()
             );
+        }
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.is_initialized(), true);
+        assert_eq!(swap_state.nonce(), accounts.nonce);
+        assert_eq!(
+            swap_state.swap_curve().curve_type,
+            accounts.swap_curve.curve_type
+        );
+        assert_eq!(*swap_state.token_a_account(), accounts.token_a_key);
+        assert_eq!(*swap_state.token_b_account(), accounts.token_b_key);
+        assert_eq!(*swap_state.pool_mint(), accounts.pool_mint_key);
+        assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
+        assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
+        assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
+        let token_a = spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, token_a_amount);
+        let token_b = spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(token_b.amount, token_b_amount);
+        let pool_account =
+            spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        assert_eq!(pool_mint.supply, pool_account.amount);
+    }
 
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    None,
-                    AuthorityType::CloseAccount,
-                    &user_key,
-                    &[],
+    #[test]
+    fn test_deposit() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 9000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        // depositing 10% of the current pool amount in token A and B means
+        // that our pool tokens will be worth 1 / 10 of the current pool amount
+        let pool_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(ProgramError::UninitializedAccount),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
-                .unwrap(),
-                vec![&mut accounts.token_b_account, &mut Account::default()],
-            )
-            .unwrap();
+            );
         }
 
-        // wrong token program id
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
         {
-            let wrong_program_id = Pubkey::new_unique();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
+            accounts.swap_account = wrong_swap_account;
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    initialize(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.nonce,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
-                        &mut Account::default(),
-                    ],
+                Err(ProgramError::IncorrectProgramId),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
+            accounts.swap_account = old_swap_account;
         }
 
-        // create swap with same token A and B
+        // wrong nonce for authority_key
         {
-            let (_token_a_repeat_key, token_a_repeat_account) = mint_token(
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
                 &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                10,
             );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_a_repeat_account;
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
-                Err(SwapError::RepeatedMint.into()),
-                accounts.initialize_swap()
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.token_b_account = old_account;
+            accounts.authority_key = old_authority;
         }
 
-        // create valid swap
-        accounts.initialize_swap().unwrap();
-
-        // create invalid flat swap
+        // not enough token A
         {
-            let token_b_price = 0;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantPrice,
-                calculator: Box::new(ConstantPriceCurve { token_b_price }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a / 2,
+                deposit_b,
+                0,
+            );
             assert_eq!(
-                Err(SwapError::InvalidCurve.into()),
-                accounts.initialize_swap()
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
         }
 
-        // create valid flat swap
+        // not enough token B
         {
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let token_b_price = 10_000;
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantPrice,
-                calculator: Box::new(ConstantPriceCurve { token_b_price }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-            accounts.initialize_swap().unwrap();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a,
+                deposit_b / 2,
+                0,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
         }
 
-        // create invalid offset swap
+        // wrong swap token accounts
         {
-            let token_b_offset = 0;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::Offset,
-                calculator: Box::new(OffsetCurve { token_b_offset }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
-                Err(SwapError::InvalidCurve.into()),
-                accounts.initialize_swap()
+                Err(TokenError::MintMismatch.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
         }
 
-        // create valid offset swap
+        // wrong pool token account
         {
-            let token_b_offset = 10;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::Offset,
-                calculator: Box::new(OffsetCurve { token_b_offset }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-            accounts.initialize_swap().unwrap();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (
+                wrong_token_key,
+                mut wrong_token_account,
+                _token_b_key,
+                mut _token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(TokenError::MintMismatch.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &wrong_token_key,
+                    &mut wrong_token_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
         }
 
-        // wrong owner key in constraint
+        // no approval
         {
-            let new_key = Pubkey::new_unique();
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Box::new(curve),
-            };
-            let owner_key = &new_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees.clone(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-            );
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                do_process_instruction_with_fee_constraints(
-                    initialize(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    deposit_all_token_types(
                         &SWAP_PROGRAM_ID,
                         &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
                         &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &token_a_key,
+                        &token_b_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.nonce,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
+                        &pool_key,
+                        DepositAllTokenTypes {
+                            pool_token_amount: pool_amount.try_into().unwrap(),
+                            maximum_token_a_amount: deposit_a,
+                            maximum_token_b_amount: deposit_b,
+                        },
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut token_b_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
+                        &mut pool_account,
                         &mut Account::default(),
                     ],
-                    &constraints,
                 )
             );
         }
 
-        // wrong fee in constraint
+        // wrong token program id
         {
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Box::new(curve),
-            };
-            let owner_key = &user_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut bad_fees = fees.clone();
-            bad_fees.trade_fee_numerator = trade_fee_numerator - 1;
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                bad_fees,
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-            );
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let wrong_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::InvalidFee.into()),
-                do_process_instruction_with_fee_constraints(
-                    initialize(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    deposit_all_token_types(
                         &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
+                        &wrong_key,
                         &accounts.swap_key,
                         &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &token_b_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.nonce,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
+                        &pool_key,
+                        DepositAllTokenTypes {
+                            pool_token_amount: pool_amount.try_into().unwrap(),
+                            maximum_token_a_amount: deposit_a,
+                            maximum_token_b_amount: deposit_b,
+                        },
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut token_b_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
+                        &mut pool_account,
                         &mut Account::default(),
                     ],
-                    &constraints,
                 )
             );
         }
 
-        // create valid swap with constraints
+        // wrong swap token accounts
         {
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Box::new(curve),
-            };
-            let owner_key = &user_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees.clone(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            do_process_instruction_with_fee_constraints(
-                initialize(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.swap_key,
-                    &accounts.authority_key,
-                    &accounts.token_a_key,
-                    &accounts.token_b_key,
-                    &accounts.pool_mint_key,
-                    &accounts.pool_fee_key,
-                    &accounts.pool_token_key,
-                    accounts.nonce,
-                    accounts.fees,
-                    accounts.swap_curve.clone(),
+
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account.clone();
+
+            // wrong swap token b account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
+        }
+
+        // wrong mint
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
+        }
+
+        // deposit 1 pool token fails beacuse it equates to 0 swap tokens
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    1,
+                    deposit_a,
+                    deposit_b,
+                )
+            );
+        }
+
+        // slippage exceeded
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            // maximum A amount in too low
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a / 10,
+                    deposit_b,
+                )
+            );
+            // maximum B amount in too low
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b / 10,
+                )
+            );
+        }
+
+        // invalid input: can't use swap pool tokens as source
+        {
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let authority_key = accounts.authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_all_token_types(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
-                .unwrap(),
-                vec![
-                    &mut accounts.swap_account,
-                    &mut Account::default(),
-                    &mut accounts.token_a_account,
-                    &mut accounts.token_b_account,
-                    &mut accounts.pool_mint_account,
-                    &mut accounts.pool_fee_account,
-                    &mut accounts.pool_token_account,
-                    &mut Account::default(),
-                ],
-                &constraints,
-            )
-            .unwrap();
+            );
         }
 
-        // create again
+        // correctly deposit
         {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            accounts
+                .deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, 0);
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(token_b.amount, 0);
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+            let swap_pool_account =
+                spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
             assert_eq!(
-                Err(SwapError::AlreadyInUse.into()),
-                accounts.initialize_swap()
+                pool_mint.supply,
+                pool_account.amount + swap_pool_account.amount
             );
         }
-        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
-        assert_eq!(swap_state.is_initialized(), true);
-        assert_eq!(swap_state.nonce(), accounts.nonce);
-        assert_eq!(
-            swap_state.swap_curve().curve_type,
-            accounts.swap_curve.curve_type
-        );
-        assert_eq!(*swap_state.token_a_account(), accounts.token_a_key);
-        assert_eq!(*swap_state.token_b_account(), accounts.token_b_key);
-        assert_eq!(*swap_state.pool_mint(), accounts.pool_mint_key);
-        assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
-        assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
-        assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
-        let token_a = spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, token_a_amount);
-        let token_b = spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(token_b.amount, token_b_amount);
-        let pool_account =
-            spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
-        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        assert_eq!(pool_mint.supply, pool_account.amount);
     }
 
     #[test]
-    fn test_deposit() {
+    fn test_withdraw() {
         let user_key = Pubkey::new_unique();
-        let depositor_key = Pubkey::new_unique();
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 2;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 10;
         let owner_withdraw_fee_numerator = 1;
         let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 20;
+        let host_fee_numerator = 7;
         let host_fee_denominator = 100;
 
         let fees = Fees {
@@ -2858,22 +6748,24 @@ mod tests {
         };
 
         let token_a_amount = 1000;
-        let token_b_amount = 9000;
+        let token_b_amount = 2000;
         let curve_type = CurveType::ConstantProduct;
         let swap_curve = SwapCurve {
             curve_type,
             calculator: Box::new(ConstantProductCurve {}),
         };
 
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let withdraw_amount = initial_pool / 4;
+        let minimum_token_a_amount = initial_a / 40;
+        let minimum_token_b_amount = initial_b / 40;
+
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
-        // depositing 10% of the current pool amount in token A and B means
-        // that our pool tokens will be worth 1 / 10 of the current pool amount
-        let pool_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
-        let deposit_a = token_a_amount / 10;
-        let deposit_b = token_b_amount / 10;
-
         // swap not initialized
         {
             let (
@@ -2883,20 +6775,20 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
             assert_eq!(
                 Err(ProgramError::UninitializedAccount),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
         }
@@ -2912,24 +6804,24 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
             let old_swap_account = accounts.swap_account;
             let mut wrong_swap_account = old_swap_account.clone();
             wrong_swap_account.owner = TOKEN_PROGRAM_ID;
             accounts.swap_account = wrong_swap_account;
             assert_eq!(
                 Err(ProgramError::IncorrectProgramId),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
             accounts.swap_account = old_swap_account;
@@ -2944,7 +6836,7 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
             let old_authority = accounts.authority_key;
             let (bad_authority_key, _nonce) = Pubkey::find_program_address(
                 &[&accounts.swap_key.to_bytes()[..]],
@@ -2953,23 +6845,23 @@ mod tests {
             accounts.authority_key = bad_authority_key;
             assert_eq!(
                 Err(SwapError::InvalidProgramAddress.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
             accounts.authority_key = old_authority;
         }
 
-        // not enough token A
+        // not enough pool tokens
         {
             let (
                 token_a_key,
@@ -2980,29 +6872,29 @@ mod tests {
                 mut pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
-                &depositor_key,
-                deposit_a / 2,
-                deposit_b,
-                0,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                to_u64(withdraw_amount).unwrap() / 2u64,
             );
             assert_eq!(
                 Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount / 2,
+                    minimum_token_b_amount / 2,
                 )
             );
         }
 
-        // not enough token B
+        // wrong token a / b accounts
         {
             let (
                 token_a_key,
@@ -3013,88 +6905,126 @@ mod tests {
                 mut pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
-                &depositor_key,
-                deposit_a,
-                deposit_b / 2,
-                0,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
             );
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
+                Err(TokenError::MintMismatch.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // wrong swap token accounts
+        // wrong pool token account
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let (
+                wrong_token_a_key,
+                mut wrong_token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                withdraw_amount.try_into().unwrap(),
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
             assert_eq!(
                 Err(TokenError::MintMismatch.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_b_key,
-                    &mut token_b_account,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &wrong_token_a_key,
+                    &mut wrong_token_a_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // wrong pool token account
+        // wrong pool fee account
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+                wrong_pool_key,
+                wrong_pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
             let (
-                wrong_token_key,
-                mut wrong_token_account,
+                _token_a_key,
+                _token_a_account,
                 _token_b_key,
-                mut _token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &wrong_token_key,
-                    &mut wrong_token_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                ),
             );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
         }
 
         // no approval
@@ -3106,40 +7036,48 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                0,
+                0,
+                withdraw_amount.try_into().unwrap(),
+            );
             let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
                 Err(TokenError::OwnerMismatch.into()),
                 do_process_instruction(
-                    deposit_all_token_types(
+                    withdraw_all_token_types(
                         &SWAP_PROGRAM_ID,
                         &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &user_transfer_authority_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
                         &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
                         &pool_key,
-                        DepositAllTokenTypes {
-                            pool_token_amount: pool_amount.try_into().unwrap(),
-                            maximum_token_a_amount: deposit_a,
-                            maximum_token_b_amount: deposit_b,
-                        },
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &token_b_key,
+                        WithdrawAllTokenTypes {
+                            pool_token_amount: withdraw_amount.try_into().unwrap(),
+                            minimum_token_a_amount,
+                            minimum_token_b_amount,
+                        }
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
                         &mut Account::default(),
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
                         &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_fee_account,
                         &mut Account::default(),
                     ],
                 )
@@ -3155,27 +7093,34 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
             let wrong_key = Pubkey::new_unique();
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
                 do_process_instruction(
-                    deposit_all_token_types(
+                    withdraw_all_token_types(
                         &SWAP_PROGRAM_ID,
                         &wrong_key,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &accounts.authority_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
                         &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
                         &pool_key,
-                        DepositAllTokenTypes {
-                            pool_token_amount: pool_amount.try_into().unwrap(),
-                            maximum_token_a_amount: deposit_a,
-                            maximum_token_b_amount: deposit_b,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &token_b_key,
+                        WithdrawAllTokenTypes {
+                            pool_token_amount: withdraw_amount.try_into().unwrap(),
+                            minimum_token_a_amount,
+                            minimum_token_b_amount,
                         },
                     )
                     .unwrap(),
@@ -3183,12 +7128,13 @@ mod tests {
                         &mut accounts.swap_account,
                         &mut Account::default(),
                         &mut Account::default(),
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
                         &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_fee_account,
                         &mut Account::default(),
                     ],
                 )
@@ -3204,7 +7150,13 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
 
             let old_a_key = accounts.token_a_key;
             let old_a_account = accounts.token_a_account;
@@ -3215,17 +7167,17 @@ mod tests {
             // wrong swap token a account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
 
@@ -3241,17 +7193,17 @@ mod tests {
             // wrong swap token b account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
 
@@ -3268,7 +7220,13 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
             let (pool_mint_key, pool_mint_account) =
                 create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
             let old_pool_key = accounts.pool_mint_key;
@@ -3278,17 +7236,17 @@ mod tests {
 
             assert_eq!(
                 Err(SwapError::IncorrectPoolMint.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
 
@@ -3296,7 +7254,7 @@ mod tests {
             accounts.pool_mint_account = old_pool_account;
         }
 
-        // deposit 1 pool token fails beacuse it equates to 0 swap tokens
+        // withdrawing 1 pool token fails because it equates to 0 output tokens
         {
             let (
                 token_a_key,
@@ -3305,20 +7263,26 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
             assert_eq!(
                 Err(SwapError::ZeroTradingTokens.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
                     1,
-                    deposit_a,
-                    deposit_b,
+                    0,
+                    0,
                 )
             );
         }
@@ -3332,74 +7296,100 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            // maximum A amount in too low
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            // minimum A amount out too high
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a / 10,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount * 10,
+                    minimum_token_b_amount,
                 )
             );
-            // maximum B amount in too low
+            // minimum B amount out too high
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b / 10,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount * 10,
                 )
             );
         }
 
-        // invalid input: can't use swap pool tokens as source
+        // invalid input: can't use swap pool tokens as destination
         {
             let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
             let swap_token_a_key = accounts.token_a_key;
             let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            let authority_key = accounts.authority_key;
             assert_eq!(
                 Err(SwapError::InvalidInput.into()),
-                accounts.deposit_all_token_types(
-                    &authority_key,
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &swap_token_a_key,
                     &mut swap_token_a_account,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // correctly deposit
+        // correct withdrawal
         {
             let (
                 token_a_key,
@@ -3408,54 +7398,150 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
             accounts
-                .deposit_all_token_types(
-                    &depositor_key,
+                .withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
                 .unwrap();
 
             let swap_token_a =
                 spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
             let swap_token_b =
                 spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    withdraw_amount - withdraw_fee,
+                    pool_mint.supply.try_into().unwrap(),
+                    swap_token_a.amount.try_into().unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            assert_eq!(
+                swap_token_a.amount,
+                token_a_amount - to_u64(results.token_a_amount).unwrap()
+            );
+            assert_eq!(
+                swap_token_b.amount,
+                token_b_amount - to_u64(results.token_b_amount).unwrap()
+            );
             let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, 0);
+            assert_eq!(
+                token_a.amount,
+                initial_a + to_u64(results.token_a_amount).unwrap()
+            );
             let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(token_b.amount, 0);
+            assert_eq!(
+                token_b.amount,
+                initial_b + to_u64(results.token_b_amount).unwrap()
+            );
             let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            let swap_pool_account =
-                spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+            assert_eq!(
+                pool_account.amount,
+                to_u64(initial_pool - withdraw_amount).unwrap()
+            );
+            let fee_account =
+                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(
+                fee_account.amount,
+                TryInto::<u64>::try_into(withdraw_fee).unwrap()
+            );
+        }
+
+        // correct withdrawal from fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.amount;
+
+            accounts
+                .withdraw_all_token_types(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    pool_fee_amount,
+                    0,
+                    0,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
             let pool_mint =
                 spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    pool_fee_amount.try_into().unwrap(),
+                    pool_mint.supply.try_into().unwrap(),
+                    swap_token_a.amount.try_into().unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
             assert_eq!(
-                pool_mint.supply,
-                pool_account.amount + swap_pool_account.amount
+                token_a.amount,
+                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
+            );
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.amount,
+                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
             );
         }
     }
 
     #[test]
-    fn test_withdraw() {
+    fn test_deposit_one_exact_in() {
         let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 2;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 10;
         let owner_withdraw_fee_numerator = 1;
         let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 7;
+        let host_fee_numerator = 20;
         let host_fee_denominator = 100;
 
         let fees = Fees {
@@ -3470,47 +7556,40 @@ mod tests {
         };
 
         let token_a_amount = 1000;
-        let token_b_amount = 2000;
+        let token_b_amount = 9000;
         let curve_type = CurveType::ConstantProduct;
         let swap_curve = SwapCurve {
             curve_type,
             calculator: Box::new(ConstantProductCurve {}),
         };
 
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let withdraw_amount = initial_pool / 4;
-        let minimum_token_a_amount = initial_a / 40;
-        let minimum_token_b_amount = initial_b / 40;
-
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+        let pool_amount = to_u64(INITIAL_SWAP_POOL_AMOUNT / 100).unwrap();
+
         // swap not initialized
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
                 Err(ProgramError::UninitializedAccount),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
                 )
             );
         }
@@ -3522,28 +7601,25 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             let old_swap_account = accounts.swap_account;
             let mut wrong_swap_account = old_swap_account.clone();
             wrong_swap_account.owner = TOKEN_PROGRAM_ID;
             accounts.swap_account = wrong_swap_account;
             assert_eq!(
                 Err(ProgramError::IncorrectProgramId),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
                 )
             );
             accounts.swap_account = old_swap_account;
@@ -3554,11 +7630,11 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             let old_authority = accounts.authority_key;
             let (bad_authority_key, _nonce) = Pubkey::find_program_address(
                 &[&accounts.swap_key.to_bytes()[..]],
@@ -3567,23 +7643,20 @@ mod tests {
             accounts.authority_key = bad_authority_key;
             assert_eq!(
                 Err(SwapError::InvalidProgramAddress.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
                 )
             );
             accounts.authority_key = old_authority;
         }
 
-        // not enough pool tokens
+        // not enough token A / B
         {
             let (
                 token_a_key,
@@ -3594,124 +7667,285 @@ mod tests {
                 mut pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                to_u64(withdraw_amount).unwrap() / 2u64,
+                &depositor_key,
+                deposit_a / 2,
+                deposit_b / 2,
+                0,
             );
             assert_eq!(
                 Err(TokenError::InsufficientFunds.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    0,
+                )
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
+                    deposit_b,
+                    0,
+                )
+            );
+        }
+
+        // wrong pool token account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(TokenError::MintMismatch.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount / 2,
-                    minimum_token_b_amount / 2,
+                    deposit_a,
+                    pool_amount,
                 )
             );
         }
 
-        // wrong token a / b accounts
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let user_transfer_authority_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    deposit_single_token_type_exact_amount_in(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
+                        DepositSingleTokenTypeExactAmountIn {
+                            source_token_amount: deposit_a,
+                            minimum_pool_token_amount: pool_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut Account::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong token program id
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let wrong_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    deposit_single_token_type_exact_amount_in(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_key,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
+                        DepositSingleTokenTypeExactAmountIn {
+                            source_token_amount: deposit_a,
+                            minimum_pool_token_amount: pool_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut Account::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong swap token accounts
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
-                mut token_b_account,
+                token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
             );
+
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account;
+
+            // wrong swap token b account
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
                     &pool_key,
                     &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
+        }
+
+        // wrong mint
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
                 )
             );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
         }
 
-        // wrong pool token account
+        // slippage exceeded
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
-            let (
-                wrong_token_a_key,
-                mut wrong_token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                withdraw_amount.try_into().unwrap(),
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            // minimum pool amount too high
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &wrong_token_a_key,
-                    &mut wrong_token_a_account,
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a / 10,
+                    pool_amount,
+                )
+            );
+            // minimum pool amount too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b / 10,
+                    pool_amount,
                 )
             );
         }
 
-        // wrong pool fee account
+        // invalid input: can't use swap pool tokens as source
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
             let (
                 _token_a_key,
                 _token_a_account,
@@ -3719,94 +7953,39 @@ mod tests {
                 _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let authority_key = accounts.authority_key;
             assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                ),
-            );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
-        }
-
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                0,
-                0,
-                withdraw_amount.try_into().unwrap(),
+                    deposit_a,
+                    pool_amount,
+                )
             );
-            let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    withdraw_all_token_types(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        &token_b_key,
-                        WithdrawAllTokenTypes {
-                            pool_token_amount: withdraw_amount.try_into().unwrap(),
-                            minimum_token_a_amount,
-                            minimum_token_b_amount,
-                        }
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &authority_key,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b,
+                    pool_amount,
                 )
             );
         }
 
-        // wrong token program id
+        // correctly deposit
         {
             let (
                 token_a_key,
@@ -3815,172 +7994,189 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
-            let wrong_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    withdraw_all_token_types(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        &token_b_key,
-                        WithdrawAllTokenTypes {
-                            pool_token_amount: withdraw_amount.try_into().unwrap(),
-                            minimum_token_a_amount,
-                            minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
+
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, 0);
+
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b,
+                    pool_amount,
                 )
+                .unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
+
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(token_b.amount, 0);
+
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+            let swap_pool_account =
+                spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            assert_eq!(
+                pool_mint.supply,
+                pool_account.amount + swap_pool_account.amount
             );
         }
+    }
 
-        // wrong swap token accounts
+    #[test]
+    fn test_withdraw_one_exact_out() {
+        let user_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 7;
+        let host_fee_denominator = 100;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 100_000;
+        let token_b_amount = 200_000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
+        let destination_a_amount = initial_a / 40;
+        let destination_b_amount = initial_b / 40;
+
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        // swap not initialized
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
-
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
-
-            // wrong swap token a account
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_all_token_types(
+                Err(ProgramError::UninitializedAccount),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
+        }
 
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
-
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
-
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account.clone();
+        accounts.initialize_swap().unwrap();
 
-            // wrong swap token b account
+        // wrong owner for swap account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
+            accounts.swap_account = wrong_swap_account;
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_all_token_types(
+                Err(ProgramError::IncorrectProgramId),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
-
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
+            accounts.swap_account = old_swap_account;
         }
 
-        // wrong mint
+        // wrong nonce for authority_key
         {
             let (
-                token_a_key,
-                mut token_a_account,
+                _token_a_key,
+                _token_a_account,
                 token_b_key,
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &TOKEN_PROGRAM_ID,
             );
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
-
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.withdraw_all_token_types(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
-
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
+            accounts.authority_key = old_authority;
         }
 
-        // withdrawing 1 pool token fails because it equates to 0 output tokens
+        // not enough pool tokens
         {
             let (
-                token_a_key,
-                mut token_a_account,
+                _token_a_key,
+                _token_a_account,
                 token_b_key,
                 mut token_b_account,
                 pool_key,
@@ -3990,335 +8186,275 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                initial_pool.try_into().unwrap(),
+                maximum_pool_token_amount / 1000,
             );
             assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.withdraw_all_token_types(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    1,
-                    0,
-                    0,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
 
-        // slippage exceeded
+        // wrong pool token account
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
+                _pool_key,
+                _pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
                 &withdrawer_key,
-                initial_a,
+                maximum_pool_token_amount,
                 initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-            // minimum A amount out too high
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount * 10,
-                    minimum_token_b_amount,
-                )
+                maximum_pool_token_amount,
             );
-            // minimum B amount out too high
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_all_token_types(
+                Err(TokenError::MintMismatch.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount * 10,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
 
-        // invalid input: can't use swap pool tokens as destination
+        // wrong pool fee account
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
+                _token_b_key,
+                _token_b_account,
+                wrong_pool_key,
+                wrong_pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                initial_pool.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
             );
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_all_token_types(
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
         }
 
-        // correct withdrawal
+        // no approval
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
                 &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-
-            accounts
-                .withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
-                .unwrap();
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    withdraw_amount - withdraw_fee,
-                    pool_mint.supply.try_into().unwrap(),
-                    swap_token_a.amount.try_into().unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            assert_eq!(
-                swap_token_a.amount,
-                token_a_amount - to_u64(results.token_a_amount).unwrap()
-            );
-            assert_eq!(
-                swap_token_b.amount,
-                token_b_amount - to_u64(results.token_b_amount).unwrap()
-            );
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.amount,
-                initial_a + to_u64(results.token_a_amount).unwrap()
-            );
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(
-                token_b.amount,
-                initial_b + to_u64(results.token_b_amount).unwrap()
-            );
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            assert_eq!(
-                pool_account.amount,
-                to_u64(initial_pool - withdraw_amount).unwrap()
+                0,
+                0,
+                maximum_pool_token_amount,
             );
-            let fee_account =
-                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+            let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
-                fee_account.amount,
-                TryInto::<u64>::try_into(withdraw_fee).unwrap()
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    withdraw_single_token_type_exact_amount_out(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        WithdrawSingleTokenTypeExactAmountOut {
+                            destination_token_amount: destination_a_amount,
+                            maximum_pool_token_amount,
+                        }
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                )
             );
         }
 
-        // correct withdrawal from fee account
+        // wrong token program id
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
-
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.amount;
-
-            accounts
-                .withdraw_all_token_types(
-                    &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    pool_fee_amount,
-                    0,
-                    0,
-                )
-                .unwrap();
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    pool_fee_amount.try_into().unwrap(),
-                    pool_mint.supply.try_into().unwrap(),
-                    swap_token_a.amount.try_into().unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.amount,
-                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
             );
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            let wrong_key = Pubkey::new_unique();
             assert_eq!(
-                token_b.amount,
-                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
-            );
-        }
-    }
-
-    #[test]
-    fn test_deposit_one_exact_in() {
-        let user_key = Pubkey::new_unique();
-        let depositor_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 20;
-        let host_fee_denominator = 100;
-
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 9000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Box::new(ConstantProductCurve {}),
-        };
-
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-
-        let deposit_a = token_a_amount / 10;
-        let deposit_b = token_b_amount / 10;
-        let pool_amount = to_u64(INITIAL_SWAP_POOL_AMOUNT / 100).unwrap();
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    withdraw_single_token_type_exact_amount_out(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_key,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        WithdrawSingleTokenTypeExactAmountOut {
+                            destination_token_amount: destination_a_amount,
+                            maximum_pool_token_amount,
+                        }
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                )
+            );
+        }
 
-        // swap not initialized
+        // wrong swap token accounts
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
             assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account.clone();
+
+            // wrong swap token b account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    deposit_a,
-                    pool_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
-        }
 
-        accounts.initialize_swap().unwrap();
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
+        }
 
-        // wrong owner for swap account
+        // wrong mint
         {
             let (
                 token_a_key,
@@ -4327,457 +8463,681 @@ mod tests {
                 _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
-            accounts.swap_account = wrong_swap_account;
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
             assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    deposit_a,
-                    pool_amount,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
-            accounts.swap_account = old_swap_account;
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
         }
 
-        // wrong nonce for authority_key
+        // slippage exceeded
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &TOKEN_PROGRAM_ID,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
             );
-            accounts.authority_key = bad_authority_key;
+
+            // maximum pool token amount too low
             assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount / 1000,
+                )
+            );
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    deposit_a,
-                    pool_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount / 1000,
                 )
             );
-            accounts.authority_key = old_authority;
         }
 
-        // not enough token A / B
+        // invalid input: can't use swap pool tokens as destination
         {
             let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
                 &user_key,
-                &depositor_key,
-                deposit_a / 2,
-                deposit_b / 2,
-                0,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
             );
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    deposit_a,
-                    0,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_b_key,
-                    &mut token_b_account,
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    deposit_b,
-                    0,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
 
-        // wrong pool token account
+        // correct withdrawal
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+
+            let pool_token_amount = accounts
+                .swap_curve
+                .trading_tokens_to_pool_tokens(
+                    destination_a_amount.try_into().unwrap(),
+                    (swap_token_a.amount - destination_a_amount)
+                        .try_into()
+                        .unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    pool_mint.supply.try_into().unwrap(),
+                    TradeDirection::AtoB,
+                    RoundDirection::Ceiling,
+                    &accounts.fees,
+                )
+                .unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(pool_token_amount).unwrap();
+
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    deposit_a,
-                    pool_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
-            );
-        }
+                .unwrap();
 
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let user_transfer_authority_key = Pubkey::new_unique();
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+
+            assert_eq!(swap_token_a.amount, token_a_amount - destination_a_amount);
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, initial_a + destination_a_amount);
+
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    deposit_single_token_type_exact_amount_in(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &pool_key,
-                        DepositSingleTokenTypeExactAmountIn {
-                            source_token_amount: deposit_a,
-                            minimum_pool_token_amount: pool_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut Account::default(),
-                    ],
-                )
+                pool_account.amount,
+                to_u64(initial_pool - pool_token_amount - withdraw_fee).unwrap()
             );
+            let fee_account =
+                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(fee_account.amount, to_u64(withdraw_fee).unwrap());
         }
 
-        // wrong token program id
+        // correct withdrawal from fee account
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 _token_b_key,
                 _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let wrong_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    deposit_single_token_type_exact_amount_in(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &pool_key,
-                        DepositSingleTokenTypeExactAmountIn {
-                            source_token_amount: deposit_a,
-                            minimum_pool_token_amount: pool_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut Account::default(),
-                    ],
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+
+            let fee_a_amount = 2;
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.amount;
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+
+            let token_a_amount = swap_token_a.amount;
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    fee_a_amount,
+                    pool_fee_amount,
                 )
-            );
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+
+            assert_eq!(swap_token_a.amount, token_a_amount - fee_a_amount);
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, initial_a + fee_a_amount);
         }
+    }
+
+    fn check_valid_swap_curve(
+        fees: Fees,
+        curve_type: CurveType,
+        calculator: Box<dyn CurveCalculator>,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees.clone(),
+            swap_curve.clone(),
+            token_a_amount,
+            token_b_amount,
+        );
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        accounts.initialize_swap().unwrap();
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+        // swap one way
+        let a_to_b_amount = initial_a / 10;
+        let minimum_token_b_amount = 0;
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        let initial_supply = pool_mint.supply;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+
+        let results = swap_curve
+            .swap(
+                a_to_b_amount.try_into().unwrap(),
+                token_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                TradeDirection::AtoB,
+                &fees,
+            )
+            .unwrap();
+
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        let token_a_amount = swap_token_a.amount;
+        assert_eq!(
+            token_a_amount,
+            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
+        );
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, initial_a - a_to_b_amount);
+
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        let token_b_amount = swap_token_b.amount;
+        assert_eq!(
+            token_b_amount,
+            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
+        );
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.amount,
+            initial_b + to_u64(results.destination_amount_swapped).unwrap()
+        );
+
+        let first_fee = swap_curve
+            .trading_tokens_to_pool_tokens(
+                results.owner_fee,
+                token_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                initial_supply.try_into().unwrap(),
+                TradeDirection::AtoB,
+                RoundDirection::Ceiling,
+                &fees,
+            )
+            .unwrap();
+        let fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(
+            fee_account.amount,
+            TryInto::<u64>::try_into(first_fee).unwrap()
+        );
+
+        let first_swap_amount = results.destination_amount_swapped;
+
+        // swap the other way
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        let initial_supply = pool_mint.supply;
+
+        let b_to_a_amount = initial_b / 10;
+        let minimum_a_amount = 0;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_b_key,
+                &mut token_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &token_a_key,
+                &mut token_a_account,
+                b_to_a_amount,
+                minimum_a_amount,
+            )
+            .unwrap();
 
-        // wrong swap token accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+        let results = swap_curve
+            .swap(
+                b_to_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                token_a_amount.try_into().unwrap(),
+                TradeDirection::BtoA,
+                &fees,
+            )
+            .unwrap();
 
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        let token_a_amount = swap_token_a.amount;
+        assert_eq!(
+            token_a_amount,
+            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
+        );
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(
+            token_a.amount,
+            initial_a - a_to_b_amount + to_u64(results.destination_amount_swapped).unwrap()
+        );
 
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        let token_b_amount = swap_token_b.amount;
+        assert_eq!(
+            token_b_amount,
+            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
+        );
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.amount,
+            initial_b + to_u64(first_swap_amount).unwrap()
+                - to_u64(results.source_amount_swapped).unwrap()
+        );
 
-            // wrong swap token a account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
+        let second_fee = swap_curve
+            .trading_tokens_to_pool_tokens(
+                results.owner_fee,
+                token_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                initial_supply.try_into().unwrap(),
+                TradeDirection::BtoA,
+                RoundDirection::Ceiling,
+                &fees,
+            )
+            .unwrap();
+        let fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(fee_account.amount, to_u64(first_fee + second_fee).unwrap());
+    }
 
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
+    #[test]
+    fn test_valid_swap_curves_all_fees() {
+        // All fees
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
 
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account;
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantProduct,
+            Box::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
+        );
+        let token_b_price = 1;
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantPrice,
+            Box::new(ConstantPriceCurve { token_b_price }),
+            token_a_amount,
+            token_b_amount,
+        );
+        let token_b_offset = 10_000_000_000;
+        check_valid_swap_curve(
+            fees,
+            CurveType::Offset,
+            Box::new(OffsetCurve { token_b_offset }),
+            token_a_amount,
+            token_b_amount,
+        );
+    }
 
-            // wrong swap token b account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
+    #[test]
+    fn test_valid_swap_curves_trade_fee_only() {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 0;
+        let owner_trade_fee_denominator = 0;
+        let owner_withdraw_fee_numerator = 0;
+        let owner_withdraw_fee_denominator = 0;
+        let host_fee_numerator = 0;
+        let host_fee_denominator = 0;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
-        }
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
 
-        // wrong mint
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantProduct,
+            Box::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
+        );
+        let token_b_price = 10_000;
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantPrice,
+            Box::new(ConstantPriceCurve { token_b_price }),
+            token_a_amount,
+            token_b_amount / token_b_price,
+        );
+        let token_b_offset = 1;
+        check_valid_swap_curve(
+            fees,
+            CurveType::Offset,
+            Box::new(OffsetCurve { token_b_offset }),
+            token_a_amount,
+            token_b_amount,
+        );
+    }
 
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
+    #[test]
+    fn test_valid_swap_with_fee_constraints() {
+        let owner_key = Pubkey::new_unique();
 
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
-        }
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 5_000_000;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-        // slippage exceeded
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            // minimum pool amount too high
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a / 10,
-                    pool_amount,
-                )
-            );
-            // minimum pool amount too high
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b / 10,
-                    pool_amount,
-                )
-            );
-        }
+        let curve = ConstantProductCurve {};
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(curve),
+        };
 
-        // invalid input: can't use swap pool tokens as source
-        {
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            let authority_key = accounts.authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &authority_key,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &authority_key,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b,
-                    pool_amount,
-                )
-            );
-        }
+        let owner_key_str = &owner_key.to_string();
+        let valid_curve_types = &[CurveType::ConstantProduct];
+        let constraints = Some(SwapConstraints {
+            owner_key: owner_key_str,
+            valid_curve_types,
+            valid_multi_token_curve_types: &[],
+            fees: &fees,
+        });
+        let mut accounts = SwapAccountInfo::new(
+            &owner_key,
+            fees.clone(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+        );
 
-        // correctly deposit
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            accounts
-                .deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-                .unwrap();
+        // initialize swap
+        do_process_instruction_with_fee_constraints(
+            initialize(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.pool_token_key,
+                accounts.nonce,
+                accounts.fees.clone(),
+                accounts.swap_curve.clone(),
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.pool_token_account,
+                &mut Account::default(),
+            ],
+            &constraints,
+        )
+        .unwrap();
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
+        let authority_key = accounts.authority_key;
 
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, 0);
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &owner_key,
+            &authority_key,
+            token_a_amount,
+            token_b_amount,
+            0,
+        );
 
-            accounts
-                .deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b,
-                    pool_amount,
-                )
-                .unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
+        let amount_in = token_a_amount / 2;
+        let minimum_amount_out = 0;
 
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(token_b.amount, 0);
+        // perform the swap
+        do_process_instruction_with_fee_constraints(
+            swap(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                &token_a_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                Some(&pool_key),
+                Swap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut token_a_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut Account::default(),
+                &mut pool_account,
+            ],
+            &constraints,
+        )
+        .unwrap();
 
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            let swap_pool_account =
-                spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            assert_eq!(
-                pool_mint.supply,
-                pool_account.amount + swap_pool_account.amount
-            );
-        }
+        // check that fees were taken in the host fee account
+        let host_fee_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+        let owner_fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        let total_fee = owner_fee_account.amount * host_fee_denominator
+            / (host_fee_denominator - host_fee_numerator);
+        assert_eq!(
+            total_fee,
+            host_fee_account.amount + owner_fee_account.amount
+        );
     }
 
     #[test]
-    fn test_withdraw_one_exact_out() {
+    fn test_invalid_swap() {
         let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
         let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
+        let trade_fee_denominator = 4;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 10;
         let owner_withdraw_fee_numerator = 1;
         let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 7;
+        let host_fee_numerator = 9;
         let host_fee_denominator = 100;
-
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -4789,90 +9149,92 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 100_000;
-        let token_b_amount = 200_000;
+        let token_a_amount = 1000;
+        let token_b_amount = 5000;
         let curve_type = CurveType::ConstantProduct;
         let swap_curve = SwapCurve {
             curve_type,
             calculator: Box::new(ConstantProductCurve {}),
         };
-
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
-        let destination_a_amount = initial_a / 40;
-        let destination_b_amount = initial_b / 40;
-
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        let minimum_token_b_amount = initial_b / 2;
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
         // swap not initialized
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
                 Err(ProgramError::UninitializedAccount),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
         }
 
         accounts.initialize_swap().unwrap();
 
-        // wrong owner for swap account
+        // wrong swap account program id
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             let old_swap_account = accounts.swap_account;
             let mut wrong_swap_account = old_swap_account.clone();
             wrong_swap_account.owner = TOKEN_PROGRAM_ID;
             accounts.swap_account = wrong_swap_account;
             assert_eq!(
                 Err(ProgramError::IncorrectProgramId),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
             accounts.swap_account = old_swap_account;
         }
 
-        // wrong nonce for authority_key
+        // wrong nonce
         {
             let (
-                _token_a_key,
-                _token_a_account,
+                token_a_key,
+                mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             let old_authority = accounts.authority_key;
             let (bad_authority_key, _nonce) = Pubkey::find_program_address(
                 &[&accounts.swap_key.to_bytes()[..]],
@@ -4881,50 +9243,198 @@ mod tests {
             accounts.authority_key = bad_authority_key;
             assert_eq!(
                 Err(SwapError::InvalidProgramAddress.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
             accounts.authority_key = old_authority;
         }
 
-        // not enough pool tokens
+        // wrong token program id
         {
             let (
-                _token_a_key,
-                _token_a_account,
+                token_a_key,
+                mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount / 1000,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let wrong_program_id = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                ),
+            );
+        }
+
+        // not enough token a to swap
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a * 2,
+                    minimum_token_b_amount * 2,
+                )
+            );
+        }
+
+        // wrong swap token A / B accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let user_transfer_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_key,
+                        &token_a_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account.clone(),
+                        &mut token_a_account,
+                        &mut token_b_account.clone(),
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                ),
+            );
+        }
+
+        // wrong user token A / B accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(TokenError::MintMismatch.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
             );
+        }
+
+        // swap from a to a
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account.clone(),
+                    &swap_token_a_key,
+                    &swap_token_a_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // wrong pool token account
+        // incorrect mint provided
         {
             let (
                 token_a_key,
@@ -4933,71 +9443,59 @@ mod tests {
                 mut token_b_account,
                 _pool_key,
                 _pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                maximum_pool_token_amount,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
         }
 
-        // wrong pool fee account
+        // incorrect fee account provided
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 wrong_pool_key,
                 wrong_pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             let old_pool_fee_account = accounts.pool_fee_account;
             let old_pool_fee_key = accounts.pool_fee_key;
             accounts.pool_fee_account = wrong_pool_account;
             accounts.pool_fee_key = wrong_pool_key;
             assert_eq!(
                 Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
             accounts.pool_fee_account = old_pool_fee_account;
@@ -5009,442 +9507,328 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                0,
-                0,
-                maximum_pool_token_amount,
-            );
-            let user_transfer_authority_key = Pubkey::new_unique();
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let user_transfer_key = Pubkey::new_unique();
             assert_eq!(
                 Err(TokenError::OwnerMismatch.into()),
                 do_process_instruction(
-                    withdraw_single_token_type_exact_amount_out(
+                    swap(
                         &SWAP_PROGRAM_ID,
                         &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
                         &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
+                        &user_transfer_key,
+                        &token_a_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
-                        &token_a_key,
-                        WithdrawSingleTokenTypeExactAmountOut {
-                            destination_token_amount: destination_a_amount,
-                            maximum_pool_token_amount,
-                        }
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                )
-            );
-        }
-
-        // wrong token program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let wrong_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    withdraw_single_token_type_exact_amount_out(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
+                        &token_b_key,
                         &accounts.pool_mint_key,
                         &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        WithdrawSingleTokenTypeExactAmountOut {
-                            destination_token_amount: destination_a_amount,
-                            maximum_pool_token_amount,
-                        }
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
                         &mut Account::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
+                        &mut token_a_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                )
-            );
-        }
-
-        // wrong swap token accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
-
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
-
-            // wrong swap token a account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
-                )
-            );
-
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
-
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
-
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account.clone();
-
-            // wrong swap token b account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
-                )
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                ),
             );
-
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
         }
 
-        // wrong mint
+        // output token value 0
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
-
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &swap_token_b_key,
+                    &swap_token_a_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    1,
+                    1,
                 )
             );
-
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
         }
 
-        // slippage exceeded
+        // slippage exceeded: minimum out amount too high
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-
-            // maximum pool token amount too low
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount / 1000,
-                )
-            );
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount / 1000,
+                    initial_a,
+                    minimum_token_b_amount * 2,
                 )
             );
         }
 
-        // invalid input: can't use swap pool tokens as destination
+        // invalid input: can't use swap pool as user source / dest
         {
             let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let swap_token_a_key = accounts.token_a_key;
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let authority_key = accounts.authority_key;
             assert_eq!(
                 Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &authority_key,
                     &swap_token_a_key,
                     &mut swap_token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
-            let swap_token_b_key = accounts.token_b_key;
             let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
             assert_eq!(
                 Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &swap_token_b_key,
                     &mut swap_token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // correct withdrawal
+        // still correct: constraint specified, no host fee account
         {
+            let authority_key = accounts.authority_key;
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-
-            let pool_token_amount = accounts
-                .swap_curve
-                .trading_tokens_to_pool_tokens(
-                    destination_a_amount.try_into().unwrap(),
-                    (swap_token_a.amount - destination_a_amount)
-                        .try_into()
-                        .unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    pool_mint.supply.try_into().unwrap(),
-                    TradeDirection::AtoB,
-                    RoundDirection::Ceiling,
-                    &accounts.fees,
-                )
-                .unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(pool_token_amount).unwrap();
-
-            accounts
-                .withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let owner_key = &swapper_key.to_string();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[],
+                valid_multi_token_curve_types: &[],
+                fees: &fees,
+            });
+            do_process_instruction_with_fee_constraints(
+                swap(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.authority_key,
+                    &accounts.authority_key,
                     &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                    &token_b_key,
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    None,
+                    Swap {
+                        amount_in: initial_a,
+                        minimum_amount_out: minimum_token_b_amount,
+                    },
                 )
-                .unwrap();
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-
-            assert_eq!(swap_token_a.amount, token_a_amount - destination_a_amount);
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, initial_a + destination_a_amount);
-
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            assert_eq!(
-                pool_account.amount,
-                to_u64(initial_pool - pool_token_amount - withdraw_fee).unwrap()
-            );
-            let fee_account =
-                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-            assert_eq!(fee_account.amount, to_u64(withdraw_fee).unwrap());
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut token_a_account,
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut Account::default(),
+                ],
+                &constraints,
+            )
+            .unwrap();
         }
 
-        // correct withdrawal from fee account
+        // invalid mint for host fee account
         {
+            let authority_key = accounts.authority_key;
             let (
                 token_a_key,
                 mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let (
+                bad_token_a_key,
+                mut bad_token_a_account,
                 _token_b_key,
-                _token_b_account,
+                mut _token_b_account,
                 _pool_key,
                 _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-
-            let fee_a_amount = 2;
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.amount;
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-
-            let token_a_amount = swap_token_a.amount;
-            accounts
-                .withdraw_single_token_type_exact_amount_out(
-                    &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    fee_a_amount,
-                    pool_fee_amount,
-                )
-                .unwrap();
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-
-            assert_eq!(swap_token_a.amount, token_a_amount - fee_a_amount);
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, initial_a + fee_a_amount);
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let owner_key = &swapper_key.to_string();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[],
+                valid_multi_token_curve_types: &[],
+                fees: &fees,
+            });
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                do_process_instruction_with_fee_constraints(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        Some(&bad_token_a_key),
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: 0,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                        &mut bad_token_a_account,
+                    ],
+                    &constraints,
+                ),
+            );
         }
     }
 
-    fn check_valid_swap_curve(
-        fees: Fees,
-        curve_type: CurveType,
-        calculator: Box<dyn CurveCalculator>,
-        token_a_amount: u64,
-        token_b_amount: u64,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
+    #[test]
+    fn test_overdraw_offset_curve() {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000_000;
+        let token_b_amount = 0;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
+        let token_b_offset = 2_000_000;
         let swap_curve = SwapCurve {
-            curve_type,
-            calculator,
+            curve_type: CurveType::Offset,
+            calculator: Box::new(OffsetCurve { token_b_offset }),
         };
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees.clone(),
-            swap_curve.clone(),
-            token_a_amount,
-            token_b_amount,
-        );
-        let initial_a = token_a_amount / 5;
-        let initial_b = token_b_amount / 5;
         accounts.initialize_swap().unwrap();
 
         let swap_token_a_key = accounts.token_a_key;
         let swap_token_b_key = accounts.token_b_key;
+        let initial_a = 500_000;
+        let initial_b = 1_000;
 
         let (
             token_a_key,
@@ -5454,13 +9838,14 @@ mod tests {
             _pool_key,
             _pool_account,
         ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-        // swap one way
-        let a_to_b_amount = initial_a / 10;
+
+        // swap a to b way, fails, there's no liquidity
+        let a_to_b_amount = initial_a;
         let minimum_token_b_amount = 0;
-        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        let initial_supply = pool_mint.supply;
-        accounts
-            .swap(
+
+        assert_eq!(
+            Err(SwapError::ZeroTradingTokens.into()),
+            accounts.swap(
                 &swapper_key,
                 &token_a_key,
                 &mut token_a_account,
@@ -5471,67 +9856,11 @@ mod tests {
                 a_to_b_amount,
                 minimum_token_b_amount,
             )
-            .unwrap();
-
-        let results = swap_curve
-            .swap(
-                a_to_b_amount.try_into().unwrap(),
-                token_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                TradeDirection::AtoB,
-                &fees,
-            )
-            .unwrap();
-
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        let token_a_amount = swap_token_a.amount;
-        assert_eq!(
-            token_a_amount,
-            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
-        );
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, initial_a - a_to_b_amount);
-
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        let token_b_amount = swap_token_b.amount;
-        assert_eq!(
-            token_b_amount,
-            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
-        );
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-        assert_eq!(
-            token_b.amount,
-            initial_b + to_u64(results.destination_amount_swapped).unwrap()
-        );
-
-        let first_fee = swap_curve
-            .trading_tokens_to_pool_tokens(
-                results.owner_fee,
-                token_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                initial_supply.try_into().unwrap(),
-                TradeDirection::AtoB,
-                RoundDirection::Ceiling,
-                &fees,
-            )
-            .unwrap();
-        let fee_account =
-            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-        assert_eq!(
-            fee_account.amount,
-            TryInto::<u64>::try_into(first_fee).unwrap()
         );
 
-        let first_swap_amount = results.destination_amount_swapped;
-
-        // swap the other way
-        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        let initial_supply = pool_mint.supply;
-
-        let b_to_a_amount = initial_b / 10;
-        let minimum_a_amount = 0;
+        // swap b to a, succeeds at offset price
+        let b_to_a_amount = initial_b;
+        let minimum_token_a_amount = 0;
         accounts
             .swap(
                 &swapper_key,
@@ -5542,74 +9871,86 @@ mod tests {
                 &token_a_key,
                 &mut token_a_account,
                 b_to_a_amount,
-                minimum_a_amount,
+                minimum_token_a_amount,
             )
             .unwrap();
 
-        let results = swap_curve
+        // try a to b again, succeeds due to new liquidity
+        accounts
             .swap(
-                b_to_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                token_a_amount.try_into().unwrap(),
-                TradeDirection::BtoA,
-                &fees,
-            )
-            .unwrap();
-
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        let token_a_amount = swap_token_a.amount;
-        assert_eq!(
-            token_a_amount,
-            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
-        );
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(
-            token_a.amount,
-            initial_a - a_to_b_amount + to_u64(results.destination_amount_swapped).unwrap()
-        );
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
 
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        let token_b_amount = swap_token_b.amount;
-        assert_eq!(
-            token_b_amount,
-            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
-        );
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        // try a to b again, fails due to no more liquidity
         assert_eq!(
-            token_b.amount,
-            initial_b + to_u64(first_swap_amount).unwrap()
-                - to_u64(results.source_amount_swapped).unwrap()
+            Err(SwapError::ZeroTradingTokens.into()),
+            accounts.swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
         );
 
-        let second_fee = swap_curve
-            .trading_tokens_to_pool_tokens(
-                results.owner_fee,
-                token_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                initial_supply.try_into().unwrap(),
-                TradeDirection::BtoA,
-                RoundDirection::Ceiling,
-                &fees,
-            )
-            .unwrap();
-        let fee_account =
-            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-        assert_eq!(fee_account.amount, to_u64(first_fee + second_fee).unwrap());
+        // Try to deposit, fails because deposits are not allowed for offset
+        // curve swaps
+        {
+            let initial_a = 100;
+            let initial_b = 100;
+            let pool_amount = 100;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(SwapError::UnsupportedCurveOperation.into()),
+                accounts.deposit_all_token_types(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount,
+                    initial_a,
+                    initial_b,
+                )
+            );
+        }
     }
 
     #[test]
-    fn test_valid_swap_curves_all_fees() {
-        // All fees
+    fn test_withdraw_all_offset_curve() {
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 10;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_numerator = 0;
         let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 20;
+        let host_fee_numerator = 10;
         let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000_000;
+        let token_b_amount = 10;
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -5621,44 +9962,80 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
+        let token_b_offset = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Box::new(OffsetCurve { token_b_offset }),
+        };
+        let total_pool = swap_curve.calculator.new_pool_supply();
+        let user_key = Pubkey::new_unique();
+        let withdrawer_key = Pubkey::new_unique();
 
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantProduct,
-            Box::new(ConstantProductCurve {}),
-            token_a_amount,
-            token_b_amount,
-        );
-        let token_b_price = 1;
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantPrice,
-            Box::new(ConstantPriceCurve { token_b_price }),
-            token_a_amount,
-            token_b_amount,
-        );
-        let token_b_offset = 10_000_000_000;
-        check_valid_swap_curve(
-            fees,
-            CurveType::Offset,
-            Box::new(OffsetCurve { token_b_offset }),
-            token_a_amount,
-            token_b_amount,
-        );
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        accounts.initialize_swap().unwrap();
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+        let pool_key = accounts.pool_token_key;
+        let mut pool_account = accounts.pool_token_account.clone();
+
+        // WithdrawAllTokenTypes takes all tokens for A and B.
+        // The curve's calculation for token B will say to transfer
+        // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
+        // moved.
+        accounts
+            .withdraw_all_token_types(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
+                0,
+                0,
+            )
+            .unwrap();
+
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, token_a_amount);
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.amount, token_b_amount);
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(swap_token_a.amount, 0);
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(swap_token_b.amount, 0);
     }
 
     #[test]
-    fn test_valid_swap_curves_trade_fee_only() {
+    fn test_withdraw_all_constant_price_curve() {
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 0;
-        let owner_trade_fee_denominator = 0;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
         let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 0;
-        let host_fee_numerator = 0;
-        let host_fee_denominator = 0;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        // initialize "unbalanced", so that withdrawing all will have some issues
+        // A: 1_000_000_000
+        // B: 2_000_000_000 (1_000 * 2_000_000)
+        let swap_token_a_amount = 1_000_000_000;
+        let swap_token_b_amount = 1_000;
+        let token_b_price = 2_000_000;
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -5670,114 +10047,212 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve { token_b_price }),
+        };
+        let total_pool = swap_curve.calculator.new_pool_supply();
+        let user_key = Pubkey::new_unique();
+        let withdrawer_key = Pubkey::new_unique();
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            swap_curve,
+            swap_token_a_amount,
+            swap_token_b_amount,
+        );
+
+        accounts.initialize_swap().unwrap();
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+        let pool_key = accounts.pool_token_key;
+        let mut pool_account = accounts.pool_token_account.clone();
+
+        // WithdrawAllTokenTypes will not take all token A and B, since their
+        // ratio is unbalanced.  It will try to take 1_500_000_000 worth of
+        // each token, which means 1_500_000_000 token A, and 750 token B.
+        // With no slippage, this will leave 250 token B in the pool.
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.withdraw_all_token_types(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
+                swap_token_a_amount,
+                swap_token_b_amount,
+            )
+        );
+
+        accounts
+            .withdraw_all_token_types(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
+                0,
+                0,
+            )
+            .unwrap();
+
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, swap_token_a_amount);
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.amount, 750);
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(swap_token_a.amount, 0);
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(swap_token_b.amount, 250);
 
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantProduct,
-            Box::new(ConstantProductCurve {}),
+        // deposit now, not enough to cover the tokens already in there
+        let token_b_amount = 10;
+        let token_a_amount = token_b_amount * token_b_price;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &user_key,
+            &withdrawer_key,
             token_a_amount,
             token_b_amount,
+            0,
         );
-        let token_b_price = 10_000;
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantPrice,
-            Box::new(ConstantPriceCurve { token_b_price }),
-            token_a_amount,
-            token_b_amount / token_b_price,
+
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.deposit_all_token_types(
+                &withdrawer_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1, // doesn't matter
+                token_a_amount,
+                token_b_amount,
+            )
         );
-        let token_b_offset = 1;
-        check_valid_swap_curve(
-            fees,
-            CurveType::Offset,
-            Box::new(OffsetCurve { token_b_offset }),
+
+        // deposit enough tokens, success!
+        let token_b_amount = 125;
+        let token_a_amount = token_b_amount * token_b_price;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &user_key,
+            &withdrawer_key,
             token_a_amount,
             token_b_amount,
+            0,
         );
+
+        accounts
+            .deposit_all_token_types(
+                &withdrawer_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1, // doesn't matter
+                token_a_amount,
+                token_b_amount,
+            )
+            .unwrap();
     }
 
     #[test]
-    fn test_valid_swap_with_fee_constraints() {
-        let owner_key = Pubkey::new_unique();
-
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
-
+    fn test_oracle_curve() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
         let token_a_amount = 1_000_000;
-        let token_b_amount = 5_000_000;
-
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let curve = ConstantProductCurve {};
+        let token_b_amount = 1_000_000;
+        let fees = Fees::default();
         let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantProduct,
-            calculator: Box::new(curve),
+            curve_type: CurveType::Oracle,
+            calculator: Box::new(OracleCurve {
+                spread_bps: 100,
+                skew_bps: 0,
+                max_price_age_slots: 10,
+                token_a_decimals: 6,
+                token_b_decimals: 6,
+                token_b_price: None,
+            }),
         };
+        // 1 token B = 2.00000000 token A
+        let oracle_key = Pubkey::new_unique();
+        let mut oracle_account = Account::new(0, 3312, &pyth::program::id());
+        oracle_account.data = pyth_price_data(-8, 200_000_000, 0, 1, 0);
 
-        let owner_key_str = &owner_key.to_string();
-        let valid_curve_types = &[CurveType::ConstantProduct];
-        let constraints = Some(SwapConstraints {
-            owner_key: owner_key_str,
-            valid_curve_types,
-            fees: &fees,
-        });
-        let mut accounts = SwapAccountInfo::new(
-            &owner_key,
-            fees.clone(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        // oracle account missing
+        assert_eq!(
+            Err(ProgramError::NotEnoughAccountKeys),
+            accounts.initialize_swap()
         );
 
-        // initialize swap
-        do_process_instruction_with_fee_constraints(
-            initialize(
-                &SWAP_PROGRAM_ID,
-                &TOKEN_PROGRAM_ID,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &accounts.pool_token_key,
-                accounts.nonce,
-                accounts.fees.clone(),
-                accounts.swap_curve.clone(),
-            )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut Account::default(),
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.pool_token_account,
-                &mut Account::default(),
-            ],
-            &constraints,
-        )
-        .unwrap();
+        // original swap layout has no room for the oracle
+        accounts.oracle_key = Some(oracle_key);
+        accounts.oracle_account = oracle_account.clone();
+        let swap_account = accounts.swap_account.clone();
+        accounts.swap_account = Account::new(0, SwapVersion::V1_LEN, &SWAP_PROGRAM_ID);
+        assert_eq!(
+            Err(SwapError::InvalidCurve.into()),
+            accounts.initialize_swap()
+        );
+        accounts.swap_account = swap_account;
 
-        let authority_key = accounts.authority_key;
+        // not owned by the pyth program
+        accounts.oracle_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(SwapError::InvalidOracleOwner.into()),
+            accounts.initialize_swap()
+        );
+
+        // not a price account
+        accounts.oracle_account = Account::new(0, 3312, &pyth::program::id());
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            accounts.initialize_swap()
+        );
+        accounts.oracle_account = oracle_account.clone();
+
+        accounts.initialize_swap().unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.oracle(), Some(&oracle_key));
+        assert_eq!(swap_state.swap_curve().curve_type, CurveType::Oracle);
 
         let (
             token_a_key,
@@ -5786,1126 +10261,2370 @@ mod tests {
             mut token_b_account,
             pool_key,
             mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &owner_key,
-            &authority_key,
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, 20_200, 10_000, 0);
+
+        // wrong oracle account
+        accounts.oracle_key = Some(Pubkey::new_unique());
+        assert_eq!(
+            Err(SwapError::IncorrectOracleAccount.into()),
+            accounts.swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                20_200,
+                0,
+            )
+        );
+        accounts.oracle_key = Some(oracle_key);
+
+        // oracle account no longer owned by the pyth program
+        accounts.oracle_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(SwapError::InvalidOracleOwner.into()),
+            accounts.swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                20_200,
+                0,
+            )
+        );
+        accounts.oracle_account = oracle_account.clone();
+
+        // price not trading
+        accounts.oracle_account.data = pyth_price_data(-8, 200_000_000, 0, 2, 0);
+        assert_eq!(
+            Err(SwapError::InvalidOraclePrice.into()),
+            accounts.swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                20_200,
+                0,
+            )
+        );
+        accounts.oracle_account = oracle_account;
+
+        // token B bought at the 2.02 ask
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                20_200,
+                10_001,
+            )
+        );
+        accounts
+            .swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                20_200,
+                10_000,
+            )
+            .unwrap();
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.amount, 20_000);
+
+        // token B deposited at the 1.98 bid
+        accounts
+            .deposit_single_token_type_exact_amount_in(
+                &depositor_key,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                10_000,
+                0,
+            )
+            .unwrap();
+        let pool = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+        // 1_000_000_000 * 19_800 / (1_020_200 + 990_000 * 2)
+        assert_eq!(pool.amount, 6_599_560);
+
+        // token A withdrawn at face value
+        accounts
+            .withdraw_single_token_type_exact_amount_out(
+                &depositor_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                19_000,
+                pool.amount,
+            )
+            .unwrap();
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, 19_000);
+    }
+
+    #[test]
+    fn test_price_accumulators() {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let token_a_amount = 1_000;
+        let token_b_amount = 2_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            Fees::default(),
+            swap_curve,
             token_a_amount,
             token_b_amount,
-            0,
         );
 
-        let amount_in = token_a_amount / 2;
-        let minimum_amount_out = 0;
+        TEST_UNIX_TIMESTAMP.store(1_000, Ordering::SeqCst);
+        accounts.initialize_swap().unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let initial = *swap_state.price_accumulators().unwrap();
+        assert_eq!(initial.last_update_timestamp, 1_000);
+        assert_eq!(initial.reserve_a, token_a_amount);
+        assert_eq!(initial.reserve_b, token_b_amount);
 
-        // perform the swap
-        do_process_instruction_with_fee_constraints(
-            swap(
-                &SWAP_PROGRAM_ID,
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, 1_000_000, 0, 0);
+
+        // 1 A = 2 B for 30 seconds, before the swap moves the price
+        TEST_UNIX_TIMESTAMP.store(1_030, Ordering::SeqCst);
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                1_000,
+                0,
+            )
+            .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let after_swap = *swap_state.price_accumulators().unwrap();
+        assert_eq!(after_swap.last_update_timestamp, 1_030);
+        assert_eq!(after_swap.reserve_a, 2_000);
+        assert_eq!(after_swap.reserve_b, 1_000);
+        assert_eq!(
+            time_weighted_average_price(&initial, &after_swap).unwrap(),
+            TimeWeightedPrice {
+                token_a_price: 2 * PRICE_SCALE,
+                token_b_price: PRICE_SCALE / 2,
+            }
+        );
+
+        // 1 A = 0.5 B for 10 more seconds, observed without updating the pool
+        let observation = after_swap.observe(1_040);
+        let time_weighted_price = TimeWeightedPrice {
+            token_a_price: 13 * PRICE_SCALE / 8,
+            token_b_price: 7 * PRICE_SCALE / 8,
+        };
+        assert_eq!(
+            time_weighted_average_price(&initial, &observation).unwrap(),
+            time_weighted_price
+        );
+
+        // tokens sent straight to the pool, followed by a small swap, do not
+        // change the price of the time before the swap
+        let donation = 500_000;
+        do_process_instruction(
+            transfer(
                 &TOKEN_PROGRAM_ID,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.authority_key,
                 &token_a_key,
                 &accounts.token_a_key,
-                &accounts.token_b_key,
-                &token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                Some(&pool_key),
-                Swap {
-                    amount_in,
-                    minimum_amount_out,
-                },
+                &swapper_key,
+                &[],
+                donation,
             )
             .unwrap(),
             vec![
-                &mut accounts.swap_account,
-                &mut Account::default(),
-                &mut Account::default(),
                 &mut token_a_account,
                 &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
-                &mut token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
                 &mut Account::default(),
-                &mut pool_account,
             ],
-            &constraints,
         )
         .unwrap();
+        TEST_UNIX_TIMESTAMP.store(1_040, Ordering::SeqCst);
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                1_000,
+                0,
+            )
+            .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let after_donation = *swap_state.price_accumulators().unwrap();
+        assert_eq!(
+            time_weighted_average_price(&initial, &after_donation).unwrap(),
+            time_weighted_price
+        );
+        let token_a = spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(after_donation.reserve_a, token_a.amount);
+    }
 
-        // check that fees were taken in the host fee account
-        let host_fee_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-        let owner_fee_account =
-            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-        let total_fee = owner_fee_account.amount * host_fee_denominator
-            / (host_fee_denominator - host_fee_numerator);
+    #[test]
+    fn test_price_accumulators_not_constant_product() {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve { token_b_price: 1 }),
+        };
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, Fees::default(), swap_curve, 1_000, 1_000);
+
+        TEST_UNIX_TIMESTAMP.store(1_000, Ordering::SeqCst);
+        accounts.initialize_swap().unwrap();
+
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, 100, 0, 0);
+
+        // the ratio of balances is not the price of a constant price pool
+        TEST_UNIX_TIMESTAMP.store(1_030, Ordering::SeqCst);
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                100,
+                0,
+            )
+            .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.price_accumulators(), None);
+        let swap_v3 = SwapV3::unpack(&accounts.swap_account.data[1..]).unwrap();
         assert_eq!(
-            total_fee,
-            host_fee_account.amount + owner_fee_account.amount
+            swap_v3.price_accumulators,
+            PriceAccumulators::new(1_000, 1_000, 1_000)
         );
     }
 
     #[test]
-    fn test_invalid_swap() {
+    fn test_route_swap() {
         let user_key = Pubkey::new_unique();
         let swapper_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 4;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 9;
-        let host_fee_denominator = 100;
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            ..Fees::default()
         };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 5000;
-        let curve_type = CurveType::ConstantProduct;
         let swap_curve = SwapCurve {
-            curve_type,
+            curve_type: CurveType::ConstantProduct,
             calculator: Box::new(ConstantProductCurve {}),
         };
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        let mut pool_ab = SwapAccountInfo::new(
+            &user_key,
+            fees.clone(),
+            swap_curve.clone(),
+            100_000,
+            200_000,
+        );
+        pool_ab.initialize_swap().unwrap();
+
+        // the second pool trades token B of the first pool for a new token C
+        let mut pool_bc =
+            SwapAccountInfo::new(&user_key, fees.clone(), swap_curve.clone(), 0, 50_000);
+        let (token_b_key, token_b_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &pool_ab.token_b_mint_key,
+            &mut pool_ab.token_b_mint_account,
+            &user_key,
+            &pool_bc.authority_key,
+            400_000,
+        );
+        pool_bc.token_a_key = token_b_key;
+        pool_bc.token_a_account = token_b_account;
+        pool_bc.token_a_mint_key = pool_ab.token_b_mint_key;
+        pool_bc.token_a_mint_account = pool_ab.token_b_mint_account.clone();
+        pool_bc.initialize_swap().unwrap();
+
+        let (token_a_key, mut token_a_account, _, _, _, _) =
+            pool_ab.setup_token_accounts(&user_key, &swapper_key, 10_000, 0, 0);
+        let (_, _, token_c_key, mut token_c_account, _, _) =
+            pool_bc.setup_token_accounts(&user_key, &swapper_key, 0, 0, 0);
+
+        let amount_in = 10_000;
+        let first = swap_curve
+            .swap(amount_in, 100_000, 200_000, TradeDirection::AtoB, &fees)
+            .unwrap();
+        let second = swap_curve
+            .swap(
+                first.destination_amount_swapped,
+                400_000,
+                50_000,
+                TradeDirection::AtoB,
+                &fees,
+            )
+            .unwrap();
+        let amount_out = to_u64(second.destination_amount_swapped).unwrap();
 
-        let initial_a = token_a_amount / 5;
-        let initial_b = token_b_amount / 5;
-        let minimum_token_b_amount = initial_b / 2;
+        // empty route
+        assert_eq!(
+            Err(SwapError::InvalidRoute.into()),
+            route_swap_through(
+                &mut [],
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_c_key,
+                &mut token_c_account,
+                10_000,
+                0,
+            )
+        );
 
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
+        // second pool does not take the output of the first
+        let pool_ab_token_a_key = pool_ab.token_a_key;
+        let pool_ab_token_b_key = pool_ab.token_b_key;
+        let pool_bc_token_b_key = pool_bc.token_a_key;
+        let pool_bc_token_c_key = pool_bc.token_b_key;
+        assert_eq!(
+            Err(SwapError::InvalidRoute.into()),
+            route_swap_through(
+                &mut [
+                    (&mut pool_ab, pool_ab_token_a_key, pool_ab_token_b_key),
+                    (&mut pool_bc, pool_bc_token_c_key, pool_bc_token_b_key),
+                ],
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_c_key,
+                &mut token_c_account,
+                10_000,
+                0,
+            )
+        );
 
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
+        // output of the last pool below the minimum
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            route_swap_through(
+                &mut [
+                    (&mut pool_ab, pool_ab_token_a_key, pool_ab_token_b_key),
+                    (&mut pool_bc, pool_bc_token_b_key, pool_bc_token_c_key),
+                ],
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_c_key,
+                &mut token_c_account,
+                10_000,
+                amount_out + 1,
+            )
+        );
+
+        route_swap_through(
+            &mut [
+                (&mut pool_ab, pool_ab_token_a_key, pool_ab_token_b_key),
+                (&mut pool_bc, pool_bc_token_b_key, pool_bc_token_c_key),
+            ],
+            &swapper_key,
+            &token_a_key,
+            &mut token_a_account,
+            &token_c_key,
+            &mut token_c_account,
+            10_000,
+            amount_out,
+        )
+        .unwrap();
+
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, 0);
+        let token_c = spl_token::state::Account::unpack(&token_c_account.data).unwrap();
+        assert_eq!(token_c.amount, amount_out);
+
+        // intermediate tokens move straight from the first pool to the second
+        let swap_token_a =
+            spl_token::state::Account::unpack(&pool_ab.token_a_account.data).unwrap();
+        assert_eq!(
+            swap_token_a.amount,
+            to_u64(first.new_swap_source_amount).unwrap()
+        );
+        let swap_token_b =
+            spl_token::state::Account::unpack(&pool_ab.token_b_account.data).unwrap();
+        assert_eq!(
+            swap_token_b.amount,
+            to_u64(first.new_swap_destination_amount).unwrap()
+        );
+        let swap_token_b =
+            spl_token::state::Account::unpack(&pool_bc.token_a_account.data).unwrap();
+        assert_eq!(
+            swap_token_b.amount,
+            to_u64(second.new_swap_source_amount).unwrap()
+        );
+        let swap_token_c =
+            spl_token::state::Account::unpack(&pool_bc.token_b_account.data).unwrap();
+        assert_eq!(
+            swap_token_c.amount,
+            to_u64(second.new_swap_destination_amount).unwrap()
+        );
+
+        // each pool takes its owner trading fee
+        for pool in [&pool_ab, &pool_bc].iter() {
+            let fee_account =
+                spl_token::state::Account::unpack(&pool.pool_fee_account.data).unwrap();
+            assert!(fee_account.amount > 0);
         }
+    }
 
-        accounts.initialize_swap().unwrap();
+    #[test]
+    fn test_multi_token_pool() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 1_000,
+            ..Fees::default()
+        };
+        let token_amounts = [1_000_000_000, 1_000_000_000, 1_000_000_000];
+        let curves = [
+            MultiTokenCurve {
+                curve_type: MultiTokenCurveType::Stable,
+                amp: 100,
+                weights: [0; MAX_TOKENS],
+            },
+            MultiTokenCurve {
+                curve_type: MultiTokenCurveType::WeightedConstantProduct,
+                amp: 0,
+                weights: [2, 1, 1, 0, 0, 0, 0, 0],
+            },
+        ];
+        let unpack_amount = |account: &Account| {
+            spl_token::state::Account::unpack(&account.data)
+                .unwrap()
+                .amount
+        };
 
-        // wrong swap account program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
-            accounts.swap_account = wrong_swap_account;
+        for curve in curves.iter() {
+            // too few tokens
+            {
+                let mut accounts = MultiTokenSwapAccountInfo::new(
+                    &user_key,
+                    fees.clone(),
+                    curve.clone(),
+                    &token_amounts[..2],
+                );
+                assert_eq!(
+                    Err(SwapError::InvalidTokenCount.into()),
+                    accounts.initialize_swap()
+                );
+            }
+
+            // repeated mint
+            {
+                let mut accounts = MultiTokenSwapAccountInfo::new(
+                    &user_key,
+                    fees.clone(),
+                    curve.clone(),
+                    &token_amounts,
+                );
+                let (token_key, token_account) = mint_token(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_mint_keys[0],
+                    &mut accounts.token_mint_accounts[0],
+                    &user_key,
+                    &accounts.authority_key,
+                    1_000,
+                );
+                accounts.token_keys[2] = token_key;
+                accounts.token_accounts[2] = token_account;
+                assert_eq!(
+                    Err(SwapError::RepeatedMint.into()),
+                    accounts.initialize_swap()
+                );
+            }
+
+            // curve not allowed by constraints
+            {
+                let mut accounts = MultiTokenSwapAccountInfo::new(
+                    &user_key,
+                    fees.clone(),
+                    curve.clone(),
+                    &token_amounts,
+                );
+                let owner_key = user_key.to_string();
+                let constraints = Some(SwapConstraints {
+                    owner_key: &owner_key,
+                    valid_curve_types: &[],
+                    valid_multi_token_curve_types: &[],
+                    fees: &fees,
+                });
+                let mut authority_account = Account::default();
+                let mut token_program_account = Account::default();
+                let mut instruction_accounts: Vec<&mut Account> = vec![
+                    &mut accounts.swap_account,
+                    &mut authority_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut token_program_account,
+                ];
+                instruction_accounts.extend(accounts.token_accounts.iter_mut());
+                assert_eq!(
+                    Err(SwapError::UnsupportedCurveType.into()),
+                    do_process_instruction_with_fee_constraints(
+                        initialize_multi_token(
+                            &SWAP_PROGRAM_ID,
+                            &TOKEN_PROGRAM_ID,
+                            &accounts.swap_key,
+                            &accounts.authority_key,
+                            &accounts.pool_mint_key,
+                            &accounts.pool_fee_key,
+                            &accounts.pool_token_key,
+                            &accounts.token_keys,
+                            None,
+                            accounts.nonce,
+                            fees.clone(),
+                            curve.clone(),
+                        )
+                        .unwrap(),
+                        instruction_accounts,
+                        &constraints,
+                    )
+                );
+            }
+
+            let mut accounts = MultiTokenSwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                curve.clone(),
+                &token_amounts,
+            );
+            accounts.initialize_swap().unwrap();
+            assert_eq!(
+                Err(SwapError::AlreadyInUse.into()),
+                accounts.initialize_swap()
+            );
+            let token_swap = SwapVersion::unpack_multi_token(&accounts.swap_account.data).unwrap();
+            assert_eq!(token_swap.token_accounts(), &accounts.token_keys[..]);
+            assert_eq!(token_swap.token_mints(), &accounts.token_mint_keys[..]);
+            assert_eq!(token_swap.curve, *curve);
             assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
+                unpack_amount(&accounts.pool_token_account),
+                INITIAL_SWAP_POOL_AMOUNT as u64
             );
-            accounts.swap_account = old_swap_account;
-        }
 
-        // wrong nonce
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &TOKEN_PROGRAM_ID,
+            // swap token 0 for token 2
+            let amount_in = 1_000_000;
+            let (mut token_keys, mut token_accounts, _pool_key, _pool_account) =
+                accounts.setup_token_accounts(&user_key, &user_key, &[amount_in, 0, 0], 0);
+            let (first_accounts, rest_accounts) = token_accounts.split_at_mut(1);
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &user_key,
+                    &token_keys[0],
+                    &mut first_accounts[0],
+                    &accounts.token_keys[0].clone(),
+                    &mut accounts.token_accounts[0].clone(),
+                    amount_in,
+                    0,
+                )
             );
-            accounts.authority_key = bad_authority_key;
+            let balances = accounts.balances();
+            let result = curve
+                .swap(0, 2, amount_in as u128, &balances, &fees)
+                .unwrap();
             assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
+                Err(SwapError::ExceededSlippage.into()),
                 accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
+                    &user_key,
+                    &token_keys[0],
+                    &mut first_accounts[0],
+                    &token_keys[2],
+                    &mut rest_accounts[1],
+                    amount_in,
+                    result.destination_amount_swapped as u64 + 1,
                 )
             );
-            accounts.authority_key = old_authority;
-        }
-
-        // wrong token program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let wrong_program_id = Pubkey::new_unique();
+            accounts
+                .swap(
+                    &user_key,
+                    &token_keys[0],
+                    &mut first_accounts[0],
+                    &token_keys[2],
+                    &mut rest_accounts[1],
+                    amount_in,
+                    result.destination_amount_swapped as u64,
+                )
+                .unwrap();
+            assert_eq!(unpack_amount(&token_accounts[0]), 0);
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
+                unpack_amount(&token_accounts[2]),
+                result.destination_amount_swapped as u64
+            );
+            let new_balances = accounts.balances();
+            assert_eq!(new_balances[0], result.new_swap_source_amount);
+            assert_eq!(new_balances[1], balances[1]);
+            assert_eq!(new_balances[2], result.new_swap_destination_amount);
+            assert!(unpack_amount(&accounts.pool_fee_account) > 0);
+
+            // deposit and withdraw all tokens
+            let pool_token_amount = 10_000_000;
+            let supply = accounts.pool_supply();
+            let deposit_amounts = accounts
+                .balances()
+                .iter()
+                .map(|balance| {
+                    multi_token::pool_tokens_to_trading_tokens(
+                        pool_token_amount as u128,
+                        supply,
+                        *balance,
+                        RoundDirection::Ceiling,
                     )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                ),
+                    .unwrap() as u64
+                })
+                .collect::<Vec<_>>();
+            let (depositor_token_keys, mut depositor_token_accounts, pool_key, mut pool_account) =
+                accounts.setup_token_accounts(&user_key, &depositor_key, &deposit_amounts, 0);
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &depositor_token_keys,
+                    &mut depositor_token_accounts,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_token_amount,
+                    deposit_amounts[..2].to_vec(),
+                )
             );
-        }
+            let mut low_amounts = deposit_amounts.clone();
+            low_amounts[1] -= 1;
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &depositor_token_keys,
+                    &mut depositor_token_accounts,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_token_amount,
+                    low_amounts,
+                )
+            );
+            let balances = accounts.balances();
+            accounts
+                .deposit_all_token_types(
+                    &depositor_key,
+                    &depositor_token_keys,
+                    &mut depositor_token_accounts,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_token_amount,
+                    deposit_amounts.clone(),
+                )
+                .unwrap();
+            assert_eq!(unpack_amount(&pool_account), pool_token_amount);
+            for ((balance, new_balance), deposit_amount) in balances
+                .iter()
+                .zip(accounts.balances().iter())
+                .zip(deposit_amounts.iter())
+            {
+                assert_eq!(*new_balance, balance + *deposit_amount as u128);
+            }
+            for depositor_token_account in depositor_token_accounts.iter() {
+                assert_eq!(unpack_amount(depositor_token_account), 0);
+            }
 
-        // not enough token a to swap
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let withdraw_fee = fees.owner_withdraw_fee(pool_token_amount as u128).unwrap();
+            let burn_amount = pool_token_amount as u128 - withdraw_fee;
+            let supply = accounts.pool_supply();
+            let withdraw_amounts = accounts
+                .balances()
+                .iter()
+                .map(|balance| {
+                    multi_token::pool_tokens_to_trading_tokens(
+                        burn_amount,
+                        supply,
+                        *balance,
+                        RoundDirection::Floor,
+                    )
+                    .unwrap() as u64
+                })
+                .collect::<Vec<_>>();
+            let mut high_amounts = withdraw_amounts.clone();
+            high_amounts[2] += 1;
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a * 2,
-                    minimum_token_b_amount * 2,
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_all_token_types(
+                    &depositor_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &depositor_token_keys,
+                    &mut depositor_token_accounts,
+                    pool_token_amount,
+                    high_amounts,
                 )
             );
-        }
+            let fee_amount = unpack_amount(&accounts.pool_fee_account);
+            accounts
+                .withdraw_all_token_types(
+                    &depositor_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &depositor_token_keys,
+                    &mut depositor_token_accounts,
+                    pool_token_amount,
+                    withdraw_amounts.clone(),
+                )
+                .unwrap();
+            assert_eq!(unpack_amount(&pool_account), 0);
+            assert_eq!(
+                unpack_amount(&accounts.pool_fee_account),
+                fee_amount + withdraw_fee as u64
+            );
+            for (depositor_token_account, withdraw_amount) in
+                depositor_token_accounts.iter().zip(withdraw_amounts.iter())
+            {
+                assert_eq!(unpack_amount(depositor_token_account), *withdraw_amount);
+            }
 
-        // wrong swap token A / B accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let user_transfer_key = Pubkey::new_unique();
+            // deposit one token, and withdraw another
+            let source_token_amount = 5_000_000;
+            let (single_token_keys, mut single_token_accounts, pool_key, mut pool_account) =
+                accounts.setup_token_accounts(
+                    &user_key,
+                    &depositor_key,
+                    &[0, source_token_amount, 0],
+                    0,
+                );
+            let minted_amount = curve
+                .deposit_single_token_type(
+                    1,
+                    source_token_amount as u128,
+                    &accounts.balances(),
+                    accounts.pool_supply(),
+                    &fees,
+                )
+                .unwrap() as u64;
+            assert!(minted_amount > 0);
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_key,
-                        &token_a_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account.clone(),
-                        &mut token_a_account,
-                        &mut token_b_account.clone(),
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                ),
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &single_token_keys[1],
+                    &mut single_token_accounts[1],
+                    &pool_key,
+                    &mut pool_account,
+                    source_token_amount,
+                    minted_amount + 1,
+                )
+            );
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &single_token_keys[1],
+                    &mut single_token_accounts[1],
+                    &pool_key,
+                    &mut pool_account,
+                    source_token_amount,
+                    minted_amount,
+                )
+                .unwrap();
+            assert_eq!(unpack_amount(&pool_account), minted_amount);
+            assert_eq!(unpack_amount(&single_token_accounts[1]), 0);
+
+            let destination_token_amount = 1_000_000;
+            let burn_amount = curve
+                .withdraw_single_token_type_exact_out(
+                    2,
+                    destination_token_amount as u128,
+                    &accounts.balances(),
+                    accounts.pool_supply(),
+                    &fees,
+                )
+                .unwrap();
+            let withdraw_fee = fees.owner_withdraw_fee(burn_amount).unwrap();
+            let pool_token_amount = (burn_amount + withdraw_fee) as u64;
+            assert!(pool_token_amount < minted_amount);
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &depositor_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &single_token_keys[2],
+                    &mut single_token_accounts[2],
+                    destination_token_amount,
+                    pool_token_amount - 1,
+                )
+            );
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &depositor_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &single_token_keys[2],
+                    &mut single_token_accounts[2],
+                    destination_token_amount,
+                    pool_token_amount,
+                )
+                .unwrap();
+            assert_eq!(
+                unpack_amount(&pool_account),
+                minted_amount - pool_token_amount
+            );
+            assert_eq!(
+                unpack_amount(&single_token_accounts[2]),
+                destination_token_amount
             );
-        }
 
-        // wrong user token A / B accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            // accounts of another mint are rejected
+            token_keys.truncate(1);
+            token_accounts.truncate(1);
+            let (other_mint_key, mut other_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let (other_key, mut other_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &other_mint_key,
+                &mut other_mint_account,
+                &user_key,
+                &user_key,
+                amount_in,
+            );
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
+                Err(SwapError::IncorrectSwapAccount.into()),
                 accounts.swap(
-                    &swapper_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    initial_a,
-                    minimum_token_b_amount,
+                    &user_key,
+                    &other_key,
+                    &mut other_account,
+                    &token_keys[0],
+                    &mut token_accounts[0],
+                    amount_in,
+                    0,
                 )
             );
         }
+    }
 
-        // swap from a to a
+    #[test]
+    fn test_concentrated_liquidity_pool() {
+        let user_key = Pubkey::new_unique();
+        let lp_key = Pubkey::new_unique();
+        let trader_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 30,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            ..Fees::default()
+        };
+        let tick_spacing = 60;
+        let sqrt_price = sqrt_price_at_tick(0).unwrap();
+        let unpack_amount = |account: &Account| {
+            spl_token::state::Account::unpack(&account.data)
+                .unwrap()
+                .amount
+        };
+
+        // invalid tick spacing
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let mut accounts =
+                ConcentratedSwapAccountInfo::new(&user_key, fees.clone(), 0, sqrt_price);
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account.clone(),
-                    &swap_token_a_key,
-                    &swap_token_a_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
             );
         }
 
-        // incorrect mint provided
+        // no minimum position liquidity
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
+            let mut accounts =
+                ConcentratedSwapAccountInfo::new(&user_key, fees.clone(), tick_spacing, sqrt_price);
+            accounts.min_position_liquidity = 0;
+            assert_eq!(
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
+            );
+        }
 
+        // fee accounts of the wrong mints
+        {
+            let mut accounts =
+                ConcentratedSwapAccountInfo::new(&user_key, fees.clone(), tick_spacing, sqrt_price);
+            std::mem::swap(&mut accounts.fee_a_key, &mut accounts.fee_b_key);
+            std::mem::swap(&mut accounts.fee_a_account, &mut accounts.fee_b_account);
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.initialize_swap()
             );
+        }
 
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
+        let mut accounts =
+            ConcentratedSwapAccountInfo::new(&user_key, fees, tick_spacing, sqrt_price);
+        accounts.initialize_swap().unwrap();
+        assert_eq!(
+            Err(SwapError::AlreadyInUse.into()),
+            accounts.initialize_swap()
+        );
+        let token_swap = accounts.token_swap();
+        assert_eq!(token_swap.curve.tick_current, 0);
+        assert_eq!(token_swap.curve.liquidity, 0);
+
+        // positions over a wide and a narrow range around the price
+        assert_eq!(
+            Err(SwapError::InvalidTick.into()),
+            accounts.open_position(&lp_key, -600, 630).map(|_| ())
+        );
+        let (wide_key, mut wide_account) = accounts.open_position(&lp_key, -6000, 6000).unwrap();
+        let (narrow_key, mut narrow_account) = accounts.open_position(&lp_key, -600, 600).unwrap();
+
+        let initial_amount = 1_000_000_000_000;
+        let (lp_a_key, mut lp_a_account, lp_b_key, mut lp_b_account) =
+            accounts.setup_token_accounts(&user_key, &lp_key, initial_amount, initial_amount);
+        let liquidity = 10_000_000_000u128;
+        assert_eq!(
+            Err(SwapError::PositionBelowMinLiquidity.into()),
+            accounts.increase_liquidity(
+                &lp_key,
+                &wide_key,
+                &mut wide_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                accounts.min_position_liquidity - 1,
+                u64::MAX,
+                u64::MAX,
+            )
+        );
+        let (wide_a, wide_b) = accounts
+            .token_swap()
+            .curve
+            .token_amounts(-6000, 6000, liquidity, RoundDirection::Ceiling)
+            .unwrap();
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.increase_liquidity(
+                &lp_key,
+                &wide_key,
+                &mut wide_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                to_u64(wide_a).unwrap() - 1,
+                u64::MAX,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::InvalidOwner.into()),
+            accounts.increase_liquidity(
+                &trader_key,
+                &wide_key,
+                &mut wide_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                u64::MAX,
+                u64::MAX,
+            )
+        );
+        accounts
+            .increase_liquidity(
+                &lp_key,
+                &wide_key,
+                &mut wide_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                u64::MAX,
+                u64::MAX,
+            )
+            .unwrap();
+        assert_eq!(
+            unpack_amount(&accounts.token_a_account),
+            to_u64(wide_a).unwrap()
+        );
+        assert_eq!(
+            unpack_amount(&accounts.token_b_account),
+            to_u64(wide_b).unwrap()
+        );
+        assert_eq!(
+            unpack_amount(&lp_a_account),
+            initial_amount - to_u64(wide_a).unwrap()
+        );
+        accounts
+            .increase_liquidity(
+                &lp_key,
+                &narrow_key,
+                &mut narrow_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                u64::MAX,
+                u64::MAX,
+            )
+            .unwrap();
+        let token_swap = accounts.token_swap();
+        assert_eq!(token_swap.curve.liquidity, 2 * liquidity);
+        assert_eq!(token_swap.curve.ticks.len(), 4);
+        let position = ConcentratedPosition::unpack(&narrow_account.data).unwrap();
+        assert_eq!(position.liquidity, liquidity);
+        assert_eq!(position.owner, lp_key);
+
+        // swap through the narrow range, crossing its lower tick
+        let amount_in = 800_000_000;
+        let (trader_a_key, mut trader_a_account, trader_b_key, mut trader_b_account) =
+            accounts.setup_token_accounts(&user_key, &trader_key, amount_in, 0);
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.swap(
+                &trader_key,
+                TradeDirection::AtoB,
+                &trader_a_key,
+                &mut trader_a_account,
+                &trader_b_key,
+                &mut trader_b_account,
+                amount_in,
+                amount_in,
+            )
+        );
+        let swap_token_b_before = unpack_amount(&accounts.token_b_account);
+        accounts
+            .swap(
+                &trader_key,
+                TradeDirection::AtoB,
+                &trader_a_key,
+                &mut trader_a_account,
+                &trader_b_key,
+                &mut trader_b_account,
+                amount_in,
+                0,
+            )
+            .unwrap();
+        let owner_fee = amount_in / 2_000;
+        let amount_out = unpack_amount(&trader_b_account);
+        assert!(amount_out > 0);
+        assert_eq!(unpack_amount(&trader_a_account), 0);
+        assert_eq!(unpack_amount(&accounts.fee_a_account), owner_fee);
+        assert_eq!(
+            unpack_amount(&accounts.token_b_account),
+            swap_token_b_before - amount_out
+        );
+        let token_swap = accounts.token_swap();
+        assert!(token_swap.curve.tick_current < -600);
+        assert_eq!(token_swap.curve.liquidity, liquidity);
+
+        // both positions earned trading fees in token A, the wide one for
+        // longer
+        let (lp_fee_a_key, mut lp_fee_a_account, lp_fee_b_key, mut lp_fee_b_account) =
+            accounts.setup_token_accounts(&user_key, &lp_key, 0, 0);
+        accounts
+            .collect_fees(
+                &lp_key,
+                &narrow_key,
+                &mut narrow_account,
+                &lp_fee_a_key,
+                &mut lp_fee_a_account,
+                &lp_fee_b_key,
+                &mut lp_fee_b_account,
+            )
+            .unwrap();
+        let narrow_fees = unpack_amount(&lp_fee_a_account);
+        accounts
+            .collect_fees(
+                &lp_key,
+                &wide_key,
+                &mut wide_account,
+                &lp_fee_a_key,
+                &mut lp_fee_a_account,
+                &lp_fee_b_key,
+                &mut lp_fee_b_account,
+            )
+            .unwrap();
+        let wide_fees = unpack_amount(&lp_fee_a_account) - narrow_fees;
+        assert!(narrow_fees > 0);
+        assert!(wide_fees > narrow_fees);
+        assert_eq!(unpack_amount(&lp_fee_b_account), 0);
+        let trade_fee = (amount_in - owner_fee) * 30 / 10_000;
+        assert!(narrow_fees + wide_fees <= trade_fee);
+        assert!(narrow_fees + wide_fees + 2 >= trade_fee);
+        let position = ConcentratedPosition::unpack(&wide_account.data).unwrap();
+        assert_eq!(position.fees_owed_a, 0);
+
+        // the narrow position is out of range and only holds token A
+        assert_eq!(
+            Err(SwapError::InsufficientLiquidity.into()),
+            accounts.decrease_liquidity(
+                &lp_key,
+                &narrow_key,
+                &mut narrow_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity + 1,
+                0,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PositionBelowMinLiquidity.into()),
+            accounts.decrease_liquidity(
+                &lp_key,
+                &narrow_key,
+                &mut narrow_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity - 1,
+                0,
+                0,
+            )
+        );
+        let lp_a_before = unpack_amount(&lp_a_account);
+        let lp_b_before = unpack_amount(&lp_b_account);
+        accounts
+            .decrease_liquidity(
+                &lp_key,
+                &narrow_key,
+                &mut narrow_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                0,
+                0,
+            )
+            .unwrap();
+        assert!(unpack_amount(&lp_a_account) > lp_a_before);
+        assert_eq!(unpack_amount(&lp_b_account), lp_b_before);
+        let token_swap = accounts.token_swap();
+        assert_eq!(token_swap.curve.ticks.len(), 2);
+        assert_eq!(token_swap.curve.liquidity, liquidity);
+
+        // the empty position can be closed, but not the wide one
+        let close = |position_key: &Pubkey, position_account: &mut Account| {
+            let destination_key = Pubkey::new_unique();
+            let mut destination_account = Account::default();
+            do_process_instruction(
+                close_position(&SWAP_PROGRAM_ID, position_key, &lp_key, &destination_key).unwrap(),
+                vec![
+                    position_account,
+                    &mut Account::default(),
+                    &mut destination_account,
+                ],
+            )
+            .map(|_| destination_account.lamports)
+        };
+        assert_eq!(
+            Err(SwapError::PositionNotEmpty.into()),
+            close(&wide_key, &mut wide_account)
+        );
+        assert_eq!(Ok(1_000), close(&narrow_key, &mut narrow_account));
+        assert_eq!(narrow_account.lamports, 0);
+        assert!(narrow_account.data.iter().all(|byte| *byte == 0));
+
+        // swapping back crosses into the wide range only
+        let (trader_a_key, mut trader_a_account, trader_b_key, mut trader_b_account) =
+            accounts.setup_token_accounts(&user_key, &trader_key, 0, amount_out);
+        accounts
+            .swap(
+                &trader_key,
+                TradeDirection::BtoA,
+                &trader_b_key,
+                &mut trader_b_account,
+                &trader_a_key,
+                &mut trader_a_account,
+                amount_out,
+                0,
+            )
+            .unwrap();
+        assert!(unpack_amount(&trader_a_account) < amount_in);
+        assert_eq!(unpack_amount(&accounts.fee_b_account), amount_out / 2_000);
+        let token_swap = accounts.token_swap();
+        assert!(token_swap.curve.tick_current >= -600);
+        assert_eq!(token_swap.curve.liquidity, liquidity);
+
+        // more than the whole range can hold
+        assert_eq!(
+            Err(SwapError::InsufficientLiquidity.into()),
+            accounts.swap(
+                &lp_key,
+                TradeDirection::AtoB,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                initial_amount / 2,
+                0,
+            )
+        );
+    }
+
+    #[test]
+    fn test_concentrated_max_ticks() {
+        let user_key = Pubkey::new_unique();
+        let lp_key = Pubkey::new_unique();
+        let tick_spacing = 60;
+        let sqrt_price = sqrt_price_at_tick(0).unwrap();
+        let resize = |accounts: &mut ConcentratedSwapAccountInfo, max_ticks: u16| {
+            accounts.max_ticks = max_ticks;
+            accounts.swap_account = Account::new(
+                0,
+                SwapVersion::concentrated_len(max_ticks),
+                &SWAP_PROGRAM_ID,
+            );
+        };
+
+        // room for fewer ticks than one position uses
+        {
+            let mut accounts = ConcentratedSwapAccountInfo::new(
+                &user_key,
+                Fees::default(),
+                tick_spacing,
+                sqrt_price,
+            );
+            resize(&mut accounts, 1);
+            assert_eq!(
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
+            );
         }
 
-        // incorrect fee account provided
+        // account not sized for the max ticks
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
+            let mut accounts = ConcentratedSwapAccountInfo::new(
+                &user_key,
+                Fees::default(),
+                tick_spacing,
+                sqrt_price,
+            );
+            accounts.max_ticks = 4;
             assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
+                Err(ProgramError::InvalidAccountData),
+                accounts.initialize_swap()
             );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
         }
 
-        // no approval
+        let mut accounts =
+            ConcentratedSwapAccountInfo::new(&user_key, Fees::default(), tick_spacing, sqrt_price);
+        resize(&mut accounts, 4);
+        accounts.initialize_swap().unwrap();
+        assert_eq!(accounts.token_swap().curve.max_ticks, 4);
+
+        let initial_amount = 1_000_000_000_000;
+        let (lp_a_key, mut lp_a_account, lp_b_key, mut lp_b_account) =
+            accounts.setup_token_accounts(&user_key, &lp_key, initial_amount, initial_amount);
+        let liquidity = accounts.min_position_liquidity;
+        let mut add_position =
+            |accounts: &mut ConcentratedSwapAccountInfo, tick_lower: i32, tick_upper: i32| {
+                let (position_key, mut position_account) =
+                    accounts.open_position(&lp_key, tick_lower, tick_upper)?;
+                accounts.increase_liquidity(
+                    &lp_key,
+                    &position_key,
+                    &mut position_account,
+                    &lp_a_key,
+                    &mut lp_a_account,
+                    &lp_b_key,
+                    &mut lp_b_account,
+                    liquidity,
+                    u64::MAX,
+                    u64::MAX,
+                )
+            };
+
+        // the pool fills up with the ticks of two positions
+        add_position(&mut accounts, -600, 600).unwrap();
+        add_position(&mut accounts, -1200, 1200).unwrap();
+        assert_eq!(accounts.token_swap().curve.ticks.len(), 4);
+        assert_eq!(
+            Err(SwapError::TooManyTicks.into()),
+            add_position(&mut accounts, -1800, 1800)
+        );
+        assert_eq!(
+            Err(SwapError::TooManyTicks.into()),
+            add_position(&mut accounts, -600, 1800)
+        );
+
+        // positions over ticks already initialized can still be added
+        add_position(&mut accounts, -1200, 600).unwrap();
+        let token_swap = accounts.token_swap();
+        assert_eq!(token_swap.curve.ticks.len(), 4);
+        assert_eq!(token_swap.curve.liquidity, 3 * liquidity);
+    }
+
+    #[test]
+    fn test_admin_update_fees_and_fee_account() {
+        let user_key = Pubkey::new_unique();
+        let admin_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let new_fees = Fees {
+            trade_fee_numerator: 30,
+            owner_trade_fee_numerator: 10,
+            ..fees.clone()
+        };
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+
+        // swaps without an admin cannot be changed
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let user_transfer_key = Pubkey::new_unique();
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve.clone(),
+                1_000_000,
+                1_000_000,
+            );
+            accounts.initialize_swap().unwrap();
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
+                Err(SwapError::InvalidAdmin.into()),
                 do_process_instruction(
-                    swap(
+                    update_fees(
                         &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
+                        &admin_key,
+                        new_fees.clone()
                     )
                     .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                ),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
             );
         }
 
-        // output token value 0
+        // only the latest swap version has room for an admin
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve.clone(),
+                1_000_000,
+                1_000_000,
+            );
+            accounts.swap_account = Account::new(0, SwapVersion::V2_LEN, &SWAP_PROGRAM_ID);
+            accounts.admin_key = Some(admin_key);
             assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &swap_token_b_key,
-                    &swap_token_a_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    1,
-                    1,
-                )
+                Err(SwapError::InvalidAdmin.into()),
+                accounts.initialize_swap()
             );
         }
 
-        // slippage exceeded: minimum out amount too high
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees.clone(), swap_curve, 1_000_000, 1_000_000);
+        accounts.admin_key = Some(admin_key);
+        accounts.initialize_swap().unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.admin(), Some(&admin_key));
+
+        // wrong admin
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount * 2,
+                Err(SwapError::InvalidAdmin.into()),
+                do_process_instruction(
+                    update_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &user_key,
+                        new_fees.clone()
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
         }
 
-        // invalid input: can't use swap pool as user source / dest
+        // admin did not sign
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let authority_key = accounts.authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &authority_key,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let mut instruction = update_fees(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                new_fees.clone(),
+            )
+            .unwrap();
+            instruction.accounts[1].is_signer = false;
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    instruction,
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
         }
 
-        // still correct: constraint specified, no host fee account
+        // invalid fees
         {
-            let authority_key = accounts.authority_key;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let owner_key = &swapper_key.to_string();
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
+            let invalid_fees = Fees {
+                trade_fee_denominator: 0,
+                ..new_fees.clone()
             };
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types: &[],
-                fees: &fees,
-            });
-            do_process_instruction_with_fee_constraints(
-                swap(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.swap_key,
-                    &accounts.authority_key,
-                    &accounts.authority_key,
-                    &token_a_key,
-                    &accounts.token_a_key,
-                    &accounts.token_b_key,
-                    &token_b_key,
-                    &accounts.pool_mint_key,
-                    &accounts.pool_fee_key,
-                    None,
-                    Swap {
-                        amount_in: initial_a,
-                        minimum_amount_out: minimum_token_b_amount,
-                    },
+            assert_eq!(
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction(
+                    update_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        invalid_fees
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
-                .unwrap(),
-                vec![
-                    &mut accounts.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut token_a_account,
-                    &mut accounts.token_a_account,
-                    &mut accounts.token_b_account,
-                    &mut token_b_account,
-                    &mut accounts.pool_mint_account,
-                    &mut accounts.pool_fee_account,
-                    &mut Account::default(),
-                ],
-                &constraints,
-            )
-            .unwrap();
+            );
         }
-
-        // invalid mint for host fee account
-        {
-            let authority_key = accounts.authority_key;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let (
-                bad_token_a_key,
-                mut bad_token_a_account,
-                _token_b_key,
-                mut _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let owner_key = &swapper_key.to_string();
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
+
+        // fees lower than the program constraints
+        {
+            let owner_key = &user_key.to_string();
             let constraints = Some(SwapConstraints {
                 owner_key,
-                valid_curve_types: &[],
-                fees: &fees,
+                valid_curve_types: &[CurveType::ConstantProduct],
+                valid_multi_token_curve_types: &[],
+                fees: &new_fees,
             });
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
+                Err(SwapError::InvalidFee.into()),
                 do_process_instruction_with_fee_constraints(
-                    swap(
+                    update_fees(
                         &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        Some(&bad_token_a_key),
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: 0,
-                        },
+                        &admin_key,
+                        fees.clone()
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                    &constraints,
+                )
+            );
+        }
+
+        do_process_instruction(
+            update_fees(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                new_fees.clone(),
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(*swap_state.fees(), new_fees);
+
+        let (new_fee_key, new_fee_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &accounts.pool_mint_key,
+            &mut accounts.pool_mint_account,
+            &accounts.authority_key,
+            &user_key,
+            0,
+        );
+
+        // fee account of the wrong mint
+        {
+            let (token_a_key, mut token_a_account, _, _, _, _) =
+                accounts.setup_token_accounts(&user_key, &user_key, 0, 0, 0);
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                do_process_instruction(
+                    set_fee_account(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        &token_a_key
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
-                        &mut Account::default(),
                         &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
+                    ],
+                )
+            );
+        }
+
+        // fee account owned by the swap authority
+        {
+            let (authority_fee_key, mut authority_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key.clone(),
+                0,
+            );
+            assert_eq!(
+                Err(SwapError::InvalidOutputOwner.into()),
+                do_process_instruction(
+                    set_fee_account(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        &authority_fee_key
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
                         &mut Account::default(),
-                        &mut bad_token_a_account,
+                        &mut authority_fee_account,
                     ],
-                    &constraints,
-                ),
+                )
             );
         }
+
+        do_process_instruction(
+            set_fee_account(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                &new_fee_key,
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut new_fee_account.clone(),
+            ],
+        )
+        .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(*swap_state.pool_fee_account(), new_fee_key);
+
+        // the old fee account is rejected, the new one receives the owner fees
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, 100_000, 0, 0);
+        assert_eq!(
+            Err(SwapError::IncorrectFeeAccount.into()),
+            accounts.swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                100_000,
+                0,
+            )
+        );
+        accounts.pool_fee_key = new_fee_key;
+        accounts.pool_fee_account = new_fee_account;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                100_000,
+                0,
+            )
+            .unwrap();
+        let fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert!(fee_account.amount > 0);
     }
 
     #[test]
-    fn test_overdraw_offset_curve() {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
+    fn test_admin_ramp_amp() {
+        let user_key = Pubkey::new_unique();
+        let admin_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 1_000_000;
 
-        let token_a_amount = 1_000_000_000;
-        let token_b_amount = 0;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
+        // only stable curves have an amplification
+        {
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve {}),
+            };
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                Fees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+            );
+            accounts.admin_key = Some(admin_key);
+            accounts.initialize_swap().unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidAmpRamp.into()),
+                do_process_instruction(
+                    ramp_amp(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        RampAmp {
+                            target_amp: 100,
+                            stop_ramp_timestamp: 1_000_000_000,
+                        },
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
 
-        let token_b_offset = 2_000_000;
         let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Box::new(OffsetCurve { token_b_offset }),
+            curve_type: CurveType::Stable,
+            calculator: Box::new(StableCurve { amp: 100 }),
         };
-        let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            Fees::default(),
+            swap_curve.clone(),
+            token_a_amount,
+            token_b_amount,
+        );
+        accounts.admin_key = Some(admin_key);
+        accounts.initialize_swap().unwrap();
 
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        // too steep
+        {
+            assert_eq!(
+                Err(SwapError::InvalidAmpRamp.into()),
+                do_process_instruction(
+                    ramp_amp(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        RampAmp {
+                            target_amp: 1_001,
+                            stop_ramp_timestamp: 1_000_000_000,
+                        },
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
 
-        accounts.initialize_swap().unwrap();
+        // ramp slowly enough that the amplification has not moved from 100
+        // by the time of the swap
+        do_process_instruction(
+            ramp_amp(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                RampAmp {
+                    target_amp: 200,
+                    stop_ramp_timestamp: 1_000_000_000,
+                },
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let amp_ramp = *swap_state.amp_ramp().unwrap();
+        assert_eq!(amp_ramp.initial_amp, 100);
+        assert_eq!(amp_ramp.target_amp, 200);
+        assert_eq!(amp_ramp.stop_timestamp, 1_000_000_000);
+        assert_eq!(
+            swap_state.swap_curve().stable_curve(),
+            Some(StableCurve { amp: 200 })
+        );
 
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
-        let initial_a = 500_000;
-        let initial_b = 1_000;
+        let amount_in = 100_000;
+        let expected = swap_curve
+            .swap(
+                amount_in as u128,
+                token_a_amount as u128,
+                token_b_amount as u128,
+                TradeDirection::AtoB,
+                &Fees::default(),
+            )
+            .unwrap();
+        let ramped_swap_curve = swap_state.swap_curve();
+        let at_target = ramped_swap_curve
+            .swap(
+                amount_in as u128,
+                token_a_amount as u128,
+                token_b_amount as u128,
+                TradeDirection::AtoB,
+                &Fees::default(),
+            )
+            .unwrap();
+        assert!(at_target.destination_amount_swapped > expected.destination_amount_swapped);
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, amount_in, 0, 0);
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                amount_in,
+                0,
+            )
+            .unwrap();
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.amount as u128, expected.destination_amount_swapped);
+    }
+
+    #[test]
+    fn test_admin_pause_and_withdraw_only() {
+        let user_key = Pubkey::new_unique();
+        let admin_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, Fees::default(), swap_curve, 1_000_000, 1_000_000);
+        accounts.admin_key = Some(admin_key);
+        accounts.initialize_swap().unwrap();
 
         let (
             token_a_key,
             mut token_a_account,
             token_b_key,
             mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, 10_000, 10_000, 0);
+        accounts
+            .deposit_all_token_types(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1_000_000,
+                10_000,
+                10_000,
+            )
+            .unwrap();
 
-        // swap a to b way, fails, there's no liquidity
-        let a_to_b_amount = initial_a;
-        let minimum_token_b_amount = 0;
+        // only the admin can change the status
+        assert_eq!(
+            Err(SwapError::InvalidAdmin.into()),
+            do_process_instruction(
+                set_status(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &user_key,
+                    SwapStatus::Paused
+                )
+                .unwrap(),
+                vec![&mut accounts.swap_account, &mut Account::default()],
+            )
+        );
 
+        // paused, nothing goes through
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::Paused,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.status(), SwapStatus::Paused);
         assert_eq!(
-            Err(SwapError::ZeroTradingTokens.into()),
+            Err(SwapError::PoolPaused.into()),
             accounts.swap(
-                &swapper_key,
+                &depositor_key,
                 &token_a_key,
                 &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
                 &token_b_key,
                 &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                100,
+                0,
             )
         );
-
-        // swap b to a, succeeds at offset price
-        let b_to_a_amount = initial_b;
-        let minimum_token_a_amount = 0;
-        accounts
-            .swap(
-                &swapper_key,
-                &token_b_key,
-                &mut token_b_account,
-                &swap_token_b_key,
-                &swap_token_a_key,
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.deposit_all_token_types(
+                &depositor_key,
                 &token_a_key,
                 &mut token_a_account,
-                b_to_a_amount,
-                minimum_token_a_amount,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1_000_000,
+                10_000,
+                10_000,
             )
-            .unwrap();
-
-        // try a to b again, succeeds due to new liquidity
-        accounts
-            .swap(
-                &swapper_key,
+        );
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.withdraw_all_token_types(
+                &depositor_key,
+                &pool_key,
+                &mut pool_account,
                 &token_a_key,
                 &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
                 &token_b_key,
                 &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                100_000,
+                0,
+                0,
             )
-            .unwrap();
+        );
 
-        // try a to b again, fails due to no more liquidity
+        // withdraw only, liquidity providers can still leave
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::WithdrawOnly,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
         assert_eq!(
-            Err(SwapError::ZeroTradingTokens.into()),
+            Err(SwapError::PoolWithdrawOnly.into()),
             accounts.swap(
-                &swapper_key,
+                &depositor_key,
                 &token_a_key,
                 &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
                 &token_b_key,
                 &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                100,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolWithdrawOnly.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &pool_key,
+                &mut pool_account,
+                100,
+                0,
             )
         );
-
-        // Try to deposit, fails because deposits are not allowed for offset
-        // curve swaps
-        {
-            let initial_a = 100;
-            let initial_b = 100;
-            let pool_amount = 100;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::UnsupportedCurveOperation.into()),
-                accounts.deposit_all_token_types(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount,
-                    initial_a,
-                    initial_b,
-                )
-            );
-        }
-    }
-
-    #[test]
-    fn test_withdraw_all_offset_curve() {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
-
-        let token_a_amount = 1_000_000_000;
-        let token_b_amount = 10;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_b_offset = 2_000_000;
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Box::new(OffsetCurve { token_b_offset }),
-        };
-        let total_pool = swap_curve.calculator.new_pool_supply();
-        let user_key = Pubkey::new_unique();
-        let withdrawer_key = Pubkey::new_unique();
-
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-
-        accounts.initialize_swap().unwrap();
-
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
-
-        let pool_key = accounts.pool_token_key;
-        let mut pool_account = accounts.pool_token_account.clone();
-
-        // WithdrawAllTokenTypes takes all tokens for A and B.
-        // The curve's calculation for token B will say to transfer
-        // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
-        // moved.
         accounts
             .withdraw_all_token_types(
-                &user_key,
+                &depositor_key,
                 &pool_key,
                 &mut pool_account,
                 &token_a_key,
                 &mut token_a_account,
                 &token_b_key,
                 &mut token_b_account,
-                total_pool.try_into().unwrap(),
+                100_000,
                 0,
                 0,
             )
             .unwrap();
+        accounts
+            .withdraw_single_token_type_exact_amount_out(
+                &depositor_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                10,
+                100_000,
+            )
+            .unwrap();
 
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, token_a_amount);
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-        assert_eq!(token_b.amount, token_b_amount);
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(swap_token_a.amount, 0);
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(swap_token_b.amount, 0);
+        // active again
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::Active,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        accounts
+            .swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &accounts.token_a_key.clone(),
+                &accounts.token_b_key.clone(),
+                &token_b_key,
+                &mut token_b_account,
+                100,
+                0,
+            )
+            .unwrap();
     }
 
     #[test]
-    fn test_withdraw_all_constant_price_curve() {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
-
-        // initialize "unbalanced", so that withdrawing all will have some issues
-        // A: 1_000_000_000
-        // B: 2_000_000_000 (1_000 * 2_000_000)
-        let swap_token_a_amount = 1_000_000_000;
-        let swap_token_b_amount = 1_000;
-        let token_b_price = 2_000_000;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantPrice,
-            calculator: Box::new(ConstantPriceCurve { token_b_price }),
-        };
-        let total_pool = swap_curve.calculator.new_pool_supply();
+    fn test_set_status_multi_token() {
         let user_key = Pubkey::new_unique();
-        let withdrawer_key = Pubkey::new_unique();
+        let admin_key = Pubkey::new_unique();
+        let curve = MultiTokenCurve {
+            curve_type: MultiTokenCurveType::Stable,
+            amp: 100,
+            weights: [0; MAX_TOKENS],
+        };
+        let token_amounts = [1_000_000_000, 1_000_000_000, 1_000_000_000];
 
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
-            swap_curve,
-            swap_token_a_amount,
-            swap_token_b_amount,
-        );
+        // a swap without an admin cannot be paused
+        {
+            let mut accounts = MultiTokenSwapAccountInfo::new(
+                &user_key,
+                Fees::default(),
+                curve.clone(),
+                &token_amounts,
+            );
+            accounts.initialize_swap().unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidAdmin.into()),
+                do_process_instruction(
+                    set_status(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        SwapStatus::Paused
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
 
+        let mut accounts =
+            MultiTokenSwapAccountInfo::new(&user_key, Fees::default(), curve, &token_amounts);
+        accounts.admin_key = Some(admin_key);
         accounts.initialize_swap().unwrap();
-
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
-
+        let token_swap = SwapVersion::unpack_multi_token(&accounts.swap_account.data).unwrap();
+        assert_eq!(token_swap.admin(), Some(&admin_key));
+        assert_eq!(token_swap.status, SwapStatus::Active);
+
+        let amount_in = 1_000;
+        let (token_keys, mut token_accounts, _pool_key, _pool_account) =
+            accounts.setup_token_accounts(&user_key, &user_key, &[10 * amount_in, 0, 0], 0);
+        let (first_accounts, rest_accounts) = token_accounts.split_at_mut(1);
         let pool_key = accounts.pool_token_key;
         let mut pool_account = accounts.pool_token_account.clone();
 
-        // WithdrawAllTokenTypes will not take all token A and B, since their
-        // ratio is unbalanced.  It will try to take 1_500_000_000 worth of
-        // each token, which means 1_500_000_000 token A, and 750 token B.
-        // With no slippage, this will leave 250 token B in the pool.
+        // only the admin can change the status
         assert_eq!(
-            Err(SwapError::ExceededSlippage.into()),
-            accounts.withdraw_all_token_types(
+            Err(SwapError::InvalidAdmin.into()),
+            do_process_instruction(
+                set_status(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &user_key,
+                    SwapStatus::Paused
+                )
+                .unwrap(),
+                vec![&mut accounts.swap_account, &mut Account::default()],
+            )
+        );
+
+        // paused, nothing goes through
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::Paused,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        let token_swap = SwapVersion::unpack_multi_token(&accounts.swap_account.data).unwrap();
+        assert_eq!(token_swap.status, SwapStatus::Paused);
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.swap(
+                &user_key,
+                &token_keys[0],
+                &mut first_accounts[0],
+                &token_keys[2],
+                &mut rest_accounts[1],
+                amount_in,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
                 &user_key,
+                &token_keys[0],
+                &mut first_accounts[0],
                 &pool_key,
                 &mut pool_account,
-                &token_a_key,
-                &mut token_a_account,
-                &token_b_key,
-                &mut token_b_account,
-                total_pool.try_into().unwrap(),
-                swap_token_a_amount,
-                swap_token_b_amount,
+                amount_in,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.withdraw_single_token_type_exact_amount_out(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_keys[1],
+                &mut rest_accounts[0],
+                amount_in,
+                u64::MAX,
             )
         );
 
-        accounts
-            .withdraw_all_token_types(
+        // withdraw only, liquidity providers can still leave
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::WithdrawOnly,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(SwapError::PoolWithdrawOnly.into()),
+            accounts.swap(
+                &user_key,
+                &token_keys[0],
+                &mut first_accounts[0],
+                &token_keys[2],
+                &mut rest_accounts[1],
+                amount_in,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolWithdrawOnly.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
                 &user_key,
+                &token_keys[0],
+                &mut first_accounts[0],
                 &pool_key,
                 &mut pool_account,
-                &token_a_key,
-                &mut token_a_account,
-                &token_b_key,
-                &mut token_b_account,
-                total_pool.try_into().unwrap(),
+                amount_in,
                 0,
+            )
+        );
+        accounts
+            .withdraw_single_token_type_exact_amount_out(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_keys[1],
+                &mut rest_accounts[0],
+                amount_in,
+                u64::MAX,
+            )
+            .unwrap();
+
+        // active again
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::Active,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        accounts
+            .swap(
+                &user_key,
+                &token_keys[0],
+                &mut first_accounts[0],
+                &token_keys[2],
+                &mut rest_accounts[1],
+                amount_in,
                 0,
             )
             .unwrap();
+    }
 
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, swap_token_a_amount);
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-        assert_eq!(token_b.amount, 750);
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(swap_token_a.amount, 0);
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(swap_token_b.amount, 250);
+    #[test]
+    fn test_set_status_concentrated() {
+        let user_key = Pubkey::new_unique();
+        let admin_key = Pubkey::new_unique();
+        let lp_key = Pubkey::new_unique();
+        let tick_spacing = 60;
+        let sqrt_price = sqrt_price_at_tick(0).unwrap();
 
-        // deposit now, not enough to cover the tokens already in there
-        let token_b_amount = 10;
-        let token_a_amount = token_b_amount * token_b_price;
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &user_key,
-            &withdrawer_key,
-            token_a_amount,
-            token_b_amount,
-            0,
+        // a swap without an admin cannot be paused
+        {
+            let mut accounts = ConcentratedSwapAccountInfo::new(
+                &user_key,
+                Fees::default(),
+                tick_spacing,
+                sqrt_price,
+            );
+            accounts.initialize_swap().unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidAdmin.into()),
+                do_process_instruction(
+                    set_status(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &admin_key,
+                        SwapStatus::Paused
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
+
+        let mut accounts =
+            ConcentratedSwapAccountInfo::new(&user_key, Fees::default(), tick_spacing, sqrt_price);
+        accounts.admin_key = Some(admin_key);
+        accounts.initialize_swap().unwrap();
+        let token_swap = accounts.token_swap();
+        assert_eq!(token_swap.admin(), Some(&admin_key));
+        assert_eq!(token_swap.status, SwapStatus::Active);
+
+        let initial_amount = 1_000_000_000_000;
+        let liquidity = 10_000_000_000u128;
+        let (position_key, mut position_account) =
+            accounts.open_position(&lp_key, -600, 600).unwrap();
+        let (lp_a_key, mut lp_a_account, lp_b_key, mut lp_b_account) =
+            accounts.setup_token_accounts(&user_key, &lp_key, initial_amount, initial_amount);
+        accounts
+            .increase_liquidity(
+                &lp_key,
+                &position_key,
+                &mut position_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                u64::MAX,
+                u64::MAX,
+            )
+            .unwrap();
+
+        // only the admin can change the status
+        assert_eq!(
+            Err(SwapError::InvalidAdmin.into()),
+            do_process_instruction(
+                set_status(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &lp_key,
+                    SwapStatus::Paused
+                )
+                .unwrap(),
+                vec![&mut accounts.swap_account, &mut Account::default()],
+            )
         );
 
+        // paused, nothing goes through
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::Paused,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        assert_eq!(accounts.token_swap().status, SwapStatus::Paused);
+        let amount_in = 1_000_000;
         assert_eq!(
-            Err(SwapError::ExceededSlippage.into()),
-            accounts.deposit_all_token_types(
-                &withdrawer_key,
-                &token_a_key,
-                &mut token_a_account,
-                &token_b_key,
-                &mut token_b_account,
-                &pool_key,
-                &mut pool_account,
-                1, // doesn't matter
-                token_a_amount,
-                token_b_amount,
+            Err(SwapError::PoolPaused.into()),
+            accounts.swap(
+                &lp_key,
+                TradeDirection::AtoB,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                amount_in,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.increase_liquidity(
+                &lp_key,
+                &position_key,
+                &mut position_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                u64::MAX,
+                u64::MAX,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolPaused.into()),
+            accounts.decrease_liquidity(
+                &lp_key,
+                &position_key,
+                &mut position_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                0,
+                0,
             )
         );
 
-        // deposit enough tokens, success!
-        let token_b_amount = 125;
-        let token_a_amount = token_b_amount * token_b_price;
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &user_key,
-            &withdrawer_key,
-            token_a_amount,
-            token_b_amount,
-            0,
+        // withdraw only, liquidity providers can still leave
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::WithdrawOnly,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(SwapError::PoolWithdrawOnly.into()),
+            accounts.swap(
+                &lp_key,
+                TradeDirection::AtoB,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                amount_in,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::PoolWithdrawOnly.into()),
+            accounts.increase_liquidity(
+                &lp_key,
+                &position_key,
+                &mut position_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity,
+                u64::MAX,
+                u64::MAX,
+            )
         );
+        accounts
+            .decrease_liquidity(
+                &lp_key,
+                &position_key,
+                &mut position_account,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                liquidity / 2,
+                0,
+                0,
+            )
+            .unwrap();
 
+        // active again
+        do_process_instruction(
+            set_status(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &admin_key,
+                SwapStatus::Active,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
         accounts
-            .deposit_all_token_types(
-                &withdrawer_key,
-                &token_a_key,
-                &mut token_a_account,
-                &token_b_key,
-                &mut token_b_account,
-                &pool_key,
-                &mut pool_account,
-                1, // doesn't matter
-                token_a_amount,
-                token_b_amount,
+            .swap(
+                &lp_key,
+                TradeDirection::AtoB,
+                &lp_a_key,
+                &mut lp_a_account,
+                &lp_b_key,
+                &mut lp_b_account,
+                amount_in,
+                0,
             )
             .unwrap();
     }
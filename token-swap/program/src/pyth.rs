@@ -0,0 +1,269 @@
+//! Pyth price accounts, used to price oracle curves
+
+use crate::{curve::oracle::OraclePrice, error::SwapError};
+use arrayref::{array_ref, array_refs};
+use solana_program::{account_info::AccountInfo, clock::Slot, program_error::ProgramError};
+use std::convert::TryFrom;
+
+/// Pyth oracle program, which must own the price accounts of oracle curves
+pub mod program {
+    solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+/// Maximum Pyth confidence interval, as a percentage of the aggregate price
+pub const PYTH_MAX_CONFIDENCE_PERCENT: u8 = 2;
+
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_VERSION: u32 = 2;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+
+// Length of the price account up to and including the aggregate price, the fields that are read
+const PYTH_PRICE_LEN: usize = 240; // 4 + 4 + 4 + 4 + 4 + 4 + 4 + 4 + 8 + 8 + 24 + 24 + 8 + 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 4 + 4 + 8
+
+/// Pyth price account fields used to price token B in token A
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PythPrice {
+    /// Price exponent
+    pub expo: i32,
+    /// Aggregate price
+    pub price: i64,
+    /// Aggregate confidence interval
+    pub conf: u64,
+    /// Aggregate price status
+    pub status: u32,
+    /// Slot when the aggregate price was published
+    pub pub_slot: u64,
+}
+
+impl PythPrice {
+    /// Unpack a price account, checking that it is owned by the Pyth program
+    pub fn unpack_account(account_info: &AccountInfo) -> Result<Self, ProgramError> {
+        if !program::check_id(account_info.owner) {
+            return Err(SwapError::InvalidOracleOwner.into());
+        }
+        Self::unpack(&account_info.data.borrow())
+    }
+
+    /// Unpack a Pyth price account
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < PYTH_PRICE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let input = array_ref![data, 0, PYTH_PRICE_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            magic,
+            version,
+            account_type,
+            _size,
+            _price_type,
+            expo,
+            _num,
+            _num_qt,
+            _last_slot,
+            _valid_slot,
+            _twap,
+            _twac,
+            _drv1,
+            _drv2,
+            _product,
+            _next,
+            _prev_slot,
+            _prev_price,
+            _prev_conf,
+            _drv3,
+            price,
+            conf,
+            status,
+            _corp_act,
+            pub_slot,
+        ) = array_refs![
+            input, 4, 4, 4, 4, 4, 4, 4, 4, 8, 8, 24, 24, 8, 8, 32, 32, 8, 8, 8, 8, 8, 8, 4, 4, 8
+        ];
+
+        if u32::from_le_bytes(*magic) != PYTH_MAGIC
+            || u32::from_le_bytes(*version) != PYTH_VERSION
+            || u32::from_le_bytes(*account_type) != PYTH_ACCOUNT_TYPE_PRICE
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            expo: i32::from_le_bytes(*expo),
+            price: i64::from_le_bytes(*price),
+            conf: u64::from_le_bytes(*conf),
+            status: u32::from_le_bytes(*status),
+            pub_slot: u64::from_le_bytes(*pub_slot),
+        })
+    }
+
+    /// Check the aggregate price status, age and confidence, and convert the
+    /// price of one token B in token A to base units of each mint
+    pub fn token_b_price(
+        &self,
+        token_a_decimals: u8,
+        token_b_decimals: u8,
+        max_age_slots: u64,
+        current_slot: Slot,
+    ) -> Result<OraclePrice, ProgramError> {
+        if self.status != PYTH_STATUS_TRADING {
+            return Err(SwapError::InvalidOraclePrice.into());
+        }
+        let age_slots = current_slot
+            .checked_sub(self.pub_slot)
+            .ok_or(SwapError::InvalidOraclePrice)?;
+        if age_slots > max_age_slots {
+            return Err(SwapError::InvalidOraclePrice.into());
+        }
+
+        let price = u64::try_from(self.price)
+            .ok()
+            .filter(|price| *price > 0)
+            .ok_or(SwapError::InvalidOraclePrice)? as u128;
+        let max_conf = price
+            .checked_mul(PYTH_MAX_CONFIDENCE_PERCENT as u128)
+            .ok_or(SwapError::CalculationFailure)?
+            / 100;
+        if self.conf as u128 > max_conf {
+            return Err(SwapError::InvalidOraclePrice.into());
+        }
+
+        let exponent = self
+            .expo
+            .checked_add(token_a_decimals as i32)
+            .and_then(|exponent| exponent.checked_sub(token_b_decimals as i32))
+            .ok_or(SwapError::CalculationFailure)?;
+        let scale = 10u128
+            .checked_pow(exponent.unsigned_abs())
+            .ok_or(SwapError::CalculationFailure)?;
+        if exponent >= 0 {
+            Ok(OraclePrice {
+                numerator: price
+                    .checked_mul(scale)
+                    .ok_or(SwapError::CalculationFailure)?,
+                denominator: 1,
+            })
+        } else {
+            Ok(OraclePrice {
+                numerator: price,
+                denominator: scale,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn pyth_price_data(
+        expo: i32,
+        price: i64,
+        conf: u64,
+        status: u32,
+        pub_slot: u64,
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; 3312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&PYTH_VERSION.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[216..224].copy_from_slice(&conf.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        data[232..240].copy_from_slice(&pub_slot.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn unpack_pyth_price() {
+        let data = pyth_price_data(-8, 2_000_000_000, 1_000_000, PYTH_STATUS_TRADING, 100);
+        assert_eq!(
+            PythPrice::unpack(&data).unwrap(),
+            PythPrice {
+                expo: -8,
+                price: 2_000_000_000,
+                conf: 1_000_000,
+                status: PYTH_STATUS_TRADING,
+                pub_slot: 100,
+            }
+        );
+
+        let mut data = data;
+        data[0] = 0;
+        assert_eq!(
+            PythPrice::unpack(&data).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(
+            PythPrice::unpack(&[0u8; 8]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[test]
+    fn pyth_token_b_price() {
+        // 1 token B = 20.00000000 token A
+        let pyth_price = PythPrice {
+            expo: -8,
+            price: 2_000_000_000,
+            conf: 1_000_000,
+            status: PYTH_STATUS_TRADING,
+            pub_slot: 100,
+        };
+        // 1 base unit of B (9 decimals) = 0.02 base units of A (6 decimals)
+        assert_eq!(
+            pyth_price.token_b_price(6, 9, 10, 100).unwrap(),
+            OraclePrice {
+                numerator: 2_000_000_000,
+                denominator: 100_000_000_000,
+            }
+        );
+        // 1 base unit of B (0 decimals) = 20_000_000_000 base units of A (9 decimals)
+        assert_eq!(
+            pyth_price.token_b_price(9, 0, 10, 110).unwrap(),
+            OraclePrice {
+                numerator: 20_000_000_000,
+                denominator: 1,
+            }
+        );
+
+        // too old
+        assert_eq!(
+            pyth_price.token_b_price(6, 6, 10, 111).unwrap_err(),
+            SwapError::InvalidOraclePrice.into()
+        );
+
+        // not trading
+        let halted_price = PythPrice {
+            status: 2,
+            ..pyth_price.clone()
+        };
+        assert_eq!(
+            halted_price.token_b_price(6, 6, 10, 100).unwrap_err(),
+            SwapError::InvalidOraclePrice.into()
+        );
+
+        // confidence interval wider than 2% of price
+        let unconfident_price = PythPrice {
+            conf: 40_000_001,
+            ..pyth_price.clone()
+        };
+        assert_eq!(
+            unconfident_price.token_b_price(6, 6, 10, 100).unwrap_err(),
+            SwapError::InvalidOraclePrice.into()
+        );
+
+        // negative or zero price
+        let negative_price = PythPrice {
+            price: -1,
+            conf: 0,
+            ..pyth_price
+        };
+        assert_eq!(
+            negative_price.token_b_price(6, 6, 10, 100).unwrap_err(),
+            SwapError::InvalidOraclePrice.into()
+        );
+    }
+}
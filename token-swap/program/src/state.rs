@@ -1,6 +1,16 @@
 //! State transition types
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::{
+    curve::{
+        base::{CurveType, SwapCurve},
+        concentrated::{add_liquidity_delta, fees_earned, ConcentratedCurve},
+        fees::Fees,
+        multi_token::{MultiTokenCurve, MAX_TOKENS},
+        stable::{AmpRamp, AMP_RAMP_LEN},
+    },
+    error::SwapError,
+    twap::{PriceAccumulators, PRICE_ACCUMULATORS_LEN},
+};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use enum_dispatch::enum_dispatch;
 use solana_program::{
@@ -8,6 +18,7 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
+use std::convert::TryFrom;
 
 /// Trait representing access to program state across all versions
 #[enum_dispatch]
@@ -37,13 +48,82 @@ pub trait SwapState {
     fn fees(&self) -> &Fees;
     /// Curve associated with swap
     fn swap_curve(&self) -> &SwapCurve;
+
+    /// Address of the oracle price account, if the curve is priced by one
+    fn oracle(&self) -> Option<&Pubkey>;
+    /// Cumulative prices of the pool, if the swap version records them and
+    /// the pool is on a constant product curve, see `PriceAccumulators`
+    fn price_accumulators(&self) -> Option<&PriceAccumulators>;
+
+    /// Address of the admin, allowed to retune the swap, if it has one
+    fn admin(&self) -> Option<&Pubkey>;
+    /// Ramp of the stable curve's amplification, if an admin started one
+    fn amp_ramp(&self) -> Option<&AmpRamp>;
+    /// Operations allowed on the swap, set by its admin
+    fn status(&self) -> SwapStatus;
+}
+
+/// Operations allowed on a swap, set by its admin to freeze the pool during
+/// an incident or to wind it down
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwapStatus {
+    /// Swaps, deposits and withdrawals are allowed
+    Active,
+    /// Nothing is allowed
+    Paused,
+    /// Only withdrawals are allowed
+    WithdrawOnly,
+}
+
+impl SwapStatus {
+    /// Fail unless swaps and deposits are allowed
+    pub fn check_trading(self) -> Result<(), SwapError> {
+        match self {
+            Self::Active => Ok(()),
+            Self::Paused => Err(SwapError::PoolPaused),
+            Self::WithdrawOnly => Err(SwapError::PoolWithdrawOnly),
+        }
+    }
+
+    /// Fail unless withdrawals are allowed
+    pub fn check_withdrawing(self) -> Result<(), SwapError> {
+        match self {
+            Self::Paused => Err(SwapError::PoolPaused),
+            Self::Active | Self::WithdrawOnly => Ok(()),
+        }
+    }
+}
+
+/// Swaps start out active
+impl Default for SwapStatus {
+    fn default() -> Self {
+        SwapStatus::Active
+    }
+}
+
+impl TryFrom<u8> for SwapStatus {
+    type Error = ProgramError;
+
+    fn try_from(status: u8) -> Result<Self, Self::Error> {
+        match status {
+            0 => Ok(SwapStatus::Active),
+            1 => Ok(SwapStatus::Paused),
+            2 => Ok(SwapStatus::WithdrawOnly),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
 }
 
 /// All versions of SwapState
 #[enum_dispatch(SwapState)]
 pub enum SwapVersion {
-    /// Latest version, used for all new swaps
+    /// Original version, still used for swaps created in accounts of its size
     SwapV1,
+    /// Second version, still used for swaps created in accounts of its size
+    SwapV2,
+    /// Latest version, used for all new swaps
+    SwapV3,
 }
 
 /// SwapVersion does not implement program_pack::Pack because there are size
@@ -51,7 +131,24 @@ pub enum SwapVersion {
 /// special implementations are provided here
 impl SwapVersion {
     /// Size of the latest version of the SwapState
-    pub const LATEST_LEN: usize = 1 + SwapV1::LEN; // add one for the version enum
+    pub const LATEST_LEN: usize = 1 + SwapV3::LEN; // add one for the version enum
+
+    /// Size of the original version of the SwapState
+    pub const V1_LEN: usize = 1 + SwapV1::LEN;
+
+    /// Size of the second version of the SwapState
+    pub const V2_LEN: usize = 1 + SwapV2::LEN;
+
+    /// Size of a multi-token swap, which is stored with its own version
+    /// number but is not a SwapState
+    pub const MULTI_TOKEN_LEN: usize = 1 + MultiTokenSwapV1::LEN;
+
+    /// Size of a concentrated liquidity swap with room for `max_ticks`
+    /// initialized ticks, which is stored with its own version number but is
+    /// not a SwapState
+    pub fn concentrated_len(max_ticks: u16) -> usize {
+        1 + ConcentratedSwapV1::packed_len(max_ticks)
+    }
 
     /// Pack a swap into a byte array, based on its version
     pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
@@ -60,6 +157,14 @@ impl SwapVersion {
                 dst[0] = 1;
                 SwapV1::pack(swap_info, &mut dst[1..])
             }
+            Self::SwapV2(swap_info) => {
+                dst[0] = 2;
+                SwapV2::pack(swap_info, &mut dst[1..])
+            }
+            Self::SwapV3(swap_info) => {
+                dst[0] = 5;
+                SwapV3::pack(swap_info, &mut dst[1..])
+            }
         }
     }
 
@@ -71,6 +176,48 @@ impl SwapVersion {
             .ok_or(ProgramError::InvalidAccountData)?;
         match version {
             1 => Ok(Box::new(SwapV1::unpack(rest)?)),
+            2 => Ok(Box::new(SwapV2::unpack(rest)?)),
+            // multi-token and concentrated liquidity swaps only go through
+            // their own unpack functions
+            3 | 4 => Err(ProgramError::InvalidAccountData),
+            5 => Ok(Box::new(SwapV3::unpack(rest)?)),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Pack a multi-token swap into a byte array
+    pub fn pack_multi_token(src: MultiTokenSwapV1, dst: &mut [u8]) -> Result<(), ProgramError> {
+        dst[0] = 3;
+        MultiTokenSwapV1::pack(src, &mut dst[1..])
+    }
+
+    /// Unpack a multi-token swap account, failing on two-token swaps
+    pub fn unpack_multi_token(input: &[u8]) -> Result<MultiTokenSwapV1, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            1 | 2 | 4 | 5 => Err(ProgramError::InvalidAccountData),
+            3 => MultiTokenSwapV1::unpack(rest),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Pack a concentrated liquidity swap into a byte array
+    pub fn pack_concentrated(src: ConcentratedSwapV1, dst: &mut [u8]) -> Result<(), ProgramError> {
+        dst[0] = 4;
+        ConcentratedSwapV1::pack(src, &mut dst[1..])
+    }
+
+    /// Unpack a concentrated liquidity swap account, failing on all other
+    /// swaps
+    pub fn unpack_concentrated(input: &[u8]) -> Result<ConcentratedSwapV1, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            1 | 2 | 3 | 5 => Err(ProgramError::InvalidAccountData),
+            4 => ConcentratedSwapV1::unpack(rest),
             _ => Err(ProgramError::UninitializedAccount),
         }
     }
@@ -78,6 +225,11 @@ impl SwapVersion {
     /// Special check to be done before any instruction processing, works for
     /// all versions
     pub fn is_initialized(input: &[u8]) -> bool {
+        match input.first() {
+            Some(&3) => return Self::unpack_multi_token(input).is_ok(),
+            Some(&4) => return Self::unpack_concentrated(input).is_ok(),
+            _ => {}
+        }
         match Self::unpack(input) {
             Ok(swap) => swap.is_initialized(),
             Err(_) => false,
@@ -170,6 +322,26 @@ impl SwapState for SwapV1 {
     fn swap_curve(&self) -> &SwapCurve {
         &self.swap_curve
     }
+
+    fn oracle(&self) -> Option<&Pubkey> {
+        None
+    }
+
+    fn price_accumulators(&self) -> Option<&PriceAccumulators> {
+        None
+    }
+
+    fn admin(&self) -> Option<&Pubkey> {
+        None
+    }
+
+    fn amp_ramp(&self) -> Option<&AmpRamp> {
+        None
+    }
+
+    fn status(&self) -> SwapStatus {
+        SwapStatus::Active
+    }
 }
 
 impl Sealed for SwapV1 {}
@@ -247,130 +419,1453 @@ impl Pack for SwapV1 {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::curve::stable::StableCurve;
+/// Program states, with room for an oracle and later additions
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV2 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub nonce: u8,
 
-    use std::convert::TryInto;
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
 
-    const TEST_FEES: Fees = Fees {
-        trade_fee_numerator: 1,
-        trade_fee_denominator: 4,
-        owner_trade_fee_numerator: 3,
-        owner_trade_fee_denominator: 10,
-        owner_withdraw_fee_numerator: 2,
-        owner_withdraw_fee_denominator: 7,
-        host_fee_numerator: 5,
-        host_fee_denominator: 20,
-    };
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
 
-    const TEST_NONCE: u8 = 255;
-    const TEST_TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
-    const TEST_TOKEN_A: Pubkey = Pubkey::new_from_array([2u8; 32]);
-    const TEST_TOKEN_B: Pubkey = Pubkey::new_from_array([3u8; 32]);
-    const TEST_POOL_MINT: Pubkey = Pubkey::new_from_array([4u8; 32]);
-    const TEST_TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([5u8; 32]);
-    const TEST_TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([6u8; 32]);
-    const TEST_POOL_FEE_ACCOUNT: Pubkey = Pubkey::new_from_array([7u8; 32]);
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    pub pool_mint: Pubkey,
 
-    const TEST_CURVE_TYPE: u8 = 2;
-    const TEST_AMP: u64 = 1;
-    const TEST_CURVE: StableCurve = StableCurve { amp: TEST_AMP };
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
 
-    #[test]
-    fn swap_version_pack() {
-        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
-        let calculator = Box::new(TEST_CURVE);
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator,
-        };
-        let swap_info = SwapVersion::SwapV1(SwapV1 {
-            is_initialized: true,
-            nonce: TEST_NONCE,
-            token_program_id: TEST_TOKEN_PROGRAM_ID,
-            token_a: TEST_TOKEN_A,
-            token_b: TEST_TOKEN_B,
-            pool_mint: TEST_POOL_MINT,
-            token_a_mint: TEST_TOKEN_A_MINT,
-            token_b_mint: TEST_TOKEN_B_MINT,
-            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
-            fees: TEST_FEES,
-            swap_curve: swap_curve.clone(),
-        });
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
 
-        let mut packed = [0u8; SwapVersion::LATEST_LEN];
-        SwapVersion::pack(swap_info, &mut packed).unwrap();
-        let unpacked = SwapVersion::unpack(&packed).unwrap();
+    /// All fee information
+    pub fees: Fees,
 
-        assert_eq!(unpacked.is_initialized(), true);
-        assert_eq!(unpacked.nonce(), TEST_NONCE);
-        assert_eq!(*unpacked.token_program_id(), TEST_TOKEN_PROGRAM_ID);
-        assert_eq!(*unpacked.token_a_account(), TEST_TOKEN_A);
-        assert_eq!(*unpacked.token_b_account(), TEST_TOKEN_B);
-        assert_eq!(*unpacked.pool_mint(), TEST_POOL_MINT);
-        assert_eq!(*unpacked.token_a_mint(), TEST_TOKEN_A_MINT);
-        assert_eq!(*unpacked.token_b_mint(), TEST_TOKEN_B_MINT);
-        assert_eq!(*unpacked.pool_fee_account(), TEST_POOL_FEE_ACCOUNT);
-        assert_eq!(*unpacked.fees(), TEST_FEES);
-        assert_eq!(*unpacked.swap_curve(), swap_curve);
+    /// Swap curve parameters, to be unpacked and used by the SwapCurve, which
+    /// calculates swaps, deposits, and withdrawals
+    pub swap_curve: SwapCurve,
+
+    /// Pyth price account pricing token B in token A, required by oracle
+    /// curves and the default pubkey otherwise
+    pub oracle: Pubkey,
+
+    /// Cumulative prices, updated before every change to the pool balances
+    pub price_accumulators: PriceAccumulators,
+}
+
+impl SwapState for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
     }
 
-    #[test]
-    fn swap_v1_pack() {
-        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
-        let calculator = Box::new(TEST_CURVE);
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator,
-        };
-        let swap_info = SwapV1 {
-            is_initialized: true,
-            nonce: TEST_NONCE,
-            token_program_id: TEST_TOKEN_PROGRAM_ID,
-            token_a: TEST_TOKEN_A,
-            token_b: TEST_TOKEN_B,
-            pool_mint: TEST_POOL_MINT,
-            token_a_mint: TEST_TOKEN_A_MINT,
-            token_b_mint: TEST_TOKEN_B_MINT,
-            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
-            fees: TEST_FEES,
-            swap_curve,
-        };
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
 
-        let mut packed = [0u8; SwapV1::LEN];
-        SwapV1::pack_into_slice(&swap_info, &mut packed);
-        let unpacked = SwapV1::unpack(&packed).unwrap();
-        assert_eq!(swap_info, unpacked);
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
 
-        let mut packed = vec![1u8, TEST_NONCE];
-        packed.extend_from_slice(&TEST_TOKEN_PROGRAM_ID.to_bytes());
-        packed.extend_from_slice(&TEST_TOKEN_A.to_bytes());
-        packed.extend_from_slice(&TEST_TOKEN_B.to_bytes());
-        packed.extend_from_slice(&TEST_POOL_MINT.to_bytes());
-        packed.extend_from_slice(&TEST_TOKEN_A_MINT.to_bytes());
-        packed.extend_from_slice(&TEST_TOKEN_B_MINT.to_bytes());
-        packed.extend_from_slice(&TEST_POOL_FEE_ACCOUNT.to_bytes());
-        packed.extend_from_slice(&TEST_FEES.trade_fee_numerator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.trade_fee_denominator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.owner_trade_fee_numerator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.owner_trade_fee_denominator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.owner_withdraw_fee_numerator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.owner_withdraw_fee_denominator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.host_fee_numerator.to_le_bytes());
-        packed.extend_from_slice(&TEST_FEES.host_fee_denominator.to_le_bytes());
-        packed.push(TEST_CURVE_TYPE);
-        packed.extend_from_slice(&TEST_AMP.to_le_bytes());
-        packed.extend_from_slice(&[0u8; 24]);
-        let unpacked = SwapV1::unpack(&packed).unwrap();
-        assert_eq!(swap_info, unpacked);
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
 
-        let packed = [0u8; SwapV1::LEN];
-        let swap_info: SwapV1 = Default::default();
-        let unpack_unchecked = SwapV1::unpack_unchecked(&packed).unwrap();
-        assert_eq!(unpack_unchecked, swap_info);
-        let err = SwapV1::unpack(&packed).unwrap_err();
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    fn oracle(&self) -> Option<&Pubkey> {
+        if self.oracle == Pubkey::default() {
+            None
+        } else {
+            Some(&self.oracle)
+        }
+    }
+
+    fn price_accumulators(&self) -> Option<&PriceAccumulators> {
+        if self.swap_curve.curve_type == CurveType::ConstantProduct {
+            Some(&self.price_accumulators)
+        } else {
+            None
+        }
+    }
+
+    fn admin(&self) -> Option<&Pubkey> {
+        None
+    }
+
+    fn amp_ramp(&self) -> Option<&AmpRamp> {
+        None
+    }
+
+    fn status(&self) -> SwapStatus {
+        SwapStatus::Active
+    }
+}
+
+impl Sealed for SwapV2 {}
+impl IsInitialized for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV2 {
+    const LEN: usize = 611;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 611];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            oracle,
+            price_accumulators,
+            padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            64,
+            33,
+            32,
+            PRICE_ACCUMULATORS_LEN,
+            168
+        ];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        oracle.copy_from_slice(self.oracle.as_ref());
+        self.price_accumulators.pack_into_slice(price_accumulators);
+        *padding = [0u8; 168];
+    }
+
+    /// Unpacks a byte buffer into a [SwapV2](struct.SwapV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 611];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            oracle,
+            price_accumulators,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            64,
+            33,
+            32,
+            PRICE_ACCUMULATORS_LEN,
+            168
+        ];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            oracle: Pubkey::new_from_array(*oracle),
+            price_accumulators: PriceAccumulators::unpack_from_slice(price_accumulators),
+        })
+    }
+}
+
+/// Program states, with an optional admin allowed to retune the swap
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV3 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Swap curve parameters, to be unpacked and used by the SwapCurve, which
+    /// calculates swaps, deposits, and withdrawals
+    pub swap_curve: SwapCurve,
+
+    /// Pyth price account pricing token B in token A, required by oracle
+    /// curves and the default pubkey otherwise
+    pub oracle: Pubkey,
+
+    /// Cumulative prices, updated before every change to the pool balances
+    pub price_accumulators: PriceAccumulators,
+
+    /// Admin allowed to update the fees, curve and fee account, or the
+    /// default pubkey if the swap cannot be changed
+    pub admin: Pubkey,
+
+    /// Ramp of a stable curve's amplification, all zeroes if none was started
+    pub amp_ramp: AmpRamp,
+
+    /// Operations allowed on the swap
+    pub status: SwapStatus,
+}
+
+impl SwapState for SwapV3 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    fn oracle(&self) -> Option<&Pubkey> {
+        if self.oracle == Pubkey::default() {
+            None
+        } else {
+            Some(&self.oracle)
+        }
+    }
+
+    fn price_accumulators(&self) -> Option<&PriceAccumulators> {
+        if self.swap_curve.curve_type == CurveType::ConstantProduct {
+            Some(&self.price_accumulators)
+        } else {
+            None
+        }
+    }
+
+    fn admin(&self) -> Option<&Pubkey> {
+        if self.admin == Pubkey::default() {
+            None
+        } else {
+            Some(&self.admin)
+        }
+    }
+
+    fn amp_ramp(&self) -> Option<&AmpRamp> {
+        if self.amp_ramp == AmpRamp::default() {
+            None
+        } else {
+            Some(&self.amp_ramp)
+        }
+    }
+
+    fn status(&self) -> SwapStatus {
+        self.status
+    }
+}
+
+impl Sealed for SwapV3 {}
+impl IsInitialized for SwapV3 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV3 {
+    const LEN: usize = 675;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 675];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            oracle,
+            price_accumulators,
+            admin,
+            amp_ramp,
+            status,
+            padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            64,
+            33,
+            32,
+            PRICE_ACCUMULATORS_LEN,
+            32,
+            AMP_RAMP_LEN,
+            1,
+            167
+        ];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        oracle.copy_from_slice(self.oracle.as_ref());
+        self.price_accumulators.pack_into_slice(price_accumulators);
+        admin.copy_from_slice(self.admin.as_ref());
+        self.amp_ramp.pack_into_slice(amp_ramp);
+        status[0] = self.status as u8;
+        *padding = [0u8; 167];
+    }
+
+    /// Unpacks a byte buffer into a [SwapV3](struct.SwapV3.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 675];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            oracle,
+            price_accumulators,
+            admin,
+            amp_ramp,
+            status,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            64,
+            33,
+            32,
+            PRICE_ACCUMULATORS_LEN,
+            32,
+            AMP_RAMP_LEN,
+            1,
+            167
+        ];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            oracle: Pubkey::new_from_array(*oracle),
+            price_accumulators: PriceAccumulators::unpack_from_slice(price_accumulators),
+            admin: Pubkey::new_from_array(*admin),
+            amp_ramp: AmpRamp::unpack_from_slice(amp_ramp),
+            status: SwapStatus::try_from(status[0])?,
+        })
+    }
+}
+
+/// Multi-token program state, for pools of 3 to 8 tokens sharing one pool
+/// token mint
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct MultiTokenSwapV1 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over all of the swap's token accounts and pool token mint.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Pool tokens are issued when tokens are deposited, and can be withdrawn
+    /// back to any of the pool's tokens.
+    pub pool_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Curve parameters, used to calculate swaps, deposits, and withdrawals
+    pub curve: MultiTokenCurve,
+
+    /// Number of tokens in the pool, only the first `token_count` token
+    /// accounts and mints are used
+    pub token_count: u8,
+    /// Token accounts of the pool
+    pub token_accounts: [Pubkey; MAX_TOKENS],
+    /// Mint information for each token account
+    pub token_mints: [Pubkey; MAX_TOKENS],
+
+    /// Admin allowed to set the status of the swap, or the default pubkey if
+    /// the swap cannot be paused
+    pub admin: Pubkey,
+
+    /// Operations allowed on the swap
+    pub status: SwapStatus,
+}
+
+impl MultiTokenSwapV1 {
+    /// Token accounts in use by the pool
+    pub fn token_accounts(&self) -> &[Pubkey] {
+        &self.token_accounts[..self.token_count as usize]
+    }
+
+    /// Mints of the token accounts in use by the pool
+    pub fn token_mints(&self) -> &[Pubkey] {
+        &self.token_mints[..self.token_count as usize]
+    }
+
+    /// Address of the admin, allowed to set the status of the swap, if it
+    /// has one
+    pub fn admin(&self) -> Option<&Pubkey> {
+        if self.admin == Pubkey::default() {
+            None
+        } else {
+            Some(&self.admin)
+        }
+    }
+}
+
+impl Sealed for MultiTokenSwapV1 {}
+impl IsInitialized for MultiTokenSwapV1 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for MultiTokenSwapV1 {
+    const LEN: usize = 820;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 820];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            pool_mint,
+            pool_fee_account,
+            fees,
+            curve,
+            token_count,
+            token_accounts,
+            token_mints,
+            admin,
+            status,
+            padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            32,
+            32,
+            32,
+            64,
+            MultiTokenCurve::LEN,
+            1,
+            32 * MAX_TOKENS,
+            32 * MAX_TOKENS,
+            32,
+            1,
+            95
+        ];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.curve.pack_into_slice(&mut curve[..]);
+        token_count[0] = self.token_count;
+        for (dst, src) in token_accounts
+            .chunks_mut(32)
+            .zip(self.token_accounts.iter())
+        {
+            dst.copy_from_slice(src.as_ref());
+        }
+        for (dst, src) in token_mints.chunks_mut(32).zip(self.token_mints.iter()) {
+            dst.copy_from_slice(src.as_ref());
+        }
+        admin.copy_from_slice(self.admin.as_ref());
+        status[0] = self.status as u8;
+        *padding = [0u8; 95];
+    }
+
+    /// Unpacks a byte buffer into a [MultiTokenSwapV1](struct.MultiTokenSwapV1.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 820];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            pool_mint,
+            pool_fee_account,
+            fees,
+            curve,
+            token_count,
+            token_accounts_data,
+            token_mints_data,
+            admin,
+            status,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            32,
+            32,
+            32,
+            64,
+            MultiTokenCurve::LEN,
+            1,
+            32 * MAX_TOKENS,
+            32 * MAX_TOKENS,
+            32,
+            1,
+            95
+        ];
+        if token_count[0] as usize > MAX_TOKENS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut token_accounts = [Pubkey::default(); MAX_TOKENS];
+        for (dst, src) in token_accounts
+            .iter_mut()
+            .zip(token_accounts_data.chunks(32))
+        {
+            *dst = Pubkey::new(src);
+        }
+        let mut token_mints = [Pubkey::default(); MAX_TOKENS];
+        for (dst, src) in token_mints.iter_mut().zip(token_mints_data.chunks(32)) {
+            *dst = Pubkey::new(src);
+        }
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            curve: MultiTokenCurve::unpack_from_slice(curve)?,
+            token_count: token_count[0],
+            token_accounts,
+            token_mints,
+            admin: Pubkey::new_from_array(*admin),
+            status: SwapStatus::try_from(status[0])?,
+        })
+    }
+}
+
+/// Concentrated liquidity program state.  Liquidity is held by position
+/// accounts over ranges of ticks instead of pool tokens, so there is no pool
+/// token mint.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct ConcentratedSwapV1 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over all of the swap's token accounts.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Token A account to receive owner trading fees on swaps from token A
+    pub fee_account_a: Pubkey,
+    /// Token B account to receive owner trading fees on swaps from token B
+    pub fee_account_b: Pubkey,
+
+    /// All fee information, the trade fee is earned by the positions in
+    /// range during a swap
+    pub fees: Fees,
+
+    /// Price, liquidity and ticks of the pool
+    pub curve: ConcentratedCurve,
+
+    /// Admin allowed to set the status of the swap, or the default pubkey if
+    /// the swap cannot be paused
+    pub admin: Pubkey,
+
+    /// Operations allowed on the swap
+    pub status: SwapStatus,
+}
+
+impl ConcentratedSwapV1 {
+    /// Length of a packed swap with room for `max_ticks` initialized ticks
+    pub fn packed_len(max_ticks: u16) -> usize {
+        Self::LEN - ConcentratedCurve::LEN + ConcentratedCurve::packed_len(max_ticks)
+    }
+
+    /// Address of the admin, allowed to set the status of the swap, if it
+    /// has one
+    pub fn admin(&self) -> Option<&Pubkey> {
+        if self.admin == Pubkey::default() {
+            None
+        } else {
+            Some(&self.admin)
+        }
+    }
+}
+
+impl Sealed for ConcentratedSwapV1 {}
+impl IsInitialized for ConcentratedSwapV1 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// The ticks of the curve are stored at the end of the swap, after `LEN`
+/// bytes, so the length of a packed swap depends on its `max_ticks`
+impl Pack for ConcentratedSwapV1 {
+    const LEN: usize = 290 + 128 + ConcentratedCurve::LEN;
+
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let swap = Self::unpack_from_slice(input)?;
+        if input.len() != Self::packed_len(swap.curve.max_ticks) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(swap)
+    }
+
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() != Self::packed_len(src.curve.max_ticks) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        src.pack_into_slice(dst);
+        Ok(())
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let (output, curve) = output.split_at_mut(290 + 128);
+        let output = array_mut_ref![output, 0, 290 + 128];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            token_a_mint,
+            token_b_mint,
+            fee_account_a,
+            fee_account_b,
+            fees,
+            admin,
+            status,
+            padding,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 32, 1, 95];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        fee_account_a.copy_from_slice(self.fee_account_a.as_ref());
+        fee_account_b.copy_from_slice(self.fee_account_b.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.curve.pack_into_slice(&mut curve[..]);
+        admin.copy_from_slice(self.admin.as_ref());
+        status[0] = self.status as u8;
+        *padding = [0u8; 95];
+    }
+
+    /// Unpacks a byte buffer into a [ConcentratedSwapV1](struct.ConcentratedSwapV1.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let (input, curve) = input.split_at(290 + 128);
+        let input = array_ref![input, 0, 290 + 128];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            token_a_mint,
+            token_b_mint,
+            fee_account_a,
+            fee_account_b,
+            fees,
+            admin,
+            status,
+            _padding,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 32, 1, 95];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            fee_account_a: Pubkey::new_from_array(*fee_account_a),
+            fee_account_b: Pubkey::new_from_array(*fee_account_b),
+            fees: Fees::unpack_from_slice(fees)?,
+            curve: ConcentratedCurve::unpack_from_slice(curve)?,
+            admin: Pubkey::new_from_array(*admin),
+            status: SwapStatus::try_from(status[0])?,
+        })
+    }
+}
+
+/// Liquidity provided to a concentrated liquidity swap over one range of
+/// ticks, with the fees it has earned
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct ConcentratedPosition {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Concentrated liquidity swap of the position
+    pub swap: Pubkey,
+    /// Owner allowed to change the liquidity and collect the fees
+    pub owner: Pubkey,
+    /// Lower tick of the range
+    pub tick_lower: i32,
+    /// Upper tick of the range
+    pub tick_upper: i32,
+    /// Liquidity provided over the range
+    pub liquidity: u128,
+    /// Token A fee growth inside the range at the last update
+    pub fee_growth_inside_last_a: u128,
+    /// Token B fee growth inside the range at the last update
+    pub fee_growth_inside_last_b: u128,
+    /// Token A fees earned and not collected yet
+    pub fees_owed_a: u64,
+    /// Token B fees earned and not collected yet
+    pub fees_owed_b: u64,
+}
+
+impl ConcentratedPosition {
+    /// Record the fees earned since the last update, from the current fee
+    /// growth inside the range, then apply a liquidity change
+    pub fn update(
+        &mut self,
+        liquidity_delta: i128,
+        fee_growth_inside_a: u128,
+        fee_growth_inside_b: u128,
+    ) -> Result<(), SwapError> {
+        let earned_a = fees_earned(
+            self.liquidity,
+            fee_growth_inside_a,
+            self.fee_growth_inside_last_a,
+        )
+        .ok_or(SwapError::CalculationFailure)?;
+        let earned_b = fees_earned(
+            self.liquidity,
+            fee_growth_inside_b,
+            self.fee_growth_inside_last_b,
+        )
+        .ok_or(SwapError::CalculationFailure)?;
+        self.fees_owed_a = u128::from(self.fees_owed_a)
+            .checked_add(earned_a)
+            .and_then(|fees| u64::try_from(fees).ok())
+            .ok_or(SwapError::ConversionFailure)?;
+        self.fees_owed_b = u128::from(self.fees_owed_b)
+            .checked_add(earned_b)
+            .and_then(|fees| u64::try_from(fees).ok())
+            .ok_or(SwapError::ConversionFailure)?;
+        self.fee_growth_inside_last_a = fee_growth_inside_a;
+        self.fee_growth_inside_last_b = fee_growth_inside_b;
+        self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)
+            .ok_or(SwapError::InsufficientLiquidity)?;
+        Ok(())
+    }
+}
+
+impl Sealed for ConcentratedPosition {}
+impl IsInitialized for ConcentratedPosition {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ConcentratedPosition {
+    const LEN: usize = 201;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 201];
+        let (
+            is_initialized,
+            swap,
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_last_a,
+            fee_growth_inside_last_b,
+            fees_owed_a,
+            fees_owed_b,
+            padding,
+        ) = mut_array_refs![output, 1, 32, 32, 4, 4, 16, 16, 16, 8, 8, 64];
+        is_initialized[0] = self.is_initialized as u8;
+        swap.copy_from_slice(self.swap.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        *tick_lower = self.tick_lower.to_le_bytes();
+        *tick_upper = self.tick_upper.to_le_bytes();
+        *liquidity = self.liquidity.to_le_bytes();
+        *fee_growth_inside_last_a = self.fee_growth_inside_last_a.to_le_bytes();
+        *fee_growth_inside_last_b = self.fee_growth_inside_last_b.to_le_bytes();
+        *fees_owed_a = self.fees_owed_a.to_le_bytes();
+        *fees_owed_b = self.fees_owed_b.to_le_bytes();
+        *padding = [0u8; 64];
+    }
+
+    /// Unpacks a byte buffer into a [ConcentratedPosition](struct.ConcentratedPosition.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 201];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            swap,
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_last_a,
+            fee_growth_inside_last_b,
+            fees_owed_a,
+            fees_owed_b,
+            _padding,
+        ) = array_refs![input, 1, 32, 32, 4, 4, 16, 16, 16, 8, 8, 64];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            swap: Pubkey::new_from_array(*swap),
+            owner: Pubkey::new_from_array(*owner),
+            tick_lower: i32::from_le_bytes(*tick_lower),
+            tick_upper: i32::from_le_bytes(*tick_upper),
+            liquidity: u128::from_le_bytes(*liquidity),
+            fee_growth_inside_last_a: u128::from_le_bytes(*fee_growth_inside_last_a),
+            fee_growth_inside_last_b: u128::from_le_bytes(*fee_growth_inside_last_b),
+            fees_owed_a: u64::from_le_bytes(*fees_owed_a),
+            fees_owed_b: u64::from_le_bytes(*fees_owed_b),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        concentrated::{sqrt_price_at_tick, Tick},
+        constant_product::ConstantProductCurve,
+        multi_token::MultiTokenCurveType,
+        stable::StableCurve,
+    };
+    use spl_math::uint::U256;
+
+    use std::convert::TryInto;
+
+    const TEST_FEES: Fees = Fees {
+        trade_fee_numerator: 1,
+        trade_fee_denominator: 4,
+        owner_trade_fee_numerator: 3,
+        owner_trade_fee_denominator: 10,
+        owner_withdraw_fee_numerator: 2,
+        owner_withdraw_fee_denominator: 7,
+        host_fee_numerator: 5,
+        host_fee_denominator: 20,
+    };
+
+    const TEST_NONCE: u8 = 255;
+    const TEST_TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+    const TEST_TOKEN_A: Pubkey = Pubkey::new_from_array([2u8; 32]);
+    const TEST_TOKEN_B: Pubkey = Pubkey::new_from_array([3u8; 32]);
+    const TEST_POOL_MINT: Pubkey = Pubkey::new_from_array([4u8; 32]);
+    const TEST_TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([5u8; 32]);
+    const TEST_TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([6u8; 32]);
+    const TEST_POOL_FEE_ACCOUNT: Pubkey = Pubkey::new_from_array([7u8; 32]);
+    const TEST_ORACLE: Pubkey = Pubkey::new_from_array([8u8; 32]);
+    const TEST_PRICE_ACCUMULATORS: PriceAccumulators = PriceAccumulators {
+        last_update_timestamp: 1_620_000_000,
+        token_a_price_cumulative: U256([1, 2, 3, 4]),
+        token_b_price_cumulative: U256([5, 6, 7, 8]),
+        reserve_a: 1_000,
+        reserve_b: 2_000,
+    };
+
+    const TEST_ADMIN: Pubkey = Pubkey::new_from_array([9u8; 32]);
+    const TEST_AMP_RAMP: AmpRamp = AmpRamp {
+        initial_amp: 10,
+        target_amp: 100,
+        start_timestamp: 1_620_000_000,
+        stop_timestamp: 1_620_086_400,
+    };
+
+    const TEST_CURVE_TYPE: u8 = 2;
+    const TEST_AMP: u64 = 1;
+    const TEST_CURVE: StableCurve = StableCurve { amp: TEST_AMP };
+
+    #[test]
+    fn swap_version_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapVersion::SwapV1(SwapV1 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve: swap_curve.clone(),
+        });
+
+        let mut packed = [0u8; SwapVersion::V1_LEN];
+        SwapVersion::pack(swap_info, &mut packed).unwrap();
+        let unpacked = SwapVersion::unpack(&packed).unwrap();
+
+        assert_eq!(unpacked.is_initialized(), true);
+        assert_eq!(unpacked.nonce(), TEST_NONCE);
+        assert_eq!(*unpacked.token_program_id(), TEST_TOKEN_PROGRAM_ID);
+        assert_eq!(*unpacked.token_a_account(), TEST_TOKEN_A);
+        assert_eq!(*unpacked.token_b_account(), TEST_TOKEN_B);
+        assert_eq!(*unpacked.pool_mint(), TEST_POOL_MINT);
+        assert_eq!(*unpacked.token_a_mint(), TEST_TOKEN_A_MINT);
+        assert_eq!(*unpacked.token_b_mint(), TEST_TOKEN_B_MINT);
+        assert_eq!(*unpacked.pool_fee_account(), TEST_POOL_FEE_ACCOUNT);
+        assert_eq!(*unpacked.fees(), TEST_FEES);
+        assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.oracle(), None);
+        assert_eq!(unpacked.price_accumulators(), None);
+    }
+
+    #[test]
+    fn swap_v2_version_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapV2 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve: swap_curve.clone(),
+            oracle: TEST_ORACLE,
+            price_accumulators: TEST_PRICE_ACCUMULATORS,
+        };
+
+        let mut packed = [0u8; SwapV2::LEN];
+        SwapV2::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = SwapV2::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+
+        let mut packed = [0u8; SwapVersion::V2_LEN];
+        SwapVersion::pack(SwapVersion::SwapV2(swap_info), &mut packed).unwrap();
+        assert_eq!(packed[0], 2);
+        let unpacked = SwapVersion::unpack(&packed).unwrap();
+        assert_eq!(*unpacked.token_a_account(), TEST_TOKEN_A);
+        assert_eq!(*unpacked.pool_fee_account(), TEST_POOL_FEE_ACCOUNT);
+        assert_eq!(*unpacked.fees(), TEST_FEES);
+        assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.oracle(), Some(&TEST_ORACLE));
+        // only constant product pools record prices
+        assert_eq!(unpacked.price_accumulators(), None);
+
+        let constant_product_swap_info = SwapV2 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve {}),
+            },
+            ..SwapV2::unpack(&packed[1..]).unwrap()
+        };
+        assert_eq!(
+            constant_product_swap_info.price_accumulators(),
+            Some(&TEST_PRICE_ACCUMULATORS)
+        );
+
+        let packed = [0u8; SwapV2::LEN];
+        let unpack_unchecked = SwapV2::unpack_unchecked(&packed).unwrap();
+        assert_eq!(unpack_unchecked.oracle(), None);
+        let err = SwapV2::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn swap_v3_version_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapV3 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve: swap_curve.clone(),
+            oracle: Pubkey::default(),
+            price_accumulators: TEST_PRICE_ACCUMULATORS,
+            admin: TEST_ADMIN,
+            amp_ramp: TEST_AMP_RAMP,
+            status: SwapStatus::WithdrawOnly,
+        };
+
+        let mut packed = [0u8; SwapV3::LEN];
+        SwapV3::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = SwapV3::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+
+        let mut packed = [0u8; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(SwapVersion::SwapV3(swap_info), &mut packed).unwrap();
+        assert_eq!(packed[0], 5);
+        assert!(SwapVersion::is_initialized(&packed));
+        let unpacked = SwapVersion::unpack(&packed).unwrap();
+        assert_eq!(*unpacked.token_a_account(), TEST_TOKEN_A);
+        assert_eq!(*unpacked.fees(), TEST_FEES);
+        assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.oracle(), None);
+        assert_eq!(unpacked.admin(), Some(&TEST_ADMIN));
+        assert_eq!(unpacked.amp_ramp(), Some(&TEST_AMP_RAMP));
+        assert_eq!(unpacked.status(), SwapStatus::WithdrawOnly);
+        assert_eq!(
+            SwapVersion::unpack_multi_token(&packed).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+
+        let packed = [0u8; SwapV3::LEN];
+        let unpack_unchecked = SwapV3::unpack_unchecked(&packed).unwrap();
+        assert_eq!(unpack_unchecked.admin(), None);
+        assert_eq!(unpack_unchecked.amp_ramp(), None);
+        assert_eq!(unpack_unchecked.status(), SwapStatus::Active);
+        let err = SwapV3::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn swap_v1_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapV1 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve,
+        };
+
+        let mut packed = [0u8; SwapV1::LEN];
+        SwapV1::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = SwapV1::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+
+        let mut packed = vec![1u8, TEST_NONCE];
+        packed.extend_from_slice(&TEST_TOKEN_PROGRAM_ID.to_bytes());
+        packed.extend_from_slice(&TEST_TOKEN_A.to_bytes());
+        packed.extend_from_slice(&TEST_TOKEN_B.to_bytes());
+        packed.extend_from_slice(&TEST_POOL_MINT.to_bytes());
+        packed.extend_from_slice(&TEST_TOKEN_A_MINT.to_bytes());
+        packed.extend_from_slice(&TEST_TOKEN_B_MINT.to_bytes());
+        packed.extend_from_slice(&TEST_POOL_FEE_ACCOUNT.to_bytes());
+        packed.extend_from_slice(&TEST_FEES.trade_fee_numerator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.trade_fee_denominator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.owner_trade_fee_numerator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.owner_trade_fee_denominator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.owner_withdraw_fee_numerator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.owner_withdraw_fee_denominator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.host_fee_numerator.to_le_bytes());
+        packed.extend_from_slice(&TEST_FEES.host_fee_denominator.to_le_bytes());
+        packed.push(TEST_CURVE_TYPE);
+        packed.extend_from_slice(&TEST_AMP.to_le_bytes());
+        packed.extend_from_slice(&[0u8; 24]);
+        let unpacked = SwapV1::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+
+        let packed = [0u8; SwapV1::LEN];
+        let swap_info: SwapV1 = Default::default();
+        let unpack_unchecked = SwapV1::unpack_unchecked(&packed).unwrap();
+        assert_eq!(unpack_unchecked, swap_info);
+        let err = SwapV1::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn multi_token_swap_pack() {
+        let mut token_accounts = [Pubkey::default(); MAX_TOKENS];
+        let mut token_mints = [Pubkey::default(); MAX_TOKENS];
+        for i in 0..3 {
+            token_accounts[i] = Pubkey::new_from_array([10 + i as u8; 32]);
+            token_mints[i] = Pubkey::new_from_array([20 + i as u8; 32]);
+        }
+        let swap_info = MultiTokenSwapV1 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            pool_mint: TEST_POOL_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            curve: MultiTokenCurve {
+                curve_type: MultiTokenCurveType::Stable,
+                amp: 100,
+                weights: [0; MAX_TOKENS],
+            },
+            token_count: 3,
+            token_accounts,
+            token_mints,
+            admin: TEST_ADMIN,
+            status: SwapStatus::Paused,
+        };
+
+        let mut packed = [0u8; MultiTokenSwapV1::LEN];
+        MultiTokenSwapV1::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = MultiTokenSwapV1::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+        assert_eq!(unpacked.token_accounts(), &token_accounts[..3]);
+        assert_eq!(unpacked.token_mints(), &token_mints[..3]);
+
+        let mut packed = [0u8; SwapVersion::MULTI_TOKEN_LEN];
+        SwapVersion::pack_multi_token(swap_info, &mut packed).unwrap();
+        assert_eq!(packed[0], 3);
+        assert!(SwapVersion::is_initialized(&packed));
+        let unpacked = SwapVersion::unpack_multi_token(&packed).unwrap();
+        assert_eq!(unpacked.token_mints(), &token_mints[..3]);
+        assert_eq!(unpacked.admin(), Some(&TEST_ADMIN));
+        assert_eq!(unpacked.status, SwapStatus::Paused);
+        let err = SwapVersion::unpack(&packed).err().unwrap();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        let mut packed = [0u8; SwapVersion::LATEST_LEN];
+        packed[0] = 2;
+        let err = SwapVersion::unpack_multi_token(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        let packed = [0u8; SwapVersion::MULTI_TOKEN_LEN];
+        assert!(!SwapVersion::is_initialized(&packed));
+        let err = SwapVersion::unpack_multi_token(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn concentrated_swap_pack() {
+        let max_ticks = 16;
+        let mut curve =
+            ConcentratedCurve::new(10, sqrt_price_at_tick(25).unwrap(), 1_000, max_ticks).unwrap();
+        curve.update_ticks(-100, 100, 1_000_000).unwrap();
+        curve.fee_growth_global_b = 1 << 70;
+        let swap_info = ConcentratedSwapV1 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            fee_account_a: Pubkey::new_from_array([9u8; 32]),
+            fee_account_b: Pubkey::new_from_array([10u8; 32]),
+            fees: TEST_FEES,
+            curve,
+            admin: Pubkey::default(),
+            status: SwapStatus::WithdrawOnly,
+        };
+
+        let mut packed = vec![0u8; SwapVersion::concentrated_len(max_ticks)];
+        SwapVersion::pack_concentrated(swap_info, &mut packed).unwrap();
+        assert_eq!(packed[0], 4);
+        assert!(SwapVersion::is_initialized(&packed));
+        let unpacked = SwapVersion::unpack_concentrated(&packed).unwrap();
+        assert_eq!(unpacked.token_a, TEST_TOKEN_A);
+        assert_eq!(unpacked.curve.liquidity, 1_000_000);
+        assert_eq!(unpacked.curve.tick_current, 25);
+        assert_eq!(unpacked.curve.ticks.len(), 2);
+        assert_eq!(unpacked.curve.fee_growth_global_b, 1 << 70);
+        assert_eq!(unpacked.admin(), None);
+        assert_eq!(unpacked.status, SwapStatus::WithdrawOnly);
+        let err = SwapVersion::unpack(&packed).err().unwrap();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+        let err = SwapVersion::unpack_multi_token(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        // the account must have room for exactly the swap's max ticks
+        let err =
+            SwapVersion::unpack_concentrated(&packed[..packed.len() - Tick::LEN]).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+        let mut larger = vec![0u8; SwapVersion::concentrated_len(max_ticks + 1)];
+        let err = SwapVersion::pack_concentrated(unpacked, &mut larger).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        let mut packed = [0u8; SwapVersion::LATEST_LEN];
+        packed[0] = 2;
+        let err = SwapVersion::unpack_concentrated(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        let packed = vec![0u8; SwapVersion::concentrated_len(max_ticks)];
+        assert!(!SwapVersion::is_initialized(&packed));
+        let err = SwapVersion::unpack_concentrated(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn concentrated_position_update_and_pack() {
+        let mut position = ConcentratedPosition {
+            is_initialized: true,
+            swap: Pubkey::new_from_array([1u8; 32]),
+            owner: Pubkey::new_from_array([2u8; 32]),
+            tick_lower: -60,
+            tick_upper: 120,
+            ..ConcentratedPosition::default()
+        };
+        position.update(1_000, 500 << 64, 7 << 64).unwrap();
+        assert_eq!(position.liquidity, 1_000);
+        assert_eq!(position.fees_owed_a, 0);
+        assert_eq!(position.fee_growth_inside_last_a, 500 << 64);
+
+        // fee growth may wrap around
+        let growth_a = (500u128 << 64).wrapping_add(3 << 63);
+        let growth_b = (7u128 << 64).wrapping_sub(u128::MAX);
+        position.update(-400, growth_a, growth_b).unwrap();
+        assert_eq!(position.liquidity, 600);
+        assert_eq!(position.fees_owed_a, 1_500);
+        assert_eq!(position.fees_owed_b, 0);
+        assert_eq!(
+            position.update(-601, growth_a, growth_b),
+            Err(SwapError::InsufficientLiquidity)
+        );
+
+        let mut packed = [0u8; ConcentratedPosition::LEN];
+        ConcentratedPosition::pack(position, &mut packed).unwrap();
+        let unpacked = ConcentratedPosition::unpack(&packed).unwrap();
+        assert_eq!(unpacked.tick_lower, -60);
+        assert_eq!(unpacked.tick_upper, 120);
+        assert_eq!(unpacked.liquidity, 600);
+        assert_eq!(unpacked.fees_owed_a, 1_500);
+        assert_eq!(unpacked.fee_growth_inside_last_b, growth_b);
+
+        let packed = [0u8; ConcentratedPosition::LEN];
+        let err = ConcentratedPosition::unpack(&packed).unwrap_err();
         assert_eq!(err, ProgramError::UninitializedAccount);
     }
 }
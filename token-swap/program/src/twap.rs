@@ -0,0 +1,320 @@
+//! Cumulative pool prices, used to compute time-weighted average prices
+
+use arrayref::{array_refs, mut_array_refs};
+use solana_program::clock::UnixTimestamp;
+use spl_math::uint::U256;
+
+/// Fixed point scale of prices, which are Q64.64 numbers
+pub const PRICE_SCALE: u128 = 1 << 64;
+
+/// Length of packed price accumulators
+pub const PRICE_ACCUMULATORS_LEN: usize = 88; // 8 + 32 + 32 + 8 + 8
+
+/// Prices of a pool summed over time, in the style of Uniswap v2 oracles.
+///
+/// The pool balances are recorded as reserves after every swap, deposit and
+/// withdrawal. On the next update, the price of each token in the other given
+/// by those reserves, as a Q64.64 fixed point number, is multiplied by the
+/// seconds since the last update and added to its accumulator, before the
+/// reserves are replaced by the new balances. Tokens sent straight to the pool
+/// accounts only count from the next update onwards, so they cannot set the
+/// price of the time before it. The accumulators wrap on overflow, since only
+/// differences between two observations are meaningful. Subtracting an
+/// earlier observation from a later one, and dividing by the seconds between
+/// them, gives the average price over that time, which cannot be moved much by
+/// balances held for only a few seconds.
+///
+/// The price is the ratio of the pool balances, which is the marginal price
+/// only on a constant product curve, so only constant product pools record
+/// prices. Pools on other curves leave their accumulators untouched and do not
+/// expose them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceAccumulators {
+    /// Unix timestamp of the last update
+    pub last_update_timestamp: UnixTimestamp,
+    /// Price of token A in token B, times seconds
+    pub token_a_price_cumulative: U256,
+    /// Price of token B in token A, times seconds
+    pub token_b_price_cumulative: U256,
+    /// Token A balance of the pool after the last update
+    pub reserve_a: u64,
+    /// Token B balance of the pool after the last update
+    pub reserve_b: u64,
+}
+
+/// Average prices between two observations of a pool's price accumulators
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeWeightedPrice {
+    /// Average price of token A in token B, as a Q64.64 fixed point number
+    pub token_a_price: u128,
+    /// Average price of token B in token A, as a Q64.64 fixed point number
+    pub token_b_price: u128,
+}
+
+impl PriceAccumulators {
+    /// Create accumulators starting at the given time and pool balances
+    pub fn new(current_timestamp: UnixTimestamp, reserve_a: u64, reserve_b: u64) -> Self {
+        Self {
+            last_update_timestamp: current_timestamp,
+            reserve_a,
+            reserve_b,
+            ..Self::default()
+        }
+    }
+
+    /// Add the prices given by the recorded reserves, times the seconds
+    /// elapsed since the last update, to the accumulators, then record the
+    /// new pool balances as the reserves. Must be called with the balances the
+    /// pool will hold once the current instruction completes.
+    pub fn update(
+        &mut self,
+        current_timestamp: UnixTimestamp,
+        swap_token_a_amount: u64,
+        swap_token_b_amount: u64,
+    ) {
+        self.accumulate(current_timestamp);
+        self.reserve_a = swap_token_a_amount;
+        self.reserve_b = swap_token_b_amount;
+    }
+
+    /// Add the prices given by the recorded reserves, times the seconds
+    /// elapsed since the last update, to the accumulators. Prices are not
+    /// accumulated while either side of the pool is empty.
+    fn accumulate(&mut self, current_timestamp: UnixTimestamp) {
+        let seconds_elapsed = current_timestamp.saturating_sub(self.last_update_timestamp);
+        if seconds_elapsed <= 0 {
+            return;
+        }
+        if self.reserve_a > 0 && self.reserve_b > 0 {
+            let seconds_elapsed = U256::from(seconds_elapsed as u64);
+            let token_a_price = spot_price(self.reserve_b, self.reserve_a);
+            let token_b_price = spot_price(self.reserve_a, self.reserve_b);
+            self.token_a_price_cumulative = self
+                .token_a_price_cumulative
+                .overflowing_add(token_a_price * seconds_elapsed)
+                .0;
+            self.token_b_price_cumulative = self
+                .token_b_price_cumulative
+                .overflowing_add(token_b_price * seconds_elapsed)
+                .0;
+        }
+        self.last_update_timestamp = current_timestamp;
+    }
+
+    /// Accumulators extended to the current time at the recorded reserves,
+    /// for reading a pool's prices up to now without writing to it. The
+    /// current pool balances are deliberately not used, since anyone can move
+    /// them by sending tokens to the pool.
+    pub fn observe(&self, current_timestamp: UnixTimestamp) -> Self {
+        let mut observation = *self;
+        observation.accumulate(current_timestamp);
+        observation
+    }
+
+    /// Pack price accumulators into a fixed size buffer
+    pub fn pack_into_slice(&self, dst: &mut [u8; PRICE_ACCUMULATORS_LEN]) {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            last_update_timestamp,
+            token_a_price_cumulative,
+            token_b_price_cumulative,
+            reserve_a,
+            reserve_b,
+        ) = mut_array_refs![dst, 8, 32, 32, 8, 8];
+        *last_update_timestamp = self.last_update_timestamp.to_le_bytes();
+        self.token_a_price_cumulative
+            .to_little_endian(token_a_price_cumulative);
+        self.token_b_price_cumulative
+            .to_little_endian(token_b_price_cumulative);
+        *reserve_a = self.reserve_a.to_le_bytes();
+        *reserve_b = self.reserve_b.to_le_bytes();
+    }
+
+    /// Unpack price accumulators from a fixed size buffer
+    pub fn unpack_from_slice(src: &[u8; PRICE_ACCUMULATORS_LEN]) -> Self {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            last_update_timestamp,
+            token_a_price_cumulative,
+            token_b_price_cumulative,
+            reserve_a,
+            reserve_b,
+        ) = array_refs![src, 8, 32, 32, 8, 8];
+        Self {
+            last_update_timestamp: UnixTimestamp::from_le_bytes(*last_update_timestamp),
+            token_a_price_cumulative: U256::from_little_endian(token_a_price_cumulative),
+            token_b_price_cumulative: U256::from_little_endian(token_b_price_cumulative),
+            reserve_a: u64::from_le_bytes(*reserve_a),
+            reserve_b: u64::from_le_bytes(*reserve_b),
+        }
+    }
+}
+
+/// Price of one token in another, as a Q64.64 fixed point number, saturating
+/// at `u128::MAX`
+fn spot_price(quote_amount: u64, base_amount: u64) -> U256 {
+    let price = (U256::from(quote_amount) << 64) / U256::from(base_amount);
+    price.min(U256::from(u128::MAX))
+}
+
+/// Compute the average prices between two observations of the same pool's
+/// price accumulators, returning None if no time elapsed between them
+pub fn time_weighted_average_price(
+    earlier: &PriceAccumulators,
+    later: &PriceAccumulators,
+) -> Option<TimeWeightedPrice> {
+    let seconds_elapsed = later
+        .last_update_timestamp
+        .checked_sub(earlier.last_update_timestamp)?;
+    if seconds_elapsed <= 0 {
+        return None;
+    }
+    let seconds_elapsed = U256::from(seconds_elapsed as u64);
+    let average = |earlier: U256, later: U256| {
+        let price = later.overflowing_sub(earlier).0 / seconds_elapsed;
+        if price > U256::from(u128::MAX) {
+            None
+        } else {
+            Some(price.as_u128())
+        }
+    };
+    Some(TimeWeightedPrice {
+        token_a_price: average(
+            earlier.token_a_price_cumulative,
+            later.token_a_price_cumulative,
+        )?,
+        token_b_price: average(
+            earlier.token_b_price_cumulative,
+            later.token_b_price_cumulative,
+        )?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_prices() {
+        let mut accumulators = PriceAccumulators::new(100, 1_000, 2_000);
+
+        // no time elapsed
+        accumulators.update(100, 1_000, 2_000);
+        assert_eq!(accumulators, PriceAccumulators::new(100, 1_000, 2_000));
+
+        // 1 A = 2 B for 10 seconds, then the balances move to 1 A = 4 B
+        accumulators.update(110, 1_000, 4_000);
+        assert_eq!(accumulators.last_update_timestamp, 110);
+        assert_eq!(
+            accumulators.token_a_price_cumulative,
+            U256::from(20 * PRICE_SCALE)
+        );
+        assert_eq!(
+            accumulators.token_b_price_cumulative,
+            U256::from(5 * PRICE_SCALE)
+        );
+        assert_eq!(accumulators.reserve_a, 1_000);
+        assert_eq!(accumulators.reserve_b, 4_000);
+
+        // 1 A = 4 B for 5 seconds, then one side of the pool is emptied
+        accumulators.update(115, 0, 4_000);
+        assert_eq!(
+            accumulators.token_a_price_cumulative,
+            U256::from(40 * PRICE_SCALE)
+        );
+
+        // empty pools don't accumulate, but still move the timestamp
+        let earlier = accumulators;
+        accumulators.update(120, 1_000, 1_000);
+        assert_eq!(accumulators.last_update_timestamp, 120);
+        assert_eq!(
+            accumulators.token_a_price_cumulative,
+            earlier.token_a_price_cumulative
+        );
+    }
+
+    #[test]
+    fn observe_prices() {
+        let accumulators = PriceAccumulators::new(0, 1_000, 2_000);
+        let observation = accumulators.observe(10);
+        assert_eq!(observation.last_update_timestamp, 10);
+        assert_eq!(
+            observation.token_a_price_cumulative,
+            U256::from(20 * PRICE_SCALE)
+        );
+        assert_eq!(observation.reserve_a, 1_000);
+        assert_eq!(observation.reserve_b, 2_000);
+
+        // balances recorded by an update only price the time after it
+        let mut updated = accumulators;
+        updated.update(10, 1_000, 1_000_000);
+        assert_eq!(
+            updated.token_a_price_cumulative,
+            observation.token_a_price_cumulative
+        );
+        assert_eq!(
+            time_weighted_average_price(&accumulators, &updated).unwrap(),
+            TimeWeightedPrice {
+                token_a_price: 2 * PRICE_SCALE,
+                token_b_price: PRICE_SCALE / 2,
+            }
+        );
+    }
+
+    #[test]
+    fn time_weighted_prices() {
+        let start = PriceAccumulators::new(0, 1_000, 2_000);
+        // 1 A = 2 B for 30 seconds, then 1 A = 4 B for 10 seconds
+        let mut middle = start.observe(30);
+        middle.update(30, 1_000, 4_000);
+        let end = middle.observe(40);
+
+        assert_eq!(
+            time_weighted_average_price(&start, &end).unwrap(),
+            TimeWeightedPrice {
+                token_a_price: 5 * PRICE_SCALE / 2,
+                token_b_price: 7 * PRICE_SCALE / 16,
+            }
+        );
+        assert_eq!(
+            time_weighted_average_price(&middle, &end).unwrap(),
+            TimeWeightedPrice {
+                token_a_price: 4 * PRICE_SCALE,
+                token_b_price: PRICE_SCALE / 4,
+            }
+        );
+        assert_eq!(time_weighted_average_price(&end, &start), None);
+        assert_eq!(time_weighted_average_price(&end, &end), None);
+
+        // wrapping accumulators still give the right average
+        let wrapping_start = PriceAccumulators {
+            last_update_timestamp: 0,
+            token_a_price_cumulative: U256::MAX - U256::from(PRICE_SCALE),
+            token_b_price_cumulative: U256::MAX,
+            reserve_a: 1_000,
+            reserve_b: 1_000,
+        };
+        let wrapping_end = wrapping_start.observe(10);
+        assert_eq!(
+            time_weighted_average_price(&wrapping_start, &wrapping_end).unwrap(),
+            TimeWeightedPrice {
+                token_a_price: PRICE_SCALE,
+                token_b_price: PRICE_SCALE,
+            }
+        );
+    }
+
+    #[test]
+    fn pack_price_accumulators() {
+        let accumulators = PriceAccumulators {
+            last_update_timestamp: 1_620_000_000,
+            token_a_price_cumulative: U256::MAX - U256::from(7),
+            token_b_price_cumulative: U256::from(PRICE_SCALE) * U256::from(u64::MAX),
+            reserve_a: u64::MAX,
+            reserve_b: 42,
+        };
+        let mut packed = [0u8; PRICE_ACCUMULATORS_LEN];
+        accumulators.pack_into_slice(&mut packed);
+        assert_eq!(PriceAccumulators::unpack_from_slice(&packed), accumulators);
+    }
+}